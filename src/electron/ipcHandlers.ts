@@ -1,11 +1,11 @@
 import { spawn } from "child_process";
-import { BrowserWindow, dialog, screen, shell } from "electron";
+import { app, BrowserWindow, dialog, screen, shell } from "electron";
 import ffprobeStatic from "ffprobe-static";
 import ffmpeg from "fluent-ffmpeg";
 import fs from "fs";
 import path from "path";
 import { baseLogger, createLoggerForFile, videoLogger } from "./logging.js";
-import { getScriptsPath } from "./pathResolver.js";
+import { getHelperCommand } from "./pathResolver.js";
 import { PUBLIC_TAG_CATEGORIES } from "./publicTags.js";
 import { getSafeFileUrl } from "./safeFileProtocol.js";
 import {
@@ -709,29 +709,49 @@ export function registerIpcHandlers(mainWindow: Electron.BrowserWindow) {
           counter++;
         }
 
-        // Build the path to create_shortcut.exe
-        const scriptsPath = getScriptsPath();
-        const exePath = path.join(scriptsPath, "create_shortcut.exe");
-
         // Run the executable
         await new Promise<void>((resolve, reject) => {
-          const proc = spawn(exePath, [sourcePath, shortcutPath], {
-            windowsHide: true,
-          });
+          const [exePath, args, usesHelper] = getHelperCommand(
+            "shortcut",
+            "create",
+            [sourcePath, shortcutPath],
+            { options: ["--lang", app.getLocale()], extraArgs: ["--json"] }
+          );
+          const proc = spawn(exePath, args, { windowsHide: true });
 
+          let output = "";
           let errorOutput = "";
+          let userMessage = "";
+          proc.stdout.on("data", (data) => {
+            output += data.toString();
+          });
           proc.stderr.on("data", (data) => {
             errorOutput += data.toString();
           });
 
           proc.on("close", (code) => {
+            // --json reports failures as {"ok":false,"code":...,"message":...} on
+            // stdout, while create_shortcut.exe only has its exit code and stderr
+            if (usesHelper) {
+              try {
+                const result = JSON.parse(output.trim());
+                if (!result.ok) {
+                  errorOutput = `${result.code}: ${result.message}`;
+                  // Already in the user's language, unlike the technical message
+                  userMessage = result.userMessage ?? "";
+                }
+              } catch {
+                // Keep whatever was written to stderr
+              }
+            }
             if (code === 0 && fs.existsSync(shortcutPath)) {
               logger.info(`Shortcut created at: ${shortcutPath}`);
               resolve();
             } else {
+              logger.error(`Failed to create shortcut: ${errorOutput}`);
               showSmallWindow(
                 "Failed to create shortcut",
-                `Failed to create shortcut for: ${sourcePath}\nError: ${errorOutput || "Unknown error"}`
+                `Failed to create shortcut for: ${sourcePath}\nError: ${userMessage || errorOutput || "Unknown error"}`
               );
               reject(new Error(errorOutput || "Failed to create shortcut"));
             }
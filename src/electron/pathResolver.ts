@@ -1,4 +1,5 @@
 import { app } from "electron";
+import fs from "fs";
 import path from "path";
 import { isDev } from "./utils/util.js";
 
@@ -22,3 +23,33 @@ export function getAssetPath() {
 export function getScriptsPath() {
   return path.join(app.getAppPath(), isDev() ? "." : "..", "/src/scripts/bin");
 }
+
+// One binary for the Rust tools: `altdesktop-helper shortcut ...` and `altdesktop-helper icon ...`
+export function getHelperPath() {
+  return path.join(getScriptsPath(), "altdesktop-helper.exe");
+}
+
+// The executables the helper replaces, still shipped until it is, which only run
+// their tool's default command and take none of the helper's own options
+const LEGACY_TOOLS = {
+  shortcut: { exe: "create_shortcut.exe", command: "create" },
+  icon: { exe: "file_to_image.exe", command: "extract" },
+};
+
+// The program and arguments that run `tool command args`: the helper when it's been
+// built, otherwise the tool's own executable. `options` go before the tool and
+// `extraArgs` after args, and both only to the helper; the third value says which
+// of the two will run.
+export function getHelperCommand(
+  tool: keyof typeof LEGACY_TOOLS,
+  command: string,
+  args: string[],
+  { options = [], extraArgs = [] }: { options?: string[]; extraArgs?: string[] } = {}
+): [string, string[], boolean] {
+  const helperPath = getHelperPath();
+  const legacy = LEGACY_TOOLS[tool];
+  if (!fs.existsSync(helperPath) && command === legacy.command) {
+    return [path.join(getScriptsPath(), legacy.exe), args, false];
+  }
+  return [helperPath, [...options, tool, command, ...args, ...extraArgs], true];
+}
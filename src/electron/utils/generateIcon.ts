@@ -5,7 +5,7 @@ import os from "os";
 import path from "path";
 import sharp from "sharp";
 import { createLoggerForFile } from "../logging.js";
-import { getScriptsPath } from "../pathResolver.js";
+import { getHelperCommand, getScriptsPath } from "../pathResolver.js";
 import { getSetting } from "../settings.js";
 import { resolveShortcut } from "./util.js";
 
@@ -116,11 +116,12 @@ export const generateIcon = async (
         logger.info(
           `running rust for default file: ${programLink}, ${outputPath}, ${iconSize.toString()}`
         );
-        process = spawn(path.join(getScriptsPath(), "file_to_image.exe"), [
+        const [exePath, args] = getHelperCommand("icon", "extract", [
           programLink,
           outputPath,
           iconSize.toString(),
         ]);
+        process = spawn(exePath, args);
       }
 
       process.stdout.on("data", (data) => {
@@ -128,7 +129,14 @@ export const generateIcon = async (
       });
 
       process.stderr.on("data", (data) => {
-        logger.error(`Executable stderr: ${data.toString().trim()}`);
+        const text = data.toString().trim();
+        // The icon tool reports failures as {"code": ..., "message": ...}
+        try {
+          const { code, message } = JSON.parse(text.split("\n")[0]);
+          logger.error(`Executable failed (${code}): ${message}`);
+        } catch {
+          logger.error(`Executable stderr: ${text}`);
+        }
       });
 
       process.on("close", (code) => {
@@ -0,0 +1,56 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Cancels one request or job from another thread, usually the one reading the
+/// cancel message. The work itself only stops at the safe points that check
+/// [`cancelled`], so what it has written so far can be cleaned up.
+#[derive(Clone, Default, Debug)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    parent: Option<Box<CancelToken>>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token cancelled along with this one, which can also be cancelled on its own,
+    /// as a watchdog does to the part of a request that hangs.
+    pub fn child(&self) -> Self {
+        CancelToken { cancelled: Arc::default(), parent: Some(Box::new(self.clone())) }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.parent.as_ref().is_some_and(|parent| parent.is_cancelled())
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+/// Runs `work` with `token` as the one [`cancelled`] checks on this thread, so code
+/// deep inside a tool can stop without every function taking a token.
+pub fn scope<T>(token: &CancelToken, work: impl FnOnce() -> T) -> T {
+    let previous = CURRENT.with(|current| current.replace(Some(token.clone())));
+    let result = work();
+    CURRENT.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// The token of the [`scope`] this thread is in, for handing on to a thread it starts.
+pub fn current() -> Option<CancelToken> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Whether the work running on this thread has been cancelled. Always false outside
+/// a [`scope`], as for a tool run from the command line.
+pub fn cancelled() -> bool {
+    CURRENT.with(|current| current.borrow().as_ref().is_some_and(CancelToken::is_cancelled))
+}
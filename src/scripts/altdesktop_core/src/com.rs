@@ -0,0 +1,31 @@
+use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT, COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED};
+
+/// COM initialized on the current thread until dropped. A thread that already had
+/// COM in another apartment keeps it, and isn't uninitialized on drop.
+pub struct ComGuard {
+    initialized: bool,
+}
+
+impl ComGuard {
+    /// A single-threaded apartment, which the shell's APIs need.
+    pub fn apartment() -> Self {
+        Self::new(COINIT_APARTMENTTHREADED)
+    }
+
+    /// The multithreaded apartment, for work that only touches free-threaded objects.
+    pub fn multithreaded() -> Self {
+        Self::new(COINIT_MULTITHREADED)
+    }
+
+    fn new(model: COINIT) -> Self {
+        ComGuard { initialized: unsafe { CoInitializeEx(None, model) }.is_ok() }
+    }
+}
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        if self.initialized {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
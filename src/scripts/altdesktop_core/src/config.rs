@@ -0,0 +1,88 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+/// Defaults for the flags the app would otherwise pass on every run, read from the
+/// TOML file given with `--config`. Every key is optional, and a flag on the command
+/// line or a field in a request still wins over the file:
+///
+/// ```toml
+/// [icon]
+/// sizes = [32, 64]
+/// format = "webp"
+/// cache-dir = 'C:\Users\me\AppData\Roaming\AltDesktop\cache'
+///
+/// [timeouts]
+/// probe = 3000
+/// download = 10000
+/// resolve = 3000
+/// extract = 30000
+///
+/// [log]
+/// level = "info"
+/// ```
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub icon: IconConfig,
+    pub timeouts: TimeoutConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct IconConfig {
+    /// What an extract or job that gives no size is written at.
+    pub sizes: Option<Vec<u32>>,
+    /// An `--format` name, checked by the icon tool when it's used.
+    pub format: Option<String>,
+    pub cache_dir: Option<String>,
+}
+
+/// In milliseconds.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TimeoutConfig {
+    /// How long `probe` waits for a path to answer.
+    pub probe: Option<u64>,
+    /// How long a favicon or Steam art download may take.
+    pub download: Option<u64>,
+    /// How long link tracking may search for a shortcut's moved target.
+    pub resolve: Option<u32>,
+    /// How long one icon extraction may wait on a shell handler or codec; 0 for no limit.
+    pub extract: Option<u64>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LogConfig {
+    /// A `--log` filter, used when neither the flag nor ALTDESKTOP_LOG is set.
+    pub level: Option<String>,
+}
+
+static CONFIG: OnceLock<(PathBuf, Config)> = OnceLock::new();
+
+/// Reads the config every later [`get`] returns. Called once, before any tool runs;
+/// the error names the file and, for a parse error, the line at fault.
+pub fn load(path: &Path) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+    let config = toml::from_str(&text).map_err(|error| format!("Invalid config {}: {}", path.display(), error))?;
+    let _ = CONFIG.set((path.to_path_buf(), config));
+    Ok(())
+}
+
+/// The loaded config, or one with every default unset when there is none.
+pub fn get() -> &'static Config {
+    static EMPTY: OnceLock<Config> = OnceLock::new();
+    match CONFIG.get() {
+        Some((_, config)) => config,
+        None => EMPTY.get_or_init(Config::default),
+    }
+}
+
+/// The file the config was loaded from, for passing on to another copy of the helper.
+pub fn path() -> Option<&'static Path> {
+    CONFIG.get().map(|(path, _)| path.as_path())
+}
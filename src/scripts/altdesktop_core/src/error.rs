@@ -0,0 +1,120 @@
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{core::*, Win32::Foundation::*};
+
+use crate::{exit, messages};
+
+// Most failures come back as HRESULT_FROM_WIN32 codes from the file system, the rest
+// from COM. Anything not listed here is reported as E_FAIL along with its raw HRESULT.
+#[cfg(windows)]
+const ERROR_CODES: &[(HRESULT, &str)] = &[
+    (E_ACCESSDENIED, "E_ACCESS"),
+    (STG_E_ACCESSDENIED, "E_ACCESS"),
+    (ERROR_WRITE_PROTECT.to_hresult(), "E_ACCESS"),
+    (ERROR_FILE_NOT_FOUND.to_hresult(), "E_NOT_FOUND"),
+    (ERROR_PATH_NOT_FOUND.to_hresult(), "E_NOT_FOUND"),
+    (ERROR_INVALID_DRIVE.to_hresult(), "E_NOT_FOUND"),
+    (ERROR_BAD_NETPATH.to_hresult(), "E_NOT_FOUND"),
+    (ERROR_BAD_NET_NAME.to_hresult(), "E_NOT_FOUND"),
+    (STG_E_FILENOTFOUND, "E_NOT_FOUND"),
+    (STG_E_PATHNOTFOUND, "E_NOT_FOUND"),
+    (ERROR_FILE_EXISTS.to_hresult(), "E_EXISTS"),
+    (ERROR_ALREADY_EXISTS.to_hresult(), "E_EXISTS"),
+    (ERROR_SHARING_VIOLATION.to_hresult(), "E_IN_USE"),
+    (ERROR_LOCK_VIOLATION.to_hresult(), "E_IN_USE"),
+    (STG_E_SHAREVIOLATION, "E_IN_USE"),
+    (STG_E_LOCKVIOLATION, "E_IN_USE"),
+    (E_INVALIDARG, "E_INVALID_ARG"),
+    (ERROR_INVALID_NAME.to_hresult(), "E_INVALID_ARG"),
+    (ERROR_FILENAME_EXCED_RANGE.to_hresult(), "E_INVALID_ARG"),
+    (ERROR_PRIVILEGE_NOT_HELD.to_hresult(), "E_PRIVILEGE"),
+    (ERROR_DISK_FULL.to_hresult(), "E_DISK_FULL"),
+    (ERROR_HANDLE_DISK_FULL.to_hresult(), "E_DISK_FULL"),
+    (STG_E_MEDIUMFULL, "E_DISK_FULL"),
+    (E_ABORT, "E_CANCELLED"),
+    (ERROR_CANCELLED.to_hresult(), "E_CANCELLED"),
+    (E_NOINTERFACE, "E_UNSUPPORTED"),
+    (E_NOTIMPL, "E_UNSUPPORTED"),
+    (REGDB_E_CLASSNOTREG, "E_UNSUPPORTED"),
+    (E_OUTOFMEMORY, "E_OUT_OF_MEMORY"),
+];
+
+/// Maps an HRESULT onto the short code reported by `--json`.
+#[cfg(windows)]
+pub fn error_code(hr: HRESULT) -> &'static str {
+    ERROR_CODES
+        .iter()
+        .find(|(code, _)| *code == hr)
+        .map_or("E_FAIL", |(_, name)| *name)
+}
+
+#[derive(Serialize)]
+pub struct ErrorReport {
+    ok: bool,
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hr: Option<String>,
+    message: String,
+    /// The code's message from [`crate::messages`], in the language asked for.
+    #[serde(rename = "userMessage")]
+    user_message: &'static str,
+}
+
+impl ErrorReport {
+    #[cfg(windows)]
+    pub fn from_error(error: &Error) -> Self {
+        let hr = error.code();
+        Self::new(error_code(hr), Some(format!("0x{:08X}", hr.0 as u32)), error.message().to_string())
+    }
+
+    /// The Unix backends have no HRESULTs, so `hr` carries the errno instead.
+    #[cfg(unix)]
+    pub fn from_io_error(error: &std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        let code = match error.kind() {
+            ErrorKind::PermissionDenied => "E_ACCESS",
+            ErrorKind::NotFound => "E_NOT_FOUND",
+            ErrorKind::AlreadyExists => "E_EXISTS",
+            ErrorKind::InvalidInput | ErrorKind::InvalidFilename => "E_INVALID_ARG",
+            ErrorKind::StorageFull => "E_DISK_FULL",
+            ErrorKind::OutOfMemory => "E_OUT_OF_MEMORY",
+            _ => "E_FAIL",
+        };
+        Self::new(code, error.raw_os_error().map(|errno| errno.to_string()), error.to_string())
+    }
+
+    /// What the tool that printed this report exits with.
+    pub fn exit_code(&self) -> u8 {
+        exit::for_code(self.code)
+    }
+
+    /// A command this platform has no backend for.
+    pub fn unsupported(message: &str) -> Self {
+        Self::new("E_UNSUPPORTED", None, message.to_string())
+    }
+
+    /// Bad command line or unreadable input, which has no HRESULT behind it.
+    pub fn usage(message: &str) -> Self {
+        Self::new("E_USAGE", None, message.to_string())
+    }
+
+    // Also into the log, when it's on, within the span of the tool or request that
+    // failed, which carries its arguments
+    fn new(code: &'static str, hr: Option<String>, message: String) -> Self {
+        if code == "E_USAGE" {
+            tracing::warn!(code, "{}", message);
+        } else {
+            tracing::error!(code, hr = hr.as_deref(), "{}", message);
+        }
+        ErrorReport { ok: false, code, hr, message, user_message: messages::user_message(code) }
+    }
+}
+
+/// An io::Error as a windows::core::Error, keeping the Win32 code when it has one.
+#[cfg(windows)]
+pub fn io_error(error: std::io::Error) -> Error {
+    match error.raw_os_error() {
+        Some(code) => HRESULT::from_win32(code as u32).into(),
+        None => Error::new(E_FAIL, error.to_string().into()),
+    }
+}
@@ -0,0 +1,30 @@
+// The exit codes every tool shares, so the app can branch on why a run failed
+// without reading its output. The JSON on stdout, or for `icon` on stderr, says the
+// same in more detail; anything else on stderr is diagnostics for a person.
+
+pub const SUCCESS: u8 = 0;
+/// Any other failure, and a command that ran but whose answer is no: a batch with
+/// failed jobs, a shortcut that doesn't resolve, a doctor check that failed.
+pub const FAILURE: u8 = 1;
+/// A bad command line, config file or request.
+pub const USAGE: u8 = 2;
+pub const NOT_FOUND: u8 = 3;
+pub const ACCESS_DENIED: u8 = 4;
+pub const TIMEOUT: u8 = 5;
+/// A tool or command this platform has no backend for.
+pub const UNSUPPORTED: u8 = 6;
+/// A crash, after which the crash record in the log folder says where.
+pub const INTERNAL: u8 = 70;
+
+/// The exit code for an [`ErrorReport`](crate::error::ErrorReport) code such as
+/// `E_NOT_FOUND`.
+pub fn for_code(code: &str) -> u8 {
+    match code {
+        "E_USAGE" | "E_INVALID_ARG" => USAGE,
+        "E_NOT_FOUND" => NOT_FOUND,
+        "E_ACCESS" | "E_PRIVILEGE" => ACCESS_DENIED,
+        "E_TIMEOUT" => TIMEOUT,
+        "E_UNSUPPORTED" => UNSUPPORTED,
+        _ => FAILURE,
+    }
+}
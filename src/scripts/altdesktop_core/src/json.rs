@@ -0,0 +1,18 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+/// Prints a value as one line of JSON on stdout.
+pub fn print(value: impl Serialize) {
+    println!("{}", serde_json::to_string(&value).unwrap());
+}
+
+/// Prints one line of a stream of JSON events, flushed so the reader sees it now.
+/// False once stdout is gone, which is the signal for a watcher to stop.
+pub fn emit(line: impl Serialize) -> bool {
+    let Ok(line) = serde_json::to_string(&line) else {
+        return true;
+    };
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", line).and_then(|_| stdout.flush()).is_ok()
+}
@@ -0,0 +1,20 @@
+//! Plumbing every Alt-Desktop helper crate needs: COM initialization, wide strings
+//! and Windows paths, JSON on stdout, the error report printed on failure and its
+//! translated messages and the exit code it maps to, opt-in file logging, the defaults
+//! of a config file, the progress lines and cancellation of batch jobs, and the checks
+//! on the paths a tool is given to read and write.
+
+pub mod cancel;
+#[cfg(windows)]
+pub mod com;
+pub mod config;
+pub mod error;
+pub mod exit;
+pub mod json;
+pub mod log;
+pub mod messages;
+#[cfg(windows)]
+pub mod path;
+pub mod progress;
+pub mod sandbox;
+pub mod wide;
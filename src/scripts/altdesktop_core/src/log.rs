@@ -0,0 +1,89 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::EnvFilter;
+
+use crate::config;
+
+/// Turns logging on when `--log` isn't given, with the same filter syntax.
+pub const ENV_VAR: &str = "ALTDESKTOP_LOG";
+
+// A day's log per file, and a week of them, next to the app's own logs
+const FILE_PREFIX: &str = "helper";
+const MAX_FILES: usize = 7;
+
+static FILTER: OnceLock<String> = OnceLock::new();
+
+/// Starts writing `tracing` events to a daily file in [`log_dir`], when `filter`,
+/// ALTDESKTOP_LOG or the config's log level asks for it; otherwise events cost
+/// nothing and go nowhere. The filter is a level such as `debug`, or per-crate
+/// directives such as `icon_extractor=trace,info`; `1` or an unreadable filter means
+/// `info`.
+///
+/// Lines are written as the events happen rather than from a background thread, so a
+/// tool that exits early or crashes still leaves everything it logged. Returns
+/// whether logging is on.
+pub fn init(filter: Option<&str>) -> bool {
+    let Some(filter) = filter
+        .map(str::to_string)
+        .or_else(|| env::var(ENV_VAR).ok())
+        .or_else(|| config::get().log.level.clone())
+    else {
+        return false;
+    };
+    let filter = match filter.trim() {
+        "" | "0" | "off" => return false,
+        "1" | "on" => "info",
+        filter if EnvFilter::try_new(filter).is_ok() => filter,
+        _ => "info",
+    };
+    // The appender complains on stderr, which callers read as the tool's output, when
+    // it prunes old files from a folder that isn't there yet
+    let dir = log_dir();
+    if fs::create_dir_all(&dir).is_err() {
+        return false;
+    }
+    let Ok(appender) = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(FILE_PREFIX)
+        .filename_suffix("log")
+        .max_log_files(MAX_FILES)
+        .build(dir)
+    else {
+        return false;
+    };
+    let started = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(filter))
+        .with_writer(appender)
+        .with_ansi(false)
+        .with_thread_ids(true)
+        .try_init()
+        .is_ok();
+    if started {
+        let _ = FILTER.set(filter.to_string());
+    }
+    started
+}
+
+/// The filter logging was started with, for passing on to a copy of the helper
+/// that doesn't inherit this process's environment.
+pub fn filter() -> Option<&'static str> {
+    FILTER.get().map(String::as_str)
+}
+
+/// %APPDATA%\AltDesktop\logs on Windows, where the app keeps its own logs;
+/// ~/Library/Logs/AltDesktop on macOS and $XDG_STATE_HOME/AltDesktop/logs elsewhere.
+pub fn log_dir() -> PathBuf {
+    let var = |name: &str| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    if cfg!(windows) {
+        return var("APPDATA").unwrap_or_else(env::temp_dir).join("AltDesktop").join("logs");
+    }
+    let home = var("HOME").unwrap_or_else(env::temp_dir);
+    if cfg!(target_os = "macos") {
+        return home.join("Library").join("Logs").join("AltDesktop");
+    }
+    var("XDG_STATE_HOME").unwrap_or_else(|| home.join(".local").join("state")).join("AltDesktop").join("logs")
+}
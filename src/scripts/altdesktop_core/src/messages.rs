@@ -0,0 +1,297 @@
+use std::sync::OnceLock;
+
+// The codes in every error report, in the order each language lists its messages;
+// E_TIMEOUT and E_DECODE are what the icon tool's timeout and decode codes show as
+const CODES: [&str; 14] = [
+    "E_ACCESS",
+    "E_NOT_FOUND",
+    "E_EXISTS",
+    "E_IN_USE",
+    "E_INVALID_ARG",
+    "E_PRIVILEGE",
+    "E_DISK_FULL",
+    "E_CANCELLED",
+    "E_UNSUPPORTED",
+    "E_OUT_OF_MEMORY",
+    "E_TIMEOUT",
+    "E_DECODE",
+    "E_USAGE",
+    "E_FAIL",
+];
+
+type Messages = [&'static str; 14];
+
+const EN: Messages = [
+    "Access to the file or folder was denied.",
+    "The file or folder could not be found.",
+    "A file or folder with that name already exists.",
+    "The file is in use by another program.",
+    "The path or value given is not valid.",
+    "This needs administrator rights.",
+    "There is not enough space on the disk.",
+    "The operation was cancelled.",
+    "This isn't supported for this kind of file.",
+    "There isn't enough memory to finish.",
+    "The operation took too long and was stopped.",
+    "The file's contents could not be read as an image.",
+    "The helper was called with arguments it doesn't understand.",
+    "Something went wrong.",
+];
+
+const DE: Messages = [
+    "Der Zugriff auf die Datei oder den Ordner wurde verweigert.",
+    "Die Datei oder der Ordner wurde nicht gefunden.",
+    "Eine Datei oder ein Ordner mit diesem Namen ist bereits vorhanden.",
+    "Die Datei wird von einem anderen Programm verwendet.",
+    "Der angegebene Pfad oder Wert ist ungültig.",
+    "Dafür sind Administratorrechte erforderlich.",
+    "Auf dem Datenträger ist nicht genügend Speicherplatz vorhanden.",
+    "Der Vorgang wurde abgebrochen.",
+    "Dies wird für diese Art von Datei nicht unterstützt.",
+    "Zum Abschließen ist nicht genügend Arbeitsspeicher verfügbar.",
+    "Der Vorgang hat zu lange gedauert und wurde beendet.",
+    "Der Inhalt der Datei konnte nicht als Bild gelesen werden.",
+    "Das Hilfsprogramm wurde mit unbekannten Argumenten aufgerufen.",
+    "Ein Fehler ist aufgetreten.",
+];
+
+const ES: Messages = [
+    "Se denegó el acceso al archivo o la carpeta.",
+    "No se encontró el archivo o la carpeta.",
+    "Ya existe un archivo o una carpeta con ese nombre.",
+    "Otro programa está usando el archivo.",
+    "La ruta o el valor indicado no es válido.",
+    "Se necesitan permisos de administrador.",
+    "No hay suficiente espacio en el disco.",
+    "Se canceló la operación.",
+    "No se admite para este tipo de archivo.",
+    "No hay suficiente memoria para terminar.",
+    "La operación tardó demasiado y se detuvo.",
+    "No se pudo leer el contenido del archivo como imagen.",
+    "Se llamó a la herramienta con argumentos que no reconoce.",
+    "Se produjo un error.",
+];
+
+const FR: Messages = [
+    "L'accès au fichier ou au dossier a été refusé.",
+    "Le fichier ou le dossier est introuvable.",
+    "Un fichier ou un dossier portant ce nom existe déjà.",
+    "Le fichier est utilisé par un autre programme.",
+    "Le chemin ou la valeur indiqué n'est pas valide.",
+    "Cette opération nécessite des droits d'administrateur.",
+    "Espace disque insuffisant.",
+    "L'opération a été annulée.",
+    "Cette opération n'est pas prise en charge pour ce type de fichier.",
+    "Mémoire insuffisante pour terminer l'opération.",
+    "L'opération a pris trop de temps et a été interrompue.",
+    "Le contenu du fichier n'a pas pu être lu comme une image.",
+    "L'outil a été appelé avec des arguments qu'il ne comprend pas.",
+    "Une erreur s'est produite.",
+];
+
+const IT: Messages = [
+    "Accesso al file o alla cartella negato.",
+    "Impossibile trovare il file o la cartella.",
+    "Esiste già un file o una cartella con questo nome.",
+    "Il file è in uso da parte di un altro programma.",
+    "Il percorso o il valore specificato non è valido.",
+    "Sono necessari i diritti di amministratore.",
+    "Spazio su disco insufficiente.",
+    "L'operazione è stata annullata.",
+    "Non supportato per questo tipo di file.",
+    "Memoria insufficiente per completare l'operazione.",
+    "L'operazione ha richiesto troppo tempo ed è stata interrotta.",
+    "Impossibile leggere il contenuto del file come immagine.",
+    "Lo strumento è stato chiamato con argomenti non riconosciuti.",
+    "Si è verificato un errore.",
+];
+
+const JA: Messages = [
+    "ファイルまたはフォルダーへのアクセスが拒否されました。",
+    "ファイルまたはフォルダーが見つかりません。",
+    "同じ名前のファイルまたはフォルダーが既に存在します。",
+    "ファイルは別のプログラムで使用中です。",
+    "指定されたパスまたは値が無効です。",
+    "管理者権限が必要です。",
+    "ディスクの空き容量が不足しています。",
+    "操作はキャンセルされました。",
+    "この種類のファイルではサポートされていません。",
+    "メモリが不足しているため完了できません。",
+    "操作に時間がかかりすぎたため中止されました。",
+    "ファイルの内容を画像として読み込めませんでした。",
+    "認識できない引数でツールが呼び出されました。",
+    "エラーが発生しました。",
+];
+
+const KO: Messages = [
+    "파일 또는 폴더에 대한 액세스가 거부되었습니다.",
+    "파일 또는 폴더를 찾을 수 없습니다.",
+    "같은 이름의 파일 또는 폴더가 이미 있습니다.",
+    "다른 프로그램에서 파일을 사용하고 있습니다.",
+    "지정한 경로 또는 값이 올바르지 않습니다.",
+    "관리자 권한이 필요합니다.",
+    "디스크 공간이 부족합니다.",
+    "작업이 취소되었습니다.",
+    "이 파일 형식에서는 지원되지 않습니다.",
+    "메모리가 부족하여 완료할 수 없습니다.",
+    "작업 시간이 너무 오래 걸려 중지되었습니다.",
+    "파일 내용을 이미지로 읽을 수 없습니다.",
+    "알 수 없는 인수로 도구가 호출되었습니다.",
+    "오류가 발생했습니다.",
+];
+
+const NL: Messages = [
+    "De toegang tot het bestand of de map is geweigerd.",
+    "Het bestand of de map is niet gevonden.",
+    "Er bestaat al een bestand of map met deze naam.",
+    "Het bestand wordt door een ander programma gebruikt.",
+    "Het opgegeven pad of de opgegeven waarde is ongeldig.",
+    "Hiervoor zijn beheerdersrechten nodig.",
+    "Er is niet genoeg ruimte op de schijf.",
+    "De bewerking is geannuleerd.",
+    "Dit wordt voor dit type bestand niet ondersteund.",
+    "Er is niet genoeg geheugen om dit te voltooien.",
+    "De bewerking duurde te lang en is gestopt.",
+    "De inhoud van het bestand kon niet als afbeelding worden gelezen.",
+    "Het hulpprogramma is aangeroepen met onbekende argumenten.",
+    "Er is iets misgegaan.",
+];
+
+const PL: Messages = [
+    "Odmowa dostępu do pliku lub folderu.",
+    "Nie można odnaleźć pliku lub folderu.",
+    "Plik lub folder o tej nazwie już istnieje.",
+    "Plik jest używany przez inny program.",
+    "Podana ścieżka lub wartość jest nieprawidłowa.",
+    "Wymagane są uprawnienia administratora.",
+    "Za mało miejsca na dysku.",
+    "Operacja została anulowana.",
+    "Nie jest to obsługiwane dla tego typu pliku.",
+    "Za mało pamięci, aby zakończyć operację.",
+    "Operacja trwała zbyt długo i została przerwana.",
+    "Nie można odczytać zawartości pliku jako obrazu.",
+    "Narzędzie wywołano z nieznanymi argumentami.",
+    "Wystąpił błąd.",
+];
+
+const PT: Messages = [
+    "O acesso ao arquivo ou à pasta foi negado.",
+    "O arquivo ou a pasta não foi encontrado.",
+    "Já existe um arquivo ou uma pasta com esse nome.",
+    "O arquivo está sendo usado por outro programa.",
+    "O caminho ou valor informado não é válido.",
+    "São necessários direitos de administrador.",
+    "Não há espaço suficiente no disco.",
+    "A operação foi cancelada.",
+    "Não há suporte para este tipo de arquivo.",
+    "Não há memória suficiente para concluir.",
+    "A operação demorou demais e foi interrompida.",
+    "Não foi possível ler o conteúdo do arquivo como imagem.",
+    "A ferramenta foi chamada com argumentos que não reconhece.",
+    "Ocorreu um erro.",
+];
+
+const RU: Messages = [
+    "Доступ к файлу или папке запрещён.",
+    "Файл или папка не найдены.",
+    "Файл или папка с таким именем уже существует.",
+    "Файл используется другой программой.",
+    "Указанный путь или значение недопустимы.",
+    "Требуются права администратора.",
+    "Недостаточно места на диске.",
+    "Операция отменена.",
+    "Для этого типа файлов это не поддерживается.",
+    "Недостаточно памяти для завершения операции.",
+    "Операция заняла слишком много времени и была остановлена.",
+    "Не удалось прочитать содержимое файла как изображение.",
+    "Инструмент вызван с неизвестными аргументами.",
+    "Произошла ошибка.",
+];
+
+const ZH_HANS: Messages = [
+    "拒绝访问该文件或文件夹。",
+    "找不到该文件或文件夹。",
+    "已存在同名的文件或文件夹。",
+    "该文件正被其他程序使用。",
+    "指定的路径或值无效。",
+    "需要管理员权限。",
+    "磁盘空间不足。",
+    "操作已取消。",
+    "不支持此类型的文件。",
+    "内存不足，无法完成操作。",
+    "操作耗时过长，已停止。",
+    "无法将文件内容读取为图像。",
+    "调用工具时使用了无法识别的参数。",
+    "发生错误。",
+];
+
+const ZH_HANT: Messages = [
+    "拒絕存取該檔案或資料夾。",
+    "找不到該檔案或資料夾。",
+    "已存在同名的檔案或資料夾。",
+    "該檔案正被其他程式使用。",
+    "指定的路徑或值無效。",
+    "需要系統管理員權限。",
+    "磁碟空間不足。",
+    "作業已取消。",
+    "不支援此類型的檔案。",
+    "記憶體不足，無法完成作業。",
+    "作業耗時過長，已停止。",
+    "無法將檔案內容讀取為影像。",
+    "呼叫工具時使用了無法辨識的引數。",
+    "發生錯誤。",
+];
+
+// Looked up by the whole tag first, then by its language alone
+const LANGUAGES: &[(&str, &Messages)] = &[
+    ("en", &EN),
+    ("de", &DE),
+    ("es", &ES),
+    ("fr", &FR),
+    ("it", &IT),
+    ("ja", &JA),
+    ("ko", &KO),
+    ("nl", &NL),
+    ("pl", &PL),
+    ("pt", &PT),
+    ("ru", &RU),
+    ("zh", &ZH_HANS),
+    ("zh-hans", &ZH_HANS),
+    ("zh-cn", &ZH_HANS),
+    ("zh-sg", &ZH_HANS),
+    ("zh-hant", &ZH_HANT),
+    ("zh-tw", &ZH_HANT),
+    ("zh-hk", &ZH_HANT),
+    ("zh-mo", &ZH_HANT),
+];
+
+static LANGUAGE: OnceLock<(String, &'static Messages)> = OnceLock::new();
+
+/// Picks the language of `userMessage` in error reports, from a BCP 47 tag such as
+/// `de`, `pt-BR` or `zh-Hant-TW`, the form Electron's `app.getLocale()` returns. A
+/// language without a catalog gets English. Only the first call has any effect.
+pub fn set_language(tag: &str) {
+    let normalized = tag.trim().replace('_', "-").to_ascii_lowercase();
+    let mut subtags = normalized.split('-');
+    let language = subtags.next().unwrap_or_default();
+    let find = |key: &str| LANGUAGES.iter().find(|(known, _)| *known == key).map(|(_, messages)| *messages);
+    let messages = find(&normalized)
+        .or_else(|| subtags.next().and_then(|subtag| find(&format!("{}-{}", language, subtag))))
+        .or_else(|| find(language))
+        .unwrap_or(&EN);
+    let _ = LANGUAGE.set((tag.to_string(), messages));
+}
+
+/// The tag given to [`set_language`], for passing on to another copy of the helper.
+pub fn language() -> Option<&'static str> {
+    LANGUAGE.get().map(|(tag, _)| tag.as_str())
+}
+
+/// A message for `code` that can be shown to the user as it is, unlike the report's
+/// `message`, which is whatever English the failing API gave. Unknown codes get the
+/// one for E_FAIL.
+pub fn user_message(code: &str) -> &'static str {
+    let messages = LANGUAGE.get().map_or(&EN, |(_, messages)| *messages);
+    let index = CODES.iter().position(|known| *known == code).unwrap_or(CODES.len() - 1);
+    messages[index]
+}
@@ -0,0 +1,62 @@
+use windows::{
+    core::HSTRING,
+    Win32::Storage::FileSystem::GetFullPathNameW,
+    Win32::System::Environment::ExpandEnvironmentStringsW,
+};
+
+use crate::wide::from_wide;
+
+/// Large enough for extended-length paths and long argument strings.
+pub const PATH_BUFFER_LEN: usize = 32768;
+
+pub const MAX_PATH: usize = 260;
+const EXTENDED_PREFIX: &str = r"\\?\";
+const EXTENDED_UNC_PREFIX: &str = r"\\?\UNC\";
+
+pub fn wide_len(path: &str) -> usize {
+    path.encode_utf16().count()
+}
+
+/// Expands %VARIABLES%, as icon locations and registry values are often stored;
+/// the value as it is when that fails.
+pub fn expand_env_vars(value: &str) -> String {
+    let mut buffer = vec![0u16; PATH_BUFFER_LEN];
+    let len = unsafe { ExpandEnvironmentStringsW(&HSTRING::from(value), Some(&mut buffer)) };
+    if len == 0 || len as usize > buffer.len() {
+        return value.to_string();
+    }
+    from_wide(&buffer)
+}
+
+pub fn strip_extended_prefix(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(EXTENDED_UNC_PREFIX) {
+        format!(r"\\{}", unc)
+    } else {
+        path.strip_prefix(EXTENDED_PREFIX).unwrap_or(path).to_string()
+    }
+}
+
+pub fn full_path(path: &str) -> String {
+    let mut buffer = vec![0u16; PATH_BUFFER_LEN];
+    let len = unsafe { GetFullPathNameW(&HSTRING::from(path), Some(&mut buffer), None) };
+    if len == 0 || len as usize > buffer.len() {
+        return path.to_string();
+    }
+    from_wide(&buffer)
+}
+
+/// Absolute form of `path`, switched to the `\\?\` syntax when it would otherwise
+/// hit MAX_PATH, for files opened with the wide file APIs.
+pub fn extended_length_path(path: &str) -> String {
+    if path.starts_with(EXTENDED_PREFIX) || path.starts_with(r"\\.\") {
+        return path.to_string();
+    }
+    let full = full_path(path);
+    if wide_len(&full) < MAX_PATH {
+        return full;
+    }
+    match full.strip_prefix(r"\\") {
+        Some(unc) => format!("{}{}", EXTENDED_UNC_PREFIX, unc),
+        None => format!("{}{}", EXTENDED_PREFIX, full),
+    }
+}
@@ -0,0 +1,53 @@
+use serde::Serialize;
+
+use crate::json;
+
+/// The progress lines a tool prints on stdout while it works through a list of jobs
+/// with `--progress`, one as each job finishes:
+/// {"type":"progress","id","completed","total","item"}. `id` is the job's index in
+/// the input, `completed` how many jobs have finished so far, this one included, and
+/// `item` the job's path. Jobs that run in parallel finish out of order, so
+/// `completed` is what a progress bar shows and `id` what to match a job by. A long
+/// job may also report `percent` along the way, without counting as finished.
+pub struct Progress {
+    enabled: bool,
+    completed: usize,
+    total: usize,
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: usize,
+    completed: usize,
+    total: usize,
+    item: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percent: Option<u64>,
+}
+
+impl Progress {
+    /// Prints nothing unless `enabled`, so a tool can count without checking its flag.
+    pub fn new(enabled: bool, total: usize) -> Self {
+        Progress { enabled, completed: 0, total }
+    }
+
+    /// Counts job `id` as finished, failed or not.
+    pub fn finished(&mut self, id: usize, item: &str) {
+        self.completed += 1;
+        self.print(id, item, None);
+    }
+
+    /// How far into job `id` it has got, as a percentage.
+    pub fn partial(&self, id: usize, item: &str, percent: u64) {
+        self.print(id, item, Some(percent));
+    }
+
+    fn print(&self, id: usize, item: &str, percent: Option<u64>) {
+        if self.enabled {
+            let (completed, total) = (self.completed, self.total);
+            let _ = json::emit(Line { kind: "progress", id, completed, total, item, percent });
+        }
+    }
+}
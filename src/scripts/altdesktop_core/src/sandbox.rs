@@ -0,0 +1,166 @@
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+// DOS device names, which open the device in any folder and with any extension
+#[cfg(windows)]
+const DEVICE_NAMES: &[&str] = &["CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "CLOCK$"];
+#[cfg(windows)]
+const NUMBERED_DEVICE_NAMES: &[&str] = &["COM", "LPT"];
+
+/// The roots given with `--allowed-output-root`, resolved as the paths checked are.
+static ROOTS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+/// Why a path was turned down.
+#[derive(Debug)]
+pub enum Rejected {
+    /// A path no tool should take at all, such as a device or stream name.
+    Invalid(String),
+    /// A file to write outside every allowed root.
+    Outside(String),
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejected::Invalid(message) | Rejected::Outside(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+/// E_INVALIDARG or E_ACCESSDENIED, for the tools that report HRESULTs.
+#[cfg(windows)]
+impl From<Rejected> for windows::core::Error {
+    fn from(rejected: Rejected) -> Self {
+        use windows::Win32::Foundation::{E_ACCESSDENIED, E_INVALIDARG};
+        let code = if matches!(rejected, Rejected::Invalid(_)) { E_INVALIDARG } else { E_ACCESSDENIED };
+        windows::core::Error::new(code, rejected.to_string().into())
+    }
+}
+
+/// Confines every file a tool writes or deletes to the folders in `roots`, from
+/// `--allowed-output-root`, so that a caller passing paths it was handed can only
+/// touch the app's own caches and shortcuts. Called once, before any tool runs; a
+/// root needn't exist yet.
+pub fn restrict(roots: Vec<PathBuf>) -> Result<(), String> {
+    let roots = roots
+        .into_iter()
+        .map(|root| {
+            check_input(&root.to_string_lossy()).map_err(|rejected| rejected.to_string())?;
+            resolve(&root).map_err(|error| format!("Invalid output root {}: {}", root.display(), error))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if !roots.is_empty() {
+        let _ = ROOTS.set(roots);
+    }
+    Ok(())
+}
+
+/// The roots, absolute, for passing on to another copy of the helper, which may not
+/// start in the same folder; empty when writes aren't restricted.
+pub fn roots() -> impl Iterator<Item = &'static Path> {
+    ROOTS.get().into_iter().flatten().map(PathBuf::as_path)
+}
+
+/// Fails for a path no tool should read or write, whatever the roots: an empty
+/// one, one with a NUL in it and, on Windows, a device such as `\\.\PhysicalDrive0`,
+/// `\\?\GLOBALROOT\...` or `C:\folder\CON.txt`.
+pub fn check_input(path: &str) -> Result<(), Rejected> {
+    if path.is_empty() {
+        return Err(Rejected::Invalid("The path is empty".to_string()));
+    }
+    if path.contains('\0') {
+        return Err(Rejected::Invalid(format!("{} contains a NUL character", path.replace('\0', "\\0"))));
+    }
+    #[cfg(windows)]
+    {
+        let lower = path.to_ascii_lowercase().replace('/', "\\");
+        if lower.starts_with(r"\\.\") || lower.starts_with(r"\??\") || lower.starts_with(r"\\?\globalroot") {
+            return Err(Rejected::Invalid(format!("{} is a device path", path)));
+        }
+        if let Some(name) = lower.split('\\').find(|name| is_device_name(name)) {
+            return Err(Rejected::Invalid(format!("{} names the {} device", path, name)));
+        }
+    }
+    Ok(())
+}
+
+/// Fails unless the file or folder at `path` may be written or deleted: it must
+/// pass [`check_input`], name no alternate data stream and, with roots given, lie
+/// inside one of them once it's made absolute and the symlinks and junctions in
+/// the part that exists are followed, the file itself included.
+pub fn check_output(path: &Path) -> Result<(), Rejected> {
+    let text = path.to_string_lossy();
+    check_input(&text)?;
+    // A colon anywhere but after a drive letter opens a stream of the file before it
+    #[cfg(windows)]
+    if path
+        .components()
+        .any(|component| matches!(component, Component::Normal(name) if name.to_string_lossy().contains(':')))
+    {
+        return Err(Rejected::Invalid(format!("{} names an alternate data stream", text)));
+    }
+    let Some(roots) = ROOTS.get() else {
+        return Ok(());
+    };
+    let resolved = resolve(path).map_err(|error| Rejected::Invalid(format!("Invalid path {}: {}", text, error)))?;
+    if roots.iter().any(|root| is_inside(&resolved, root)) {
+        return Ok(());
+    }
+    Err(Rejected::Outside(format!("{} is outside the folders the helper may write to", resolved.display())))
+}
+
+// The real path of the deepest part of `path` that exists, with the rest appended,
+// which mustn't climb back out with `..` since nothing there exists to resolve it.
+// A link to something missing is turned down, as writing through it would create
+// its target wherever that is
+fn resolve(path: &Path) -> Result<PathBuf, String> {
+    let absolute = std::path::absolute(path).map_err(|error| error.to_string())?;
+    let components: Vec<Component> = absolute.components().collect();
+    for existing in (1..=components.len()).rev() {
+        let part: PathBuf = components[..existing].iter().collect();
+        let Ok(mut resolved) = fs::canonicalize(&part) else {
+            if fs::symlink_metadata(&part).is_ok_and(|metadata| metadata.is_symlink()) {
+                return Err(format!("{} is a link to something that doesn't exist", part.display()));
+            }
+            continue;
+        };
+        for component in &components[existing..] {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::CurDir => {}
+                _ => return Err("\"..\" follows a folder that doesn't exist".to_string()),
+            }
+        }
+        return Ok(resolved);
+    }
+    Err("No part of it exists".to_string())
+}
+
+// By whole components, so C:\cache doesn't hold C:\cache-other, and on Windows
+// without regard to case, as the file system compares names
+fn is_inside(path: &Path, root: &Path) -> bool {
+    if cfg!(windows) {
+        let lower = |path: &Path| PathBuf::from(path.to_string_lossy().to_lowercase());
+        lower(path).starts_with(lower(root))
+    } else {
+        path.starts_with(root)
+    }
+}
+
+// The part before the first dot, less trailing spaces, is what Windows matches
+#[cfg(windows)]
+fn is_device_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default().trim_end_matches(' ');
+    let numbered = match stem.as_bytes() {
+        [a, b, c, digit] => {
+            NUMBERED_DEVICE_NAMES.iter().any(|prefix| prefix.as_bytes().eq_ignore_ascii_case(&[*a, *b, *c]))
+                && (b'1'..=b'9').contains(digit)
+        }
+        _ => false,
+    };
+    numbered || DEVICE_NAMES.iter().any(|device| stem.eq_ignore_ascii_case(device))
+}
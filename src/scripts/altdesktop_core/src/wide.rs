@@ -0,0 +1,10 @@
+/// A NUL-terminated UTF-16 copy of `value`, for the W APIs that take a PCWSTR.
+pub fn to_wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(Some(0)).collect()
+}
+
+/// Converts a NUL-terminated buffer filled in by a `Get*` call into a String.
+pub fn from_wide(buffer: &[u16]) -> String {
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..len])
+}
@@ -0,0 +1,13 @@
+// Embeds altdesktop-helper.manifest, which makes the process Per-Monitor V2 DPI aware
+// before any of its code runs. Only the MSVC linker takes a manifest; other builds
+// fall back on monitors::use_physical_pixels, which main calls first thing.
+fn main() {
+    println!("cargo:rerun-if-changed=altdesktop-helper.manifest");
+    let windows = std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows");
+    let msvc = std::env::var("CARGO_CFG_TARGET_ENV").as_deref() == Ok("msvc");
+    if windows && msvc {
+        let manifest = std::path::Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("altdesktop-helper.manifest");
+        println!("cargo:rustc-link-arg-bins=/MANIFEST:EMBED");
+        println!("cargo:rustc-link-arg-bins=/MANIFESTINPUT:{}", manifest.display());
+    }
+}
@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use create_shortcut::known_folders::known_folder_path;
+use serde_json::Value;
+use windows::Win32::UI::Shell::FOLDERID_ProgramData;
+
+use super::{Game, IconHint};
+
+// The launcher writes a JSON .item file here for each installed game, engine and
+// plugin, machine-wide
+const MANIFESTS: &str = r"Epic\EpicGamesLauncher\Data\Manifests";
+
+/// The games the Epic Games Launcher has installed; none without it.
+pub fn games() -> Vec<Game> {
+    let Ok(program_data) = known_folder_path(&FOLDERID_ProgramData) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(Path::new(&program_data).join(MANIFESTS)) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("item")))
+        .filter_map(|path| read_manifest(&path))
+        .collect()
+}
+
+fn read_manifest(path: &Path) -> Option<Game> {
+    let manifest: Value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    let text = |key: &str| manifest.get(key).and_then(Value::as_str).filter(|value| !value.is_empty());
+    let app_name = text("AppName")?;
+    // DLC has a manifest of its own naming the game it belongs to, and Unreal
+    // Engine versions are installed the same way as games
+    let is_engine = manifest["AppCategories"]
+        .as_array()
+        .is_some_and(|categories| categories.iter().any(|category| category == "engines" || category == "plugins"));
+    if manifest["bIsIncompleteInstall"] == true || text("MainGameAppName").is_some_and(|main| main != app_name) || is_engine {
+        return None;
+    }
+    let install_dir = text("InstallLocation")?.replace('/', "\\");
+    let executable = text("LaunchExecutable")
+        .map(|executable| PathBuf::from(&install_dir).join(executable.replace('/', "\\")))
+        .filter(|executable| executable.is_file());
+    Some(Game {
+        launcher: "epic",
+        id: app_name.to_string(),
+        name: text("DisplayName").unwrap_or(app_name).to_string(),
+        // Through the launcher, which signs the game in and keeps it updated
+        target: format!(
+            "com.epicgames.launcher://apps/{}%3A{}%3A{}?action=launch&silent=true",
+            text("CatalogNamespace")?,
+            text("CatalogItemId")?,
+            app_name
+        ),
+        install_dir,
+        library: None,
+        arguments: None,
+        working_dir: None,
+        icon: executable.map(|path| IconHint::File { path: path.to_string_lossy().into_owned(), index: 0 }),
+    })
+}
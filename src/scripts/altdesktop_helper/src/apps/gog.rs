@@ -0,0 +1,60 @@
+use std::path::Path;
+
+use windows::{core::*, Win32::System::Registry::*};
+
+use super::{Game, IconHint};
+use crate::registry::read_string;
+
+// GOG's installers register every game here, whether or not GOG Galaxy is used,
+// in the 32-bit view
+const GAMES_KEY: PCWSTR = w!(r"SOFTWARE\GOG.com\Games");
+const NAME_LENGTH: usize = 256;
+
+/// The games installed from GOG; none when there are none.
+pub fn games() -> Vec<Game> {
+    let mut key = HKEY::default();
+    if unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, GAMES_KEY, 0, KEY_READ | KEY_WOW64_32KEY, &mut key) }.is_err() {
+        return Vec::new();
+    }
+    let mut games = Vec::new();
+    for index in 0.. {
+        let mut name = [0u16; NAME_LENGTH];
+        let mut length = NAME_LENGTH as u32;
+        let listed =
+            unsafe { RegEnumKeyExW(key, index, PWSTR(name.as_mut_ptr()), &mut length, None, PWSTR::null(), None, None) };
+        if listed.is_err() {
+            break;
+        }
+        let subkey = HSTRING::from_wide(&name[..length as usize]).unwrap_or_default();
+        games.extend(read_game(key, &subkey));
+    }
+    unsafe {
+        let _ = RegCloseKey(key);
+    }
+    games
+}
+
+fn read_game(key: HKEY, subkey: &HSTRING) -> Option<Game> {
+    let value = |name: PCWSTR| read_string(key, subkey, name).filter(|value| !value.is_empty());
+    // DLC names the game it extends
+    if value(w!("dependsOn")).is_some() {
+        return None;
+    }
+    let id = value(w!("gameID"))?;
+    let install_dir = value(w!("path"))?;
+    let executable = value(w!("exe")).filter(|executable| Path::new(executable).is_file())?;
+    // goggame-<id>.ico is the icon Galaxy and the installer's shortcuts use
+    let icon = Path::new(&install_dir).join(format!("goggame-{}.ico", id));
+    let icon = if icon.is_file() { icon.to_string_lossy().into_owned() } else { executable.clone() };
+    Some(Game {
+        launcher: "gog",
+        name: value(w!("gameName")).unwrap_or_else(|| id.clone()),
+        id,
+        library: None,
+        target: executable,
+        arguments: value(w!("launchParam")),
+        working_dir: value(w!("workingDir")).or_else(|| Some(install_dir.clone())),
+        install_dir,
+        icon: Some(IconHint::File { path: icon, index: 0 }),
+    })
+}
@@ -0,0 +1,422 @@
+#[cfg(windows)]
+mod epic;
+#[cfg(windows)]
+mod gog;
+#[cfg(windows)]
+mod search;
+#[cfg(windows)]
+mod start_menu;
+#[cfg(windows)]
+mod steam;
+#[cfg(windows)]
+mod store;
+#[cfg(windows)]
+mod uninstall;
+#[cfg(windows)]
+mod vdf;
+
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use altdesktop_core::error::ErrorReport;
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use serde_json::{json, Value};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper apps list
+  altdesktop-helper apps start-menu
+  altdesktop-helper apps games [--shortcuts <folder> [--icon-dir <folder>]]
+  altdesktop-helper apps search <query> [--limit <n>] [--windows-search]
+
+list prints the installed applications as a JSON array sorted by name, merging Start
+Menu shortcuts, the Programs and Features (uninstall) entries of all users, the
+Store apps of the current user and the games `games` lists. Each app has a name, its
+sources, a target to pass to `launch` (null when nothing launchable was found) and
+an icon hint: {\"path\",\"index\"}
+for `icon extract`, or {\"package\"} for `icon extract --package`. publisher, version,
+installLocation and appUserModelId are included when known.
+
+start-menu prints every shortcut in the current user's and the all-users Start Menu
+Programs folders, documents and uninstallers included, as a JSON array of
+{\"name\",\"path\",\"folder\",\"scope\"} plus the fields `shortcut read` prints. folder
+is the subfolder relative to Programs and scope is user or common.
+
+games prints the games installed through Steam, the Epic Games Launcher and GOG as a
+JSON array sorted by name, each {\"launcher\",\"id\",\"name\",\"installDir\",\"target\",
+\"icon\"}, with icon as list has it or null. Games still downloading for the first
+time, DLC and add-ons are left out.
+  steam  id is the appid, and library the Steam library folder the game is in;
+         target is steam://rungameid/<id> and icon the small one in Steam's
+         library cache.
+  epic   id is the app name; target is the launcher's com.epicgames.launcher://
+         URI, and icon the game's executable.
+  gog    id is the game ID; GOG games need no launcher, so target is the game's
+         executable, with arguments and workingDir to pass to launch, and icon
+         the one the installer left in installDir.
+--shortcuts writes a shortcut to each game into <folder>, a <name>.url to a URI
+target or a <name>.lnk to an executable, replacing any of the same name, and prints
+{\"ok\",\"results\":[{\"id\",\"name\",\"path\",\"ok\"}]}; failed games carry the error
+fields of a failed shortcut --json run. Shortcuts can't show a JPEG icon, so
+--icon-dir converts Steam's to <launcher>-<id>.ico in its folder, created if
+missing, for the shortcut to use; without it they show Steam's icon.
+
+search finds what can be launched by a name, such as one typed into a launcher: the
+Start Menu's app shortcuts, the Store apps, the programs registered under App Paths
+and the PATHEXT programs in each PATH folder, plus with --windows-search the
+programs the Windows Search index finds, which is slower. It prints a JSON array,
+best match first and at most --limit (20) long, of {\"name\",\"target\",
+\"executable\",\"sources\",\"score\",\"icon\"}. target and icon are as list has them,
+executable is the program target starts when known, and matches of one program are
+merged, keeping the first source's target. score ranks the whole name, its start,
+the start of its words, anywhere in it, its initials and its letters in order, then
+Start Menu entries before Store apps, App Paths, Windows Search and PATH.";
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct App {
+    name: String,
+    /// Where the app was found: startMenu, uninstall, store and/or a games launcher.
+    sources: Vec<&'static str>,
+    target: Option<String>,
+    icon: Option<IconHint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    publisher: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    install_location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    app_user_model_id: Option<String>,
+}
+
+/// Where `icon extract` can find an app's icon.
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum IconHint {
+    File { path: String, index: i32 },
+    Package { package: String },
+}
+
+#[cfg(windows)]
+impl IconHint {
+    /// Parses an icon location as the registry stores them: "path", path, or either
+    /// followed by ,index. None for "%1", which means each file is its own icon.
+    pub fn parse(location: &str) -> Option<IconHint> {
+        let (path, index) = match location.rsplit_once(',') {
+            Some((path, index)) if index.trim().parse::<i32>().is_ok() => (path, index.trim().parse().unwrap()),
+            _ => (location, 0),
+        };
+        let path = path.trim().trim_matches('"');
+        if path.is_empty() || path == "%1" {
+            return None;
+        }
+        Some(IconHint::File { path: path.to_string(), index })
+    }
+}
+
+/// A game installed through one of the launchers games knows.
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Game {
+    pub launcher: &'static str,
+    pub id: String,
+    pub name: String,
+    pub install_dir: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub library: Option<String>,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    pub icon: Option<IconHint>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        None => tool::finish(list_apps()),
+        Some("--start-menu") if args.len() == 2 => tool::finish(Ok(start_menu::shortcuts())),
+        Some("--start-menu") => tool::usage_error("start-menu takes no arguments", USAGE),
+        Some("--games") => run_games(&args[2..]),
+        Some("--search") => run_search(&args[2..]),
+        Some(_) => tool::usage_error("list takes no arguments", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("apps")
+}
+
+// Start Menu shortcuts come first because they are what Explorer launches; the
+// uninstall entries then fill in details, and only add apps of their own when no
+// shortcut matches them by name or points into their install folder
+#[cfg(windows)]
+fn list_apps() -> windows::core::Result<Vec<App>> {
+    let shortcuts: Vec<_> = start_menu::shortcuts().into_iter().filter(start_menu::is_app).collect();
+    // The executable behind each shortcut, by index, for matching install folders
+    let targets: Vec<String> = shortcuts.iter().map(|shortcut| shortcut.info.target_path.clone()).collect();
+    let mut apps: Vec<App> = shortcuts
+        .into_iter()
+        .map(|shortcut| App {
+            name: shortcut.name,
+            sources: vec!["startMenu"],
+            target: Some(shortcut.path.clone()),
+            // icon extract follows the link to whatever icon Explorer shows for it
+            icon: Some(IconHint::File { path: shortcut.path, index: 0 }),
+            publisher: None,
+            version: None,
+            install_location: None,
+            app_user_model_id: shortcut.info.app_user_model_id,
+        })
+        .collect();
+
+    for entry in uninstall::entries() {
+        let key = dedup_key(&entry.name);
+        let matching: Vec<usize> = (0..apps.len())
+            .filter(|&index| {
+                dedup_key(&apps[index].name) == key
+                    || match (targets.get(index), &entry.install_location) {
+                        (Some(target), Some(folder)) => is_inside(target, folder),
+                        _ => false,
+                    }
+            })
+            .collect();
+        if matching.is_empty() {
+            apps.push(App {
+                name: entry.name,
+                sources: vec!["uninstall"],
+                target: entry.executable.clone(),
+                icon: entry.icon,
+                publisher: entry.publisher,
+                version: entry.version,
+                install_location: entry.install_location,
+                app_user_model_id: None,
+            });
+            continue;
+        }
+        for index in matching {
+            let app = &mut apps[index];
+            if !app.sources.contains(&"uninstall") {
+                app.sources.push("uninstall");
+            }
+            app.publisher = app.publisher.take().or_else(|| entry.publisher.clone());
+            app.version = app.version.take().or_else(|| entry.version.clone());
+            app.install_location = app.install_location.take().or_else(|| entry.install_location.clone());
+        }
+    }
+
+    for package in store::packaged_apps()? {
+        let key = dedup_key(&package.name);
+        let target = format!("shell:AppsFolder\\{}", package.app_user_model_id);
+        match apps.iter_mut().find(|app| dedup_key(&app.name) == key) {
+            Some(app) => {
+                app.sources.push("store");
+                app.target = app.target.take().or(Some(target));
+                app.app_user_model_id = app.app_user_model_id.take().or(Some(package.app_user_model_id));
+            }
+            None => apps.push(App {
+                name: package.name,
+                sources: vec!["store"],
+                target: Some(target),
+                icon: Some(IconHint::Package { package: package.family_name }),
+                publisher: None,
+                version: None,
+                install_location: None,
+                app_user_model_id: Some(package.app_user_model_id),
+            }),
+        }
+    }
+
+    // Launched the way the launcher does rather than straight from the executable
+    // an uninstall entry names, so Steam's and Epic's games get their overlays,
+    // updates and cloud saves
+    for game in installed_games() {
+        let key = dedup_key(&game.name);
+        match apps.iter_mut().find(|app| dedup_key(&app.name) == key) {
+            Some(app) => {
+                app.sources.push(game.launcher);
+                if !app.sources.contains(&"startMenu") {
+                    app.target = Some(game.target);
+                }
+                app.install_location = app.install_location.take().or(Some(game.install_dir));
+            }
+            None => apps.push(App {
+                name: game.name,
+                sources: vec![game.launcher],
+                target: Some(game.target),
+                icon: game.icon,
+                publisher: None,
+                version: None,
+                install_location: Some(game.install_dir),
+                app_user_model_id: None,
+            }),
+        }
+    }
+
+    apps.sort_by_cached_key(|app| dedup_key(&app.name));
+    Ok(apps)
+}
+
+#[cfg(windows)]
+fn run_games(args: &[String]) -> ExitCode {
+    let mut shortcuts = None;
+    let mut icon_dir = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let target = match arg.as_str() {
+            "--shortcuts" => &mut shortcuts,
+            "--icon-dir" => &mut icon_dir,
+            other => return tool::usage_error(&format!("Unknown games argument: {}", other), USAGE),
+        };
+        match iter.next() {
+            Some(value) => *target = Some(value.clone()),
+            None => return tool::usage_error(&format!("{} expects a value", arg), USAGE),
+        }
+    }
+
+    let games = installed_games();
+    let Some(folder) = shortcuts else {
+        if icon_dir.is_some() {
+            return tool::usage_error("--icon-dir expects --shortcuts", USAGE);
+        }
+        return tool::finish(Ok(games));
+    };
+    let results: Vec<Value> = games
+        .iter()
+        .map(|game| {
+            let written = write_game_shortcut(game, &folder, icon_dir.as_deref());
+            let mut result = match &written {
+                Ok(path) => json!({ "path": path, "ok": true }),
+                Err(error) => serde_json::to_value(ErrorReport::from_error(error)).unwrap(),
+            };
+            result["id"] = Value::from(game.id.as_str());
+            result["name"] = Value::from(game.name.as_str());
+            result
+        })
+        .collect();
+    let ok = results.iter().all(|result| result["ok"] == true);
+    println!("{}", json!({ "ok": ok, "results": results }));
+    if ok { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+#[cfg(windows)]
+fn run_search(args: &[String]) -> ExitCode {
+    let mut query = None;
+    let mut limit = search::DEFAULT_LIMIT;
+    let mut windows_search = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--limit" => match iter.next().map(|value| value.parse::<usize>()) {
+                Some(Ok(value)) if value > 0 => limit = value,
+                Some(_) => return tool::usage_error("--limit expects a positive number", USAGE),
+                None => return tool::usage_error("--limit expects a value", USAGE),
+            },
+            "--windows-search" => windows_search = true,
+            other if other.starts_with("--") => {
+                return tool::usage_error(&format!("Unknown search argument: {}", other), USAGE);
+            }
+            _ if query.is_some() => return tool::usage_error("search takes one query", USAGE),
+            _ => query = Some(arg.as_str()),
+        }
+    }
+    match query.filter(|query| !query.trim().is_empty()) {
+        Some(query) => tool::finish(search::search(query, limit, windows_search)),
+        None => tool::usage_error("search expects a query", USAGE),
+    }
+}
+
+#[cfg(windows)]
+fn installed_games() -> Vec<Game> {
+    let mut games: Vec<Game> = steam::games().into_iter().chain(epic::games()).chain(gog::games()).collect();
+    games.sort_by_cached_key(|game| dedup_key(&game.name));
+    games
+}
+
+#[cfg(windows)]
+fn write_game_shortcut(game: &Game, folder: &str, icon_dir: Option<&str>) -> windows::core::Result<String> {
+    let folder = create_shortcut::known_folders::expand_known_folder(folder)?;
+    let name = Some(file_name(&game.name)).filter(|name| !name.is_empty()).unwrap_or_else(|| game.id.clone());
+    let icon = match &game.icon {
+        Some(IconHint::File { path, .. }) if is_image(path) => match icon_dir {
+            Some(icon_dir) => Some((convert_icon(path, icon_dir, game)?, 0)),
+            None => None,
+        },
+        Some(IconHint::File { path, index }) => Some((path.clone(), *index)),
+        _ => None,
+    };
+    let icon = icon.as_ref().map(|(path, index)| (path.as_str(), *index));
+
+    let is_uri = game.target.contains("://");
+    let path = std::path::Path::new(&folder).join(format!("{}.{}", name, if is_uri { "url" } else { "lnk" }));
+    let path = path.to_string_lossy().into_owned();
+    if is_uri {
+        create_shortcut::url::write_url_shortcut(&game.target, &path, icon)?;
+    } else {
+        create_shortcut::create::create_program_shortcut(
+            &path,
+            &game.target,
+            game.arguments.as_deref(),
+            game.working_dir.as_deref(),
+            icon,
+        )?;
+    }
+    Ok(path)
+}
+
+#[cfg(windows)]
+fn is_image(path: &str) -> bool {
+    let extension = std::path::Path::new(path).extension().map(|extension| extension.to_string_lossy().to_lowercase());
+    matches!(extension.as_deref(), Some("jpg" | "jpeg" | "png"))
+}
+
+#[cfg(windows)]
+fn convert_icon(image: &str, icon_dir: &str, game: &Game) -> windows::core::Result<String> {
+    use windows::{core::Error, Win32::Foundation::E_FAIL};
+
+    let icon_dir = std::path::absolute(create_shortcut::known_folders::expand_known_folder(icon_dir)?).map_err(tool::io_error)?;
+    altdesktop_core::sandbox::check_output(&icon_dir)?;
+    std::fs::create_dir_all(&icon_dir).map_err(tool::io_error)?;
+    let path = icon_dir.join(format!("{}-{}.ico", game.launcher, game.id));
+    image::open(image)
+        .and_then(|decoded| decoded.save_with_format(&path, image::ImageFormat::Ico))
+        .map_err(|error| Error::new(E_FAIL, format!("Could not convert {}: {}", image, error).into()))?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+// What Windows allows in a file name, as Steam does for its own desktop shortcuts
+#[cfg(windows)]
+fn file_name(name: &str) -> String {
+    let cleaned: String = name.chars().filter(|&c| !c.is_control() && !r#"<>:"/\|?*"#.contains(c)).collect();
+    cleaned.trim().trim_end_matches('.').to_string()
+}
+
+#[cfg(windows)]
+fn dedup_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+#[cfg(windows)]
+fn is_inside(path: &str, folder: &str) -> bool {
+    let folder = folder.trim_end_matches('\\');
+    // A drive root or an empty InstallLocation would claim every app
+    if folder.len() <= 2 {
+        return false;
+    }
+    path.get(..folder.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(folder))
+        && path.as_bytes().get(folder.len()) == Some(&b'\\')
+}
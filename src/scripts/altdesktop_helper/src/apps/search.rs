@@ -0,0 +1,255 @@
+use std::env;
+use std::path::Path;
+
+use serde::Serialize;
+use windows::{core::*, Win32::System::Registry::*, Win32::UI::Shell::*};
+
+use super::{start_menu, store, IconHint};
+use crate::registry::{read_string, subkey_names};
+use crate::tool;
+
+pub const DEFAULT_LIMIT: usize = 20;
+
+// The 32-bit view's key is read by its real name, which the 64-bit helper sees as is
+const APP_PATHS: [(HKEY, &str); 3] = [
+    (HKEY_CURRENT_USER, r"SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths"),
+    (HKEY_LOCAL_MACHINE, r"SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths"),
+    (HKEY_LOCAL_MACHINE, r"SOFTWARE\WOW6432Node\Microsoft\Windows\CurrentVersion\App Paths"),
+];
+const DEFAULT_PATHEXT: &str = ".COM;.EXE;.BAT;.CMD";
+// What a Windows Search hit may be to count as launchable
+const PROGRAM_EXTENSIONS: &[&str] = &["exe", "lnk", "bat", "cmd", "com", "msc", "cpl"];
+
+/// One launchable thing whose name matches the query.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Found {
+    pub name: String,
+    /// What to pass to `launch`: the Start Menu shortcut, the program, or
+    /// shell:AppsFolder\<AppUserModelID> for a Store app.
+    pub target: String,
+    /// The program it starts, when known; the matches of one program are merged.
+    pub executable: Option<String>,
+    /// Where it was found: startMenu, store, appPaths, path and/or windowsSearch.
+    pub sources: Vec<&'static str>,
+    pub score: u32,
+    pub icon: Option<IconHint>,
+}
+
+/// Everything launchable whose name or program matches `query`, best first and at
+/// most `limit` of them. Windows Search is only asked with `windows_search`, since
+/// it's slow and finds far more than programs.
+pub fn search(query: &str, limit: usize, windows_search: bool) -> Result<Vec<Found>> {
+    let query = query.trim().to_lowercase();
+    let mut found = Vec::new();
+
+    for shortcut in start_menu::shortcuts().into_iter().filter(start_menu::is_app) {
+        let executable = Some(shortcut.info.target_path).filter(|target| !target.is_empty());
+        let icon = IconHint::File { path: shortcut.path.clone(), index: 0 };
+        add(&mut found, &query, shortcut.name, shortcut.path, executable, "startMenu", Some(icon));
+    }
+    for package in store::packaged_apps()? {
+        let target = format!("shell:AppsFolder\\{}", package.app_user_model_id);
+        let icon = IconHint::Package { package: package.family_name };
+        add(&mut found, &query, package.name, target, None, "store", Some(icon));
+    }
+    let programs = app_paths().into_iter().map(|program| (program, "appPaths"));
+    for ((name, path), source) in programs.chain(path_programs().into_iter().map(|program| (program, "path"))) {
+        let icon = IconHint::File { path: path.clone(), index: 0 };
+        add(&mut found, &query, name, path.clone(), Some(path), source, Some(icon));
+    }
+    if windows_search {
+        for (name, path) in windows_search_hits(&query, limit) {
+            let icon = IconHint::File { path: path.clone(), index: 0 };
+            let executable = is_extension(&path, "exe").then(|| path.clone());
+            add(&mut found, &query, name, path, executable, "windowsSearch", Some(icon));
+        }
+    }
+
+    found.sort_by(|a, b| {
+        b.score.cmp(&a.score).then_with(|| a.name.len().cmp(&b.name.len())).then_with(|| a.name.cmp(&b.name))
+    });
+    found.truncate(limit);
+    Ok(found)
+}
+
+// Merged with an earlier match of the same program, which keeps its target: the
+// sources are added in the order their targets are best launched by
+fn add(
+    found: &mut Vec<Found>,
+    query: &str,
+    name: String,
+    target: String,
+    executable: Option<String>,
+    source: &'static str,
+    icon: Option<IconHint>,
+) {
+    let program = executable.as_deref().and_then(|path| Path::new(path).file_stem()).map(|stem| stem.to_string_lossy());
+    let names = [Some(name.as_str()), program.as_deref()].into_iter().flatten();
+    let score = names.filter_map(|name| match_score(name, query)).max();
+    let Some(score) = score else {
+        return;
+    };
+    let score = score + source_bonus(source);
+    let key = executable.as_deref().unwrap_or(&target).to_lowercase();
+    match found.iter_mut().find(|seen| seen.executable.as_deref().unwrap_or(&seen.target).to_lowercase() == key) {
+        Some(seen) => {
+            if !seen.sources.contains(&source) {
+                seen.sources.push(source);
+            }
+            seen.score = seen.score.max(score);
+        }
+        None => found.push(Found { name, target, executable, sources: vec![source], score, icon }),
+    }
+}
+
+// How well `name` matches the lowercase `query`, or None for no match at all: the
+// whole name, its start, the start of each query word in one of its words, the
+// start of a word, anywhere, its words' initials, and last its letters in order
+fn match_score(name: &str, query: &str) -> Option<u32> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let name = name.to_lowercase();
+    let words: Vec<&str> = name.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).collect();
+    let query_words: Vec<&str> = query.split_whitespace().collect();
+    let initials: String = words.iter().filter_map(|word| word.chars().next()).collect();
+    let score = if name == query {
+        100
+    } else if name.starts_with(query) {
+        80
+    } else if query_words.len() > 1 && query_words.iter().all(|part| words.iter().any(|word| word.starts_with(part))) {
+        70
+    } else if words.iter().any(|word| word.starts_with(query)) {
+        60
+    } else if name.contains(query) {
+        40
+    } else if query.len() > 1 && initials.starts_with(query) {
+        30
+    } else if is_subsequence(query, &name) {
+        10
+    } else {
+        return None;
+    };
+    Some(score)
+}
+
+fn is_subsequence(query: &str, name: &str) -> bool {
+    let mut letters = name.chars();
+    query.chars().filter(|c| !c.is_whitespace()).all(|wanted| letters.any(|letter| letter == wanted))
+}
+
+// Between two equally good names, the one Explorer would offer first
+fn source_bonus(source: &str) -> u32 {
+    match source {
+        "startMenu" => 5,
+        "store" => 4,
+        "appPaths" => 3,
+        "windowsSearch" => 1,
+        _ => 0,
+    }
+}
+
+// The programs registered to run by name from the Run box, as their name and path;
+// each key is named after its program's file
+fn app_paths() -> Vec<(String, String)> {
+    let mut programs = Vec::new();
+    for (root, key) in APP_PATHS {
+        for file_name in subkey_names(root, &HSTRING::from(key)) {
+            let subkey = HSTRING::from(format!(r"{}\{}", key, file_name));
+            let Some(path) = read_string(root, &subkey, PCWSTR::null()) else {
+                continue;
+            };
+            let path = path.trim().trim_matches('"').to_string();
+            let name = Path::new(&file_name).file_stem().map(|stem| stem.to_string_lossy().into_owned());
+            if Path::new(&path).is_file() {
+                programs.push((name.unwrap_or(file_name), path));
+            }
+        }
+    }
+    programs
+}
+
+// What the Run box and a console would start by name: the PATHEXT programs in each
+// PATH folder, the first of a name winning as it does there
+fn path_programs() -> Vec<(String, String)> {
+    let extensions = env::var("PATHEXT").unwrap_or_else(|_| DEFAULT_PATHEXT.to_string());
+    let extensions: Vec<String> =
+        extensions.split(';').map(|extension| extension.trim().trim_start_matches('.').to_lowercase()).collect();
+    let mut programs: Vec<(String, String)> = Vec::new();
+    for folder in env::split_paths(&env::var_os("PATH").unwrap_or_default()) {
+        let Ok(entries) = std::fs::read_dir(&folder) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(extension) = path.extension().map(|extension| extension.to_string_lossy().to_lowercase()) else {
+                continue;
+            };
+            let Some(name) = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()) else {
+                continue;
+            };
+            if !extensions.contains(&extension)
+                || programs.iter().any(|(seen, _)| seen.eq_ignore_ascii_case(&name))
+                || !entry.file_type().is_ok_and(|kind| kind.is_file())
+            {
+                continue;
+            }
+            programs.push((name, path.to_string_lossy().into_owned()));
+        }
+    }
+    programs
+}
+
+// A search-ms: folder, which the shell fills from the index as it's enumerated; only
+// the first few hits are read, since a broad query can match thousands of files
+fn windows_search_hits(query: &str, limit: usize) -> Vec<(String, String)> {
+    let uri = format!("search-ms:query={}", encode_query(&format!("kind:program {}", query)));
+    let mut hits = Vec::new();
+    unsafe {
+        let Ok(folder) = SHCreateItemFromParsingName::<_, _, IShellItem>(&HSTRING::from(uri), None) else {
+            return hits;
+        };
+        let Ok(entries) = folder.BindToHandler::<_, IEnumShellItems>(None, &BHID_EnumItems) else {
+            return hits;
+        };
+        while hits.len() < limit {
+            let mut next = [None];
+            let mut fetched = 0;
+            if entries.Next(&mut next, Some(&mut fetched)).is_err() || fetched == 0 {
+                break;
+            }
+            let Some(item) = next[0].take() else {
+                break;
+            };
+            let Ok(path) = item.GetDisplayName(SIGDN_FILESYSPATH).map(|path| tool::take_string(path)) else {
+                continue;
+            };
+            if !PROGRAM_EXTENSIONS.iter().any(|extension| is_extension(&path, extension)) {
+                continue;
+            }
+            let Ok(name) = item.GetDisplayName(SIGDN_NORMALDISPLAY).map(|name| tool::take_string(name)) else {
+                continue;
+            };
+            hits.push((name, path));
+        }
+    }
+    hits
+}
+
+fn is_extension(path: &str, extension: &str) -> bool {
+    Path::new(path).extension().is_some_and(|found| found.eq_ignore_ascii_case(extension))
+}
+
+// Percent-encodes everything but letters and digits, as search-ms: expects
+fn encode_query(query: &str) -> String {
+    let mut encoded = String::new();
+    for byte in query.bytes() {
+        if byte.is_ascii_alphanumeric() {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
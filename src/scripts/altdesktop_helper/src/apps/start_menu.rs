@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use altdesktop_core::com::ComGuard;
+use create_shortcut::read::{read_shortcut, ShortcutInfo};
+use serde::Serialize;
+use windows::{
+    core::GUID,
+    Win32::UI::Shell::{FOLDERID_CommonPrograms, FOLDERID_Programs},
+};
+
+const ROOTS: [(&GUID, &str); 2] = [(&FOLDERID_Programs, "user"), (&FOLDERID_CommonPrograms, "common")];
+
+#[derive(Serialize)]
+pub struct Shortcut {
+    /// The file name without .lnk, which is what the Start Menu shows.
+    pub name: String,
+    pub path: String,
+    /// The subfolder it is in, relative to Programs, e.g. "Accessories"; "" at the top.
+    pub folder: String,
+    /// user for the current user's Start Menu, common for the one all users share.
+    pub scope: &'static str,
+    #[serde(flatten)]
+    pub info: ShortcutInfo,
+}
+
+/// Every shortcut in the current user's and the common Start Menu Programs folders.
+pub fn shortcuts() -> Vec<Shortcut> {
+    let mut found = Vec::new();
+    for (id, scope) in ROOTS {
+        // Either may be missing on a stripped-down install; the other is still worth listing
+        if let Ok(root) = create_shortcut::known_folders::known_folder_path(id) {
+            let root = PathBuf::from(root);
+            collect(&root, &root, scope, &mut found);
+        }
+    }
+
+    // Loading a link is mostly waiting on the disk, so the links are read in parallel,
+    // each thread in the MTA, where ShellLink is as happy as in an STA
+    let threads = thread::available_parallelism().map_or(1, |count| count.get()).min(found.len().max(1));
+    let chunk = found.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let workers: Vec<_> = found.chunks(chunk).map(|chunk| scope.spawn(|| read_all(chunk))).collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap_or_default()).collect()
+    })
+}
+
+/// Whether a shortcut starts a program, rather than an uninstaller, a document or
+/// a web page, for the app list.
+pub fn is_app(shortcut: &Shortcut) -> bool {
+    let target = shortcut.info.target_path.as_str();
+    let file_name = Path::new(target).file_name().map(|name| name.to_string_lossy().to_lowercase());
+    if shortcut.name.to_lowercase().contains("uninstall") || file_name.is_some_and(|name| name.starts_with("unins")) {
+        return false;
+    }
+    // Advertised and shell-namespace targets, such as ::{GUID} folders, have no extension
+    match Path::new(target).extension() {
+        Some(extension) => {
+            ["exe", "bat", "cmd", "msc", "cpl"].iter().any(|program| extension.eq_ignore_ascii_case(program))
+        }
+        None => true,
+    }
+}
+
+struct Found {
+    path: PathBuf,
+    folder: String,
+    scope: &'static str,
+}
+
+fn collect(root: &Path, folder: &Path, scope: &'static str, found: &mut Vec<Found>) {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            collect(root, &path, scope, found);
+        } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("lnk")) {
+            let folder = folder.strip_prefix(root).map(|folder| folder.to_string_lossy().into_owned()).unwrap_or_default();
+            found.push(Found { path, folder, scope });
+        }
+    }
+}
+
+fn read_all(found: &[Found]) -> Vec<Shortcut> {
+    let _com = ComGuard::multithreaded();
+    found
+        .iter()
+        .filter_map(|found| {
+            let path = found.path.to_string_lossy().into_owned();
+            // A link that won't load is broken for Explorer too, so it isn't listed
+            let info = read_shortcut(&path).ok()?;
+            Some(Shortcut {
+                name: found.path.file_stem()?.to_string_lossy().into_owned(),
+                path,
+                folder: found.folder.clone(),
+                scope: found.scope,
+                info,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use windows::{core::*, Win32::System::Registry::*};
+
+use super::{vdf, Game, IconHint};
+use crate::registry::read_string;
+
+// Steamworks Common Redistributables, which every library has and nobody plays
+const REDISTRIBUTABLES_APP_ID: &str = "228980";
+// An appmanifest StateFlags bit; still set while an update is waiting or downloading
+const FULLY_INSTALLED: u32 = 4;
+
+/// Where Steam is installed, as the current user's client last recorded it, or as
+/// its installer did.
+pub fn steam_path() -> Option<PathBuf> {
+    let path = read_string(HKEY_CURRENT_USER, &HSTRING::from(r"Software\Valve\Steam"), w!("SteamPath"))
+        .or_else(|| read_string(HKEY_LOCAL_MACHINE, &HSTRING::from(r"SOFTWARE\WOW6432Node\Valve\Steam"), w!("InstallPath")))?;
+    // SteamPath is written with forward slashes
+    let path = PathBuf::from(path.replace('/', "\\"));
+    path.is_dir().then_some(path)
+}
+
+/// The games installed in every Steam library; none without Steam.
+pub fn games() -> Vec<Game> {
+    let Some(steam) = steam_path() else {
+        return Vec::new();
+    };
+    libraries(&steam).iter().flat_map(|library| library_games(&steam, library)).collect()
+}
+
+// Steam's own folder is always a library, whether or not libraryfolders.vdf lists it
+fn libraries(steam: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![steam.to_path_buf()];
+    let listed = fs::read_to_string(steam.join(r"steamapps\libraryfolders.vdf")).ok();
+    let Some(root) = listed.as_deref().and_then(vdf::parse) else {
+        return libraries;
+    };
+    let Some(folders) = root.get("libraryfolders") else {
+        return libraries;
+    };
+    for (key, value) in folders.entries() {
+        // Older clients wrote "1" "D:\\SteamLibrary"; newer ones a block per library
+        // with its path and apps, next to settings such as contentstatsid
+        let path = match value {
+            vdf::Value::String(path) if key.parse::<u32>().is_ok() => Some(path.as_str()),
+            vdf::Value::Object(_) => value.string("path"),
+            vdf::Value::String(_) => None,
+        };
+        let Some(path) = path.map(PathBuf::from) else {
+            continue;
+        };
+        if !libraries.iter().any(|known| same_path(known, &path)) {
+            libraries.push(path);
+        }
+    }
+    libraries
+}
+
+fn library_games(steam: &Path, library: &Path) -> Vec<Game> {
+    let Ok(entries) = fs::read_dir(library.join("steamapps")) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            name.starts_with("appmanifest_") && name.ends_with(".acf")
+        })
+        .filter_map(|entry| read_manifest(steam, library, &entry.path()))
+        .collect()
+}
+
+fn read_manifest(steam: &Path, library: &Path, manifest: &Path) -> Option<Game> {
+    let text = fs::read_to_string(manifest).ok()?;
+    let root = vdf::parse(&text)?;
+    let state = root.get("AppState")?;
+    let app_id = state.string("appid")?;
+    let flags = state.string("StateFlags").and_then(|flags| flags.parse::<u32>().ok()).unwrap_or(0);
+    if app_id == REDISTRIBUTABLES_APP_ID || flags & FULLY_INSTALLED == 0 {
+        return None;
+    }
+    let install_dir = library.join(r"steamapps\common").join(state.string("installdir")?);
+    Some(Game {
+        launcher: "steam",
+        id: app_id.to_string(),
+        name: state.string("name").unwrap_or(app_id).to_string(),
+        install_dir: install_dir.to_string_lossy().into_owned(),
+        library: Some(library.to_string_lossy().into_owned()),
+        // Through Steam, for its overlay, updates and cloud saves
+        target: format!("steam://rungameid/{}", app_id),
+        arguments: None,
+        working_dir: None,
+        icon: library_icon(steam, app_id).map(|path| IconHint::File { path, index: 0 }),
+    })
+}
+
+// The small icon Steam shows in its library, a JPEG in its cache. Clients before
+// 2024 cached <appid>_icon.jpg; newer ones use a folder per app, where the icon is
+// named after its SHA-1 and the other art has fixed names
+fn library_icon(steam: &Path, app_id: &str) -> Option<String> {
+    let cache = steam.join(r"appcache\librarycache");
+    let flat = cache.join(format!("{}_icon.jpg", app_id));
+    if flat.is_file() {
+        return Some(flat.to_string_lossy().into_owned());
+    }
+    fs::read_dir(cache.join(app_id)).ok()?.flatten().map(|entry| entry.path()).find_map(|path| {
+        let stem = path.file_stem()?.to_str()?;
+        let is_jpeg = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("jpg"));
+        (is_jpeg && stem.len() == 40 && stem.bytes().all(|c| c.is_ascii_hexdigit())).then(|| path.to_string_lossy().into_owned())
+    })
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    let trimmed = |path: &Path| path.to_string_lossy().trim_end_matches('\\').to_lowercase();
+    trimmed(a) == trimmed(b)
+}
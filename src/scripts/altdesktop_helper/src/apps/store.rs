@@ -0,0 +1,42 @@
+use windows::{core::*, Win32::UI::Shell::*};
+
+use crate::tool;
+
+pub struct PackagedApp {
+    pub name: String,
+    /// PackageFamilyName!AppId, which shell:AppsFolder launches.
+    pub app_user_model_id: String,
+    pub family_name: String,
+}
+
+/// The current user's Store and other packaged apps, as Explorer's Applications
+/// folder (shell:AppsFolder) lists them.
+pub fn packaged_apps() -> Result<Vec<PackagedApp>> {
+    let mut apps = Vec::new();
+    unsafe {
+        let folder: IShellItem = SHGetKnownFolderItem(&FOLDERID_AppsFolder, KF_FLAG_DEFAULT, None)?;
+        let entries: IEnumShellItems = folder.BindToHandler(None, &BHID_EnumItems)?;
+        loop {
+            let mut next = [None];
+            let mut fetched = 0;
+            if entries.Next(&mut next, Some(&mut fetched)).is_err() || fetched == 0 {
+                break;
+            }
+            let Some(item) = next[0].take() else {
+                break;
+            };
+            // The folder lists desktop apps too, under their path or an explicit ID
+            let Ok(id) = item.GetDisplayName(SIGDN_PARENTRELATIVEPARSING).map(|id| tool::take_string(id)) else {
+                continue;
+            };
+            let Some((family_name, _)) = id.split_once('!') else {
+                continue;
+            };
+            let Ok(name) = item.GetDisplayName(SIGDN_NORMALDISPLAY).map(|name| tool::take_string(name)) else {
+                continue;
+            };
+            apps.push(PackagedApp { name, family_name: family_name.to_string(), app_user_model_id: id });
+        }
+    }
+    Ok(apps)
+}
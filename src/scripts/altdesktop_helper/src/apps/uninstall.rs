@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use windows::{core::*, Win32::System::Registry::*};
+
+use super::IconHint;
+use crate::registry::{read_dword, read_string};
+
+const UNINSTALL_KEY: PCWSTR = w!("SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Uninstall");
+const NAME_LENGTH: usize = 256;
+
+pub struct UninstallEntry {
+    pub name: String,
+    pub publisher: Option<String>,
+    pub version: Option<String>,
+    pub install_location: Option<String>,
+    pub icon: Option<IconHint>,
+    /// DisplayIcon, when it names a program that isn't the uninstaller.
+    pub executable: Option<String>,
+}
+
+/// The apps Programs and Features lists: machine-wide entries from both registry
+/// views and the current user's, without updates or system components.
+pub fn entries() -> Vec<UninstallEntry> {
+    let views = [
+        (HKEY_LOCAL_MACHINE, KEY_WOW64_64KEY),
+        (HKEY_LOCAL_MACHINE, KEY_WOW64_32KEY),
+        (HKEY_CURRENT_USER, REG_SAM_FLAGS(0)),
+    ];
+    let mut entries: Vec<UninstallEntry> = Vec::new();
+    for (root, view) in views {
+        let mut key = HKEY::default();
+        if unsafe { RegOpenKeyExW(root, UNINSTALL_KEY, 0, KEY_READ | view, &mut key) }.is_err() {
+            continue;
+        }
+        for index in 0.. {
+            let mut name = [0u16; NAME_LENGTH];
+            let mut length = NAME_LENGTH as u32;
+            let listed = unsafe {
+                RegEnumKeyExW(key, index, PWSTR(name.as_mut_ptr()), &mut length, None, PWSTR::null(), None, None)
+            };
+            if listed.is_err() {
+                break;
+            }
+            let subkey = HSTRING::from_wide(&name[..length as usize]).unwrap_or_default();
+            if let Some(entry) = read_entry(key, &subkey) {
+                // 32-bit installers sometimes register in both views
+                if !entries.iter().any(|seen| seen.name == entry.name) {
+                    entries.push(entry);
+                }
+            }
+        }
+        unsafe {
+            let _ = RegCloseKey(key);
+        }
+    }
+    entries
+}
+
+fn read_entry(key: HKEY, subkey: &HSTRING) -> Option<UninstallEntry> {
+    let name = read_string(key, subkey, w!("DisplayName"))?;
+    let hidden = read_dword(key, subkey, w!("SystemComponent")) == Some(1)
+        || read_string(key, subkey, w!("ParentKeyName")).is_some()
+        || read_string(key, subkey, w!("ReleaseType")).is_some();
+    if hidden {
+        return None;
+    }
+
+    let icon = read_string(key, subkey, w!("DisplayIcon")).and_then(|value| IconHint::parse(&value));
+    let executable = match &icon {
+        Some(IconHint::File { path, .. }) if is_launchable(path) => Some(path.clone()),
+        _ => None,
+    };
+    Some(UninstallEntry {
+        name,
+        publisher: read_string(key, subkey, w!("Publisher")),
+        version: read_string(key, subkey, w!("DisplayVersion")),
+        install_location: read_string(key, subkey, w!("InstallLocation")).map(|folder| folder.trim_matches('"').to_string()),
+        icon,
+        executable,
+    })
+}
+
+fn is_launchable(path: &str) -> bool {
+    let path = Path::new(path);
+    let is_exe = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("exe"));
+    let file_name = path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    is_exe && !file_name.starts_with("unins") && !file_name.contains("setup") && path.exists()
+}
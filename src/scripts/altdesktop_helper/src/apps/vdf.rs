@@ -0,0 +1,109 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+// Valve's text KeyValues format, which Steam's libraryfolders.vdf and appmanifest
+// .acf files are written in: quoted keys, each followed by a quoted value or by a
+// block of further pairs in braces.
+
+pub enum Value {
+    String(String),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The value of `key` in this block; Steam doesn't keep to one case for keys.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries().iter().find(|(name, _)| name.eq_ignore_ascii_case(key)).map(|(_, value)| value)
+    }
+
+    pub fn string(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            Value::String(value) => Some(value),
+            Value::Object(_) => None,
+        }
+    }
+
+    /// The pairs of a block, in file order; none for a string.
+    pub fn entries(&self) -> &[(String, Value)] {
+        match self {
+            Value::Object(entries) => entries,
+            Value::String(_) => &[],
+        }
+    }
+}
+
+enum Token {
+    Text(String),
+    Open,
+    Close,
+}
+
+/// Parses a whole file into a block of its top-level pairs; None when it is
+/// malformed, such as one cut short while Steam was writing it.
+pub fn parse(text: &str) -> Option<Value> {
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    parse_block(&mut chars, false).map(Value::Object)
+}
+
+fn parse_block(chars: &mut Peekable<Chars>, nested: bool) -> Option<Vec<(String, Value)>> {
+    let mut entries = Vec::new();
+    loop {
+        let key = match next_token(chars) {
+            None => return (!nested).then_some(entries),
+            Some(Token::Close) => return nested.then_some(entries),
+            Some(Token::Open) => return None,
+            Some(Token::Text(key)) => key,
+        };
+        let value = match next_token(chars)? {
+            Token::Text(value) => Value::String(value),
+            Token::Open => Value::Object(parse_block(chars, true)?),
+            Token::Close => return None,
+        };
+        entries.push((key, value));
+    }
+}
+
+fn next_token(chars: &mut Peekable<Chars>) -> Option<Token> {
+    loop {
+        match chars.next()? {
+            c if c.is_whitespace() => {}
+            '{' => return Some(Token::Open),
+            '}' => return Some(Token::Close),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => return Some(Token::Text(text)),
+                        '\\' => match chars.next()? {
+                            'n' => text.push('\n'),
+                            't' => text.push('\t'),
+                            other => text.push(other),
+                        },
+                        c => text.push(c),
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            first => {
+                let mut text = first.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '{' | '}' | '"') {
+                        break;
+                    }
+                    text.push(c);
+                    chars.next();
+                }
+                // A platform condition such as [$WIN32] after a value
+                if !(text.starts_with('[') && text.ends_with(']')) {
+                    return Some(Token::Text(text));
+                }
+            }
+        }
+    }
+}
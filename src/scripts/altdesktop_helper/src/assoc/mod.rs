@@ -0,0 +1,45 @@
+#[cfg(windows)]
+mod open_with;
+#[cfg(windows)]
+mod query;
+
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper assoc query <.ext>
+  altdesktop-helper assoc open-with <path> [--all]
+  altdesktop-helper assoc open-with <path> --handler <name>
+
+query prints what Explorer knows about a file type: {\"query\",\"progId\",\"typeName\",
+\"contentType\",\"icon\",\"perFileIcon\",\"application\"}. icon is the type's icon as
+{\"path\",\"index\"} for `icon extract`, or null with perFileIcon true when each file
+supplies its own, as .exe and .ico files do. application is the default app that
+opens the type, {\"name\",\"executable\",\"command\",\"appUserModelId\",\"icon\"}, or null
+when nothing is registered. <.ext> may also be a ProgID such as txtfile or a URL
+protocol such as steam.
+
+open-with prints the apps Explorer recommends under Open with for <path>, or every
+registered one with --all, as a JSON array of
+{\"name\",\"displayName\",\"icon\",\"recommended\"}. --handler opens <path> with the app
+of that name and prints {\"ok\":true}. <path> may start with a known folder token
+such as {Desktop}.";
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--open-with") => open_with::run(&args),
+        _ => query::run(&args),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("assoc")
+}
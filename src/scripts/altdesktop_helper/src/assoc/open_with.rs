@@ -0,0 +1,147 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Foundation::{E_INVALIDARG, ERROR_NOT_FOUND, S_OK},
+    Win32::System::Com::IDataObject,
+    Win32::UI::Shell::*,
+};
+
+use super::USAGE;
+use crate::apps::IconHint;
+use crate::tool;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Handler {
+    /// What --handler takes: the app's executable path or Store app ID.
+    name: String,
+    display_name: String,
+    icon: Option<IconHint>,
+    recommended: bool,
+}
+
+struct Options {
+    path: String,
+    all: bool,
+    handler: Option<String>,
+}
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let path = match resolve_path(&options.path) {
+        Ok(path) => path,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    match &options.handler {
+        Some(handler) => tool::finish(open_with(&path, handler).map(|()| tool::DONE)),
+        None => tool::finish(list_handlers(&path, options.all)),
+    }
+}
+
+fn parse_args(args: &[String]) -> std::result::Result<Options, String> {
+    let mut all = false;
+    let mut handler = None;
+    let mut positional = Vec::new();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--open-with" => {}
+            "--all" => all = true,
+            "--handler" => handler = Some(value()?),
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+    if all && handler.is_some() {
+        return Err("--all cannot be combined with --handler".to_string());
+    }
+    match positional.as_slice() {
+        [path] => Ok(Options { path: path.clone(), all, handler }),
+        _ => Err("Expected <path>".to_string()),
+    }
+}
+
+fn resolve_path(path: &str) -> Result<PathBuf> {
+    let path = create_shortcut::known_folders::expand_known_folder(path)?;
+    let path = std::path::absolute(&path).map_err(tool::io_error)?;
+    std::fs::metadata(&path).map_err(tool::io_error)?;
+    Ok(path)
+}
+
+// Handlers are registered per extension, so the file itself only matters to Invoke
+fn handlers(path: &Path, all: bool) -> Result<Vec<IAssocHandler>> {
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .ok_or_else(|| Error::new(E_INVALIDARG, format!("{} has no extension", path.display()).into()))?;
+    let filter = if all { ASSOC_FILTER_NONE } else { ASSOC_FILTER_RECOMMENDED };
+    let mut handlers = Vec::new();
+    unsafe {
+        let entries = SHAssocEnumHandlers(&HSTRING::from(extension), filter)?;
+        loop {
+            let mut next = [None];
+            let mut fetched = 0;
+            if entries.Next(&mut next, Some(&mut fetched)).is_err() || fetched == 0 {
+                break;
+            }
+            handlers.extend(next[0].take());
+        }
+    }
+    Ok(handlers)
+}
+
+/// The apps Explorer offers under Open with for `path`; `all` adds the ones it
+/// hides behind "Choose another app".
+fn list_handlers(path: &Path, all: bool) -> Result<Vec<Handler>> {
+    let mut listed = Vec::new();
+    for handler in handlers(path, all)? {
+        unsafe {
+            let Ok(name) = handler.GetName().map(|name| tool::take_string(name)) else {
+                continue;
+            };
+            let display_name = handler.GetUIName().map(|name| tool::take_string(name)).unwrap_or_else(|_| name.clone());
+            let icon = match name.split_once('!') {
+                // A Store app, whose icon location is a resource reference
+                Some((family, _)) => Some(IconHint::Package { package: family.to_string() }),
+                None => {
+                    let mut location = PWSTR::null();
+                    let mut index = 0;
+                    match handler.GetIconLocation(&mut location, &mut index) {
+                        Ok(()) => Some(tool::take_string(location))
+                            .filter(|location| !location.is_empty())
+                            .map(|path| IconHint::File { path, index }),
+                        Err(_) => None,
+                    }
+                }
+            };
+            // Not recommended is S_FALSE, a success code
+            let recommended = handler.IsRecommended() == S_OK;
+            listed.push(Handler { name, display_name, icon, recommended });
+        }
+    }
+    Ok(listed)
+}
+
+fn open_with(path: &Path, name: &str) -> Result<()> {
+    // Any handler the type has may be chosen, not just the recommended ones
+    let handler = handlers(path, true)?
+        .into_iter()
+        .find(|handler| {
+            unsafe { handler.GetName() }.is_ok_and(|found| unsafe { tool::take_string(found) }.eq_ignore_ascii_case(name))
+        })
+        .ok_or_else(|| Error::new(ERROR_NOT_FOUND.to_hresult(), format!("No handler named {} opens this file", name).into()))?;
+    unsafe {
+        let item: IShellItem = SHCreateItemFromParsingName(&HSTRING::from(path), None)?;
+        let data: IDataObject = item.BindToHandler(None, &BHID_DataObject)?;
+        handler.Invoke(&data)
+    }
+}
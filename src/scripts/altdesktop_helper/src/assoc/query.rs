@@ -0,0 +1,82 @@
+use std::process::ExitCode;
+
+use serde::Serialize;
+use windows::{core::*, Win32::UI::Shell::*};
+
+use super::USAGE;
+use crate::apps::IconHint;
+use crate::tool;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Association {
+    query: String,
+    prog_id: Option<String>,
+    type_name: Option<String>,
+    content_type: Option<String>,
+    icon: Option<IconHint>,
+    per_file_icon: bool,
+    application: Option<Application>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Application {
+    name: Option<String>,
+    executable: Option<String>,
+    command: Option<String>,
+    /// Set when the default app is a Store app, which has no executable to show.
+    app_user_model_id: Option<String>,
+    icon: Option<IconHint>,
+}
+
+pub fn run(args: &[String]) -> ExitCode {
+    match args {
+        [_, query] if !query.starts_with("--") => tool::finish(Ok(query_association(query))),
+        _ => tool::usage_error("Expected <.ext>", USAGE),
+    }
+}
+
+fn query_association(query: &str) -> Association {
+    let assoc = HSTRING::from(query);
+    // Without INIT_IGNOREUNKNOWN an unregistered type answers as the catch-all
+    // "Unknown" class, which is right for the type name but not for anything else
+    let registered = |kind| association_string(ASSOCF_INIT_IGNOREUNKNOWN, kind, &assoc);
+
+    let default_icon = registered(ASSOCSTR_DEFAULTICON);
+    let executable = registered(ASSOCSTR_EXECUTABLE);
+    let app_user_model_id = registered(ASSOCSTR_APPID);
+    let command = registered(ASSOCSTR_COMMAND);
+
+    let application = if executable.is_some() || app_user_model_id.is_some() || command.is_some() {
+        let icon = match app_user_model_id.as_deref().and_then(|id| id.split_once('!')) {
+            Some((family, _)) => Some(IconHint::Package { package: family.to_string() }),
+            None => executable.as_deref().and_then(IconHint::parse),
+        };
+        Some(Application { name: registered(ASSOCSTR_FRIENDLYAPPNAME), executable, command, app_user_model_id, icon })
+    } else {
+        None
+    };
+
+    Association {
+        query: query.to_string(),
+        prog_id: registered(ASSOCSTR_PROGID),
+        type_name: association_string(ASSOCF_NONE, ASSOCSTR_FRIENDLYDOCNAME, &assoc),
+        content_type: registered(ASSOCSTR_CONTENTTYPE),
+        per_file_icon: default_icon.as_deref().is_some_and(|icon| icon.trim().trim_matches('"') == "%1"),
+        icon: default_icon.as_deref().and_then(IconHint::parse),
+        application,
+    }
+}
+
+// None when the type doesn't have this string, which AssocQueryString reports with
+// a different error depending on where the lookup stopped
+fn association_string(flags: ASSOCF, kind: ASSOCSTR, assoc: &HSTRING) -> Option<String> {
+    let mut length = 0u32;
+    // Reports the length, including the terminator, with S_FALSE
+    unsafe { AssocQueryStringW(flags, kind, assoc, PCWSTR::null(), PWSTR::null(), &mut length).ok().ok()? };
+    let mut buffer = vec![0u16; length as usize];
+    unsafe { AssocQueryStringW(flags, kind, assoc, PCWSTR::null(), PWSTR(buffer.as_mut_ptr()), &mut length).ok().ok()? };
+    let length = buffer.iter().position(|&unit| unit == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..length])).filter(|value| !value.is_empty())
+}
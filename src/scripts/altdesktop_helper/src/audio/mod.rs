@@ -0,0 +1,195 @@
+#[cfg(windows)]
+mod policy;
+
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Devices::FunctionDiscovery::PKEY_Device_FriendlyName,
+    Win32::Foundation::ERROR_NOT_FOUND,
+    Win32::Media::Audio::Endpoints::IAudioEndpointVolume,
+    Win32::Media::Audio::*,
+    Win32::System::Com::StructuredStorage::{PropVariantClear, PropVariantToStringAlloc},
+    Win32::System::Com::{CoCreateInstance, CLSCTX_ALL, STGM_READ},
+    Win32::System::Variant::VT_EMPTY,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper audio list
+  altdesktop-helper audio set [--device <id>] [--volume <0-100>] [--mute | --unmute]
+  altdesktop-helper audio set-default <id> [--communications]
+
+list prints every active playback device as {\"id\",\"name\",\"default\",
+\"defaultCommunications\",\"volume\",\"muted\"}, volume a percentage as the volume
+flyout shows it.
+set changes the master volume or mute of the device with <id>, or of the default
+device, and prints that device as list does.
+set-default makes <id> the default playback device, as Sound settings does, or with
+--communications the device calls use instead, and prints {\"ok\":true}.";
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Device {
+    id: String,
+    name: String,
+    default: bool,
+    default_communications: bool,
+    volume: u32,
+    muted: bool,
+}
+
+#[cfg(windows)]
+struct Change {
+    device: Option<String>,
+    volume: Option<u32>,
+    mute: Option<bool>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--set") => match parse_change(&args[2..]) {
+            Ok(change) => tool::finish(set(&change)),
+            Err(message) => tool::usage_error(&message, USAGE),
+        },
+        Some("--set-default") => match &args[2..] {
+            [id] if !id.starts_with("--") => tool::finish(policy::set_default(id, false).map(|()| tool::DONE)),
+            [id, flag] if flag == "--communications" => tool::finish(policy::set_default(id, true).map(|()| tool::DONE)),
+            _ => tool::usage_error("Expected <id> [--communications]", USAGE),
+        },
+        None => tool::finish(list_devices()),
+        Some(_) => tool::usage_error("list takes no arguments", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("audio")
+}
+
+#[cfg(windows)]
+fn parse_change(args: &[String]) -> std::result::Result<Change, String> {
+    let mut change = Change { device: None, volume: None, mute: None };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--device" => change.device = Some(value()?),
+            "--volume" => {
+                let volume = value()?;
+                change.volume = match volume.parse::<u32>() {
+                    Ok(volume @ 0..=100) => Some(volume),
+                    _ => return Err(format!("Invalid --volume: {} (expected 0 to 100)", volume)),
+                }
+            }
+            "--mute" => change.mute = Some(true),
+            "--unmute" => change.mute = Some(false),
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    if change.volume.is_none() && change.mute.is_none() {
+        return Err("Expected --volume, --mute or --unmute".to_string());
+    }
+    Ok(change)
+}
+
+#[cfg(windows)]
+fn enumerator() -> Result<IMMDeviceEnumerator> {
+    unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL) }
+}
+
+// None when there is no playback device at all
+#[cfg(windows)]
+fn default_id(enumerator: &IMMDeviceEnumerator, role: ERole) -> Option<String> {
+    unsafe { enumerator.GetDefaultAudioEndpoint(eRender, role).and_then(|device| device.GetId()) }
+        .ok()
+        .map(|id| unsafe { tool::take_string(id) })
+}
+
+#[cfg(windows)]
+fn list_devices() -> Result<Vec<Device>> {
+    let enumerator = enumerator()?;
+    let default = default_id(&enumerator, eConsole);
+    let communications = default_id(&enumerator, eCommunications);
+    let mut devices = Vec::new();
+    unsafe {
+        let collection = enumerator.EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)?;
+        for index in 0..collection.GetCount()? {
+            let device = collection.Item(index)?;
+            devices.push(describe(&device, default.as_deref(), communications.as_deref())?);
+        }
+    }
+    Ok(devices)
+}
+
+#[cfg(windows)]
+fn describe(device: &IMMDevice, default: Option<&str>, communications: Option<&str>) -> Result<Device> {
+    unsafe {
+        let id = tool::take_string(device.GetId()?);
+        let volume: IAudioEndpointVolume = device.Activate(CLSCTX_ALL, None)?;
+        Ok(Device {
+            name: friendly_name(device).unwrap_or_else(|| id.clone()),
+            default: default == Some(id.as_str()),
+            default_communications: communications == Some(id.as_str()),
+            volume: (volume.GetMasterVolumeLevelScalar()? * 100.0).round() as u32,
+            muted: volume.GetMute()?.as_bool(),
+            id,
+        })
+    }
+}
+
+// "Speakers (Realtek High Definition Audio)", as Sound settings lists it
+#[cfg(windows)]
+fn friendly_name(device: &IMMDevice) -> Option<String> {
+    unsafe {
+        let store = device.OpenPropertyStore(STGM_READ).ok()?;
+        let mut variant = store.GetValue(&PKEY_Device_FriendlyName).ok()?;
+        let name = if variant.Anonymous.Anonymous.vt == VT_EMPTY {
+            None
+        } else {
+            PropVariantToStringAlloc(&variant).ok().map(|text| tool::take_string(text))
+        };
+        let _ = PropVariantClear(&mut variant);
+        name
+    }
+}
+
+#[cfg(windows)]
+fn set(change: &Change) -> Result<Device> {
+    let enumerator = enumerator()?;
+    let device = unsafe {
+        match &change.device {
+            Some(id) => enumerator.GetDevice(&HSTRING::from(id)).map_err(|error| {
+                if error.code() == ERROR_NOT_FOUND.to_hresult() {
+                    Error::new(error.code(), format!("No audio device with id {}", id).into())
+                } else {
+                    error
+                }
+            })?,
+            None => enumerator.GetDefaultAudioEndpoint(eRender, eConsole)?,
+        }
+    };
+    unsafe {
+        let volume: IAudioEndpointVolume = device.Activate(CLSCTX_ALL, None)?;
+        if let Some(level) = change.volume {
+            volume.SetMasterVolumeLevelScalar(level as f32 / 100.0, std::ptr::null())?;
+        }
+        if let Some(mute) = change.mute {
+            volume.SetMute(mute, std::ptr::null())?;
+        }
+    }
+    describe(&device, default_id(&enumerator, eConsole).as_deref(), default_id(&enumerator, eCommunications).as_deref())
+}
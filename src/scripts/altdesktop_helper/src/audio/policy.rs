@@ -0,0 +1,36 @@
+use std::ffi::c_void;
+
+use windows::{
+    core::*,
+    Win32::Media::Audio::{eCommunications, eConsole, eMultimedia, ERole},
+    Win32::System::Com::{CoCreateInstance, CLSCTX_ALL},
+};
+
+// IPolicyConfig is how Sound settings changes the default device. It is
+// undocumented, so it is called through its vtable: IUnknown's three methods, then
+// ten others, then SetDefaultEndpoint. The layout has held since Windows 7.
+const CPOLICY_CONFIG_CLIENT: GUID = GUID::from_u128(0x870af99c_171d_4f9e_af0d_e63df40c2bc9);
+const IID_POLICY_CONFIG: GUID = GUID::from_u128(0xf8679f50_850a_41cf_9c72_430f290290c8);
+const SET_DEFAULT_ENDPOINT: usize = 13;
+
+type SetDefaultEndpoint = unsafe extern "system" fn(this: *mut c_void, id: PCWSTR, role: ERole) -> HRESULT;
+
+/// Makes the device with `id` the default for media and games, or with
+/// `communications` for calls.
+pub fn set_default(id: &str, communications: bool) -> Result<()> {
+    let roles: &[ERole] = if communications { &[eCommunications] } else { &[eConsole, eMultimedia] };
+    let id = HSTRING::from(id);
+    unsafe {
+        let client: IUnknown = CoCreateInstance(&CPOLICY_CONFIG_CLIENT, None, CLSCTX_ALL)?;
+        let mut config = std::ptr::null_mut();
+        client.query(&IID_POLICY_CONFIG, &mut config).ok()?;
+        let config = IUnknown::from_raw(config);
+
+        let vtable = *(config.as_raw() as *const *const usize);
+        let set_default_endpoint: SetDefaultEndpoint = std::mem::transmute(*vtable.add(SET_DEFAULT_ENDPOINT));
+        for &role in roles {
+            set_default_endpoint(config.as_raw(), PCWSTR(id.as_ptr()), role).ok()?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use std::env;
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+use serde::Serialize;
+
+use crate::{serve, tool, TOOLS};
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper capabilities
+
+Prints what this build of the helper can do, so the app can tell an older or
+platform-limited helper from a broken one and leave out what it lacks:
+{\"version\",\"protocolVersion\",\"platform\",\"arch\",\"tools\",\"methods\",\"features\"}.
+protocolVersion goes up whenever a --serve method or a tool's JSON changes in a way
+an app written for the previous one can't read. tools lists every tool, as
+{\"name\",\"commands\",\"supported\"}: its commands, the first being the one run
+without a flag, and whether it works on this platform rather than failing as
+unsupported. methods are the --serve methods and features the optional parts of
+the helper, each true or false; a feature an older helper doesn't list is one it
+doesn't have:
+  serve          --serve over stdin
+  pipe           --serve --pipe
+  elevate        --elevate
+  cancel         the cancel method, and cancelling icon batch jobs
+  priority       the priority of --serve requests
+  sharedMemory   icon.extract's sharedMemory
+  progress       --progress of icon and shortcut batch
+  config         --config
+  crashDumps     a minidump next to each crash record
+  outputRoots    --allowed-output-root
+  stockIcons     icon extract --stock, and stock in icon batch jobs
+Also answered by the capabilities method of --serve.";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    version: &'static str,
+    protocol_version: u32,
+    platform: &'static str,
+    arch: &'static str,
+    tools: Vec<ToolInfo>,
+    methods: &'static [&'static str],
+    features: Features,
+}
+
+#[derive(Serialize)]
+struct ToolInfo {
+    name: &'static str,
+    commands: Vec<&'static str>,
+    supported: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Features {
+    serve: bool,
+    pipe: bool,
+    elevate: bool,
+    cancel: bool,
+    priority: bool,
+    shared_memory: bool,
+    progress: bool,
+    config: bool,
+    crash_dumps: bool,
+    output_roots: bool,
+    stock_icons: bool,
+}
+
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    if args.len() > 1 {
+        return tool::usage_error(&format!("Unknown option: {}", args[1].to_string_lossy()), USAGE);
+    }
+    altdesktop_core::json::print(capabilities());
+    ExitCode::SUCCESS
+}
+
+pub fn capabilities() -> Capabilities {
+    let tools = TOOLS
+        .iter()
+        .map(|(name, tool)| ToolInfo {
+            name,
+            commands: tool.default_command.into_iter().chain(tool.commands.iter().copied()).collect(),
+            supported: cfg!(windows) || tool.portable,
+        })
+        .collect();
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        protocol_version: serve::PROTOCOL_VERSION,
+        platform: env::consts::OS,
+        arch: env::consts::ARCH,
+        tools,
+        methods: serve::METHODS,
+        features: Features {
+            serve: true,
+            pipe: cfg!(windows),
+            elevate: cfg!(windows),
+            cancel: true,
+            priority: true,
+            shared_memory: cfg!(windows),
+            progress: true,
+            config: true,
+            crash_dumps: cfg!(windows),
+            output_roots: true,
+            stock_icons: cfg!(windows),
+        },
+    }
+}
@@ -0,0 +1,377 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::path::PathBuf;
+
+#[cfg(windows)]
+use image::{ImageFormat, RgbImage};
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{E_FAIL, E_INVALIDARG, HMODULE, HWND, POINT, RECT},
+    Win32::Graphics::Direct3D::D3D_DRIVER_TYPE_UNKNOWN,
+    Win32::Graphics::Direct3D11::*,
+    Win32::Graphics::Dxgi::Common::{DXGI_MODE_ROTATION_IDENTITY, DXGI_MODE_ROTATION_UNSPECIFIED},
+    Win32::Graphics::Dxgi::*,
+    Win32::Graphics::Gdi::*,
+    Win32::Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS},
+    Win32::UI::WindowsAndMessaging::{GetWindowRect, IsIconic},
+};
+
+#[cfg(windows)]
+use crate::desktop::parse_hwnd;
+#[cfg(windows)]
+use crate::monitors::{list_monitors, Rect};
+use crate::tool;
+#[cfg(windows)]
+use crate::window::{check_window, visible_frame};
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper capture monitor [<monitor>] <output.png>
+  altdesktop-helper capture window <hwnd> <output.png>
+  altdesktop-helper capture region --x <x> --y <y> --width <width> --height <height> <output.png>
+
+Saves what is on screen to a PNG and prints {\"path\",\"width\",\"height\",\"bounds\",
+\"method\"}, bounds being the area captured in physical pixels on the virtual screen.
+monitor captures a whole display, the primary one unless <monitor> gives its index
+or device name as monitors list prints them. region captures a rectangle of the
+virtual screen, which may span displays. Both read the display's frame through DXGI
+desktop duplication (method duplication) where they can, and copy it out of GDI's
+screen instead (method gdi) for a rotated display, a region across displays, or
+where duplication isn't available, as over some remote desktop sessions. window
+asks <hwnd> to draw itself (method printWindow), so windows in front of it don't
+show, cropped to its visible frame; it can't be minimized. <hwnd> is decimal or
+0x-prefixed hex.";
+
+// How long to wait for the display to present a frame. Duplication hands over the
+// whole desktop on its first frame, but only once something is drawn
+#[cfg(windows)]
+const FRAME_TIMEOUT_MS: u32 = 500;
+// PrintWindow's PW_RENDERFULLCONTENT, without which windows drawn with DirectX or
+// DirectComposition come out black
+#[cfg(windows)]
+const PW_RENDERFULLCONTENT: u32 = 2;
+
+#[cfg(windows)]
+enum Command {
+    Monitor { monitor: Option<String>, output: String },
+    Window { hwnd: HWND, output: String },
+    Region { bounds: RECT, output: String },
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct Capture {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    bounds: Rect,
+    method: &'static str,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match parse_args(&args) {
+        Ok(command) => tool::finish(capture(command)),
+        Err(message) => tool::usage_error(&message, USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("capture")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<Command, String> {
+    match args.get(1).map(String::as_str) {
+        Some("--window") => match &args[2..] {
+            [hwnd, output] => Ok(Command::Window { hwnd: parse_hwnd(hwnd)?, output: output.clone() }),
+            _ => Err("Expected <hwnd> <output.png>".to_string()),
+        },
+        Some("--region") => parse_region(&args[2..]),
+        _ => match &args[1..] {
+            [output] if !output.starts_with("--") => Ok(Command::Monitor { monitor: None, output: output.clone() }),
+            [monitor, output] if !monitor.starts_with("--") => {
+                Ok(Command::Monitor { monitor: Some(monitor.clone()), output: output.clone() })
+            }
+            _ => Err("Expected [<monitor>] <output.png>".to_string()),
+        },
+    }
+}
+
+#[cfg(windows)]
+fn parse_region(args: &[String]) -> std::result::Result<Command, String> {
+    let mut x = None;
+    let mut y = None;
+    let mut width = None;
+    let mut height = None;
+    let mut output = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || {
+            let value = iter.next().ok_or_else(|| format!("{} requires a value", flag))?;
+            value.parse::<i32>().map_err(|_| format!("Invalid {}: {}", flag, value))
+        };
+        match flag {
+            "--x" => x = Some(value()?),
+            "--y" => y = Some(value()?),
+            "--width" => width = Some(value()?),
+            "--height" => height = Some(value()?),
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ if output.is_none() => output = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    let (Some(x), Some(y), Some(width), Some(height)) = (x, y, width, height) else {
+        return Err("region requires --x, --y, --width and --height".to_string());
+    };
+    if width <= 0 || height <= 0 {
+        return Err("--width and --height must be positive".to_string());
+    }
+    let output = output.ok_or_else(|| "Expected <output.png>".to_string())?;
+    Ok(Command::Region { bounds: RECT { left: x, top: y, right: x + width, bottom: y + height }, output })
+}
+
+#[cfg(windows)]
+fn capture(command: Command) -> Result<Capture> {
+    let (image, bounds, method, output) = match command {
+        Command::Monitor { monitor, output } => {
+            let bounds = monitor_bounds(monitor.as_deref())?;
+            let (image, method) = capture_screen(bounds)?;
+            (image, bounds, method, output)
+        }
+        Command::Region { bounds, output } => {
+            let (image, method) = capture_screen(bounds)?;
+            (image, bounds, method, output)
+        }
+        Command::Window { hwnd, output } => {
+            let (image, bounds) = capture_window(hwnd)?;
+            (image, bounds, "printWindow", output)
+        }
+    };
+    let path = std::path::absolute(&output).map_err(tool::io_error)?;
+    altdesktop_core::sandbox::check_output(&path)?;
+    image.save_with_format(&path, ImageFormat::Png).map_err(|error| Error::new(E_FAIL, error.to_string().into()))?;
+    Ok(Capture { path, width: image.width(), height: image.height(), bounds: bounds.into(), method })
+}
+
+#[cfg(windows)]
+fn monitor_bounds(monitor: Option<&str>) -> Result<RECT> {
+    let monitors = list_monitors()?;
+    let found = match monitor {
+        None => monitors.iter().find(|found| found.primary),
+        Some(name) => monitors
+            .iter()
+            .find(|found| name.parse() == Ok(found.index) || found.device_name.eq_ignore_ascii_case(name)),
+    };
+    let found = found.ok_or_else(|| {
+        Error::new(E_INVALIDARG, format!("No monitor {}", monitor.unwrap_or("is the primary one")).into())
+    })?;
+    let Rect { x, y, width, height } = &found.bounds;
+    Ok(RECT { left: *x, top: *y, right: x + width, bottom: y + height })
+}
+
+// Duplication sees one display at a time, so a region is cropped out of the frame
+// of the display that holds all of it
+#[cfg(windows)]
+fn capture_screen(bounds: RECT) -> Result<(RgbImage, &'static str)> {
+    let monitor = unsafe { MonitorFromPoint(POINT { x: bounds.left, y: bounds.top }, MONITOR_DEFAULTTONULL) };
+    let mut info = MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+    let contained = !monitor.is_invalid()
+        && unsafe { GetMonitorInfoW(monitor, &mut info) }.as_bool()
+        && bounds.left >= info.rcMonitor.left
+        && bounds.top >= info.rcMonitor.top
+        && bounds.right <= info.rcMonitor.right
+        && bounds.bottom <= info.rcMonitor.bottom;
+    if contained && let Ok(Some(frame)) = duplicate_frame(monitor) {
+        let (x, y) = ((bounds.left - info.rcMonitor.left) as u32, (bounds.top - info.rcMonitor.top) as u32);
+        let (width, height) = ((bounds.right - bounds.left) as u32, (bounds.bottom - bounds.top) as u32);
+        let image = image::imageops::crop_imm(&frame, x, y, width, height).to_image();
+        return Ok((image, "duplication"));
+    }
+    Ok((capture_gdi(bounds)?, "gdi"))
+}
+
+// None when the display is rotated, whose frames come in its unrotated orientation
+#[cfg(windows)]
+fn duplicate_frame(monitor: HMONITOR) -> Result<Option<RgbImage>> {
+    let Some((adapter, output)) = find_output(monitor)? else {
+        return Ok(None);
+    };
+    let mut description = DXGI_OUTPUT_DESC::default();
+    unsafe { output.GetDesc(&mut description)? };
+    if description.Rotation != DXGI_MODE_ROTATION_IDENTITY && description.Rotation != DXGI_MODE_ROTATION_UNSPECIFIED {
+        return Ok(None);
+    }
+
+    unsafe {
+        let mut device = None;
+        let mut context = None;
+        D3D11CreateDevice(
+            &adapter,
+            D3D_DRIVER_TYPE_UNKNOWN,
+            HMODULE::default(),
+            D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+            None,
+            D3D11_SDK_VERSION,
+            Some(&mut device),
+            None,
+            Some(&mut context),
+        )?;
+        let (Some(device), Some(context)) = (device, context) else {
+            return Err(Error::new(E_FAIL, "Direct3D didn't create a device".into()));
+        };
+        let duplication = output.cast::<IDXGIOutput1>()?.DuplicateOutput(&device)?;
+
+        let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
+        let mut resource = None;
+        duplication.AcquireNextFrame(FRAME_TIMEOUT_MS, &mut frame_info, &mut resource)?;
+        let result = (|| {
+            let texture = resource
+                .as_ref()
+                .ok_or_else(|| Error::new(E_FAIL, "The display presented no frame".into()))?
+                .cast::<ID3D11Texture2D>()?;
+            let mut desc = D3D11_TEXTURE2D_DESC::default();
+            texture.GetDesc(&mut desc);
+            // The frame lives on the GPU; a staging copy is what the CPU may read
+            desc.Usage = D3D11_USAGE_STAGING;
+            desc.BindFlags = 0;
+            desc.CPUAccessFlags = D3D11_CPU_ACCESS_READ.0 as u32;
+            desc.MiscFlags = 0;
+            let mut staging = None;
+            device.CreateTexture2D(&desc, None, Some(&mut staging))?;
+            let staging = staging.ok_or_else(|| Error::new(E_FAIL, "Direct3D didn't create a texture".into()))?;
+            context.CopyResource(&staging, &texture);
+
+            let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+            context.Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))?;
+            let mut image = RgbImage::new(desc.Width, desc.Height);
+            for (y, row) in image.rows_mut().enumerate() {
+                let source = std::slice::from_raw_parts(
+                    (mapped.pData as *const u8).add(y * mapped.RowPitch as usize),
+                    desc.Width as usize * 4,
+                );
+                for (pixel, bgra) in row.zip(source.chunks_exact(4)) {
+                    pixel.0 = [bgra[2], bgra[1], bgra[0]];
+                }
+            }
+            context.Unmap(&staging, 0);
+            Ok(Some(image))
+        })();
+        let _ = duplication.ReleaseFrame();
+        result
+    }
+}
+
+#[cfg(windows)]
+fn find_output(monitor: HMONITOR) -> Result<Option<(IDXGIAdapter1, IDXGIOutput)>> {
+    let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1()? };
+    for adapter_index in 0.. {
+        let Ok(adapter) = (unsafe { factory.EnumAdapters1(adapter_index) }) else {
+            break;
+        };
+        for output_index in 0.. {
+            let Ok(output) = (unsafe { adapter.EnumOutputs(output_index) }) else {
+                break;
+            };
+            let mut description = DXGI_OUTPUT_DESC::default();
+            if unsafe { output.GetDesc(&mut description) }.is_ok() && description.Monitor == monitor {
+                return Ok(Some((adapter, output)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(windows)]
+fn capture_gdi(bounds: RECT) -> Result<RgbImage> {
+    let (width, height) = (bounds.right - bounds.left, bounds.bottom - bounds.top);
+    unsafe {
+        let screen = GetDC(None);
+        if screen.is_invalid() {
+            return Err(Error::from_win32());
+        }
+        let result = with_bitmap(screen, width, height, |memory| {
+            // CAPTUREBLT brings along layered windows, such as tooltips and menus
+            BitBlt(memory, 0, 0, width, height, screen, bounds.left, bounds.top, SRCCOPY | CAPTUREBLT)
+        });
+        ReleaseDC(None, screen);
+        result
+    }
+}
+
+#[cfg(windows)]
+fn capture_window(hwnd: HWND) -> Result<(RgbImage, RECT)> {
+    check_window(hwnd)?;
+    if unsafe { IsIconic(hwnd) }.as_bool() {
+        return Err(Error::new(E_INVALIDARG, "A minimized window can't be captured".into()));
+    }
+    let mut window = RECT::default();
+    unsafe { GetWindowRect(hwnd, &mut window)? };
+    let frame = visible_frame(hwnd);
+    let (width, height) = (window.right - window.left, window.bottom - window.top);
+
+    let image = unsafe {
+        let screen = GetDC(None);
+        if screen.is_invalid() {
+            return Err(Error::from_win32());
+        }
+        let result = with_bitmap(screen, width, height, |memory| {
+            PrintWindow(hwnd, memory, PRINT_WINDOW_FLAGS(PW_RENDERFULLCONTENT)).ok()
+        });
+        ReleaseDC(None, screen);
+        result?
+    };
+    // PrintWindow draws the invisible resize borders too
+    let (x, y) = ((frame.left - window.left).max(0) as u32, (frame.top - window.top).max(0) as u32);
+    let (frame_width, frame_height) = ((frame.right - frame.left) as u32, (frame.bottom - frame.top) as u32);
+    let image = image::imageops::crop_imm(&image, x, y, frame_width, frame_height).to_image();
+    Ok((image, frame))
+}
+
+// Draws into a top-down 32-bit DIB section of width by height and reads it back
+#[cfg(windows)]
+unsafe fn with_bitmap(dc: HDC, width: i32, height: i32, draw: impl FnOnce(HDC) -> Result<()>) -> Result<RgbImage> {
+    let info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width,
+            biHeight: -height,
+            biPlanes: 1,
+            biBitCount: 32,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    unsafe {
+        let memory = CreateCompatibleDC(dc);
+        let mut bits = std::ptr::null_mut();
+        let result = CreateDIBSection(memory, &info, DIB_RGB_COLORS, &mut bits, None, 0).and_then(|bitmap| {
+            let previous = SelectObject(memory, bitmap);
+            let drawn = draw(memory).map(|_| {
+                GdiFlush();
+                let pixels = std::slice::from_raw_parts(bits as *const u8, (width * height * 4) as usize);
+                let mut image = RgbImage::new(width as u32, height as u32);
+                for (pixel, bgra) in image.pixels_mut().zip(pixels.chunks_exact(4)) {
+                    pixel.0 = [bgra[2], bgra[1], bgra[0]];
+                }
+                image
+            });
+            SelectObject(memory, previous);
+            let _ = DeleteObject(bitmap);
+            drawn
+        });
+        let _ = DeleteDC(memory);
+        result
+    }
+}
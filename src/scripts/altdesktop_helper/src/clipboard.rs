@@ -0,0 +1,259 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::path::PathBuf;
+#[cfg(windows)]
+use std::thread;
+#[cfg(windows)]
+use std::time::Duration;
+
+#[cfg(windows)]
+use altdesktop_core::sandbox;
+#[cfg(windows)]
+use image::{codecs::bmp::BmpDecoder, DynamicImage, ImageFormat};
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{GlobalFree, E_FAIL, ERROR_NOT_FOUND, HANDLE, HGLOBAL, HWND, POINT},
+    Win32::System::DataExchange::*,
+    Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE},
+    Win32::System::Ole::{CF_DIB, CF_DIBV5, CF_HDROP, DROPEFFECT, DROPEFFECT_COPY, DROPEFFECT_MOVE},
+    Win32::UI::Shell::{DragQueryFileW, DROPFILES, HDROP},
+    Win32::UI::WindowsAndMessaging::{CreateWindowExW, DestroyWindow, HWND_MESSAGE, WINDOW_EX_STYLE, WINDOW_STYLE},
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper clipboard get-image <output.png>
+  altdesktop-helper clipboard get-files
+  altdesktop-helper clipboard set-files <path>... [--cut]
+
+get-image saves the image on the clipboard as a PNG, with its transparency when the
+app that copied it kept one, and prints {\"path\",\"width\",\"height\"}.
+get-files prints {\"files\":[...],\"effect\"} for files copied or cut in Explorer; effect
+is copy, or move after a cut, and files is empty when there are none.
+set-files puts <path>... on the clipboard as Explorer's Copy does, or Cut with --cut,
+and prints {\"ok\":true}. Paths may start with a known folder token such as {Desktop}.";
+
+// Another app can be holding the clipboard open for a moment
+#[cfg(windows)]
+const OPEN_ATTEMPTS: u32 = 10;
+#[cfg(windows)]
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(20);
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct SavedImage {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct Files {
+    files: Vec<String>,
+    effect: &'static str,
+}
+
+// Open for as long as this lives
+#[cfg(windows)]
+struct Clipboard;
+
+#[cfg(windows)]
+impl Clipboard {
+    fn open(owner: HWND) -> Result<Clipboard> {
+        let mut attempt = 1;
+        loop {
+            match unsafe { OpenClipboard(owner) } {
+                Ok(()) => return Ok(Clipboard),
+                Err(error) if attempt == OPEN_ATTEMPTS => return Err(error),
+                Err(_) => {
+                    attempt += 1;
+                    thread::sleep(OPEN_RETRY_DELAY);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Clipboard {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseClipboard();
+        }
+    }
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--get-files") if args.len() == 2 => tool::finish(get_files()),
+        Some("--get-files") => tool::usage_error("get-files takes no arguments", USAGE),
+        Some("--set-files") => {
+            let mut cut = false;
+            let mut paths = Vec::new();
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--cut" => cut = true,
+                    flag if flag.starts_with("--") => return tool::usage_error(&format!("Unknown option: {}", flag), USAGE),
+                    _ => paths.push(arg.as_str()),
+                }
+            }
+            if paths.is_empty() {
+                return tool::usage_error("Expected at least one <path>", USAGE);
+            }
+            tool::finish(set_files(&paths, cut).map(|()| tool::DONE))
+        }
+        Some(flag) if flag.starts_with("--") => tool::usage_error(&format!("Unknown option: {}", flag), USAGE),
+        Some(output) if args.len() == 2 => tool::finish(get_image(output)),
+        _ => tool::usage_error("Expected <output.png>", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("clipboard")
+}
+
+// Browsers and Office also put a PNG up, the only one of these formats that
+// reliably keeps transparency; CF_BITMAP is always offered as CF_DIB too
+#[cfg(windows)]
+fn get_image(output: &str) -> Result<SavedImage> {
+    let path = std::path::absolute(create_shortcut::known_folders::expand_known_folder(output)?).map_err(tool::io_error)?;
+    sandbox::check_output(&path)?;
+    let image = {
+        let _clipboard = Clipboard::open(HWND(0))?;
+        let png = unsafe { RegisterClipboardFormatW(w!("PNG")) };
+        if let Some(bytes) = read_format(png) {
+            image::load_from_memory_with_format(&bytes, ImageFormat::Png)
+        } else if let Some(bytes) = read_format(CF_DIBV5.0 as u32).or_else(|| read_format(CF_DIB.0 as u32)) {
+            BmpDecoder::new_without_file_header(std::io::Cursor::new(bytes)).and_then(DynamicImage::from_decoder)
+        } else {
+            return Err(Error::new(ERROR_NOT_FOUND.to_hresult(), "The clipboard holds no image".into()));
+        }
+    }
+    .map_err(image_error)?;
+
+    image.save_with_format(&path, ImageFormat::Png).map_err(image_error)?;
+    Ok(SavedImage { path, width: image.width(), height: image.height() })
+}
+
+#[cfg(windows)]
+fn get_files() -> Result<Files> {
+    let _clipboard = Clipboard::open(HWND(0))?;
+    let mut files = Vec::new();
+    if let Ok(handle) = unsafe { GetClipboardData(CF_HDROP.0 as u32) } {
+        let drop = HDROP(handle.0);
+        let count = unsafe { DragQueryFileW(drop, u32::MAX, None) };
+        for index in 0..count {
+            let length = unsafe { DragQueryFileW(drop, index, None) } as usize;
+            let mut name = vec![0u16; length + 1];
+            let copied = unsafe { DragQueryFileW(drop, index, Some(&mut name)) } as usize;
+            files.push(String::from_utf16_lossy(&name[..copied]));
+        }
+    }
+
+    // Explorer marks a cut with a move effect alone; a copy also allows linking
+    let preferred = unsafe { RegisterClipboardFormatW(w!("Preferred DropEffect")) };
+    let effect = read_format(preferred)
+        .and_then(|bytes| bytes.get(..4).map(|value| u32::from_le_bytes(value.try_into().unwrap())))
+        .filter(|&effect| effect & DROPEFFECT_MOVE.0 != 0 && effect & DROPEFFECT_COPY.0 == 0)
+        .map_or("copy", |_| "move");
+    Ok(Files { files, effect })
+}
+
+#[cfg(windows)]
+fn set_files(paths: &[&str], cut: bool) -> Result<()> {
+    let mut list = Vec::new();
+    for path in paths {
+        let path = std::path::absolute(create_shortcut::known_folders::expand_known_folder(path)?).map_err(tool::io_error)?;
+        std::fs::metadata(&path).map_err(tool::io_error)?;
+        list.extend(HSTRING::from(path.as_path()).as_wide());
+        list.push(0);
+    }
+    list.push(0);
+
+    // A DROPFILES header with the double-null-terminated list of paths after it
+    let header = DROPFILES {
+        pFiles: std::mem::size_of::<DROPFILES>() as u32,
+        pt: POINT::default(),
+        fNC: false.into(),
+        fWide: true.into(),
+    };
+    let mut data = unsafe {
+        std::slice::from_raw_parts(&header as *const DROPFILES as *const u8, std::mem::size_of::<DROPFILES>())
+    }
+    .to_vec();
+    data.extend(list.iter().flat_map(|unit| unit.to_le_bytes()));
+    let effect: DROPEFFECT = if cut { DROPEFFECT_MOVE } else { DROPEFFECT_COPY };
+
+    // The clipboard needs an owner for SetClipboardData to keep what it's given
+    let window = unsafe {
+        CreateWindowExW(WINDOW_EX_STYLE(0), w!("STATIC"), None, WINDOW_STYLE(0), 0, 0, 0, 0, HWND_MESSAGE, None, None, None)
+    };
+    if window.0 == 0 {
+        return Err(Error::from_win32());
+    }
+    let result = (|| {
+        let _clipboard = Clipboard::open(window)?;
+        unsafe { EmptyClipboard()? };
+        write_format(CF_HDROP.0 as u32, &data)?;
+        let preferred = unsafe { RegisterClipboardFormatW(w!("Preferred DropEffect")) };
+        write_format(preferred, &effect.0.to_le_bytes())
+    })();
+    unsafe {
+        let _ = DestroyWindow(window);
+    }
+    result
+}
+
+// Copies out a format's bytes; the clipboard must be open
+#[cfg(windows)]
+fn read_format(format: u32) -> Option<Vec<u8>> {
+    unsafe {
+        let handle = GetClipboardData(format).ok()?;
+        let global = HGLOBAL(handle.0 as _);
+        let data = GlobalLock(global);
+        if data.is_null() {
+            return None;
+        }
+        let bytes = std::slice::from_raw_parts(data as *const u8, GlobalSize(global)).to_vec();
+        let _ = GlobalUnlock(global);
+        Some(bytes)
+    }
+}
+
+// The clipboard owns the memory once SetClipboardData succeeds
+#[cfg(windows)]
+fn write_format(format: u32, bytes: &[u8]) -> Result<()> {
+    unsafe {
+        let global = GlobalAlloc(GMEM_MOVEABLE, bytes.len())?;
+        let data = GlobalLock(global);
+        if data.is_null() {
+            let _ = GlobalFree(global);
+            return Err(Error::from_win32());
+        }
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), data as *mut u8, bytes.len());
+        let _ = GlobalUnlock(global);
+        if let Err(error) = SetClipboardData(format, HANDLE(global.0 as isize)) {
+            let _ = GlobalFree(global);
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn image_error(error: image::ImageError) -> Error {
+    Error::new(E_FAIL, error.to_string().into())
+}
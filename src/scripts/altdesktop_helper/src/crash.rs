@@ -0,0 +1,196 @@
+use std::env;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use altdesktop_core::exit;
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{
+    Win32::Foundation::{CloseHandle, GENERIC_WRITE, HMODULE, MAX_PATH},
+    Win32::Storage::FileSystem::{CREATE_ALWAYS, CreateFileW, FILE_ATTRIBUTE_NORMAL, FILE_SHARE_NONE},
+    Win32::System::Diagnostics::Debug::{
+        EXCEPTION_POINTERS, MINIDUMP_EXCEPTION_INFORMATION, MiniDumpWithIndirectlyReferencedMemory,
+        MiniDumpWithThreadInfo, MiniDumpWriteDump, SetUnhandledExceptionFilter,
+    },
+    Win32::System::LibraryLoader::{
+        GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS, GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT, GetModuleFileNameW,
+        GetModuleHandleExW,
+    },
+    Win32::System::Threading::{GetCurrentProcess, GetCurrentProcessId, GetCurrentThreadId, TerminateProcess},
+    core::{HSTRING, PCWSTR},
+};
+
+/// What a crash leaves next to the logs, as crash-<time>-<pid>.json, with a minidump
+/// of the same name on Windows.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    kind: &'static str,
+    message: String,
+    // The panic's source line, or for an exception, its code and where it was raised
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    /// The DLL the exception was raised in, which for a crash during icon extraction
+    /// is usually a shell extension rather than the helper.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<String>,
+    thread: String,
+    pid: u32,
+    /// Unix time in milliseconds.
+    time: u64,
+    version: &'static str,
+    args: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dump: Option<String>,
+}
+
+/// Catches panics and, on Windows, the access violations and other structured
+/// exceptions that nothing else handles, which is how a faulty shell extension
+/// loaded into the helper takes it down. Either writes a crash record and a
+/// minidump to [`log_dir`](altdesktop_core::log::log_dir), whether or not logging
+/// is on, and exits with [`exit::INTERNAL`]. Called first thing in `main`.
+pub fn install() {
+    panic::set_hook(Box::new(on_panic));
+    #[cfg(windows)]
+    unsafe {
+        SetUnhandledExceptionFilter(Some(on_exception));
+    }
+}
+
+fn on_panic(info: &PanicHookInfo) {
+    let message = match info.payload().downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => info.payload().downcast_ref::<String>().cloned().unwrap_or_else(|| "Box<dyn Any>".to_string()),
+    };
+    let mut record = Record::new("panic", message);
+    record.location = info.location().map(|location| location.to_string());
+    tracing::error!(message = %record.message, location = ?record.location, "Panicked");
+    #[cfg(windows)]
+    {
+        record.dump = write_dump(&record, None);
+    }
+    save(&record);
+    eprintln!("altdesktop-helper crashed: {}", record.message);
+    // Unwinding on would only let a thread's panic take down what it was doing, and
+    // leave the rest of the helper running on whatever state it left behind
+    process::exit(exit::INTERNAL.into());
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn on_exception(pointers: *const EXCEPTION_POINTERS) -> i32 {
+    let exception = unsafe { &*(*pointers).ExceptionRecord };
+    let code = exception.ExceptionCode.0 as u32;
+    let address = exception.ExceptionAddress as usize;
+    let mut record = Record::new("exception", format!("Unhandled exception 0x{:08X}", code));
+    record.code = Some(format!("0x{:08X}", code));
+    record.address = Some(format!("0x{:X}", address));
+    record.module = module_at(address);
+    tracing::error!(code = %format!("0x{:08X}", code), module = ?record.module, "Crashed");
+    record.dump = write_dump(&record, Some(pointers));
+    save(&record);
+    eprintln!("altdesktop-helper crashed: {}", record.message);
+    // Straight out, without running anything else on a process that just faulted
+    unsafe {
+        let _ = TerminateProcess(GetCurrentProcess(), exit::INTERNAL.into());
+    }
+    // EXCEPTION_EXECUTE_HANDLER, should termination somehow fail
+    1
+}
+
+impl Record {
+    fn new(kind: &'static str, message: String) -> Self {
+        let thread = thread::current();
+        Record {
+            kind,
+            message,
+            location: None,
+            code: None,
+            address: None,
+            module: None,
+            thread: thread.name().map_or_else(|| format!("{:?}", thread.id()), str::to_string),
+            pid: process::id(),
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64),
+            version: env!("CARGO_PKG_VERSION"),
+            args: env::args().skip(1).collect(),
+            dump: None,
+        }
+    }
+
+    fn path(&self, extension: &str) -> PathBuf {
+        altdesktop_core::log::log_dir().join(format!("crash-{}-{}.{}", self.time, self.pid, extension))
+    }
+}
+
+fn save(record: &Record) {
+    let path = record.path("json");
+    if let Some(dir) = path.parent()
+        && fs::create_dir_all(dir).is_ok()
+        && let Ok(text) = serde_json::to_string_pretty(record)
+    {
+        let _ = fs::write(&path, text);
+    }
+}
+
+// A dump taken from inside the crashed process, which is all a helper without a
+// watcher process can do; it holds the stacks, which is what finding the culprit takes
+#[cfg(windows)]
+fn write_dump(record: &Record, pointers: Option<*const EXCEPTION_POINTERS>) -> Option<String> {
+    let path = record.path("dmp");
+    fs::create_dir_all(path.parent()?).ok()?;
+    unsafe {
+        let file = CreateFileW(
+            &HSTRING::from(path.as_path()),
+            GENERIC_WRITE.0,
+            FILE_SHARE_NONE,
+            None,
+            CREATE_ALWAYS,
+            FILE_ATTRIBUTE_NORMAL,
+            None,
+        )
+        .ok()?;
+        let exception = pointers.map(|pointers| MINIDUMP_EXCEPTION_INFORMATION {
+            ThreadId: GetCurrentThreadId(),
+            ExceptionPointers: pointers as *mut _,
+            ClientPointers: false.into(),
+        });
+        let written = MiniDumpWriteDump(
+            GetCurrentProcess(),
+            GetCurrentProcessId(),
+            file,
+            MiniDumpWithIndirectlyReferencedMemory | MiniDumpWithThreadInfo,
+            exception.as_ref().map(|exception| exception as *const _),
+            None,
+            None,
+        );
+        let _ = CloseHandle(file);
+        if written.is_err() {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+    }
+    Some(path.to_string_lossy().into_owned())
+}
+
+#[cfg(windows)]
+fn module_at(address: usize) -> Option<String> {
+    let mut module = HMODULE::default();
+    let mut name = [0u16; MAX_PATH as usize];
+    unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            PCWSTR(address as *const u16),
+            &mut module,
+        )
+        .ok()?;
+        let length = GetModuleFileNameW(module, &mut name) as usize;
+        (length > 0).then(|| String::from_utf16_lossy(&name[..length]))
+    }
+}
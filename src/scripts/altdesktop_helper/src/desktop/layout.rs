@@ -0,0 +1,277 @@
+use std::ffi::c_void;
+use std::io::{self, Read};
+use std::mem::size_of;
+use std::process::ExitCode;
+use std::thread;
+
+use altdesktop_core::error::ErrorReport;
+use altdesktop_core::json::emit;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use windows::{
+    core::*,
+    Win32::Foundation::{CloseHandle, HANDLE, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
+    Win32::System::Diagnostics::Debug::{ReadProcessMemory, WriteProcessMemory},
+    Win32::System::Memory::{VirtualAllocEx, VirtualFreeEx, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_READWRITE},
+    Win32::System::Threading::{OpenProcess, PROCESS_VM_OPERATION, PROCESS_VM_READ, PROCESS_VM_WRITE},
+    Win32::UI::Controls::*,
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+use super::find_icons;
+use crate::tool;
+
+const CLASS_NAME: PCWSTR = w!("AltDesktopHelperLayout");
+// Explorer lays the icons out again for a new resolution a moment after it changes,
+// and a monitor being plugged in changes it more than once
+const SETTLE_TIMER: usize = 1;
+const SETTLE_MILLISECONDS: u32 = 1500;
+// Each message waits on Explorer's UI thread, which may be busy or hung
+const MESSAGE_TIMEOUT_MS: u32 = 2000;
+// Longer than any name Explorer shows under an icon
+const TEXT_LENGTH: usize = 260;
+// The parts of the buffer in Explorer that the list view reads from and writes to.
+// Its LVITEMW is laid out as ours is, since a 64-bit Explorer needs a 64-bit helper
+const ITEM_OFFSET: usize = 0;
+const POINT_OFFSET: usize = ITEM_OFFSET + size_of::<LVITEMW>();
+const TEXT_OFFSET: usize = POINT_OFFSET + size_of::<POINT>();
+const BUFFER_SIZE: usize = TEXT_OFFSET + TEXT_LENGTH * size_of::<u16>();
+
+/// Where each of Explorer's desktop icons is, in the list view's client pixels.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Layout {
+    #[serde(default)]
+    width: i32,
+    #[serde(default)]
+    height: i32,
+    #[serde(default)]
+    auto_arrange: bool,
+    #[serde(default)]
+    snap_to_grid: bool,
+    icons: Vec<Icon>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Icon {
+    name: String,
+    x: i32,
+    y: i32,
+}
+
+#[derive(Serialize)]
+pub struct Restored {
+    moved: usize,
+    missing: Vec<String>,
+}
+
+// A buffer allocated in the process that owns the list view, since the LVM messages
+// that take a pointer are only marshaled within a process
+struct Remote {
+    process: HANDLE,
+    address: *mut c_void,
+}
+
+impl Remote {
+    fn open(window: HWND) -> Result<Self> {
+        let mut process_id = 0;
+        unsafe { GetWindowThreadProcessId(window, Some(&mut process_id)) };
+        if process_id == 0 {
+            return Err(Error::from_win32());
+        }
+        let process = unsafe { OpenProcess(PROCESS_VM_OPERATION | PROCESS_VM_READ | PROCESS_VM_WRITE, false, process_id)? };
+        let address = unsafe { VirtualAllocEx(process, None, BUFFER_SIZE, MEM_COMMIT | MEM_RESERVE, PAGE_READWRITE) };
+        if address.is_null() {
+            let error = Error::from_win32();
+            unsafe {
+                let _ = CloseHandle(process);
+            }
+            return Err(error);
+        }
+        Ok(Remote { process, address })
+    }
+
+    fn at(&self, offset: usize) -> *mut c_void {
+        self.address.wrapping_byte_add(offset)
+    }
+
+    fn write<T>(&self, offset: usize, value: &T) -> Result<()> {
+        unsafe { WriteProcessMemory(self.process, self.at(offset), value as *const T as _, size_of::<T>(), None) }
+    }
+
+    fn read_point(&self) -> Result<POINT> {
+        let mut point = POINT::default();
+        let size = size_of::<POINT>();
+        unsafe { ReadProcessMemory(self.process, self.at(POINT_OFFSET), &mut point as *mut POINT as _, size, None)? };
+        Ok(point)
+    }
+
+    fn read_text(&self, length: usize) -> Result<String> {
+        let mut text = vec![0u16; length.min(TEXT_LENGTH - 1)];
+        let size = text.len() * size_of::<u16>();
+        unsafe { ReadProcessMemory(self.process, self.at(TEXT_OFFSET), text.as_mut_ptr() as _, size, None)? };
+        Ok(String::from_utf16_lossy(&text))
+    }
+}
+
+impl Drop for Remote {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = VirtualFreeEx(self.process, self.address, 0, MEM_RELEASE);
+            let _ = CloseHandle(self.process);
+        }
+    }
+}
+
+/// Reads the position and name of every icon in the desktop's list view, `icons`.
+pub fn read_layout(icons: HWND) -> Result<Layout> {
+    let remote = Remote::open(icons)?;
+    let count = send(icons, LVM_GETITEMCOUNT, 0, 0)?;
+    let mut items = Vec::with_capacity(count);
+    for index in 0..count {
+        let item = LVITEMW {
+            mask: LVIF_TEXT,
+            iItem: index as i32,
+            pszText: PWSTR(remote.at(TEXT_OFFSET) as _),
+            cchTextMax: TEXT_LENGTH as i32,
+            ..Default::default()
+        };
+        remote.write(ITEM_OFFSET, &item)?;
+        let length = send(icons, LVM_GETITEMTEXTW, index, remote.at(ITEM_OFFSET) as isize)?;
+        let name = remote.read_text(length)?;
+        send(icons, LVM_GETITEMPOSITION, index, remote.at(POINT_OFFSET) as isize)?;
+        let point = remote.read_point()?;
+        items.push(Icon { name, x: point.x, y: point.y });
+    }
+
+    let mut rect = RECT::default();
+    unsafe { GetClientRect(icons, &mut rect)? };
+    let style = unsafe { GetWindowLongPtrW(icons, GWL_STYLE) } as u32;
+    let extended_style = send(icons, LVM_GETEXTENDEDLISTVIEWSTYLE, 0, 0)? as u32;
+    Ok(Layout {
+        width: rect.right - rect.left,
+        height: rect.bottom - rect.top,
+        auto_arrange: style & LVS_AUTOARRANGE != 0,
+        snap_to_grid: extended_style & LVS_EX_SNAPTOGRID != 0,
+        icons: items,
+    })
+}
+
+/// Moves each icon `layout` names back to where it was, matching them by name and,
+/// among icons of the same name, by their order.
+pub fn restore(icons: HWND, layout: &Layout) -> Result<Restored> {
+    let current = read_layout(icons)?;
+    let remote = Remote::open(icons)?;
+    let mut placed = vec![false; current.icons.len()];
+    let mut moved = 0;
+    let mut missing = Vec::new();
+    for icon in &layout.icons {
+        let found = current.icons.iter().enumerate().position(|(index, item)| !placed[index] && item.name == icon.name);
+        let Some(index) = found else {
+            missing.push(icon.name.clone());
+            continue;
+        };
+        placed[index] = true;
+        let item = &current.icons[index];
+        if (item.x, item.y) == (icon.x, icon.y) {
+            continue;
+        }
+        // The 32-bit form, since the other packs x and y into 16 bits each of its lParam
+        remote.write(POINT_OFFSET, &POINT { x: icon.x, y: icon.y })?;
+        send(icons, LVM_SETITEMPOSITION32, index, remote.at(POINT_OFFSET) as isize)?;
+        moved += 1;
+    }
+    Ok(Restored { moved, missing })
+}
+
+/// Reads a layout as `desktop layout` prints it from the file, or from stdin without one.
+pub fn load(file: Option<&str>) -> std::result::Result<Layout, String> {
+    let text = match file {
+        Some(file) => std::fs::read_to_string(file).map_err(|error| format!("Could not read {}: {}", file, error))?,
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map_err(|error| format!("Failed to read stdin: {}", error))?;
+            text
+        }
+    };
+    serde_json::from_str(&text).map_err(|error| format!("Invalid layout: {}", error))
+}
+
+pub fn hold_until_stdin_closes() -> ExitCode {
+    let layout = match find_icons().and_then(read_layout) {
+        Ok(layout) => layout,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    let mut ready = serde_json::to_value(&layout).unwrap();
+    ready["type"] = Value::from("ready");
+    if !emit(ready) {
+        return ExitCode::SUCCESS;
+    }
+    // The window belongs to the thread that pumps its messages
+    let held = layout.clone();
+    thread::spawn(move || watch(held));
+    let _ = io::stdin().read_to_end(&mut Vec::new());
+    // Found again, since Explorer may have restarted meanwhile
+    emit_restored(find_icons().and_then(|icons| restore(icons, &layout)));
+    ExitCode::SUCCESS
+}
+
+fn watch(layout: Layout) {
+    let window = match tool::hidden_window(CLASS_NAME, Some(window_proc)) {
+        Ok(window) => window,
+        Err(error) => {
+            // The layout would never be put back, so don't leave the caller waiting
+            tool::exit_with(error);
+        }
+    };
+    let mut message = MSG::default();
+    while unsafe { GetMessageW(&mut message, HWND(0), 0, 0) }.as_bool() {
+        if message.message != WM_TIMER || message.wParam.0 != SETTLE_TIMER {
+            unsafe { DispatchMessageW(&message) };
+            continue;
+        }
+        unsafe {
+            let _ = KillTimer(window, SETTLE_TIMER);
+        }
+        if !emit_restored(find_icons().and_then(|icons| restore(icons, &layout))) {
+            return;
+        }
+    }
+}
+
+fn emit_restored(restored: Result<Restored>) -> bool {
+    let (mut line, kind) = match restored {
+        Ok(restored) => (serde_json::to_value(restored).unwrap(), "restored"),
+        Err(error) => (serde_json::to_value(ErrorReport::from_error(&error)).unwrap(), "error"),
+    };
+    line["type"] = Value::from(kind);
+    emit(line)
+}
+
+fn send(window: HWND, message: u32, wparam: usize, lparam: isize) -> Result<usize> {
+    let mut result = 0;
+    let sent = unsafe {
+        SendMessageTimeoutW(
+            window,
+            message,
+            WPARAM(wparam),
+            LPARAM(lparam),
+            SMTO_ABORTIFHUNG,
+            MESSAGE_TIMEOUT_MS,
+            Some(&mut result),
+        )
+    };
+    if sent.0 == 0 {
+        return Err(Error::from_win32());
+    }
+    Ok(result)
+}
+
+// Each display change restarts the settle timer, whose expiry the loop handles
+extern "system" fn window_proc(window: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if message == WM_DISPLAYCHANGE {
+        unsafe { SetTimer(window, SETTLE_TIMER, SETTLE_MILLISECONDS, None) };
+        return LRESULT(0);
+    }
+    unsafe { DefWindowProcW(window, message, wparam, lparam) }
+}
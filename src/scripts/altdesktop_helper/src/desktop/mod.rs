@@ -0,0 +1,367 @@
+#[cfg(windows)]
+mod layout;
+
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::{self, Read};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{BOOL, E_FAIL, E_INVALIDARG, HWND, LPARAM, POINT, RECT, WPARAM},
+    Win32::Graphics::Gdi::MapWindowPoints,
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper desktop attach <hwnd> [--monitor <index>]
+  altdesktop-helper desktop worker-w
+  altdesktop-helper desktop icons [show | hide [--while-running]]
+  altdesktop-helper desktop layout [restore [<layout.json>] | --while-running]
+
+attach moves the window behind the desktop icons, sized to the monitor at <index>
+in `monitors list` order or to the whole virtual screen, prints
+{\"ok\":true,\"workerW\":...} and stays running. When stdin closes it puts the window
+back where it was, with its original parent and style, and exits.
+<hwnd> is decimal or 0x-prefixed hex, as from getNativeWindowHandle().
+worker-w only prints the handle of the window behind the icons.
+icons prints {\"visible\"}, whether Explorer's desktop icons are showing. show and
+hide change that until Explorer restarts, without touching its View > Show desktop
+icons setting, and print {\"ok\":true}. With --while-running hide stays running and
+puts the icons back as they were when stdin closes, including when the caller exits
+or crashes.
+layout prints where Explorer's desktop icons are, {\"width\",\"height\",\"autoArrange\",
+\"snapToGrid\",\"icons\":[{\"name\",\"x\",\"y\"}]}, in pixels from the top left of the
+desktop, width and height being its size. restore reads such a layout from the file
+or stdin and moves each icon of the same name back, the nth of a repeated name to
+the nth position, and prints {\"moved\",\"missing\"}: how many were moved and the
+names of those no longer on the desktop. Icons the layout doesn't name stay where
+they are; with autoArrange or snapToGrid on, Explorer moves them all again. With
+--while-running layout prints {\"type\":\"ready\"} with the layout and stays
+running: whenever the display changes, once Explorer has rearranged the icons for
+it, the layout is put back and {\"type\":\"restored\",\"moved\",\"missing\"} printed,
+or {\"type\":\"error\"} with the JSON error when it can't be, and once more when
+stdin closes, including when the caller exits or crashes.";
+
+// Undocumented: asks Progman to split the wallpaper into its own WorkerW behind
+// the icons, as it does for the wallpaper fade animation
+#[cfg(windows)]
+const WM_SPAWN_WORKER: u32 = 0x052C;
+#[cfg(windows)]
+const SPAWN_TIMEOUT_MS: u32 = 1000;
+
+#[cfg(windows)]
+enum Command {
+    Attach { hwnd: HWND, monitor: Option<usize> },
+    WorkerW,
+    Icons { visible: Option<bool>, while_running: bool },
+    Layout,
+    RestoreLayout { file: Option<String> },
+    HoldLayout,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let command = match tool::string_args(args).and_then(|args| parse_args(&args)) {
+        Ok(command) => command,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match command {
+        Command::WorkerW => {
+            tool::finish(find_worker_w().map(|worker| serde_json::json!({ "workerW": format_hwnd(worker) })))
+        }
+        Command::Attach { hwnd, monitor } => attach_until_stdin_closes(hwnd, monitor),
+        Command::Icons { visible: None, .. } => {
+            tool::finish(find_icons().map(|icons| serde_json::json!({ "visible": unsafe { IsWindowVisible(icons) }.as_bool() })))
+        }
+        Command::Icons { visible: Some(false), while_running: true } => hide_icons_until_stdin_closes(),
+        Command::Icons { visible: Some(visible), .. } => {
+            tool::finish(find_icons().map(|icons| show_icons(icons, visible)).map(|()| tool::DONE))
+        }
+        Command::Layout => tool::finish(find_icons().and_then(layout::read_layout)),
+        Command::RestoreLayout { file } => match layout::load(file.as_deref()) {
+            Ok(layout) => tool::finish(find_icons().and_then(|icons| layout::restore(icons, &layout))),
+            Err(message) => tool::usage_error(&message, USAGE),
+        },
+        Command::HoldLayout => layout::hold_until_stdin_closes(),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("desktop")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<Command, String> {
+    if args.get(1).map(String::as_str) == Some("--icons") {
+        return match &args[2..] {
+            [] => Ok(Command::Icons { visible: None, while_running: false }),
+            [action] if action == "show" => Ok(Command::Icons { visible: Some(true), while_running: false }),
+            [action] if action == "hide" => Ok(Command::Icons { visible: Some(false), while_running: false }),
+            [action, flag] if action == "hide" && flag == "--while-running" => {
+                Ok(Command::Icons { visible: Some(false), while_running: true })
+            }
+            [_, flag] | [flag, ..] if flag.starts_with("--") => Err(format!("Unknown option: {}", flag)),
+            _ => Err("Expected show or hide".to_string()),
+        };
+    }
+    if args.get(1).map(String::as_str) == Some("--layout") {
+        return match &args[2..] {
+            [] => Ok(Command::Layout),
+            [flag] if flag == "--while-running" => Ok(Command::HoldLayout),
+            [action] if action == "restore" => Ok(Command::RestoreLayout { file: None }),
+            [action, file] if action == "restore" && !file.starts_with("--") => {
+                Ok(Command::RestoreLayout { file: Some(file.clone()) })
+            }
+            [_, flag] | [flag, ..] if flag.starts_with("--") => Err(format!("Unknown option: {}", flag)),
+            _ => Err("Expected restore or --while-running".to_string()),
+        };
+    }
+    let mut worker_w = false;
+    let mut monitor = None;
+    let mut positional = Vec::new();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--worker-w" => worker_w = true,
+            "--monitor" => {
+                let value = value()?;
+                monitor = Some(value.parse::<usize>().map_err(|_| format!("Invalid --monitor: {}", value))?);
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    match (worker_w, positional.as_slice()) {
+        (true, []) if monitor.is_none() => Ok(Command::WorkerW),
+        (true, _) => Err("worker-w takes no arguments".to_string()),
+        (false, [hwnd]) => Ok(Command::Attach { hwnd: parse_hwnd(hwnd)?, monitor }),
+        (false, _) => Err("Expected <hwnd>".to_string()),
+    }
+}
+
+/// Accepts the decimal or 0x-prefixed hex handles the tools take.
+#[cfg(windows)]
+pub fn parse_hwnd(value: &str) -> std::result::Result<HWND, String> {
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => isize::from_str_radix(hex, 16),
+        None => value.parse::<isize>(),
+    };
+    match parsed {
+        Ok(handle) if handle != 0 => Ok(HWND(handle)),
+        _ => Err(format!("Invalid window handle: {}", value)),
+    }
+}
+
+#[cfg(windows)]
+pub fn format_hwnd(hwnd: HWND) -> String {
+    format!("0x{:X}", hwnd.0)
+}
+
+/// Finds the WorkerW that draws the wallpaper behind the desktop icons, asking
+/// Explorer to create it first if it hasn't.
+#[cfg(windows)]
+pub fn find_worker_w() -> Result<HWND> {
+    let progman = unsafe { FindWindowW(w!("Progman"), None) };
+    if progman.0 == 0 {
+        return Err(Error::new(E_FAIL, "Explorer's desktop window (Progman) was not found".into()));
+    }
+    unsafe {
+        SendMessageTimeoutW(progman, WM_SPAWN_WORKER, WPARAM(0), LPARAM(0), SMTO_NORMAL, SPAWN_TIMEOUT_MS, None);
+    }
+
+    // Usually the icons (SHELLDLL_DefView) end up in one top-level WorkerW and the
+    // wallpaper in the WorkerW right after it
+    let mut worker = HWND(0);
+    unsafe {
+        let _ = EnumWindows(Some(find_icons_sibling), LPARAM(&mut worker as *mut HWND as isize));
+    }
+    if worker.0 == 0 {
+        // Since Windows 11 24H2 the icons stay in Progman and the WorkerW is its child
+        worker = unsafe { FindWindowExW(progman, HWND(0), w!("WorkerW"), None) };
+    }
+    if worker.0 == 0 {
+        return Err(Error::new(E_FAIL, "The desktop has no WorkerW window".into()));
+    }
+    Ok(worker)
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn find_icons_sibling(hwnd: HWND, worker: LPARAM) -> BOOL {
+    let icons = unsafe { FindWindowExW(hwnd, HWND(0), w!("SHELLDLL_DefView"), None) };
+    if icons.0 == 0 {
+        return true.into();
+    }
+    let sibling = unsafe { FindWindowExW(HWND(0), hwnd, w!("WorkerW"), None) };
+    unsafe { *(worker.0 as *mut HWND) = sibling };
+    // Stop enumerating
+    false.into()
+}
+
+// The list view that draws the desktop icons, in Progman or, once the wallpaper has
+// its own WorkerW, in the WorkerW before it
+#[cfg(windows)]
+fn find_icons() -> Result<HWND> {
+    let progman = unsafe { FindWindowW(w!("Progman"), None) };
+    let mut view = unsafe { FindWindowExW(progman, HWND(0), w!("SHELLDLL_DefView"), None) };
+    if view.0 == 0 {
+        unsafe {
+            let _ = EnumWindows(Some(find_icons_view), LPARAM(&mut view as *mut HWND as isize));
+        }
+    }
+    let icons = unsafe { FindWindowExW(view, HWND(0), w!("SysListView32"), None) };
+    if view.0 == 0 || icons.0 == 0 {
+        return Err(Error::new(E_FAIL, "Explorer's desktop icons were not found".into()));
+    }
+    Ok(icons)
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn find_icons_view(hwnd: HWND, view: LPARAM) -> BOOL {
+    let found = unsafe { FindWindowExW(hwnd, HWND(0), w!("SHELLDLL_DefView"), None) };
+    if found.0 == 0 {
+        return true.into();
+    }
+    unsafe { *(view.0 as *mut HWND) = found };
+    false.into()
+}
+
+#[cfg(windows)]
+fn show_icons(icons: HWND, visible: bool) {
+    unsafe {
+        let _ = ShowWindow(icons, if visible { SW_SHOW } else { SW_HIDE });
+    }
+}
+
+#[cfg(windows)]
+fn hide_icons_until_stdin_closes() -> ExitCode {
+    let icons = match find_icons() {
+        Ok(icons) => icons,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    // Icons the user had turned off in Explorer stay off
+    let was_visible = unsafe { IsWindowVisible(icons) }.as_bool();
+    show_icons(icons, false);
+    let code = tool::finish(Ok(tool::DONE));
+
+    let _ = io::stdin().read_to_end(&mut Vec::new());
+    // Gone if Explorer restarted meanwhile, and its new icons are showing anyway
+    if was_visible && unsafe { IsWindow(icons) }.as_bool() {
+        show_icons(icons, true);
+    }
+    code
+}
+
+// What attach changed, to put back on exit
+#[cfg(windows)]
+struct Placement {
+    parent: HWND,
+    style: isize,
+    // In the parent's client coordinates
+    rect: RECT,
+}
+
+#[cfg(windows)]
+fn attach_until_stdin_closes(hwnd: HWND, monitor: Option<usize>) -> ExitCode {
+    let attached = attach(hwnd, monitor);
+    let (worker, placement) = match attached {
+        Ok(attached) => attached,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    let code = tool::finish(Ok(serde_json::json!({ "ok": true, "workerW": format_hwnd(worker) })));
+
+    // The caller keeps stdin open for as long as the window should stay attached
+    let _ = io::stdin().read_to_end(&mut Vec::new());
+    if unsafe { IsWindow(hwnd) }.as_bool() {
+        restore(hwnd, &placement);
+    }
+    code
+}
+
+#[cfg(windows)]
+fn attach(hwnd: HWND, monitor: Option<usize>) -> Result<(HWND, Placement)> {
+    if !unsafe { IsWindow(hwnd) }.as_bool() {
+        return Err(Error::new(E_INVALIDARG, format!("{} is not a window", format_hwnd(hwnd)).into()));
+    }
+    let worker = find_worker_w()?;
+
+    // Work out the target area first, so a bad --monitor leaves the window alone
+    let mut area = match monitor {
+        Some(index) => {
+            let monitors = crate::monitors::list_monitors()?;
+            let bounds = &monitors
+                .get(index)
+                .ok_or_else(|| {
+                    Error::new(E_INVALIDARG, format!("No monitor {} (there are {})", index, monitors.len()).into())
+                })?
+                .bounds;
+            RECT { left: bounds.x, top: bounds.y, right: bounds.x + bounds.width, bottom: bounds.y + bounds.height }
+        }
+        None => unsafe {
+            RECT {
+                left: GetSystemMetrics(SM_XVIRTUALSCREEN),
+                top: GetSystemMetrics(SM_YVIRTUALSCREEN),
+                right: GetSystemMetrics(SM_XVIRTUALSCREEN) + GetSystemMetrics(SM_CXVIRTUALSCREEN),
+                bottom: GetSystemMetrics(SM_YVIRTUALSCREEN) + GetSystemMetrics(SM_CYVIRTUALSCREEN),
+            }
+        },
+    };
+    to_client(worker, &mut area);
+
+    let parent = unsafe { GetParent(hwnd) };
+    let mut rect = RECT::default();
+    unsafe { GetWindowRect(hwnd, &mut rect)? };
+    to_client(parent, &mut rect);
+    let placement = Placement { parent, style: unsafe { GetWindowLongPtrW(hwnd, GWL_STYLE) }, rect };
+
+    // A child window, but no frame: WorkerW paints nothing around it
+    let style = (placement.style & !(WS_POPUP.0 | WS_CAPTION.0 | WS_THICKFRAME.0) as isize) | WS_CHILD.0 as isize;
+    unsafe {
+        SetWindowLongPtrW(hwnd, GWL_STYLE, style);
+        SetParent(hwnd, worker);
+        SetWindowPos(
+            hwnd,
+            HWND(0),
+            area.left,
+            area.top,
+            area.right - area.left,
+            area.bottom - area.top,
+            SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED | SWP_SHOWWINDOW,
+        )?;
+    }
+    Ok((worker, placement))
+}
+
+#[cfg(windows)]
+fn restore(hwnd: HWND, placement: &Placement) {
+    let rect = placement.rect;
+    unsafe {
+        SetParent(hwnd, placement.parent);
+        SetWindowLongPtrW(hwnd, GWL_STYLE, placement.style);
+        let _ = SetWindowPos(
+            hwnd,
+            HWND(0),
+            rect.left,
+            rect.top,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED,
+        );
+    }
+}
+
+// Screen coordinates into `window`'s client area; a null window is the screen itself
+#[cfg(windows)]
+fn to_client(window: HWND, rect: &mut RECT) {
+    let mut points = [POINT { x: rect.left, y: rect.top }, POINT { x: rect.right, y: rect.bottom }];
+    unsafe { MapWindowPoints(HWND(0), window, &mut points) };
+    *rect = RECT { left: points[0].x, top: points[0].y, right: points[1].x, bottom: points[1].y };
+}
@@ -0,0 +1,110 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use altdesktop_core::error::ErrorReport;
+#[cfg(windows)]
+use altdesktop_core::wide::from_wide;
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::E_FAIL,
+    Win32::Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES,
+    Win32::UI::Shell::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper display-name <path>...
+
+Prints the name and type Explorer shows for each path, in the user's language:
+{\"ok\",\"results\":[{\"path\",\"ok\",\"displayName\",\"typeName\"}]}. displayName is
+Control Panel rather than its GUID, the translated name of a known folder such as
+Documents, and a shortcut's name without .lnk, or any file's without its extension
+when Explorer hides them; typeName is the Type column's text, e.g. Shortcut or Text
+Document. A path may start with a known folder token, or be a shell parsing name
+such as ::{26EE0668-A00A-44D7-9371-BEB064C98683} for Control Panel. Failed paths
+carry the error fields of a failed shortcut --json run.";
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DisplayName {
+    path: String,
+    ok: bool,
+    display_name: String,
+    type_name: String,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let paths = &args[1..];
+    if let Some(flag) = paths.iter().find(|arg| arg.starts_with("--")) {
+        return tool::usage_error(&format!("Unknown option: {}", flag), USAGE);
+    }
+    if paths.is_empty() {
+        return tool::usage_error("Expected at least one path", USAGE);
+    }
+
+    let results: Vec<Value> = paths
+        .iter()
+        .map(|path| match display_name(path) {
+            Ok(name) => serde_json::to_value(name).unwrap(),
+            Err(error) => {
+                let mut result = serde_json::to_value(ErrorReport::from_error(&error)).unwrap();
+                result["path"] = Value::from(path.as_str());
+                result
+            }
+        })
+        .collect();
+    let ok = results.iter().all(|result| result["ok"] == true);
+    println!("{}", json!({ "ok": ok, "results": results }));
+    if ok { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("display-name")
+}
+
+// Through an ID list, so parsing names work as well as paths
+#[cfg(windows)]
+fn display_name(path: &str) -> Result<DisplayName> {
+    let mut name = create_shortcut::known_folders::expand_known_folder(path)?;
+    if !name.starts_with("::") {
+        name = std::path::absolute(&name).map_err(tool::io_error)?.to_string_lossy().into_owned();
+    }
+    let mut pidl = std::ptr::null_mut();
+    unsafe { SHParseDisplayName(&HSTRING::from(name.as_str()), None, &mut pidl, 0, None)? };
+
+    let mut info = SHFILEINFOW::default();
+    let found = unsafe {
+        let found = SHGetFileInfoW(
+            PCWSTR(pidl as *const u16),
+            FILE_FLAGS_AND_ATTRIBUTES(0),
+            Some(&mut info),
+            std::mem::size_of::<SHFILEINFOW>() as u32,
+            SHGFI_PIDL | SHGFI_DISPLAYNAME | SHGFI_TYPENAME,
+        );
+        ILFree(Some(pidl));
+        found
+    };
+    if found == 0 {
+        return Err(Error::new(E_FAIL, format!("The shell has no name for {}", name).into()));
+    }
+    Ok(DisplayName {
+        path: path.to_string(),
+        ok: true,
+        display_name: from_wide(&info.szDisplayName),
+        type_name: from_wide(&info.szTypeName),
+    })
+}
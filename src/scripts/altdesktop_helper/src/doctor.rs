@@ -0,0 +1,317 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::fs::{self, OpenOptions};
+#[cfg(windows)]
+use std::io::Write;
+#[cfg(windows)]
+use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use std::time::Instant;
+
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::System::Com::*,
+    Win32::System::Registry::HKEY_LOCAL_MACHINE,
+    Win32::System::SystemInformation::{GetSystemDirectoryW, GetWindowsDirectoryW},
+    Win32::UI::Shell::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper doctor [--cache-dir <dir>]
+
+Checks what the helper depends on and prints a health report,
+{\"ok\",\"version\",\"checks\":[{\"name\",\"status\",\"message\",\"details\"}]}, for
+attaching to a bug report. status is pass, warn (works, but something may fail) or
+fail; ok is whether nothing failed, and the exit code says the same. The checks:
+  com                  COM runs in a single-threaded apartment and creates a shell
+                       link
+  shell                shell32's version and which shell interfaces can be created
+  cacheDirectory       the icon cache can be written: --cache-dir, else
+                       %APPDATA%\\AltDesktop, and the temp folder
+  shortcutDirectories  shortcuts can be written to the Desktop and Start Menu, and
+                       whether the Public Desktop needs elevation
+  longPaths            whether Windows' LongPathsEnabled policy is on
+  iconExtraction       explorer.exe's icon extracts to a PNG, and how long it took
+Nothing is left behind: files written to test access are deleted.";
+
+#[cfg(windows)]
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    message: String,
+    #[serde(skip_serializing_if = "Value::is_null")]
+    details: Value,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct Report {
+    ok: bool,
+    version: &'static str,
+    checks: Vec<Check>,
+}
+
+// Every class is created with CLSCTX_ALL, since the wallpaper one lives in Explorer
+#[cfg(windows)]
+const SHELL_CLASSES: &[(&str, &GUID, bool)] = &[
+    ("IShellLinkW", &ShellLink, true),
+    ("IKnownFolderManager", &KnownFolderManager, true),
+    ("IThumbnailCache", &LocalThumbnailCache, false),
+    ("ICustomDestinationList", &DestinationList, false),
+    ("IApplicationDestinations", &ApplicationDestinations, false),
+    ("IDesktopWallpaper", &DesktopWallpaper, false),
+];
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let cache_dir = match parse_args(&args) {
+        Ok(cache_dir) => cache_dir,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+
+    let checks = vec![
+        check_com(),
+        check_shell(),
+        check_cache_directory(cache_dir),
+        check_shortcut_directories(),
+        check_long_paths(),
+        check_icon_extraction(),
+    ];
+    let ok = checks.iter().all(|check| check.status != Status::Fail);
+    altdesktop_core::json::print(Report { ok, version: env!("CARGO_PKG_VERSION"), checks });
+    if ok { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("doctor")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<Option<PathBuf>, String> {
+    let mut cache_dir = None;
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--cache-dir" => cache_dir = Some(PathBuf::from(iter.next().ok_or("--cache-dir requires a value")?)),
+            flag => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+    Ok(cache_dir)
+}
+
+#[cfg(windows)]
+fn check(name: &'static str, status: Status, message: impl Into<String>, details: Value) -> Check {
+    Check { name, status, message: message.into(), details }
+}
+
+// The helper initialized COM before running the tool, as it does for every tool
+#[cfg(windows)]
+fn check_com() -> Check {
+    let mut apartment = APTTYPE::default();
+    let mut qualifier = APTTYPEQUALIFIER::default();
+    if let Err(error) = unsafe { CoGetApartmentType(&mut apartment, &mut qualifier) } {
+        return check("com", Status::Fail, format!("COM isn't initialized: {}", error.message()), Value::Null);
+    }
+    let name = match apartment {
+        APTTYPE_STA => "sta",
+        APTTYPE_MAINSTA => "mainSta",
+        APTTYPE_MTA => "mta",
+        APTTYPE_NA => "neutral",
+        _ => "unknown",
+    };
+    let details = json!({ "apartment": name });
+    if apartment != APTTYPE_STA && apartment != APTTYPE_MAINSTA {
+        return check("com", Status::Fail, "The shell APIs need a single-threaded apartment", details);
+    }
+    match unsafe { CoCreateInstance::<_, IShellLinkW>(&ShellLink, None, CLSCTX_INPROC_SERVER) } {
+        Ok(_) => check("com", Status::Pass, "COM is available", details),
+        Err(error) => check("com", Status::Fail, format!("Creating a shell link failed: {}", error.message()), details),
+    }
+}
+
+#[cfg(windows)]
+fn check_shell() -> Check {
+    let shell32 = system_directory().join("shell32.dll");
+    let version = crate::fileinfo::file_version(&shell32);
+    let mut interfaces = serde_json::Map::new();
+    let mut missing_required = Vec::new();
+    let mut missing = Vec::new();
+    for (name, class, required) in SHELL_CLASSES {
+        let created = unsafe { CoCreateInstance::<_, IUnknown>(*class, None, CLSCTX_ALL) }.is_ok();
+        interfaces.insert(name.to_string(), created.into());
+        if !created {
+            if *required { missing_required.push(*name) } else { missing.push(*name) }
+        }
+    }
+    // Thumbnails and jumbo icons go through a shell item rather than a class of their own
+    let image_factory =
+        unsafe { SHCreateItemFromParsingName::<_, _, IShellItemImageFactory>(&HSTRING::from(shell32.as_path()), None) };
+    interfaces.insert("IShellItemImageFactory".to_string(), image_factory.is_ok().into());
+    if image_factory.is_err() {
+        missing_required.push("IShellItemImageFactory");
+    }
+
+    let details = json!({ "shell32": version, "interfaces": interfaces });
+    if !missing_required.is_empty() {
+        return check("shell", Status::Fail, format!("Missing {}", missing_required.join(", ")), details);
+    }
+    if !missing.is_empty() {
+        let message = format!("Missing {}; the features using them will fail", missing.join(", "));
+        return check("shell", Status::Warn, message, details);
+    }
+    check("shell", Status::Pass, "Every shell interface the helper uses is available", details)
+}
+
+#[cfg(windows)]
+fn check_cache_directory(cache_dir: Option<PathBuf>) -> Check {
+    let cache_dir = cache_dir.unwrap_or_else(|| {
+        std::env::var_os("APPDATA").map(PathBuf::from).unwrap_or_default().join("AltDesktop")
+    });
+    let results = vec![write_access(&cache_dir), write_access(&std::env::temp_dir())];
+    let failed: Vec<&str> = results.iter().filter(|result| !result.writable).map(|result| result.path.as_str()).collect();
+    let details = json!(results);
+    if failed.is_empty() {
+        check("cacheDirectory", Status::Pass, "The cache and temp folders can be written", details)
+    } else {
+        check("cacheDirectory", Status::Fail, format!("Can't write to {}", failed.join(", ")), details)
+    }
+}
+
+#[cfg(windows)]
+fn check_shortcut_directories() -> Check {
+    let folder = |token: &str| create_shortcut::known_folders::expand_known_folder(token).ok().map(PathBuf::from);
+    let mut results = Vec::new();
+    let mut failed = Vec::new();
+    for token in ["{Desktop}", "{Programs}"] {
+        match folder(token) {
+            Some(path) => {
+                let result = write_access(&path);
+                if !result.writable {
+                    failed.push(result.path.clone());
+                }
+                results.push(result);
+            }
+            None => failed.push(token.to_string()),
+        }
+    }
+    // Only elevated processes can write here, which --elevate is for
+    let public = folder("{PublicDesktop}").map(|path| write_access(&path));
+    let public_writable = public.as_ref().is_some_and(|result| result.writable);
+    let details = json!({ "folders": results, "publicDesktop": public });
+    if !failed.is_empty() {
+        return check("shortcutDirectories", Status::Fail, format!("Can't write to {}", failed.join(", ")), details);
+    }
+    let message = if public_writable {
+        "Shortcut folders can be written, the Public Desktop included"
+    } else {
+        "Shortcut folders can be written; the Public Desktop needs --elevate"
+    };
+    check("shortcutDirectories", Status::Pass, message, details)
+}
+
+#[cfg(windows)]
+fn check_long_paths() -> Check {
+    let enabled = crate::registry::read_dword(
+        HKEY_LOCAL_MACHINE,
+        &HSTRING::from(r"SYSTEM\CurrentControlSet\Control\FileSystem"),
+        w!("LongPathsEnabled"),
+    );
+    let details = json!({ "longPathsEnabled": enabled.map(|value| value != 0) });
+    if enabled.is_some_and(|value| value != 0) {
+        check("longPaths", Status::Pass, "Long paths are enabled", details)
+    } else {
+        // The helper writes \\?\ paths itself; Explorer and the apps a shortcut opens may not
+        let message = "LongPathsEnabled is off: files past 260 characters work in the helper but may not open elsewhere";
+        check("longPaths", Status::Warn, message, details)
+    }
+}
+
+#[cfg(windows)]
+fn check_icon_extraction() -> Check {
+    let input = windows_directory().join("explorer.exe");
+    let output = std::env::temp_dir().join(format!("altdesktop-doctor-{}.png", std::process::id()));
+    let start = Instant::now();
+    let result = icon_extractor::extract_json(json!({
+        "input": input.to_string_lossy(),
+        "output": output.to_string_lossy(),
+        "size": 256,
+    }));
+    let elapsed = start.elapsed().as_millis() as u64;
+    let written = fs::metadata(&output).is_ok_and(|metadata| metadata.len() > 0);
+    let _ = fs::remove_file(&output);
+    match result {
+        Ok(_) if written => {
+            let details = json!({ "input": input, "elapsed": elapsed });
+            check("iconExtraction", Status::Pass, "explorer.exe's icon was extracted", details)
+        }
+        Ok(_) => {
+            let details = json!({ "input": input, "elapsed": elapsed });
+            check("iconExtraction", Status::Fail, "Extraction reported success but wrote nothing", details)
+        }
+        Err(error) => {
+            let details = json!({ "input": input, "elapsed": elapsed, "error": error });
+            check("iconExtraction", Status::Fail, "explorer.exe's icon couldn't be extracted", details)
+        }
+    }
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct Access {
+    path: String,
+    writable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+// A folder that isn't there yet counts as writable when the one it would be made in is
+#[cfg(windows)]
+fn write_access(dir: &Path) -> Access {
+    let existing = dir.ancestors().find(|ancestor| ancestor.is_dir()).unwrap_or(dir);
+    let probe = existing.join(format!(".altdesktop-doctor-{}.tmp", std::process::id()));
+    let written = OpenOptions::new().write(true).create_new(true).open(&probe).and_then(|mut file| file.write_all(b"ok"));
+    let _ = fs::remove_file(&probe);
+    Access {
+        path: dir.to_string_lossy().into_owned(),
+        writable: written.is_ok(),
+        message: written.err().map(|error| error.to_string()),
+    }
+}
+
+#[cfg(windows)]
+fn system_directory() -> PathBuf {
+    let mut buffer = [0u16; 260];
+    let length = unsafe { GetSystemDirectoryW(Some(&mut buffer)) } as usize;
+    PathBuf::from(String::from_utf16_lossy(&buffer[..length.min(buffer.len())]))
+}
+
+#[cfg(windows)]
+fn windows_directory() -> PathBuf {
+    let mut buffer = [0u16; 260];
+    let length = unsafe { GetWindowsDirectoryW(Some(&mut buffer)) } as usize;
+    PathBuf::from(String::from_utf16_lossy(&buffer[..length.min(buffer.len())]))
+}
@@ -0,0 +1,271 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::{self, Read};
+#[cfg(windows)]
+use std::path::PathBuf;
+#[cfg(windows)]
+use std::thread;
+
+#[cfg(windows)]
+use altdesktop_core::com::ComGuard;
+#[cfg(windows)]
+use altdesktop_core::json::emit;
+#[cfg(windows)]
+use altdesktop_core::wide::from_wide;
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{HWND, LPARAM, LRESULT, MAX_PATH, WPARAM},
+    Win32::Storage::FileSystem::*,
+    Win32::UI::Shell::{SHGetFileInfoW, SHFILEINFOW, SHGFI_DISPLAYNAME},
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper drives list [--icons <dir>] [--icon-size <px>]
+  altdesktop-helper drives watch [--icons <dir>] [--icon-size <px>]
+
+list prints [{\"root\",\"type\",\"label\",\"displayName\",\"fileSystem\",\"ready\",\"total\",
+\"free\",\"icon\"}] for every drive letter in use: type is fixed, removable, network,
+optical, ram or unknown; displayName is what Explorer calls the drive, such as
+Local Disk (C:); total and free are in bytes, free being what this user may still
+write. A drive that isn't ready, such as a card reader without a card, has null
+label, fileSystem, total and free. --icons writes each drive's icon to
+<dir>\\drive-<letter>.png at --icon-size pixels (default 32) and puts its path in icon.
+watch prints {\"type\":\"ready\",\"drives\"} with what list prints, then
+{\"type\":\"changed\",\"changes\":[{\"root\",\"change\"}],\"drives\"} whenever a drive
+is added, removed, or has its media inserted, ejected or relabeled, where change is
+added, removed or changed, until stdin closes.";
+
+#[cfg(windows)]
+const CLASS_NAME: PCWSTR = w!("AltDesktopHelperDrives");
+// Plugging in a stick sends an arrival for each of its volumes, then Explorer's
+// own refresh; they are read once the burst is over
+#[cfg(windows)]
+const SETTLE_TIMER: usize = 1;
+#[cfg(windows)]
+const SETTLE_MILLISECONDS: u32 = 300;
+#[cfg(windows)]
+const DEFAULT_ICON_SIZE: u32 = 32;
+// GetDriveType's results, which the windows crate keeps in another feature
+#[cfg(windows)]
+const DRIVE_TYPES: &[(u32, &str)] = &[(2, "removable"), (3, "fixed"), (4, "network"), (5, "optical"), (6, "ram")];
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Drive {
+    root: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    label: Option<String>,
+    display_name: Option<String>,
+    file_system: Option<String>,
+    ready: bool,
+    total: Option<u64>,
+    free: Option<u64>,
+    icon: Option<PathBuf>,
+}
+
+#[cfg(windows)]
+struct Icons {
+    dir: PathBuf,
+    size: u32,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let watch = args.get(1).is_some_and(|arg| arg == "--watch");
+    let icons = match parse_icons(&args[1 + watch as usize..]) {
+        Ok(icons) => icons,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    if watch { watch_until_stdin_closes(icons) } else { tool::finish(list_drives(icons.as_ref())) }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("drives")
+}
+
+#[cfg(windows)]
+fn parse_icons(args: &[String]) -> std::result::Result<Option<Icons>, String> {
+    let mut dir = None;
+    let mut size = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--icons" => dir = Some(value()?),
+            "--icon-size" => {
+                let value = value()?;
+                size = Some(match value.parse() {
+                    Ok(size @ 1..=256) => size,
+                    _ => return Err(format!("Invalid icon size: {} (expected 1 to 256 pixels)", value)),
+                });
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    match (dir, size) {
+        (None, Some(_)) => Err("--icon-size requires --icons".to_string()),
+        (None, None) => Ok(None),
+        (Some(dir), size) => {
+            let dir = std::path::absolute(dir).map_err(|e| e.to_string())?;
+            Ok(Some(Icons { dir, size: size.unwrap_or(DEFAULT_ICON_SIZE) }))
+        }
+    }
+}
+
+#[cfg(windows)]
+fn list_drives(icons: Option<&Icons>) -> Result<Vec<Drive>> {
+    let length = unsafe { GetLogicalDriveStringsW(None) };
+    let mut buffer = vec![0u16; length as usize];
+    let length = unsafe { GetLogicalDriveStringsW(Some(&mut buffer)) };
+    if length == 0 {
+        return Err(Error::from_win32());
+    }
+    // Roots such as C:\ one after another, each ending in a NUL
+    Ok(buffer[..length as usize]
+        .split(|&unit| unit == 0)
+        .filter(|root| !root.is_empty())
+        .map(|root| describe(String::from_utf16_lossy(root), icons))
+        .collect())
+}
+
+#[cfg(windows)]
+fn describe(root: String, icons: Option<&Icons>) -> Drive {
+    let path = HSTRING::from(root.as_str());
+    let drive_type = unsafe { GetDriveTypeW(&path) };
+    let kind = DRIVE_TYPES.iter().find(|(value, _)| *value == drive_type).map_or("unknown", |(_, name)| name);
+
+    let mut label = [0u16; MAX_PATH as usize + 1];
+    let mut file_system = [0u16; MAX_PATH as usize + 1];
+    // Fails with ERROR_NOT_READY for an empty card reader or optical drive
+    let ready = unsafe {
+        GetVolumeInformationW(&path, Some(&mut label), None, None, None, Some(&mut file_system))
+    }
+    .is_ok();
+    let (mut free, mut total) = (0u64, 0u64);
+    let space = ready && unsafe { GetDiskFreeSpaceExW(&path, Some(&mut free), Some(&mut total), None) }.is_ok();
+
+    let icon = icons.and_then(|icons| {
+        let output = icons.dir.join(format!("drive-{}.png", root.trim_end_matches(['\\', ':'])));
+        let job = json!({ "input": root, "output": output, "size": icons.size });
+        icon_extractor::extract_json(job).ok().map(|_| output)
+    });
+    Drive {
+        kind,
+        label: ready.then(|| from_wide(&label)),
+        display_name: display_name(&path),
+        file_system: ready.then(|| from_wide(&file_system)).filter(|name| !name.is_empty()),
+        ready,
+        total: space.then_some(total),
+        free: space.then_some(free),
+        icon,
+        root,
+    }
+}
+
+#[cfg(windows)]
+fn display_name(path: &HSTRING) -> Option<String> {
+    let mut info = SHFILEINFOW::default();
+    let found = unsafe {
+        SHGetFileInfoW(path, FILE_FLAGS_AND_ATTRIBUTES(0), Some(&mut info), std::mem::size_of::<SHFILEINFOW>() as u32, SHGFI_DISPLAYNAME)
+    };
+    Some(from_wide(&info.szDisplayName)).filter(|name| found != 0 && !name.is_empty())
+}
+
+#[cfg(windows)]
+fn watch_until_stdin_closes(icons: Option<Icons>) -> ExitCode {
+    // The window belongs to the thread that pumps its messages
+    thread::spawn(move || watch(icons));
+    let _ = io::stdin().read_to_end(&mut Vec::new());
+    ExitCode::SUCCESS
+}
+
+#[cfg(windows)]
+fn watch(icons: Option<Icons>) {
+    // The shell's icon lookups need an STA on this thread too
+    let _com = ComGuard::apartment();
+    let window = match tool::hidden_window(CLASS_NAME, Some(window_proc)) {
+        Ok(window) => window,
+        Err(error) => {
+            // Nothing would ever be reported, so don't leave the caller waiting
+            tool::exit_with(error);
+        }
+    };
+
+    let mut drives = list_drives(icons.as_ref()).unwrap_or_default();
+    if !emit(json!({ "type": "ready", "drives": drives })) {
+        return;
+    }
+    let mut message = MSG::default();
+    while unsafe { GetMessageW(&mut message, HWND(0), 0, 0) }.as_bool() {
+        if message.message != WM_TIMER || message.wParam.0 != SETTLE_TIMER {
+            unsafe { DispatchMessageW(&message) };
+            continue;
+        }
+        unsafe {
+            let _ = KillTimer(window, SETTLE_TIMER);
+        }
+        let current = list_drives(icons.as_ref()).unwrap_or_default();
+        let changes = changes(&drives, &current);
+        if changes.is_empty() {
+            continue;
+        }
+        if !emit(json!({ "type": "changed", "changes": changes, "drives": current })) {
+            return;
+        }
+        drives = current;
+    }
+}
+
+// Free space moves all the time, so only what the drive is counts as a change
+#[cfg(windows)]
+fn changes(previous: &[Drive], current: &[Drive]) -> Vec<Value> {
+    let find = |drives: &[Drive], root: &str| drives.iter().position(|drive| drive.root == root);
+    let mut changes = Vec::new();
+    for drive in current {
+        match find(previous, &drive.root).map(|index| &previous[index]) {
+            None => changes.push(json!({ "root": drive.root, "change": "added" })),
+            Some(old) if (old.kind, old.ready, &old.label, &old.file_system, old.total)
+                != (drive.kind, drive.ready, &drive.label, &drive.file_system, drive.total) =>
+            {
+                changes.push(json!({ "root": drive.root, "change": "changed" }))
+            }
+            Some(_) => {}
+        }
+    }
+    for drive in previous {
+        if find(current, &drive.root).is_none() {
+            changes.push(json!({ "root": drive.root, "change": "removed" }));
+        }
+    }
+    changes
+}
+
+// Network drives and media changes arrive as other WM_DEVICECHANGE events than volume
+// arrivals, so any of them restarts the settle timer, and the loop compares the lists
+#[cfg(windows)]
+extern "system" fn window_proc(window: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if message == WM_DEVICECHANGE {
+        unsafe { SetTimer(window, SETTLE_TIMER, SETTLE_MILLISECONDS, None) };
+        return LRESULT(1);
+    }
+    unsafe { DefWindowProcW(window, message, wparam, lparam) }
+}
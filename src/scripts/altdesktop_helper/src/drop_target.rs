@@ -0,0 +1,565 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::cell::Cell;
+#[cfg(windows)]
+use std::ffi::c_void;
+#[cfg(windows)]
+use std::sync::atomic::{AtomicU32, Ordering};
+#[cfg(windows)]
+use std::sync::mpsc::Receiver;
+
+#[cfg(windows)]
+use altdesktop_core::error::ErrorReport;
+#[cfg(windows)]
+use serde::{Deserialize, Serialize};
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{E_INVALIDARG, E_NOINTERFACE, E_POINTER, HWND, LPARAM, LRESULT, POINT, POINTL, RECT, S_OK, WPARAM},
+    Win32::Graphics::Gdi::{ClientToScreen, ScreenToClient},
+    Win32::System::Com::{IDataObject, DVASPECT_CONTENT, FORMATETC, TYMED_HGLOBAL},
+    Win32::System::DataExchange::RegisterClipboardFormatW,
+    Win32::System::LibraryLoader::GetModuleHandleW,
+    Win32::System::Memory::{GlobalLock, GlobalSize, GlobalUnlock},
+    Win32::System::Ole::*,
+    Win32::System::SystemServices::*,
+    Win32::UI::Shell::{IShellItemArray, SHCreateShellItemArrayFromDataObject, SIGDN_DESKTOPABSOLUTEPARSING},
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper drop-target attach <hwnd>
+
+Accepts OLE drops onto <hwnd>'s client area, with data HTML5 drag events lose:
+shell items such as files inside a zip folder, virtual files dragged out of Outlook,
+and which keys are held. Windows only lets a process accept drops on its own windows,
+so drops land on a nearly invisible window of the helper's laid over <hwnd> while it
+is armed: write \"arm\" on a line when the page sees a dragenter, and it takes over
+from the next mouse move. It disarms itself once the drag leaves or drops, after a
+click, or 2 seconds after arming if no drag arrives; \"disarm\" does so at once.
+Prints {\"type\":\"ready\"}, then {\"type\":\"armed\"} and {\"type\":\"disarmed\"},
+{\"type\":\"enter\"} and {\"type\":\"over\"} with x, y in <hwnd>'s client pixels, keys
+held (ctrl, shift, alt and the mouse buttons), the effects the source allows and the
+chosen effect, {\"type\":\"leave\"}, and {\"type\":\"drop\"} adding files, items (the
+parsing names of every shell item), text, url and virtualFiles [{\"name\",\"size\"}].
+Runs until stdin closes. <hwnd> is decimal or 0x-prefixed hex.";
+
+#[cfg(windows)]
+const CLASS_NAME: PCWSTR = w!("AltDesktopHelperDropTarget");
+#[cfg(windows)]
+const WM_REQUEST: u32 = WM_APP + 1;
+#[cfg(windows)]
+const WM_OVERLAY_CLICKED: u32 = WM_APP + 2;
+#[cfg(windows)]
+const ARM_TIMER: usize = 1;
+#[cfg(windows)]
+const ARM_TIMEOUT_MS: u32 = 2000;
+// The lowest alpha that still takes the mouse
+#[cfg(windows)]
+const OVERLAY_ALPHA: u8 = 1;
+#[cfg(windows)]
+const MK_ALT: u32 = 0x20;
+#[cfg(windows)]
+const FD_FILESIZE: u32 = 0x40;
+
+#[cfg(windows)]
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Request {
+    Arm,
+    Disarm,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct VirtualFile {
+    name: String,
+    size: Option<u64>,
+}
+
+/// A hand-rolled IDropTarget: the vtable pointer has to come first.
+#[cfg(windows)]
+#[repr(C)]
+struct DropTarget {
+    vtable: *const IDropTarget_Vtbl,
+    references: AtomicU32,
+    target: HWND,
+    overlay: HWND,
+    // What the last DragOver reported, so only changes are printed
+    last: Cell<(i32, i32, u32, u32)>,
+}
+
+#[cfg(windows)]
+static VTABLE: IDropTarget_Vtbl = IDropTarget_Vtbl {
+    base__: IUnknown_Vtbl { QueryInterface: query_interface, AddRef: add_ref, Release: release },
+    DragEnter: drag_enter,
+    DragOver: drag_over,
+    DragLeave: drag_leave,
+    Drop: drag_drop,
+};
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let target = match tool::string_args(args).and_then(|args| parse_args(&args)) {
+        Ok(target) => target,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    if !unsafe { IsWindow(target) }.as_bool() {
+        return tool::finish::<()>(Err(Error::new(E_INVALIDARG, format!("0x{:X} is not a window", target.0).into())));
+    }
+    // RegisterDragDrop needs OLE on top of the apartment main set up
+    if let Err(error) = unsafe { OleInitialize(None) } {
+        return tool::finish::<()>(Err(error));
+    }
+    let code = match create_overlay(target) {
+        Ok(overlay) => serve(target, overlay),
+        Err(error) => tool::finish::<()>(Err(error)),
+    };
+    unsafe { OleUninitialize() };
+    code
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("drop-target")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<HWND, String> {
+    match args {
+        [_, flag] if flag.starts_with("--") => Err(format!("Unknown option: {}", flag)),
+        [_, hwnd] => {
+            let handle = match hwnd.strip_prefix("0x").or_else(|| hwnd.strip_prefix("0X")) {
+                Some(hex) => isize::from_str_radix(hex, 16),
+                None => hwnd.parse::<isize>(),
+            };
+            match handle {
+                Ok(handle) if handle != 0 => Ok(HWND(handle)),
+                _ => Err(format!("Invalid hwnd: {}", hwnd)),
+            }
+        }
+        _ => Err("Expected <hwnd>".to_string()),
+    }
+}
+
+// Owned by the target, so it stays above it, and never activated
+#[cfg(windows)]
+fn create_overlay(target: HWND) -> Result<HWND> {
+    unsafe {
+        let instance = GetModuleHandleW(None)?;
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(overlay_proc),
+            hInstance: instance.into(),
+            lpszClassName: CLASS_NAME,
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            return Err(Error::from_win32());
+        }
+        let overlay = CreateWindowExW(
+            WS_EX_LAYERED | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+            CLASS_NAME,
+            None,
+            WS_POPUP,
+            0,
+            0,
+            0,
+            0,
+            target,
+            None,
+            instance,
+            None,
+        );
+        if overlay.0 == 0 {
+            return Err(Error::from_win32());
+        }
+        SetLayeredWindowAttributes(overlay, None, OVERLAY_ALPHA, LWA_ALPHA)?;
+        Ok(overlay)
+    }
+}
+
+#[cfg(windows)]
+extern "system" fn overlay_proc(window: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    unsafe {
+        match message {
+            WM_MOUSEACTIVATE => LRESULT(MA_NOACTIVATE as isize),
+            // A real click means no drag is coming; the overlay mustn't swallow more
+            WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN => {
+                let _ = PostMessageW(window, WM_OVERLAY_CLICKED, WPARAM(0), LPARAM(0));
+                LRESULT(0)
+            }
+            _ => DefWindowProcW(window, message, wparam, lparam),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn serve(target: HWND, overlay: HWND) -> ExitCode {
+    let state = Box::new(DropTarget {
+        vtable: &VTABLE,
+        references: AtomicU32::new(1),
+        target,
+        overlay,
+        last: Cell::new((0, 0, 0, 0)),
+    });
+    let drop_target: IDropTarget = unsafe { IDropTarget::from_raw(Box::into_raw(state) as *mut c_void) };
+    if let Err(error) = unsafe { RegisterDragDrop(overlay, &drop_target) } {
+        unsafe {
+            let _ = DestroyWindow(overlay);
+        }
+        return tool::finish::<()>(Err(error));
+    }
+    emit(json!({ "type": "ready" }));
+
+    let requests = tool::read_requests(overlay, WM_REQUEST);
+    pump(target, overlay, requests);
+    unsafe {
+        let _ = RevokeDragDrop(overlay);
+        let _ = DestroyWindow(overlay);
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(windows)]
+fn pump(target: HWND, overlay: HWND, requests: Receiver<Option<std::result::Result<Request, String>>>) {
+    let mut message = MSG::default();
+    while unsafe { GetMessageW(&mut message, HWND(0), 0, 0) }.as_bool() {
+        match message.message {
+            WM_REQUEST => {
+                while let Ok(request) = requests.try_recv() {
+                    match request {
+                        Some(Ok(Request::Arm)) => match arm(target, overlay) {
+                            Ok(()) => emit(json!({ "type": "armed" })),
+                            Err(error) => emit(error_line(&error)),
+                        },
+                        Some(Ok(Request::Disarm)) => disarm(overlay),
+                        Some(Err(message)) => {
+                            let mut line =
+                                serde_json::to_value(ErrorReport::usage(&format!("Invalid request: {}", message))).unwrap();
+                            line["type"] = Value::from("error");
+                            emit(line);
+                        }
+                        None => return,
+                    }
+                }
+            }
+            WM_OVERLAY_CLICKED => disarm(overlay),
+            WM_TIMER if message.hwnd == overlay && message.wParam.0 == ARM_TIMER => disarm(overlay),
+            _ => unsafe {
+                let _ = TranslateMessage(&message);
+                DispatchMessageW(&message);
+            },
+        }
+    }
+}
+
+// Covers the target's client area as it is now, above it but not activated
+#[cfg(windows)]
+fn arm(target: HWND, overlay: HWND) -> Result<()> {
+    let mut client = RECT::default();
+    let mut origin = POINT::default();
+    unsafe {
+        GetClientRect(target, &mut client)?;
+        let _ = ClientToScreen(target, &mut origin);
+        SetWindowPos(
+            overlay,
+            HWND_TOP,
+            origin.x,
+            origin.y,
+            client.right - client.left,
+            client.bottom - client.top,
+            SWP_NOACTIVATE | SWP_SHOWWINDOW,
+        )?;
+        if SetTimer(overlay, ARM_TIMER, ARM_TIMEOUT_MS, None) == 0 {
+            return Err(Error::from_win32());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn disarm(overlay: HWND) {
+    unsafe {
+        let _ = KillTimer(overlay, ARM_TIMER);
+        if IsWindowVisible(overlay).as_bool() {
+            let _ = ShowWindow(overlay, SW_HIDE);
+            emit(json!({ "type": "disarmed" }));
+        }
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn query_interface(this: *mut c_void, iid: *const GUID, interface: *mut *mut c_void) -> HRESULT {
+    if iid.is_null() || interface.is_null() {
+        return E_POINTER;
+    }
+    unsafe {
+        if *iid == IUnknown::IID || *iid == IDropTarget::IID {
+            *interface = this;
+            add_ref(this);
+            S_OK
+        } else {
+            *interface = std::ptr::null_mut();
+            E_NOINTERFACE
+        }
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn add_ref(this: *mut c_void) -> u32 {
+    let state = unsafe { &*(this as *const DropTarget) };
+    state.references.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn release(this: *mut c_void) -> u32 {
+    let remaining = unsafe { &*(this as *const DropTarget) }.references.fetch_sub(1, Ordering::Release) - 1;
+    if remaining == 0 {
+        std::sync::atomic::fence(Ordering::Acquire);
+        drop(unsafe { Box::from_raw(this as *mut DropTarget) });
+    }
+    remaining
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn drag_enter(
+    this: *mut c_void,
+    _data: *mut c_void,
+    keys: MODIFIERKEYS_FLAGS,
+    point: POINTL,
+    effect: *mut DROPEFFECT,
+) -> HRESULT {
+    let state = unsafe { &*(this as *const DropTarget) };
+    unsafe {
+        let _ = KillTimer(state.overlay, ARM_TIMER);
+    }
+    let (line, chosen) = state.describe("enter", keys, point, unsafe { *effect });
+    unsafe { *effect = chosen };
+    emit(line);
+    S_OK
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn drag_over(this: *mut c_void, keys: MODIFIERKEYS_FLAGS, point: POINTL, effect: *mut DROPEFFECT) -> HRESULT {
+    let state = unsafe { &*(this as *const DropTarget) };
+    let allowed = unsafe { *effect };
+    let (line, chosen) = state.describe("over", keys, point, allowed);
+    unsafe { *effect = chosen };
+    // Called on every tick of the drag loop, moving or not
+    let client = state.client_point(point);
+    let current = (client.x, client.y, keys.0, chosen.0);
+    if state.last.replace(current) != current {
+        emit(line);
+    }
+    S_OK
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn drag_leave(this: *mut c_void) -> HRESULT {
+    let state = unsafe { &*(this as *const DropTarget) };
+    emit(json!({ "type": "leave" }));
+    disarm(state.overlay);
+    S_OK
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn drag_drop(
+    this: *mut c_void,
+    data: *mut c_void,
+    keys: MODIFIERKEYS_FLAGS,
+    point: POINTL,
+    effect: *mut DROPEFFECT,
+) -> HRESULT {
+    let state = unsafe { &*(this as *const DropTarget) };
+    let (mut line, chosen) = state.describe("drop", keys, point, unsafe { *effect });
+    unsafe { *effect = chosen };
+    if let Some(data) = unsafe { IDataObject::from_raw_borrowed(&data) } {
+        line["files"] = json!(dropped_files(data));
+        line["items"] = json!(shell_items(data));
+        line["text"] = json!(read_text(data, CF_UNICODETEXT.0 as u32));
+        line["url"] = json!(read_text(data, unsafe { RegisterClipboardFormatW(w!("UniformResourceLocatorW")) }));
+        line["virtualFiles"] = json!(virtual_files(data));
+    }
+    emit(line);
+    disarm(state.overlay);
+    S_OK
+}
+
+#[cfg(windows)]
+impl DropTarget {
+    fn client_point(&self, point: POINTL) -> POINT {
+        let mut client = POINT { x: point.x, y: point.y };
+        unsafe {
+            let _ = ScreenToClient(self.target, &mut client);
+        }
+        client
+    }
+
+    // The event line, and the effect to answer with: Ctrl copies, Shift moves and
+    // Alt or Ctrl+Shift links, like Explorer, falling back to whatever the source allows
+    fn describe(&self, kind: &str, keys: MODIFIERKEYS_FLAGS, point: POINTL, allowed: DROPEFFECT) -> (Value, DROPEFFECT) {
+        let ctrl = keys.0 & MK_CONTROL.0 != 0;
+        let shift = keys.0 & MK_SHIFT.0 != 0;
+        let alt = keys.0 & MK_ALT != 0;
+        let wanted = match (ctrl, shift, alt) {
+            (true, true, _) | (_, _, true) => DROPEFFECT_LINK,
+            (false, true, _) => DROPEFFECT_MOVE,
+            _ => DROPEFFECT_COPY,
+        };
+        let chosen = [wanted, DROPEFFECT_COPY, DROPEFFECT_MOVE, DROPEFFECT_LINK]
+            .into_iter()
+            .find(|effect| allowed.0 & effect.0 != 0)
+            .unwrap_or(DROPEFFECT_NONE);
+
+        let mut held = Vec::new();
+        for (flag, name) in [
+            (MK_CONTROL.0, "ctrl"),
+            (MK_SHIFT.0, "shift"),
+            (MK_ALT, "alt"),
+            (MK_LBUTTON.0, "left"),
+            (MK_RBUTTON.0, "right"),
+            (MK_MBUTTON.0, "middle"),
+        ] {
+            if keys.0 & flag != 0 {
+                held.push(name);
+            }
+        }
+        let client = self.client_point(point);
+        let line = json!({
+            "type": kind,
+            "x": client.x,
+            "y": client.y,
+            "keys": held,
+            "allowed": effect_names(allowed),
+            "effect": effect_names(chosen).first(),
+        });
+        (line, chosen)
+    }
+}
+
+#[cfg(windows)]
+fn effect_names(effect: DROPEFFECT) -> Vec<&'static str> {
+    [(DROPEFFECT_COPY, "copy"), (DROPEFFECT_MOVE, "move"), (DROPEFFECT_LINK, "link")]
+        .into_iter()
+        .filter(|(flag, _)| effect.0 & flag.0 != 0)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+// A format's bytes, when the source offers it in global memory
+#[cfg(windows)]
+fn read_global(data: &IDataObject, format: u32) -> Option<Vec<u8>> {
+    let request = FORMATETC {
+        cfFormat: format as u16,
+        ptd: std::ptr::null_mut(),
+        dwAspect: DVASPECT_CONTENT.0,
+        lindex: -1,
+        tymed: TYMED_HGLOBAL.0 as u32,
+    };
+    unsafe {
+        let mut medium = data.GetData(&request).ok()?;
+        let global = medium.u.hGlobal;
+        let locked = GlobalLock(global);
+        let bytes = (!locked.is_null()).then(|| std::slice::from_raw_parts(locked as *const u8, GlobalSize(global)).to_vec());
+        if !locked.is_null() {
+            let _ = GlobalUnlock(global);
+        }
+        ReleaseStgMedium(&mut medium);
+        bytes
+    }
+}
+
+#[cfg(windows)]
+fn wide_until_null(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).take_while(|&unit| unit != 0).collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(windows)]
+fn read_text(data: &IDataObject, format: u32) -> Option<String> {
+    read_global(data, format).map(|bytes| wide_until_null(&bytes)).filter(|text| !text.is_empty())
+}
+
+// CF_HDROP: a DROPFILES header, then the paths, each null-terminated
+#[cfg(windows)]
+fn dropped_files(data: &IDataObject) -> Vec<String> {
+    let Some(bytes) = read_global(data, CF_HDROP.0 as u32) else {
+        return Vec::new();
+    };
+    let Some(header) = bytes.get(..std::mem::size_of::<windows::Win32::UI::Shell::DROPFILES>()) else {
+        return Vec::new();
+    };
+    let offset = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let wide = u32::from_le_bytes(header[16..20].try_into().unwrap()) != 0;
+    let Some(list) = bytes.get(offset..) else {
+        return Vec::new();
+    };
+    let names: Vec<String> = if wide {
+        let units: Vec<u16> = list.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        units.split(|&unit| unit == 0).map(String::from_utf16_lossy).collect()
+    } else {
+        list.split(|&byte| byte == 0).map(|name| String::from_utf8_lossy(name).into_owned()).collect()
+    };
+    names.into_iter().take_while(|name| !name.is_empty()).collect()
+}
+
+#[cfg(windows)]
+fn shell_items(data: &IDataObject) -> Vec<String> {
+    let mut items = Vec::new();
+    unsafe {
+        let Ok(array) = SHCreateShellItemArrayFromDataObject::<_, IShellItemArray>(data) else {
+            return items;
+        };
+        for index in 0..array.GetCount().unwrap_or(0) {
+            if let Ok(name) = array.GetItemAt(index).and_then(|item| item.GetDisplayName(SIGDN_DESKTOPABSOLUTEPARSING)) {
+                items.push(tool::take_string(name));
+            }
+        }
+    }
+    items
+}
+
+// FileGroupDescriptorW: a count, then a FILEDESCRIPTORW per file, whose contents
+// the source only hands over on request
+#[cfg(windows)]
+fn virtual_files(data: &IDataObject) -> Vec<VirtualFile> {
+    use windows::Win32::UI::Shell::FILEDESCRIPTORW;
+
+    let format = unsafe { RegisterClipboardFormatW(w!("FileGroupDescriptorW")) };
+    let Some(bytes) = read_global(data, format) else {
+        return Vec::new();
+    };
+    let count = bytes.get(..4).map_or(0, |count| u32::from_le_bytes(count.try_into().unwrap()) as usize);
+    let size = std::mem::size_of::<FILEDESCRIPTORW>();
+    bytes[4.min(bytes.len())..]
+        .chunks_exact(size)
+        .take(count)
+        .map(|chunk| {
+            let descriptor = unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const FILEDESCRIPTORW) };
+            let name = descriptor.cFileName;
+            let length = name.iter().position(|&unit| unit == 0).unwrap_or(name.len());
+            let size = (descriptor.dwFlags & FD_FILESIZE != 0)
+                .then_some(((descriptor.nFileSizeHigh as u64) << 32) | descriptor.nFileSizeLow as u64);
+            VirtualFile { name: String::from_utf16_lossy(&name[..length]), size }
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn error_line(error: &Error) -> Value {
+    let mut line = serde_json::to_value(ErrorReport::from_error(error)).unwrap();
+    line["type"] = Value::from("error");
+    line
+}
+
+// Closing stdin is what stops this tool, so output lost to a closed stdout is ignored
+#[cfg(windows)]
+fn emit(line: Value) {
+    let _ = altdesktop_core::json::emit(line);
+}
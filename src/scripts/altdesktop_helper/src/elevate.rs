@@ -0,0 +1,247 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::os::windows::io::FromRawHandle;
+use std::process::ExitCode;
+use std::thread;
+
+use windows::{
+    core::*,
+    Win32::Foundation::{CloseHandle, ERROR_CANCELLED, ERROR_PIPE_CONNECTED, GENERIC_READ, GENERIC_WRITE, HANDLE},
+    Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY},
+    Win32::Storage::FileSystem::*,
+    Win32::System::Console::{SetStdHandle, STD_ERROR_HANDLE, STD_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE},
+    Win32::System::Pipes::*,
+    Win32::System::Threading::*,
+    Win32::UI::Shell::*,
+    Win32::UI::WindowsAndMessaging::SW_HIDE,
+};
+
+use crate::tool;
+
+pub const CHILD_FLAG: &str = "--elevated-child";
+
+const BUFFER_SIZE: u32 = 64 * 1024;
+
+// The elevated copy's stdin, stdout and stderr, in the order it opens them
+const STREAMS: [(&str, STD_HANDLE); 3] =
+    [("in", STD_INPUT_HANDLE), ("out", STD_OUTPUT_HANDLE), ("err", STD_ERROR_HANDLE)];
+
+/// Runs `args` (a tool and its arguments) in an elevated copy of the helper, after
+/// the UAC prompt, and relays its stdin, stdout and stderr over pipes so the caller
+/// sees what an unelevated run would print. Returns the copy's exit code. Runs `run`
+/// in this process instead when it is elevated already.
+pub fn run_elevated(args: Vec<OsString>, run: fn(Vec<OsString>) -> ExitCode) -> ExitCode {
+    if is_elevated() {
+        return run(args);
+    }
+    match relay(&args) {
+        Ok(code) => ExitCode::from(code.min(255) as u8),
+        Err(error) => tool::finish::<()>(Err(error)),
+    }
+}
+
+/// In the elevated copy: connects stdin, stdout and stderr to the pipes of the
+/// process that started it, and moves to its working directory.
+pub fn attach_to_parent(pipe: &str, working_dir: &str) -> io::Result<()> {
+    for (suffix, std_handle) in STREAMS {
+        let access = if std_handle == STD_INPUT_HANDLE { GENERIC_READ } else { GENERIC_WRITE };
+        unsafe {
+            let handle = CreateFileW(
+                &HSTRING::from(format!("{}-{}", pipe, suffix)),
+                access.0,
+                FILE_SHARE_MODE(0),
+                None,
+                OPEN_EXISTING,
+                FILE_FLAGS_AND_ATTRIBUTES(0),
+                None,
+            )?;
+            // std looks the handles up on every read and write, so this redirects
+            // println! and stdin alike for the rest of the process
+            SetStdHandle(std_handle, handle)?;
+        }
+    }
+    std::env::set_current_dir(working_dir)
+}
+
+fn is_elevated() -> bool {
+    unsafe {
+        let mut token = HANDLE::default();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).is_err() {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut length = 0u32;
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut _ as _),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut length,
+        );
+        let _ = CloseHandle(token);
+        result.is_ok() && elevation.TokenIsElevated != 0
+    }
+}
+
+fn relay(args: &[OsString]) -> Result<u32> {
+    let pipe = format!(r"\\.\pipe\altdesktop-helper-elevate-{}", std::process::id());
+    // Created before the copy starts, so they're there to open however fast it is
+    let pipes = STREAMS
+        .iter()
+        .map(|(suffix, std_handle)| create_pipe(&format!("{}-{}", pipe, suffix), *std_handle))
+        .collect::<Result<Vec<_>>>()?;
+
+    let executable = std::env::current_exe().map_err(tool::io_error)?;
+    let working_dir = std::env::current_dir().map_err(tool::io_error)?;
+    let mut parameters = Vec::new();
+    // An elevated process starts with a fresh environment, so ALTDESKTOP_LOG is passed on
+    // too, along with the options it would otherwise lose
+    if let Some(filter) = altdesktop_core::log::filter() {
+        parameters.extend(["--log".to_string(), filter.to_string()]);
+    }
+    if let Some(tag) = altdesktop_core::messages::language() {
+        parameters.extend(["--lang".to_string(), tag.to_string()]);
+    }
+    if let Some(config) = altdesktop_core::config::path() {
+        parameters.extend(["--config".to_string(), config.to_string_lossy().into_owned()]);
+    }
+    for root in altdesktop_core::sandbox::roots() {
+        parameters.extend(["--allowed-output-root".to_string(), root.to_string_lossy().into_owned()]);
+    }
+    parameters.extend([CHILD_FLAG.to_string(), pipe.clone(), working_dir.to_string_lossy().into_owned()]);
+    parameters.extend(args.iter().map(|arg| arg.to_string_lossy().into_owned()));
+    let parameters = HSTRING::from(parameters.iter().map(|arg| quote(arg)).collect::<Vec<_>>().join(" "));
+    let executable = HSTRING::from(executable.as_path());
+
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+        fMask: SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NOASYNC | SEE_MASK_FLAG_NO_UI,
+        lpVerb: w!("runas"),
+        lpFile: PCWSTR(executable.as_ptr()),
+        lpParameters: PCWSTR(parameters.as_ptr()),
+        // The copy is a console program; its output goes through the pipes instead
+        nShow: SW_HIDE.0,
+        ..Default::default()
+    };
+    if let Err(error) = unsafe { ShellExecuteExW(&mut info) } {
+        for (handle, _) in pipes {
+            let _ = unsafe { CloseHandle(handle) };
+        }
+        if error.code() == ERROR_CANCELLED.to_hresult() {
+            return Err(Error::new(error.code(), "The elevation prompt was declined".into()));
+        }
+        return Err(error);
+    }
+    let child = unsafe { GetProcessId(info.hProcess) };
+
+    let mut outputs = Vec::new();
+    for (handle, std_handle) in pipes {
+        let handle = handle.0;
+        let relay = move || {
+            let handle = HANDLE(handle);
+            // Anyone may open a pipe by name; only the copy gets to speak for it
+            if !connect(handle, child) {
+                let _ = unsafe { CloseHandle(handle) };
+                return;
+            }
+            let mut pipe = unsafe { File::from_raw_handle(handle.0 as _) };
+            let _ = match std_handle {
+                STD_INPUT_HANDLE => io::copy(&mut io::stdin(), &mut pipe),
+                STD_OUTPUT_HANDLE => io::copy(&mut pipe, &mut io::stdout()),
+                _ => io::copy(&mut pipe, &mut io::stderr()),
+            };
+        };
+        if std_handle == STD_INPUT_HANDLE {
+            // Never joined: it waits on this process's stdin, which may never close
+            drop(thread::spawn(relay));
+        } else {
+            outputs.push(thread::spawn(relay));
+        }
+    }
+
+    let mut code = 1u32;
+    unsafe {
+        WaitForSingleObject(info.hProcess, INFINITE);
+        let _ = GetExitCodeProcess(info.hProcess, &mut code);
+        let _ = CloseHandle(info.hProcess);
+    }
+    // A copy that exited before opening its pipes leaves their threads waiting for
+    // it; opening them here lets those threads see it wasn't the copy and give up
+    for (suffix, std_handle) in STREAMS {
+        if std_handle != STD_INPUT_HANDLE {
+            unsafe {
+                if let Ok(handle) = CreateFileW(
+                    &HSTRING::from(format!("{}-{}", pipe, suffix)),
+                    GENERIC_WRITE.0,
+                    FILE_SHARE_MODE(0),
+                    None,
+                    OPEN_EXISTING,
+                    FILE_FLAGS_AND_ATTRIBUTES(0),
+                    None,
+                ) {
+                    let _ = CloseHandle(handle);
+                }
+            }
+        }
+    }
+    for output in outputs {
+        let _ = output.join();
+    }
+    Ok(code)
+}
+
+// Inbound pipes carry the copy's output to this process; the outbound one, stdin
+fn create_pipe(name: &str, std_handle: STD_HANDLE) -> Result<(HANDLE, STD_HANDLE)> {
+    let access = if std_handle == STD_INPUT_HANDLE { PIPE_ACCESS_OUTBOUND } else { PIPE_ACCESS_INBOUND };
+    let handle = unsafe {
+        CreateNamedPipeW(
+            &HSTRING::from(name),
+            access | FILE_FLAG_FIRST_PIPE_INSTANCE,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            1,
+            BUFFER_SIZE,
+            BUFFER_SIZE,
+            0,
+            None,
+        )
+    };
+    if handle.is_invalid() {
+        return Err(Error::from_win32());
+    }
+    Ok((handle, std_handle))
+}
+
+// Waits for a client, and checks it is the elevated copy
+fn connect(handle: HANDLE, child: u32) -> bool {
+    match unsafe { ConnectNamedPipe(handle, None) } {
+        Err(error) if error.code() != ERROR_PIPE_CONNECTED.to_hresult() => return false,
+        _ => {}
+    }
+    let mut client = 0u32;
+    unsafe { GetNamedPipeClientProcessId(handle, &mut client) }.is_ok() && client == child
+}
+
+// Quotes an argument so CommandLineToArgvW, and so std::env::args, splits it back out
+// unchanged: backslashes only escape when they precede a quote
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes + 1));
+                backslashes = 0;
+            }
+            _ => backslashes = 0,
+        }
+        quoted.push(c);
+    }
+    quoted.extend(std::iter::repeat_n('\\', backslashes));
+    quoted.push('"');
+    quoted
+}
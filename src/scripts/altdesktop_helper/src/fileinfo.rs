@@ -0,0 +1,224 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::Read;
+#[cfg(windows)]
+use std::os::windows::fs::MetadataExt;
+#[cfg(windows)]
+use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Storage::FileSystem::{
+        GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW, FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_READONLY,
+        VS_FIXEDFILEINFO,
+    },
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper fileinfo <path>
+
+Prints what Explorer's Details tab shows for <path>: from an executable or DLL's
+version resource its productName, fileDescription, companyName, fileVersion,
+productVersion, originalFilename, internalName and legalCopyright, the numeric
+fixedFileVersion and fixedProductVersion as a.b.c.d, and from its PE header the
+architecture (x86, x64, arm or arm64) and subsystem (gui or console); all null for
+other files. Every file also gets its size, created and modified times in
+milliseconds since 1970, readOnly, hidden, and displayName: the name to show for it,
+the file description, else the product name, else the file name without extension.
+<path> may start with a known folder token such as {Desktop}.";
+
+// Tried when a file has no translation table, which older tools often left out:
+// US English in Unicode, then in the Windows-1252 code page
+#[cfg(windows)]
+const FALLBACK_TRANSLATIONS: [(u16, u16); 2] = [(0x0409, 0x04B0), (0x0409, 0x04E4)];
+
+#[cfg(windows)]
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Version {
+    product_name: Option<String>,
+    file_description: Option<String>,
+    company_name: Option<String>,
+    file_version: Option<String>,
+    product_version: Option<String>,
+    original_filename: Option<String>,
+    internal_name: Option<String>,
+    legal_copyright: Option<String>,
+    fixed_file_version: Option<String>,
+    fixed_product_version: Option<String>,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileInfo {
+    path: PathBuf,
+    display_name: String,
+    size: u64,
+    created: Option<u64>,
+    modified: Option<u64>,
+    read_only: bool,
+    hidden: bool,
+    #[serde(flatten)]
+    version: Version,
+    architecture: Option<&'static str>,
+    subsystem: Option<&'static str>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.as_slice() {
+        [_, flag] if flag.starts_with("--") => tool::usage_error(&format!("Unknown option: {}", flag), USAGE),
+        [_, path] => tool::finish(file_info(path)),
+        _ => tool::usage_error("Expected <path>", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("fileinfo")
+}
+
+#[cfg(windows)]
+fn file_info(path: &str) -> Result<FileInfo> {
+    let path = std::path::absolute(create_shortcut::known_folders::expand_known_folder(path)?).map_err(tool::io_error)?;
+    let metadata = std::fs::metadata(&path).map_err(tool::io_error)?;
+    let version = if metadata.is_file() { read_version(&path) } else { None }.unwrap_or_default();
+    let (architecture, subsystem) = if metadata.is_file() { read_pe_header(&path) } else { None }.unwrap_or_default();
+
+    let display_name = version
+        .file_description
+        .clone()
+        .or_else(|| version.product_name.clone())
+        .or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| path.to_string_lossy().into_owned());
+    let attributes = metadata.file_attributes();
+    Ok(FileInfo {
+        display_name,
+        size: metadata.len(),
+        created: metadata.created().ok().and_then(unix_millis),
+        modified: metadata.modified().ok().and_then(unix_millis),
+        read_only: attributes & FILE_ATTRIBUTE_READONLY.0 != 0,
+        hidden: attributes & FILE_ATTRIBUTE_HIDDEN.0 != 0,
+        version,
+        architecture,
+        subsystem,
+        path,
+    })
+}
+
+#[cfg(windows)]
+fn unix_millis(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|since| since.as_millis() as u64)
+}
+
+/// An executable or DLL's fixed file version, as a.b.c.d.
+#[cfg(windows)]
+pub fn file_version(path: &Path) -> Option<String> {
+    read_version(path)?.fixed_file_version
+}
+
+#[cfg(windows)]
+fn read_version(path: &Path) -> Option<Version> {
+    let path = HSTRING::from(path);
+    let size = unsafe { GetFileVersionInfoSizeW(&path, None) };
+    if size == 0 {
+        return None;
+    }
+    let mut block = vec![0u8; size as usize];
+    unsafe { GetFileVersionInfoW(&path, 0, size, block.as_mut_ptr() as _) }.ok()?;
+
+    let translations: Vec<(u16, u16)> = query(&block, "\\VarFileInfo\\Translation")
+        .map(|table| table.chunks_exact(4).map(|entry| (wide_unit(&entry[..2]), wide_unit(&entry[2..]))).collect())
+        .unwrap_or_default();
+    let string = |name: &str| {
+        translations.iter().chain(&FALLBACK_TRANSLATIONS).find_map(|(language, code_page)| {
+            let value = query(&block, &format!("\\StringFileInfo\\{:04x}{:04x}\\{}", language, code_page, name))?;
+            let units: Vec<u16> = value.chunks_exact(2).map(wide_unit).take_while(|&unit| unit != 0).collect();
+            Some(String::from_utf16_lossy(&units).trim().to_string()).filter(|value| !value.is_empty())
+        })
+    };
+
+    let fixed = query(&block, "\\")
+        .filter(|fixed| fixed.len() >= std::mem::size_of::<VS_FIXEDFILEINFO>())
+        .map(|fixed| unsafe { std::ptr::read_unaligned(fixed.as_ptr() as *const VS_FIXEDFILEINFO) });
+    let dotted = |most: u32, least: u32| format!("{}.{}.{}.{}", most >> 16, most & 0xffff, least >> 16, least & 0xffff);
+    Some(Version {
+        product_name: string("ProductName"),
+        file_description: string("FileDescription"),
+        company_name: string("CompanyName"),
+        file_version: string("FileVersion"),
+        product_version: string("ProductVersion"),
+        original_filename: string("OriginalFilename"),
+        internal_name: string("InternalName"),
+        legal_copyright: string("LegalCopyright"),
+        fixed_file_version: fixed.map(|fixed| dotted(fixed.dwFileVersionMS, fixed.dwFileVersionLS)),
+        fixed_product_version: fixed.map(|fixed| dotted(fixed.dwProductVersionMS, fixed.dwProductVersionLS)),
+    })
+}
+
+// The bytes of one value in a version block; string lengths are in characters
+#[cfg(windows)]
+fn query<'a>(block: &'a [u8], sub_block: &str) -> Option<&'a [u8]> {
+    let mut value = std::ptr::null_mut();
+    let mut length = 0u32;
+    let found = unsafe { VerQueryValueW(block.as_ptr() as _, &HSTRING::from(sub_block), &mut value, &mut length) };
+    if !found.as_bool() || value.is_null() {
+        return None;
+    }
+    let bytes = if sub_block.starts_with("\\StringFileInfo") { length as usize * 2 } else { length as usize };
+    let offset = (value as usize).checked_sub(block.as_ptr() as usize)?;
+    block.get(offset..offset.checked_add(bytes)?)
+}
+
+#[cfg(windows)]
+fn wide_unit(pair: &[u8]) -> u16 {
+    u16::from_le_bytes([pair[0], pair[1]])
+}
+
+// The machine and subsystem fields of the COFF and optional headers
+#[cfg(windows)]
+fn read_pe_header(path: &Path) -> Option<(Option<&'static str>, Option<&'static str>)> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut header = vec![0u8; 4096];
+    let read = file.read(&mut header).ok()?;
+    header.truncate(read);
+    if !header.starts_with(b"MZ") {
+        return None;
+    }
+    let pe = u32::from_le_bytes(header.get(0x3C..0x40)?.try_into().ok()?) as usize;
+    if header.get(pe..pe + 4)? != b"PE\0\0" {
+        return None;
+    }
+    let machine = u16::from_le_bytes(header.get(pe + 4..pe + 6)?.try_into().ok()?);
+    // The optional header follows the 20-byte COFF header; Subsystem is at the same
+    // offset in PE32 and PE32+
+    let subsystem = header.get(pe + 24 + 68..pe + 24 + 70).map(|field| u16::from_le_bytes([field[0], field[1]]));
+
+    let architecture = match machine {
+        0x014C => Some("x86"),
+        0x8664 => Some("x64"),
+        0x01C4 => Some("arm"),
+        0xAA64 => Some("arm64"),
+        _ => None,
+    };
+    let subsystem = match subsystem {
+        Some(2) => Some("gui"),
+        Some(3) => Some("console"),
+        _ => None,
+    };
+    Some((architecture, subsystem))
+}
@@ -0,0 +1,320 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::path::PathBuf;
+
+#[cfg(windows)]
+use altdesktop_core::sandbox;
+#[cfg(windows)]
+use altdesktop_core::wide::from_wide;
+#[cfg(windows)]
+use image::{ImageFormat, RgbaImage};
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{COLORREF, E_FAIL, E_INVALIDARG, LPARAM, RECT},
+    Win32::Graphics::Gdi::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper fonts list
+  altdesktop-helper fonts preview <family> <output.png> [--size <px>] [--text <text>]
+                                  [--color #rrggbb] [--weight <100-900>] [--italic]
+
+list prints [{\"family\",\"styles\",\"monospace\",\"symbol\",\"scalable\"}] for every font
+family installed for this user or the whole machine, sorted by name. styles are the
+faces the family has, such as Regular, Bold or Light Italic. Fonts that aren't
+scalable are bitmap fonts, which only look right at the sizes they were drawn for.
+preview renders <text> (default: the quick brown fox) in the family as Windows
+draws it, antialiased on a transparent background, and prints {\"path\",\"width\",
+\"height\"}. --size is the em height in pixels (default 32); --weight defaults to 400,
+regular, and 700 is bold. <text> may hold line breaks.";
+
+#[cfg(windows)]
+const DEFAULT_SIZE: i32 = 32;
+#[cfg(windows)]
+const DEFAULT_TEXT: &str = "The quick brown fox jumps over the lazy dog";
+// Room for overhangs DrawText doesn't measure, such as italic tails
+#[cfg(windows)]
+const PADDING: i32 = 2;
+// Beyond this a preview is no longer a preview
+#[cfg(windows)]
+const MAX_SIZE: i32 = 512;
+#[cfg(windows)]
+const MAX_DIMENSION: i32 = 8192;
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct Family {
+    family: String,
+    styles: Vec<String>,
+    monospace: bool,
+    symbol: bool,
+    scalable: bool,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct Preview {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+}
+
+#[cfg(windows)]
+struct PreviewOptions {
+    family: String,
+    output: String,
+    size: i32,
+    text: String,
+    color: [u8; 3],
+    weight: i32,
+    italic: bool,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--preview") => match parse_preview(&args[2..]) {
+            Ok(options) => tool::finish(preview(&options)),
+            Err(message) => tool::usage_error(&message, USAGE),
+        },
+        None => tool::finish(list_families()),
+        Some(_) => tool::usage_error("list takes no arguments", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("fonts")
+}
+
+#[cfg(windows)]
+fn parse_preview(args: &[String]) -> std::result::Result<PreviewOptions, String> {
+    let mut options = PreviewOptions {
+        family: String::new(),
+        output: String::new(),
+        size: DEFAULT_SIZE,
+        text: DEFAULT_TEXT.to_string(),
+        color: [0, 0, 0],
+        weight: FW_NORMAL.0 as i32,
+        italic: false,
+    };
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--size" => {
+                let value = value()?;
+                options.size = value.parse().ok().filter(|size| (1..=MAX_SIZE).contains(size)).ok_or_else(|| {
+                    format!("Invalid size: {} (expected 1 to {} pixels)", value, MAX_SIZE)
+                })?;
+            }
+            "--text" => options.text = value()?,
+            "--color" => options.color = parse_color(&value()?)?,
+            "--weight" => {
+                let value = value()?;
+                options.weight = value
+                    .parse()
+                    .ok()
+                    .filter(|weight| (100..=900).contains(weight))
+                    .ok_or_else(|| format!("Invalid weight: {} (expected 100 to 900)", value))?;
+            }
+            "--italic" => options.italic = true,
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+    let [family, output] = <[String; 2]>::try_from(positional).map_err(|_| "Expected <family> <output.png>".to_string())?;
+    if family.encode_utf16().count() >= 32 {
+        return Err(format!("Font family name is too long: {}", family));
+    }
+    if options.text.is_empty() {
+        return Err("--text cannot be empty".to_string());
+    }
+    options.family = family;
+    options.output = output;
+    Ok(options)
+}
+
+#[cfg(windows)]
+fn parse_color(value: &str) -> std::result::Result<[u8; 3], String> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    let rgb = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6)
+        .ok_or_else(|| format!("Invalid color: {} (expected #rrggbb)", value))?;
+    Ok([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+}
+
+// GDI reports every family once per character set it covers, and each of its
+// styles once more when asked about the family by name
+#[cfg(windows)]
+fn list_families() -> Result<Vec<Family>> {
+    let dc = ScreenDc::new()?;
+    let mut families: Vec<Family> = Vec::new();
+    for font in enumerate(dc.0, "") {
+        let family = face_name(&font.log_font);
+        // Names starting with @ are the same fonts rotated for vertical text
+        if family.starts_with('@') || families.iter().any(|known| known.family == family) {
+            continue;
+        }
+        let mut styles: Vec<String> = Vec::new();
+        let mut symbol = false;
+        for face in enumerate(dc.0, &family) {
+            let style = from_wide(&face.style);
+            if !style.is_empty() && !styles.contains(&style) {
+                styles.push(style);
+            }
+            symbol |= face.log_font.lfCharSet == SYMBOL_CHARSET;
+        }
+        families.push(Family {
+            monospace: font.log_font.lfPitchAndFamily & 0x3 == FIXED_PITCH.0,
+            scalable: font.font_type & RASTER_FONTTYPE == 0,
+            symbol,
+            styles,
+            family,
+        });
+    }
+    families.sort_by_key(|family| family.family.to_lowercase());
+    Ok(families)
+}
+
+#[cfg(windows)]
+struct EnumeratedFont {
+    log_font: LOGFONTW,
+    style: [u16; 32],
+    font_type: u32,
+}
+
+#[cfg(windows)]
+fn enumerate(dc: HDC, family: &str) -> Vec<EnumeratedFont> {
+    unsafe extern "system" fn collect(font: *const LOGFONTW, _: *const TEXTMETRICW, font_type: u32, found: LPARAM) -> i32 {
+        // For EnumFontFamiliesEx the LOGFONT is the start of an ENUMLOGFONTEX
+        let font = unsafe { &*(font as *const ENUMLOGFONTEXW) };
+        let found = unsafe { &mut *(found.0 as *mut Vec<EnumeratedFont>) };
+        found.push(EnumeratedFont { log_font: font.elfLogFont, style: font.elfStyle, font_type });
+        1
+    }
+    let mut query = LOGFONTW { lfCharSet: DEFAULT_CHARSET, ..Default::default() };
+    for (slot, unit) in query.lfFaceName.iter_mut().zip(family.encode_utf16()) {
+        *slot = unit;
+    }
+    let mut found: Vec<EnumeratedFont> = Vec::new();
+    unsafe { EnumFontFamiliesExW(dc, &query, Some(collect), LPARAM(&mut found as *mut _ as isize), 0) };
+    found
+}
+
+// GDI quietly substitutes another font for a name it doesn't know, so the family is
+// looked up first and drawn by the name it is installed under
+#[cfg(windows)]
+fn preview(options: &PreviewOptions) -> Result<Preview> {
+    let dc = ScreenDc::new()?;
+    let Some(installed) = enumerate(dc.0, &options.family).into_iter().next() else {
+        return Err(Error::new(E_INVALIDARG, format!("Font is not installed: {}", options.family).into()));
+    };
+    let mut font = LOGFONTW {
+        lfHeight: -options.size,
+        lfWeight: options.weight,
+        lfItalic: options.italic as u8,
+        lfCharSet: installed.log_font.lfCharSet,
+        // Grayscale coverage converts to alpha; ClearType's colored fringes wouldn't
+        lfQuality: ANTIALIASED_QUALITY,
+        lfFaceName: installed.log_font.lfFaceName,
+        ..Default::default()
+    };
+    if font.lfCharSet != SYMBOL_CHARSET {
+        font.lfCharSet = DEFAULT_CHARSET;
+    }
+    let mut text: Vec<u16> = options.text.encode_utf16().collect();
+    let path = std::path::absolute(&options.output).map_err(tool::io_error)?;
+    sandbox::check_output(&path)?;
+
+    unsafe {
+        let memory = CreateCompatibleDC(dc.0);
+        let hfont = CreateFontIndirectW(&font);
+        let previous = SelectObject(memory, hfont);
+        let result = (|| {
+            let format = DT_NOPREFIX | DT_EXPANDTABS;
+            let mut bounds = RECT::default();
+            DrawTextW(memory, &mut text, &mut bounds, format | DT_CALCRECT);
+            let width = (bounds.right + 2 * PADDING).min(MAX_DIMENSION);
+            let height = (bounds.bottom + 2 * PADDING).min(MAX_DIMENSION);
+
+            let info = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: width,
+                    // Negative for top-down rows
+                    biHeight: -height,
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let mut bits = std::ptr::null_mut();
+            let bitmap = CreateDIBSection(memory, &info, DIB_RGB_COLORS, &mut bits, None, 0)?;
+            let previous_bitmap = SelectObject(memory, bitmap);
+            // White on black, so each pixel's brightness is how much the glyph covers it
+            SetBkMode(memory, TRANSPARENT);
+            SetTextColor(memory, COLORREF(0x00FF_FFFF));
+            let mut area = RECT { left: PADDING, top: PADDING, right: width - PADDING, bottom: height - PADDING };
+            DrawTextW(memory, &mut text, &mut area, format);
+            GdiFlush();
+
+            let pixels = std::slice::from_raw_parts(bits as *const u8, (width * height * 4) as usize);
+            let [red, green, blue] = options.color;
+            let mut image = RgbaImage::new(width as u32, height as u32);
+            for (pixel, bgra) in image.pixels_mut().zip(pixels.chunks_exact(4)) {
+                let coverage = bgra[0].max(bgra[1]).max(bgra[2]);
+                pixel.0 = [red, green, blue, coverage];
+            }
+            SelectObject(memory, previous_bitmap);
+            let _ = DeleteObject(bitmap);
+            image.save_with_format(&path, ImageFormat::Png).map_err(|error| Error::new(E_FAIL, error.to_string().into()))?;
+            Ok(Preview { path: path.clone(), width: width as u32, height: height as u32 })
+        })();
+        SelectObject(memory, previous);
+        let _ = DeleteObject(hfont);
+        let _ = DeleteDC(memory);
+        result
+    }
+}
+
+#[cfg(windows)]
+struct ScreenDc(HDC);
+
+#[cfg(windows)]
+impl ScreenDc {
+    fn new() -> Result<Self> {
+        let dc = unsafe { GetDC(None) };
+        if dc.is_invalid() {
+            return Err(Error::from_win32());
+        }
+        Ok(ScreenDc(dc))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ScreenDc {
+    fn drop(&mut self) {
+        unsafe { ReleaseDC(None, self.0) };
+    }
+}
+
+#[cfg(windows)]
+fn face_name(font: &LOGFONTW) -> String {
+    from_wide(&font.lfFaceName)
+}
@@ -0,0 +1,137 @@
+use std::process::ExitCode;
+
+use altdesktop_core::error::ErrorReport;
+use create_shortcut::known_folders::expand_known_folder;
+use serde::Serialize;
+use serde_json::{json, Value};
+use windows::{
+    core::*,
+    Win32::Foundation::CloseHandle,
+    Win32::Storage::CloudFilters::*,
+    Win32::Storage::FileSystem::*,
+};
+
+use super::USAGE;
+use crate::tool;
+
+// ReadFile's access right, which hydrating asks for; reading attributes doesn't
+// need it, and so never starts a download
+const FILE_READ_DATA: u32 = 1;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CloudState {
+    path: String,
+    ok: bool,
+    placeholder: bool,
+    availability: &'static str,
+    pinned: bool,
+    in_sync: bool,
+    provider: Option<String>,
+}
+
+pub fn run(args: &[String]) -> ExitCode {
+    let mut hydrate = false;
+    let mut paths = Vec::new();
+    for arg in args.iter().skip(2) {
+        match arg.as_str() {
+            "--hydrate" => hydrate = true,
+            flag if flag.starts_with("--") => return tool::usage_error(&format!("Unknown option: {}", flag), USAGE),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.is_empty() {
+        return tool::usage_error("Expected at least one path", USAGE);
+    }
+
+    let results: Vec<Value> = paths
+        .iter()
+        .map(|path| match cloud_state(path, hydrate) {
+            Ok(state) => serde_json::to_value(state).unwrap(),
+            Err(error) => {
+                let mut result = serde_json::to_value(ErrorReport::from_error(&error)).unwrap();
+                result["path"] = Value::from(path.as_str());
+                result
+            }
+        })
+        .collect();
+    let ok = results.iter().all(|result| result["ok"] == true);
+    println!("{}", json!({ "ok": ok, "results": results }));
+    if ok { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+fn cloud_state(path: &str, hydrate: bool) -> Result<CloudState> {
+    let expanded = expand_known_folder(path)?;
+    let absolute = std::path::absolute(&expanded).map_err(tool::io_error)?;
+    let name = HSTRING::from(absolute.as_path());
+    let mut state = read_state(path, &name)?;
+    if hydrate && state.availability != "local" {
+        hydrate_file(&name)?;
+        state = read_state(path, &name)?;
+    }
+    Ok(state)
+}
+
+// The directory entry has the attributes and reparse tag without opening the file
+fn read_state(path: &str, name: &HSTRING) -> Result<CloudState> {
+    let mut entry = WIN32_FIND_DATAW::default();
+    unsafe {
+        let find = FindFirstFileW(name, &mut entry)?;
+        let _ = FindClose(find);
+    }
+    let attributes = entry.dwFileAttributes;
+    let has = |attribute: FILE_FLAGS_AND_ATTRIBUTES| attributes & attribute.0 != 0;
+    let placeholder = unsafe { CfGetPlaceholderStateFromAttributeTag(attributes, entry.dwReserved0) };
+    let is = |flag: CF_PLACEHOLDER_STATE| placeholder != CF_PLACEHOLDER_STATE_INVALID && placeholder.0 & flag.0 != 0;
+
+    // A folder whose contents haven't been listed yet recalls on open instead
+    let availability = if has(FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS) || has(FILE_ATTRIBUTE_RECALL_ON_OPEN) {
+        if is(CF_PLACEHOLDER_STATE_PARTIALLY_ON_DISK) { "partial" } else { "online" }
+    } else {
+        "local"
+    };
+    Ok(CloudState {
+        path: path.to_string(),
+        ok: true,
+        placeholder: is(CF_PLACEHOLDER_STATE_PLACEHOLDER),
+        availability,
+        pinned: has(FILE_ATTRIBUTE_PINNED),
+        in_sync: is(CF_PLACEHOLDER_STATE_IN_SYNC),
+        provider: provider_name(name),
+    })
+}
+
+// The sync engine's name for itself, e.g. OneDrive; None outside a sync root
+fn provider_name(name: &HSTRING) -> Option<String> {
+    let mut info = CF_SYNC_ROOT_PROVIDER_INFO::default();
+    unsafe {
+        CfGetSyncRootInfoByPath(
+            name,
+            CF_SYNC_ROOT_INFO_PROVIDER,
+            &mut info as *mut _ as _,
+            std::mem::size_of::<CF_SYNC_ROOT_PROVIDER_INFO>() as u32,
+            None,
+        )
+        .ok()?;
+    }
+    let length = info.ProviderName.iter().position(|&c| c == 0).unwrap_or(info.ProviderName.len());
+    Some(String::from_utf16_lossy(&info.ProviderName[..length])).filter(|name| !name.is_empty())
+}
+
+// Downloads the whole file and waits until it is on disk
+fn hydrate_file(name: &HSTRING) -> Result<()> {
+    unsafe {
+        let handle = CreateFileW(
+            name,
+            FILE_READ_DATA,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            None,
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            None,
+        )?;
+        let hydrated = CfHydratePlaceholder(handle, 0, -1, CF_HYDRATE_FLAG_NONE, None);
+        let _ = CloseHandle(handle);
+        hydrated
+    }
+}
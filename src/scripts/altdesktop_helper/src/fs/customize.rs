@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use altdesktop_core::sandbox;
+use serde::Serialize;
+use windows::{core::*, Win32::UI::Shell::*};
+
+use super::USAGE;
+use crate::tool;
+
+// Long enough for any path desktop.ini's IconResource holds, and the tooltips
+// Explorer shows
+const ICON_FILE_LENGTH: usize = 1024;
+const INFO_TIP_LENGTH: usize = 1024;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Customization {
+    folder: PathBuf,
+    icon: Option<String>,
+    icon_index: Option<i32>,
+    info_tip: Option<String>,
+}
+
+#[derive(Default)]
+struct Changes {
+    icon: Option<(String, i32)>,
+    info_tip: Option<String>,
+    clear: bool,
+}
+
+pub fn run(args: &[String]) -> ExitCode {
+    let (folder, changes) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    tool::finish(customize(&folder, &changes))
+}
+
+fn parse_args(args: &[String]) -> std::result::Result<(String, Changes), String> {
+    let mut folder = None;
+    let mut icon = None;
+    let mut icon_index = 0;
+    let mut changes = Changes::default();
+    let mut iter = args.iter().skip(2);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--icon" => icon = Some(value()?),
+            "--icon-index" => {
+                let value = value()?;
+                icon_index = value.parse::<i32>().map_err(|_| format!("Invalid icon index: {}", value))?;
+            }
+            "--info-tip" => changes.info_tip = Some(value()?),
+            "--clear" => changes.clear = true,
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ if folder.is_none() => folder = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    let folder = folder.ok_or_else(|| "Expected <folder>".to_string())?;
+    changes.icon = icon.map(|icon| (icon, icon_index));
+    Ok((folder, changes))
+}
+
+// SHGetSetFolderCustomSettings writes desktop.ini as Explorer's Customize tab does,
+// marking it hidden and system and the folder read-only so Explorer reads it
+fn customize(folder: &str, changes: &Changes) -> Result<Customization> {
+    let folder = std::path::absolute(create_shortcut::known_folders::expand_known_folder(folder)?).map_err(tool::io_error)?;
+    sandbox::check_output(&folder)?;
+    if !std::fs::metadata(&folder).map_err(tool::io_error)?.is_dir() {
+        let message = format!("{} is not a folder", folder.display());
+        return Err(tool::io_error(std::io::Error::new(std::io::ErrorKind::NotADirectory, message)));
+    }
+    let path = HSTRING::from(folder.as_path());
+
+    let mut icon = match &changes.icon {
+        Some((icon, _)) => {
+            let icon = std::path::absolute(create_shortcut::known_folders::expand_known_folder(icon)?).map_err(tool::io_error)?;
+            sandbox::check_input(&icon.to_string_lossy())?;
+            Some(HSTRING::from(icon.as_path()).as_wide().iter().copied().chain([0]).collect::<Vec<u16>>())
+        }
+        None => None,
+    };
+    let mut info_tip: Option<Vec<u16>> = changes.info_tip.as_ref().map(|tip| tip.encode_utf16().chain([0]).collect());
+    let mut settings = SHFOLDERCUSTOMSETTINGS { dwSize: std::mem::size_of::<SHFOLDERCUSTOMSETTINGS>() as u32, ..Default::default() };
+    // A mask bit with a null string takes the entry out of desktop.ini
+    if icon.is_some() || changes.clear {
+        settings.dwMask |= FCSM_ICONFILE;
+        if let Some(icon) = &mut icon {
+            settings.pszIconFile = PWSTR(icon.as_mut_ptr());
+            settings.iIconIndex = changes.icon.as_ref().map_or(0, |(_, index)| *index);
+        }
+    }
+    if info_tip.is_some() || changes.clear {
+        settings.dwMask |= FCSM_INFOTIP;
+        if let Some(info_tip) = &mut info_tip {
+            settings.pszInfoTip = PWSTR(info_tip.as_mut_ptr());
+        }
+    }
+    if settings.dwMask != 0 {
+        unsafe {
+            SHGetSetFolderCustomSettings(&mut settings, &path, FCS_FORCEWRITE)?;
+            // Explorer caches folder icons until told the folder changed
+            SHChangeNotify(SHCNE_UPDATEITEM, SHCNF_PATHW, Some(path.as_ptr() as _), None);
+            SHChangeNotify(SHCNE_UPDATEDIR, SHCNF_PATHW, Some(path.as_ptr() as _), None);
+        }
+    }
+    read(folder, &path)
+}
+
+fn read(folder: PathBuf, path: &HSTRING) -> Result<Customization> {
+    let mut icon = vec![0u16; ICON_FILE_LENGTH];
+    let mut info_tip = vec![0u16; INFO_TIP_LENGTH];
+    let mut settings = SHFOLDERCUSTOMSETTINGS {
+        dwSize: std::mem::size_of::<SHFOLDERCUSTOMSETTINGS>() as u32,
+        dwMask: FCSM_ICONFILE | FCSM_INFOTIP,
+        pszIconFile: PWSTR(icon.as_mut_ptr()),
+        cchIconFile: icon.len() as u32,
+        pszInfoTip: PWSTR(info_tip.as_mut_ptr()),
+        cchInfoTip: info_tip.len() as u32,
+        ..Default::default()
+    };
+    // Fails when the folder has no desktop.ini, which is no customization at all
+    let found = unsafe { SHGetSetFolderCustomSettings(&mut settings, path, FCS_READ) }.is_ok();
+    let text = |units: &[u16]| {
+        let length = units.iter().position(|&unit| unit == 0).unwrap_or(units.len());
+        Some(String::from_utf16_lossy(&units[..length])).filter(|text| found && !text.is_empty())
+    };
+    let icon = text(&icon);
+    Ok(Customization {
+        folder,
+        icon_index: icon.as_ref().map(|_| settings.iIconIndex),
+        icon,
+        info_tip: text(&info_tip),
+    })
+}
@@ -0,0 +1,56 @@
+use std::path::Path;
+use std::process::ExitCode;
+
+use altdesktop_core::sandbox;
+use create_shortcut::fslink::{create_fs_link, read_fs_link, LinkType};
+use create_shortcut::known_folders::expand_known_folder;
+
+use super::USAGE;
+use crate::tool;
+
+enum Command {
+    Create { target: String, link: String, link_type: LinkType },
+    Query(String),
+}
+
+pub fn run(args: &[String]) -> ExitCode {
+    match parse_args(args) {
+        Ok(Command::Create { target, link, link_type }) => tool::finish((|| {
+            let (target, link) = (expand_known_folder(&target)?, expand_known_folder(&link)?);
+            sandbox::check_input(&target)?;
+            sandbox::check_output(Path::new(&link))?;
+            create_fs_link(&target, &link, link_type)?;
+            read_fs_link(&link)
+        })()),
+        Ok(Command::Query(path)) => tool::finish(expand_known_folder(&path).and_then(|path| read_fs_link(&path))),
+        Err(message) => tool::usage_error(&message, USAGE),
+    }
+}
+
+fn parse_args(args: &[String]) -> std::result::Result<Command, String> {
+    let mut query = false;
+    let mut link_type = None;
+    let mut paths = Vec::new();
+    let mut iter = args.iter().skip(2);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--query" => query = true,
+            "--type" => link_type = Some(LinkType::parse(&value()?)?),
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    match (query, paths.as_slice()) {
+        (true, _) if link_type.is_some() => Err("--type only applies to creating a link".to_string()),
+        (true, [path]) => Ok(Command::Query(path.clone())),
+        (true, _) => Err("--query expects <path>".to_string()),
+        (false, [target, link]) => Ok(Command::Create {
+            target: target.clone(),
+            link: link.clone(),
+            link_type: link_type.unwrap_or(LinkType::Auto),
+        }),
+        (false, _) => Err("Expected <targetPath> and <linkPath>".to_string()),
+    }
+}
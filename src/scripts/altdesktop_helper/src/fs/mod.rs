@@ -0,0 +1,87 @@
+#[cfg(windows)]
+mod cloud;
+#[cfg(windows)]
+mod customize;
+#[cfg(windows)]
+mod link;
+#[cfg(windows)]
+mod trash;
+#[cfg(windows)]
+mod watch;
+
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper fs watch <dir>... [--recursive] [--debounce <ms>]
+  altdesktop-helper fs trash <path>... [--progress]
+  altdesktop-helper fs trash --restore <originalPath>... [--progress]
+  altdesktop-helper fs trash --info [<drive>]
+  altdesktop-helper fs link <targetPath> <linkPath> [--type auto|symlink|junction|hardlink]
+  altdesktop-helper fs link --query <path>
+  altdesktop-helper fs customize <folder> [--icon <path>] [--icon-index <index>] [--info-tip <text>] [--clear]
+  altdesktop-helper fs cloud <path>... [--hydrate]
+
+watch streams changes under each <dir> as JSON lines until stdin closes. The first
+line is {\"type\":\"ready\",\"roots\":[...]}; then each change is
+{\"type\":\"created|deleted|modified|renamed\",\"path\",\"oldPath\",\"root\"}, where
+oldPath is only on renames. {\"type\":\"overflow\",\"root\"} means changes were lost
+and the folder should be rescanned. Changes to one path within --debounce ms
+(default 100) of each other are merged into one event. A <dir> may start with a
+known folder token such as {Desktop} or {StartMenu}.
+
+trash recycles each path as Explorer's Delete key does, undoable from Explorer, and
+prompts before deleting anything that can't be recycled. --restore puts back the
+latest recycled item deleted from each path. Both print
+{\"ok\",\"results\":[{\"path\",\"ok\",...}]}, with the error fields of a failed
+shortcut --json run on failed paths; --progress adds a
+{\"type\":\"progress\",\"id\",\"completed\",\"total\",\"item\"} line before it after each
+path, id being the path's index in the list.
+--info prints {\"items\",\"size\"} for the whole Recycle Bin or one drive.
+
+link makes <linkPath> a file system link to <targetPath> rather than a .lnk: a
+symbolic link, which needs Developer Mode or elevation; a junction, for folders on a
+local drive; or a hard link, for files on the same drive. auto, the default, makes a
+symbolic link, else a junction for a folder. It prints what --query prints for the
+new link: {\"path\",\"kind\",\"target\",\"relative\",\"targetExists\",\"links\",\"tag\"},
+where kind is symlink, junction, hardlink, file, directory or reparsePoint for any
+other reparse point, such as a cloud placeholder, target is where a symlink or
+junction points, links is how many names the file has, and tag is the reparse tag.
+
+customize sets the icon Explorer shows for <folder> and the tooltip it shows on
+hover, writing the folder's desktop.ini as the Customize tab of its Properties does,
+or with --clear removes the ones not given. It prints {\"folder\",\"icon\",
+\"iconIndex\",\"infoTip\"} with what the folder then has, null where it has none, and
+only prints them without any options.
+
+cloud reports whether each path is a OneDrive or other cloud file placeholder, from
+its directory entry so that checking never downloads it. It prints {\"ok\",\"results\":
+[{\"path\",\"ok\",\"placeholder\",\"availability\",\"pinned\",\"inSync\",\"provider\"}]}:
+availability is online when opening or reading the path would download it first,
+partial when only some of it is on disk, and local otherwise; pinned is whether the
+user chose Always keep on this device; provider is the sync app's name, null outside
+a cloud folder. --hydrate downloads each file that isn't local, waiting for it, and
+prints its state after. Failed paths carry the error fields of a failed shortcut
+--json run. Paths may start with a known folder token.";
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--trash") => trash::run(&args),
+        Some("--cloud") => cloud::run(&args),
+        Some("--customize") => customize::run(&args),
+        Some("--link") => link::run(&args),
+        _ => watch::run(&args),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("fs")
+}
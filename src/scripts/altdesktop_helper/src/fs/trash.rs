@@ -0,0 +1,210 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use altdesktop_core::error::ErrorReport;
+use altdesktop_core::progress::Progress;
+use altdesktop_core::sandbox;
+use serde_json::{json, Value};
+use windows::{
+    core::*,
+    Win32::Foundation::{ERROR_CANCELLED, ERROR_FILE_NOT_FOUND},
+    Win32::System::Com::{CoCreateInstance, CoTaskMemFree, CLSCTX_ALL},
+    Win32::UI::Shell::*,
+};
+
+use super::USAGE;
+use crate::tool;
+
+// What Explorer's Delete key does: recycle, keep it undoable, and still ask before
+// deleting outright something too big for the Recycle Bin or on a drive without one
+const TRASH_FLAGS: FILEOPERATION_FLAGS = FILEOPERATION_FLAGS(
+    FOFX_RECYCLEONDELETE.0 | FOF_ALLOWUNDO.0 | FOF_NOCONFIRMATION.0 | FOF_WANTNUKEWARNING.0 | FOF_SILENT.0 | FOF_NOERRORUI.0,
+);
+const RESTORE_FLAGS: FILEOPERATION_FLAGS =
+    FILEOPERATION_FLAGS(FOF_ALLOWUNDO.0 | FOF_NOCONFIRMMKDIR.0 | FOF_SILENT.0 | FOF_NOERRORUI.0);
+
+enum Mode {
+    Trash,
+    Restore,
+    Info,
+}
+
+pub fn run(args: &[String]) -> ExitCode {
+    let (mode, paths, progress) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match mode {
+        Mode::Info => tool::finish(recycle_bin_info(paths.first().map(String::as_str))),
+        Mode::Trash => run_each(&paths, progress, trash),
+        Mode::Restore => {
+            let index = match recycled_items() {
+                Ok(index) => index,
+                Err(error) => return tool::finish::<()>(Err(error)),
+            };
+            run_each(&paths, progress, |path| restore(&index, path))
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> std::result::Result<(Mode, Vec<String>, bool), String> {
+    let mut mode = Mode::Trash;
+    let mut progress = false;
+    let mut paths = Vec::new();
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
+            "--trash" => {}
+            "--restore" => mode = Mode::Restore,
+            "--info" => mode = Mode::Info,
+            "--progress" => progress = true,
+            flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    match mode {
+        Mode::Info if paths.len() > 1 => Err("--info takes at most one drive".to_string()),
+        Mode::Trash | Mode::Restore if paths.is_empty() => Err("Expected at least one path".to_string()),
+        _ => Ok((mode, paths, progress)),
+    }
+}
+
+// Each path is its own operation, so one locked file doesn't stop the rest and
+// every path gets its own result, with a progress line after each if asked
+fn run_each(paths: &[String], progress: bool, operation: impl Fn(&str) -> Result<()>) -> ExitCode {
+    let mut stdout = io::stdout().lock();
+    let mut progress = Progress::new(progress, paths.len());
+    let mut results = Vec::with_capacity(paths.len());
+    for (index, path) in paths.iter().enumerate() {
+        let result = match operation(path) {
+            Ok(()) => json!({ "path": path, "ok": true }),
+            Err(error) => {
+                let mut result = serde_json::to_value(ErrorReport::from_error(&error)).unwrap();
+                result["path"] = Value::from(path.as_str());
+                result
+            }
+        };
+        progress.finished(index, path);
+        results.push(result);
+    }
+
+    let ok = results.iter().all(|result| result["ok"] == true);
+    let _ = writeln!(stdout, "{}", json!({ "ok": ok, "results": results }));
+    if ok { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+fn trash(path: &str) -> Result<()> {
+    let path = std::path::absolute(path).map_err(tool::io_error)?;
+    sandbox::check_output(&path)?;
+    unsafe {
+        let item: IShellItem = SHCreateItemFromParsingName(&HSTRING::from(path.as_path()), None)?;
+        let operation: IFileOperation = CoCreateInstance(&FileOperation, None, CLSCTX_ALL)?;
+        operation.SetOperationFlags(TRASH_FLAGS)?;
+        operation.DeleteItem(&item, None)?;
+        perform(&operation)
+    }
+}
+
+unsafe fn perform(operation: &IFileOperation) -> Result<()> {
+    unsafe {
+        operation.PerformOperations()?;
+        // Declining the "delete permanently?" prompt isn't an error to PerformOperations
+        if operation.GetAnyOperationsAborted()?.as_bool() {
+            return Err(Error::new(ERROR_CANCELLED.to_hresult(), "The operation was cancelled".into()));
+        }
+    }
+    Ok(())
+}
+
+/// The whole Recycle Bin's item count and total size in bytes, or one drive's.
+fn recycle_bin_info(drive: Option<&str>) -> Result<Value> {
+    let mut info = SHQUERYRBINFO { cbSize: std::mem::size_of::<SHQUERYRBINFO>() as u32, ..Default::default() };
+    unsafe {
+        match drive {
+            Some(drive) => SHQueryRecycleBinW(&HSTRING::from(drive), &mut info)?,
+            None => SHQueryRecycleBinW(PCWSTR::null(), &mut info)?,
+        }
+    }
+    Ok(json!({ "items": info.i64NumItems, "size": info.i64Size }))
+}
+
+struct RecycledItem {
+    item: IShellItem,
+    original_path: String,
+    // FILETIME, to restore the latest of several deletions of one path
+    deleted_at: u64,
+}
+
+// Every item in the Recycle Bin, across drives, with where it was deleted from
+fn recycled_items() -> Result<Vec<RecycledItem>> {
+    let mut items = Vec::new();
+    unsafe {
+        let bin: IShellItem = SHGetKnownFolderItem(&FOLDERID_RecycleBinFolder, KF_FLAG_DEFAULT, None)?;
+        let entries: IEnumShellItems = bin.BindToHandler(None, &BHID_EnumItems)?;
+        loop {
+            let mut next = [None];
+            let mut fetched = 0;
+            if entries.Next(&mut next, Some(&mut fetched)).is_err() || fetched == 0 {
+                break;
+            }
+            let Some(item) = next[0].take() else {
+                break;
+            };
+            let Ok(path) = item.GetDisplayName(SIGDN_FILESYSPATH) else {
+                continue;
+            };
+            let stored = PathBuf::from(String::from_utf16_lossy(path.as_wide()));
+            CoTaskMemFree(Some(path.0 as _));
+            if let Some((original_path, deleted_at)) = read_index_file(&stored) {
+                items.push(RecycledItem { item, original_path, deleted_at });
+            }
+        }
+    }
+    Ok(items)
+}
+
+// Each recycled $R<id> file has a $I<id> file beside it recording the original path
+// and deletion time. Version 1 (Vista to 8.1) has a fixed 260-character path;
+// version 2 prefixes it with its length.
+fn read_index_file(stored: &Path) -> Option<(String, u64)> {
+    let name = stored.file_name()?.to_str()?;
+    let index = stored.with_file_name(format!("$I{}", name.strip_prefix("$R")?));
+    let bytes = std::fs::read(index).ok()?;
+    let version = i64::from_le_bytes(bytes.get(..8)?.try_into().ok()?);
+    let deleted_at = u64::from_le_bytes(bytes.get(16..24)?.try_into().ok()?);
+    let path = match version {
+        1 => bytes.get(24..24 + 520)?,
+        2 => {
+            let length = u32::from_le_bytes(bytes.get(24..28)?.try_into().ok()?) as usize;
+            bytes.get(28..28 + length * 2)?
+        }
+        _ => return None,
+    };
+    let units: Vec<u16> = path.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    let length = units.iter().position(|&unit| unit == 0).unwrap_or(units.len());
+    Some((String::from_utf16_lossy(&units[..length]), deleted_at))
+}
+
+fn restore(index: &[RecycledItem], path: &str) -> Result<()> {
+    let path = std::path::absolute(path).map_err(tool::io_error)?;
+    sandbox::check_output(&path)?;
+    let wanted = path.to_string_lossy();
+    let recycled = index
+        .iter()
+        .filter(|recycled| recycled.original_path.eq_ignore_ascii_case(&wanted))
+        .max_by_key(|recycled| recycled.deleted_at)
+        .ok_or_else(|| Error::new(ERROR_FILE_NOT_FOUND.to_hresult(), format!("{} is not in the Recycle Bin", wanted).into()))?;
+
+    let (Some(folder), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(Error::new(ERROR_FILE_NOT_FOUND.to_hresult(), format!("{} has no parent folder", wanted).into()));
+    };
+    // Restoring into a folder that was deleted since recreates it, as Explorer does
+    std::fs::create_dir_all(folder).map_err(tool::io_error)?;
+    unsafe {
+        let destination: IShellItem = SHCreateItemFromParsingName(&HSTRING::from(folder), None)?;
+        let operation: IFileOperation = CoCreateInstance(&FileOperation, None, CLSCTX_ALL)?;
+        operation.SetOperationFlags(RESTORE_FLAGS)?;
+        operation.MoveItem(&recycled.item, &destination, &HSTRING::from(name), None)?;
+        perform(&operation)
+    }
+}
@@ -0,0 +1,267 @@
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Foundation::{CloseHandle, HANDLE},
+    Win32::Storage::FileSystem::*,
+};
+
+use super::USAGE;
+use crate::tool;
+
+const DEFAULT_DEBOUNCE_MS: u64 = 100;
+// Big enough for a burst like extracting an archive onto the desktop; when it
+// isn't, Windows reports an overflow rather than dropping changes silently
+const BUFFER_SIZE: usize = 64 * 1024;
+
+struct Options {
+    roots: Vec<String>,
+    recursive: bool,
+    debounce: Duration,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Event {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_path: Option<String>,
+    root: String,
+}
+
+// What the reader threads send: one record from ReadDirectoryChangesW, or None
+// for an overflow
+struct Change {
+    root: usize,
+    action: Option<(FILE_ACTION, String)>,
+}
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let opened: Result<Vec<(String, HANDLE)>> = options.roots.iter().map(|root| open_root(root)).collect();
+    let roots = match opened {
+        Ok(roots) => roots,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+
+    let names: Vec<String> = roots.iter().map(|(name, _)| name.clone()).collect();
+    println!("{}", serde_json::json!({ "type": "ready", "roots": names }));
+    let _ = io::stdout().flush();
+
+    let (changes, received) = mpsc::channel();
+    for (index, (_, handle)) in roots.into_iter().enumerate() {
+        let changes = changes.clone();
+        let recursive = options.recursive;
+        thread::spawn(move || read_changes(index, handle, recursive, changes));
+    }
+    drop(changes);
+    let debounce = options.debounce;
+    thread::spawn(move || emit_events(received, names, debounce));
+
+    // Watches run until the caller closes stdin; the readers die with the process
+    let _ = io::stdin().read_to_end(&mut Vec::new());
+    ExitCode::SUCCESS
+}
+
+fn parse_args(args: &[String]) -> std::result::Result<Options, String> {
+    let mut options = Options { roots: Vec::new(), recursive: false, debounce: Duration::from_millis(DEFAULT_DEBOUNCE_MS) };
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--recursive" => options.recursive = true,
+            "--debounce" => {
+                let value = value()?;
+                let ms = value.parse::<u64>().map_err(|_| format!("Invalid --debounce: {}", value))?;
+                options.debounce = Duration::from_millis(ms);
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => options.roots.push(arg.clone()),
+        }
+    }
+    if options.roots.is_empty() {
+        return Err("Expected at least one <dir>".to_string());
+    }
+    Ok(options)
+}
+
+fn open_root(root: &str) -> Result<(String, HANDLE)> {
+    let expanded = create_shortcut::known_folders::expand_known_folder(root)?;
+    let path = std::path::absolute(&expanded).map_err(tool::io_error)?;
+    let name = path.to_string_lossy().trim_end_matches('\\').to_string();
+    let handle = unsafe {
+        CreateFileW(
+            &HSTRING::from(path.as_path()),
+            FILE_LIST_DIRECTORY.0,
+            // Watching must not stop anyone else from renaming or deleting
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAG_BACKUP_SEMANTICS,
+            HANDLE(0),
+        )?
+    };
+    Ok((name, handle))
+}
+
+fn read_changes(root: usize, handle: HANDLE, recursive: bool, changes: Sender<Change>) {
+    // FILE_NOTIFY_INFORMATION records need DWORD alignment
+    let mut buffer = vec![0u32; BUFFER_SIZE / 4];
+    let filter = FILE_NOTIFY_CHANGE_FILE_NAME
+        | FILE_NOTIFY_CHANGE_DIR_NAME
+        | FILE_NOTIFY_CHANGE_LAST_WRITE
+        | FILE_NOTIFY_CHANGE_SIZE;
+    loop {
+        let mut returned = 0;
+        let read = unsafe {
+            ReadDirectoryChangesW(
+                handle,
+                buffer.as_mut_ptr().cast(),
+                BUFFER_SIZE as u32,
+                recursive,
+                filter,
+                Some(&mut returned),
+                None,
+                None,
+            )
+        };
+        if read.is_err() {
+            // The folder itself was deleted or its volume went away
+            let _ = changes.send(Change { root, action: None });
+            break;
+        }
+        if returned == 0 {
+            if changes.send(Change { root, action: None }).is_err() {
+                break;
+            }
+            continue;
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts(buffer.as_ptr().cast::<u8>(), returned as usize) };
+        let mut offset = 0;
+        loop {
+            let record = unsafe { &*(bytes.as_ptr().add(offset) as *const FILE_NOTIFY_INFORMATION) };
+            let name = unsafe {
+                std::slice::from_raw_parts(record.FileName.as_ptr(), record.FileNameLength as usize / 2)
+            };
+            let change = Change { root, action: Some((record.Action, String::from_utf16_lossy(name))) };
+            if changes.send(change).is_err() {
+                unsafe {
+                    let _ = CloseHandle(handle);
+                }
+                return;
+            }
+            if record.NextEntryOffset == 0 {
+                break;
+            }
+            offset += record.NextEntryOffset as usize;
+        }
+    }
+    unsafe {
+        let _ = CloseHandle(handle);
+    }
+}
+
+// Merges changes to the same path that arrive within `debounce` of each other, and
+// prints each event once its path has been quiet that long
+fn emit_events(changes: Receiver<Change>, roots: Vec<String>, debounce: Duration) {
+    let mut pending: Vec<(Event, Instant)> = Vec::new();
+    // Windows reports a rename as an old-name record directly followed by a new-name one
+    let mut renamed_from: Option<String> = None;
+    let mut stdout = io::stdout().lock();
+
+    loop {
+        let wait = pending.iter().map(|(_, at)| (*at + debounce).saturating_duration_since(Instant::now())).min();
+        let received = match wait {
+            Some(wait) => changes.recv_timeout(wait),
+            None => changes.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(Change { root, action: None }) => {
+                pending.retain(|(event, _)| event.root != roots[root]);
+                let event = Event { kind: "overflow", path: None, old_path: None, root: roots[root].clone() };
+                pending.push((event, Instant::now()));
+            }
+            Ok(Change { root, action: Some((action, name)) }) => {
+                let path = format!("{}\\{}", roots[root], name);
+                match action {
+                    FILE_ACTION_RENAMED_OLD_NAME => renamed_from = Some(path),
+                    FILE_ACTION_RENAMED_NEW_NAME => match renamed_from.take() {
+                        Some(old_path) => rename(&mut pending, &roots[root], old_path, path),
+                        None => merge(&mut pending, &roots[root], "created", path),
+                    },
+                    FILE_ACTION_ADDED => merge(&mut pending, &roots[root], "created", path),
+                    FILE_ACTION_REMOVED => merge(&mut pending, &roots[root], "deleted", path),
+                    _ => merge(&mut pending, &roots[root], "modified", path),
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        let (due, waiting): (Vec<_>, Vec<_>) = pending.drain(..).partition(|(_, at)| now >= *at + debounce);
+        pending = waiting;
+        for (event, _) in due {
+            if writeln!(stdout, "{}", serde_json::to_string(&event).unwrap()).and_then(|_| stdout.flush()).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn merge(pending: &mut Vec<(Event, Instant)>, root: &str, kind: &'static str, path: String) {
+    let now = Instant::now();
+    let Some(index) = pending.iter().position(|(event, _)| event.path.as_deref() == Some(path.as_str())) else {
+        let event = Event { kind, path: Some(path), old_path: None, root: root.to_string() };
+        pending.push((event, now));
+        return;
+    };
+    let merged = match (pending[index].0.kind, kind) {
+        // Came and went before anyone saw it
+        ("created", "deleted") => None,
+        ("created", _) => Some("created"),
+        ("deleted", "created") => Some("modified"),
+        ("renamed", "modified") => Some("renamed"),
+        (_, kind) => Some(kind),
+    };
+    match merged {
+        Some(kind) => {
+            let (event, at) = &mut pending[index];
+            event.kind = kind;
+            if kind != "renamed" {
+                event.old_path = None;
+            }
+            *at = now;
+        }
+        None => {
+            pending.remove(index);
+        }
+    }
+}
+
+fn rename(pending: &mut Vec<(Event, Instant)>, root: &str, old_path: String, path: String) {
+    let existing = pending.iter().position(|(event, _)| event.path.as_deref() == Some(old_path.as_str()));
+    let event = match existing.map(|index| pending.remove(index).0) {
+        // Still new to the caller, so it was simply created under the new name
+        Some(event) if event.kind == "created" => Event { path: Some(path), ..event },
+        // Renamed twice: report the first name it had
+        Some(Event { kind: "renamed", old_path: Some(first), .. }) => {
+            Event { kind: "renamed", path: Some(path), old_path: Some(first), root: root.to_string() }
+        }
+        _ => Event { kind: "renamed", path: Some(path), old_path: Some(old_path), root: root.to_string() },
+    };
+    pending.push((event, Instant::now()));
+}
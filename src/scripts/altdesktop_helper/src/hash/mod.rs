@@ -0,0 +1,212 @@
+#[cfg(windows)]
+mod xxh64;
+
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::fs::File;
+#[cfg(windows)]
+use std::io::{self, Read, Write};
+
+#[cfg(windows)]
+use altdesktop_core::error::ErrorReport;
+#[cfg(windows)]
+use altdesktop_core::progress::Progress;
+#[cfg(windows)]
+use create_shortcut::known_folders::expand_known_folder;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{core::*, Win32::Security::Cryptography::*};
+
+use crate::tool;
+#[cfg(windows)]
+use xxh64::Xxh64;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper hash <path>... [--algorithm sha256|xxh64] [--progress]
+  altdesktop-helper hash --batch [--algorithm sha256|xxh64] [--progress] < paths.json
+
+Hashes each file's contents in one read and prints {\"ok\",\"results\":[{\"path\",\"ok\",
+\"size\",\"sha256\",\"xxh64\"}]}, as lowercase hex, with the error fields of a failed
+shortcut --json run on files that couldn't be read. --algorithm computes only one of
+the two: sha256 for a hash nobody can forge, xxh64 for a much faster one that is only
+fit for telling files apart. --batch reads the paths from stdin as a JSON array of
+strings instead. --progress adds {\"type\":\"progress\",\"id\",\"completed\",\"total\",
+\"item\"} lines before the result, one as each file is done, with completed files of
+total so far and id the file's index in the list; while a file over 8MB is hashed,
+its lines also carry each percent read. Paths may start with a known folder token.";
+
+#[cfg(windows)]
+const BUFFER_SIZE: usize = 1024 * 1024;
+// Below this a file hashes too quickly for percentages to mean anything
+#[cfg(windows)]
+const PROGRESS_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+#[cfg(windows)]
+#[derive(Clone, Copy)]
+struct Algorithms {
+    sha256: bool,
+    xxh64: bool,
+}
+
+#[cfg(windows)]
+struct Options {
+    paths: Vec<String>,
+    algorithms: Algorithms,
+    progress: bool,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match parse_args(&args) {
+        Ok(options) => hash_each(&options),
+        Err(message) => tool::usage_error(&message, USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("hash")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<Options, String> {
+    let mut batch = false;
+    let mut algorithms = Algorithms { sha256: true, xxh64: true };
+    let mut progress = false;
+    let mut paths = Vec::new();
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--batch" => batch = true,
+            "--algorithm" => {
+                algorithms = match value()?.as_str() {
+                    "sha256" => Algorithms { sha256: true, xxh64: false },
+                    "xxh64" => Algorithms { sha256: false, xxh64: true },
+                    other => return Err(format!("Unknown algorithm: {} (expected sha256 or xxh64)", other)),
+                }
+            }
+            "--progress" => progress = true,
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if batch {
+        if !paths.is_empty() {
+            return Err("--batch reads its paths from stdin and takes none as arguments".to_string());
+        }
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input).map_err(|e| format!("Failed to read stdin: {}", e))?;
+        paths = serde_json::from_str(&input).map_err(|e| format!("Invalid batch JSON: {}", e))?;
+    } else if paths.is_empty() {
+        return Err("Expected at least one path".to_string());
+    }
+    Ok(Options { paths, algorithms, progress })
+}
+
+// Like fs trash, one unreadable file doesn't stop the rest
+#[cfg(windows)]
+fn hash_each(options: &Options) -> ExitCode {
+    let mut stdout = io::stdout().lock();
+    let mut progress = Progress::new(options.progress, options.paths.len());
+    let mut results = Vec::with_capacity(options.paths.len());
+    for (index, path) in options.paths.iter().enumerate() {
+        let mut report = |percent: u64| progress.partial(index, path, percent);
+        let result = match hash_file(path, options.algorithms, &mut report) {
+            Ok(mut result) => {
+                result["path"] = Value::from(path.as_str());
+                result["ok"] = Value::from(true);
+                result
+            }
+            Err(error) => {
+                let mut result = serde_json::to_value(ErrorReport::from_error(&error)).unwrap();
+                result["path"] = Value::from(path.as_str());
+                result
+            }
+        };
+        progress.finished(index, path);
+        results.push(result);
+    }
+
+    let ok = results.iter().all(|result| result["ok"] == true);
+    let _ = writeln!(stdout, "{}", json!({ "ok": ok, "results": results }));
+    if ok { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+#[cfg(windows)]
+fn hash_file(path: &str, algorithms: Algorithms, report: &mut impl FnMut(u64)) -> Result<Value> {
+    let path = std::path::absolute(expand_known_folder(path)?).map_err(tool::io_error)?;
+    let mut file = File::open(&path).map_err(tool::io_error)?;
+    let size = file.metadata().map_err(tool::io_error)?.len();
+
+    let mut sha256 = algorithms.sha256.then(Sha256::new).transpose()?;
+    let mut xxh64 = algorithms.xxh64.then(Xxh64::new);
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    let (mut read, mut reported) = (0u64, 0u64);
+    loop {
+        let length = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(length) => length,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(tool::io_error(error)),
+        };
+        if let Some(sha256) = &mut sha256 {
+            sha256.update(&buffer[..length])?;
+        }
+        if let Some(xxh64) = &mut xxh64 {
+            xxh64.update(&buffer[..length]);
+        }
+        read += length as u64;
+        // The file may grow while it's read, which would pass 100 early
+        let percent = (read * 100 / size.max(1)).min(99);
+        if size >= PROGRESS_THRESHOLD && percent > reported {
+            report(percent);
+            reported = percent;
+        }
+    }
+    Ok(json!({
+        "size": read,
+        "sha256": sha256.map(Sha256::finish).transpose()?,
+        "xxh64": xxh64.map(|xxh64| format!("{:016x}", xxh64.finish())),
+    }))
+}
+
+// CNG's SHA-256, which uses the CPU's SHA instructions where it has them
+#[cfg(windows)]
+struct Sha256(BCRYPT_HASH_HANDLE);
+
+#[cfg(windows)]
+impl Sha256 {
+    fn new() -> Result<Self> {
+        let mut handle = BCRYPT_HASH_HANDLE::default();
+        unsafe { BCryptCreateHash(BCRYPT_SHA256_ALG_HANDLE, &mut handle, None, None, 0).ok()? };
+        Ok(Sha256(handle))
+    }
+
+    fn update(&mut self, data: &[u8]) -> Result<()> {
+        unsafe { BCryptHashData(self.0, data, 0).ok() }
+    }
+
+    fn finish(self) -> Result<String> {
+        let mut digest = [0u8; 32];
+        unsafe { BCryptFinishHash(self.0, &mut digest, 0).ok()? };
+        Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Sha256 {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = BCryptDestroyHash(self.0);
+        }
+    }
+}
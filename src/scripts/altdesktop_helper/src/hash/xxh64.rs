@@ -0,0 +1,101 @@
+// XXH64 with seed 0, as in the reference xxHash implementation. It is no defense
+// against tampering, only a fast way to tell files apart.
+
+const PRIME_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME_5: u64 = 0x27D4_EB2F_1656_67C5;
+const STRIPE: usize = 32;
+
+pub struct Xxh64 {
+    lanes: [u64; 4],
+    // Bytes that don't yet fill a stripe
+    pending: [u8; STRIPE],
+    pending_len: usize,
+    total_len: u64,
+}
+
+impl Xxh64 {
+    pub fn new() -> Self {
+        Xxh64 {
+            lanes: [PRIME_1.wrapping_add(PRIME_2), PRIME_2, 0, 0u64.wrapping_sub(PRIME_1)],
+            pending: [0; STRIPE],
+            pending_len: 0,
+            total_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        if self.pending_len > 0 {
+            let take = (STRIPE - self.pending_len).min(data.len());
+            self.pending[self.pending_len..self.pending_len + take].copy_from_slice(&data[..take]);
+            self.pending_len += take;
+            data = &data[take..];
+            if self.pending_len < STRIPE {
+                return;
+            }
+            let stripe = self.pending;
+            self.consume(&stripe);
+            self.pending_len = 0;
+        }
+        let mut stripes = data.chunks_exact(STRIPE);
+        for stripe in &mut stripes {
+            self.consume(stripe);
+        }
+        let rest = stripes.remainder();
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.pending_len = rest.len();
+    }
+
+    pub fn finish(&self) -> u64 {
+        let mut hash = if self.total_len >= STRIPE as u64 {
+            let [v1, v2, v3, v4] = self.lanes;
+            let mut hash = v1
+                .rotate_left(1)
+                .wrapping_add(v2.rotate_left(7))
+                .wrapping_add(v3.rotate_left(12))
+                .wrapping_add(v4.rotate_left(18));
+            for lane in self.lanes {
+                hash = (hash ^ round(0, lane)).wrapping_mul(PRIME_1).wrapping_add(PRIME_4);
+            }
+            hash
+        } else {
+            PRIME_5
+        };
+        hash = hash.wrapping_add(self.total_len);
+
+        let mut rest = &self.pending[..self.pending_len];
+        while rest.len() >= 8 {
+            hash ^= round(0, u64::from_le_bytes(rest[..8].try_into().unwrap()));
+            hash = hash.rotate_left(27).wrapping_mul(PRIME_1).wrapping_add(PRIME_4);
+            rest = &rest[8..];
+        }
+        if rest.len() >= 4 {
+            hash ^= (u32::from_le_bytes(rest[..4].try_into().unwrap()) as u64).wrapping_mul(PRIME_1);
+            hash = hash.rotate_left(23).wrapping_mul(PRIME_2).wrapping_add(PRIME_3);
+            rest = &rest[4..];
+        }
+        for &byte in rest {
+            hash ^= (byte as u64).wrapping_mul(PRIME_5);
+            hash = hash.rotate_left(11).wrapping_mul(PRIME_1);
+        }
+
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(PRIME_2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(PRIME_3);
+        hash ^ (hash >> 32)
+    }
+
+    fn consume(&mut self, stripe: &[u8]) {
+        for (lane, word) in self.lanes.iter_mut().zip(stripe.chunks_exact(8)) {
+            *lane = round(*lane, u64::from_le_bytes(word.try_into().unwrap()));
+        }
+    }
+}
+
+fn round(accumulator: u64, input: u64) -> u64 {
+    accumulator.wrapping_add(input.wrapping_mul(PRIME_2)).rotate_left(31).wrapping_mul(PRIME_1)
+}
@@ -0,0 +1,247 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::{self, Write};
+#[cfg(windows)]
+use std::sync::mpsc::Receiver;
+
+#[cfg(windows)]
+use altdesktop_core::error::ErrorReport;
+#[cfg(windows)]
+use serde::Deserialize;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{E_INVALIDARG, ERROR_NOT_FOUND, HWND},
+    Win32::UI::Input::KeyboardAndMouse::*,
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper hotkeys listen [<config.json>]
+
+Registers system-wide hotkeys and prints a JSON line for each press until stdin
+closes. <config.json> holds an initial array of {\"id\",\"keys\"}, where keys is like
+Win+Alt+D or Ctrl+Shift+F5. The first line is
+{\"type\":\"ready\",\"registered\":[...]}, then {\"type\":\"pressed\",\"id\"} per press.
+Each stdin line changes the set: {\"add\":{\"id\",\"keys\"}} registers or replaces one and
+answers {\"type\":\"added\",\"id\"}, {\"remove\":\"<id>\"} answers {\"type\":\"removed\",\"id\"}.
+Failures, including keys another app already holds, are
+{\"type\":\"error\",\"id\",...} with the error fields of a failed shortcut --json run.";
+
+// Posted to the window when the stdin reader has queued a request
+#[cfg(windows)]
+const WM_REQUEST: u32 = WM_APP + 1;
+
+#[cfg(windows)]
+#[derive(Deserialize)]
+struct Binding {
+    id: String,
+    keys: String,
+}
+
+#[cfg(windows)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Request {
+    Add(Binding),
+    Remove(String),
+}
+
+#[cfg(windows)]
+struct Hotkeys {
+    window: HWND,
+    // Indexed by RegisterHotKey ID minus one; None once removed
+    slots: Vec<Option<Registered>>,
+}
+
+#[cfg(windows)]
+struct Registered {
+    id: String,
+    modifiers: HOT_KEY_MODIFIERS,
+    key: u16,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let bindings = match tool::string_args(args).and_then(|args| parse_args(&args)) {
+        Ok(bindings) => bindings,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    // Message-only: never shown, but RegisterHotKey needs a window to post to
+    let window = unsafe {
+        CreateWindowExW(WINDOW_EX_STYLE(0), w!("STATIC"), None, WINDOW_STYLE(0), 0, 0, 0, 0, HWND_MESSAGE, None, None, None)
+    };
+    if window.0 == 0 {
+        return tool::finish::<()>(Err(Error::from_win32()));
+    }
+
+    let mut hotkeys = Hotkeys { window, slots: Vec::new() };
+    let mut registered = Vec::new();
+    let mut failed = Vec::new();
+    for binding in bindings {
+        match hotkeys.add(&binding) {
+            Ok(()) => registered.push(binding.id),
+            Err(error) => failed.push(error_line(&binding.id, &error)),
+        }
+    }
+    println!("{}", json!({ "type": "ready", "registered": registered }));
+    for line in failed {
+        println!("{}", line);
+    }
+    let _ = io::stdout().flush();
+
+    let requests = tool::read_requests(window, WM_REQUEST);
+    hotkeys.pump(requests);
+    unsafe {
+        let _ = DestroyWindow(window);
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("hotkeys")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<Vec<Binding>, String> {
+    match args {
+        [_] => Ok(Vec::new()),
+        [_, flag] if flag.starts_with("--") => Err(format!("Unknown option: {}", flag)),
+        [_, config] => {
+            let text = std::fs::read_to_string(config).map_err(|error| format!("Could not read {}: {}", config, error))?;
+            serde_json::from_str(&text).map_err(|error| format!("Invalid config {}: {}", config, error))
+        }
+        _ => Err("Expected at most one <config.json>".to_string()),
+    }
+}
+
+#[cfg(windows)]
+impl Hotkeys {
+    fn pump(&mut self, requests: Receiver<Option<std::result::Result<Request, String>>>) {
+        let mut message = MSG::default();
+        while unsafe { GetMessageW(&mut message, HWND(0), 0, 0) }.as_bool() {
+            match message.message {
+                WM_HOTKEY => {
+                    if let Some(Some(registered)) = self.slots.get(message.wParam.0.wrapping_sub(1)) {
+                        emit(json!({ "type": "pressed", "id": registered.id }));
+                    }
+                }
+                WM_REQUEST => {
+                    while let Ok(request) = requests.try_recv() {
+                        match request {
+                            Some(request) => self.handle(request),
+                            None => return,
+                        }
+                    }
+                }
+                _ => unsafe {
+                    DispatchMessageW(&message);
+                },
+            }
+        }
+    }
+
+    fn handle(&mut self, request: std::result::Result<Request, String>) {
+        match request {
+            Ok(Request::Add(binding)) => match self.add(&binding) {
+                Ok(()) => emit(json!({ "type": "added", "id": binding.id })),
+                Err(error) => emit(error_line(&binding.id, &error)),
+            },
+            Ok(Request::Remove(id)) => {
+                if self.remove(&id).is_some() {
+                    emit(json!({ "type": "removed", "id": id }));
+                } else {
+                    let error = Error::new(ERROR_NOT_FOUND.to_hresult(), format!("No hotkey with id {}", id).into());
+                    emit(error_line(&id, &error));
+                }
+            }
+            Err(message) => {
+                let mut line = serde_json::to_value(ErrorReport::usage(&format!("Invalid request: {}", message))).unwrap();
+                line["type"] = Value::from("error");
+                emit(line);
+            }
+        }
+    }
+
+    // Adding an ID that is already registered replaces its keys, or keeps the old
+    // ones if the new ones can't be registered
+    fn add(&mut self, binding: &Binding) -> Result<()> {
+        let (modifiers, key) = parse_keys(&binding.keys).map_err(|message| Error::new(E_INVALIDARG, message.into()))?;
+        // Unregistered first, so the same keys can be given again
+        let previous = self.remove(&binding.id);
+        let registered = Registered { id: binding.id.clone(), modifiers, key };
+        if let Err(error) = self.register(registered) {
+            if let Some(previous) = previous {
+                let _ = self.register(previous);
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn register(&mut self, registered: Registered) -> Result<()> {
+        let slot = self.slots.iter().position(Option::is_none).unwrap_or(self.slots.len());
+        unsafe {
+            RegisterHotKey(self.window, slot as i32 + 1, registered.modifiers | MOD_NOREPEAT, registered.key as u32)?
+        };
+        if slot == self.slots.len() {
+            self.slots.push(None);
+        }
+        self.slots[slot] = Some(registered);
+        Ok(())
+    }
+
+    fn remove(&mut self, id: &str) -> Option<Registered> {
+        let slot = self.slots.iter().position(|slot| slot.as_ref().is_some_and(|registered| registered.id == id))?;
+        unsafe {
+            let _ = UnregisterHotKey(self.window, slot as i32 + 1);
+        }
+        self.slots[slot].take()
+    }
+}
+
+// Like shortcut hotkeys, but with Win, which shortcuts can't use
+#[cfg(windows)]
+fn parse_keys(value: &str) -> std::result::Result<(HOT_KEY_MODIFIERS, u16), String> {
+    let mut modifiers = HOT_KEY_MODIFIERS(0);
+    let mut key = None;
+    for part in value.split('+').map(str::trim) {
+        match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => modifiers |= MOD_CONTROL,
+            "alt" => modifiers |= MOD_ALT,
+            "shift" => modifiers |= MOD_SHIFT,
+            "win" | "windows" | "super" => modifiers |= MOD_WIN,
+            _ => {
+                if key.is_some() {
+                    return Err(format!("Hotkey has more than one key: {}", value));
+                }
+                key = Some(
+                    create_shortcut::hotkey::parse_key(part).ok_or_else(|| format!("Unknown hotkey key: {}", part))?,
+                );
+            }
+        }
+    }
+    let key = key.ok_or_else(|| format!("Hotkey has no key: {}", value))?;
+    Ok((modifiers, key))
+}
+
+#[cfg(windows)]
+fn error_line(id: &str, error: &Error) -> Value {
+    let mut line = serde_json::to_value(ErrorReport::from_error(error)).unwrap();
+    line["type"] = Value::from("error");
+    line["id"] = Value::from(id);
+    line
+}
+
+// Closing stdin is what stops this tool, so output lost to a closed stdout is ignored
+#[cfg(windows)]
+fn emit(line: Value) {
+    let _ = altdesktop_core::json::emit(line);
+}
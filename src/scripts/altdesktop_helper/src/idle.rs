@@ -0,0 +1,164 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::{self, Read};
+#[cfg(windows)]
+use std::thread;
+#[cfg(windows)]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(windows)]
+use altdesktop_core::json::emit;
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use serde_json::json;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::System::SystemInformation::GetTickCount,
+    Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO},
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper idle query
+  altdesktop-helper idle watch [--after <seconds>]...
+
+query prints {\"idle\",\"lastInput\"}: the milliseconds since the user last pressed a
+key, moved the mouse or touched the screen in this session, and when that was, in
+milliseconds since 1970.
+watch prints {\"type\":\"ready\",\"idle\"}, then {\"type\":\"idle\",\"after\",\"idle\"} each
+time the user has been idle for one of the --after thresholds, 300 seconds when
+none are given, and {\"type\":\"active\",\"idle\"} with how long they were away once
+they come back, until stdin closes.";
+
+#[cfg(windows)]
+const DEFAULT_THRESHOLD: u64 = 300;
+// Input has no notification, so coming back is noticed within this long
+#[cfg(windows)]
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Idle {
+    idle: u64,
+    last_input: Option<u64>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--watch") => match parse_thresholds(&args[2..]) {
+            Ok(thresholds) => watch_until_stdin_closes(thresholds),
+            Err(message) => tool::usage_error(&message, USAGE),
+        },
+        None => tool::finish(idle_time().map(|idle| Idle {
+            idle,
+            last_input: SystemTime::now()
+                .checked_sub(Duration::from_millis(idle))
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_millis() as u64),
+        })),
+        Some(_) => tool::usage_error("query takes no arguments", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("idle")
+}
+
+// In milliseconds, smallest first
+#[cfg(windows)]
+fn parse_thresholds(args: &[String]) -> std::result::Result<Vec<u64>, String> {
+    let mut thresholds = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--after" => {
+                let seconds = value()?;
+                match seconds.parse::<u64>() {
+                    Ok(seconds) if seconds > 0 => thresholds.push(seconds * 1000),
+                    _ => return Err(format!("Invalid --after: {} (expected a number of seconds)", seconds)),
+                }
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    if thresholds.is_empty() {
+        thresholds.push(DEFAULT_THRESHOLD * 1000);
+    }
+    thresholds.sort_unstable();
+    thresholds.dedup();
+    Ok(thresholds)
+}
+
+// Both counts wrap every 49.7 days, so the difference is taken modulo that too
+#[cfg(windows)]
+fn idle_time() -> Result<u64> {
+    let mut info = LASTINPUTINFO { cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32, dwTime: 0 };
+    if !unsafe { GetLastInputInfo(&mut info) }.as_bool() {
+        return Err(Error::from_win32());
+    }
+    Ok(unsafe { GetTickCount() }.wrapping_sub(info.dwTime) as u64)
+}
+
+#[cfg(windows)]
+fn watch_until_stdin_closes(thresholds: Vec<u64>) -> ExitCode {
+    let idle = match idle_time() {
+        Ok(idle) => idle,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    if !emit(json!({ "type": "ready", "idle": idle })) {
+        return ExitCode::SUCCESS;
+    }
+    thread::spawn(move || watch(thresholds, idle));
+    let _ = io::stdin().read_to_end(&mut Vec::new());
+    ExitCode::SUCCESS
+}
+
+#[cfg(windows)]
+fn watch(thresholds: Vec<u64>, mut idle: u64) {
+    // How many thresholds this stretch of idleness has already crossed; starting
+    // past some of them reports those straight away
+    let mut crossed = 0;
+    loop {
+        while let Some(&after) = thresholds.get(crossed).filter(|&&after| idle >= after) {
+            if !emit(json!({ "type": "idle", "after": after / 1000, "idle": idle })) {
+                return;
+            }
+            crossed += 1;
+        }
+
+        // Asleep until the next threshold while active, polling for input once idle
+        let wait = match thresholds.get(crossed) {
+            Some(&after) if crossed == 0 => Duration::from_millis(after - idle),
+            _ => POLL_INTERVAL,
+        };
+        thread::sleep(wait);
+        let previous = idle;
+        idle = match idle_time() {
+            Ok(idle) => idle,
+            Err(_) => continue,
+        };
+        if crossed > 0 && idle < previous {
+            // Away for as long as idle had grown to, plus what passed before the input
+            let away = previous + wait.as_millis() as u64 - idle;
+            if !emit(json!({ "type": "active", "idle": away })) {
+                return;
+            }
+            crossed = 0;
+        }
+    }
+}
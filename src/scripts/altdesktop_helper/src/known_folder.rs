@@ -0,0 +1,109 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use create_shortcut::known_folders::{find_known_folder, known_folder_path, KNOWN_FOLDERS};
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{E_INVALIDARG, HANDLE},
+    Win32::System::Com::CoTaskMemFree,
+    Win32::UI::Shell::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper known-folder [<name>...]
+
+Prints {\"name\",\"token\",\"path\",\"displayName\",\"redirected\"} for each known folder
+named, or for every one the helper knows when none are given: its current path,
+following redirection to OneDrive or a network share, the name Explorer shows for
+it in the user's language, and whether it was moved from where Windows puts it by
+default. path and displayName are null for a folder this user doesn't have, such as
+OneDrive before it is set up. <name> is a token as in {Desktop}, with or without the
+braces: Desktop, PublicDesktop, StartMenu, CommonStartMenu, Programs, CommonPrograms,
+Startup, CommonStartup, QuickLaunch, SendTo, Favorites, Documents, Downloads,
+Pictures, Music, Videos, Screenshots, Templates, Recent, Profile, OneDrive, Public,
+PublicDocuments, RoamingAppData, LocalAppData, ProgramData, ProgramFiles,
+ProgramFilesX86 or Fonts.";
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Folder {
+    name: &'static str,
+    token: String,
+    path: Option<String>,
+    display_name: Option<String>,
+    redirected: bool,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    if let Some(flag) = args[1..].iter().find(|arg| arg.starts_with("--")) {
+        return tool::usage_error(&format!("Unknown option: {}", flag), USAGE);
+    }
+    let folders: Result<Vec<(&'static str, GUID)>> = if args.len() == 1 {
+        Ok(KNOWN_FOLDERS.to_vec())
+    } else {
+        args[1..]
+            .iter()
+            .map(|name| {
+                let token = name.strip_prefix('{').and_then(|name| name.strip_suffix('}')).unwrap_or(name);
+                find_known_folder(token)
+                    .ok_or_else(|| Error::new(E_INVALIDARG, format!("Unknown known folder: {}", name).into()))
+            })
+            .collect()
+    };
+    tool::finish(folders.map(|folders| folders.into_iter().map(|(name, id)| describe(name, &id)).collect::<Vec<_>>()))
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("known-folder")
+}
+
+#[cfg(windows)]
+fn describe(name: &'static str, id: &GUID) -> Folder {
+    let path = known_folder_path(id).ok();
+    let default_path = default_path(id);
+    let redirected = match (&path, &default_path) {
+        (Some(path), Some(default_path)) => !path.eq_ignore_ascii_case(default_path),
+        _ => false,
+    };
+    Folder {
+        name,
+        token: format!("{{{}}}", name),
+        display_name: path.as_ref().and_then(|_| display_name(id)),
+        path,
+        redirected,
+    }
+}
+
+// Where the folder would be had it never been moved, whether or not it exists there
+#[cfg(windows)]
+fn default_path(id: &GUID) -> Option<String> {
+    unsafe {
+        let path = SHGetKnownFolderPath(id, KF_FLAG_DEFAULT_PATH | KF_FLAG_DONT_VERIFY, HANDLE(0)).ok()?;
+        let value = path.to_string().ok();
+        CoTaskMemFree(Some(path.0 as *const _));
+        value
+    }
+}
+
+// A folder's desktop.ini and the known folder's own definition both feed into this
+#[cfg(windows)]
+fn display_name(id: &GUID) -> Option<String> {
+    unsafe {
+        let item: IShellItem = SHGetKnownFolderItem(id, KF_FLAG_DEFAULT, HANDLE(0)).ok()?;
+        let name = item.GetDisplayName(SIGDN_NORMALDISPLAY).ok()?;
+        Some(tool::take_string(name))
+    }
+}
@@ -0,0 +1,196 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::time::Instant;
+
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::CloseHandle,
+    Win32::System::Threading::*,
+    Win32::UI::Shell::*,
+    Win32::UI::WindowsAndMessaging::{SHOW_WINDOW_CMD, SW_HIDE, SW_SHOWMAXIMIZED, SW_SHOWMINNOACTIVE, SW_SHOWNORMAL},
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper launch <target> [--verb <verb>] [--args <arguments>] [--working-dir <dir>]
+                           [--show normal|minimized|maximized|hidden] [--env <name>=<value>]...
+                           [--priority idle|below-normal|normal|above-normal|high] [--wait]
+
+Opens <target> the way Explorer would: a file, folder, shortcut, or URL with a
+registered protocol such as steam://rungameid/440. --verb is open (the default),
+runas to elevate through UAC, edit, print, explore, or any other verb the target's
+file type registers. <target> may start with a known folder token such as {Desktop}.
+Prints {\"ok\":true,\"pid\":...}; pid is null when no new process was started, e.g.
+when the target was handed to an app that was already running.
+--env sets an environment variable for the new process, or with an empty value
+removes it; the rest of the helper's environment is passed on as usual. --priority
+sets the new process's priority class. Neither reaches a target handed to a running
+app, and --env doesn't survive the UAC prompt of --verb runas.
+--wait waits for the new process to exit and adds its exitCode and runtime, in ms,
+both null when no new process was started.";
+
+#[cfg(windows)]
+struct Options {
+    target: String,
+    verb: Option<String>,
+    arguments: Option<String>,
+    working_dir: Option<String>,
+    show: SHOW_WINDOW_CMD,
+    env: Vec<(String, String)>,
+    priority: Option<PROCESS_CREATION_FLAGS>,
+    wait: bool,
+}
+
+#[cfg(windows)]
+struct Launched {
+    pid: Option<u32>,
+    exit_code: Option<u32>,
+    runtime: Option<u64>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let options = match tool::string_args(args).and_then(|args| parse_args(&args)) {
+        Ok(options) => options,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    tool::finish(launch(&options).map(|launched| {
+        let mut line = serde_json::json!({ "ok": true, "pid": launched.pid });
+        if options.wait {
+            line["exitCode"] = launched.exit_code.into();
+            line["runtime"] = launched.runtime.into();
+        }
+        line
+    }))
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("launch")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<Options, String> {
+    let mut verb = None;
+    let mut arguments = None;
+    let mut working_dir = None;
+    let mut show = SW_SHOWNORMAL;
+    let mut env = Vec::new();
+    let mut priority = None;
+    let mut wait = false;
+    let mut positional = Vec::new();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--verb" => verb = Some(value()?).filter(|verb| verb != "open"),
+            "--args" => arguments = Some(value()?),
+            "--working-dir" => working_dir = Some(value()?),
+            "--show" => {
+                show = match value()?.as_str() {
+                    "normal" => SW_SHOWNORMAL,
+                    "minimized" => SW_SHOWMINNOACTIVE,
+                    "maximized" => SW_SHOWMAXIMIZED,
+                    "hidden" => SW_HIDE,
+                    other => return Err(format!("Invalid --show: {} (expected normal, minimized, maximized or hidden)", other)),
+                }
+            }
+            "--env" => {
+                let value = value()?;
+                match value.split_once('=') {
+                    Some((name, value)) if !name.is_empty() => env.push((name.to_string(), value.to_string())),
+                    _ => return Err(format!("Invalid --env: {} (expected <name>=<value>)", value)),
+                }
+            }
+            "--priority" => {
+                priority = Some(match value()?.as_str() {
+                    "idle" => IDLE_PRIORITY_CLASS,
+                    "below-normal" => BELOW_NORMAL_PRIORITY_CLASS,
+                    "normal" => NORMAL_PRIORITY_CLASS,
+                    "above-normal" => ABOVE_NORMAL_PRIORITY_CLASS,
+                    "high" => HIGH_PRIORITY_CLASS,
+                    other => {
+                        return Err(format!(
+                            "Invalid --priority: {} (expected idle, below-normal, normal, above-normal or high)",
+                            other
+                        ));
+                    }
+                })
+            }
+            "--wait" => wait = true,
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    match positional.as_slice() {
+        [target] => Ok(Options { target: target.clone(), verb, arguments, working_dir, show, env, priority, wait }),
+        _ => Err("Expected <target>".to_string()),
+    }
+}
+
+/// Runs the target through ShellExecuteEx and returns the new process's ID, if any,
+/// and with `wait` how it exited.
+#[cfg(windows)]
+fn launch(options: &Options) -> Result<Launched> {
+    let target = HSTRING::from(create_shortcut::known_folders::expand_known_folder(&options.target)?);
+    let verb = options.verb.as_deref().map(HSTRING::from);
+    let arguments = options.arguments.as_deref().map(HSTRING::from);
+    let working_dir = options.working_dir.as_deref().map(HSTRING::from);
+    let pcwstr = |value: &Option<HSTRING>| value.as_ref().map_or(PCWSTR::null(), |value| PCWSTR(value.as_ptr()));
+
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+        // NOASYNC because the process exits right after; NO_UI keeps failures on
+        // stdout rather than in a message box, though UAC still prompts
+        fMask: SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NOASYNC | SEE_MASK_FLAG_NO_UI,
+        lpVerb: pcwstr(&verb),
+        lpFile: PCWSTR(target.as_ptr()),
+        lpParameters: pcwstr(&arguments),
+        lpDirectory: pcwstr(&working_dir),
+        nShow: options.show.0,
+        ..Default::default()
+    };
+    // ShellExecuteEx has no environment of its own to pass; the new process inherits
+    // this one's, and the helper exits right after
+    for (name, value) in &options.env {
+        unsafe {
+            if value.is_empty() {
+                std::env::remove_var(name);
+            } else {
+                std::env::set_var(name, value);
+            }
+        }
+    }
+    unsafe { ShellExecuteExW(&mut info)? };
+    let started = Instant::now();
+
+    let mut launched = Launched { pid: None, exit_code: None, runtime: None };
+    if info.hProcess.is_invalid() {
+        return Ok(launched);
+    }
+    unsafe {
+        launched.pid = Some(GetProcessId(info.hProcess)).filter(|&pid| pid != 0);
+        if let Some(priority) = options.priority {
+            // The process has started by now; failing to change its priority, e.g.
+            // for an elevated one, shouldn't report the launch as failed
+            let _ = SetPriorityClass(info.hProcess, priority);
+        }
+        if options.wait {
+            WaitForSingleObject(info.hProcess, INFINITE);
+            let mut code = 0u32;
+            if GetExitCodeProcess(info.hProcess, &mut code).is_ok() {
+                launched.exit_code = Some(code);
+            }
+            launched.runtime = Some(started.elapsed().as_millis() as u64);
+        }
+        let _ = CloseHandle(info.hProcess);
+    }
+    Ok(launched)
+}
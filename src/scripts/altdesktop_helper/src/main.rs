@@ -0,0 +1,661 @@
+mod apps;
+mod assoc;
+mod audio;
+mod capabilities;
+mod capture;
+mod clipboard;
+mod crash;
+mod desktop;
+mod display_name;
+mod doctor;
+mod drives;
+mod drop_target;
+#[cfg(windows)]
+mod elevate;
+mod fileinfo;
+mod fonts;
+mod fs;
+mod hash;
+mod hotkeys;
+mod idle;
+mod known_folder;
+mod launch;
+mod media;
+mod monitors;
+mod notify;
+mod pick_color;
+#[cfg(windows)]
+mod pipe;
+mod power;
+mod probe;
+mod process;
+#[cfg(windows)]
+mod registry;
+mod schedule;
+mod serve;
+mod shell;
+mod startup;
+mod taskbar;
+mod theme;
+mod tile;
+mod tool;
+mod tray;
+mod vdesktop;
+mod wallpaper;
+mod window;
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use altdesktop_core::com::ComGuard;
+use altdesktop_core::exit;
+
+const USAGE: &str = "Usage:
+  altdesktop-helper shortcut <command> [arguments]
+  altdesktop-helper icon <command> [arguments]
+  altdesktop-helper wallpaper <command> [arguments]
+  altdesktop-helper monitors <command>
+  altdesktop-helper desktop <command> [arguments]
+  altdesktop-helper fs <command> [arguments]
+  altdesktop-helper launch <target> [arguments]
+  altdesktop-helper shell <command> [arguments]
+  altdesktop-helper apps <command> [arguments]
+  altdesktop-helper assoc <command> [arguments]
+  altdesktop-helper theme <command>
+  altdesktop-helper hotkeys listen [<config.json>]
+  altdesktop-helper notify --title <title> [arguments]
+  altdesktop-helper tray show --icon <path> [arguments]
+  altdesktop-helper clipboard <command> [arguments]
+  altdesktop-helper drop-target attach <hwnd>
+  altdesktop-helper fileinfo <path>
+  altdesktop-helper audio <command> [arguments]
+  altdesktop-helper media <command> [arguments]
+  altdesktop-helper power <command>
+  altdesktop-helper idle <command> [arguments]
+  altdesktop-helper windows <command> [arguments]
+  altdesktop-helper known-folder [<name>...]
+  altdesktop-helper fonts <command> [arguments]
+  altdesktop-helper hash <path>... [arguments]
+  altdesktop-helper drives <command> [arguments]
+  altdesktop-helper probe <path>... [--timeout <ms>]
+  altdesktop-helper startup <command> [arguments]
+  altdesktop-helper capture <command> [arguments]
+  altdesktop-helper pick-color [--interval <ms>]
+  altdesktop-helper taskbar <command>
+  altdesktop-helper vdesktop <command> [arguments]
+  altdesktop-helper display-name <path>...
+  altdesktop-helper tile export [<tile.json>]
+  altdesktop-helper process watch <pid>... [--children]
+  altdesktop-helper doctor [--cache-dir <dir>]
+  altdesktop-helper capabilities
+  altdesktop-helper --serve [--pipe [name]]
+  altdesktop-helper --elevate <tool> <command> [arguments]
+  altdesktop-helper [--log <filter>] [--lang <tag>] [--config <file.toml>] [--allowed-output-root <dir>]...
+                    <any of the above>
+
+Shortcut commands: create, validate, edit, clone, read, pin-state, resolve, verify, delete,
+                   url, fs-link, jump-list, batch, list-known-folders
+Icon commands: extract, batch, watch, enumerate, overlay, archive, steam-art
+Wallpaper commands: set, color, slideshow, next, previous, current
+Monitors commands: list, cursor
+Desktop commands: attach, worker-w, icons, layout
+Fs commands: watch, trash, link, customize, cloud
+Shell commands: properties, menu, invoke, watch
+Apps commands: list, start-menu, games, search
+Assoc commands: query, open-with
+Theme commands: query, watch
+Clipboard commands: get-image, get-files, set-files
+Audio commands: list, set, set-default
+Media commands: now-playing, watch, send
+Power commands: status, watch
+Idle commands: query, watch
+Windows commands: list, focus, minimize, maximize, restore, move, close, backdrop
+Fonts commands: list, preview
+Drives commands: list, watch
+Startup commands: list, query, add, remove
+Capture commands: monitor, window, region
+Taskbar commands: query, watch
+Vdesktop commands: list, window, move, switch
+
+Run a command without arguments to see its own usage.
+
+Every tool exits with the same codes, so the reason for a failure can be told
+without reading the output: 0 success, 1 any other failure or an answer of no (a
+batch with failed jobs, a shortcut that doesn't resolve), 2 a bad command line,
+config or request, 3 not found, 4 access denied, 5 timeout, 6 not supported on this
+platform, 70 a crash. Results and JSON errors go to stdout. icon is the exception:
+its --stdout and --data-uri write the image itself there, so its error goes to
+stderr as one JSON line, the first, and needs parsing. Anything else on stderr, such
+as usage text, is diagnostics for a person.
+
+--serve stays resident and answers newline-delimited JSON-RPC 2.0 requests on
+stdin, one response line each on stdout, until stdin closes. Methods:
+  icon.extract      params: an icon batch job, plus an optional cacheDir and sharedMemory
+  memory.release    params: {\"name\"} of a shared section; result: {\"released\"}
+  shortcut.create   params: a shortcut batch spec; result: {\"path\"}
+  shortcut.resolve  params: {\"path\", \"save\"}; result: as shortcut resolve
+  tile.export       params: a tile export definition; result: as tile export
+  capabilities      params: {}; result: as capabilities
+  cancel            params: {\"id\"} of an earlier request; result: {\"cancelled\"}
+Failed calls have error code -32000 with the tool's JSON error as data. A cancel is
+answered at once: the request it names is answered with error code -32800, straight
+away if it hadn't started, or once it reaches a point where it can stop, removing
+any files it had written. cancelled is false for a request that was answered already.
+With sharedMemory set, icon.extract writes no files: its result lists sections,
+[{\"name\", \"size\", \"length\"}], one named file mapping per frame that the app opens
+with OpenFileMapping and reads length bytes of, in the job's format (rgba gives the
+width and height as little-endian u32s, then the pixels). The helper holds each one
+until memory.release names it or the session ends.
+A request may carry \"priority\" next to its method: visible (the tiles in view),
+normal (the default) or background (prefetching, bulk refreshes). Queued requests
+run highest priority first, in order of arrival within one; with --pipe, background
+requests only ever take half the workers and normal ones all but one, so a visible
+request never waits long behind a bulk refresh.
+
+--pipe serves the same methods on \\\\.\\pipe\\<name> (default altdesktop-helper) to
+any number of clients instead, until killed. Messages are a 4-byte little-endian
+length followed by the JSON; requests run concurrently and responses arrive as
+they finish, so match them by id.
+
+--elevate runs the tool in a copy of the helper started through the UAC prompt, for
+commands that need administrator rights, such as writing to the Public Desktop or
+creating shortcuts for every user. Its stdout, stderr and stdin are relayed over
+pipes and its exit code is returned, so the caller sees what an unelevated run would
+print. It runs the tool directly when the helper is elevated already; a declined
+prompt fails with the JSON error of ERROR_CANCELLED.
+
+--log writes a timestamped log to helper.<date>.log in %APPDATA%\\AltDesktop\\logs,
+one file a day and the last 7 kept. <filter> is a level (error, warn, info, debug,
+trace) or directives such as icon_extractor=trace,info. Failures are logged with
+their HRESULT and the arguments of the tool or request that failed. ALTDESKTOP_LOG
+turns logging on the same way without the flag.
+
+A crash, a panic or an exception no handler catches (usually raised by a shell
+extension loaded while extracting an icon), exits with code 70 after writing
+crash-<time>-<pid>.json to the same folder, whether or not logging is on: its kind,
+message, exception code and faulting module, and the helper's version and arguments.
+On Windows a minidump of the same name sits next to it, named by its dump field.
+
+Every JSON error carries userMessage, a sentence for its code that can be shown to
+the user as it is, next to the stable code and the technical message. --lang picks
+its language from a tag such as de, pt-BR or zh-Hant (English is the default, and
+the fallback for languages without a translation).
+
+--config reads defaults from a TOML file, so they needn't be passed on every run.
+Flags and request fields still win over it; every key is optional:
+  [icon]      sizes = [32, 64], format = \"webp\", cache-dir = '<dir>'
+              (as --sizes, --format and --cache-dir of icon extract and batch jobs)
+  [timeouts]  probe, download, resolve, extract, in ms: probe --timeout, favicon and
+              Steam art downloads (default 10000), shortcut resolve (default 3000),
+              icon extract --timeout (default 30000)
+  [log]       level = \"info\" (as --log, when ALTDESKTOP_LOG isn't set either)
+An unreadable or invalid file fails the run before the tool starts, saying why.
+
+--allowed-output-root, given once per folder, confines every file the tools write or
+delete (icon outputs and cache folders, shortcuts, captures, clipboard images, font
+previews, media art, links, trashed files) to those folders and the ones inside
+them, for an app that passes on paths it was handed, such as from a renderer it
+doesn't trust. A path is compared once it's absolute and the symlinks and junctions
+in the part that exists are followed, the file itself included, so neither .. nor a
+link leads out; one outside fails with E_ACCESS (exit code 4). Whether or not it's
+given, paths to read or write that name a device (\\\\.\\..., \\\\?\\GLOBALROOT\\...,
+CON, NUL, COM1) and paths to write that name an alternate data stream
+(file.txt:stream) fail with E_INVALID_ARG (exit code 2). The restriction holds for
+every --serve request and is passed on to --elevate.";
+
+struct Tool {
+    run: fn(Vec<OsString>) -> ExitCode,
+    // Run without a flag: the tool's original positional form. None for a tool
+    // with no commands, which takes its arguments straight after its name.
+    default_command: Option<&'static str>,
+    // Every other command is the tool's flag of the same name
+    commands: &'static [&'static str],
+    // Whether it runs anywhere but Windows, where the rest only fail as unsupported
+    portable: bool,
+}
+
+const SHORTCUT: Tool = Tool {
+    run: create_shortcut::run,
+    default_command: Some("create"),
+    commands: &[
+        "validate", "edit", "clone", "read", "pin-state", "resolve", "verify", "delete",
+        "url", "fs-link", "jump-list", "batch", "list-known-folders",
+    ],
+    portable: false,
+};
+
+const ICON: Tool = Tool {
+    run: icon_extractor::run,
+    default_command: Some("extract"),
+    commands: &["batch", "watch", "enumerate", "overlay", "archive", "steam-art"],
+    portable: true,
+};
+
+const WALLPAPER: Tool = Tool {
+    run: wallpaper::run,
+    default_command: Some("set"),
+    commands: &["color", "slideshow", "next", "previous", "current"],
+    portable: false,
+};
+
+const MONITORS: Tool = Tool {
+    run: monitors::run,
+    default_command: Some("list"),
+    commands: &["cursor"],
+    portable: false,
+};
+
+const DESKTOP: Tool = Tool {
+    run: desktop::run,
+    default_command: Some("attach"),
+    commands: &["worker-w", "icons", "layout"],
+    portable: false,
+};
+
+const FS: Tool = Tool {
+    run: fs::run,
+    default_command: Some("watch"),
+    commands: &["trash", "link", "customize", "cloud"],
+    portable: false,
+};
+
+const LAUNCH: Tool = Tool {
+    run: launch::run,
+    default_command: None,
+    commands: &[],
+    portable: false,
+};
+
+const SHELL: Tool = Tool {
+    run: shell::run,
+    default_command: Some("properties"),
+    commands: &["menu", "invoke", "watch"],
+    portable: false,
+};
+
+const APPS: Tool = Tool {
+    run: apps::run,
+    default_command: Some("list"),
+    commands: &["start-menu", "games", "search"],
+    portable: false,
+};
+
+const ASSOC: Tool = Tool {
+    run: assoc::run,
+    default_command: Some("query"),
+    commands: &["open-with"],
+    portable: false,
+};
+
+const THEME: Tool = Tool {
+    run: theme::run,
+    default_command: Some("query"),
+    commands: &["watch"],
+    portable: false,
+};
+
+const HOTKEYS: Tool = Tool {
+    run: hotkeys::run,
+    default_command: Some("listen"),
+    commands: &[],
+    portable: false,
+};
+
+const NOTIFY: Tool = Tool {
+    run: notify::run,
+    default_command: None,
+    commands: &[],
+    portable: false,
+};
+
+const TRAY: Tool = Tool {
+    run: tray::run,
+    default_command: Some("show"),
+    commands: &[],
+    portable: false,
+};
+
+const CLIPBOARD: Tool = Tool {
+    run: clipboard::run,
+    default_command: Some("get-image"),
+    commands: &["get-files", "set-files"],
+    portable: false,
+};
+
+const DROP_TARGET: Tool = Tool {
+    run: drop_target::run,
+    default_command: Some("attach"),
+    commands: &[],
+    portable: false,
+};
+
+const FILEINFO: Tool = Tool {
+    run: fileinfo::run,
+    default_command: None,
+    commands: &[],
+    portable: false,
+};
+
+const AUDIO: Tool = Tool {
+    run: audio::run,
+    default_command: Some("list"),
+    commands: &["set", "set-default"],
+    portable: false,
+};
+
+const MEDIA: Tool = Tool {
+    run: media::run,
+    default_command: Some("now-playing"),
+    commands: &["watch", "send"],
+    portable: false,
+};
+
+const POWER: Tool = Tool {
+    run: power::run,
+    default_command: Some("status"),
+    commands: &["watch"],
+    portable: false,
+};
+
+const IDLE: Tool = Tool {
+    run: idle::run,
+    default_command: Some("query"),
+    commands: &["watch"],
+    portable: false,
+};
+
+const WINDOWS: Tool = Tool {
+    run: window::run,
+    default_command: Some("list"),
+    commands: &["focus", "minimize", "maximize", "restore", "move", "close", "backdrop"],
+    portable: false,
+};
+
+const KNOWN_FOLDER: Tool = Tool {
+    run: known_folder::run,
+    default_command: None,
+    commands: &[],
+    portable: false,
+};
+
+const FONTS: Tool = Tool {
+    run: fonts::run,
+    default_command: Some("list"),
+    commands: &["preview"],
+    portable: false,
+};
+
+const HASH: Tool = Tool {
+    run: hash::run,
+    default_command: None,
+    commands: &[],
+    portable: false,
+};
+
+const DRIVES: Tool = Tool {
+    run: drives::run,
+    default_command: Some("list"),
+    commands: &["watch"],
+    portable: false,
+};
+
+const PROBE: Tool = Tool {
+    run: probe::run,
+    default_command: None,
+    commands: &[],
+    portable: false,
+};
+
+const STARTUP: Tool = Tool {
+    run: startup::run,
+    default_command: Some("list"),
+    commands: &["query", "add", "remove"],
+    portable: false,
+};
+
+const CAPTURE: Tool = Tool {
+    run: capture::run,
+    default_command: Some("monitor"),
+    commands: &["window", "region"],
+    portable: false,
+};
+
+const PICK_COLOR: Tool = Tool {
+    run: pick_color::run,
+    default_command: None,
+    commands: &[],
+    portable: false,
+};
+
+const TASKBAR: Tool = Tool {
+    run: taskbar::run,
+    default_command: Some("query"),
+    commands: &["watch"],
+    portable: false,
+};
+
+const VDESKTOP: Tool = Tool {
+    run: vdesktop::run,
+    default_command: Some("list"),
+    commands: &["window", "move", "switch"],
+    portable: false,
+};
+
+const DISPLAY_NAME: Tool = Tool {
+    run: display_name::run,
+    default_command: None,
+    commands: &[],
+    portable: false,
+};
+
+const PROCESS: Tool = Tool {
+    run: process::run,
+    default_command: Some("watch"),
+    commands: &[],
+    portable: false,
+};
+
+const TILE: Tool = Tool {
+    run: tile::run,
+    default_command: Some("export"),
+    commands: &[],
+    portable: false,
+};
+
+const DOCTOR: Tool = Tool {
+    run: doctor::run,
+    default_command: None,
+    commands: &[],
+    portable: false,
+};
+
+const CAPABILITIES: Tool = Tool {
+    run: capabilities::run,
+    default_command: None,
+    commands: &[],
+    portable: true,
+};
+
+const TOOLS: &[(&str, &Tool)] = &[
+    ("shortcut", &SHORTCUT),
+    ("icon", &ICON),
+    ("wallpaper", &WALLPAPER),
+    ("monitors", &MONITORS),
+    ("desktop", &DESKTOP),
+    ("fs", &FS),
+    ("launch", &LAUNCH),
+    ("shell", &SHELL),
+    ("apps", &APPS),
+    ("assoc", &ASSOC),
+    ("theme", &THEME),
+    ("hotkeys", &HOTKEYS),
+    ("notify", &NOTIFY),
+    ("tray", &TRAY),
+    ("clipboard", &CLIPBOARD),
+    ("drop-target", &DROP_TARGET),
+    ("fileinfo", &FILEINFO),
+    ("audio", &AUDIO),
+    ("media", &MEDIA),
+    ("power", &POWER),
+    ("idle", &IDLE),
+    ("windows", &WINDOWS),
+    ("known-folder", &KNOWN_FOLDER),
+    ("fonts", &FONTS),
+    ("hash", &HASH),
+    ("drives", &DRIVES),
+    ("probe", &PROBE),
+    ("startup", &STARTUP),
+    ("capture", &CAPTURE),
+    ("pick-color", &PICK_COLOR),
+    ("taskbar", &TASKBAR),
+    ("vdesktop", &VDESKTOP),
+    ("display-name", &DISPLAY_NAME),
+    ("tile", &TILE),
+    ("process", &PROCESS),
+    ("doctor", &DOCTOR),
+    ("capabilities", &CAPABILITIES),
+];
+
+fn main() -> ExitCode {
+    crash::install();
+    // Before any tool reads a coordinate or creates a window, since a window keeps
+    // the DPI awareness it was created with
+    #[cfg(windows)]
+    monitors::use_physical_pixels();
+    let mut args = env::args_os().skip(1).peekable();
+    // The options every tool shares, in any order before the tool's name
+    let mut log_filter = None;
+    let mut config = None;
+    let mut output_roots = Vec::new();
+    loop {
+        match args.peek().and_then(|arg| arg.to_str()) {
+            Some("--log") => {
+                args.next();
+                log_filter = args.next();
+            }
+            Some("--config") => {
+                args.next();
+                config = args.next();
+            }
+            Some("--allowed-output-root") => {
+                args.next();
+                output_roots.extend(args.next().map(PathBuf::from));
+            }
+            Some("--lang") => {
+                args.next();
+                if let Some(tag) = args.next() {
+                    altdesktop_core::messages::set_language(&tag.to_string_lossy());
+                }
+            }
+            _ => break,
+        }
+    }
+    // First, since the config can turn logging on
+    if let Some(path) = config
+        && let Err(message) = altdesktop_core::config::load(Path::new(&path))
+    {
+        eprintln!("{}", message);
+        return ExitCode::from(exit::USAGE);
+    }
+    if let Err(message) = altdesktop_core::sandbox::restrict(output_roots) {
+        eprintln!("{}", message);
+        return ExitCode::from(exit::USAGE);
+    }
+    altdesktop_core::log::init(log_filter.as_ref().and_then(|filter| filter.to_str()));
+    if args.peek().is_some_and(|arg| arg == "--serve") {
+        let rest: Vec<String> = args.skip(1).map(|arg| arg.to_string_lossy().into_owned()).collect();
+        return match rest.as_slice() {
+            [] => with_com(serve::serve),
+            [flag, name @ ..] if flag == "--pipe" && name.len() <= 1 => serve_pipe(name.first().map(String::as_str)),
+            _ => usage_error("Expected --serve or --serve --pipe [name]"),
+        };
+    }
+    if args.peek().is_some_and(|arg| arg == "--elevate") {
+        return elevate(args.skip(1).collect());
+    }
+    #[cfg(windows)]
+    if args.peek().is_some_and(|arg| arg == elevate::CHILD_FLAG) {
+        args.next();
+        let (Some(pipe), Some(working_dir)) = (args.next(), args.next()) else {
+            return usage_error("Expected a pipe and a working directory");
+        };
+        if let Err(error) = elevate::attach_to_parent(&pipe.to_string_lossy(), &working_dir.to_string_lossy()) {
+            // Nobody is reading, but the parent still gets the exit code
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+        return run_tool(args.collect());
+    }
+    run_tool(args.collect())
+}
+
+fn run_tool(args: Vec<OsString>) -> ExitCode {
+    let mut args = args.into_iter();
+    let Some(tool_name) = args.next() else {
+        return usage_error("Expected a tool and a command");
+    };
+
+    let tool_name = tool_name.to_string_lossy().into_owned();
+    let Some(&(_, tool)) = TOOLS.iter().find(|(name, _)| *name == tool_name) else {
+        return usage_error(&format!("Unknown tool: {}", tool_name));
+    };
+
+    // The tools parse their arguments after a program name, which their errors don't use
+    let mut tool_args = vec![OsString::from(format!("altdesktop-helper {}", tool_name))];
+    if let Some(default_command) = tool.default_command {
+        let Some(command) = args.next() else {
+            return usage_error(&format!("Expected a {} command", tool_name));
+        };
+        match command.to_str() {
+            Some(name) if name == default_command => {}
+            Some(name) if tool.commands.contains(&name) => tool_args.push(format!("--{}", name).into()),
+            _ => return usage_error(&format!("Unknown {} command: {}", tool_name, command.to_string_lossy())),
+        }
+    }
+    tool_args.extend(args);
+
+    // Whatever the tool logs, its failures included, is tagged with what it was asked
+    let _span = tracing::info_span!("tool", name = %tool_name, args = ?&tool_args[1..]).entered();
+    tracing::debug!("started");
+    let code = with_com(|| (tool.run)(tool_args));
+    tracing::debug!(?code, "finished");
+    code
+}
+
+#[cfg(windows)]
+fn serve_pipe(name: Option<&str>) -> ExitCode {
+    pipe::serve_pipe(name.unwrap_or(pipe::DEFAULT_PIPE_NAME))
+}
+
+#[cfg(not(windows))]
+fn serve_pipe(_name: Option<&str>) -> ExitCode {
+    eprintln!("--pipe is only supported on Windows");
+    ExitCode::from(exit::UNSUPPORTED)
+}
+
+#[cfg(windows)]
+fn elevate(args: Vec<OsString>) -> ExitCode {
+    elevate::run_elevated(args, run_tool)
+}
+
+#[cfg(not(windows))]
+fn elevate(_args: Vec<OsString>) -> ExitCode {
+    eprintln!("--elevate is only supported on Windows");
+    ExitCode::from(exit::UNSUPPORTED)
+}
+
+// Initialized once for whichever tool runs; the shell APIs both use need an STA
+fn with_com(run: impl FnOnce() -> ExitCode) -> ExitCode {
+    #[cfg(windows)]
+    let _com = ComGuard::apartment();
+    run()
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    ExitCode::from(exit::USAGE)
+}
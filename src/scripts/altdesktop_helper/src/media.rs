@@ -0,0 +1,328 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::{self, Read, Write};
+#[cfg(windows)]
+use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use std::sync::mpsc::{self, Sender};
+#[cfg(windows)]
+use std::thread;
+#[cfg(windows)]
+use std::time::Duration;
+
+#[cfg(windows)]
+use altdesktop_core::sandbox;
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use serde_json::json;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Foundation::{EventRegistrationToken, TypedEventHandler},
+    Media::Control::*,
+    Storage::Streams::DataReader,
+    Win32::Foundation::{E_FAIL, ERROR_NOT_FOUND},
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper media now-playing [--art <output.png>]
+  altdesktop-helper media watch [--art <output.png>]
+  altdesktop-helper media send play|pause|toggle|next|previous|stop
+
+now-playing prints what the media flyout shows, or null when nothing is playing:
+{\"app\",\"title\",\"artist\",\"album\",\"albumArtist\",\"status\",\"position\",\"duration\",
+\"art\"}, where app is the player's AppUserModelID, status is playing, paused,
+stopped, changing, opened or closed, position and duration are milliseconds, and
+art is the path the album art was saved to with --art, or null.
+watch prints {\"type\":\"ready\",\"media\"} with the same, then
+{\"type\":\"changed\",\"media\"} whenever the player, track or status changes, until
+stdin closes. The art file is rewritten for each new track.
+send passes a command to the current player and prints {\"ok\":true}.
+<output.png> may start with a known folder token such as {Desktop}.";
+
+// Players update the title, artist and art one at a time; they are read once the
+// burst is over
+#[cfg(windows)]
+const SETTLE_TIME: Duration = Duration::from_millis(150);
+#[cfg(windows)]
+const COMMANDS: [&str; 6] = ["play", "pause", "toggle", "next", "previous", "stop"];
+// TimeSpan ticks are 100ns
+#[cfg(windows)]
+const TICKS_PER_MILLISECOND: i64 = 10_000;
+
+#[cfg(windows)]
+#[derive(Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct NowPlaying {
+    app: String,
+    title: String,
+    artist: String,
+    album: String,
+    album_artist: String,
+    status: &'static str,
+    position: Option<u64>,
+    duration: Option<u64>,
+    art: Option<PathBuf>,
+}
+
+#[cfg(windows)]
+enum Signal {
+    Changed,
+    StdinClosed,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--send") => match &args[2..] {
+            [command] if COMMANDS.contains(&command.as_str()) => tool::finish(send(command).map(|()| tool::DONE)),
+            _ => tool::usage_error("Expected play, pause, toggle, next, previous or stop", USAGE),
+        },
+        Some("--watch") => match parse_art(&args[2..]) {
+            Ok(art) => match art.map(|art| art_path(art)).transpose() {
+                Ok(art) => watch_until_stdin_closes(art.as_deref()),
+                Err(error) => tool::finish::<()>(Err(error)),
+            },
+            Err(message) => tool::usage_error(&message, USAGE),
+        },
+        _ => match parse_art(&args[1..]) {
+            Ok(art) => tool::finish(art.map(|art| art_path(art)).transpose().and_then(|art| {
+                now_playing(&manager()?, art.as_deref(), true)
+            })),
+            Err(message) => tool::usage_error(&message, USAGE),
+        },
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("media")
+}
+
+#[cfg(windows)]
+fn parse_art(args: &[String]) -> std::result::Result<Option<&String>, String> {
+    match args {
+        [] => Ok(None),
+        [flag, path] if flag == "--art" => Ok(Some(path)),
+        [flag] if flag == "--art" => Err("--art requires a value".to_string()),
+        [flag, ..] if flag.starts_with("--") && flag != "--art" => Err(format!("Unknown option: {}", flag)),
+        _ => Err("Expected at most --art <output.png>".to_string()),
+    }
+}
+
+#[cfg(windows)]
+fn art_path(art: &str) -> Result<PathBuf> {
+    std::path::absolute(create_shortcut::known_folders::expand_known_folder(art)?).map_err(tool::io_error)
+}
+
+#[cfg(windows)]
+fn manager() -> Result<GlobalSystemMediaTransportControlsSessionManager> {
+    GlobalSystemMediaTransportControlsSessionManager::RequestAsync()?.get()
+}
+
+// The session the media flyout shows; `save_art` is false when only playback changed
+#[cfg(windows)]
+fn now_playing(
+    manager: &GlobalSystemMediaTransportControlsSessionManager,
+    art: Option<&Path>,
+    save_art: bool,
+) -> Result<Option<NowPlaying>> {
+    let Ok(session) = manager.GetCurrentSession() else {
+        return Ok(None);
+    };
+    let properties = session.TryGetMediaPropertiesAsync()?.get()?;
+    let status = session.GetPlaybackInfo().and_then(|info| info.PlaybackStatus()).map_or("closed", status_name);
+    let (position, duration) = match session.GetTimelineProperties() {
+        Ok(timeline) => {
+            let millis = |ticks: i64| u64::try_from(ticks / TICKS_PER_MILLISECOND).ok();
+            let start = timeline.StartTime().map_or(0, |time| time.Duration);
+            let end = timeline.EndTime().map_or(0, |time| time.Duration);
+            let position = timeline.Position().ok().and_then(|time| millis(time.Duration - start));
+            (position, Some(end - start).filter(|&length| length > 0).and_then(millis))
+        }
+        Err(_) => (None, None),
+    };
+
+    let art = match art {
+        Some(path) if save_art => save_thumbnail(&properties, path)?.then(|| path.to_path_buf()),
+        Some(path) => path.exists().then(|| path.to_path_buf()),
+        None => None,
+    };
+    Ok(Some(NowPlaying {
+        app: session.SourceAppUserModelId()?.to_string(),
+        title: properties.Title()?.to_string(),
+        artist: properties.Artist()?.to_string(),
+        album: properties.AlbumTitle()?.to_string(),
+        album_artist: properties.AlbumArtist()?.to_string(),
+        status,
+        position,
+        duration,
+        art,
+    }))
+}
+
+#[cfg(windows)]
+fn status_name(status: GlobalSystemMediaTransportControlsSessionPlaybackStatus) -> &'static str {
+    match status {
+        GlobalSystemMediaTransportControlsSessionPlaybackStatus::Playing => "playing",
+        GlobalSystemMediaTransportControlsSessionPlaybackStatus::Paused => "paused",
+        GlobalSystemMediaTransportControlsSessionPlaybackStatus::Stopped => "stopped",
+        GlobalSystemMediaTransportControlsSessionPlaybackStatus::Changing => "changing",
+        GlobalSystemMediaTransportControlsSessionPlaybackStatus::Opened => "opened",
+        _ => "closed",
+    }
+}
+
+// False, and any earlier art removed, when the track has none
+#[cfg(windows)]
+fn save_thumbnail(properties: &GlobalSystemMediaTransportControlsSessionMediaProperties, path: &Path) -> Result<bool> {
+    sandbox::check_output(path)?;
+    let Ok(thumbnail) = properties.Thumbnail() else {
+        let _ = std::fs::remove_file(path);
+        return Ok(false);
+    };
+    let stream = thumbnail.OpenReadAsync()?.get()?;
+    let length = stream.Size()? as u32;
+    let reader = DataReader::CreateDataReader(&stream.GetInputStreamAt(0)?)?;
+    reader.LoadAsync(length)?.get()?;
+    let mut encoded = vec![0; length as usize];
+    reader.ReadBytes(&mut encoded)?;
+
+    // Players hand over a PNG or a JPEG
+    let image = image::load_from_memory(&encoded).map_err(image_error)?;
+    image.save_with_format(path, image::ImageFormat::Png).map_err(image_error)?;
+    Ok(true)
+}
+
+#[cfg(windows)]
+fn image_error(error: image::ImageError) -> Error {
+    Error::new(E_FAIL, error.to_string().into())
+}
+
+#[cfg(windows)]
+fn send(command: &str) -> Result<()> {
+    let session = manager()?
+        .GetCurrentSession()
+        .map_err(|_| Error::new(ERROR_NOT_FOUND.to_hresult(), "Nothing is playing".into()))?;
+    let accepted = match command {
+        "play" => session.TryPlayAsync()?,
+        "pause" => session.TryPauseAsync()?,
+        "toggle" => session.TryTogglePlayPauseAsync()?,
+        "next" => session.TrySkipNextAsync()?,
+        "previous" => session.TrySkipPreviousAsync()?,
+        _ => session.TryStopAsync()?,
+    }
+    .get()?;
+    if !accepted {
+        let app = session.SourceAppUserModelId().map(|app| app.to_string()).unwrap_or_default();
+        return Err(Error::new(E_FAIL, format!("{} did not accept {}", app, command).into()));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+struct Subscription {
+    session: GlobalSystemMediaTransportControlsSession,
+    properties: EventRegistrationToken,
+    playback: EventRegistrationToken,
+}
+
+// Follows whichever session is current, since only it raises the events
+#[cfg(windows)]
+fn subscribe(manager: &GlobalSystemMediaTransportControlsSessionManager, signals: &Sender<Signal>) -> Option<Subscription> {
+    let session = manager.GetCurrentSession().ok()?;
+    let changed = signals.clone();
+    let properties = session
+        .MediaPropertiesChanged(&TypedEventHandler::new(move |_, _| {
+            let _ = changed.send(Signal::Changed);
+            Ok(())
+        }))
+        .ok()?;
+    let changed = signals.clone();
+    let playback = session
+        .PlaybackInfoChanged(&TypedEventHandler::new(move |_, _| {
+            let _ = changed.send(Signal::Changed);
+            Ok(())
+        }))
+        .ok()?;
+    Some(Subscription { session, properties, playback })
+}
+
+#[cfg(windows)]
+fn unsubscribe(subscription: Option<Subscription>) {
+    if let Some(subscription) = subscription {
+        let _ = subscription.session.RemoveMediaPropertiesChanged(subscription.properties);
+        let _ = subscription.session.RemovePlaybackInfoChanged(subscription.playback);
+    }
+}
+
+// What decides whether the art has to be saved again
+#[cfg(windows)]
+fn track(media: &Option<NowPlaying>) -> Option<(&str, &str, &str)> {
+    media.as_ref().map(|media| (media.app.as_str(), media.title.as_str(), media.artist.as_str()))
+}
+
+#[cfg(windows)]
+fn watch_until_stdin_closes(art: Option<&Path>) -> ExitCode {
+    let manager = match manager() {
+        Ok(manager) => manager,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    let (signals, received) = mpsc::channel();
+    let changed = signals.clone();
+    let registered = manager.CurrentSessionChanged(&TypedEventHandler::new(move |_, _| {
+        let _ = changed.send(Signal::Changed);
+        Ok(())
+    }));
+    if let Err(error) = registered {
+        return tool::finish::<()>(Err(error));
+    }
+    let mut subscription = subscribe(&manager, &signals);
+    let stdin_closed = signals.clone();
+    thread::spawn(move || {
+        let _ = io::stdin().read_to_end(&mut Vec::new());
+        let _ = stdin_closed.send(Signal::StdinClosed);
+    });
+
+    let mut current = now_playing(&manager, art, true).ok().flatten();
+    let mut stdout = io::stdout().lock();
+    if writeln!(stdout, "{}", json!({ "type": "ready", "media": current })).and_then(|_| stdout.flush()).is_err() {
+        return ExitCode::SUCCESS;
+    }
+    while let Ok(Signal::Changed) = received.recv() {
+        thread::sleep(SETTLE_TIME);
+        let mut closed = false;
+        while let Ok(signal) = received.try_recv() {
+            closed |= matches!(signal, Signal::StdinClosed);
+        }
+        if closed {
+            break;
+        }
+
+        // Cheap enough to redo on every change, and catches a new current session
+        unsubscribe(subscription.take());
+        subscription = subscribe(&manager, &signals);
+        let mut latest = now_playing(&manager, art, false).ok().flatten();
+        if art.is_some() && track(&latest) != track(&current) {
+            latest = now_playing(&manager, art, true).ok().flatten();
+        }
+        if latest == current {
+            continue;
+        }
+        if writeln!(stdout, "{}", json!({ "type": "changed", "media": latest })).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+        current = latest;
+    }
+    unsubscribe(subscription);
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,175 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{BOOL, LPARAM, POINT, RECT},
+    Win32::Graphics::Gdi::*,
+    Win32::UI::HiDpi::{
+        GetDpiForMonitor, SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+        MDT_EFFECTIVE_DPI,
+    },
+    Win32::UI::WindowsAndMessaging::{GetCursorPos, MONITORINFOF_PRIMARY},
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper monitors list
+  altdesktop-helper monitors cursor
+
+list prints every display as JSON: its device name, bounds and work area in physical
+pixels on the virtual screen, effective DPI, scale factor, refresh rate in Hz and
+whether it is the primary display.
+cursor prints {\"x\",\"y\",\"monitor\"}: where the mouse cursor is, in the same
+pixels, and the display it is on, as list prints it.";
+
+#[cfg(windows)]
+const BASE_DPI: u32 = 96;
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Monitor {
+    pub index: usize,
+    /// GDI's name for the display, e.g. \\.\DISPLAY1.
+    pub device_name: String,
+    pub bounds: Rect,
+    /// The bounds minus the taskbar and any docked app bars.
+    pub work_area: Rect,
+    pub dpi: u32,
+    /// 1.5 at 150%.
+    pub scale_factor: f32,
+    pub refresh_rate: Option<u32>,
+    pub primary: bool,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[cfg(windows)]
+impl From<RECT> for Rect {
+    fn from(rect: RECT) -> Self {
+        Rect { x: rect.left, y: rect.top, width: rect.right - rect.left, height: rect.bottom - rect.top }
+    }
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+pub struct Cursor {
+    pub x: i32,
+    pub y: i32,
+    pub monitor: Monitor,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--cursor") if args.len() == 2 => tool::finish(cursor()),
+        Some("--cursor") => tool::usage_error("cursor takes no arguments", USAGE),
+        None => tool::finish(list_monitors()),
+        Some(_) => tool::usage_error("list takes no arguments", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("monitors")
+}
+
+/// Every display, in EnumDisplayMonitors order.
+#[cfg(windows)]
+pub fn list_monitors() -> Result<Vec<Monitor>> {
+    monitor_handles()?
+        .into_iter()
+        .enumerate()
+        .map(|(index, handle)| describe(index, handle))
+        .collect()
+}
+
+/// Where the mouse cursor is, and the display it is on: the one the user is
+/// looking at, as far as the helper can tell.
+#[cfg(windows)]
+pub fn cursor() -> Result<Cursor> {
+    let mut point = POINT::default();
+    unsafe { GetCursorPos(&mut point)? };
+    // Nearest, since the cursor can sit on the edge of a gap between displays
+    let handle = unsafe { MonitorFromPoint(point, MONITOR_DEFAULTTONEAREST) };
+    let index = monitor_handles()?.iter().position(|&known| known == handle).unwrap_or(0);
+    Ok(Cursor { x: point.x, y: point.y, monitor: describe(index, handle)? })
+}
+
+/// Every display's handle, in EnumDisplayMonitors order, so a handle's position is
+/// the index list_monitors gives it.
+#[cfg(windows)]
+pub fn monitor_handles() -> Result<Vec<HMONITOR>> {
+    let mut handles: Vec<HMONITOR> = Vec::new();
+    let enumerated = unsafe {
+        EnumDisplayMonitors(HDC::default(), None, Some(collect_monitor), LPARAM(&mut handles as *mut _ as isize))
+    };
+    if !enumerated.as_bool() {
+        return Err(Error::from_win32());
+    }
+    Ok(handles)
+}
+
+/// Opts the process out of DPI virtualization, under which Windows scales geometry
+/// to 96 DPI and reports every monitor at 96. The embedded manifest has done this
+/// already in an MSVC build; main calls it first for any other build.
+#[cfg(windows)]
+pub fn use_physical_pixels() {
+    unsafe {
+        let _ = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn collect_monitor(monitor: HMONITOR, _: HDC, _: *mut RECT, handles: LPARAM) -> BOOL {
+    unsafe { (*(handles.0 as *mut Vec<HMONITOR>)).push(monitor) };
+    true.into()
+}
+
+#[cfg(windows)]
+fn describe(index: usize, handle: HMONITOR) -> Result<Monitor> {
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+    if !unsafe { GetMonitorInfoW(handle, &mut info.monitorInfo) }.as_bool() {
+        return Err(Error::from_win32());
+    }
+    let length = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+    let device_name = String::from_utf16_lossy(&info.szDevice[..length]);
+
+    let (mut dpi, mut dpi_y) = (0, 0);
+    if unsafe { GetDpiForMonitor(handle, MDT_EFFECTIVE_DPI, &mut dpi, &mut dpi_y) }.is_err() || dpi == 0 {
+        dpi = BASE_DPI;
+    }
+
+    let mut mode = DEVMODEW { dmSize: std::mem::size_of::<DEVMODEW>() as u16, ..Default::default() };
+    let has_mode = unsafe { EnumDisplaySettingsW(&HSTRING::from(device_name.as_str()), ENUM_CURRENT_SETTINGS, &mut mode) };
+    // 0 and 1 both mean the hardware's default rate
+    let refresh_rate = Some(mode.dmDisplayFrequency).filter(|&rate| has_mode.as_bool() && rate > 1);
+
+    Ok(Monitor {
+        index,
+        device_name,
+        bounds: info.monitorInfo.rcMonitor.into(),
+        work_area: info.monitorInfo.rcWork.into(),
+        dpi,
+        scale_factor: dpi as f32 / BASE_DPI as f32,
+        refresh_rate,
+        primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+    })
+}
@@ -0,0 +1,232 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::{self, Read};
+#[cfg(windows)]
+use std::sync::mpsc::{self, Sender};
+#[cfg(windows)]
+use std::thread;
+
+#[cfg(windows)]
+use altdesktop_core::error::ErrorReport;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Data::Xml::Dom::XmlDocument,
+    Foundation::TypedEventHandler,
+    Win32::Foundation::E_FAIL,
+    UI::Notifications::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper notify --title <title> [--body <text>] [--image <path>]
+                           [--action <id>=<label>]... [--app-id <AppUserModelID>]
+
+Shows a Windows toast notification as Alt-Desktop (--app-id com.gillsb.alt-desktop
+by default), which needs a Start Menu shortcut carrying that app ID. --image is shown
+in place of the app's logo; each --action adds a button, at most five. Prints
+{\"type\":\"shown\"}, then stays until the toast is dealt with and prints one of
+{\"type\":\"activated\",\"action\"}, with the button's id or null for the toast itself,
+{\"type\":\"dismissed\",\"reason\"}, where reason is userCanceled, timedOut or
+applicationHidden, or {\"type\":\"error\",...} with the error fields of a failed
+shortcut --json run. Closing stdin first withdraws the toast.";
+
+#[cfg(windows)]
+const DEFAULT_APP_ID: &str = "com.gillsb.alt-desktop";
+// The most buttons a toast has room for
+#[cfg(windows)]
+const MAX_ACTIONS: usize = 5;
+
+#[cfg(windows)]
+struct Options {
+    title: String,
+    body: Option<String>,
+    image: Option<String>,
+    actions: Vec<(String, String)>,
+    app_id: String,
+}
+
+// How the wait for the toast ended
+#[cfg(windows)]
+enum Outcome {
+    Activated(Option<String>),
+    Dismissed(ToastDismissalReason),
+    Failed(Error),
+    StdinClosed,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let options = match tool::string_args(args).and_then(|args| parse_args(&args)) {
+        Ok(options) => options,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let (sender, outcomes) = mpsc::channel();
+    let (notifier, toast) = match show(&options, sender.clone()) {
+        Ok(shown) => shown,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    emit(json!({ "type": "shown" }));
+
+    thread::spawn(move || {
+        let _ = io::stdin().read_to_end(&mut Vec::new());
+        let _ = sender.send(Outcome::StdinClosed);
+    });
+    match outcomes.recv() {
+        Ok(Outcome::Activated(action)) => emit(json!({ "type": "activated", "action": action })),
+        Ok(Outcome::Dismissed(reason)) => emit(json!({ "type": "dismissed", "reason": dismissal_reason(reason) })),
+        Ok(Outcome::Failed(error)) => {
+            let report = ErrorReport::from_error(&error);
+            let code = report.exit_code();
+            let mut line = serde_json::to_value(report).unwrap();
+            line["type"] = Value::from("error");
+            emit(line);
+            return ExitCode::from(code);
+        }
+        Ok(Outcome::StdinClosed) | Err(_) => {
+            let _ = notifier.Hide(&toast);
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("notify")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<Options, String> {
+    let mut title = None;
+    let mut body = None;
+    let mut image = None;
+    let mut actions = Vec::new();
+    let mut app_id = DEFAULT_APP_ID.to_string();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--title" => title = Some(value()?),
+            "--body" => body = Some(value()?),
+            "--image" => image = Some(value()?),
+            "--app-id" => app_id = value()?,
+            "--action" => {
+                let action = value()?;
+                match action.split_once('=') {
+                    Some((id, label)) if !id.is_empty() && !label.is_empty() => {
+                        actions.push((id.to_string(), label.to_string()))
+                    }
+                    _ => return Err(format!("Invalid --action: {} (expected <id>=<label>)", action)),
+                }
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    if actions.len() > MAX_ACTIONS {
+        return Err(format!("A toast has at most {} actions", MAX_ACTIONS));
+    }
+    let title = title.ok_or_else(|| "Expected --title".to_string())?;
+    Ok(Options { title, body, image, actions, app_id })
+}
+
+#[cfg(windows)]
+fn show(options: &Options, outcomes: Sender<Outcome>) -> Result<(ToastNotifier, ToastNotification)> {
+    let document = XmlDocument::new()?;
+    document.LoadXml(&HSTRING::from(toast_xml(options)?))?;
+    let toast = ToastNotification::CreateToastNotification(&document)?;
+
+    // The handlers run on a thread pool thread and may race; only the first is read
+    let activated = outcomes.clone();
+    toast.Activated(&TypedEventHandler::new(move |_: &Option<ToastNotification>, args: &Option<IInspectable>| {
+        let action = args
+            .as_ref()
+            .and_then(|args| args.cast::<ToastActivatedEventArgs>().ok())
+            .and_then(|args| args.Arguments().ok())
+            .map(|arguments| arguments.to_string())
+            .filter(|arguments| !arguments.is_empty());
+        let _ = activated.send(Outcome::Activated(action));
+        Ok(())
+    }))?;
+    let dismissed = outcomes.clone();
+    toast.Dismissed(&TypedEventHandler::new(
+        move |_: &Option<ToastNotification>, args: &Option<ToastDismissedEventArgs>| {
+            let reason = args.as_ref().and_then(|args| args.Reason().ok()).unwrap_or(ToastDismissalReason::UserCanceled);
+            let _ = dismissed.send(Outcome::Dismissed(reason));
+            Ok(())
+        },
+    ))?;
+    toast.Failed(&TypedEventHandler::new(move |_: &Option<ToastNotification>, args: &Option<ToastFailedEventArgs>| {
+        let code = args.as_ref().and_then(|args| args.ErrorCode().ok()).unwrap_or(E_FAIL);
+        let _ = outcomes.send(Outcome::Failed(code.into()));
+        Ok(())
+    }))?;
+
+    let notifier = ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(&options.app_id))?;
+    notifier.Show(&toast)?;
+    Ok((notifier, toast))
+}
+
+// The ToastGeneric template; the toast itself activates with empty arguments, each
+// button with its id
+#[cfg(windows)]
+fn toast_xml(options: &Options) -> Result<String> {
+    let mut binding = format!("<text>{}</text>", escape(&options.title));
+    if let Some(body) = &options.body {
+        binding.push_str(&format!("<text>{}</text>", escape(body)));
+    }
+    if let Some(image) = &options.image {
+        let path = create_shortcut::known_folders::expand_known_folder(image)?;
+        let path = std::path::absolute(&path).map_err(tool::io_error)?;
+        std::fs::metadata(&path).map_err(tool::io_error)?;
+        let uri = format!("file:///{}", path.to_string_lossy().replace('\\', "/"));
+        binding.push_str(&format!("<image placement=\"appLogoOverride\" src=\"{}\"/>", escape(&uri)));
+    }
+
+    let mut xml = format!("<toast launch=\"\"><visual><binding template=\"ToastGeneric\">{}</binding></visual>", binding);
+    if !options.actions.is_empty() {
+        xml.push_str("<actions>");
+        for (id, label) in &options.actions {
+            xml.push_str(&format!(
+                "<action content=\"{}\" arguments=\"{}\" activationType=\"foreground\"/>",
+                escape(label),
+                escape(id)
+            ));
+        }
+        xml.push_str("</actions>");
+    }
+    xml.push_str("</toast>");
+    Ok(xml)
+}
+
+#[cfg(windows)]
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(windows)]
+fn dismissal_reason(reason: ToastDismissalReason) -> &'static str {
+    match reason {
+        ToastDismissalReason::ApplicationHidden => "applicationHidden",
+        ToastDismissalReason::TimedOut => "timedOut",
+        _ => "userCanceled",
+    }
+}
+
+// Closing stdin is what stops this tool, so output lost to a closed stdout is ignored
+#[cfg(windows)]
+fn emit(line: Value) {
+    let _ = altdesktop_core::json::emit(line);
+}
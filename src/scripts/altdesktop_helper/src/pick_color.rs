@@ -0,0 +1,212 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::{self, Read};
+#[cfg(windows)]
+use std::sync::atomic::{AtomicI32, AtomicU8, Ordering};
+#[cfg(windows)]
+use std::thread;
+
+#[cfg(windows)]
+use altdesktop_core::json::emit;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM},
+    Win32::Graphics::Gdi::{GetDC, GetPixel, ReleaseDC, CLR_INVALID},
+    Win32::System::Threading::GetCurrentThreadId,
+    Win32::UI::Input::KeyboardAndMouse::VK_ESCAPE,
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper pick-color [--interval <ms>]
+
+An eyedropper for the whole screen. Prints {\"type\":\"color\",\"x\",\"y\",\"color\"} with
+the #rrggbb color of the pixel under the cursor whenever it changes, checking every
+--interval ms (default 30), until the user picks one. A left click picks the pixel
+under the cursor and prints {\"type\":\"picked\",\"x\",\"y\",\"color\"}; Escape, a right
+click or closing stdin prints {\"type\":\"cancelled\"}. Either way the click doesn't
+reach the window under the cursor. x and y are physical pixels on the virtual
+screen; color is null over what the helper can't read, such as an elevated app's
+window seen from an unelevated helper.";
+
+#[cfg(windows)]
+const DEFAULT_INTERVAL: u32 = 30;
+#[cfg(windows)]
+const SAMPLE_TIMER: usize = 1;
+// Posted to the picking thread when stdin closes
+#[cfg(windows)]
+const WM_STDIN_CLOSED: u32 = WM_APP;
+
+// What the hooks have seen, for the message loop that installed them. Each button
+// press is swallowed with its release, so the release ends the pick
+#[cfg(windows)]
+const PICKING: u8 = 0;
+#[cfg(windows)]
+const PICKED: u8 = 1;
+#[cfg(windows)]
+const CANCELLED: u8 = 2;
+#[cfg(windows)]
+static STATE: AtomicU8 = AtomicU8::new(PICKING);
+#[cfg(windows)]
+static PICKED_X: AtomicI32 = AtomicI32::new(0);
+#[cfg(windows)]
+static PICKED_Y: AtomicI32 = AtomicI32::new(0);
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match parse_interval(&args[1..]) {
+        Ok(interval) => tool::finish(pick(interval)),
+        Err(message) => tool::usage_error(&message, USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("pick-color")
+}
+
+#[cfg(windows)]
+fn parse_interval(args: &[String]) -> std::result::Result<u32, String> {
+    let mut interval = DEFAULT_INTERVAL;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--interval" => {
+                let value = value()?;
+                interval = match value.parse() {
+                    Ok(ms @ 10..=1000) => ms,
+                    _ => return Err(format!("Invalid interval: {} (expected 10 to 1000 ms)", value)),
+                };
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    Ok(interval)
+}
+
+// Prints the color lines as they come and returns the last line, so failing to hook
+// the mouse still answers with the usual error JSON
+#[cfg(windows)]
+fn pick(interval: u32) -> Result<Value> {
+    let thread_id = unsafe { GetCurrentThreadId() };
+    unsafe {
+        let mouse = SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_hook), None, 0)?;
+        let keyboard = match SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_hook), None, 0) {
+            Ok(keyboard) => keyboard,
+            Err(error) => {
+                let _ = UnhookWindowsHookEx(mouse);
+                return Err(error);
+            }
+        };
+        thread::spawn(move || {
+            let _ = io::stdin().read_to_end(&mut Vec::new());
+            let _ = PostThreadMessageW(thread_id, WM_STDIN_CLOSED, WPARAM(0), LPARAM(0));
+        });
+        SetTimer(HWND(0), SAMPLE_TIMER, interval, None);
+
+        let mut last = None;
+        let mut message = MSG::default();
+        while GetMessageW(&mut message, HWND(0), 0, 0).as_bool() {
+            if message.message == WM_STDIN_CLOSED {
+                STATE.store(CANCELLED, Ordering::SeqCst);
+                break;
+            }
+            if message.message != WM_TIMER {
+                DispatchMessageW(&message);
+                continue;
+            }
+            let mut cursor = POINT::default();
+            if GetCursorPos(&mut cursor).is_err() {
+                continue;
+            }
+            let color = color_at(cursor);
+            if last != Some((cursor.x, cursor.y, color.clone())) {
+                if !emit(json!({ "type": "color", "x": cursor.x, "y": cursor.y, "color": color })) {
+                    STATE.store(CANCELLED, Ordering::SeqCst);
+                    break;
+                }
+                last = Some((cursor.x, cursor.y, color));
+            }
+        }
+        let _ = UnhookWindowsHookEx(mouse);
+        let _ = UnhookWindowsHookEx(keyboard);
+    }
+
+    if STATE.load(Ordering::SeqCst) == PICKED {
+        let point = POINT { x: PICKED_X.load(Ordering::SeqCst), y: PICKED_Y.load(Ordering::SeqCst) };
+        return Ok(json!({ "type": "picked", "x": point.x, "y": point.y, "color": color_at(point) }));
+    }
+    Ok(json!({ "type": "cancelled" }))
+}
+
+#[cfg(windows)]
+fn color_at(point: POINT) -> Option<String> {
+    unsafe {
+        let screen = GetDC(None);
+        if screen.is_invalid() {
+            return None;
+        }
+        let pixel = GetPixel(screen, point.x, point.y);
+        ReleaseDC(None, screen);
+        // COLORREF is 0x00bbggrr
+        (pixel.0 != CLR_INVALID)
+            .then(|| format!("#{:02x}{:02x}{:02x}", pixel.0 & 0xFF, (pixel.0 >> 8) & 0xFF, (pixel.0 >> 16) & 0xFF))
+    }
+}
+
+#[cfg(windows)]
+extern "system" fn mouse_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION as i32 {
+        let state = STATE.load(Ordering::SeqCst);
+        match wparam.0 as u32 {
+            WM_LBUTTONDOWN if state == PICKING => {
+                let info = unsafe { &*(lparam.0 as *const MSLLHOOKSTRUCT) };
+                PICKED_X.store(info.pt.x, Ordering::SeqCst);
+                PICKED_Y.store(info.pt.y, Ordering::SeqCst);
+                STATE.store(PICKED, Ordering::SeqCst);
+                return LRESULT(1);
+            }
+            WM_RBUTTONDOWN if state == PICKING => {
+                STATE.store(CANCELLED, Ordering::SeqCst);
+                return LRESULT(1);
+            }
+            WM_LBUTTONUP if state == PICKED => {
+                unsafe { PostQuitMessage(0) };
+                return LRESULT(1);
+            }
+            WM_RBUTTONUP if state == CANCELLED => {
+                unsafe { PostQuitMessage(0) };
+                return LRESULT(1);
+            }
+            _ => {}
+        }
+    }
+    unsafe { CallNextHookEx(HHOOK::default(), code, wparam, lparam) }
+}
+
+#[cfg(windows)]
+extern "system" fn keyboard_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION as i32 && wparam.0 as u32 == WM_KEYDOWN {
+        let info = unsafe { &*(lparam.0 as *const KBDLLHOOKSTRUCT) };
+        if info.vkCode == VK_ESCAPE.0 as u32 && STATE.load(Ordering::SeqCst) == PICKING {
+            STATE.store(CANCELLED, Ordering::SeqCst);
+            unsafe { PostQuitMessage(0) };
+            return LRESULT(1);
+        }
+    }
+    unsafe { CallNextHookEx(HHOOK::default(), code, wparam, lparam) }
+}
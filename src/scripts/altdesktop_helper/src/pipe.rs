@@ -0,0 +1,216 @@
+use std::io;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use altdesktop_core::cancel::CancelToken;
+use altdesktop_core::com::ComGuard;
+use windows::{
+    core::HSTRING,
+    Win32::Foundation::{CloseHandle, ERROR_BROKEN_PIPE, ERROR_IO_PENDING, ERROR_PIPE_CONNECTED, HANDLE},
+    Win32::Storage::FileSystem::{
+        ReadFile, WriteFile, FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED, PIPE_ACCESS_DUPLEX,
+    },
+    Win32::System::IO::{GetOverlappedResult, OVERLAPPED},
+    Win32::System::Pipes::*,
+    Win32::System::Threading::CreateEventW,
+};
+
+use crate::schedule::Scheduler;
+use crate::serve::{InFlight, Incoming};
+
+pub const DEFAULT_PIPE_NAME: &str = "altdesktop-helper";
+
+const BUFFER_SIZE: u32 = 64 * 1024;
+// Far larger than any request; a bigger length means the client isn't speaking the protocol
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Serves JSON-RPC on `\\.\pipe\<name>` to any number of local clients. Each message,
+/// in both directions, is a 4-byte little-endian length followed by that many bytes
+/// of UTF-8 JSON. A client may send several requests without waiting: they run
+/// concurrently on a shared pool of worker threads, taken in the order the
+/// [`Scheduler`] picks from every client's, and responses come back as they finish,
+/// matched to requests by id; a cancel names one by its id. Runs until the process
+/// is killed.
+pub fn serve_pipe(name: &str) -> ExitCode {
+    let path = if name.starts_with(r"\\") { name.to_string() } else { format!(r"\\.\pipe\{}", name) };
+    match accept_loop(&path) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Failed to serve on {}: {}", path, e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn accept_loop(path: &str) -> io::Result<()> {
+    let workers = thread::available_parallelism().map_or(4, |count| count.get());
+    let scheduler = Arc::new(Scheduler::<(Arc<Connection>, String, CancelToken)>::new(workers));
+    for _ in 0..workers {
+        let scheduler = scheduler.clone();
+        thread::spawn(move || {
+            // Each worker is its own STA, as the shell APIs behind every method need
+            let _com = ComGuard::apartment();
+            while let Some((priority, (connection, message, token))) = scheduler.pop() {
+                let response = connection.in_flight.handle(&message, &token);
+                scheduler.finished(priority);
+                if let Some(response) = response {
+                    // The client may have gone; its other responses fail the same way
+                    let _ = connection.write_message(&response.to_string());
+                }
+            }
+        });
+    }
+
+    let mut first = true;
+    loop {
+        // Only the first instance claims the name, so a second helper can't
+        // quietly share it with this one
+        let connection = Arc::new(Connection::create(path, first)?);
+        first = false;
+        // One client failing to connect, say by closing straight away, leaves the
+        // pipe to the rest; its instance is dropped and the next one waits instead
+        if let Err(error) = connection.connect() {
+            tracing::warn!(path, "A client failed to connect to the pipe: {}", error);
+            continue;
+        }
+
+        let requests = scheduler.clone();
+        thread::spawn(move || {
+            while let Ok(Some(message)) = connection.read_message() {
+                // Cancels are answered here, so they needn't wait for a free worker
+                match connection.in_flight.receive(message) {
+                    Incoming::Answered(response) => {
+                        if let Some(response) = response {
+                            let _ = connection.write_message(&response.to_string());
+                        }
+                    }
+                    Incoming::Queued(message, token, priority) => {
+                        requests.push(priority, (connection.clone(), message, token));
+                    }
+                }
+            }
+        });
+    }
+}
+
+// One client's end of the pipe. Opened for overlapped I/O, since synchronous I/O on
+// a handle is serialized and a pending read would hold back every response.
+struct Connection {
+    handle: HANDLE,
+    writing: Mutex<()>,
+    // Request ids are the client's own, so each connection cancels only its own
+    in_flight: InFlight,
+}
+
+impl Connection {
+    fn create(path: &str, first: bool) -> io::Result<Self> {
+        let mut open_mode = PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED;
+        if first {
+            open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+        }
+        let handle = unsafe {
+            CreateNamedPipeW(
+                &HSTRING::from(path),
+                open_mode,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                None,
+            )
+        };
+        if handle.is_invalid() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Connection { handle, writing: Mutex::new(()), in_flight: InFlight::default() })
+    }
+
+    // Waits for a client to open this instance
+    fn connect(&self) -> io::Result<()> {
+        match self.overlapped(|overlapped| unsafe { ConnectNamedPipe(self.handle, Some(overlapped)) }) {
+            // The client got in between creating the instance and waiting on it
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_CONNECTED.0 as i32) => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    // None once the client disconnects
+    fn read_message(&self) -> io::Result<Option<String>> {
+        let mut header = [0u8; 4];
+        if !self.read_exact(&mut header)? {
+            return Ok(None);
+        }
+        let length = u32::from_le_bytes(header) as usize;
+        if length > MAX_MESSAGE_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Message of {} bytes is too large", length)));
+        }
+        let mut body = vec![0u8; length];
+        if !self.read_exact(&mut body)? {
+            return Ok(None);
+        }
+        String::from_utf8(body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // False if the client disconnected before the buffer was filled
+    fn read_exact(&self, mut buffer: &mut [u8]) -> io::Result<bool> {
+        while !buffer.is_empty() {
+            let read = match self.overlapped(|overlapped| unsafe {
+                ReadFile(self.handle, Some(&mut *buffer), None, Some(overlapped))
+            }) {
+                Err(e) if e.raw_os_error() == Some(ERROR_BROKEN_PIPE.0 as i32) => return Ok(false),
+                result => result?,
+            };
+            if read == 0 {
+                return Ok(false);
+            }
+            buffer = &mut buffer[read as usize..];
+        }
+        Ok(true)
+    }
+
+    fn write_message(&self, body: &str) -> io::Result<()> {
+        let mut message = Vec::with_capacity(4 + body.len());
+        message.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        message.extend_from_slice(body.as_bytes());
+
+        // Workers finish in any order; one message must not land inside another
+        let _writing = self.writing.lock().unwrap();
+        let mut remaining = message.as_slice();
+        while !remaining.is_empty() {
+            let written = self.overlapped(|overlapped| unsafe {
+                WriteFile(self.handle, Some(remaining), None, Some(overlapped))
+            })?;
+            remaining = &remaining[written as usize..];
+        }
+        Ok(())
+    }
+
+    // Starts an operation on the handle and blocks until it completes, returning
+    // the bytes transferred
+    fn overlapped(&self, start: impl FnOnce(*mut OVERLAPPED) -> windows::core::Result<()>) -> io::Result<u32> {
+        let event = unsafe { CreateEventW(None, true, false, None) }?;
+        let mut overlapped = OVERLAPPED { hEvent: event, ..Default::default() };
+        let result = match start(&mut overlapped) {
+            Err(e) if e.code() != ERROR_IO_PENDING.to_hresult() => Err(e),
+            _ => {
+                let mut transferred = 0;
+                unsafe { GetOverlappedResult(self.handle, &overlapped, &mut transferred, true) }.map(|_| transferred)
+            }
+        };
+        unsafe {
+            let _ = CloseHandle(event);
+        }
+        Ok(result?)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DisconnectNamedPipe(self.handle);
+            let _ = CloseHandle(self.handle);
+        }
+    }
+}
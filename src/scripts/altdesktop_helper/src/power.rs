@@ -0,0 +1,219 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::{self, Read};
+#[cfg(windows)]
+use std::thread;
+
+#[cfg(windows)]
+use altdesktop_core::json::emit;
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{LocalFree, HANDLE, HLOCAL, HWND, LPARAM, LRESULT, WPARAM},
+    Win32::System::Power::*,
+    Win32::System::Registry::HKEY,
+    Win32::System::SystemServices::{GUID_ACTIVE_POWERSCHEME, GUID_BATTERY_PERCENTAGE_REMAINING, GUID_POWER_SAVING_STATUS},
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper power status
+  altdesktop-helper power watch
+
+status prints {\"battery\",\"pluggedIn\",\"charging\",\"percent\",\"remaining\",\"batterySaver\",
+\"plan\"}: whether there is a battery, whether the PC runs on mains power, whether the
+battery is charging, its charge from 0 to 100, Windows' estimate of the seconds left
+on battery, whether battery saver is on, and the active power plan as {\"id\",\"name\"}.
+Each of pluggedIn, percent, remaining and plan is null when Windows doesn't know it.
+watch prints {\"type\":\"ready\",\"power\"} with what status prints, then
+{\"type\":\"changed\",\"changes\":[...],\"power\"} whenever any of it changes, listing the
+fields that did, and {\"type\":\"suspend\"} and {\"type\":\"resume\"} around sleep, until
+stdin closes.";
+
+#[cfg(windows)]
+const CLASS_NAME: PCWSTR = w!("AltDesktopHelperPower");
+// Unplugging sends a status change for the power source, then one for the estimate
+// once Windows has it; they are read once the burst is over
+#[cfg(windows)]
+const SETTLE_TIMER: usize = 1;
+#[cfg(windows)]
+const SETTLE_MILLISECONDS: u32 = 100;
+// SYSTEM_POWER_STATUS's markers for a value Windows doesn't know
+#[cfg(windows)]
+const UNKNOWN_BYTE: u8 = 255;
+#[cfg(windows)]
+const UNKNOWN_LIFETIME: u32 = u32::MAX;
+#[cfg(windows)]
+const BATTERY_CHARGING: u8 = 8;
+#[cfg(windows)]
+const NO_SYSTEM_BATTERY: u8 = 128;
+#[cfg(windows)]
+const BATTERY_SAVER_ON: u8 = 1;
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PowerStatus {
+    battery: bool,
+    plugged_in: Option<bool>,
+    charging: bool,
+    percent: Option<u8>,
+    remaining: Option<u32>,
+    battery_saver: bool,
+    plan: Option<Plan>,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct Plan {
+    id: String,
+    name: String,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--watch") if args.len() == 2 => watch_until_stdin_closes(),
+        Some("--watch") => tool::usage_error("watch takes no arguments", USAGE),
+        None => tool::finish(query_status()),
+        Some(_) => tool::usage_error("status takes no arguments", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("power")
+}
+
+#[cfg(windows)]
+fn query_status() -> Result<PowerStatus> {
+    let mut status = SYSTEM_POWER_STATUS::default();
+    unsafe { GetSystemPowerStatus(&mut status)? };
+    let battery = status.BatteryFlag != UNKNOWN_BYTE && status.BatteryFlag & NO_SYSTEM_BATTERY == 0;
+    Ok(PowerStatus {
+        battery,
+        plugged_in: match status.ACLineStatus {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        },
+        charging: battery && status.BatteryFlag & BATTERY_CHARGING != 0,
+        percent: Some(status.BatteryLifePercent).filter(|&percent| battery && percent <= 100),
+        remaining: Some(status.BatteryLifeTime).filter(|&seconds| battery && seconds != UNKNOWN_LIFETIME),
+        battery_saver: status.SystemStatusFlag == BATTERY_SAVER_ON,
+        plan: active_plan(),
+    })
+}
+
+#[cfg(windows)]
+fn active_plan() -> Option<Plan> {
+    unsafe {
+        let mut scheme = std::ptr::null_mut();
+        PowerGetActiveScheme(HKEY::default(), &mut scheme).ok()?;
+        let id = *scheme;
+        let _ = LocalFree(HLOCAL(scheme as _));
+
+        // The name's size in bytes, terminator included
+        let mut size = 0u32;
+        PowerReadFriendlyName(HKEY::default(), Some(&id), None, None, None, &mut size).ok()?;
+        let mut name = vec![0u16; size as usize / 2];
+        PowerReadFriendlyName(HKEY::default(), Some(&id), None, None, Some(name.as_mut_ptr() as _), &mut size).ok()?;
+        let length = name.iter().position(|&unit| unit == 0).unwrap_or(name.len());
+        Some(Plan { id: format!("{:?}", id).to_lowercase(), name: String::from_utf16_lossy(&name[..length]) })
+    }
+}
+
+#[cfg(windows)]
+fn watch_until_stdin_closes() -> ExitCode {
+    // The window belongs to the thread that pumps its messages
+    thread::spawn(watch);
+    let _ = io::stdin().read_to_end(&mut Vec::new());
+    ExitCode::SUCCESS
+}
+
+#[cfg(windows)]
+fn watch() {
+    let window = match tool::hidden_window(CLASS_NAME, Some(window_proc)) {
+        Ok(window) => window,
+        Err(error) => {
+            // Nothing would ever be reported, so don't leave the caller waiting
+            tool::exit_with(error);
+        }
+    };
+    // Status changes cover the power source and charge; these cover the rest
+    for setting in [GUID_ACTIVE_POWERSCHEME, GUID_POWER_SAVING_STATUS, GUID_BATTERY_PERCENTAGE_REMAINING] {
+        let _ = unsafe { RegisterPowerSettingNotification(HANDLE(window.0), &setting, DEVICE_NOTIFY_WINDOW_HANDLE.0) };
+    }
+
+    let mut power = status_value();
+    if !emit(json!({ "type": "ready", "power": power })) {
+        return;
+    }
+    let mut message = MSG::default();
+    while unsafe { GetMessageW(&mut message, HWND(0), 0, 0) }.as_bool() {
+        if message.message != WM_TIMER || message.wParam.0 != SETTLE_TIMER {
+            unsafe { DispatchMessageW(&message) };
+            continue;
+        }
+        unsafe {
+            let _ = KillTimer(window, SETTLE_TIMER);
+        }
+        let current = status_value();
+        let changes: Vec<&String> = match (&current, &power) {
+            (Value::Object(current), Value::Object(previous)) => {
+                current.iter().filter(|(field, value)| previous.get(*field) != Some(*value)).map(|(field, _)| field).collect()
+            }
+            _ => Vec::new(),
+        };
+        // Registering reports each setting's current value straight away
+        if changes.is_empty() {
+            continue;
+        }
+        if !emit(json!({ "type": "changed", "changes": changes, "power": current })) {
+            return;
+        }
+        power = current;
+    }
+}
+
+#[cfg(windows)]
+fn status_value() -> Value {
+    match query_status() {
+        Ok(status) => serde_json::to_value(status).unwrap(),
+        Err(_) => Value::Null,
+    }
+}
+
+// WM_POWERBROADCAST is sent rather than posted; changes restart the settle timer,
+// whose expiry the loop handles
+#[cfg(windows)]
+extern "system" fn window_proc(window: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if message != WM_POWERBROADCAST {
+        return unsafe { DefWindowProcW(window, message, wparam, lparam) };
+    }
+    match wparam.0 as u32 {
+        PBT_APMSUSPEND => {
+            emit(json!({ "type": "suspend" }));
+        }
+        PBT_APMRESUMEAUTOMATIC => {
+            emit(json!({ "type": "resume" }));
+        }
+        PBT_APMPOWERSTATUSCHANGE | PBT_POWERSETTINGCHANGE => unsafe {
+            SetTimer(window, SETTLE_TIMER, SETTLE_MILLISECONDS, None);
+        },
+        _ => {}
+    }
+    LRESULT(1)
+}
@@ -0,0 +1,178 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::path::{Component, Path, Prefix};
+#[cfg(windows)]
+use std::sync::mpsc;
+#[cfg(windows)]
+use std::thread;
+#[cfg(windows)]
+use std::time::{Duration, Instant};
+
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{core::HSTRING, Win32::Storage::FileSystem::GetDriveTypeW};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper probe <path>... [--timeout <ms>]
+
+Checks whether each path can be reached without waiting out Windows' own network
+timeouts, which can pass 30 seconds for a NAS that is switched off. Prints
+[{\"path\",\"network\",\"status\",\"reachable\",\"exists\",\"elapsed\"}] once every path
+has answered or --timeout ms have passed (default 3000, or the config's). status is ok, missing (the
+share answered but the path isn't there), accessDenied, offline (the server or share
+can't be reached), timeout or error, offline and error with a message; reachable is
+whether the volume or server answered at all, exists whether the path is there, and
+elapsed is how many ms the answer took, null on a timeout. network is whether the
+path is a UNC path or on a mapped network drive. Paths are probed at the same time,
+so the whole run takes at most about --timeout. Paths may start with a known folder
+token.";
+
+#[cfg(windows)]
+const DEFAULT_TIMEOUT: u64 = 3000;
+// GetDriveType's DRIVE_REMOTE, which the windows crate keeps in another feature
+#[cfg(windows)]
+const DRIVE_REMOTE: u32 = 4;
+// The errors an unreachable server or share fails with, rather than a missing path
+#[cfg(windows)]
+const OFFLINE_ERRORS: &[i32] = &[
+    53,   // ERROR_BAD_NETPATH
+    64,   // ERROR_NETNAME_DELETED
+    67,   // ERROR_BAD_NET_NAME
+    121,  // ERROR_SEM_TIMEOUT
+    1222, // ERROR_NO_NET_OR_BAD_PATH
+    1231, // ERROR_NETWORK_UNREACHABLE
+    1232, // ERROR_HOST_UNREACHABLE
+    2250, // ERROR_NOT_CONNECTED, a mapped drive that isn't connected
+];
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Probe {
+    path: String,
+    network: bool,
+    status: &'static str,
+    reachable: bool,
+    exists: bool,
+    elapsed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match parse_args(&args) {
+        Ok((paths, timeout)) => tool::finish(Ok(probe_all(paths, timeout))),
+        Err(message) => tool::usage_error(&message, USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("probe")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<(Vec<String>, Duration), String> {
+    let mut timeout = altdesktop_core::config::get().timeouts.probe.unwrap_or(DEFAULT_TIMEOUT);
+    let mut paths = Vec::new();
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--timeout" => {
+                let value = value()?;
+                timeout = match value.parse() {
+                    Ok(ms @ 1..) => ms,
+                    _ => return Err(format!("Invalid timeout: {} (expected milliseconds)", value)),
+                };
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.is_empty() {
+        return Err("Expected at least one path".to_string());
+    }
+    Ok((paths, Duration::from_millis(timeout)))
+}
+
+// Each path is read on a thread of its own, which is abandoned if it doesn't answer
+// in time; the process exits right after printing, taking any stuck thread with it
+#[cfg(windows)]
+fn probe_all(paths: Vec<String>, timeout: Duration) -> Vec<Probe> {
+    let start = Instant::now();
+    let (sender, receiver) = mpsc::channel();
+    let mut probes: Vec<Probe> = paths
+        .into_iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let expanded = create_shortcut::known_folders::expand_known_folder(&path).unwrap_or_else(|_| path.clone());
+            let sender = sender.clone();
+            let network = is_network_path(Path::new(&expanded));
+            thread::spawn(move || {
+                let _ = sender.send((index, std::fs::metadata(&expanded), start.elapsed()));
+            });
+            Probe {
+                path,
+                network,
+                status: "timeout",
+                reachable: false,
+                exists: false,
+                elapsed: None,
+                message: None,
+            }
+        })
+        .collect();
+    drop(sender);
+
+    let mut pending = probes.len();
+    while pending > 0 {
+        let Some(left) = timeout.checked_sub(start.elapsed()) else {
+            break;
+        };
+        let Ok((index, result, elapsed)) = receiver.recv_timeout(left) else {
+            break;
+        };
+        let probe = &mut probes[index];
+        probe.elapsed = Some(elapsed.as_millis() as u64);
+        (probe.status, probe.message) = match result {
+            Ok(_) => ("ok", None),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => ("missing", None),
+            Err(error) if error.kind() == std::io::ErrorKind::PermissionDenied => ("accessDenied", None),
+            Err(error) if error.raw_os_error().is_some_and(|code| OFFLINE_ERRORS.contains(&code)) => {
+                ("offline", Some(error.to_string()))
+            }
+            Err(error) => ("error", Some(error.to_string())),
+        };
+        probe.reachable = matches!(probe.status, "ok" | "missing" | "accessDenied");
+        probe.exists = probe.status == "ok";
+        pending -= 1;
+    }
+    probes
+}
+
+#[cfg(windows)]
+fn is_network_path(path: &Path) -> bool {
+    match path.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::UNC(..) | Prefix::VerbatimUNC(..) => true,
+            Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
+                let root = HSTRING::from(format!("{}:\\", letter as char));
+                unsafe { GetDriveTypeW(&root) == DRIVE_REMOTE }
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,234 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::collections::HashSet;
+#[cfg(windows)]
+use std::io::{self, Read};
+#[cfg(windows)]
+use std::thread;
+
+#[cfg(windows)]
+use altdesktop_core::json::emit;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    Win32::Foundation::{CloseHandle, FILETIME, HANDLE, WAIT_OBJECT_0},
+    Win32::System::Diagnostics::ToolHelp::*,
+    Win32::System::SystemServices::MAXIMUM_WAIT_OBJECTS,
+    Win32::System::Threading::*,
+};
+
+#[cfg(windows)]
+use crate::window::process_path;
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper process watch <pid>... [--children]
+
+Watches processes, such as the ones launch started, and prints
+{\"type\":\"ready\",\"processes\":[{\"pid\",\"path\"}]} for those still running, then
+{\"type\":\"exited\",\"pid\",\"root\",\"exitCode\"} as each exits and
+{\"type\":\"ended\",\"root\"} once nothing is left running for a <pid> given, straight
+away for one that had already exited. root is the <pid> a process is watched for.
+--children also watches the processes they start, and those processes' own, printing
+{\"type\":\"spawned\",\"pid\",\"parent\",\"root\",\"path\"} for each, so a launcher that
+starts a game and exits doesn't end its root while the game runs; children are
+looked for twice a second, so one that exits sooner may be missed. path is null for
+an elevated process. Stops when every root has ended or stdin closes.";
+
+// How often --children looks for new processes; exits are seen straight away
+#[cfg(windows)]
+const POLL_MILLISECONDS: u32 = 500;
+
+#[cfg(windows)]
+struct Process {
+    pid: u32,
+    root: u32,
+    // None once it has exited
+    handle: Option<HANDLE>,
+    created: u64,
+    exited: Option<u64>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let mut children = false;
+    let mut pids = Vec::new();
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "--children" => children = true,
+            flag if flag.starts_with("--") => return tool::usage_error(&format!("Unknown option: {}", flag), USAGE),
+            _ => match arg.parse::<u32>() {
+                Ok(pid) if !pids.contains(&pid) => pids.push(pid),
+                Ok(_) => {}
+                Err(_) => return tool::usage_error(&format!("Invalid pid: {}", arg), USAGE),
+            },
+        }
+    }
+    if pids.is_empty() {
+        return tool::usage_error("Expected at least one pid", USAGE);
+    }
+
+    thread::spawn(move || {
+        watch(pids, children);
+        // Everything has ended, or nobody is reading
+        std::process::exit(0);
+    });
+    let _ = io::stdin().read_to_end(&mut Vec::new());
+    ExitCode::SUCCESS
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("process")
+}
+
+#[cfg(windows)]
+fn watch(roots: Vec<u32>, children: bool) {
+    // Opened before anything is printed, so an exit in between is still reported
+    let mut processes: Vec<Process> = roots
+        .iter()
+        .filter_map(|&pid| {
+            let (handle, created) = open(pid)?;
+            Some(Process { pid, root: pid, handle: Some(handle), created, exited: None })
+        })
+        .collect();
+    let running: Vec<Value> =
+        processes.iter().map(|process| json!({ "pid": process.pid, "path": process_path(process.pid) })).collect();
+    if !emit(json!({ "type": "ready", "processes": running })) {
+        return;
+    }
+
+    let mut ended = HashSet::new();
+    loop {
+        // Before exits are collected, so a launcher's root doesn't end when the
+        // launcher exits right after starting its child
+        if children && !adopt_children(&mut processes, &ended) {
+            return;
+        }
+        for process in processes.iter_mut() {
+            let Some(handle) = process.handle else {
+                continue;
+            };
+            if unsafe { WaitForSingleObject(handle, 0) } != WAIT_OBJECT_0 {
+                continue;
+            }
+            let mut exit_code = 0u32;
+            let exit_code = unsafe { GetExitCodeProcess(handle, &mut exit_code) }.ok().map(|()| exit_code);
+            process.exited = Some(times(handle).map_or(u64::MAX, |(_, exited)| exited));
+            process.handle = None;
+            unsafe {
+                let _ = CloseHandle(handle);
+            }
+            if !emit(json!({ "type": "exited", "pid": process.pid, "root": process.root, "exitCode": exit_code })) {
+                return;
+            }
+        }
+        for &root in &roots {
+            if ended.contains(&root) || processes.iter().any(|process| process.root == root && process.handle.is_some()) {
+                continue;
+            }
+            ended.insert(root);
+            if !emit(json!({ "type": "ended", "root": root })) {
+                return;
+            }
+        }
+
+        let handles: Vec<HANDLE> = processes.iter().filter_map(|process| process.handle).collect();
+        if handles.is_empty() {
+            return;
+        }
+        // Only so many handles can be waited on at once; past that, the rest are
+        // looked at on the next poll
+        let timeout = if children || handles.len() > MAXIMUM_WAIT_OBJECTS as usize { POLL_MILLISECONDS } else { INFINITE };
+        unsafe { WaitForMultipleObjects(&handles[..handles.len().min(MAXIMUM_WAIT_OBJECTS as usize)], false, timeout) };
+    }
+}
+
+// Starts watching every new process whose parent is watched, until none are left to
+// find, so a child and its own children found in one snapshot are all taken. False
+// once stdout is gone.
+#[cfg(windows)]
+fn adopt_children(processes: &mut Vec<Process>, ended: &HashSet<u32>) -> bool {
+    let Ok(snapshot) = (unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) }) else {
+        return true;
+    };
+    let mut entries = Vec::new();
+    let mut entry = PROCESSENTRY32W { dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32, ..Default::default() };
+    let mut found = unsafe { Process32FirstW(snapshot, &mut entry) }.is_ok();
+    while found {
+        entries.push((entry.th32ProcessID, entry.th32ParentProcessID));
+        found = unsafe { Process32NextW(snapshot, &mut entry) }.is_ok();
+    }
+    unsafe {
+        let _ = CloseHandle(snapshot);
+    }
+
+    loop {
+        let mut adopted = false;
+        for &(pid, parent) in &entries {
+            let watched = |process: &Process| process.pid == pid && process.handle.is_some();
+            if processes.iter().any(watched) || !processes.iter().any(|process| process.pid == parent) {
+                continue;
+            }
+            let Some((handle, created)) = open(pid) else {
+                continue;
+            };
+            // A parent's pid may have been reused since it exited, so the child must
+            // have started while that parent was running
+            let root = processes
+                .iter()
+                .find(|process| {
+                    process.pid == parent
+                        && !ended.contains(&process.root)
+                        && process.created <= created
+                        && process.exited.is_none_or(|exited| created <= exited)
+                })
+                .map(|process| process.root);
+            let Some(root) = root else {
+                unsafe {
+                    let _ = CloseHandle(handle);
+                }
+                continue;
+            };
+            processes.push(Process { pid, root, handle: Some(handle), created, exited: None });
+            adopted = true;
+            if !emit(json!({ "type": "spawned", "pid": pid, "parent": parent, "root": root, "path": process_path(pid) })) {
+                return false;
+            }
+        }
+        if !adopted {
+            return true;
+        }
+    }
+}
+
+// Only waiting and the start time are needed, which any process allows
+#[cfg(windows)]
+fn open(pid: u32) -> Option<(HANDLE, u64)> {
+    unsafe {
+        let handle = OpenProcess(PROCESS_SYNCHRONIZE | PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        match times(handle) {
+            Some((created, _)) => Some((handle, created)),
+            None => {
+                let _ = CloseHandle(handle);
+                None
+            }
+        }
+    }
+}
+
+// When the process started and exited, as FILETIMEs
+#[cfg(windows)]
+fn times(handle: HANDLE) -> Option<(u64, u64)> {
+    let (mut created, mut exited, mut kernel, mut user) = Default::default();
+    unsafe { GetProcessTimes(handle, &mut created, &mut exited, &mut kernel, &mut user) }.ok()?;
+    let ticks = |time: FILETIME| (time.dwHighDateTime as u64) << 32 | time.dwLowDateTime as u64;
+    Some((ticks(created), ticks(exited)))
+}
@@ -0,0 +1,105 @@
+use windows::{core::*, Win32::Foundation::ERROR_FILE_NOT_FOUND, Win32::System::Registry::*};
+
+// The longest value and key names the registry allows, and their NUL
+const VALUE_NAME_LENGTH: usize = 16384;
+const KEY_NAME_LENGTH: usize = 256;
+
+// Value reads for callers that treat a missing key, a missing value and one of the
+// wrong type alike, as None
+
+/// REG_EXPAND_SZ values come back expanded; empty strings come back as None.
+pub fn read_string(key: HKEY, subkey: &HSTRING, value: PCWSTR) -> Option<String> {
+    let mut size = 0u32;
+    unsafe { RegGetValueW(key, subkey, value, RRF_RT_REG_SZ, None, None, Some(&mut size)) }.ok()?;
+    let mut buffer = vec![0u16; size as usize / 2 + 1];
+    size = (buffer.len() * 2) as u32;
+    unsafe {
+        RegGetValueW(key, subkey, value, RRF_RT_REG_SZ, None, Some(buffer.as_mut_ptr().cast()), Some(&mut size))
+    }
+    .ok()?;
+    let length = buffer.iter().position(|&unit| unit == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..length])).filter(|value| !value.trim().is_empty())
+}
+
+pub fn read_dword(key: HKEY, subkey: &HSTRING, value: PCWSTR) -> Option<u32> {
+    let mut data = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    unsafe {
+        RegGetValueW(key, subkey, value, RRF_RT_REG_DWORD, None, Some(&mut data as *mut u32 as _), Some(&mut size))
+    }
+    .ok()?;
+    Some(data)
+}
+
+pub fn read_binary(key: HKEY, subkey: &HSTRING, value: PCWSTR) -> Option<Vec<u8>> {
+    let mut size = 0u32;
+    unsafe { RegGetValueW(key, subkey, value, RRF_RT_REG_BINARY, None, None, Some(&mut size)) }.ok()?;
+    let mut data = vec![0u8; size as usize];
+    unsafe { RegGetValueW(key, subkey, value, RRF_RT_REG_BINARY, None, Some(data.as_mut_ptr().cast()), Some(&mut size)) }
+        .ok()?;
+    data.truncate(size as usize);
+    Some(data)
+}
+
+pub fn value_names(key: HKEY, subkey: &HSTRING) -> Vec<String> {
+    let mut opened = HKEY::default();
+    if unsafe { RegOpenKeyExW(key, subkey, 0, KEY_READ, &mut opened) }.is_err() {
+        return Vec::new();
+    }
+    let mut names = Vec::new();
+    for index in 0.. {
+        let mut name = vec![0u16; VALUE_NAME_LENGTH];
+        let mut length = VALUE_NAME_LENGTH as u32;
+        let listed = unsafe { RegEnumValueW(opened, index, PWSTR(name.as_mut_ptr()), &mut length, None, None, None, None) };
+        if listed.is_err() {
+            break;
+        }
+        names.push(String::from_utf16_lossy(&name[..length as usize]));
+    }
+    unsafe {
+        let _ = RegCloseKey(opened);
+    }
+    names
+}
+
+pub fn subkey_names(key: HKEY, subkey: &HSTRING) -> Vec<String> {
+    let mut opened = HKEY::default();
+    if unsafe { RegOpenKeyExW(key, subkey, 0, KEY_READ, &mut opened) }.is_err() {
+        return Vec::new();
+    }
+    let mut names = Vec::new();
+    for index in 0.. {
+        let mut name = [0u16; KEY_NAME_LENGTH];
+        let mut length = KEY_NAME_LENGTH as u32;
+        let listed =
+            unsafe { RegEnumKeyExW(opened, index, PWSTR(name.as_mut_ptr()), &mut length, None, PWSTR::null(), None, None) };
+        if listed.is_err() {
+            break;
+        }
+        names.push(String::from_utf16_lossy(&name[..length as usize]));
+    }
+    unsafe {
+        let _ = RegCloseKey(opened);
+    }
+    names
+}
+
+// Writes, which unlike the reads report why they failed: usually access denied, for
+// HKEY_LOCAL_MACHINE without elevation
+
+/// Creates the key when it doesn't exist yet.
+pub fn write_string(key: HKEY, subkey: &HSTRING, value: &HSTRING, data: &str) -> Result<()> {
+    let data: Vec<u16> = data.encode_utf16().chain([0]).collect();
+    unsafe {
+        RegSetKeyValueW(key, subkey, value, REG_SZ.0, Some(data.as_ptr().cast()), (data.len() * 2) as u32)
+    }
+}
+
+/// Returns whether there was a value to delete.
+pub fn delete_value(key: HKEY, subkey: &HSTRING, value: &HSTRING) -> Result<bool> {
+    match unsafe { RegDeleteKeyValueW(key, subkey, value) } {
+        Ok(()) => Ok(true),
+        Err(error) if error.code() == ERROR_FILE_NOT_FOUND.to_hresult() => Ok(false),
+        Err(error) => Err(error),
+    }
+}
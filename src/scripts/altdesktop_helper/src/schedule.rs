@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+
+use serde_json::Value;
+
+/// How soon a served request runs, from the `priority` member of the request:
+/// `visible` for what's on screen now, such as the icons of the tiles in view,
+/// `normal` when there is none, and `background` for prefetching and bulk refreshes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Priority {
+    Visible,
+    Normal,
+    Background,
+}
+
+impl Priority {
+    // Highest first, the order the queues are served in
+    const ALL: [Priority; 3] = [Priority::Visible, Priority::Normal, Priority::Background];
+
+    pub fn from_request(priority: &Value) -> Result<Self, String> {
+        match priority {
+            Value::Null => Ok(Priority::Normal),
+            Value::String(name) => match name.as_str() {
+                "visible" => Ok(Priority::Visible),
+                "normal" => Ok(Priority::Normal),
+                "background" => Ok(Priority::Background),
+                _ => Err(format!("Unknown priority: {}", name)),
+            },
+            _ => Err("Expected priority to be visible, normal or background".to_string()),
+        }
+    }
+}
+
+/// The requests waiting for one of `workers` threads. A free worker takes the oldest
+/// request of the highest priority whose class is under its limit: every worker may
+/// run visible requests, all but one normal ones, and only half background ones,
+/// so a bulk refresh never holds up what the user is looking at for longer than
+/// one request that's already running.
+pub struct Scheduler<T> {
+    state: Mutex<State<T>>,
+    ready: Condvar,
+}
+
+struct State<T> {
+    queues: [VecDeque<T>; 3],
+    running: [usize; 3],
+    limits: [usize; 3],
+    closed: bool,
+}
+
+impl<T> Scheduler<T> {
+    pub fn new(workers: usize) -> Self {
+        let limits = [workers, workers.saturating_sub(1).max(1), (workers / 2).max(1)];
+        let state = State { queues: Default::default(), running: [0; 3], limits, closed: false };
+        Scheduler { state: Mutex::new(state), ready: Condvar::new() }
+    }
+
+    pub fn push(&self, priority: Priority, item: T) {
+        self.state.lock().unwrap().queues[priority as usize].push_back(item);
+        self.ready.notify_all();
+    }
+
+    /// No more requests are coming: [`pop`](Self::pop) returns None once the queues run dry.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.ready.notify_all();
+    }
+
+    /// Waits for the next request to run, which counts against its class's limit
+    /// until it's [`finished`](Self::finished).
+    pub fn pop(&self) -> Option<(Priority, T)> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let next = Priority::ALL.into_iter().find(|&priority| {
+                let class = priority as usize;
+                !state.queues[class].is_empty() && state.running[class] < state.limits[class]
+            });
+            if let Some(priority) = next {
+                state.running[priority as usize] += 1;
+                return state.queues[priority as usize].pop_front().map(|item| (priority, item));
+            }
+            if state.closed && state.queues.iter().all(VecDeque::is_empty) {
+                return None;
+            }
+            state = self.ready.wait(state).unwrap();
+        }
+    }
+
+    pub fn finished(&self, priority: Priority) {
+        self.state.lock().unwrap().running[priority as usize] -= 1;
+        self.ready.notify_all();
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use altdesktop_core::cancel::{self, CancelToken};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::capabilities;
+use crate::schedule::{Priority, Scheduler};
+#[cfg(windows)]
+use crate::tile;
+
+// Codes defined by JSON-RPC 2.0
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+// First of the codes JSON-RPC leaves to servers; `data` holds the tool's own error
+const TOOL_ERROR: i32 = -32000;
+// What the Language Server Protocol answers a cancelled request with
+const REQUEST_CANCELLED: i32 = -32800;
+
+/// Bumped whenever a method's params or result, or a tool's JSON, change in a way an
+/// app written for the previous version can't read.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Every method [`serve`] answers, as `capabilities` lists them.
+pub const METHODS: &[&str] =
+    &["icon.extract", "memory.release", "shortcut.create", "shortcut.resolve", "tile.export", "capabilities", "cancel"];
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    // Absent for notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Answers newline-delimited JSON-RPC 2.0 requests on stdin, one response line
+/// per request on stdout, until stdin closes. Requests are handled on the calling
+/// thread, so COM only has to be initialized once for the whole session, in order of
+/// their `priority` and then of arrival; stdin is read on another, so a cancel
+/// reaches the request it names while earlier ones still run, and a visible request
+/// gets ahead of the background ones that were queued before it.
+///
+/// Methods take the same objects as the tools' batch modes:
+///   icon.extract      an `icon batch` job, plus an optional cacheDir and sharedMemory
+///   memory.release    {"name"} of a section icon.extract shared; returns {"released"}
+///   shortcut.create   a `shortcut batch` spec; returns {"path"}
+///   shortcut.resolve  {"path", "save"}; returns the `shortcut resolve` result
+///   tile.export       a `tile export` definition; returns what it prints
+///   capabilities      {}; returns what `capabilities` prints
+///   cancel            {"id"} of an earlier request; returns {"cancelled"}
+pub fn serve() -> ExitCode {
+    let in_flight = Arc::new(InFlight::default());
+    let scheduler = Arc::new(Scheduler::new(1));
+    let (reader, requests) = (in_flight.clone(), scheduler.clone());
+    // Not joined: a failed response ends the session without waiting on stdin
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match reader.receive(line) {
+                Incoming::Answered(response) => {
+                    if !response.is_none_or(|response| respond(&response)) {
+                        break;
+                    }
+                }
+                Incoming::Queued(line, token, priority) => requests.push(priority, (line, token)),
+            }
+        }
+        requests.close();
+    });
+    while let Some((priority, (line, token))) = scheduler.pop() {
+        let response = in_flight.handle(&line, &token);
+        scheduler.finished(priority);
+        if response.is_some_and(|response| !respond(&response)) {
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+// Flushed per response so the caller never waits on a buffered answer; the lock
+// keeps a cancel's answer from landing inside another
+fn respond(response: &Value) -> bool {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", response).and_then(|_| stdout.flush()).is_ok()
+}
+
+/// What to do with a message as soon as it has been read.
+pub enum Incoming {
+    /// A cancel, answered at once, or None for a cancel sent as a notification.
+    Answered(Option<Value>),
+    /// Anything else, to [`InFlight::handle`] in its turn.
+    Queued(String, CancelToken, Priority),
+}
+
+/// One client's requests that have been read but not answered yet, by id, which is
+/// what a cancel names them by.
+#[derive(Default)]
+pub struct InFlight {
+    tokens: Mutex<HashMap<String, CancelToken>>,
+}
+
+impl InFlight {
+    /// Answers a cancel or a request with a bad priority, or registers any other
+    /// message so a later cancel can reach it.
+    pub fn receive(&self, line: String) -> Incoming {
+        let message: Value = serde_json::from_str(&line).unwrap_or_default();
+        let token = CancelToken::new();
+        if message["method"] != "cancel" {
+            let priority = match Priority::from_request(&message["priority"]) {
+                Ok(priority) => priority,
+                Err(error) => {
+                    let id = message.get("id").cloned();
+                    return Incoming::Answered(id.map(|id| error_response(id, INVALID_REQUEST, &error, None)));
+                }
+            };
+            if let Some(id) = message.get("id") {
+                self.tokens.lock().unwrap().insert(id.to_string(), token.clone());
+            }
+            return Incoming::Queued(line, token, priority);
+        }
+        let Some(id) = message.get("id") else {
+            self.cancel(&message["params"]);
+            return Incoming::Answered(None);
+        };
+        Incoming::Answered(Some(match message["params"].get("id") {
+            Some(_) if message["jsonrpc"] == "2.0" => {
+                json!({ "jsonrpc": "2.0", "id": id, "result": { "cancelled": self.cancel(&message["params"]) } })
+            }
+            _ => error_response(id.clone(), INVALID_PARAMS, "Expected params {\"id\"} of a request", None),
+        }))
+    }
+
+    // False when the request has been answered already, or never arrived
+    fn cancel(&self, params: &Value) -> bool {
+        let Some(id) = params.get("id") else {
+            return false;
+        };
+        let token = self.tokens.lock().unwrap().get(&id.to_string()).cloned();
+        token.inspect(CancelToken::cancel).is_some()
+    }
+
+    /// Answers a queued message, unless it was cancelled while it waited. Tools check
+    /// `token` at their safe points, and a request they gave up on is answered with
+    /// REQUEST_CANCELLED rather than its error.
+    pub fn handle(&self, line: &str, token: &CancelToken) -> Option<Value> {
+        let response = if token.is_cancelled() {
+            let id = serde_json::from_str::<Value>(line).ok().and_then(|message| message.get("id").cloned());
+            id.map(|id| error_response(id, REQUEST_CANCELLED, "The request was cancelled", None))
+        } else {
+            cancel::scope(token, || handle(line))
+        };
+        if let Some(id) = response.as_ref().and_then(|response| response.get("id")) {
+            self.tokens.lock().unwrap().remove(&id.to_string());
+        }
+        let mut response = response?;
+        if token.is_cancelled() && response["error"]["code"] == TOOL_ERROR {
+            response["error"]["code"] = Value::from(REQUEST_CANCELLED);
+        }
+        Some(response)
+    }
+}
+
+// Answers one JSON-RPC message, or returns None for a notification
+fn handle(line: &str) -> Option<Value> {
+    let message: Value = match serde_json::from_str(line) {
+        Ok(message) => message,
+        Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &format!("Parse error: {}", e), None)),
+    };
+    // A request that can't be read still gets a response, with whatever id it had
+    let id = message.get("id").cloned().unwrap_or(Value::Null);
+    let request = match serde_json::from_value::<Request>(message) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(_) => return Some(error_response(id, INVALID_REQUEST, "Expected \"jsonrpc\": \"2.0\"", None)),
+        Err(e) => return Some(error_response(id, INVALID_REQUEST, &format!("Invalid request: {}", e), None)),
+    };
+
+    let _span = tracing::info_span!("request", method = %request.method, params = %request.params).entered();
+    let result = if !request.params.is_object() {
+        Err((INVALID_PARAMS, "Expected params to be an object".to_string(), None))
+    } else {
+        match dispatch(&request.method, request.params) {
+            Some(Ok(result)) => Ok(result),
+            Some(Err(report)) => {
+                let message = report["message"].as_str().unwrap_or("Request failed").to_string();
+                Err((TOOL_ERROR, message, Some(report)))
+            }
+            None => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", request.method), None)),
+        }
+    };
+
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message, data)) => error_response(id, code, &message, data),
+    })
+}
+
+fn dispatch(method: &str, params: Value) -> Option<Result<Value, Value>> {
+    match method {
+        "icon.extract" => Some(icon_extractor::extract_json(params)),
+        "memory.release" => Some(icon_extractor::release_json(params)),
+        "capabilities" => Some(Ok(json!(capabilities::capabilities()))),
+        #[cfg(windows)]
+        "shortcut.create" => Some(create_shortcut::create_json(params)),
+        #[cfg(windows)]
+        "shortcut.resolve" => Some(create_shortcut::resolve_json(params)),
+        #[cfg(windows)]
+        "tile.export" => Some(tile::export_json(params)),
+        #[cfg(not(windows))]
+        "shortcut.create" | "shortcut.resolve" | "tile.export" => Some(Err(json!(altdesktop_core::error::ErrorReport::unsupported(
+            &format!("{} is only supported on Windows", method)
+        )))),
+        _ => None,
+    }
+}
+
+fn error_response(id: Value, code: i32, message: &str, data: Option<Value>) -> Value {
+    let mut error = json!({ "code": code, "message": message });
+    if let Some(data) = data {
+        error["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
@@ -0,0 +1,290 @@
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Foundation::{E_INVALIDARG, LPARAM, WPARAM},
+    Win32::UI::Shell::*,
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+use super::ui::{pump_messages, wait_for_windows};
+use super::USAGE;
+use crate::tool;
+
+// The range of command IDs handed to the handlers; entries are reported and chosen
+// by their offset from ID_FIRST, which is what InvokeCommand takes
+const ID_FIRST: u32 = 1;
+const ID_LAST: u32 = 0x7FFF;
+// Not in windows 0.52
+const CMIC_MASK_UNICODE: u32 = 0x4000;
+const CMIC_MASK_NOASYNC: u32 = 0x100;
+const VERB_LENGTH: usize = 256;
+// Some commands open their window a moment after InvokeCommand returns
+const INVOKE_GRACE_MS: u32 = 500;
+
+#[derive(Serialize)]
+struct MenuItem {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verb: Option<String>,
+    disabled: bool,
+    checked: bool,
+    default: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    items: Option<Vec<MenuItem>>,
+}
+
+enum Choice {
+    Id(u32),
+    Verb(String),
+}
+
+struct Options {
+    path: String,
+    extended: bool,
+    choice: Option<Choice>,
+}
+
+// The handlers behind the menu live as long as the IContextMenu, so the two are
+// kept, and the menu destroyed, together
+struct ContextMenu {
+    menu: IContextMenu,
+    hmenu: HMENU,
+}
+
+impl Drop for ContextMenu {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DestroyMenu(self.hmenu);
+        }
+    }
+}
+
+pub fn run_menu(args: &[String]) -> ExitCode {
+    let options = match parse_args(args, false) {
+        Ok(options) => options,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let listed = build_menu(&options.path, options.extended)
+        .map(|(_, items)| serde_json::json!({ "path": options.path, "items": items }));
+    tool::finish(listed)
+}
+
+pub fn run_invoke(args: &[String]) -> ExitCode {
+    let options = match parse_args(args, true) {
+        Ok(options) => options,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let Some(choice) = &options.choice else {
+        return tool::usage_error("Expected --id or --verb", USAGE);
+    };
+    match invoke(&options.path, options.extended, choice) {
+        Ok(()) => {}
+        Err(error) => return tool::finish::<()>(Err(error)),
+    }
+    let code = tool::finish(Ok(tool::DONE));
+    let _ = io::stdout().flush();
+
+    // Dialogs such as Open with and Properties run in this process
+    pump_messages(INVOKE_GRACE_MS);
+    wait_for_windows();
+    code
+}
+
+fn parse_args(args: &[String], invoking: bool) -> std::result::Result<Options, String> {
+    let mut extended = false;
+    let mut choice = None;
+    let mut positional = Vec::new();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--menu" | "--invoke" => {}
+            "--extended" => extended = true,
+            "--id" if invoking => {
+                let value = value()?;
+                choice = Some(Choice::Id(value.parse::<u32>().map_err(|_| format!("Invalid --id: {}", value))?));
+            }
+            "--verb" if invoking => {
+                let verb = value()?;
+                // Handlers compare the ANSI copy as often as the Unicode one
+                if !verb.is_ascii() || verb.is_empty() {
+                    return Err(format!("Invalid --verb: {}", verb));
+                }
+                choice = Some(Choice::Verb(verb));
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    match positional.as_slice() {
+        [path] => Ok(Options { path: path.clone(), extended, choice }),
+        _ => Err("Expected <path>".to_string()),
+    }
+}
+
+/// Builds the menu Explorer would show on a right-click of `path`, submenus
+/// included; `extended` adds the entries Explorer keeps for Shift+right-click.
+fn build_menu(path: &str, extended: bool) -> Result<(ContextMenu, Vec<MenuItem>)> {
+    let path = create_shortcut::known_folders::expand_known_folder(path)?;
+    let path = std::path::absolute(&path).map_err(tool::io_error)?;
+    let flags = if extended { CMF_NORMAL | CMF_EXTENDEDVERBS } else { CMF_NORMAL };
+    let menu = unsafe {
+        let item: IShellItem = SHCreateItemFromParsingName(&HSTRING::from(path.as_path()), None)?;
+        let menu: IContextMenu = item.BindToHandler(None, &BHID_SFUIObject)?;
+        ContextMenu { menu, hmenu: CreatePopupMenu()? }
+    };
+    unsafe { menu.menu.QueryContextMenu(menu.hmenu, 0, ID_FIRST, ID_LAST, flags)? };
+
+    // Send to, Open with and the like fill their submenus only when they open
+    let popups: Option<IContextMenu3> = menu.menu.cast().ok();
+    let items = read_items(&menu.menu, popups.as_ref(), menu.hmenu);
+    Ok((menu, items))
+}
+
+fn read_items(menu: &IContextMenu, popups: Option<&IContextMenu3>, hmenu: HMENU) -> Vec<MenuItem> {
+    let count = unsafe { GetMenuItemCount(hmenu) }.max(0) as u32;
+    let mut items = Vec::new();
+    for position in 0..count {
+        let mut info = MENUITEMINFOW {
+            cbSize: std::mem::size_of::<MENUITEMINFOW>() as u32,
+            fMask: MIIM_ID | MIIM_FTYPE | MIIM_STATE | MIIM_SUBMENU | MIIM_STRING,
+            ..Default::default()
+        };
+        if unsafe { GetMenuItemInfoW(hmenu, position, true, &mut info) }.is_err() {
+            continue;
+        }
+        let text = menu_text(hmenu, position, info.cch);
+
+        let state = |flag: MENU_ITEM_STATE| info.fState.0 & flag.0 == flag.0;
+        let mut item = MenuItem {
+            kind: "item",
+            id: None,
+            text,
+            verb: None,
+            disabled: state(MFS_DISABLED),
+            checked: state(MFS_CHECKED),
+            default: state(MFS_DEFAULT),
+            items: None,
+        };
+        if info.fType.0 & MFT_SEPARATOR.0 != 0 {
+            // Handlers often leave two in a row, or one at either end; callers can tidy up
+            item.kind = "separator";
+            item.text = None;
+        } else if !info.hSubMenu.is_invalid() {
+            if let Some(popups) = popups {
+                unsafe {
+                    let _ = popups.HandleMenuMsg2(
+                        WM_INITMENUPOPUP,
+                        WPARAM(info.hSubMenu.0 as usize),
+                        LPARAM(position as isize),
+                        None,
+                    );
+                }
+            }
+            item.kind = "submenu";
+            item.items = Some(read_items(menu, popups, info.hSubMenu));
+        } else if (ID_FIRST..=ID_LAST).contains(&info.wID) {
+            let id = info.wID - ID_FIRST;
+            item.id = Some(id);
+            item.verb = command_verb(menu, id);
+        } else {
+            // Not one of the handlers' commands, so there is nothing to invoke
+            continue;
+        }
+        items.push(item);
+    }
+    items
+}
+
+// The label without its & accelerator markers or any tab-separated shortcut key
+fn menu_text(hmenu: HMENU, position: u32, length: u32) -> Option<String> {
+    if length == 0 {
+        // Owner-drawn entries have no text of their own
+        return None;
+    }
+    let mut buffer = vec![0u16; length as usize + 1];
+    let mut info = MENUITEMINFOW {
+        cbSize: std::mem::size_of::<MENUITEMINFOW>() as u32,
+        fMask: MIIM_STRING,
+        dwTypeData: PWSTR(buffer.as_mut_ptr()),
+        cch: buffer.len() as u32,
+        ..Default::default()
+    };
+    unsafe { GetMenuItemInfoW(hmenu, position, true, &mut info) }.ok()?;
+    let raw = String::from_utf16_lossy(&buffer[..info.cch as usize]);
+    let raw = raw.split('\t').next().unwrap_or_default();
+
+    let mut text = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // && is a literal ampersand
+            '&' => text.extend(chars.next()),
+            c => text.push(c),
+        }
+    }
+    Some(text)
+}
+
+// The language-independent name for a command, such as "open" or "delete"; many
+// third-party entries don't have one
+fn command_verb(menu: &IContextMenu, id: u32) -> Option<String> {
+    let mut buffer = [0u16; VERB_LENGTH];
+    unsafe {
+        menu.GetCommandString(id as usize, GCS_VERBW, None, PSTR(buffer.as_mut_ptr().cast()), VERB_LENGTH as u32)
+            .ok()?;
+    }
+    let length = buffer.iter().position(|&unit| unit == 0).unwrap_or(VERB_LENGTH);
+    Some(String::from_utf16_lossy(&buffer[..length])).filter(|verb| !verb.is_empty())
+}
+
+fn invoke(path: &str, extended: bool, choice: &Choice) -> Result<()> {
+    // Rebuilt from scratch, so --id is only meaningful with the same --extended
+    // as the `menu` call it came from
+    let (menu, items) = build_menu(path, extended)?;
+    let verb_ansi;
+    let verb_wide;
+    let mut info = CMINVOKECOMMANDINFOEX {
+        cbSize: std::mem::size_of::<CMINVOKECOMMANDINFOEX>() as u32,
+        fMask: CMIC_MASK_UNICODE | CMIC_MASK_NOASYNC,
+        nShow: SW_SHOWNORMAL.0,
+        ..Default::default()
+    };
+    match choice {
+        Choice::Id(id) => {
+            let item = find_item(&items, *id)
+                .ok_or_else(|| Error::new(E_INVALIDARG, format!("The menu has no entry {}", id).into()))?;
+            if item.disabled {
+                return Err(Error::new(E_INVALIDARG, format!("Menu entry {} is disabled", id).into()));
+            }
+            // MAKEINTRESOURCE: an ID rather than a verb
+            info.lpVerb = PCSTR(*id as usize as *const u8);
+            info.lpVerbW = PCWSTR(*id as usize as *const u16);
+        }
+        Choice::Verb(verb) => {
+            verb_ansi = format!("{}\0", verb);
+            verb_wide = HSTRING::from(verb.as_str());
+            info.lpVerb = PCSTR(verb_ansi.as_ptr());
+            info.lpVerbW = PCWSTR(verb_wide.as_ptr());
+        }
+    }
+    unsafe { menu.menu.InvokeCommand(&info as *const CMINVOKECOMMANDINFOEX as *const CMINVOKECOMMANDINFO) }
+}
+
+fn find_item(items: &[MenuItem], id: u32) -> Option<&MenuItem> {
+    items.iter().find_map(|item| match &item.items {
+        Some(items) => find_item(items, id),
+        None => Some(item).filter(|item| item.id == Some(id)),
+    })
+}
@@ -0,0 +1,59 @@
+#[cfg(windows)]
+mod menu;
+#[cfg(windows)]
+mod properties;
+#[cfg(windows)]
+mod ui;
+#[cfg(windows)]
+mod watch;
+
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper shell properties <path>
+  altdesktop-helper shell menu <path> [--extended]
+  altdesktop-helper shell invoke <path> (--id <id> | --verb <verb>) [--extended]
+  altdesktop-helper shell watch [<path>...] [--recursive]
+
+properties opens Explorer's Properties sheet for <path>, prints {\"ok\":true} once it
+is on screen and exits when the user closes it.
+menu prints the entries of <path>'s right-click menu as a tree of items, separators
+and submenus; each item has an id, its text and, when it has one, a verb such as
+open or delete. --extended adds the entries Explorer shows on Shift+right-click.
+invoke runs an entry, chosen by an id from a menu call with the same --extended or
+by verb, prints {\"ok\":true} and, like properties, stays until any window it opened
+in this process closes.
+watch prints {\"type\":\"ready\",\"roots\"} and then a line for each change the shell
+announces under the paths, or anywhere when there are none, until stdin closes:
+{\"type\",\"path\",\"oldPath\"} where type is created, deleted, renamed, folderCreated,
+folderDeleted, folderRenamed, updated, folderUpdated, attributesChanged, driveAdded,
+driveRemoved, mediaInserted, mediaRemoved, shared, unshared, serverDisconnected,
+freeSpaceChanged, imageUpdated or associationsChanged. These include what fs watch
+can't see, such as a drive being plugged in, a file type getting a new default app or
+icon, or the Recycle Bin emptying. path is a file system path, or a parsing name such
+as ::{645FF040-5081-101B-9F08-00AA002F954E} for the Recycle Bin, which watch also takes;
+oldPath comes with renames. imageUpdated and associationsChanged have no path: icons
+shown so far may be out of date. --recursive includes changes below each path.
+<path> may start with a known folder token such as {Desktop}.";
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--menu") => menu::run_menu(&args),
+        Some("--invoke") => menu::run_invoke(&args),
+        Some("--watch") => watch::run(&args),
+        _ => properties::run(&args),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("shell")
+}
@@ -0,0 +1,53 @@
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use windows::{
+    core::*,
+    Win32::Foundation::{E_FAIL, HWND},
+    Win32::UI::Shell::{SHObjectProperties, SHOP_FILEPATH},
+};
+
+use super::ui::{process_has_windows, pump_messages, wait_for_windows, POLL_INTERVAL_MS};
+use super::USAGE;
+use crate::tool;
+
+// How long the sheet may take to appear, e.g. while a slow network path is read
+const OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn run(args: &[String]) -> ExitCode {
+    let [_, path] = args else {
+        return tool::usage_error("Expected <path>", USAGE);
+    };
+    match open_properties(path) {
+        Ok(()) => {}
+        Err(error) => return tool::finish::<()>(Err(error)),
+    }
+    let code = tool::finish(Ok(tool::DONE));
+    let _ = io::stdout().flush();
+
+    // The sheet lives on a thread of this process, so exiting now would close it
+    wait_for_windows();
+    code
+}
+
+fn open_properties(path: &str) -> Result<()> {
+    let path = create_shortcut::known_folders::expand_known_folder(path)?;
+    let path = std::path::absolute(&path).map_err(tool::io_error)?;
+    // Fails without saying why; a missing path is the usual reason
+    std::fs::metadata(&path).map_err(tool::io_error)?;
+    let opened = unsafe { SHObjectProperties(HWND(0), SHOP_FILEPATH, &HSTRING::from(path.as_path()), None) };
+    if !opened.as_bool() {
+        return Err(Error::new(E_FAIL, format!("Could not open the properties of {}", path.display()).into()));
+    }
+
+    // SHObjectProperties returns before the sheet is created
+    let started = Instant::now();
+    while !process_has_windows() {
+        if started.elapsed() > OPEN_TIMEOUT {
+            return Err(Error::new(E_FAIL, "The Properties sheet did not appear".into()));
+        }
+        pump_messages(POLL_INTERVAL_MS);
+    }
+    Ok(())
+}
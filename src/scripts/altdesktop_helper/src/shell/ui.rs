@@ -0,0 +1,50 @@
+use windows::{
+    Win32::Foundation::{BOOL, HWND, LPARAM},
+    Win32::System::Console::GetConsoleWindow,
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+// Shell UI such as the Properties sheet runs on threads of whichever process asked
+// for it, and closes when that process exits, so commands that open some wait here
+
+pub const POLL_INTERVAL_MS: u32 = 100;
+
+/// Keeps this process alive until its last visible window closes.
+pub fn wait_for_windows() {
+    while process_has_windows() {
+        pump_messages(POLL_INTERVAL_MS);
+    }
+}
+
+pub fn pump_messages(timeout_ms: u32) {
+    unsafe {
+        MsgWaitForMultipleObjects(None, false, timeout_ms, QS_ALLINPUT);
+        let mut message = MSG::default();
+        while PeekMessageW(&mut message, HWND(0), 0, 0, PM_REMOVE).as_bool() {
+            TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+    }
+}
+
+pub fn process_has_windows() -> bool {
+    let mut found = false;
+    unsafe {
+        let _ = EnumWindows(Some(visible_in_process), LPARAM(&mut found as *mut bool as isize));
+    }
+    found
+}
+
+unsafe extern "system" fn visible_in_process(hwnd: HWND, found: LPARAM) -> BOOL {
+    // Run from a terminal, the console window reports this process as its owner
+    if hwnd == unsafe { GetConsoleWindow() } {
+        return true.into();
+    }
+    let mut process_id = 0;
+    unsafe { GetWindowThreadProcessId(hwnd, Some(&mut process_id)) };
+    if process_id == std::process::id() && unsafe { IsWindowVisible(hwnd) }.as_bool() {
+        unsafe { *(found.0 as *mut bool) = true };
+        return false.into();
+    }
+    true.into()
+}
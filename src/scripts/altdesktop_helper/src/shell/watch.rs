@@ -0,0 +1,202 @@
+use std::io::{self, Read};
+use std::process::ExitCode;
+use std::thread;
+
+use altdesktop_core::json::emit;
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Foundation::{BOOL, E_FAIL, HANDLE, HWND, LPARAM, WPARAM},
+    Win32::System::Com::CoTaskMemFree,
+    Win32::System::Threading::GetCurrentThreadId,
+    Win32::UI::Shell::Common::ITEMIDLIST,
+    Win32::UI::Shell::*,
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+use super::USAGE;
+use crate::tool;
+
+// Posted to the window for each batch of changes, and to the thread when stdin closes
+const WM_SHELL_CHANGE: u32 = WM_APP;
+const WM_STDIN_CLOSED: u32 = WM_APP + 1;
+
+struct Options {
+    roots: Vec<String>,
+    recursive: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Event {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_path: Option<String>,
+}
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    // Message-only: never shown, but the shell posts its notifications to a window
+    let window = unsafe {
+        CreateWindowExW(WINDOW_EX_STYLE(0), w!("STATIC"), None, WINDOW_STYLE(0), 0, 0, 0, 0, HWND_MESSAGE, None, None, None)
+    };
+    if window.0 == 0 {
+        return tool::finish::<()>(Err(Error::from_win32()));
+    }
+    let roots = if options.roots.is_empty() {
+        // The desktop is the root of the whole namespace, so this watches everything
+        unsafe { SHGetSpecialFolderLocation(HWND(0), CSIDL_DESKTOP as i32) }.map(|pidl| vec![pidl as *const _])
+    } else {
+        options.roots.iter().map(|root| parse_root(root)).collect::<Result<Vec<_>>>()
+    };
+    let roots = match roots {
+        Ok(roots) => roots,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    let code = watch(window, &roots, options.recursive || options.roots.is_empty(), &options.roots);
+    for pidl in roots {
+        unsafe { ILFree(Some(pidl)) };
+    }
+    let _ = unsafe { DestroyWindow(window) };
+    code
+}
+
+fn parse_args(args: &[String]) -> std::result::Result<Options, String> {
+    let mut options = Options { roots: Vec::new(), recursive: false };
+    for arg in args.iter().skip(2) {
+        match arg.as_str() {
+            "--recursive" => options.recursive = true,
+            flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => options.roots.push(arg.clone()),
+        }
+    }
+    Ok(options)
+}
+
+// A path, which may start with a known folder token, or a shell parsing name such
+// as the Recycle Bin's ::{645FF040-5081-101B-9F08-00AA002F954E}
+fn parse_root(root: &str) -> Result<*const ITEMIDLIST> {
+    let mut name = create_shortcut::known_folders::expand_known_folder(root)?;
+    if !name.starts_with("::") {
+        name = std::path::absolute(&name).map_err(tool::io_error)?.to_string_lossy().into_owned();
+    }
+    let mut pidl = std::ptr::null_mut();
+    unsafe { SHParseDisplayName(&HSTRING::from(name.as_str()), None, &mut pidl, 0, None)? };
+    Ok(pidl)
+}
+
+fn watch(window: HWND, roots: &[*const ITEMIDLIST], recursive: bool, names: &[String]) -> ExitCode {
+    let entries: Vec<SHChangeNotifyEntry> =
+        roots.iter().map(|pidl| SHChangeNotifyEntry { pidl: *pidl as _, fRecursive: BOOL::from(recursive) }).collect();
+    // Shell-level as well as file system changes: those Explorer makes itself, and
+    // the ones it announces for drives, associations and icons
+    let registration = unsafe {
+        SHChangeNotifyRegister(
+            window,
+            SHCNRF_ShellLevel | SHCNRF_InterruptLevel | SHCNRF_NewDelivery,
+            SHCNE_ALLEVENTS.0 as i32,
+            WM_SHELL_CHANGE,
+            entries.len() as i32,
+            entries.as_ptr(),
+        )
+    };
+    if registration == 0 {
+        return tool::finish::<()>(Err(Error::new(E_FAIL, "Could not register for shell notifications".into())));
+    }
+
+    if emit(serde_json::json!({ "type": "ready", "roots": names })) {
+        let thread_id = unsafe { GetCurrentThreadId() };
+        thread::spawn(move || {
+            let _ = io::stdin().read_to_end(&mut Vec::new());
+            let _ = unsafe { PostThreadMessageW(thread_id, WM_STDIN_CLOSED, WPARAM(0), LPARAM(0)) };
+        });
+        let mut message = MSG::default();
+        while unsafe { GetMessageW(&mut message, HWND(0), 0, 0) }.as_bool() {
+            match message.message {
+                WM_STDIN_CLOSED => break,
+                WM_SHELL_CHANGE => {
+                    if let Some(event) = read_change(message.wParam, message.lParam)
+                        && !emit(&event)
+                    {
+                        break;
+                    }
+                }
+                _ => unsafe {
+                    DispatchMessageW(&message);
+                },
+            }
+        }
+    }
+    let _ = unsafe { SHChangeNotifyDeregister(registration) };
+    ExitCode::SUCCESS
+}
+
+// With new delivery the message carries a handle to the change, which has to be
+// locked to read its event and the two ID lists it involves
+fn read_change(wparam: WPARAM, lparam: LPARAM) -> Option<Event> {
+    let mut pidls: *mut *mut ITEMIDLIST = std::ptr::null_mut();
+    let mut event = 0i32;
+    let lock = unsafe {
+        SHChangeNotification_Lock(HANDLE(wparam.0 as isize), lparam.0 as u32, Some(&mut pidls), Some(&mut event))
+    };
+    if lock.is_invalid() {
+        return None;
+    }
+    let event = SHCNE_ID(event as u32 & !SHCNE_INTERRUPT.0);
+    let item = |index: usize| unsafe {
+        if pidls.is_null() { None } else { display_name(*pidls.add(index)) }
+    };
+    let change = event_name(event).map(|kind| match event {
+        SHCNE_RENAMEITEM | SHCNE_RENAMEFOLDER => Event { kind, path: item(1), old_path: item(0) },
+        // These carry an image index or nothing in place of an ID list
+        SHCNE_UPDATEIMAGE | SHCNE_ASSOCCHANGED => Event { kind, path: None, old_path: None },
+        _ => Event { kind, path: item(0), old_path: None },
+    });
+    let _ = unsafe { SHChangeNotification_Unlock(lock) };
+    change
+}
+
+fn event_name(event: SHCNE_ID) -> Option<&'static str> {
+    Some(match event {
+        SHCNE_CREATE => "created",
+        SHCNE_DELETE => "deleted",
+        SHCNE_RENAMEITEM => "renamed",
+        SHCNE_MKDIR => "folderCreated",
+        SHCNE_RMDIR => "folderDeleted",
+        SHCNE_RENAMEFOLDER => "folderRenamed",
+        SHCNE_UPDATEITEM => "updated",
+        SHCNE_UPDATEDIR => "folderUpdated",
+        SHCNE_ATTRIBUTES => "attributesChanged",
+        SHCNE_DRIVEADD => "driveAdded",
+        SHCNE_DRIVEREMOVED => "driveRemoved",
+        SHCNE_MEDIAINSERTED => "mediaInserted",
+        SHCNE_MEDIAREMOVED => "mediaRemoved",
+        SHCNE_NETSHARE => "shared",
+        SHCNE_NETUNSHARE => "unshared",
+        SHCNE_SERVERDISCONNECT => "serverDisconnected",
+        SHCNE_FREESPACE => "freeSpaceChanged",
+        SHCNE_UPDATEIMAGE => "imageUpdated",
+        SHCNE_ASSOCCHANGED => "associationsChanged",
+        // DRIVEADDGUI repeats a DRIVEADD, and extended events are private to Explorer
+        _ => return None,
+    })
+}
+
+// A file system path where there is one, otherwise a parsing name like ::{GUID}
+fn display_name(pidl: *const ITEMIDLIST) -> Option<String> {
+    if pidl.is_null() {
+        return None;
+    }
+    unsafe {
+        let name = SHGetNameFromIDList(pidl, SIGDN_DESKTOPABSOLUTEPARSING).ok()?;
+        let value = name.to_string().ok();
+        CoTaskMemFree(Some(name.0 as _));
+        value
+    }
+}
@@ -0,0 +1,372 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use altdesktop_core::error::ErrorReport;
+#[cfg(windows)]
+use altdesktop_core::exit;
+#[cfg(windows)]
+use create_shortcut::known_folders::{expand_known_folder, known_folder_path};
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::E_INVALIDARG,
+    Win32::System::Registry::{HKEY, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE},
+    Win32::UI::Shell::{FOLDERID_CommonStartup, FOLDERID_Startup},
+};
+
+#[cfg(windows)]
+use crate::registry::{delete_value, read_binary, read_string, value_names, write_string};
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper startup list [--scope user|machine]
+  altdesktop-helper startup query <name> [--scope user|machine]
+  altdesktop-helper startup add <name> <target> [--args <args>] [--working-dir <dir>]
+                                [--method run|folder] [--scope user|machine]
+  altdesktop-helper startup remove <name> [--method run|folder] [--scope user|machine]
+
+Manages what starts at login, the two ways Explorer offers: a value in the Run
+registry key (--method run, the default), or a shortcut in the Startup folder
+(--method folder). The run method only starts programs; use folder for documents,
+folders or scripts. --scope user (the default for add and remove) registers for
+this user only, machine for everyone, which needs administrator rights: run it
+through --elevate. list prints [{\"name\",\"method\",\"scope\",\"target\",\"arguments\",
+\"command\",\"path\",\"enabled\"}] for both scopes unless --scope is given: command is
+the Run value as written, path the Startup folder file, and enabled false when the
+entry is turned off in Task Manager's Startup tab. query prints {\"name\",
+\"registered\",\"entries\"} with the entries of that name. add registers <target>,
+replacing an entry of the same name and method, turns it back on if it was turned
+off, and prints the entry. remove deletes the entries of that name with either
+method unless --method is given, and prints {\"ok\":true,\"removed\"} with what was
+there. <target> and <dir> may start with a known folder token.";
+
+#[cfg(windows)]
+const RUN_KEY: &HSTRING = h!("Software\\Microsoft\\Windows\\CurrentVersion\\Run");
+// Where Task Manager records entries turned off: binary values whose first byte is
+// odd when disabled, named after the Run value or the Startup folder file
+#[cfg(windows)]
+const APPROVED_RUN_KEY: &HSTRING = h!("Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\StartupApproved\\Run");
+#[cfg(windows)]
+const APPROVED_FOLDER_KEY: &HSTRING =
+    h!("Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\StartupApproved\\StartupFolder");
+
+#[cfg(windows)]
+#[derive(Clone, Copy, PartialEq)]
+enum Scope {
+    User,
+    Machine,
+}
+
+#[cfg(windows)]
+impl Scope {
+    fn name(self) -> &'static str {
+        match self {
+            Scope::User => "user",
+            Scope::Machine => "machine",
+        }
+    }
+
+    fn hive(self) -> HKEY {
+        match self {
+            Scope::User => HKEY_CURRENT_USER,
+            Scope::Machine => HKEY_LOCAL_MACHINE,
+        }
+    }
+
+    fn folder_token(self) -> &'static str {
+        match self {
+            Scope::User => "{Startup}",
+            Scope::Machine => "{CommonStartup}",
+        }
+    }
+
+    fn folder(self) -> Result<String> {
+        match self {
+            Scope::User => known_folder_path(&FOLDERID_Startup),
+            Scope::Machine => known_folder_path(&FOLDERID_CommonStartup),
+        }
+    }
+}
+
+#[cfg(windows)]
+#[derive(Clone, Copy, PartialEq)]
+enum Method {
+    Run,
+    Folder,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct Entry {
+    name: String,
+    method: &'static str,
+    scope: &'static str,
+    target: Option<String>,
+    arguments: Option<String>,
+    command: Option<String>,
+    path: Option<String>,
+    enabled: bool,
+}
+
+#[cfg(windows)]
+#[derive(Default)]
+struct Options {
+    positional: Vec<String>,
+    scope: Option<Scope>,
+    method: Option<Method>,
+    arguments: Option<String>,
+    working_dir: Option<String>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let (command, rest) = match args.get(1).map(String::as_str) {
+        Some(flag @ ("--query" | "--add" | "--remove")) => (flag, &args[2..]),
+        _ => ("--list", &args[1..]),
+    };
+    let options = match parse_args(command, rest) {
+        Ok(options) => options,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let scopes = match options.scope {
+        Some(scope) => vec![scope],
+        None if matches!(command, "--add" | "--remove") => vec![Scope::User],
+        None => vec![Scope::User, Scope::Machine],
+    };
+    match command {
+        "--query" => {
+            let name = &options.positional[0];
+            let entries: Vec<Entry> =
+                list_entries(&scopes).into_iter().filter(|entry| entry.name.eq_ignore_ascii_case(name)).collect();
+            tool::finish(Ok(json!({ "name": name, "registered": !entries.is_empty(), "entries": entries })))
+        }
+        "--add" => match add(&options, scopes[0]) {
+            Ok(entry) => tool::finish(Ok(entry)),
+            Err(report) => {
+                println!("{}", report);
+                ExitCode::from(exit::for_code(report["code"].as_str().unwrap_or_default()))
+            }
+        },
+        "--remove" => tool::finish(remove(&options, scopes[0])),
+        _ => tool::finish(Ok(list_entries(&scopes))),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("startup")
+}
+
+#[cfg(windows)]
+fn parse_args(command: &str, args: &[String]) -> std::result::Result<Options, String> {
+    let mut options = Options::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--scope" => {
+                options.scope = Some(match value()?.as_str() {
+                    "user" => Scope::User,
+                    "machine" => Scope::Machine,
+                    other => return Err(format!("Unknown scope: {} (expected user or machine)", other)),
+                })
+            }
+            "--method" if command != "--list" && command != "--query" => {
+                options.method = Some(match value()?.as_str() {
+                    "run" => Method::Run,
+                    "folder" => Method::Folder,
+                    other => return Err(format!("Unknown method: {} (expected run or folder)", other)),
+                })
+            }
+            "--args" if command == "--add" => options.arguments = Some(value()?),
+            "--working-dir" if command == "--add" => options.working_dir = Some(value()?),
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => options.positional.push(arg.clone()),
+        }
+    }
+    let expected = match command {
+        "--add" => (2, "Expected <name> <target>"),
+        "--query" | "--remove" => (1, "Expected <name>"),
+        _ => (0, "list takes no arguments"),
+    };
+    if options.positional.len() != expected.0 {
+        return Err(expected.1.to_string());
+    }
+    if let Some(name) = options.positional.first() {
+        // Names become file names for the folder method, so both follow their rules
+        if name.trim().is_empty() || name.contains(['\\', '/', ':', '*', '?', '"', '<', '>', '|']) {
+            return Err(format!("Invalid name: {} (it must be usable as a file name)", name));
+        }
+    }
+    Ok(options)
+}
+
+#[cfg(windows)]
+fn list_entries(scopes: &[Scope]) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for &scope in scopes {
+        for name in value_names(scope.hive(), RUN_KEY) {
+            let value = HSTRING::from(name.as_str());
+            let Some(command) = read_string(scope.hive(), RUN_KEY, PCWSTR(value.as_ptr())) else {
+                continue;
+            };
+            let (target, arguments) = split_command(&command);
+            entries.push(Entry {
+                enabled: is_enabled(scope, APPROVED_RUN_KEY, &name),
+                name,
+                method: "run",
+                scope: scope.name(),
+                target: Some(target),
+                arguments: Some(arguments),
+                command: Some(command),
+                path: None,
+            });
+        }
+        let Ok(folder) = scope.folder() else {
+            continue;
+        };
+        let Ok(files) = std::fs::read_dir(&folder) else {
+            continue;
+        };
+        for file in files.flatten() {
+            let path = file.path();
+            let file_name = file.file_name().to_string_lossy().into_owned();
+            if !path.is_file() || file_name.eq_ignore_ascii_case("desktop.ini") {
+                continue;
+            }
+            let path = path.to_string_lossy().into_owned();
+            // Anything else in the folder is opened as it is
+            let (target, arguments) = if file_name.to_ascii_lowercase().ends_with(".lnk") {
+                match create_shortcut::read::read_shortcut(&path) {
+                    Ok(info) => (Some(info.target_path), Some(info.arguments)),
+                    Err(_) => (None, None),
+                }
+            } else {
+                (Some(path.clone()), None)
+            };
+            entries.push(Entry {
+                name: file_name.rsplit_once('.').map_or(file_name.as_str(), |(stem, _)| stem).to_string(),
+                method: "folder",
+                scope: scope.name(),
+                target,
+                arguments,
+                command: None,
+                path: Some(path),
+                enabled: is_enabled(scope, APPROVED_FOLDER_KEY, &file_name),
+            });
+        }
+    }
+    entries
+}
+
+#[cfg(windows)]
+fn is_enabled(scope: Scope, approved_key: &HSTRING, name: &str) -> bool {
+    let name = HSTRING::from(name);
+    let approval = read_binary(scope.hive(), approved_key, PCWSTR(name.as_ptr()));
+    approval.and_then(|data| data.first().copied()).is_none_or(|flags| flags & 1 == 0)
+}
+
+// Explorer splits an unquoted Run command the way CreateProcess does, so a program
+// path with spaces still ends at its .exe
+#[cfg(windows)]
+fn split_command(command: &str) -> (String, String) {
+    let command = command.trim();
+    if let Some((target, arguments)) = command.strip_prefix('"').and_then(|rest| rest.split_once('"')) {
+        return (target.to_string(), arguments.trim_start().to_string());
+    }
+    let end = match command.to_ascii_lowercase().find(".exe") {
+        Some(index) => index + ".exe".len(),
+        None => command.find(' ').unwrap_or(command.len()),
+    };
+    (command[..end].to_string(), command[end..].trim_start().to_string())
+}
+
+#[cfg(windows)]
+fn add(options: &Options, scope: Scope) -> std::result::Result<Entry, Value> {
+    let report = |error: Error| serde_json::to_value(ErrorReport::from_error(&error)).unwrap();
+    let absolute = |path: &str| -> Result<String> {
+        let path = std::path::absolute(expand_known_folder(path)?).map_err(tool::io_error)?;
+        Ok(path.to_string_lossy().into_owned())
+    };
+    let name = options.positional[0].clone();
+    let target = absolute(&options.positional[1]).map_err(report)?;
+    let arguments = options.arguments.clone().unwrap_or_default();
+
+    if options.method.unwrap_or(Method::Run) == Method::Folder {
+        let working_dir = options.working_dir.as_deref().map(absolute).transpose().map_err(report)?;
+        let spec = json!({
+            "path": format!("{}\\{}.lnk", scope.folder_token(), name),
+            "target": target,
+            "args": options.arguments,
+            "workingDir": working_dir,
+        });
+        let path = create_shortcut::create_json(spec)?["path"].as_str().unwrap_or_default().to_string();
+        delete_value(scope.hive(), APPROVED_FOLDER_KEY, &HSTRING::from(format!("{}.lnk", name))).map_err(report)?;
+        return Ok(Entry {
+            name,
+            method: "folder",
+            scope: scope.name(),
+            target: Some(target),
+            arguments: Some(arguments),
+            command: None,
+            path: Some(path),
+            enabled: true,
+        });
+    }
+
+    if options.working_dir.is_some() {
+        let error = Error::new(E_INVALIDARG, "--working-dir needs --method folder; Run entries start in the system folder".into());
+        return Err(report(error));
+    }
+    let command = if arguments.is_empty() { format!("\"{}\"", target) } else { format!("\"{}\" {}", target, arguments) };
+    let value = HSTRING::from(name.as_str());
+    write_string(scope.hive(), RUN_KEY, &value, &command).map_err(report)?;
+    delete_value(scope.hive(), APPROVED_RUN_KEY, &value).map_err(report)?;
+    Ok(Entry {
+        name,
+        method: "run",
+        scope: scope.name(),
+        target: Some(target),
+        arguments: Some(arguments),
+        command: Some(command),
+        path: None,
+        enabled: true,
+    })
+}
+
+#[cfg(windows)]
+fn remove(options: &Options, scope: Scope) -> Result<Value> {
+    let name = &options.positional[0];
+    let wanted = |entry: &Entry| match options.method {
+        Some(Method::Run) => entry.method == "run",
+        Some(Method::Folder) => entry.method == "folder",
+        None => true,
+    };
+    let entries: Vec<Entry> =
+        list_entries(&[scope]).into_iter().filter(|entry| entry.name.eq_ignore_ascii_case(name) && wanted(entry)).collect();
+    for entry in &entries {
+        match &entry.path {
+            Some(path) => {
+                std::fs::remove_file(path).map_err(tool::io_error)?;
+                let file_name = path.rsplit('\\').next().unwrap_or_default();
+                delete_value(scope.hive(), APPROVED_FOLDER_KEY, &HSTRING::from(file_name))?;
+            }
+            None => {
+                let value = HSTRING::from(entry.name.as_str());
+                delete_value(scope.hive(), RUN_KEY, &value)?;
+                delete_value(scope.hive(), APPROVED_RUN_KEY, &value)?;
+            }
+        }
+    }
+    Ok(json!({ "ok": true, "removed": entries }))
+}
@@ -0,0 +1,277 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::{self, Read};
+#[cfg(windows)]
+use std::sync::atomic::{AtomicU32, Ordering};
+#[cfg(windows)]
+use std::thread;
+
+#[cfg(windows)]
+use altdesktop_core::json::emit;
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM},
+    Win32::Graphics::Gdi::{GetMonitorInfoW, MonitorFromWindow, HMONITOR, MONITORINFO, MONITOR_DEFAULTTONEAREST},
+    Win32::System::Threading::GetCurrentThreadId,
+    Win32::UI::Shell::*,
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+#[cfg(windows)]
+use crate::monitors::{self, Rect};
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper taskbar query
+  altdesktop-helper taskbar watch
+
+query prints [{\"monitor\",\"primary\",\"edge\",\"bounds\",\"autoHide\"}], one entry per
+taskbar: the primary one, and one for each other display when the taskbar is shown
+on all of them. monitor is the display's index as monitors list prints it, edge is
+left, top, right or bottom, and bounds is where the taskbar is in physical pixels
+while it is shown, even when it has slid away. A taskbar that doesn't auto-hide is
+already left out of the display's work area; one that does overlaps the work area
+when it slides in.
+watch prints {\"type\":\"ready\",\"taskbars\"} with what query prints, then
+{\"type\":\"changed\",\"taskbars\"} whenever any of it changes, such as the taskbar
+being moved, auto-hide being switched, a display coming or going or Explorer
+restarting, until stdin closes.";
+
+#[cfg(windows)]
+const CLASS_NAME: PCWSTR = w!("AltDesktopHelperTaskbar");
+// The shell's appbar notifications, and what's posted when stdin closes
+#[cfg(windows)]
+const WM_APPBAR: u32 = WM_APP + 1;
+#[cfg(windows)]
+const WM_STDIN_CLOSED: u32 = WM_APP + 2;
+// Moving the taskbar to another edge resizes it and changes the work area, one
+// notification each; it is read once the burst is over
+#[cfg(windows)]
+const SETTLE_TIMER: usize = 1;
+#[cfg(windows)]
+const SETTLE_MILLISECONDS: u32 = 200;
+
+// Registered at startup; the message Explorer broadcasts when the taskbar comes back
+#[cfg(windows)]
+static TASKBAR_CREATED: AtomicU32 = AtomicU32::new(0);
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Taskbar {
+    monitor: usize,
+    primary: bool,
+    edge: &'static str,
+    bounds: Rect,
+    auto_hide: bool,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--watch") if args.len() == 2 => watch(),
+        Some("--watch") => tool::usage_error("watch takes no arguments", USAGE),
+        None => tool::finish(list_taskbars()),
+        Some(_) => tool::usage_error("query takes no arguments", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("taskbar")
+}
+
+#[cfg(windows)]
+fn list_taskbars() -> Result<Vec<Taskbar>> {
+    let handles = monitors::monitor_handles()?;
+    // One setting for every taskbar, though each display's slides away on its own
+    let mut data = APPBARDATA { cbSize: std::mem::size_of::<APPBARDATA>() as u32, ..Default::default() };
+    let auto_hide = unsafe { SHAppBarMessage(ABM_GETSTATE, &mut data) } as u32 & ABS_AUTOHIDE != 0;
+
+    let mut taskbars = Vec::new();
+    let primary = unsafe { FindWindowW(w!("Shell_TrayWnd"), None) };
+    // No taskbar at all while Explorer isn't running
+    if primary.0 != 0 {
+        data.hWnd = primary;
+        if unsafe { SHAppBarMessage(ABM_GETTASKBARPOS, &mut data) } != 0 {
+            taskbars.push(describe(primary, data.rc, Some(data.uEdge), true, auto_hide, &handles));
+        }
+    }
+    // The shell only answers for the primary taskbar; the others are plain windows
+    let mut secondary = HWND(0);
+    loop {
+        secondary = unsafe { FindWindowExW(None, secondary, w!("Shell_SecondaryTrayWnd"), None) };
+        if secondary.0 == 0 {
+            break;
+        }
+        let mut rect = RECT::default();
+        if unsafe { GetWindowRect(secondary, &mut rect) }.is_ok() {
+            taskbars.push(describe(secondary, rect, None, false, auto_hide, &handles));
+        }
+    }
+    Ok(taskbars)
+}
+
+#[cfg(windows)]
+fn describe(
+    window: HWND,
+    rect: RECT,
+    edge: Option<u32>,
+    primary: bool,
+    auto_hide: bool,
+    handles: &[HMONITOR],
+) -> Taskbar {
+    let handle = unsafe { MonitorFromWindow(window, MONITOR_DEFAULTTONEAREST) };
+    let mut info = MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+    let display = if unsafe { GetMonitorInfoW(handle, &mut info) }.as_bool() { info.rcMonitor } else { rect };
+    let edge = edge.unwrap_or_else(|| nearest_edge(rect, display));
+    Taskbar {
+        monitor: handles.iter().position(|&known| known == handle).unwrap_or(0),
+        primary,
+        edge: match edge {
+            ABE_LEFT => "left",
+            ABE_TOP => "top",
+            ABE_RIGHT => "right",
+            _ => "bottom",
+        },
+        bounds: shown_bounds(rect, edge, display).into(),
+        auto_hide,
+    }
+}
+
+// A taskbar runs along the edge it is docked to, so it is wider than tall on the
+// top and bottom
+#[cfg(windows)]
+fn nearest_edge(rect: RECT, display: RECT) -> u32 {
+    let (width, height) = (rect.right - rect.left, rect.bottom - rect.top);
+    if width >= height {
+        if rect.top + rect.bottom < display.top + display.bottom { ABE_TOP } else { ABE_BOTTOM }
+    } else if rect.left + rect.right < display.left + display.right {
+        ABE_LEFT
+    } else {
+        ABE_RIGHT
+    }
+}
+
+// An auto-hidden taskbar keeps its size while it slides past the edge, leaving a
+// sliver on screen; put it back against the edge
+#[cfg(windows)]
+fn shown_bounds(rect: RECT, edge: u32, display: RECT) -> RECT {
+    let (width, height) = (rect.right - rect.left, rect.bottom - rect.top);
+    match edge {
+        ABE_LEFT => RECT { left: display.left, right: display.left + width, ..rect },
+        ABE_TOP => RECT { top: display.top, bottom: display.top + height, ..rect },
+        ABE_RIGHT => RECT { left: display.right - width, right: display.right, ..rect },
+        _ => RECT { top: display.bottom - height, bottom: display.bottom, ..rect },
+    }
+}
+
+#[cfg(windows)]
+fn taskbars_value() -> Value {
+    match list_taskbars() {
+        Ok(taskbars) => serde_json::to_value(taskbars).unwrap(),
+        Err(_) => Value::Null,
+    }
+}
+
+#[cfg(windows)]
+fn watch() -> ExitCode {
+    let window = match create_window() {
+        Ok(window) => window,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    // Registered but never given a position, so it takes no room on screen; it is
+    // how the shell reports the taskbar moving or auto-hide being switched
+    register_appbar(window);
+
+    let mut taskbars = taskbars_value();
+    if emit(json!({ "type": "ready", "taskbars": taskbars })) {
+        let thread_id = unsafe { GetCurrentThreadId() };
+        thread::spawn(move || {
+            let _ = io::stdin().read_to_end(&mut Vec::new());
+            let _ = unsafe { PostThreadMessageW(thread_id, WM_STDIN_CLOSED, WPARAM(0), LPARAM(0)) };
+        });
+        let mut message = MSG::default();
+        while unsafe { GetMessageW(&mut message, HWND(0), 0, 0) }.as_bool() {
+            if message.message == WM_STDIN_CLOSED {
+                break;
+            }
+            if message.message != WM_TIMER || message.wParam.0 != SETTLE_TIMER {
+                unsafe { DispatchMessageW(&message) };
+                continue;
+            }
+            unsafe {
+                let _ = KillTimer(window, SETTLE_TIMER);
+            }
+            let current = taskbars_value();
+            if current == taskbars {
+                continue;
+            }
+            if !emit(json!({ "type": "changed", "taskbars": current })) {
+                break;
+            }
+            taskbars = current;
+        }
+    }
+    let mut data = APPBARDATA { cbSize: std::mem::size_of::<APPBARDATA>() as u32, hWnd: window, ..Default::default() };
+    unsafe {
+        SHAppBarMessage(ABM_REMOVE, &mut data);
+        let _ = DestroyWindow(window);
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(windows)]
+fn register_appbar(window: HWND) {
+    let mut data = APPBARDATA {
+        cbSize: std::mem::size_of::<APPBARDATA>() as u32,
+        hWnd: window,
+        uCallbackMessage: WM_APPBAR,
+        ..Default::default()
+    };
+    unsafe { SHAppBarMessage(ABM_NEW, &mut data) };
+}
+
+// Listening for TaskbarCreated, which Explorer broadcasts once it has restarted
+#[cfg(windows)]
+fn create_window() -> Result<HWND> {
+    let window = tool::hidden_window(CLASS_NAME, Some(window_proc))?;
+    unsafe {
+        let taskbar_created = RegisterWindowMessageW(w!("TaskbarCreated"));
+        TASKBAR_CREATED.store(taskbar_created, Ordering::Relaxed);
+        // When elevated, Explorer's broadcast would otherwise be filtered out
+        let _ = ChangeWindowMessageFilterEx(window, taskbar_created, MSGFLT_ALLOW, None);
+        Ok(window)
+    }
+}
+
+// Anything that may have moved a taskbar restarts the settle timer, whose expiry the
+// loop handles
+#[cfg(windows)]
+extern "system" fn window_proc(window: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let settle = match message {
+        WM_APPBAR => matches!(wparam.0 as u32, ABN_POSCHANGED | ABN_STATECHANGE),
+        WM_SETTINGCHANGE | WM_DISPLAYCHANGE => true,
+        _ if message != 0 && message == TASKBAR_CREATED.load(Ordering::Relaxed) => {
+            // The new Explorer knows nothing of the old one's appbars
+            register_appbar(window);
+            true
+        }
+        _ => return unsafe { DefWindowProcW(window, message, wparam, lparam) },
+    };
+    if settle {
+        unsafe { SetTimer(window, SETTLE_TIMER, SETTLE_MILLISECONDS, None) };
+    }
+    LRESULT(0)
+}
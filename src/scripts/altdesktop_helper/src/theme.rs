@@ -0,0 +1,240 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::io::{self, Read, Write};
+#[cfg(windows)]
+use std::thread;
+#[cfg(windows)]
+use std::time::Duration;
+
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{BOOL, HANDLE, WAIT_OBJECT_0},
+    Win32::Graphics::Dwm::DwmGetColorizationColor,
+    Win32::System::Registry::*,
+    Win32::System::Threading::{CreateEventW, WaitForMultipleObjects, WaitForSingleObject, INFINITE},
+    Win32::UI::Accessibility::{HCF_HIGHCONTRASTON, HIGHCONTRASTW},
+    Win32::UI::WindowsAndMessaging::{SystemParametersInfoW, SPI_GETHIGHCONTRAST, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS},
+};
+
+#[cfg(windows)]
+use crate::registry::{read_binary, read_dword};
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper theme query
+  altdesktop-helper theme watch
+
+Prints the colors and effects Windows is set to use: whether apps and the taskbar
+use the light theme, whether transparency effects are on, the accent color and
+Explorer's eight shades of it, whether the accent shows on title bars and on the
+taskbar, DWM's window colorization color and whether high contrast is on. Colors
+are #rrggbb; accentColor and accentPalette are null until an accent has been set.
+
+watch prints {\"type\":\"ready\",\"theme\"} with what query prints, then
+{\"type\":\"changed\",\"changes\":[...],\"theme\"} whenever any of it changes, listing the
+fields that did, until stdin closes.";
+
+#[cfg(windows)]
+const PERSONALIZE_KEY: &HSTRING = h!("Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize");
+#[cfg(windows)]
+const DWM_KEY: &HSTRING = h!("Software\\Microsoft\\Windows\\DWM");
+#[cfg(windows)]
+const ACCENT_KEY: &HSTRING = h!("Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\Accent");
+#[cfg(windows)]
+const HIGH_CONTRAST_KEY: &HSTRING = h!("Control Panel\\Accessibility\\HighContrast");
+// Switching themes writes a burst of values; they are read once it is over
+#[cfg(windows)]
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Theme {
+    apps_use_light_theme: bool,
+    system_uses_light_theme: bool,
+    transparency: bool,
+    accent_color: Option<String>,
+    accent_palette: Option<Vec<String>>,
+    accent_on_title_bars: bool,
+    accent_on_taskbar: bool,
+    colorization: Option<Colorization>,
+    high_contrast: bool,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Colorization {
+    color: String,
+    alpha: u8,
+    /// False when DWM blends the color into the glass behind it.
+    opaque_blend: bool,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        None => tool::finish(Ok(query_theme())),
+        Some("--watch") if args.len() == 2 => watch_until_stdin_closes(),
+        Some("--watch") => tool::usage_error("watch takes no arguments", USAGE),
+        Some(_) => tool::usage_error("query takes no arguments", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("theme")
+}
+
+// Missing values mean the setting was never changed, so each falls back to what
+// a fresh install does
+#[cfg(windows)]
+fn query_theme() -> Theme {
+    let personalize = |value| read_dword(HKEY_CURRENT_USER, PERSONALIZE_KEY, value);
+    let dwm = |value| read_dword(HKEY_CURRENT_USER, DWM_KEY, value);
+
+    // AccentColor is 0xAABBGGRR
+    let accent_color = dwm(w!("AccentColor")).map(|abgr| hex_color(abgr & 0xff, (abgr >> 8) & 0xff, (abgr >> 16) & 0xff));
+    // Eight RGBA entries, lightest to darkest
+    let accent_palette = read_binary(HKEY_CURRENT_USER, ACCENT_KEY, w!("AccentPalette"))
+        .filter(|palette| palette.len() >= 32)
+        .map(|palette| {
+            palette[..32]
+                .chunks_exact(4)
+                .map(|rgba| hex_color(rgba[0] as u32, rgba[1] as u32, rgba[2] as u32))
+                .collect()
+        });
+
+    let mut argb = 0u32;
+    let mut opaque = BOOL(0);
+    let colorization = unsafe { DwmGetColorizationColor(&mut argb, &mut opaque) }.ok().map(|()| Colorization {
+        color: hex_color((argb >> 16) & 0xff, (argb >> 8) & 0xff, argb & 0xff),
+        alpha: (argb >> 24) as u8,
+        opaque_blend: opaque.as_bool(),
+    });
+
+    Theme {
+        apps_use_light_theme: personalize(w!("AppsUseLightTheme")) != Some(0),
+        system_uses_light_theme: personalize(w!("SystemUsesLightTheme")) != Some(0),
+        transparency: personalize(w!("EnableTransparency")) != Some(0),
+        accent_color,
+        accent_palette,
+        accent_on_title_bars: dwm(w!("ColorPrevalence")) == Some(1),
+        accent_on_taskbar: personalize(w!("ColorPrevalence")) == Some(1),
+        colorization,
+        high_contrast: high_contrast(),
+    }
+}
+
+#[cfg(windows)]
+fn high_contrast() -> bool {
+    let mut contrast = HIGHCONTRASTW { cbSize: std::mem::size_of::<HIGHCONTRASTW>() as u32, ..Default::default() };
+    let read = unsafe {
+        SystemParametersInfoW(
+            SPI_GETHIGHCONTRAST,
+            contrast.cbSize,
+            Some(&mut contrast as *mut HIGHCONTRASTW as _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+    };
+    read.is_ok() && contrast.dwFlags.0 & HCF_HIGHCONTRASTON.0 != 0
+}
+
+#[cfg(windows)]
+fn hex_color(red: u32, green: u32, blue: u32) -> String {
+    format!("#{:02x}{:02x}{:02x}", red, green, blue)
+}
+
+#[cfg(windows)]
+fn watch_until_stdin_closes() -> ExitCode {
+    let keys = match open_watched_keys() {
+        Ok(keys) => keys,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    // Registry notifications end with the thread that asked for them, so one thread
+    // both arms and waits on them
+    thread::spawn(move || watch(keys));
+    let _ = io::stdin().read_to_end(&mut Vec::new());
+    ExitCode::SUCCESS
+}
+
+// Each key with the event its change notification signals
+#[cfg(windows)]
+fn open_watched_keys() -> Result<Vec<(HKEY, HANDLE)>> {
+    let mut keys = Vec::new();
+    for subkey in [PERSONALIZE_KEY, DWM_KEY, ACCENT_KEY, HIGH_CONTRAST_KEY] {
+        let mut key = HKEY::default();
+        // Accent only exists once an accent color has been picked; DWM writes the
+        // color at the same time, so its key still sees the change
+        if unsafe { RegOpenKeyExW(HKEY_CURRENT_USER, subkey, 0, KEY_NOTIFY, &mut key) }.is_err() {
+            continue;
+        }
+        let event = unsafe { CreateEventW(None, false, false, None)? };
+        keys.push((key, event));
+    }
+    Ok(keys)
+}
+
+#[cfg(windows)]
+fn arm(key: HKEY, event: HANDLE) -> Result<()> {
+    unsafe { RegNotifyChangeKeyValue(key, false, REG_NOTIFY_CHANGE_LAST_SET, event, true) }
+}
+
+#[cfg(windows)]
+fn watch(keys: Vec<(HKEY, HANDLE)>) {
+    for &(key, event) in &keys {
+        if let Err(error) = arm(key, event) {
+            // Nothing would ever be reported, so don't leave the caller waiting
+            tool::exit_with(error);
+        }
+    }
+    // Read after arming, so nothing changes unseen between the two
+    let mut theme = serde_json::to_value(query_theme()).unwrap();
+    let mut stdout = io::stdout().lock();
+    if writeln!(stdout, "{}", json!({ "type": "ready", "theme": theme })).and_then(|_| stdout.flush()).is_err() {
+        return;
+    }
+    let events: Vec<HANDLE> = keys.iter().map(|&(_, event)| event).collect();
+    loop {
+        let woken = unsafe { WaitForMultipleObjects(&events, false, INFINITE) };
+        let Some(&(key, event)) = keys.get(woken.0.wrapping_sub(WAIT_OBJECT_0.0) as usize) else {
+            return;
+        };
+        // Re-armed before reading, so a change made while reading isn't missed
+        let _ = arm(key, event);
+        thread::sleep(SETTLE_TIME);
+        for &(key, event) in &keys {
+            if unsafe { WaitForSingleObject(event, 0) } == WAIT_OBJECT_0 {
+                let _ = arm(key, event);
+            }
+        }
+
+        let current = serde_json::to_value(query_theme()).unwrap();
+        let changes: Vec<&String> = match (&current, &theme) {
+            (Value::Object(current), Value::Object(previous)) => {
+                current.iter().filter(|(field, value)| previous.get(*field) != Some(*value)).map(|(field, _)| field).collect()
+            }
+            _ => Vec::new(),
+        };
+        if changes.is_empty() {
+            // Something else in the same keys changed, such as the wallpaper fit
+            continue;
+        }
+        let line = json!({ "type": "changed", "changes": changes, "theme": current });
+        if writeln!(stdout, "{}", line).and_then(|_| stdout.flush()).is_err() {
+            return;
+        }
+        theme = current;
+    }
+}
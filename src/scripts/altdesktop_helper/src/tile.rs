@@ -0,0 +1,254 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::fs;
+#[cfg(windows)]
+use std::io::{self, Read};
+#[cfg(windows)]
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)]
+use altdesktop_core::error::ErrorReport;
+#[cfg(windows)]
+use altdesktop_core::exit;
+#[cfg(windows)]
+use altdesktop_core::sandbox;
+#[cfg(windows)]
+use create_shortcut::known_folders::expand_known_folder;
+#[cfg(windows)]
+use serde::{Deserialize, Serialize};
+#[cfg(windows)]
+use serde_json::{Map, Value};
+#[cfg(windows)]
+use windows::{core::*, Win32::Foundation::E_INVALIDARG};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper tile export [<tile.json>]
+
+Creates a tile's shortcut and its icon together, reading the tile from the file or
+stdin: {\"shortcut\",\"icon\",\"iconDir\"}. shortcut is a shortcut batch spec without
+icon or iconIndex, and icon an icon batch job without output. The icon is written as
+one .ico holding every size (as icoOut) to <iconDir>\\<shortcut name>.ico, iconDir
+being the shortcut's own folder when it's left out, and the shortcut points at it.
+Either both are written or neither is: a file already at either path is set aside
+until both are, and a failure removes whatever was written, folders included, and
+puts those files back. Prints {\"shortcut\",\"icon\",\"created\",\"replaced\"}: created
+lists every folder and file made, replaced the files that were overwritten. An error
+is the JSON error of the step that failed. Also answered by the tile.export method
+of --serve.";
+
+// Fields of the tile's parts that the export fills in itself
+#[cfg(windows)]
+const SHORTCUT_ICON_FIELDS: &[&str] = &["icon", "iconIndex"];
+#[cfg(windows)]
+const ICON_OUTPUT_FIELDS: &[&str] = &["output", "icoOut", "sharedMemory"];
+
+#[cfg(windows)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Tile {
+    shortcut: Map<String, Value>,
+    icon: Map<String, Value>,
+    icon_dir: Option<String>,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct Exported {
+    shortcut: String,
+    icon: String,
+    created: Vec<String>,
+    replaced: Vec<String>,
+}
+
+/// What an export has changed so far, which is undone if a later step fails.
+#[cfg(windows)]
+#[derive(Default)]
+struct Transaction {
+    /// Folders and files in the order they were made.
+    created: Vec<PathBuf>,
+    /// Each file that was in the way, and where it was moved to.
+    set_aside: Vec<(PathBuf, PathBuf)>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let tile = match tool::string_args(args).and_then(|args| read_tile(&args)) {
+        Ok(tile) => tile,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match export_json(tile) {
+        Ok(exported) => tool::finish(Ok(exported)),
+        Err(report) => {
+            println!("{}", report);
+            ExitCode::from(exit::for_code(report["code"].as_str().unwrap_or_default()))
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("tile")
+}
+
+#[cfg(windows)]
+fn read_tile(args: &[String]) -> std::result::Result<Value, String> {
+    let text = match args {
+        [_] => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map_err(|error| format!("Failed to read stdin: {}", error))?;
+            text
+        }
+        [_, flag] if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+        [_, file] => fs::read_to_string(file).map_err(|error| format!("Could not read {}: {}", file, error))?,
+        _ => return Err("Expected at most one <tile.json>".to_string()),
+    };
+    serde_json::from_str(&text).map_err(|error| format!("Invalid tile JSON: {}", error))
+}
+
+/// Exports the tile in a `tile export` definition and returns what it prints, or the
+/// JSON error of the step that failed once everything is rolled back. Used by
+/// `altdesktop-helper --serve`.
+#[cfg(windows)]
+pub fn export_json(tile: Value) -> std::result::Result<Value, Value> {
+    let tile = serde_json::from_value(tile).map_err(|e| invalid(format!("Invalid tile: {}", e)))?;
+    export(tile).map(|exported| serde_json::to_value(exported).unwrap())
+}
+
+#[cfg(windows)]
+fn export(mut tile: Tile) -> std::result::Result<Exported, Value> {
+    let Some(path) = tile.shortcut.get("path").and_then(Value::as_str) else {
+        return Err(invalid("The shortcut needs a path".to_string()));
+    };
+    if let Some(field) = SHORTCUT_ICON_FIELDS.iter().find(|field| tile.shortcut.contains_key(**field)) {
+        return Err(invalid(format!("The shortcut's icon is the tile's own; leave out its {}", field)));
+    }
+    if let Some(field) = ICON_OUTPUT_FIELDS.iter().find(|field| tile.icon.contains_key(**field)) {
+        return Err(invalid(format!("The icon is written to iconDir; leave out its {}", field)));
+    }
+
+    let shortcut = absolute(path).map_err(report)?;
+    let folder = match &tile.icon_dir {
+        Some(dir) => absolute(dir).map_err(report)?,
+        None => shortcut.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    let Some(name) = shortcut.file_stem() else {
+        return Err(invalid(format!("{} names no file", shortcut.display())));
+    };
+    let icon = folder.join(format!("{}.ico", name.to_string_lossy()));
+    // Both are checked before either is touched, so a path outside the roots fails
+    // with nothing to undo
+    for path in [&shortcut, &icon] {
+        sandbox::check_output(path).map_err(|rejected| report(rejected.into()))?;
+    }
+
+    let icon_text = icon.to_string_lossy().into_owned();
+    tile.icon.insert("output".to_string(), Value::from(icon_text.as_str()));
+    tile.icon.insert("icoOut".to_string(), Value::from(true));
+    tile.shortcut.insert("path".to_string(), Value::from(shortcut.to_string_lossy()));
+    tile.shortcut.insert("icon".to_string(), Value::from(icon_text.as_str()));
+    tile.shortcut.insert("iconIndex".to_string(), Value::from(0));
+
+    let mut transaction = Transaction::default();
+    let result = (|| {
+        transaction.set_aside(&shortcut)?;
+        transaction.set_aside(&icon)?;
+        for folder in [folder.as_path(), shortcut.parent().unwrap_or(folder.as_path())] {
+            transaction.create_folder(folder)?;
+        }
+        transaction.write(&icon, || icon_extractor::extract_json(Value::Object(tile.icon)).map(drop))?;
+        transaction.write(&shortcut, || create_shortcut::create_json(Value::Object(tile.shortcut)).map(drop))
+    })();
+    if let Err(report) = result {
+        transaction.roll_back();
+        return Err(report);
+    }
+    let (created, replaced) = transaction.commit();
+    Ok(Exported { shortcut: shortcut.to_string_lossy().into_owned(), icon: icon_text, created, replaced })
+}
+
+#[cfg(windows)]
+impl Transaction {
+    // Moved to a name of its own in the same folder, which a rename back restores
+    // whole, rather than copied, so the export writes a file of its own
+    fn set_aside(&mut self, path: &Path) -> std::result::Result<(), Value> {
+        if fs::symlink_metadata(path).is_err() {
+            return Ok(());
+        }
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(format!(".{}.bak", std::process::id()));
+        let backup = PathBuf::from(backup);
+        fs::rename(path, &backup).map_err(|error| report(tool::io_error(error)))?;
+        self.set_aside.push((path.to_path_buf(), backup));
+        Ok(())
+    }
+
+    fn create_folder(&mut self, folder: &Path) -> std::result::Result<(), Value> {
+        let missing: Vec<PathBuf> = folder.ancestors().take_while(|folder| !folder.exists()).map(Path::to_path_buf).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        sandbox::check_output(folder).map_err(|rejected| report(rejected.into()))?;
+        fs::create_dir_all(folder).map_err(|error| report(tool::io_error(error)))?;
+        self.created.extend(missing.into_iter().rev());
+        Ok(())
+    }
+
+    // Whatever a failed step left at `path` is its own, since anything that was
+    // there before has been set aside
+    fn write(
+        &mut self,
+        path: &Path,
+        step: impl FnOnce() -> std::result::Result<(), Value>,
+    ) -> std::result::Result<(), Value> {
+        if let Err(report) = step() {
+            let _ = fs::remove_file(path);
+            return Err(report);
+        }
+        self.created.push(path.to_path_buf());
+        Ok(())
+    }
+
+    // Newest first, so each folder is empty by the time it's removed
+    fn roll_back(self) {
+        for path in self.created.iter().rev() {
+            let removed = if path.is_dir() { fs::remove_dir(path) } else { fs::remove_file(path) };
+            if let Err(error) = removed {
+                tracing::warn!(path = %path.display(), "Could not remove what a failed tile export wrote: {}", error);
+            }
+        }
+        for (path, backup) in &self.set_aside {
+            if let Err(error) = fs::rename(backup, path) {
+                tracing::warn!(path = %path.display(), "Could not restore a file a failed tile export set aside: {}", error);
+            }
+        }
+    }
+
+    fn commit(self) -> (Vec<String>, Vec<String>) {
+        for (path, backup) in &self.set_aside {
+            if let Err(error) = fs::remove_file(backup) {
+                tracing::warn!(path = %path.display(), "Could not remove the file a tile export replaced: {}", error);
+            }
+        }
+        let text = |path: &PathBuf| path.to_string_lossy().into_owned();
+        (self.created.iter().map(text).collect(), self.set_aside.iter().map(|(path, _)| text(path)).collect())
+    }
+}
+
+#[cfg(windows)]
+fn absolute(path: &str) -> Result<PathBuf> {
+    std::path::absolute(expand_known_folder(path)?).map_err(tool::io_error)
+}
+
+#[cfg(windows)]
+fn invalid(message: String) -> Value {
+    report(Error::new(E_INVALIDARG, message.into()))
+}
+
+#[cfg(windows)]
+fn report(error: Error) -> Value {
+    serde_json::to_value(ErrorReport::from_error(&error)).unwrap()
+}
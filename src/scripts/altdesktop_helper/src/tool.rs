@@ -0,0 +1,149 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+use altdesktop_core::error::ErrorReport;
+use altdesktop_core::exit;
+use altdesktop_core::json::print;
+use serde::Serialize;
+
+// Plumbing for the tools that live in the helper itself. They always answer in
+// JSON on stdout: the result on success, or the same {"ok":false,"code",...} object
+// `shortcut --json` prints on failure.
+
+pub fn string_args(args: Vec<OsString>) -> Result<Vec<String>, String> {
+    args.into_iter()
+        .map(|arg| arg.into_string().map_err(|arg| format!("Argument is not valid Unicode: {}", arg.to_string_lossy())))
+        .collect()
+}
+
+#[cfg(windows)]
+pub fn finish<T: Serialize>(result: windows::core::Result<T>) -> ExitCode {
+    match result {
+        Ok(value) => {
+            print(value);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            let report = ErrorReport::from_error(&error);
+            let code = report.exit_code();
+            print(report);
+            ExitCode::from(code)
+        }
+    }
+}
+
+#[cfg(windows)]
+pub use altdesktop_core::error::io_error;
+
+/// Prints the error and exits the process with its code, for a watcher whose window
+/// or event couldn't be set up, where nothing would ever be reported.
+#[cfg(windows)]
+pub fn exit_with(error: windows::core::Error) -> ! {
+    let report = ErrorReport::from_error(&error);
+    let code = report.exit_code();
+    print(report);
+    std::process::exit(code.into())
+}
+
+/// Reads a JSON request from each stdin line on its own thread, posting `message`
+/// to `window` whenever one is queued. A closed stdin queues None, the request to stop.
+#[cfg(windows)]
+pub fn read_requests<T: serde::de::DeserializeOwned + Send + 'static>(
+    window: windows::Win32::Foundation::HWND,
+    message: u32,
+) -> std::sync::mpsc::Receiver<Option<Result<T, String>>> {
+    use std::io::BufRead;
+    use windows::Win32::{
+        Foundation::{LPARAM, WPARAM},
+        UI::WindowsAndMessaging::PostMessageW,
+    };
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = serde_json::from_str::<T>(&line).map_err(|error| error.to_string());
+            if sender.send(Some(request)).is_err() {
+                return;
+            }
+            unsafe {
+                let _ = PostMessageW(window, message, WPARAM(0), LPARAM(0));
+            }
+        }
+        let _ = sender.send(None);
+        unsafe {
+            let _ = PostMessageW(window, message, WPARAM(0), LPARAM(0));
+        }
+    });
+    receiver
+}
+
+/// Creates a hidden window of its own class, `class_name`, whose messages go to
+/// `window_proc`. It's top-level rather than message-only, since a message-only window
+/// hears none of the shell's and the system's broadcasts.
+#[cfg(windows)]
+pub fn hidden_window(
+    class_name: windows::core::PCWSTR,
+    window_proc: windows::Win32::UI::WindowsAndMessaging::WNDPROC,
+) -> windows::core::Result<windows::Win32::Foundation::HWND> {
+    use windows::Win32::{System::LibraryLoader::GetModuleHandleW, UI::WindowsAndMessaging::*};
+
+    unsafe {
+        let instance = GetModuleHandleW(None)?;
+        let class = WNDCLASSW { lpfnWndProc: window_proc, hInstance: instance.into(), lpszClassName: class_name, ..Default::default() };
+        if RegisterClassW(&class) == 0 {
+            return Err(windows::core::Error::from_win32());
+        }
+        let window = CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            class_name,
+            None,
+            WS_OVERLAPPED,
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            instance,
+            None,
+        );
+        if window.0 == 0 {
+            return Err(windows::core::Error::from_win32());
+        }
+        Ok(window)
+    }
+}
+
+/// Copies out a string the shell allocated with CoTaskMemAlloc, and frees it.
+#[cfg(windows)]
+pub unsafe fn take_string(value: windows::core::PWSTR) -> String {
+    let string = String::from_utf16_lossy(unsafe { value.as_wide() });
+    unsafe { windows::Win32::System::Com::CoTaskMemFree(Some(value.0 as _)) };
+    string
+}
+
+pub fn usage_error(message: &str, usage: &str) -> ExitCode {
+    print(ErrorReport::usage(message));
+    eprintln!("{}", usage);
+    ExitCode::from(exit::USAGE)
+}
+
+#[cfg(not(windows))]
+pub fn unsupported(tool: &str) -> ExitCode {
+    print(ErrorReport::unsupported(&format!("{} is only supported on Windows", tool)));
+    ExitCode::from(exit::UNSUPPORTED)
+}
+
+/// {"ok":true}, for commands with nothing else to report.
+#[derive(Serialize)]
+pub struct Done {
+    ok: bool,
+}
+
+pub const DONE: Done = Done { ok: true };
@@ -0,0 +1,425 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use std::sync::atomic::{AtomicU32, Ordering};
+#[cfg(windows)]
+use std::sync::mpsc::Receiver;
+
+#[cfg(windows)]
+use altdesktop_core::error::ErrorReport;
+#[cfg(windows)]
+use serde::Deserialize;
+#[cfg(windows)]
+use serde_json::{json, Value};
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{E_INVALIDARG, HWND, LPARAM, LRESULT, WPARAM},
+    Win32::UI::Shell::*,
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper tray show --icon <path> [--tooltip <text>] [--menu <menu.json>]
+
+Shows an icon in the notification area until stdin closes, putting it back whenever
+Explorer restarts. <path> is an .ico file, or an executable or DLL whose first icon
+is used. <menu.json> is the right-click menu: an array of
+{\"id\",\"text\",\"disabled\",\"checked\",\"default\"} items, {\"type\":\"separator\"} and
+{\"text\",\"items\":[...]} submenus.
+The first line is {\"type\":\"ready\"}, then {\"type\":\"click\",\"button\"} with left,
+middle or right (right only without a menu), {\"type\":\"doubleClick\"}, which follows
+a left click, {\"type\":\"selected\",\"id\"} for a menu item and {\"type\":\"restored\"}
+after Explorer restarts. Each stdin line may change any of {\"icon\",\"tooltip\",\"menu\"}
+and answers {\"type\":\"updated\"}. Failures are {\"type\":\"error\",...} with the error
+fields of a failed shortcut --json run.";
+
+#[cfg(windows)]
+const CLASS_NAME: PCWSTR = w!("AltDesktopHelperTray");
+#[cfg(windows)]
+const ICON_ID: u32 = 1;
+// The icon's callback, as the shell sends it and as it is posted back to the loop
+#[cfg(windows)]
+const WM_TRAY: u32 = WM_APP + 1;
+#[cfg(windows)]
+const WM_TRAY_EVENT: u32 = WM_APP + 2;
+#[cfg(windows)]
+const WM_TASKBAR_RESTARTED: u32 = WM_APP + 3;
+#[cfg(windows)]
+const WM_REQUEST: u32 = WM_APP + 4;
+#[cfg(windows)]
+const NIN_KEYSELECT: u32 = NIN_SELECT + 1;
+// The longest tooltip NOTIFYICONDATAW has room for, leaving the terminator
+#[cfg(windows)]
+const TOOLTIP_LENGTH: usize = 127;
+
+// Registered at startup; the message Explorer broadcasts when the taskbar comes back
+#[cfg(windows)]
+static TASKBAR_CREATED: AtomicU32 = AtomicU32::new(0);
+
+#[cfg(windows)]
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct MenuEntry {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    id: Option<String>,
+    text: Option<String>,
+    disabled: bool,
+    checked: bool,
+    default: bool,
+    items: Option<Vec<MenuEntry>>,
+}
+
+#[cfg(windows)]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Update {
+    icon: Option<String>,
+    tooltip: Option<String>,
+    menu: Option<Vec<MenuEntry>>,
+}
+
+#[cfg(windows)]
+struct Options {
+    icon: String,
+    tooltip: String,
+    menu: Vec<MenuEntry>,
+}
+
+#[cfg(windows)]
+struct Tray {
+    window: HWND,
+    icon: HICON,
+    tooltip: String,
+    menu: Vec<MenuEntry>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let options = match tool::string_args(args).and_then(|args| parse_args(&args)) {
+        Ok(options) => options,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    let icon = match load_icon(&options.icon) {
+        Ok(icon) => icon,
+        Err(error) => return tool::finish::<()>(Err(error)),
+    };
+    let window = match create_window() {
+        Ok(window) => window,
+        Err(error) => {
+            unsafe {
+                let _ = DestroyIcon(icon);
+            }
+            return tool::finish::<()>(Err(error));
+        }
+    };
+
+    let mut tray = Tray { window, icon, tooltip: options.tooltip, menu: options.menu };
+    // Without a taskbar yet, the icon appears once Explorer announces one
+    let added = tray.add();
+    emit(json!({ "type": "ready" }));
+    if let Err(error) = added {
+        emit(error_line(&error));
+    }
+
+    let requests = tool::read_requests(window, WM_REQUEST);
+    tray.pump(requests);
+    tray.remove();
+    unsafe {
+        let _ = DestroyIcon(tray.icon);
+        let _ = DestroyWindow(window);
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("tray")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<Options, String> {
+    let mut icon = None;
+    let mut tooltip = String::new();
+    let mut menu = Vec::new();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--icon" => icon = Some(value()?),
+            "--tooltip" => tooltip = value()?,
+            "--menu" => {
+                let path = value()?;
+                let text = std::fs::read_to_string(&path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+                menu = serde_json::from_str(&text).map_err(|error| format!("Invalid menu {}: {}", path, error))?;
+                check_menu(&menu)?;
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    let icon = icon.ok_or_else(|| "Expected --icon".to_string())?;
+    Ok(Options { icon, tooltip, menu })
+}
+
+#[cfg(windows)]
+fn check_menu(entries: &[MenuEntry]) -> std::result::Result<(), String> {
+    for entry in entries {
+        match (entry.kind.as_deref(), &entry.items) {
+            (Some("separator"), _) => {}
+            (Some(other), _) if other != "item" && other != "submenu" => {
+                return Err(format!("Unknown menu entry type: {}", other));
+            }
+            (_, Some(items)) => {
+                if entry.text.is_none() {
+                    return Err("A submenu needs a text".to_string());
+                }
+                check_menu(items)?;
+            }
+            (_, None) => {
+                if entry.id.is_none() || entry.text.is_none() {
+                    return Err("A menu item needs an id and a text".to_string());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn load_icon(path: &str) -> Result<HICON> {
+    let path = create_shortcut::known_folders::expand_known_folder(path)?;
+    let path = std::path::absolute(&path).map_err(tool::io_error)?;
+    std::fs::metadata(&path).map_err(tool::io_error)?;
+    let path = HSTRING::from(path.as_path());
+
+    let is_ico = path.to_string().to_ascii_lowercase().ends_with(".ico");
+    unsafe {
+        if is_ico {
+            let size = GetSystemMetrics(SM_CXSMICON);
+            let icon = LoadImageW(None, &path, IMAGE_ICON, size, size, LR_LOADFROMFILE)?;
+            return Ok(HICON(icon.0));
+        }
+        let mut icon = HICON::default();
+        if ExtractIconExW(&path, 0, None, Some(&mut icon), 1) == 0 || icon.is_invalid() {
+            return Err(Error::new(E_INVALIDARG, format!("{} has no icon", path).into()));
+        }
+        Ok(icon)
+    }
+}
+
+// Listening for TaskbarCreated, which Explorer broadcasts once it has restarted
+#[cfg(windows)]
+fn create_window() -> Result<HWND> {
+    let window = tool::hidden_window(CLASS_NAME, Some(window_proc))?;
+    unsafe {
+        let taskbar_created = RegisterWindowMessageW(w!("TaskbarCreated"));
+        TASKBAR_CREATED.store(taskbar_created, Ordering::Relaxed);
+        // When elevated, Explorer's broadcast would otherwise be filtered out
+        let _ = ChangeWindowMessageFilterEx(window, taskbar_created, MSGFLT_ALLOW, None);
+        Ok(window)
+    }
+}
+
+// The shell sends rather than posts some of these, so they'd never reach the loop
+// that owns the icon; each is posted back to it
+#[cfg(windows)]
+extern "system" fn window_proc(window: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let forward = match message {
+        WM_TRAY => Some(WM_TRAY_EVENT),
+        _ if message != 0 && message == TASKBAR_CREATED.load(Ordering::Relaxed) => Some(WM_TASKBAR_RESTARTED),
+        _ => None,
+    };
+    unsafe {
+        match forward {
+            Some(forward) => {
+                let _ = PostMessageW(window, forward, wparam, lparam);
+                LRESULT(0)
+            }
+            None => DefWindowProcW(window, message, wparam, lparam),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Tray {
+    fn pump(&mut self, requests: Receiver<Option<std::result::Result<Update, String>>>) {
+        let mut message = MSG::default();
+        while unsafe { GetMessageW(&mut message, HWND(0), 0, 0) }.as_bool() {
+            match message.message {
+                WM_TRAY_EVENT => self.handle_event(message.wParam, message.lParam),
+                WM_TASKBAR_RESTARTED => match self.add() {
+                    Ok(()) => emit(json!({ "type": "restored" })),
+                    Err(error) => emit(error_line(&error)),
+                },
+                WM_REQUEST => {
+                    while let Ok(request) = requests.try_recv() {
+                        match request {
+                            Some(request) => self.handle_request(request),
+                            None => return,
+                        }
+                    }
+                }
+                _ => unsafe {
+                    DispatchMessageW(&message);
+                },
+            }
+        }
+    }
+
+    // With NOTIFYICON_VERSION_4 the event is in lParam's low word and the anchor
+    // point in wParam
+    fn handle_event(&self, wparam: WPARAM, lparam: LPARAM) {
+        let x = (wparam.0 & 0xffff) as u16 as i16 as i32;
+        let y = ((wparam.0 >> 16) & 0xffff) as u16 as i16 as i32;
+        match (lparam.0 & 0xffff) as u32 {
+            NIN_SELECT | NIN_KEYSELECT => emit(json!({ "type": "click", "button": "left" })),
+            WM_LBUTTONDBLCLK => emit(json!({ "type": "doubleClick" })),
+            WM_MBUTTONUP => emit(json!({ "type": "click", "button": "middle" })),
+            WM_CONTEXTMENU if self.menu.is_empty() => emit(json!({ "type": "click", "button": "right" })),
+            WM_CONTEXTMENU => match self.show_menu(x, y) {
+                Ok(Some(id)) => emit(json!({ "type": "selected", "id": id })),
+                Ok(None) => {}
+                Err(error) => emit(error_line(&error)),
+            },
+            _ => {}
+        }
+    }
+
+    fn handle_request(&mut self, request: std::result::Result<Update, String>) {
+        let update = match request.and_then(|update| {
+            check_menu(update.menu.as_deref().unwrap_or_default())?;
+            Ok(update)
+        }) {
+            Ok(update) => update,
+            Err(message) => {
+                let mut line = serde_json::to_value(ErrorReport::usage(&format!("Invalid request: {}", message))).unwrap();
+                line["type"] = Value::from("error");
+                emit(line);
+                return;
+            }
+        };
+        if let Some(path) = &update.icon {
+            match load_icon(path) {
+                Ok(icon) => unsafe {
+                    let _ = DestroyIcon(std::mem::replace(&mut self.icon, icon));
+                },
+                Err(error) => return emit(error_line(&error)),
+            }
+        }
+        if let Some(tooltip) = update.tooltip {
+            self.tooltip = tooltip;
+        }
+        if let Some(menu) = update.menu {
+            self.menu = menu;
+        }
+        match self.notify(NIM_MODIFY) {
+            Ok(()) => emit(json!({ "type": "updated" })),
+            Err(error) => emit(error_line(&error)),
+        }
+    }
+
+    fn data(&self) -> NOTIFYICONDATAW {
+        let mut data = NOTIFYICONDATAW {
+            cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+            hWnd: self.window,
+            uID: ICON_ID,
+            uFlags: NIF_MESSAGE | NIF_ICON | NIF_TIP | NIF_SHOWTIP,
+            uCallbackMessage: WM_TRAY,
+            hIcon: self.icon,
+            ..Default::default()
+        };
+        for (slot, unit) in data.szTip.iter_mut().zip(self.tooltip.encode_utf16().take(TOOLTIP_LENGTH)) {
+            *slot = unit;
+        }
+        data.Anonymous.uVersion = NOTIFYICON_VERSION_4;
+        data
+    }
+
+    fn notify(&self, message: NOTIFY_ICON_MESSAGE) -> Result<()> {
+        if unsafe { Shell_NotifyIconW(message, &self.data()) }.as_bool() {
+            Ok(())
+        } else {
+            Err(Error::from_win32())
+        }
+    }
+
+    fn add(&self) -> Result<()> {
+        self.notify(NIM_ADD)?;
+        self.notify(NIM_SETVERSION)
+    }
+
+    fn remove(&self) {
+        let _ = self.notify(NIM_DELETE);
+    }
+
+    fn show_menu(&self, x: i32, y: i32) -> Result<Option<String>> {
+        let mut ids = Vec::new();
+        unsafe {
+            let menu = CreatePopupMenu()?;
+            if let Err(error) = append_entries(menu, &self.menu, &mut ids) {
+                let _ = DestroyMenu(menu);
+                return Err(error);
+            }
+            // Without being foreground, the menu wouldn't close on a click elsewhere
+            let _ = SetForegroundWindow(self.window);
+            let chosen = TrackPopupMenu(menu, TPM_RETURNCMD | TPM_RIGHTBUTTON | TPM_NONOTIFY, x, y, 0, self.window, None);
+            let _ = PostMessageW(self.window, WM_NULL, WPARAM(0), LPARAM(0));
+            let _ = DestroyMenu(menu);
+            Ok(ids.get((chosen.0 as usize).wrapping_sub(1)).cloned())
+        }
+    }
+}
+
+// Item command IDs are positions in `ids` plus one, as 0 is TrackPopupMenu's cancel
+#[cfg(windows)]
+unsafe fn append_entries(menu: HMENU, entries: &[MenuEntry], ids: &mut Vec<String>) -> Result<()> {
+    for entry in entries {
+        if entry.kind.as_deref() == Some("separator") {
+            unsafe { AppendMenuW(menu, MF_SEPARATOR, 0, None)? };
+            continue;
+        }
+        let text = HSTRING::from(entry.text.as_deref().unwrap_or_default());
+        let mut flags = MF_STRING;
+        if entry.disabled {
+            flags |= MF_GRAYED;
+        }
+        if entry.checked {
+            flags |= MF_CHECKED;
+        }
+        match &entry.items {
+            Some(items) => unsafe {
+                let submenu = CreatePopupMenu()?;
+                append_entries(submenu, items, ids)?;
+                AppendMenuW(menu, flags | MF_POPUP, submenu.0 as usize, &text)?;
+            },
+            None => unsafe {
+                ids.push(entry.id.clone().unwrap_or_default());
+                AppendMenuW(menu, flags, ids.len(), &text)?;
+                if entry.default {
+                    SetMenuDefaultItem(menu, ids.len() as u32, 0)?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn error_line(error: &Error) -> Value {
+    let mut line = serde_json::to_value(ErrorReport::from_error(error)).unwrap();
+    line["type"] = Value::from("error");
+    line
+}
+
+// Closing stdin is what stops this tool, so output lost to a closed stdout is ignored
+#[cfg(windows)]
+fn emit(line: Value) {
+    let _ = altdesktop_core::json::emit(line);
+}
@@ -0,0 +1,206 @@
+use std::ffi::c_void;
+
+use windows::{
+    core::*,
+    Win32::Foundation::{E_NOTIMPL, ERROR_NOT_FOUND, HWND},
+    Win32::Graphics::Gdi::HMONITOR,
+    Win32::System::Com::{CoCreateInstance, IServiceProvider, CLSCTX_LOCAL_SERVER},
+    Win32::UI::Shell::Common::IObjectArray,
+};
+
+// Listing and switching desktops, and moving other processes' windows, go through
+// Explorer's IVirtualDesktopManagerInternal, which Task View itself uses. It is
+// undocumented and its IID and layout change between Windows releases, so each
+// known version is asked for in turn and called through its vtable.
+const CLSID_IMMERSIVE_SHELL: GUID = GUID::from_u128(0xc2f03a33_21f5_47fa_b4bb_156362a2f239);
+const SID_VIRTUAL_DESKTOP_MANAGER_INTERNAL: GUID = GUID::from_u128(0xc5e0cdca_7b6e_41b2_9fc4_d93975cc467b);
+// Unchanged since Windows 10; GetViewForHwnd is its fourth method
+const IID_APPLICATION_VIEW_COLLECTION: GUID = GUID::from_u128(0x1841c6d7_4f9d_42c0_af41_8747538f10e5);
+const GET_VIEW_FOR_HWND: usize = 6;
+
+// In every version: MoveViewToDesktop is the second method, GetCurrentDesktop the
+// fourth, and IVirtualDesktop's GetId its second
+const MOVE_VIEW_TO_DESKTOP: usize = 4;
+const GET_CURRENT_DESKTOP: usize = 6;
+const GET_ID: usize = 4;
+// Where the later methods are in the versions without per_monitor
+const GET_DESKTOPS: usize = 7;
+const SWITCH_DESKTOP: usize = 9;
+
+struct Layout {
+    manager: GUID,
+    desktop: GUID,
+    // Windows 11 21H2 and early 22H2 builds kept a set of desktops per display, so
+    // these methods took one and GetAllCurrentDesktops came before GetDesktops
+    per_monitor: bool,
+}
+
+impl Layout {
+    // The slot of a method that comes after GetCurrentDesktop
+    fn slot(&self, index: usize) -> usize {
+        if self.per_monitor { index + 1 } else { index }
+    }
+}
+
+// Newest first
+const LAYOUTS: [Layout; 4] = [
+    // Windows 11 24H2
+    Layout {
+        manager: GUID::from_u128(0x53f5ca0b_158f_4124_900c_057158060b27),
+        desktop: GUID::from_u128(0x3f07f4be_b107_441a_af0f_39d82529072c),
+        per_monitor: false,
+    },
+    // Windows 11 22H2 and 23H2
+    Layout {
+        manager: GUID::from_u128(0xa3175f2d_239c_4bd2_8aa0_eeba8b0b138e),
+        desktop: GUID::from_u128(0x3f07f4be_b107_441a_af0f_39d82529072c),
+        per_monitor: false,
+    },
+    // Windows 11 21H2
+    Layout {
+        manager: GUID::from_u128(0xb2f925b9_5a0f_4d2e_9f4d_2b1507593c10),
+        desktop: GUID::from_u128(0x536d3495_b208_4cc9_ae26_de8111275bf8),
+        per_monitor: true,
+    },
+    // Windows 10 1809 and later
+    Layout {
+        manager: GUID::from_u128(0xf31574d6_b682_4cdc_bd56_1827860abec6),
+        desktop: GUID::from_u128(0xff72ffdd_be7e_43fc_9c03_ad81681e88e4),
+        per_monitor: false,
+    },
+];
+
+type GetDesktop = unsafe extern "system" fn(this: *mut c_void, desktop: *mut *mut c_void) -> HRESULT;
+type GetDesktopOn = unsafe extern "system" fn(this: *mut c_void, monitor: HMONITOR, desktop: *mut *mut c_void) -> HRESULT;
+type SwitchDesktop = unsafe extern "system" fn(this: *mut c_void, desktop: *mut c_void) -> HRESULT;
+type SwitchDesktopOn = unsafe extern "system" fn(this: *mut c_void, monitor: HMONITOR, desktop: *mut c_void) -> HRESULT;
+type MoveViewToDesktop = unsafe extern "system" fn(this: *mut c_void, view: *mut c_void, desktop: *mut c_void) -> HRESULT;
+type GetViewForHwnd = unsafe extern "system" fn(this: *mut c_void, hwnd: HWND, view: *mut *mut c_void) -> HRESULT;
+type GetId = unsafe extern "system" fn(this: *mut c_void, id: *mut GUID) -> HRESULT;
+
+/// Explorer's virtual desktops, through whichever version of its interfaces this
+/// Windows has.
+pub struct Desktops {
+    shell: IServiceProvider,
+    manager: IUnknown,
+    layout: &'static Layout,
+}
+
+impl Desktops {
+    pub fn open() -> Result<Self> {
+        let shell: IServiceProvider = unsafe { CoCreateInstance(&CLSID_IMMERSIVE_SHELL, None, CLSCTX_LOCAL_SERVER)? };
+        for layout in &LAYOUTS {
+            if let Ok(manager) = query_service(&shell, &SID_VIRTUAL_DESKTOP_MANAGER_INTERNAL, &layout.manager) {
+                return Ok(Desktops { shell, manager, layout });
+            }
+        }
+        Err(Error::new(E_NOTIMPL, "This version of Windows' virtual desktop interfaces is not known".into()))
+    }
+
+    /// Every desktop's id, in Task View order.
+    pub fn list(&self) -> Result<Vec<GUID>> {
+        self.desktops()?.iter().map(desktop_id).collect()
+    }
+
+    pub fn current(&self) -> Result<GUID> {
+        let mut desktop = std::ptr::null_mut();
+        unsafe {
+            if self.layout.per_monitor {
+                let get: GetDesktopOn = method(&self.manager, GET_CURRENT_DESKTOP);
+                get(self.manager.as_raw(), HMONITOR(0), &mut desktop).ok()?;
+            } else {
+                let get: GetDesktop = method(&self.manager, GET_CURRENT_DESKTOP);
+                get(self.manager.as_raw(), &mut desktop).ok()?;
+            }
+            desktop_id(&IUnknown::from_raw(desktop))
+        }
+    }
+
+    pub fn switch(&self, id: GUID) -> Result<()> {
+        let desktop = self.find(id)?;
+        unsafe {
+            if self.layout.per_monitor {
+                let switch: SwitchDesktopOn = method(&self.manager, self.layout.slot(SWITCH_DESKTOP));
+                switch(self.manager.as_raw(), HMONITOR(0), desktop.as_raw()).ok()
+            } else {
+                let switch: SwitchDesktop = method(&self.manager, self.layout.slot(SWITCH_DESKTOP));
+                switch(self.manager.as_raw(), desktop.as_raw()).ok()
+            }
+        }
+    }
+
+    /// Moves any process's window; IVirtualDesktopManager only moves this one's.
+    pub fn move_window(&self, hwnd: HWND, id: GUID) -> Result<()> {
+        let desktop = self.find(id)?;
+        let views = query_service(&self.shell, &IID_APPLICATION_VIEW_COLLECTION, &IID_APPLICATION_VIEW_COLLECTION)?;
+        unsafe {
+            let get_view: GetViewForHwnd = method(&views, GET_VIEW_FOR_HWND);
+            let mut view = std::ptr::null_mut();
+            get_view(views.as_raw(), hwnd, &mut view).ok()?;
+            let view = IUnknown::from_raw(view);
+            let move_view: MoveViewToDesktop = method(&self.manager, MOVE_VIEW_TO_DESKTOP);
+            move_view(self.manager.as_raw(), view.as_raw(), desktop.as_raw()).ok()
+        }
+    }
+
+    fn desktops(&self) -> Result<Vec<IUnknown>> {
+        let mut array = std::ptr::null_mut();
+        unsafe {
+            if self.layout.per_monitor {
+                let get: GetDesktopOn = method(&self.manager, self.layout.slot(GET_DESKTOPS));
+                get(self.manager.as_raw(), HMONITOR(0), &mut array).ok()?;
+            } else {
+                let get: GetDesktop = method(&self.manager, self.layout.slot(GET_DESKTOPS));
+                get(self.manager.as_raw(), &mut array).ok()?;
+            }
+            let array = IObjectArray::from_raw(array);
+            let mut desktops = Vec::new();
+            for index in 0..array.GetCount()? {
+                // GetAt's generic form would ask for an IID known at compile time
+                let mut desktop = std::ptr::null_mut();
+                (Interface::vtable(&array).GetAt)(array.as_raw(), index, &self.layout.desktop, &mut desktop).ok()?;
+                desktops.push(IUnknown::from_raw(desktop));
+            }
+            Ok(desktops)
+        }
+    }
+
+    fn find(&self, id: GUID) -> Result<IUnknown> {
+        for desktop in self.desktops()? {
+            if desktop_id(&desktop)? == id {
+                return Ok(desktop);
+            }
+        }
+        Err(Error::new(ERROR_NOT_FOUND.to_hresult(), format!("No virtual desktop {}", format_id(id)).into()))
+    }
+}
+
+/// A desktop id as Windows writes it in the registry, braces included.
+pub fn format_id(id: GUID) -> String {
+    format!("{{{:?}}}", id)
+}
+
+fn desktop_id(desktop: &IUnknown) -> Result<GUID> {
+    let mut id = GUID::zeroed();
+    unsafe {
+        let get_id: GetId = method(desktop, GET_ID);
+        get_id(desktop.as_raw(), &mut id).ok()?;
+    }
+    Ok(id)
+}
+
+fn query_service(shell: &IServiceProvider, service: &GUID, iid: &GUID) -> Result<IUnknown> {
+    let mut object = std::ptr::null_mut();
+    unsafe {
+        (Interface::vtable(shell).QueryService)(shell.as_raw(), service, iid, &mut object).ok()?;
+        Ok(IUnknown::from_raw(object))
+    }
+}
+
+// The function in slot `index` of the object's vtable, counting IUnknown's three
+unsafe fn method<F: Copy>(object: &IUnknown, index: usize) -> F {
+    unsafe {
+        let vtable = *(object.as_raw() as *const *const usize);
+        std::mem::transmute_copy(&*vtable.add(index))
+    }
+}
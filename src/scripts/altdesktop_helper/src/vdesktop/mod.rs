@@ -0,0 +1,168 @@
+#[cfg(windows)]
+mod internal;
+
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{E_INVALIDARG, HWND},
+    Win32::System::Com::{CLSIDFromString, CoCreateInstance, CLSCTX_ALL},
+    Win32::System::Registry::HKEY_CURRENT_USER,
+    Win32::UI::Shell::{IVirtualDesktopManager, VirtualDesktopManager},
+};
+
+#[cfg(windows)]
+use self::internal::{format_id, Desktops};
+#[cfg(windows)]
+use crate::desktop::{format_hwnd, parse_hwnd};
+#[cfg(windows)]
+use crate::registry::read_string;
+#[cfg(windows)]
+use crate::window::check_window;
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper vdesktop list
+  altdesktop-helper vdesktop window <hwnd>
+  altdesktop-helper vdesktop move <hwnd> <desktop>
+  altdesktop-helper vdesktop switch <desktop>
+
+list prints [{\"index\",\"id\",\"name\",\"current\"}] for every virtual desktop in Task
+View order: id is its GUID in braces, which stays the same while desktops are added,
+removed and reordered, and name is null until the user names it, when Windows shows
+\"Desktop <index + 1>\".
+window prints {\"hwnd\",\"desktop\",\"current\"}: the id of the desktop <hwnd> is on, null
+for a window that isn't on one, and whether that is the current desktop.
+move moves <hwnd>, any process's window, to <desktop>; switch makes <desktop> the
+current one, as Task View and Ctrl+Win+arrow do. Both print {\"ok\":true}.
+<hwnd> is decimal or 0x-prefixed hex; <desktop> is an id or an index from list.
+Listing, moving and switching go through Explorer's own undocumented interfaces,
+which change between Windows releases; on one the helper doesn't know they fail
+with E_NOTIMPL, while window keeps working.";
+
+#[cfg(windows)]
+const DESKTOPS_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Explorer\VirtualDesktops\Desktops";
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct VirtualDesktop {
+    index: usize,
+    id: String,
+    name: Option<String>,
+    current: bool,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+struct WindowDesktop {
+    hwnd: String,
+    desktop: Option<String>,
+    current: bool,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args = match tool::string_args(args) {
+        Ok(args) => args,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match args.get(1).map(String::as_str) {
+        Some("--window") => match &args[2..] {
+            [hwnd] => match parse_hwnd(hwnd) {
+                Ok(hwnd) => tool::finish(window_desktop(hwnd)),
+                Err(message) => tool::usage_error(&message, USAGE),
+            },
+            _ => tool::usage_error("Expected <hwnd>", USAGE),
+        },
+        Some("--move") => match &args[2..] {
+            [hwnd, desktop] => match parse_hwnd(hwnd) {
+                Ok(hwnd) => tool::finish(move_window(hwnd, desktop).map(|()| tool::DONE)),
+                Err(message) => tool::usage_error(&message, USAGE),
+            },
+            _ => tool::usage_error("Expected <hwnd> <desktop>", USAGE),
+        },
+        Some("--switch") => match &args[2..] {
+            [desktop] => tool::finish(switch(desktop).map(|()| tool::DONE)),
+            _ => tool::usage_error("Expected <desktop>", USAGE),
+        },
+        None => tool::finish(list_desktops()),
+        Some(_) => tool::usage_error("list takes no arguments", USAGE),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("vdesktop")
+}
+
+#[cfg(windows)]
+fn list_desktops() -> Result<Vec<VirtualDesktop>> {
+    let desktops = Desktops::open()?;
+    let current = desktops.current()?;
+    Ok(desktops
+        .list()?
+        .into_iter()
+        .enumerate()
+        .map(|(index, id)| {
+            let id_string = format_id(id);
+            VirtualDesktop {
+                index,
+                // Explorer keeps the names in the registry, under every version
+                name: read_string(HKEY_CURRENT_USER, &HSTRING::from(format!(r"{}\{}", DESKTOPS_KEY, id_string)), w!("Name"))
+                    .filter(|name| !name.is_empty()),
+                id: id_string,
+                current: id == current,
+            }
+        })
+        .collect())
+}
+
+// Through the documented interface, which answers for any window on any version
+#[cfg(windows)]
+fn window_desktop(hwnd: HWND) -> Result<WindowDesktop> {
+    check_window(hwnd)?;
+    let manager: IVirtualDesktopManager = unsafe { CoCreateInstance(&VirtualDesktopManager, None, CLSCTX_ALL)? };
+    let desktop = unsafe { manager.GetWindowDesktopId(hwnd)? };
+    Ok(WindowDesktop {
+        hwnd: format_hwnd(hwnd),
+        // A zero id for windows such as the taskbar, which are on none in particular
+        desktop: (desktop != GUID::zeroed()).then(|| format_id(desktop)),
+        current: unsafe { manager.IsWindowOnCurrentVirtualDesktop(hwnd)? }.as_bool(),
+    })
+}
+
+#[cfg(windows)]
+fn move_window(hwnd: HWND, desktop: &str) -> Result<()> {
+    check_window(hwnd)?;
+    let desktops = Desktops::open()?;
+    let id = resolve(&desktops, desktop)?;
+    desktops.move_window(hwnd, id)
+}
+
+#[cfg(windows)]
+fn switch(desktop: &str) -> Result<()> {
+    let desktops = Desktops::open()?;
+    let id = resolve(&desktops, desktop)?;
+    desktops.switch(id)
+}
+
+// An id in braces, or an index into list's order
+#[cfg(windows)]
+fn resolve(desktops: &Desktops, desktop: &str) -> Result<GUID> {
+    if desktop.starts_with('{') {
+        return unsafe { CLSIDFromString(&HSTRING::from(desktop)) }
+            .map_err(|error| Error::new(error.code(), format!("Invalid desktop id: {}", desktop).into()));
+    }
+    let ids = desktops.list()?;
+    match desktop.parse::<usize>() {
+        Ok(index) if index < ids.len() => Ok(ids[index]),
+        _ => Err(Error::new(
+            E_INVALIDARG,
+            format!("Invalid desktop: {} (expected an id or an index below {})", desktop, ids.len()).into(),
+        )),
+    }
+}
@@ -0,0 +1,293 @@
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{COLORREF, E_INVALIDARG},
+    Win32::System::Com::{CoCreateInstance, CLSCTX_LOCAL_SERVER},
+    Win32::UI::Shell::*,
+};
+
+#[cfg(windows)]
+use serde::Serialize;
+
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper wallpaper set <imagePath> [--monitor <index|devicePath>] [--fit <mode>]
+  altdesktop-helper wallpaper color <#rrggbb>
+  altdesktop-helper wallpaper slideshow <folder> [--interval <seconds>] [--shuffle|--no-shuffle]
+  altdesktop-helper wallpaper next|previous [--monitor <index|devicePath>]
+  altdesktop-helper wallpaper current
+
+set applies the image to every monitor unless --monitor picks one, by its index
+among the wallpaper monitors or its device path. --fit is one of fill, fit, stretch,
+tile, center or span, and applies to every monitor: Windows has a single fit mode.
+color sets the solid background color and turns the wallpaper image off; the next
+set turns it back on.
+slideshow cycles every monitor through the images in <folder>, keeping the current
+interval and shuffle unless given. next and previous advance it.
+current prints the fit, background color, slideshow settings and each monitor's
+image as JSON.";
+
+#[cfg(windows)]
+const FITS: &[(&str, DESKTOP_WALLPAPER_POSITION)] = &[
+    ("fill", DWPOS_FILL),
+    ("fit", DWPOS_FIT),
+    ("stretch", DWPOS_STRETCH),
+    ("tile", DWPOS_TILE),
+    ("center", DWPOS_CENTER),
+    ("span", DWPOS_SPAN),
+];
+
+#[cfg(windows)]
+enum Command {
+    Set { image_path: String, monitor: Option<String>, fit: Option<DESKTOP_WALLPAPER_POSITION> },
+    Color { color: COLORREF },
+    Slideshow { folder: String, interval: Option<u32>, shuffle: Option<bool> },
+    Advance { monitor: Option<String>, direction: DESKTOP_SLIDESHOW_DIRECTION },
+    Current,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WallpaperState {
+    /// False while a solid color is shown instead of an image.
+    enabled: bool,
+    fit: Option<&'static str>,
+    background_color: String,
+    /// Present while a slideshow is running.
+    slideshow: Option<Slideshow>,
+    monitors: Vec<MonitorWallpaper>,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Slideshow {
+    folder: Option<String>,
+    interval_seconds: u32,
+    shuffle: bool,
+}
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MonitorWallpaper {
+    index: u32,
+    device_path: String,
+    /// The image on screen, which during a slideshow is the current slide.
+    wallpaper: Option<String>,
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let command = match tool::string_args(args).and_then(|args| parse_args(&args)) {
+        Ok(command) => command,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    tool::finish(execute(command))
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("wallpaper")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<Command, String> {
+    let mut mode = None;
+    let mut color = None;
+    let mut monitor = None;
+    let mut fit = None;
+    let mut interval = None;
+    let mut shuffle = None;
+    let mut positional = Vec::new();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--color" | "--slideshow" | "--next" | "--previous" | "--current" => {
+                if let Some(previous) = mode.replace(flag) {
+                    return Err(format!("{} cannot be combined with {}", flag, previous));
+                }
+                if flag == "--color" {
+                    color = Some(parse_color(&value()?)?);
+                }
+            }
+            "--monitor" => monitor = Some(value()?),
+            "--fit" => fit = Some(parse_fit(&value()?)?),
+            "--interval" => {
+                let value = value()?;
+                let seconds = value.parse::<u32>().ok().filter(|&seconds| seconds > 0);
+                interval = Some(seconds.ok_or_else(|| format!("Invalid --interval: {}", value))?);
+            }
+            "--shuffle" => shuffle = Some(true),
+            "--no-shuffle" => shuffle = Some(false),
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let only_monitor = fit.is_none() && interval.is_none() && shuffle.is_none();
+    match (mode, positional.as_slice()) {
+        (None, [image_path]) if interval.is_none() && shuffle.is_none() => {
+            Ok(Command::Set { image_path: image_path.clone(), monitor, fit })
+        }
+        (None, _) => Err("Expected <imagePath>".to_string()),
+        (Some("--color"), []) if only_monitor && monitor.is_none() => Ok(Command::Color { color: color.unwrap() }),
+        (Some("--slideshow"), [folder]) if monitor.is_none() && fit.is_none() => {
+            Ok(Command::Slideshow { folder: folder.clone(), interval, shuffle })
+        }
+        (Some("--slideshow"), _) => Err("Expected <folder>".to_string()),
+        (Some("--next"), []) if only_monitor => Ok(Command::Advance { monitor, direction: DSD_FORWARD }),
+        (Some("--previous"), []) if only_monitor => Ok(Command::Advance { monitor, direction: DSD_BACKWARD }),
+        (Some("--current"), []) if only_monitor && monitor.is_none() => Ok(Command::Current),
+        (Some(flag), _) => Err(format!("Unexpected arguments for {}", flag.trim_start_matches("--"))),
+    }
+}
+
+#[cfg(windows)]
+fn parse_fit(value: &str) -> std::result::Result<DESKTOP_WALLPAPER_POSITION, String> {
+    FITS.iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, fit)| *fit)
+        .ok_or_else(|| format!("Unknown fit mode: {} (expected fill, fit, stretch, tile, center or span)", value))
+}
+
+#[cfg(windows)]
+fn parse_color(value: &str) -> std::result::Result<COLORREF, String> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    let rgb = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6)
+        .ok_or_else(|| format!("Invalid color: {} (expected #rrggbb)", value))?;
+    // COLORREF is 0x00bbggrr
+    let (r, g, b) = (rgb >> 16, (rgb >> 8) & 0xff, rgb & 0xff);
+    Ok(COLORREF(b << 16 | g << 8 | r))
+}
+
+#[cfg(windows)]
+fn execute(command: Command) -> Result<serde_json::Value> {
+    let wallpaper: IDesktopWallpaper = unsafe { CoCreateInstance(&DesktopWallpaper, None, CLSCTX_LOCAL_SERVER)? };
+    match command {
+        Command::Set { image_path, monitor, fit } => {
+            // Relative paths would be resolved against Explorer's directory, not ours
+            let image_path = std::path::absolute(&image_path).map_err(tool::io_error)?;
+            std::fs::metadata(&image_path).map_err(tool::io_error)?;
+            let monitor_id = monitor.map(|monitor| monitor_id(&wallpaper, &monitor)).transpose()?;
+            unsafe {
+                wallpaper.Enable(true)?;
+                if let Some(fit) = fit {
+                    wallpaper.SetPosition(fit)?;
+                }
+                match &monitor_id {
+                    Some(monitor_id) => wallpaper.SetWallpaper(monitor_id, &HSTRING::from(image_path.as_path()))?,
+                    None => wallpaper.SetWallpaper(PCWSTR::null(), &HSTRING::from(image_path.as_path()))?,
+                }
+            }
+        }
+        Command::Color { color } => unsafe {
+            wallpaper.SetBackgroundColor(color)?;
+            wallpaper.Enable(false)?;
+        },
+        Command::Slideshow { folder, interval, shuffle } => {
+            let folder = std::path::absolute(&folder).map_err(tool::io_error)?;
+            unsafe {
+                let item: IShellItem = SHCreateItemFromParsingName(&HSTRING::from(folder.as_path()), None)?;
+                let items: IShellItemArray = SHCreateShellItemArrayFromShellItem(&item)?;
+
+                let (mut options, mut tick) = (DESKTOP_SLIDESHOW_OPTIONS(0), 0);
+                wallpaper.GetSlideshowOptions(&mut options, &mut tick)?;
+                if let Some(shuffle) = shuffle {
+                    options = if shuffle { DSO_SHUFFLEIMAGES } else { DESKTOP_SLIDESHOW_OPTIONS(0) };
+                }
+                if let Some(interval) = interval {
+                    tick = interval.saturating_mul(1000);
+                }
+
+                wallpaper.Enable(true)?;
+                wallpaper.SetSlideshow(&items)?;
+                wallpaper.SetSlideshowOptions(options, tick)?;
+            }
+        }
+        Command::Advance { monitor, direction } => {
+            let monitor_id = monitor.map(|monitor| monitor_id(&wallpaper, &monitor)).transpose()?;
+            unsafe {
+                match &monitor_id {
+                    Some(monitor_id) => wallpaper.AdvanceSlideshow(monitor_id, direction)?,
+                    None => wallpaper.AdvanceSlideshow(PCWSTR::null(), direction)?,
+                }
+            }
+        }
+        Command::Current => return Ok(serde_json::to_value(current_state(&wallpaper)?).unwrap()),
+    }
+    Ok(serde_json::to_value(tool::DONE).unwrap())
+}
+
+#[cfg(windows)]
+fn current_state(wallpaper: &IDesktopWallpaper) -> Result<WallpaperState> {
+    unsafe {
+        let status = wallpaper.GetStatus()?;
+        let position = wallpaper.GetPosition()?;
+        let COLORREF(color) = wallpaper.GetBackgroundColor()?;
+
+        let slideshow = if status.0 & DSS_SLIDESHOW.0 != 0 {
+            let (mut options, mut tick) = (DESKTOP_SLIDESHOW_OPTIONS(0), 0);
+            wallpaper.GetSlideshowOptions(&mut options, &mut tick)?;
+            // The slideshow keeps the folder it was given as its only item
+            let folder = wallpaper
+                .GetSlideshow()
+                .and_then(|items| items.GetItemAt(0))
+                .and_then(|item| item.GetDisplayName(SIGDN_FILESYSPATH))
+                .ok()
+                .map(|path| tool::take_string(path));
+            Some(Slideshow { folder, interval_seconds: tick / 1000, shuffle: options.0 & DSO_SHUFFLEIMAGES.0 != 0 })
+        } else {
+            None
+        };
+
+        let monitors = monitor_ids(wallpaper)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, id)| MonitorWallpaper {
+                index: index as u32,
+                wallpaper: wallpaper.GetWallpaper(&id).ok().map(|path| tool::take_string(path)).filter(|path| !path.is_empty()),
+                device_path: id.to_string_lossy(),
+            })
+            .collect();
+
+        Ok(WallpaperState {
+            enabled: status.0 & DSS_ENABLED.0 != 0,
+            fit: FITS.iter().find(|(_, fit)| *fit == position).map(|(name, _)| *name),
+            background_color: format!("#{:02x}{:02x}{:02x}", color & 0xff, (color >> 8) & 0xff, (color >> 16) & 0xff),
+            slideshow,
+            monitors,
+        })
+    }
+}
+
+#[cfg(windows)]
+fn monitor_ids(wallpaper: &IDesktopWallpaper) -> Result<Vec<HSTRING>> {
+    let count = unsafe { wallpaper.GetMonitorDevicePathCount()? };
+    (0..count)
+        .map(|index| unsafe { wallpaper.GetMonitorDevicePathAt(index).map(|path| HSTRING::from(tool::take_string(path))) })
+        .collect()
+}
+
+/// Resolves `--monitor` to the device path IDesktopWallpaper identifies monitors by.
+#[cfg(windows)]
+fn monitor_id(wallpaper: &IDesktopWallpaper, monitor: &str) -> Result<HSTRING> {
+    let paths = monitor_ids(wallpaper)?;
+    let found = match monitor.parse::<usize>() {
+        Ok(index) => paths.get(index),
+        Err(_) => paths.iter().find(|path| path.to_string_lossy().eq_ignore_ascii_case(monitor)),
+    };
+    found.cloned().ok_or_else(|| {
+        Error::new(E_INVALIDARG, format!("No monitor {} (there are {})", monitor, paths.len()).into())
+    })
+}
@@ -0,0 +1,191 @@
+use std::ffi::c_void;
+use std::str::FromStr;
+
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Foundation::{BOOL, E_NOTIMPL, HWND},
+    Win32::Graphics::Dwm::*,
+    Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress},
+    Win32::System::Registry::HKEY_LOCAL_MACHINE,
+    Win32::UI::Controls::MARGINS,
+};
+
+use crate::registry::read_string;
+
+const CURRENT_VERSION_KEY: &HSTRING = h!("SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion");
+// Windows 11 22H2, which added DWMWA_SYSTEMBACKDROP_TYPE
+const SYSTEM_BACKDROP_BUILD: u32 = 22621;
+// Windows 11 21H2, whose only backdrop was mica through an undocumented attribute
+const MICA_ATTRIBUTE_BUILD: u32 = 22000;
+const DWMWA_MICA_EFFECT: DWMWINDOWATTRIBUTE = DWMWINDOWATTRIBUTE(1029);
+// Windows 10 1803, the first with acrylic through the accent policy
+const ACRYLIC_BUILD: u32 = 17134;
+// Before Windows 10 20H1 the dark mode attribute had the number 19
+const DARK_MODE_BUILD: u32 = 18985;
+const DWMWA_USE_IMMERSIVE_DARK_MODE_BEFORE_20H1: DWMWINDOWATTRIBUTE = DWMWINDOWATTRIBUTE(19);
+// #00000033, in the accent policy's ABGR order
+const DEFAULT_TINT: u32 = 0x3300_0000;
+
+// SetWindowCompositionAttribute is what Windows 10's own translucent surfaces use.
+// It is undocumented, so it is looked up in user32 rather than linked.
+const WCA_ACCENT_POLICY: u32 = 19;
+const ACCENT_DISABLED: u32 = 0;
+const ACCENT_ENABLE_BLURBEHIND: u32 = 3;
+const ACCENT_ENABLE_ACRYLICBLURBEHIND: u32 = 4;
+// Draws the gradient color over the blur
+const ACCENT_FLAG_DRAW_TINT: u32 = 2;
+
+#[repr(C)]
+struct AccentPolicy {
+    accent_state: u32,
+    accent_flags: u32,
+    gradient_color: u32,
+    animation_id: u32,
+}
+
+#[repr(C)]
+struct CompositionAttributeData {
+    attribute: u32,
+    data: *mut c_void,
+    size: usize,
+}
+
+type SetWindowCompositionAttribute = unsafe extern "system" fn(HWND, *mut CompositionAttributeData) -> BOOL;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Backdrop {
+    Mica,
+    Tabbed,
+    Acrylic,
+    Blur,
+    None,
+}
+
+impl FromStr for Backdrop {
+    type Err = ();
+
+    fn from_str(value: &str) -> std::result::Result<Self, ()> {
+        match value {
+            "mica" => Ok(Backdrop::Mica),
+            "tabbed" => Ok(Backdrop::Tabbed),
+            "acrylic" => Ok(Backdrop::Acrylic),
+            "blur" => Ok(Backdrop::Blur),
+            "none" => Ok(Backdrop::None),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Backdrop {
+    fn name(self) -> &'static str {
+        match self {
+            Backdrop::Mica => "mica",
+            Backdrop::Tabbed => "tabbed",
+            Backdrop::Acrylic => "acrylic",
+            Backdrop::Blur => "blur",
+            Backdrop::None => "none",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Applied {
+    backdrop: &'static str,
+    method: &'static str,
+}
+
+/// `#rrggbbaa`, or `#rrggbb` for an opaque color, in the accent policy's ABGR order.
+pub fn parse_tint(value: &str) -> Option<u32> {
+    let hex = value.strip_prefix('#')?;
+    let rgba = match hex.len() {
+        6 => u32::from_str_radix(hex, 16).ok()? << 8 | 0xff,
+        8 => u32::from_str_radix(hex, 16).ok()?,
+        _ => return None,
+    };
+    Some(rgba.swap_bytes())
+}
+
+/// Applies the closest backdrop to `kind` that this build of Windows has. The others
+/// are cleared, so switching from one method to another doesn't leave both applied.
+pub fn apply(hwnd: HWND, kind: Backdrop, dark: Option<bool>, tint: Option<u32>) -> Result<Applied> {
+    let build = windows_build();
+    if let Some(dark) = dark {
+        let attribute =
+            if build >= DARK_MODE_BUILD { DWMWA_USE_IMMERSIVE_DARK_MODE } else { DWMWA_USE_IMMERSIVE_DARK_MODE_BEFORE_20H1 };
+        set_attribute(hwnd, attribute, BOOL::from(dark).0)?;
+    }
+
+    let (applied, method) = match kind {
+        Backdrop::Mica | Backdrop::Tabbed | Backdrop::Acrylic if build >= SYSTEM_BACKDROP_BUILD => {
+            (kind, "systemBackdrop")
+        }
+        Backdrop::Mica | Backdrop::Tabbed if build >= MICA_ATTRIBUTE_BUILD => (Backdrop::Mica, "micaAttribute"),
+        Backdrop::Mica | Backdrop::Tabbed | Backdrop::Acrylic if build >= ACRYLIC_BUILD => {
+            (Backdrop::Acrylic, "accentPolicy")
+        }
+        Backdrop::None => (Backdrop::None, "none"),
+        _ => (Backdrop::Blur, "accentPolicy"),
+    };
+
+    // Backdrops only show through the part of the window DWM draws; extending the
+    // frame hands it the whole client area
+    let margin = if method == "systemBackdrop" || method == "micaAttribute" { -1 } else { 0 };
+    let margins = MARGINS { cxLeftWidth: margin, cxRightWidth: margin, cyTopHeight: margin, cyBottomHeight: margin };
+    unsafe { DwmExtendFrameIntoClientArea(hwnd, &margins)? };
+
+    if build >= SYSTEM_BACKDROP_BUILD {
+        let backdrop = match (method, applied) {
+            ("systemBackdrop", Backdrop::Mica) => DWMSBT_MAINWINDOW,
+            ("systemBackdrop", Backdrop::Tabbed) => DWMSBT_TABBEDWINDOW,
+            ("systemBackdrop", _) => DWMSBT_TRANSIENTWINDOW,
+            _ => DWMSBT_NONE,
+        };
+        set_attribute(hwnd, DWMWA_SYSTEMBACKDROP_TYPE, backdrop.0)?;
+    } else if build >= MICA_ATTRIBUTE_BUILD {
+        set_attribute(hwnd, DWMWA_MICA_EFFECT, BOOL::from(method == "micaAttribute").0)?;
+    }
+    let accent = match (method, applied) {
+        ("accentPolicy", Backdrop::Acrylic) => ACCENT_ENABLE_ACRYLICBLURBEHIND,
+        ("accentPolicy", _) => ACCENT_ENABLE_BLURBEHIND,
+        _ => ACCENT_DISABLED,
+    };
+    // Nothing to clear where the accent policy is missing, only nothing to apply
+    match set_accent(hwnd, accent, tint.unwrap_or(DEFAULT_TINT)) {
+        Err(error) if accent == ACCENT_DISABLED && error.code() == E_NOTIMPL => {}
+        result => result?,
+    }
+    Ok(Applied { backdrop: applied.name(), method })
+}
+
+// GetVersionEx reports Windows 8 to processes without a compatibility manifest
+fn windows_build() -> u32 {
+    read_string(HKEY_LOCAL_MACHINE, CURRENT_VERSION_KEY, w!("CurrentBuildNumber"))
+        .and_then(|build| build.parse().ok())
+        .unwrap_or(0)
+}
+
+fn set_attribute(hwnd: HWND, attribute: DWMWINDOWATTRIBUTE, value: i32) -> Result<()> {
+    unsafe { DwmSetWindowAttribute(hwnd, attribute, &value as *const i32 as _, std::mem::size_of::<i32>() as u32) }
+}
+
+fn set_accent(hwnd: HWND, state: u32, tint: u32) -> Result<()> {
+    let set_window_composition_attribute: SetWindowCompositionAttribute = unsafe {
+        let user32 = GetModuleHandleW(w!("user32.dll"))?;
+        let Some(address) = GetProcAddress(user32, s!("SetWindowCompositionAttribute")) else {
+            return Err(Error::new(E_NOTIMPL, "This version of Windows has no accent policy".into()));
+        };
+        std::mem::transmute(address)
+    };
+    let flags = if state == ACCENT_DISABLED { 0 } else { ACCENT_FLAG_DRAW_TINT };
+    let mut policy = AccentPolicy { accent_state: state, accent_flags: flags, gradient_color: tint, animation_id: 0 };
+    let mut data = CompositionAttributeData {
+        attribute: WCA_ACCENT_POLICY,
+        data: &mut policy as *mut AccentPolicy as _,
+        size: std::mem::size_of::<AccentPolicy>(),
+    };
+    if !unsafe { set_window_composition_attribute(hwnd, &mut data) }.as_bool() {
+        return Err(Error::from_win32());
+    }
+    Ok(())
+}
@@ -0,0 +1,379 @@
+#[cfg(windows)]
+mod backdrop;
+
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use serde::Serialize;
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::{BOOL, CloseHandle, E_ACCESSDENIED, ERROR_INVALID_WINDOW_HANDLE, HWND, LPARAM, RECT, WPARAM},
+    Win32::Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED, DWMWA_EXTENDED_FRAME_BOUNDS},
+    Win32::Graphics::Gdi::{GetMonitorInfoW, MonitorFromWindow, MONITORINFO, MONITORINFOEXW, MONITOR_DEFAULTTONEAREST},
+    Win32::System::Threading::{
+        AttachThreadInput, GetCurrentThreadId, OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    },
+    Win32::UI::WindowsAndMessaging::*,
+};
+
+#[cfg(windows)]
+use self::backdrop::Backdrop;
+#[cfg(windows)]
+use crate::desktop::{format_hwnd, parse_hwnd};
+#[cfg(windows)]
+use crate::monitors::Rect;
+use crate::tool;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper windows list [--all]
+  altdesktop-helper windows focus <hwnd>
+  altdesktop-helper windows minimize <hwnd>
+  altdesktop-helper windows maximize <hwnd>
+  altdesktop-helper windows restore <hwnd>
+  altdesktop-helper windows move <hwnd> --x <x> --y <y> [--width <width> --height <height>]
+  altdesktop-helper windows close <hwnd>
+  altdesktop-helper windows backdrop <hwnd> mica|tabbed|acrylic|blur|none [--theme dark|light] [--tint <color>]
+
+list prints the windows Alt+Tab shows, frontmost first, or every top-level window
+with --all, as {\"hwnd\",\"title\",\"class\",\"pid\",\"path\",\"bounds\",\"monitor\",\"minimized\",
+\"maximized\",\"foreground\"}: path is the owning process's executable, or null when
+it can't be read, bounds are the visible frame in physical pixels on the virtual
+screen, and monitor is the device name of the display most of it is on, as
+monitors list prints it.
+focus restores <hwnd> if it is minimized and brings it to the front. move places its
+visible frame at <x>,<y>, resizing it with --width and --height, restoring it first
+if it is minimized or maximized. close asks it to close, as its close button would,
+without waiting. Each of these prints {\"ok\":true}.
+backdrop gives <hwnd> a translucent system backdrop behind its whole client area,
+which shows wherever the window paints transparent pixels. Where this version of
+Windows lacks the one asked for it falls back, mica and tabbed to acrylic and acrylic
+to blur, and prints {\"backdrop\",\"method\"} with the one applied and how:
+systemBackdrop on Windows 11 22H2 and later, micaAttribute on the first Windows 11
+release, accentPolicy on Windows 10. --theme picks the dark or light title bar and
+mica tint; --tint is the #rrggbbaa color the accent policy lays over its acrylic
+and blur, #00000033 when not given.
+<hwnd> is decimal or 0x-prefixed hex.";
+
+#[cfg(windows)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Window {
+    hwnd: String,
+    title: String,
+    class: String,
+    pid: u32,
+    path: Option<String>,
+    bounds: Rect,
+    monitor: Option<String>,
+    minimized: bool,
+    maximized: bool,
+    foreground: bool,
+}
+
+#[cfg(windows)]
+enum Command {
+    List { all: bool },
+    Focus(HWND),
+    Show(HWND, SHOW_WINDOW_CMD),
+    Move { hwnd: HWND, x: i32, y: i32, size: Option<(i32, i32)> },
+    Close(HWND),
+    Backdrop { hwnd: HWND, kind: Backdrop, dark: Option<bool>, tint: Option<u32> },
+}
+
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let command = match tool::string_args(args).and_then(|args| parse_args(&args)) {
+        Ok(command) => command,
+        Err(message) => return tool::usage_error(&message, USAGE),
+    };
+    match command {
+        Command::List { all } => tool::finish(list_windows(all)),
+        Command::Focus(hwnd) => tool::finish(focus(hwnd).map(|()| tool::DONE)),
+        Command::Show(hwnd, show) => tool::finish(check_window(hwnd).map(|()| show_window(hwnd, show)).map(|()| tool::DONE)),
+        Command::Move { hwnd, x, y, size } => tool::finish(move_window(hwnd, x, y, size).map(|()| tool::DONE)),
+        Command::Close(hwnd) => tool::finish(close(hwnd).map(|()| tool::DONE)),
+        Command::Backdrop { hwnd, kind, dark, tint } => {
+            tool::finish(check_window(hwnd).and_then(|()| backdrop::apply(hwnd, kind, dark, tint)))
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run(_args: Vec<OsString>) -> ExitCode {
+    tool::unsupported("windows")
+}
+
+#[cfg(windows)]
+fn parse_args(args: &[String]) -> std::result::Result<Command, String> {
+    let command = args.get(1).map(String::as_str);
+    if command.is_none() || command == Some("--all") {
+        return match &args[1..] {
+            [] => Ok(Command::List { all: false }),
+            [_] => Ok(Command::List { all: true }),
+            _ => Err("list takes only --all".to_string()),
+        };
+    }
+    let command = command.unwrap();
+    let Some(hwnd) = args.get(2) else {
+        return Err("Expected <hwnd>".to_string());
+    };
+    let hwnd = parse_hwnd(hwnd)?;
+
+    let rest = &args[3..];
+    match command {
+        "--move" => parse_move(hwnd, rest),
+        "--backdrop" => parse_backdrop(hwnd, rest),
+        _ if !rest.is_empty() => Err(format!("Unexpected argument: {}", rest[0])),
+        "--focus" => Ok(Command::Focus(hwnd)),
+        "--minimize" => Ok(Command::Show(hwnd, SW_MINIMIZE)),
+        "--maximize" => Ok(Command::Show(hwnd, SW_MAXIMIZE)),
+        "--restore" => Ok(Command::Show(hwnd, SW_RESTORE)),
+        _ => Ok(Command::Close(hwnd)),
+    }
+}
+
+#[cfg(windows)]
+fn parse_move(hwnd: HWND, args: &[String]) -> std::result::Result<Command, String> {
+    let mut x = None;
+    let mut y = None;
+    let mut width = None;
+    let mut height = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || {
+            let value = iter.next().ok_or_else(|| format!("{} requires a value", flag))?;
+            value.parse::<i32>().map_err(|_| format!("Invalid {}: {}", flag, value))
+        };
+        match flag {
+            "--x" => x = Some(value()?),
+            "--y" => y = Some(value()?),
+            "--width" => width = Some(value()?),
+            "--height" => height = Some(value()?),
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    let (Some(x), Some(y)) = (x, y) else {
+        return Err("move requires --x and --y".to_string());
+    };
+    let size = match (width, height) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => Some((width, height)),
+        (None, None) => None,
+        (Some(_), Some(_)) => return Err("--width and --height must be positive".to_string()),
+        _ => return Err("--width and --height go together".to_string()),
+    };
+    Ok(Command::Move { hwnd, x, y, size })
+}
+
+#[cfg(windows)]
+fn parse_backdrop(hwnd: HWND, args: &[String]) -> std::result::Result<Command, String> {
+    let mut kind = None;
+    let mut dark = None;
+    let mut tint = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} requires a value", flag));
+        match flag {
+            "--theme" => {
+                dark = match value()?.as_str() {
+                    "dark" => Some(true),
+                    "light" => Some(false),
+                    theme => return Err(format!("Invalid --theme: {} (expected dark or light)", theme)),
+                }
+            }
+            "--tint" => {
+                let color = value()?;
+                tint = Some(backdrop::parse_tint(&color).ok_or_else(|| format!("Invalid --tint: {} (expected #rrggbbaa)", color))?);
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ if kind.is_none() => {
+                kind = Some(arg.parse::<Backdrop>().map_err(|()| {
+                    format!("Invalid backdrop: {} (expected mica, tabbed, acrylic, blur or none)", arg)
+                })?)
+            }
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    let kind = kind.ok_or_else(|| "Expected mica, tabbed, acrylic, blur or none".to_string())?;
+    Ok(Command::Backdrop { hwnd, kind, dark, tint })
+}
+
+#[cfg(windows)]
+fn list_windows(all: bool) -> Result<Vec<Window>> {
+    let mut handles: Vec<HWND> = Vec::new();
+    unsafe { EnumWindows(Some(collect_window), LPARAM(&mut handles as *mut _ as isize))? };
+
+    let foreground = unsafe { GetForegroundWindow() };
+    Ok(handles
+        .into_iter()
+        .filter(|&hwnd| all || shown_in_alt_tab(hwnd))
+        .map(|hwnd| describe(hwnd, foreground))
+        .collect())
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn collect_window(hwnd: HWND, handles: LPARAM) -> BOOL {
+    unsafe { (*(handles.0 as *mut Vec<HWND>)).push(hwnd) };
+    true.into()
+}
+
+// Visible, not cloaked on another virtual desktop or as a suspended UWP app, and
+// either unowned or asking for a taskbar button, as Explorer decides
+#[cfg(windows)]
+fn shown_in_alt_tab(hwnd: HWND) -> bool {
+    unsafe {
+        if !IsWindowVisible(hwnd).as_bool() || GetWindowTextLengthW(hwnd) == 0 {
+            return false;
+        }
+        let mut cloaked = 0u32;
+        let cloaked_read = DwmGetWindowAttribute(hwnd, DWMWA_CLOAKED, &mut cloaked as *mut u32 as _, 4);
+        if cloaked_read.is_ok() && cloaked != 0 {
+            return false;
+        }
+        let style = WINDOW_EX_STYLE(GetWindowLongPtrW(hwnd, GWL_EXSTYLE) as u32);
+        if style.contains(WS_EX_APPWINDOW) {
+            return true;
+        }
+        !style.contains(WS_EX_TOOLWINDOW) && !style.contains(WS_EX_NOACTIVATE) && GetWindow(hwnd, GW_OWNER).0 == 0
+    }
+}
+
+#[cfg(windows)]
+fn describe(hwnd: HWND, foreground: HWND) -> Window {
+    let mut title = vec![0u16; unsafe { GetWindowTextLengthW(hwnd) } as usize + 1];
+    let length = unsafe { GetWindowTextW(hwnd, &mut title) } as usize;
+    let mut class = [0u16; 256];
+    let class_length = unsafe { GetClassNameW(hwnd, &mut class) } as usize;
+    let mut pid = 0;
+    unsafe { GetWindowThreadProcessId(hwnd, Some(&mut pid)) };
+
+    Window {
+        hwnd: format_hwnd(hwnd),
+        title: String::from_utf16_lossy(&title[..length]),
+        class: String::from_utf16_lossy(&class[..class_length]),
+        pid,
+        path: process_path(pid),
+        bounds: visible_frame(hwnd).into(),
+        monitor: monitor_name(hwnd),
+        minimized: unsafe { IsIconic(hwnd) }.as_bool(),
+        maximized: unsafe { IsZoomed(hwnd) }.as_bool(),
+        foreground: hwnd == foreground,
+    }
+}
+
+/// The process's executable, or None for an elevated or protected one while the
+/// helper isn't elevated.
+#[cfg(windows)]
+pub fn process_path(pid: u32) -> Option<String> {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut path = [0u16; 1024];
+        let mut length = path.len() as u32;
+        let queried = QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, PWSTR(path.as_mut_ptr()), &mut length);
+        let _ = CloseHandle(process);
+        queried.ok()?;
+        Some(String::from_utf16_lossy(&path[..length as usize]))
+    }
+}
+
+// Without the invisible resize borders GetWindowRect counts on Windows 10 and later
+#[cfg(windows)]
+pub fn visible_frame(hwnd: HWND) -> RECT {
+    let mut frame = RECT::default();
+    let read = unsafe {
+        DwmGetWindowAttribute(hwnd, DWMWA_EXTENDED_FRAME_BOUNDS, &mut frame as *mut RECT as _, std::mem::size_of::<RECT>() as u32)
+    };
+    if read.is_err() {
+        unsafe {
+            let _ = GetWindowRect(hwnd, &mut frame);
+        }
+    }
+    frame
+}
+
+#[cfg(windows)]
+fn monitor_name(hwnd: HWND) -> Option<String> {
+    let monitor = unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) };
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+    if !unsafe { GetMonitorInfoW(monitor, &mut info as *mut MONITORINFOEXW as *mut MONITORINFO) }.as_bool() {
+        return None;
+    }
+    let length = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+    Some(String::from_utf16_lossy(&info.szDevice[..length]))
+}
+
+#[cfg(windows)]
+pub fn check_window(hwnd: HWND) -> Result<()> {
+    if unsafe { IsWindow(hwnd) }.as_bool() {
+        Ok(())
+    } else {
+        Err(Error::new(ERROR_INVALID_WINDOW_HANDLE.to_hresult(), format!("No window {}", format_hwnd(hwnd)).into()))
+    }
+}
+
+#[cfg(windows)]
+fn show_window(hwnd: HWND, show: SHOW_WINDOW_CMD) {
+    unsafe {
+        // Not waiting on the window's thread, which may be hung
+        let _ = ShowWindowAsync(hwnd, show);
+    }
+}
+
+// Windows only lets the process the user last interacted with take the foreground;
+// sharing input state with it for a moment lends the helper that right
+#[cfg(windows)]
+fn focus(hwnd: HWND) -> Result<()> {
+    check_window(hwnd)?;
+    unsafe {
+        if IsIconic(hwnd).as_bool() {
+            show_window(hwnd, SW_RESTORE);
+        }
+        if SetForegroundWindow(hwnd).as_bool() {
+            return Ok(());
+        }
+        let foreground = GetWindowThreadProcessId(GetForegroundWindow(), None);
+        let current = GetCurrentThreadId();
+        let attached = foreground != 0 && foreground != current && AttachThreadInput(current, foreground, true).as_bool();
+        let _ = BringWindowToTop(hwnd);
+        let focused = SetForegroundWindow(hwnd).as_bool();
+        if attached {
+            let _ = AttachThreadInput(current, foreground, false);
+        }
+        if !focused && GetForegroundWindow() != hwnd {
+            return Err(Error::new(E_ACCESSDENIED, "Windows kept the window from coming to the front".into()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn move_window(hwnd: HWND, x: i32, y: i32, size: Option<(i32, i32)>) -> Result<()> {
+    check_window(hwnd)?;
+    unsafe {
+        if IsIconic(hwnd).as_bool() || IsZoomed(hwnd).as_bool() {
+            show_window(hwnd, SW_RESTORE);
+        }
+        // SetWindowPos takes the outer rectangle, borders and all
+        let mut outer = RECT::default();
+        GetWindowRect(hwnd, &mut outer)?;
+        let frame = visible_frame(hwnd);
+        let (left, top) = (frame.left - outer.left, frame.top - outer.top);
+        let (right, bottom) = (outer.right - frame.right, outer.bottom - frame.bottom);
+        let (width, height) = size.unwrap_or((frame.right - frame.left, frame.bottom - frame.top));
+        let flags = if size.is_some() { SWP_NOZORDER | SWP_NOACTIVATE } else { SWP_NOZORDER | SWP_NOACTIVATE | SWP_NOSIZE };
+        SetWindowPos(hwnd, None, x - left, y - top, width + left + right, height + top + bottom, flags)
+    }
+}
+
+#[cfg(windows)]
+fn close(hwnd: HWND) -> Result<()> {
+    check_window(hwnd)?;
+    unsafe { PostMessageW(hwnd, WM_CLOSE, WPARAM(0), LPARAM(0)) }
+}
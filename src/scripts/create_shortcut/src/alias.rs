@@ -0,0 +1,228 @@
+use std::env;
+use std::ffi::{OsString, c_void};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use altdesktop_core::error::ErrorReport;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper shortcut create <targetPath> <shortcutPath> [--symlink] [--json]
+
+<shortcutPath> may start with {Desktop} or {Applications}.
+
+Writes a Finder alias, which keeps finding its target after the target is moved or
+renamed. --symlink writes a symbolic link instead, which every program follows but
+which breaks when the target moves.";
+
+struct Options {
+    target_path: String,
+    shortcut_path: String,
+    symlink: bool,
+    json: bool,
+}
+
+type CFTypeRef = *const c_void;
+type CFIndex = isize;
+
+// kCFURLBookmarkCreationSuitableForBookmarkFile: the bookmark Finder reads as an alias
+const SUITABLE_FOR_BOOKMARK_FILE: usize = 1 << 10;
+
+#[link(name = "CoreFoundation", kind = "framework")]
+unsafe extern "C" {
+    fn CFURLCreateFromFileSystemRepresentation(
+        allocator: CFTypeRef,
+        buffer: *const u8,
+        length: CFIndex,
+        is_directory: u8,
+    ) -> CFTypeRef;
+    fn CFURLCreateBookmarkData(
+        allocator: CFTypeRef,
+        url: CFTypeRef,
+        options: usize,
+        resource_properties: CFTypeRef,
+        relative_to: CFTypeRef,
+        error: *mut CFTypeRef,
+    ) -> CFTypeRef;
+    fn CFURLWriteBookmarkDataToFile(bookmark: CFTypeRef, file_url: CFTypeRef, options: usize, error: *mut CFTypeRef) -> u8;
+    fn CFErrorGetCode(error: CFTypeRef) -> CFIndex;
+    fn CFRelease(object: CFTypeRef);
+}
+
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let json = args.iter().skip(1).any(|arg| arg == "--json");
+    let args: Vec<String> = match args.into_iter().map(|arg| arg.into_string()).collect() {
+        Ok(args) => args,
+        Err(arg) => return usage_error(&format!("Argument is not valid Unicode: {}", arg.to_string_lossy()), json),
+    };
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message, json),
+    };
+
+    let result = expand_folder_token(&options.shortcut_path).and_then(|shortcut_path| {
+        let created = if options.symlink {
+            create_symlink(&options.target_path, &shortcut_path)
+        } else {
+            create_alias(&options.target_path, &shortcut_path)
+        };
+        created.map(|_| shortcut_path)
+    });
+    match result {
+        Ok(path) => {
+            if options.json {
+                println!("{}", serde_json::json!({ "ok": true, "path": path }));
+            }
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            let report = ErrorReport::from_io_error(&error);
+            let code = report.exit_code();
+            if options.json {
+                println!("{}", serde_json::to_string(&report).unwrap());
+            } else {
+                eprintln!("Error: {}", error);
+            }
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn usage_error(message: &str, json: bool) -> ExitCode {
+    if json {
+        println!("{}", serde_json::to_string(&ErrorReport::usage(message)).unwrap());
+    } else {
+        eprintln!("{}", message);
+        eprintln!("{}", USAGE);
+    }
+    ExitCode::from(altdesktop_core::exit::USAGE)
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut json = false;
+    let mut symlink = false;
+    let mut positional = Vec::new();
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--symlink" => symlink = true,
+            flag if flag.starts_with("--") => {
+                return Err(format!("Unknown option: {} (only creating aliases and symlinks is supported here)", flag));
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() != 2 {
+        return Err("Expected <targetPath> and <shortcutPath>".to_string());
+    }
+    let shortcut_path = positional.pop().unwrap();
+    let target_path = positional.pop().unwrap();
+    Ok(Options { target_path, shortcut_path, symlink, json })
+}
+
+fn expand_folder_token(path: &str) -> io::Result<String> {
+    let Some(rest) = path.strip_prefix('{') else {
+        return Ok(path.to_string());
+    };
+    let Some((token, rest)) = rest.split_once('}') else {
+        return Ok(path.to_string());
+    };
+    let folder = match token {
+        "Desktop" => home_dir().join("Desktop"),
+        "Applications" => home_dir().join("Applications"),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown folder token: {{{}}}", token),
+            ));
+        }
+    };
+    // ~/Applications only exists once the user or an installer has made it
+    fs::create_dir_all(&folder)?;
+    let rest = rest.trim_start_matches('/');
+    Ok(folder.join(rest).to_string_lossy().into_owned())
+}
+
+fn home_dir() -> PathBuf {
+    env::var_os("HOME").map(PathBuf::from).unwrap_or_default()
+}
+
+// An alias is a bookmark file: Core Foundation records the target's volume and file
+// ID along with its path, which is how Finder follows it after a move
+fn create_alias(target_path: &str, shortcut_path: &str) -> io::Result<()> {
+    let target = fs::canonicalize(target_path)?;
+    let target_url = file_url(&target, target.is_dir())?;
+    let shortcut_url = match file_url(Path::new(shortcut_path), false) {
+        Ok(url) => url,
+        Err(error) => {
+            unsafe { CFRelease(target_url) };
+            return Err(error);
+        }
+    };
+    let result = unsafe {
+        let mut error: CFTypeRef = std::ptr::null();
+        let bookmark = CFURLCreateBookmarkData(
+            std::ptr::null(),
+            target_url,
+            SUITABLE_FOR_BOOKMARK_FILE,
+            std::ptr::null(),
+            std::ptr::null(),
+            &mut error,
+        );
+        let result = if bookmark.is_null() {
+            Err(bookmark_error(error, "create a bookmark of", &target))
+        } else if CFURLWriteBookmarkDataToFile(bookmark, shortcut_url, 0, &mut error) == 0 {
+            Err(bookmark_error(error, "write an alias to", &target))
+        } else {
+            Ok(())
+        };
+        if !bookmark.is_null() {
+            CFRelease(bookmark);
+        }
+        result
+    };
+    unsafe {
+        CFRelease(shortcut_url);
+        CFRelease(target_url);
+    }
+    result
+}
+
+fn file_url(path: &Path, is_directory: bool) -> io::Result<CFTypeRef> {
+    let bytes = path.as_os_str().as_bytes();
+    let url = unsafe {
+        CFURLCreateFromFileSystemRepresentation(std::ptr::null(), bytes.as_ptr(), bytes.len() as CFIndex, is_directory as u8)
+    };
+    if url.is_null() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid path: {}", path.display())));
+    }
+    Ok(url)
+}
+
+// Takes ownership of the CFError, which may be null
+unsafe fn bookmark_error(error: CFTypeRef, action: &str, target: &Path) -> io::Error {
+    if error.is_null() {
+        return io::Error::other(format!("Could not {} {}", action, target.display()));
+    }
+    let code = unsafe { CFErrorGetCode(error) };
+    unsafe { CFRelease(error) };
+    // Bookmark failures carry Cocoa or POSIX error codes; the POSIX ones map directly
+    let error = io::Error::from_raw_os_error(code as i32);
+    io::Error::new(error.kind(), format!("Could not {} {} (error {})", action, target.display(), code))
+}
+
+// A symlink can't be overwritten in place, so an earlier shortcut at the same path
+// is removed first, as writing a .lnk or .desktop over it would replace it
+fn create_symlink(target_path: &str, shortcut_path: &str) -> io::Result<()> {
+    let target = fs::canonicalize(target_path)?;
+    if let Ok(existing) = fs::symlink_metadata(shortcut_path)
+        && !existing.is_dir()
+    {
+        fs::remove_file(shortcut_path)?;
+    }
+    symlink(target, shortcut_path)
+}
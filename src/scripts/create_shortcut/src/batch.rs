@@ -0,0 +1,96 @@
+use std::io::{self, Read};
+use std::path::Path;
+
+use altdesktop_core::progress::Progress;
+use altdesktop_core::sandbox;
+use serde::{Deserialize, Serialize};
+use windows::{core::*, Win32::Foundation::E_INVALIDARG};
+
+use crate::create::create_shortcut;
+use crate::hotkey::parse_hotkey;
+use crate::known_folders::expand_known_folder;
+use crate::options::{parse_show_cmd, ShortcutFields};
+use crate::url::create_url_shortcut;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShortcutSpec {
+    path: String,
+    target: Option<String>,
+    url: Option<String>,
+    base_dir: Option<String>,
+    working_dir: Option<String>,
+    args: Option<String>,
+    description: Option<String>,
+    icon: Option<String>,
+    icon_index: Option<i32>,
+    hotkey: Option<String>,
+    show: Option<String>,
+    run_as_admin: Option<bool>,
+    app_user_model_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BatchResult {
+    path: String,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Reads a JSON array of shortcut specs from stdin and creates them all with a single
+/// COM initialization. Prints one result per spec and returns whether every item succeeded,
+/// with a progress line after each shortcut when asked.
+pub fn run_batch(progress: bool) -> std::result::Result<bool, String> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| format!("Failed to read stdin: {}", e))?;
+    let specs: Vec<ShortcutSpec> =
+        serde_json::from_str(&input).map_err(|e| format!("Invalid batch JSON: {}", e))?;
+
+    let mut progress = Progress::new(progress, specs.len());
+    let results: Vec<BatchResult> = specs
+        .into_iter()
+        .enumerate()
+        .map(|(index, spec)| {
+            let path = spec.path.clone();
+            let result = match create_from_spec(spec) {
+                Ok(_) => BatchResult { path, ok: true, error: None },
+                Err(error) => BatchResult { path, ok: false, error: Some(error.message().to_string()) },
+            };
+            progress.finished(index, &result.path);
+            result
+        })
+        .collect();
+
+    println!("{}", serde_json::to_string(&results).unwrap());
+    Ok(results.iter().all(|result| result.ok))
+}
+
+/// Creates the shortcut a spec describes and returns its path with known folders expanded.
+pub fn create_from_spec(spec: ShortcutSpec) -> Result<String> {
+    let invalid = |message: String| Error::new(E_INVALIDARG, message.into());
+    let fields = ShortcutFields {
+        target_path: spec.target,
+        base_dir: spec.base_dir,
+        working_dir: spec.working_dir,
+        arguments: spec.args,
+        description: spec.description,
+        icon_path: spec.icon,
+        icon_index: spec.icon_index,
+        hotkey: spec.hotkey.as_deref().map(parse_hotkey).transpose().map_err(invalid)?,
+        show_cmd: spec.show.as_deref().map(parse_show_cmd).transpose().map_err(invalid)?,
+        run_as_admin: spec.run_as_admin,
+        app_user_model_id: spec.app_user_model_id,
+    };
+
+    let path = expand_known_folder(&spec.path)?;
+    sandbox::check_output(Path::new(&path))?;
+    match (&spec.url, &fields.target_path) {
+        (Some(url), None) => create_url_shortcut(url, &path, &fields)?,
+        (None, Some(_)) => create_shortcut(&path, &fields)?,
+        _ => return Err(invalid("Expected exactly one of \"target\" or \"url\"".to_string())),
+    }
+    Ok(path)
+}
@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+
+use altdesktop_core::path::{extended_length_path, PATH_BUFFER_LEN};
+use altdesktop_core::wide::from_wide;
+use windows::{
+    core::*,
+    Win32::Storage::EnhancedStorage::PKEY_AppUserModel_ID,
+    Win32::UI::Shell::*,
+};
+
+use crate::link::{
+    clear_expandable_target, has_env_vars, is_shell_target, load_shell_link,
+    new_shell_link, save_shell_link, set_expandable_target, set_link_flag, set_target_id_list,
+};
+use crate::options::ShortcutFields;
+use crate::paths::link_field_path;
+use crate::propstore::set_string_property;
+
+pub fn create_shortcut(shortcut_path: &str, fields: &ShortcutFields) -> Result<()> {
+    let shell = new_shell_link()?;
+    apply_fields(&shell, Some(shortcut_path), fields)?;
+    save_shell_link(&shell, shortcut_path)
+}
+
+/// Creates a shortcut to a program for callers other than the shortcut tool, which
+/// have no ShortcutFields; the rest of the fields are left at their defaults.
+pub fn create_program_shortcut(
+    shortcut_path: &str,
+    target_path: &str,
+    arguments: Option<&str>,
+    working_dir: Option<&str>,
+    icon: Option<(&str, i32)>,
+) -> Result<()> {
+    let fields = ShortcutFields {
+        target_path: Some(target_path.to_string()),
+        arguments: arguments.map(str::to_string),
+        working_dir: working_dir.map(str::to_string),
+        icon_path: icon.map(|(path, _)| path.to_string()),
+        icon_index: icon.map(|(_, index)| index),
+        ..Default::default()
+    };
+    create_shortcut(shortcut_path, &fields)
+}
+
+/// Loads an existing shortcut and only touches the fields that were passed,
+/// so arguments or comments set outside Alt-Desktop survive.
+pub fn edit_shortcut(shortcut_path: &str, fields: &ShortcutFields) -> Result<()> {
+    let shell = load_shell_link(shortcut_path)?;
+    apply_fields(&shell, Some(shortcut_path), fields)?;
+    save_shell_link(&shell, shortcut_path)
+}
+
+/// Copies every property of `source_path` (including data blocks and the property
+/// store, since the whole link is loaded) to `shortcut_path`, then applies overrides.
+pub fn clone_shortcut(source_path: &str, shortcut_path: &str, fields: &ShortcutFields) -> Result<()> {
+    let shell = load_shell_link(source_path)?;
+    apply_fields(&shell, Some(shortcut_path), fields)?;
+    save_shell_link(&shell, shortcut_path)
+}
+
+pub fn apply_fields(shell: &IShellLinkW, shortcut_path: Option<&str>, fields: &ShortcutFields) -> Result<()> {
+    unsafe {
+        if let Some(target_path) = fields.target_path.as_deref().filter(|t| is_shell_target(t)) {
+            set_target_id_list(shell, target_path)?;
+        } else if let Some(target_path) = &fields.target_path {
+            // Only a target given relative to --base-dir, which the caller chose to
+            // move along with the shortcut
+            let relative = fields.base_dir.is_some() && is_relative_target(target_path);
+            let target_path = resolve_target(target_path, fields.base_dir.as_deref());
+            let target_str = target_path.to_string_lossy();
+
+            let is_folder = Path::new(&extended_length_path(&target_str)).is_dir();
+            if has_env_vars(&target_str) {
+                set_expandable_target(shell, &target_str)?;
+            } else if is_folder {
+                // Folder links point at the folder's ID list, like the ones Explorer makes
+                clear_expandable_target(shell)?;
+                set_target_id_list(shell, &link_field_path(&target_str))?;
+            } else {
+                clear_expandable_target(shell)?;
+                shell.SetPath(&HSTRING::from(link_field_path(&target_str)))?;
+            }
+
+            if !is_folder && fields.working_dir.is_none() {
+                let working_dir = target_path.parent().unwrap_or_else(|| Path::new(""));
+                shell.SetWorkingDirectory(&HSTRING::from(link_field_path(&working_dir.to_string_lossy())))?;
+            }
+
+            // Records the target relative to the .lnk so portable installs keep
+            // working when the drive letter changes
+            if let (true, Some(shortcut_path)) = (relative, shortcut_path) {
+                shell.SetRelativePath(&HSTRING::from(shortcut_path), 0)?;
+            }
+        }
+
+        // Some launchers need a working directory other than the exe's folder
+        if let Some(working_dir) = &fields.working_dir {
+            shell.SetWorkingDirectory(&HSTRING::from(link_field_path(working_dir)))?;
+        }
+
+        if let Some(arguments) = &fields.arguments {
+            shell.SetArguments(&HSTRING::from(arguments))?;
+        }
+
+        // Shown as the tooltip / "Comment" field in Explorer
+        if let Some(description) = &fields.description {
+            shell.SetDescription(&HSTRING::from(description))?;
+        }
+
+        // Negative indices refer to resource IDs rather than positions
+        if fields.icon_path.is_some() || fields.icon_index.is_some() {
+            let (current_path, current_index) = icon_location(shell)?;
+            let icon_path = fields
+                .icon_path
+                .clone()
+                .or_else(|| (!current_path.is_empty()).then_some(current_path))
+                .or_else(|| fields.target_path.clone().filter(|t| !is_shell_target(t)))
+                .unwrap_or_default();
+            let icon_index = fields.icon_index.unwrap_or(current_index);
+            shell.SetIconLocation(&HSTRING::from(link_field_path(&icon_path)), icon_index)?;
+        }
+
+        if let Some(hotkey) = fields.hotkey {
+            shell.SetHotkey(hotkey)?;
+        }
+
+        if let Some(show_cmd) = fields.show_cmd {
+            shell.SetShowCmd(show_cmd)?;
+        }
+
+        // Same as ticking "Run as administrator" under Advanced in the shortcut properties
+        if let Some(run_as_admin) = fields.run_as_admin {
+            set_link_flag(shell, SLDF_RUNAS_USER, run_as_admin)?;
+        }
+
+        // Lets the launched app group with its own taskbar button and toasts
+        if let Some(app_user_model_id) = &fields.app_user_model_id {
+            set_string_property(shell, &PKEY_AppUserModel_ID, app_user_model_id)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_relative_target(target_path: &str) -> bool {
+    !target_path.starts_with('%') && Path::new(target_path).is_relative()
+}
+
+/// Joins a relative target onto `base_dir`; absolute and `%VAR%` targets are kept as is.
+pub fn resolve_target(target_path: &str, base_dir: Option<&str>) -> PathBuf {
+    match base_dir {
+        Some(base_dir) if is_relative_target(target_path) => Path::new(base_dir).join(target_path),
+        _ => PathBuf::from(target_path),
+    }
+}
+
+fn icon_location(shell: &IShellLinkW) -> Result<(String, i32)> {
+    let mut buffer = vec![0u16; PATH_BUFFER_LEN];
+    let mut index = 0;
+    unsafe {
+        shell.GetIconLocation(&mut buffer, &mut index)?;
+    }
+    Ok((from_wide(&buffer), index))
+}
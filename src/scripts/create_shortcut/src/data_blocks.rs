@@ -0,0 +1,240 @@
+use altdesktop_core::path::{extended_length_path, PATH_BUFFER_LEN};
+use altdesktop_core::wide::from_wide;
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Foundation::{LocalFree, COLORREF, HLOCAL},
+    Win32::System::ApplicationInstallationAndServicing::*,
+    Win32::System::Console::COORD,
+    Win32::UI::Shell::*,
+};
+
+use crate::known_folders::{known_folder_path, KNOWN_FOLDERS};
+
+// Not in the SDK's headers, though Explorer writes it to every shortcut whose target
+// is inside a known folder
+const EXP_KNOWN_FOLDER_SIG: u32 = 0xA000_000B;
+
+#[repr(C, packed(1))]
+#[derive(Clone, Copy)]
+#[allow(non_snake_case)]
+struct EXP_KNOWN_FOLDER {
+    cbSize: u32,
+    dwSignature: u32,
+    idKnownFolder: GUID,
+    cbOffset: u32,
+}
+
+// Product and component codes are GUIDs with braces
+const CODE_LENGTH: usize = 39;
+
+/// The extra data blocks stored after a shortcut's fields, each None when it has none.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataBlocks {
+    /// The target as it was given, with its %VARIABLES% unexpanded.
+    pub expandable_target: Option<String>,
+    pub expandable_icon: Option<String>,
+    pub special_folder: Option<SpecialFolder>,
+    pub known_folder: Option<KnownFolderBlock>,
+    pub console: Option<ConsoleProperties>,
+    pub advertised: Option<Advertised>,
+}
+
+/// The CSIDL folder the target's ID list starts in, `offset` bytes into it.
+#[derive(Serialize)]
+pub struct SpecialFolder {
+    pub csidl: u32,
+    pub path: Option<String>,
+    pub offset: u32,
+}
+
+/// The known folder the target's ID list starts in, `offset` bytes into it.
+#[derive(Serialize)]
+pub struct KnownFolderBlock {
+    pub id: String,
+    /// Its `{Token}`, for the folders a shortcut path can start with.
+    pub token: Option<String>,
+    pub path: Option<String>,
+    pub offset: u32,
+}
+
+/// The window a console program's shortcut opens it in, as its Properties set it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsoleProperties {
+    pub face_name: String,
+    pub font_size: Coord,
+    pub font_weight: u32,
+    pub cursor_size: u32,
+    pub screen_buffer: Coord,
+    pub window: Coord,
+    /// Where the window opens, or None to let Windows place it.
+    pub window_origin: Option<Coord>,
+    pub full_screen: bool,
+    pub quick_edit: bool,
+    pub insert_mode: bool,
+    pub history_buffer_size: u32,
+    pub history_buffers: u32,
+    pub history_no_duplicates: bool,
+    /// Indexes into `colors`.
+    pub text_color: u16,
+    pub background_color: u16,
+    /// The 16 console colors as #RRGGBB.
+    pub colors: Vec<String>,
+    pub code_page: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct Coord {
+    pub x: i16,
+    pub y: i16,
+}
+
+/// A shortcut Windows Installer advertised, such as Office's, which starts the
+/// program through the installer so a missing feature is installed first.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Advertised {
+    pub descriptor: String,
+    pub product_code: Option<String>,
+    pub feature_id: Option<String>,
+    pub component_code: Option<String>,
+    /// local, source, advertised, absent or unknown: whether the component is installed.
+    pub state: &'static str,
+    /// The program the installer starts, when it's installed.
+    pub path: Option<String>,
+}
+
+pub fn read_data_blocks(shell: &IShellLinkW, shortcut_path: &str) -> Result<DataBlocks> {
+    let data_list: IShellLinkDataList = shell.cast()?;
+    unsafe {
+        let expandable = |signature| {
+            let block = copy_block::<EXP_SZ_LINK>(&data_list, signature);
+            block.map(|block| from_wide(&{ block.swzTarget }))
+        };
+        let expandable_target = expandable(EXP_SZ_LINK_SIG);
+        let expandable_icon = expandable(EXP_SZ_ICON_SIG);
+        let special_folder = copy_block::<EXP_SPECIAL_FOLDER>(&data_list, EXP_SPECIAL_FOLDER_SIG).map(special_folder);
+        let known_folder = copy_block::<EXP_KNOWN_FOLDER>(&data_list, EXP_KNOWN_FOLDER_SIG).map(known_folder);
+        let console = copy_block::<NT_CONSOLE_PROPS>(&data_list, NT_CONSOLE_PROPS_SIG).map(|block| {
+            let code_page = copy_block::<NT_FE_CONSOLE_PROPS>(&data_list, NT_FE_CONSOLE_PROPS_SIG);
+            console_properties(block, code_page.map(|block| block.uCodePage))
+        });
+        let advertised = copy_block::<EXP_DARWIN_LINK>(&data_list, EXP_DARWIN_ID_SIG)
+            .map(|block| advertised(shortcut_path, from_wide(&{ block.szwDarwinID })));
+        Ok(DataBlocks { expandable_target, expandable_icon, special_folder, known_folder, console, advertised })
+    }
+}
+
+// The shell hands out a LocalAlloc'd copy; one shorter than T is from an older
+// version of the block, and read as missing
+unsafe fn copy_block<T: Copy>(data_list: &IShellLinkDataList, signature: u32) -> Option<T> {
+    let mut block = std::ptr::null_mut();
+    unsafe {
+        data_list.CopyDataBlock(signature, &mut block).ok()?;
+        if block.is_null() {
+            return None;
+        }
+        let header = std::ptr::read_unaligned(block as *const DATABLOCK_HEADER);
+        let value = (header.cbSize as usize >= std::mem::size_of::<T>())
+            .then(|| std::ptr::read_unaligned(block as *const T));
+        let _ = LocalFree(HLOCAL(block));
+        value
+    }
+}
+
+fn special_folder(block: EXP_SPECIAL_FOLDER) -> SpecialFolder {
+    let mut buffer = [0u16; 260];
+    let path = unsafe { SHGetFolderPathW(None, block.idSpecialFolder as i32, None, 0, &mut buffer) }
+        .ok()
+        .map(|_| from_wide(&buffer));
+    SpecialFolder { csidl: block.idSpecialFolder, path, offset: block.cbOffset }
+}
+
+fn known_folder(block: EXP_KNOWN_FOLDER) -> KnownFolderBlock {
+    let id = block.idKnownFolder;
+    KnownFolderBlock {
+        id: format!("{{{:?}}}", id),
+        token: KNOWN_FOLDERS.iter().find(|(_, known)| *known == id).map(|(name, _)| format!("{{{}}}", name)),
+        path: known_folder_path(&id).ok(),
+        offset: block.cbOffset,
+    }
+}
+
+fn console_properties(block: NT_CONSOLE_PROPS, code_page: Option<u32>) -> ConsoleProperties {
+    let coord = |coord: COORD| Coord { x: coord.X, y: coord.Y };
+    let fill = block.wFillAttribute;
+    ConsoleProperties {
+        face_name: from_wide(&{ block.FaceName }),
+        font_size: coord(block.dwFontSize),
+        font_weight: block.uFontWeight,
+        cursor_size: block.uCursorSize,
+        screen_buffer: coord(block.dwScreenBufferSize),
+        window: coord(block.dwWindowSize),
+        window_origin: (!block.bAutoPosition.as_bool()).then(|| coord(block.dwWindowOrigin)),
+        full_screen: block.bFullScreen.as_bool(),
+        quick_edit: block.bQuickEdit.as_bool(),
+        insert_mode: block.bInsertMode.as_bool(),
+        history_buffer_size: block.uHistoryBufferSize,
+        history_buffers: block.uNumberOfHistoryBuffers,
+        history_no_duplicates: block.bHistoryNoDup.as_bool(),
+        text_color: fill & 0x0F,
+        background_color: (fill >> 4) & 0x0F,
+        colors: { block.ColorTable }.iter().map(|&color| format_color(color)).collect(),
+        code_page,
+    }
+}
+
+// COLORREF is 0x00BBGGRR
+fn format_color(color: COLORREF) -> String {
+    let [red, green, blue, _] = color.0.to_le_bytes();
+    format!("#{:02X}{:02X}{:02X}", red, green, blue)
+}
+
+// The installer knows the product and component from the shortcut, and where the
+// component's key file is once it's installed
+fn advertised(shortcut_path: &str, descriptor: String) -> Advertised {
+    let mut product = [0u16; CODE_LENGTH];
+    let mut feature = [0u16; MAX_FEATURE_CHARS as usize + 1];
+    let mut component = [0u16; CODE_LENGTH];
+    let status = unsafe {
+        MsiGetShortcutTargetW(
+            &HSTRING::from(extended_length_path(shortcut_path)),
+            PWSTR(product.as_mut_ptr()),
+            PWSTR(feature.as_mut_ptr()),
+            PWSTR(component.as_mut_ptr()),
+        )
+    };
+    let non_empty = |buffer: &[u16]| Some(from_wide(buffer)).filter(|code| !code.is_empty());
+    let (product_code, feature_id, component_code) = if status == 0 {
+        (non_empty(&product), non_empty(&feature), non_empty(&component))
+    } else {
+        (None, None, None)
+    };
+
+    let (state, path) = match (&product_code, &component_code) {
+        (Some(product), Some(component)) => {
+            let mut buffer = vec![0u16; PATH_BUFFER_LEN];
+            let mut length = buffer.len() as u32;
+            let state = unsafe {
+                let (product, component) = (HSTRING::from(product), HSTRING::from(component));
+                MsiGetComponentPathW(&product, &component, PWSTR(buffer.as_mut_ptr()), Some(&mut length))
+            };
+            let path = matches!(state, INSTALLSTATE_LOCAL | INSTALLSTATE_SOURCE).then(|| from_wide(&buffer));
+            (state_name(state), path.filter(|path| !path.is_empty()))
+        }
+        _ => ("unknown", None),
+    };
+    Advertised { descriptor, product_code, feature_id, component_code, state, path }
+}
+
+fn state_name(state: INSTALLSTATE) -> &'static str {
+    match state {
+        INSTALLSTATE_LOCAL => "local",
+        INSTALLSTATE_SOURCE => "source",
+        INSTALLSTATE_ADVERTISED => "advertised",
+        INSTALLSTATE_ABSENT => "absent",
+        _ => "unknown",
+    }
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use windows::{
+    core::*,
+    Win32::Foundation::{E_ABORT, E_INVALIDARG},
+    Win32::System::Com::*,
+    Win32::UI::Shell::*,
+};
+
+/// Moves a shortcut to the Recycle Bin through the same IFileOperation path
+/// Explorer uses, so the deletion can be undone.
+pub fn delete_shortcut(shortcut_path: &str) -> Result<()> {
+    let is_shortcut = Path::new(shortcut_path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("lnk") || ext.eq_ignore_ascii_case("url"));
+    if !is_shortcut {
+        return Err(Error::new(E_INVALIDARG, "Only .lnk and .url files can be deleted".into()));
+    }
+
+    unsafe {
+        let item: IShellItem = SHCreateItemFromParsingName(&HSTRING::from(shortcut_path), None::<&IBindCtx>)?;
+        let operation: IFileOperation = CoCreateInstance(&FileOperation, None, CLSCTX_ALL)?;
+        operation.SetOperationFlags(
+            FOF_ALLOWUNDO | FOFX_RECYCLEONDELETE | FOF_NOCONFIRMATION | FOF_SILENT | FOF_NOERRORUI,
+        )?;
+        operation.DeleteItem(&item, None::<&IFileOperationProgressSink>)?;
+        operation.PerformOperations()?;
+
+        if operation.GetAnyOperationsAborted()?.as_bool() {
+            return Err(Error::new(E_ABORT, "Delete operation was aborted".into()));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+
+use altdesktop_core::error::ErrorReport;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper shortcut create <targetPath> <shortcutPath.desktop> [fields]
+
+<shortcutPath> may start with {Desktop} or {Applications}.
+
+Fields:
+  --args <arguments>  --working-dir <path>  --description <text>  --icon <path|name>
+  --name <text>  (defaults to the file name)  --terminal  --json";
+
+/// The freedesktop counterpart of a .lnk: a `[Desktop Entry]` launcher file.
+#[derive(Default)]
+struct EntryFields {
+    name: Option<String>,
+    arguments: Option<String>,
+    working_dir: Option<String>,
+    description: Option<String>,
+    icon: Option<String>,
+    terminal: bool,
+}
+
+struct Options {
+    target_path: String,
+    shortcut_path: String,
+    fields: EntryFields,
+    json: bool,
+}
+
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let json = args.iter().skip(1).any(|arg| arg == "--json");
+    let args: Vec<String> = match args.into_iter().map(|arg| arg.into_string()).collect() {
+        Ok(args) => args,
+        Err(arg) => return usage_error(&format!("Argument is not valid Unicode: {}", arg.to_string_lossy()), json),
+    };
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message, args.iter().skip(1).any(|arg| arg == "--json")),
+    };
+
+    let result = expand_folder_token(&options.shortcut_path).and_then(|shortcut_path| {
+        create_desktop_entry(&options.target_path, &shortcut_path, &options.fields).map(|_| shortcut_path)
+    });
+    match result {
+        Ok(path) => {
+            if options.json {
+                println!("{}", serde_json::json!({ "ok": true, "path": path }));
+            }
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            let report = ErrorReport::from_io_error(&error);
+            let code = report.exit_code();
+            if options.json {
+                println!("{}", serde_json::to_string(&report).unwrap());
+            } else {
+                eprintln!("Error: {}", error);
+            }
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn usage_error(message: &str, json: bool) -> ExitCode {
+    if json {
+        println!("{}", serde_json::to_string(&ErrorReport::usage(message)).unwrap());
+    } else {
+        eprintln!("{}", message);
+        eprintln!("{}", USAGE);
+    }
+    ExitCode::from(altdesktop_core::exit::USAGE)
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut json = false;
+    let mut positional = Vec::new();
+    let mut fields = EntryFields::default();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("{} requires a value", flag))
+        };
+        match flag {
+            "--json" => json = true,
+            "--terminal" => fields.terminal = true,
+            "--name" => fields.name = Some(value()?),
+            "--args" => fields.arguments = Some(value()?),
+            "--working-dir" => fields.working_dir = Some(value()?),
+            "--description" => fields.description = Some(value()?),
+            "--icon" => fields.icon = Some(value()?),
+            _ if flag.starts_with("--") => {
+                return Err(format!("Unknown option: {} (only creating .desktop files is supported here)", flag));
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() != 2 {
+        return Err("Expected <targetPath> and <shortcutPath>".to_string());
+    }
+    let shortcut_path = positional.pop().unwrap();
+    let target_path = positional.pop().unwrap();
+    if !shortcut_path.ends_with(".desktop") {
+        return Err("<shortcutPath> must end in .desktop".to_string());
+    }
+    Ok(Options { target_path, shortcut_path, fields, json })
+}
+
+fn expand_folder_token(path: &str) -> io::Result<String> {
+    let Some(rest) = path.strip_prefix('{') else {
+        return Ok(path.to_string());
+    };
+    let Some((token, rest)) = rest.split_once('}') else {
+        return Ok(path.to_string());
+    };
+    let folder = match token {
+        "Desktop" => desktop_dir(),
+        "Applications" => data_home().join("applications"),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown folder token: {{{}}}", token),
+            ));
+        }
+    };
+    // ~/.local/share/applications doesn't exist until something installs a launcher
+    fs::create_dir_all(&folder)?;
+    let rest = rest.trim_start_matches(['/', '\\']);
+    Ok(folder.join(rest).to_string_lossy().into_owned())
+}
+
+// The desktop folder is localized on most distros; xdg-user-dir knows its real name
+fn desktop_dir() -> PathBuf {
+    let output = Command::new("xdg-user-dir").arg("DESKTOP").stderr(Stdio::null()).output();
+    if let Ok(output) = output
+        && output.status.success()
+    {
+        let dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !dir.is_empty() {
+            return PathBuf::from(dir);
+        }
+    }
+    home_dir().join("Desktop")
+}
+
+fn data_home() -> PathBuf {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .unwrap_or_else(|| home_dir().join(".local/share"))
+}
+
+fn home_dir() -> PathBuf {
+    env::var_os("HOME").map(PathBuf::from).unwrap_or_default()
+}
+
+fn create_desktop_entry(target_path: &str, shortcut_path: &str, fields: &EntryFields) -> io::Result<()> {
+    let target = fs::canonicalize(target_path).unwrap_or_else(|_| PathBuf::from(target_path));
+    let name = fields.name.clone().unwrap_or_else(|| {
+        Path::new(shortcut_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+
+    let mut contents = String::from("[Desktop Entry]\n");
+    // Folders open in the file manager, like a folder .lnk would
+    if target.is_dir() {
+        contents.push_str("Type=Link\n");
+        push_key(&mut contents, "Name", &name);
+        push_key(&mut contents, "URL", &file_url(&target));
+    } else {
+        contents.push_str("Type=Application\n");
+        push_key(&mut contents, "Name", &name);
+        let mut exec = quote_exec_arg(&target.to_string_lossy());
+        if let Some(arguments) = fields.arguments.as_deref().filter(|a| !a.is_empty()) {
+            exec.push(' ');
+            exec.push_str(&arguments.replace('%', "%%"));
+        }
+        push_key(&mut contents, "Exec", &exec);
+        let working_dir = fields
+            .working_dir
+            .clone()
+            .or_else(|| target.parent().map(|dir| dir.to_string_lossy().into_owned()));
+        if let Some(working_dir) = working_dir {
+            push_key(&mut contents, "Path", &working_dir);
+        }
+        push_key(&mut contents, "Terminal", if fields.terminal { "true" } else { "false" });
+    }
+    if let Some(description) = &fields.description {
+        push_key(&mut contents, "Comment", description);
+    }
+    if let Some(icon) = &fields.icon {
+        push_key(&mut contents, "Icon", icon);
+    }
+
+    fs::write(shortcut_path, contents)?;
+    // File managers refuse to launch desktop files that aren't executable
+    fs::set_permissions(shortcut_path, fs::Permissions::from_mode(0o755))?;
+    mark_trusted(shortcut_path);
+    Ok(())
+}
+
+// GNOME additionally wants the file marked as trusted before it shows the icon and
+// launches it. Other desktops don't have gio or the attribute, which is fine.
+fn mark_trusted(shortcut_path: &str) {
+    let _ = Command::new("gio")
+        .args(["set", shortcut_path, "metadata::trusted", "true"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+fn push_key(contents: &mut String, key: &str, value: &str) {
+    contents.push_str(key);
+    contents.push('=');
+    contents.push_str(&escape_value(value));
+    contents.push('\n');
+}
+
+// String values escape backslashes and control characters
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Exec arguments containing reserved characters must be double-quoted, and `%` starts
+// a field code, so it is doubled
+fn quote_exec_arg(arg: &str) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(', ')', '`',
+    ];
+    let arg = arg.replace('%', "%%");
+    if !arg.contains(RESERVED) {
+        return arg;
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn file_url(path: &Path) -> String {
+    let mut url = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    url
+}
@@ -0,0 +1,309 @@
+use std::fs;
+use std::path::Path;
+
+use altdesktop_core::path::{extended_length_path, full_path, strip_extended_prefix};
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Foundation::*,
+    Win32::Storage::FileSystem::*,
+    Win32::System::Ioctl::{FSCTL_GET_REPARSE_POINT, FSCTL_SET_REPARSE_POINT},
+    Win32::System::IO::DeviceIoControl,
+};
+
+const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA000_0003;
+const IO_REPARSE_TAG_SYMLINK: u32 = 0xA000_000C;
+const SYMLINK_FLAG_RELATIVE: u32 = 1;
+// MAXIMUM_REPARSE_DATA_BUFFER_SIZE
+const REPARSE_BUFFER_LEN: usize = 16 * 1024;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LinkType {
+    Auto,
+    Symlink,
+    Junction,
+    Hardlink,
+}
+
+impl LinkType {
+    pub fn parse(value: &str) -> std::result::Result<LinkType, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "auto" => Ok(LinkType::Auto),
+            "symlink" => Ok(LinkType::Symlink),
+            "junction" => Ok(LinkType::Junction),
+            "hardlink" => Ok(LinkType::Hardlink),
+            _ => Err(format!("Invalid link type: {}", value)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LinkType::Auto => "auto",
+            LinkType::Symlink => "symlink",
+            LinkType::Junction => "junction",
+            LinkType::Hardlink => "hardlink",
+        }
+    }
+}
+
+/// Creates a real file system link instead of a .lnk. `Auto` tries a symlink first
+/// (which works unelevated in Developer Mode) and falls back to a junction for folders.
+/// Returns the kind of link that was created.
+pub fn create_fs_link(target_path: &str, link_path: &str, link_type: LinkType) -> Result<LinkType> {
+    let target = full_path(target_path);
+    let is_dir = Path::new(&extended_length_path(&target)).is_dir();
+
+    match link_type {
+        LinkType::Symlink => create_symlink(&target, link_path, is_dir).map(|_| LinkType::Symlink),
+        LinkType::Junction => create_junction(&target, link_path).map(|_| LinkType::Junction),
+        LinkType::Hardlink => create_hard_link(&target, link_path, is_dir).map(|_| LinkType::Hardlink),
+        LinkType::Auto => match create_symlink(&target, link_path, is_dir) {
+            Ok(()) => Ok(LinkType::Symlink),
+            Err(error) if is_dir && error.code() == ERROR_PRIVILEGE_NOT_HELD.to_hresult() => {
+                create_junction(&target, link_path).map(|_| LinkType::Junction)
+            }
+            Err(error) => Err(error),
+        },
+    }
+}
+
+fn create_symlink(target: &str, link_path: &str, is_dir: bool) -> Result<()> {
+    let mut flags = SYMBOLIC_LINK_FLAG_ALLOW_UNPRIVILEGED_CREATE;
+    if is_dir {
+        flags |= SYMBOLIC_LINK_FLAG_DIRECTORY;
+    }
+    let link = HSTRING::from(extended_length_path(link_path));
+    let created = unsafe { CreateSymbolicLinkW(&link, &HSTRING::from(target), flags) };
+    if created.0 == 0 {
+        let error = Error::from_win32();
+        if error.code() == ERROR_PRIVILEGE_NOT_HELD.to_hresult() {
+            return Err(Error::new(
+                error.code(),
+                "Creating symbolic links needs Developer Mode turned on, or running elevated".into(),
+            ));
+        }
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Hard links need no privileges, but only join files on one NTFS volume.
+fn create_hard_link(target: &str, link_path: &str, is_dir: bool) -> Result<()> {
+    if is_dir {
+        return Err(Error::new(E_INVALIDARG, "Hard links can only point at files".into()));
+    }
+    let link = HSTRING::from(extended_length_path(link_path));
+    unsafe { CreateHardLinkW(&link, &HSTRING::from(extended_length_path(target)), None) }.map_err(|error| {
+        if error.code() == ERROR_NOT_SAME_DEVICE.to_hresult() {
+            Error::new(error.code(), "Hard links must be on the same drive as their target".into())
+        } else {
+            error
+        }
+    })
+}
+
+/// Junctions need no privileges but only work for local folders. The link is an empty
+/// directory carrying a mount-point reparse buffer.
+fn create_junction(target: &str, link_path: &str) -> Result<()> {
+    let target = junction_target(target)?;
+    if !Path::new(&extended_length_path(&target)).is_dir() {
+        return Err(Error::new(E_INVALIDARG, "Junctions can only point at folders".into()));
+    }
+
+    let link = extended_length_path(link_path);
+    fs::create_dir(&link).map_err(|e| Error::new(E_FAIL, e.to_string().into()))?;
+
+    let result = set_mount_point(&link, &target);
+    if result.is_err() {
+        let _ = fs::remove_dir(&link);
+    }
+    result
+}
+
+// The target in the plain form both names of the reparse buffer take, without a
+// \\?\ prefix or a trailing backslash past the root
+fn junction_target(target: &str) -> Result<String> {
+    let plain = strip_extended_prefix(target);
+    // Which also turns \\?\UNC\server\share into this form
+    if plain.starts_with(r"\\") {
+        return Err(Error::new(E_INVALIDARG, "Junctions can't point at network shares".into()));
+    }
+    let trimmed = plain.trim_end_matches('\\');
+    // A drive root keeps its backslash, since \??\C: names the volume rather than
+    // its root folder
+    Ok(if trimmed.ends_with(':') { plain } else { trimmed.to_string() })
+}
+
+fn set_mount_point(link: &str, target: &str) -> Result<()> {
+    let substitute: Vec<u16> = format!(r"\??\{}", target).encode_utf16().collect();
+    let print: Vec<u16> = target.encode_utf16().collect();
+
+    let substitute_bytes = (substitute.len() * 2) as u16;
+    let print_bytes = (print.len() * 2) as u16;
+    // Four USHORT offsets/lengths, then both names with their NUL terminators
+    let data_len = 8 + substitute_bytes + 2 + print_bytes + 2;
+
+    let mut buffer = Vec::with_capacity(8 + data_len as usize);
+    buffer.extend_from_slice(&IO_REPARSE_TAG_MOUNT_POINT.to_le_bytes());
+    buffer.extend_from_slice(&data_len.to_le_bytes());
+    buffer.extend_from_slice(&0u16.to_le_bytes());
+    buffer.extend_from_slice(&0u16.to_le_bytes());
+    buffer.extend_from_slice(&substitute_bytes.to_le_bytes());
+    buffer.extend_from_slice(&(substitute_bytes + 2).to_le_bytes());
+    buffer.extend_from_slice(&print_bytes.to_le_bytes());
+    for unit in substitute.iter().chain([&0]).chain(print.iter()).chain([&0]) {
+        buffer.extend_from_slice(&unit.to_le_bytes());
+    }
+
+    unsafe {
+        let handle = CreateFileW(
+            &HSTRING::from(link),
+            GENERIC_WRITE.0,
+            FILE_SHARE_MODE(0),
+            None,
+            OPEN_EXISTING,
+            FILE_FLAG_OPEN_REPARSE_POINT | FILE_FLAG_BACKUP_SEMANTICS,
+            None,
+        )?;
+        let result = DeviceIoControl(
+            handle,
+            FSCTL_SET_REPARSE_POINT,
+            Some(buffer.as_ptr() as *const _),
+            buffer.len() as u32,
+            None,
+            0,
+            None,
+            None,
+        );
+        let _ = CloseHandle(handle);
+        result
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkInfo {
+    pub path: String,
+    /// symlink, junction or hardlink; file or directory for neither; reparsePoint for
+    /// other reparse points, such as cloud placeholders and app execution aliases.
+    pub kind: &'static str,
+    /// Where a symlink or junction points, as stored.
+    pub target: Option<String>,
+    /// Whether that is relative to the link's folder.
+    pub relative: bool,
+    /// Whether the target exists; always true for anything but a link.
+    pub target_exists: bool,
+    /// How many names the file has; more than one makes each a hard link.
+    pub links: u32,
+    /// The reparse tag, as 0x-prefixed hex.
+    pub tag: Option<String>,
+}
+
+/// Says what `path` is without following it, when it is a link.
+pub fn read_fs_link(path: &str) -> Result<LinkInfo> {
+    let full = full_path(path);
+    unsafe {
+        let handle = CreateFileW(
+            &HSTRING::from(extended_length_path(&full)),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAG_OPEN_REPARSE_POINT | FILE_FLAG_BACKUP_SEMANTICS,
+            None,
+        )?;
+        let mut info = BY_HANDLE_FILE_INFORMATION::default();
+        let result = GetFileInformationByHandle(handle, &mut info).map(|()| {
+            let reparse = info.dwFileAttributes & FILE_ATTRIBUTE_REPARSE_POINT.0 != 0;
+            (info, if reparse { read_reparse_point(handle) } else { None })
+        });
+        let _ = CloseHandle(handle);
+        let (info, reparse) = result?;
+
+        let is_dir = info.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY.0 != 0;
+        let (kind, target, relative) = match &reparse {
+            Some((IO_REPARSE_TAG_SYMLINK, target, relative)) => ("symlink", target.clone(), *relative),
+            Some((IO_REPARSE_TAG_MOUNT_POINT, target, _)) => ("junction", target.clone(), false),
+            Some(_) => ("reparsePoint", None, false),
+            None if !is_dir && info.nNumberOfLinks > 1 => ("hardlink", None, false),
+            None if is_dir => ("directory", None, false),
+            None => ("file", None, false),
+        };
+        let target_exists = match &target {
+            Some(target) if relative => Path::new(&full).parent().is_some_and(|folder| folder.join(target).exists()),
+            Some(target) => Path::new(target).exists(),
+            None => true,
+        };
+        Ok(LinkInfo {
+            path: full,
+            kind,
+            target,
+            relative,
+            target_exists,
+            links: info.nNumberOfLinks,
+            tag: reparse.map(|(tag, _, _)| format!("0x{:08X}", tag)),
+        })
+    }
+}
+
+// The tag, and for symlinks and junctions their print name and whether it's relative
+unsafe fn read_reparse_point(handle: HANDLE) -> Option<(u32, Option<String>, bool)> {
+    let mut buffer = vec![0u8; REPARSE_BUFFER_LEN];
+    let mut returned = 0u32;
+    unsafe {
+        DeviceIoControl(
+            handle,
+            FSCTL_GET_REPARSE_POINT,
+            None,
+            0,
+            Some(buffer.as_mut_ptr() as *mut _),
+            buffer.len() as u32,
+            Some(&mut returned),
+            None,
+        )
+    }
+    .ok()?;
+    buffer.truncate(returned as usize);
+
+    let u16_at = |offset: usize| buffer.get(offset..offset + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]));
+    let tag = u32::from_le_bytes(buffer.get(..4)?.try_into().ok()?);
+    // After the 8-byte header: substitute name offset and length, print name offset
+    // and length, then for symlinks a flags field, then the names
+    let (names_start, relative) = match tag {
+        IO_REPARSE_TAG_SYMLINK => {
+            let flags = u32::from_le_bytes(buffer.get(16..20)?.try_into().ok()?);
+            (20, flags & SYMLINK_FLAG_RELATIVE != 0)
+        }
+        IO_REPARSE_TAG_MOUNT_POINT => (16, false),
+        _ => return Some((tag, None, false)),
+    };
+    let name = |offset: u16, length: u16| -> Option<String> {
+        let start = names_start + offset as usize;
+        let bytes = buffer.get(start..start + length as usize)?;
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        Some(String::from_utf16_lossy(&units)).filter(|name| !name.is_empty())
+    };
+    // The print name is the one meant for people; older tools left it empty
+    let target = name(u16_at(12)?, u16_at(14)?)
+        .or_else(|| name(u16_at(8)?, u16_at(10)?).map(|name| name.trim_start_matches(r"\??\").to_string()));
+    Some((tag, target, relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn junction_target_takes_extended_length_local_paths() {
+        assert_eq!(junction_target(r"\\?\C:\").unwrap(), r"C:\");
+        assert_eq!(junction_target(r"\\?\C:\Games\").unwrap(), r"C:\Games");
+        assert_eq!(junction_target(r"D:\Tools").unwrap(), r"D:\Tools");
+    }
+
+    #[test]
+    fn junction_target_rejects_network_shares() {
+        for target in [r"\\server\share", r"\\?\UNC\server\share"] {
+            assert_eq!(junction_target(target).unwrap_err().code(), E_INVALIDARG);
+        }
+    }
+}
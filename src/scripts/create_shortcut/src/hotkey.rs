@@ -0,0 +1,100 @@
+// Shortcut hotkeys are stored as a WORD: the low byte is the virtual-key code,
+// the high byte holds the HOTKEYF_* modifier flags.
+const HOTKEYF_SHIFT: u16 = 0x01;
+const HOTKEYF_CONTROL: u16 = 0x02;
+const HOTKEYF_ALT: u16 = 0x04;
+
+const NAMED_KEYS: &[(&str, u16)] = &[
+    ("Space", 0x20),
+    ("PageUp", 0x21),
+    ("PageDown", 0x22),
+    ("End", 0x23),
+    ("Home", 0x24),
+    ("Left", 0x25),
+    ("Up", 0x26),
+    ("Right", 0x27),
+    ("Down", 0x28),
+    ("Insert", 0x2D),
+    ("Delete", 0x2E),
+    ("NumLock", 0x90),
+    ("ScrollLock", 0x91),
+];
+
+/// Parses a string like "Ctrl+Alt+F5" into the WORD expected by `IShellLinkW::SetHotkey`.
+pub fn parse_hotkey(value: &str) -> Result<u16, String> {
+    let mut modifiers = 0u16;
+    let mut key = None;
+
+    for part in value.split('+').map(str::trim) {
+        match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => modifiers |= HOTKEYF_CONTROL,
+            "alt" => modifiers |= HOTKEYF_ALT,
+            "shift" => modifiers |= HOTKEYF_SHIFT,
+            _ => {
+                if key.is_some() {
+                    return Err(format!("Hotkey has more than one key: {}", value));
+                }
+                key = Some(parse_key(part).ok_or_else(|| format!("Unknown hotkey key: {}", part))?);
+            }
+        }
+    }
+
+    let key = key.ok_or_else(|| format!("Hotkey has no key: {}", value))?;
+    Ok((modifiers << 8) | key)
+}
+
+/// The virtual-key code for a key name such as "A", "7", "F5" or "PageUp".
+pub fn parse_key(name: &str) -> Option<u16> {
+    if name.len() == 1 {
+        let c = name.chars().next()?.to_ascii_uppercase();
+        if c.is_ascii_uppercase() || c.is_ascii_digit() {
+            return Some(c as u16);
+        }
+        return None;
+    }
+
+    if let Some(number) = name.strip_prefix(['F', 'f'])
+        && let Ok(n @ 1..=24) = number.parse::<u16>()
+    {
+        return Some(0x70 + n - 1);
+    }
+
+    NAMED_KEYS
+        .iter()
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        .map(|(_, code)| *code)
+}
+
+/// Formats a hotkey WORD back into the "Ctrl+Alt+F5" form accepted by `parse_hotkey`.
+pub fn format_hotkey(hotkey: u16) -> Option<String> {
+    let key = hotkey & 0xFF;
+    if key == 0 {
+        return None;
+    }
+    let modifiers = hotkey >> 8;
+
+    let mut parts = Vec::new();
+    if modifiers & HOTKEYF_CONTROL != 0 {
+        parts.push("Ctrl".to_string());
+    }
+    if modifiers & HOTKEYF_ALT != 0 {
+        parts.push("Alt".to_string());
+    }
+    if modifiers & HOTKEYF_SHIFT != 0 {
+        parts.push("Shift".to_string());
+    }
+    parts.push(format_key(key));
+    Some(parts.join("+"))
+}
+
+fn format_key(code: u16) -> String {
+    match code {
+        0x30..=0x39 | 0x41..=0x5A => (code as u8 as char).to_string(),
+        0x70..=0x87 => format!("F{}", code - 0x70 + 1),
+        _ => NAMED_KEYS
+            .iter()
+            .find(|(_, key_code)| *key_code == code)
+            .map(|(name, _)| name.to_string())
+            .unwrap_or_else(|| format!("0x{:02X}", code)),
+    }
+}
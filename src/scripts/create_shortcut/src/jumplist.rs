@@ -0,0 +1,130 @@
+use std::io::{self, Read};
+
+use altdesktop_core::path::PATH_BUFFER_LEN;
+use altdesktop_core::wide::from_wide;
+use serde::Deserialize;
+use windows::{
+    core::*,
+    Win32::Foundation::E_INVALIDARG,
+    Win32::Storage::EnhancedStorage::PKEY_Title,
+    Win32::System::Com::*,
+    Win32::UI::Shell::Common::*,
+    Win32::UI::Shell::*,
+};
+
+use crate::create::apply_fields;
+use crate::link::{new_shell_link, target_path};
+use crate::options::ShortcutFields;
+use crate::propstore::set_string_property;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JumpListSpec {
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    items: Vec<JumpListItem>,
+    #[serde(default)]
+    tasks: Vec<JumpListItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JumpListItem {
+    title: String,
+    target: String,
+    args: Option<String>,
+    description: Option<String>,
+    icon: Option<String>,
+    icon_index: Option<i32>,
+}
+
+/// Replaces the jump list of `app_id` with the JSON spec read from stdin:
+/// a custom category of tiles plus optional user tasks.
+pub fn update_jump_list(app_id: &str) -> Result<()> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| Error::new(E_INVALIDARG, format!("Failed to read stdin: {}", e).into()))?;
+    let spec: JumpListSpec = serde_json::from_str(&input)
+        .map_err(|e| Error::new(E_INVALIDARG, format!("Invalid jump list JSON: {}", e).into()))?;
+
+    unsafe {
+        let list: ICustomDestinationList = CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER)?;
+        list.SetAppID(&HSTRING::from(app_id))?;
+
+        let mut min_slots = 0;
+        let removed: IObjectArray = list.BeginList(&mut min_slots)?;
+        let removed = removed_items(&removed)?;
+
+        if !spec.items.is_empty() {
+            let collection = build_collection(&spec.items, &removed)?;
+            let category = spec.category.as_deref().unwrap_or("Tiles");
+            list.AppendCategory(&HSTRING::from(category), &collection.cast::<IObjectArray>()?)?;
+        }
+
+        if !spec.tasks.is_empty() {
+            let collection = build_collection(&spec.tasks, &[])?;
+            list.AddUserTasks(&collection.cast::<IObjectArray>()?)?;
+        }
+
+        list.CommitList()
+    }
+}
+
+pub fn clear_jump_list(app_id: &str) -> Result<()> {
+    unsafe {
+        let list: ICustomDestinationList = CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER)?;
+        list.DeleteList(&HSTRING::from(app_id))
+    }
+}
+
+fn build_collection(items: &[JumpListItem], removed: &[(String, String)]) -> Result<IObjectCollection> {
+    unsafe {
+        let collection: IObjectCollection =
+            CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER)?;
+        for item in items {
+            let args = item.args.clone().unwrap_or_default();
+            // Windows rejects the whole list if it contains an item the user removed
+            if removed
+                .iter()
+                .any(|(target, removed_args)| target.eq_ignore_ascii_case(&item.target) && *removed_args == args)
+            {
+                continue;
+            }
+            collection.AddObject(&item_link(item)?)?;
+        }
+        Ok(collection)
+    }
+}
+
+fn item_link(item: &JumpListItem) -> Result<IShellLinkW> {
+    let shell = new_shell_link()?;
+    let fields = ShortcutFields {
+        target_path: Some(item.target.clone()),
+        arguments: item.args.clone(),
+        description: item.description.clone(),
+        icon_path: item.icon.clone(),
+        icon_index: item.icon_index,
+        ..Default::default()
+    };
+    // Jump list items aren't saved to a .lnk of their own
+    apply_fields(&shell, None, &fields)?;
+    // Jump list entries show PKEY_Title rather than a file name
+    set_string_property(&shell, &PKEY_Title, &item.title)?;
+    Ok(shell)
+}
+
+fn removed_items(removed: &IObjectArray) -> Result<Vec<(String, String)>> {
+    unsafe {
+        let mut items = Vec::new();
+        for i in 0..removed.GetCount()? {
+            if let Ok(shell) = removed.GetAt::<IShellLinkW>(i) {
+                let mut buffer = vec![0u16; PATH_BUFFER_LEN];
+                shell.GetArguments(&mut buffer)?;
+                items.push((target_path(&shell)?, from_wide(&buffer)));
+            }
+        }
+        Ok(items)
+    }
+}
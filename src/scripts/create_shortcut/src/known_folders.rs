@@ -0,0 +1,87 @@
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Foundation::{E_INVALIDARG, HANDLE},
+    Win32::System::Com::CoTaskMemFree,
+    Win32::UI::Shell::*,
+};
+
+/// Tokens accepted at the start of a shortcut path, e.g. `{Desktop}\App.lnk`.
+/// Resolving them here avoids guessing localized or redirected folder locations.
+pub const KNOWN_FOLDERS: &[(&str, GUID)] = &[
+    ("Desktop", FOLDERID_Desktop),
+    ("PublicDesktop", FOLDERID_PublicDesktop),
+    ("StartMenu", FOLDERID_StartMenu),
+    ("CommonStartMenu", FOLDERID_CommonStartMenu),
+    ("Programs", FOLDERID_Programs),
+    ("CommonPrograms", FOLDERID_CommonPrograms),
+    ("Startup", FOLDERID_Startup),
+    ("CommonStartup", FOLDERID_CommonStartup),
+    ("QuickLaunch", FOLDERID_QuickLaunch),
+    ("SendTo", FOLDERID_SendTo),
+    ("Favorites", FOLDERID_Favorites),
+    ("Documents", FOLDERID_Documents),
+    ("Downloads", FOLDERID_Downloads),
+    ("Pictures", FOLDERID_Pictures),
+    ("Music", FOLDERID_Music),
+    ("Videos", FOLDERID_Videos),
+    ("Screenshots", FOLDERID_Screenshots),
+    ("Templates", FOLDERID_Templates),
+    ("Recent", FOLDERID_Recent),
+    ("Profile", FOLDERID_Profile),
+    ("OneDrive", FOLDERID_SkyDrive),
+    ("Public", FOLDERID_Public),
+    ("PublicDocuments", FOLDERID_PublicDocuments),
+    ("RoamingAppData", FOLDERID_RoamingAppData),
+    ("LocalAppData", FOLDERID_LocalAppData),
+    ("ProgramData", FOLDERID_ProgramData),
+    ("ProgramFiles", FOLDERID_ProgramFiles),
+    ("ProgramFilesX86", FOLDERID_ProgramFilesX86),
+    ("Fonts", FOLDERID_Fonts),
+];
+
+#[derive(Serialize)]
+pub struct KnownFolder {
+    pub token: String,
+    pub path: Option<String>,
+}
+
+pub fn known_folder_path(id: &GUID) -> Result<String> {
+    unsafe {
+        let path = SHGetKnownFolderPath(id, KF_FLAG_DEFAULT, HANDLE(0))?;
+        let value = path.to_string();
+        CoTaskMemFree(Some(path.0 as *const _));
+        value.map_err(|_| Error::new(E_INVALIDARG, "Known folder path is not valid UTF-16".into()))
+    }
+}
+
+/// Replaces a leading `{Token}` in `path` with the known folder it names.
+/// Paths without a token are returned unchanged.
+pub fn expand_known_folder(path: &str) -> Result<String> {
+    let Some(rest) = path.strip_prefix('{') else {
+        return Ok(path.to_string());
+    };
+    let Some((token, remainder)) = rest.split_once('}') else {
+        return Ok(path.to_string());
+    };
+
+    let (_, id) = find_known_folder(token)
+        .ok_or_else(|| Error::new(E_INVALIDARG, format!("Unknown known folder token: {{{}}}", token).into()))?;
+
+    Ok(format!("{}{}", known_folder_path(&id)?, remainder))
+}
+
+/// Looks a known folder up by its token name, ignoring case.
+pub fn find_known_folder(name: &str) -> Option<(&'static str, GUID)> {
+    KNOWN_FOLDERS.iter().find(|(known, _)| known.eq_ignore_ascii_case(name)).copied()
+}
+
+pub fn list_known_folders() -> Vec<KnownFolder> {
+    KNOWN_FOLDERS
+        .iter()
+        .map(|(name, id)| KnownFolder {
+            token: format!("{{{}}}", name),
+            path: known_folder_path(id).ok(),
+        })
+        .collect()
+}
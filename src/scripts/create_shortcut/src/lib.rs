@@ -0,0 +1,263 @@
+#[cfg(windows)]
+mod batch;
+#[cfg(windows)]
+pub mod create;
+#[cfg(windows)]
+mod data_blocks;
+#[cfg(windows)]
+mod delete;
+#[cfg(windows)]
+pub mod fslink;
+#[cfg(windows)]
+pub mod hotkey;
+#[cfg(windows)]
+mod jumplist;
+#[cfg(windows)]
+pub mod known_folders;
+#[cfg(windows)]
+mod link;
+#[cfg(windows)]
+mod options;
+#[cfg(windows)]
+mod paths;
+#[cfg(windows)]
+mod pin;
+#[cfg(windows)]
+mod propstore;
+#[cfg(windows)]
+pub mod read;
+#[cfg(windows)]
+mod resolve;
+#[cfg(windows)]
+pub mod url;
+#[cfg(windows)]
+mod validate;
+#[cfg(windows)]
+mod verify;
+#[cfg(target_os = "macos")]
+mod alias;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod desktop_entry;
+
+use std::ffi::OsString;
+#[cfg(windows)]
+use std::path::Path;
+use std::process::ExitCode;
+
+#[cfg(windows)]
+use windows::{core::*, Win32::Foundation::E_INVALIDARG};
+
+#[cfg(windows)]
+use altdesktop_core::error::{error_code, ErrorReport};
+#[cfg(windows)]
+use altdesktop_core::exit;
+#[cfg(windows)]
+use altdesktop_core::sandbox;
+#[cfg(windows)]
+use options::{parse_args, wants_json, Command, Options, USAGE};
+
+// What a successful command leaves for --json to report
+#[cfg(windows)]
+enum Outcome {
+    // The command already wrote its own JSON result
+    Printed,
+    Saved(String),
+    Done,
+}
+
+/// Runs one `altdesktop-helper shortcut` invocation; `args[0]` is the program name.
+/// Expects COM to be initialized, apartment-threaded, on the calling thread.
+#[cfg(windows)]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let json = args.iter().skip(1).any(|arg| arg == "--json");
+    let args: Vec<String> = match args.into_iter().map(|arg| arg.into_string()).collect() {
+        Ok(args) => args,
+        Err(arg) => return usage_error(&format!("Argument is not valid Unicode: {}", arg.to_string_lossy()), json),
+    };
+    let Options { mut command, json } = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message, wants_json(&args)),
+    };
+
+    let mut exit_code = exit::SUCCESS;
+    let result = expand_known_folders(&mut command)
+        .and_then(|_| check_paths(&mut command))
+        .and_then(|_| execute(command, &mut exit_code));
+
+    match result {
+        Ok(Outcome::Saved(path)) if json => println!("{}", serde_json::json!({ "ok": true, "path": path })),
+        Ok(Outcome::Done) if json => println!("{}", serde_json::json!({ "ok": true })),
+        Ok(_) => {}
+        Err(error) if json => {
+            let report = ErrorReport::from_error(&error);
+            exit_code = report.exit_code();
+            println!("{}", serde_json::to_string(&report).unwrap());
+        }
+        Err(error) => {
+            eprintln!("Error: {:?}", error);
+            exit_code = exit::for_code(error_code(error.code()));
+        }
+    }
+    ExitCode::from(exit_code)
+}
+
+#[cfg(windows)]
+fn usage_error(message: &str, json: bool) -> ExitCode {
+    if json {
+        println!("{}", serde_json::to_string(&ErrorReport::usage(message)).unwrap());
+    } else {
+        eprintln!("{}", message);
+        eprintln!("{}", USAGE);
+    }
+    ExitCode::from(exit::USAGE)
+}
+
+#[cfg(windows)]
+fn expand_known_folders(command: &mut Command) -> Result<()> {
+    for shortcut_path in command.shortcut_paths_mut() {
+        *shortcut_path = known_folders::expand_known_folder(shortcut_path)?;
+    }
+    Ok(())
+}
+
+// Every path it's given, for device names and the like, and the ones it writes
+// against --allowed-output-root too
+#[cfg(windows)]
+fn check_paths(command: &mut Command) -> Result<()> {
+    for path in command.shortcut_paths_mut() {
+        sandbox::check_input(path)?;
+    }
+    if let Command::FsLink { target_path, .. } = command {
+        sandbox::check_input(target_path)?;
+    }
+    for path in command.output_paths() {
+        sandbox::check_output(Path::new(path))?;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn execute(command: Command, exit_code: &mut u8) -> Result<Outcome> {
+    match command {
+        Command::Create { shortcut_path, fields, pins } => create::create_shortcut(&shortcut_path, &fields)
+            .and_then(|_| apply_pins(&shortcut_path, &pins))
+            .map(|_| Outcome::Saved(shortcut_path)),
+        Command::Edit { shortcut_path, fields, pins } => create::edit_shortcut(&shortcut_path, &fields)
+            .and_then(|_| apply_pins(&shortcut_path, &pins))
+            .map(|_| Outcome::Saved(shortcut_path)),
+        Command::Clone { source_path, shortcut_path, fields } => {
+            create::clone_shortcut(&source_path, &shortcut_path, &fields).map(|_| Outcome::Saved(shortcut_path))
+        }
+        Command::PinState { shortcut_path } => pin::pin_state(&shortcut_path).map(|state| {
+            println!("{}", serde_json::to_string(&state).unwrap());
+            Outcome::Printed
+        }),
+        Command::Read { shortcut_path } => read::read_shortcut(&shortcut_path).map(|info| {
+            println!("{}", serde_json::to_string(&info).unwrap());
+            Outcome::Printed
+        }),
+        Command::Resolve { shortcut_path, save } => {
+            resolve::resolve_shortcut(&shortcut_path, save).map(|result| {
+                println!("{}", serde_json::to_string(&result).unwrap());
+                if !result.resolved {
+                    *exit_code = exit::FAILURE;
+                }
+                Outcome::Printed
+            })
+        }
+        Command::Verify { shortcut_path } => verify::verify_shortcut(&shortcut_path).map(|report| {
+            println!("{}", serde_json::to_string(&report).unwrap());
+            if !report.ok {
+                *exit_code = exit::FAILURE;
+            }
+            Outcome::Printed
+        }),
+        Command::Validate { shortcut_path, fields } => {
+            let (report, issue) = validate::validate_shortcut(&shortcut_path, &fields);
+            println!("{}", serde_json::to_string(&report).unwrap());
+            if let Some(issue) = issue {
+                *exit_code = issue.exit_code();
+            }
+            Ok(Outcome::Printed)
+        }
+        Command::Delete { shortcut_path } => {
+            delete::delete_shortcut(&shortcut_path).map(|_| Outcome::Saved(shortcut_path))
+        }
+        Command::Url { url, shortcut_path, fields } => {
+            url::create_url_shortcut(&url, &shortcut_path, &fields).map(|_| Outcome::Saved(shortcut_path))
+        }
+        Command::FsLink { target_path, link_path, link_type } => {
+            fslink::create_fs_link(&target_path, &link_path, link_type).map(|created| {
+                println!("{}", serde_json::json!({ "linkType": created.name() }));
+                Outcome::Printed
+            })
+        }
+        Command::JumpList { app_id, clear: true } => jumplist::clear_jump_list(&app_id).map(|_| Outcome::Done),
+        Command::JumpList { app_id, clear: false } => jumplist::update_jump_list(&app_id).map(|_| Outcome::Done),
+        Command::ListKnownFolders => {
+            println!("{}", serde_json::to_string(&known_folders::list_known_folders()).unwrap());
+            Ok(Outcome::Printed)
+        }
+        Command::Batch { progress } => match batch::run_batch(progress) {
+            Ok(all_ok) => {
+                if !all_ok {
+                    *exit_code = exit::FAILURE;
+                }
+                Ok(Outcome::Printed)
+            }
+            Err(message) => Err(Error::new(E_INVALIDARG, message.into())),
+        },
+    }
+}
+
+/// Creates one shortcut from a batch spec object and returns {"path": ...} instead of
+/// printing it. Errors are the object `--json` prints. Used by `altdesktop-helper --serve`.
+#[cfg(windows)]
+pub fn create_json(spec: serde_json::Value) -> std::result::Result<serde_json::Value, serde_json::Value> {
+    let result = serde_json::from_value(spec)
+        .map_err(|e| Error::new(E_INVALIDARG, format!("Invalid shortcut spec: {}", e).into()))
+        .and_then(batch::create_from_spec);
+    match result {
+        Ok(path) => Ok(serde_json::json!({ "path": path })),
+        Err(error) => Err(serde_json::to_value(ErrorReport::from_error(&error)).unwrap()),
+    }
+}
+
+/// Runs link tracking over the shortcut in {"path", "save"} and returns the `--resolve`
+/// result. An unresolved shortcut is still a result, with `resolved` false.
+#[cfg(windows)]
+pub fn resolve_json(request: serde_json::Value) -> std::result::Result<serde_json::Value, serde_json::Value> {
+    let result = serde_json::from_value::<resolve::ResolveRequest>(request)
+        .map_err(|e| Error::new(E_INVALIDARG, format!("Invalid resolve request: {}", e).into()))
+        .and_then(|request| {
+            let path = known_folders::expand_known_folder(&request.path)?;
+            sandbox::check_input(&path)?;
+            if request.save {
+                sandbox::check_output(Path::new(&path))?;
+            }
+            resolve::resolve_shortcut(&path, request.save)
+        });
+    match result {
+        Ok(result) => Ok(serde_json::to_value(result).unwrap()),
+        Err(error) => Err(serde_json::to_value(ErrorReport::from_error(&error)).unwrap()),
+    }
+}
+
+#[cfg(windows)]
+fn apply_pins(shortcut_path: &str, pins: &[pin::PinRequest]) -> Result<()> {
+    pins.iter().try_for_each(|request| pin::apply_pin(shortcut_path, *request))
+}
+
+/// Runs one `altdesktop-helper shortcut` invocation; `args[0]` is the program name.
+// Desktop entries are the only shortcut type on other Unix desktops
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    desktop_entry::run(args)
+}
+
+/// Runs one `altdesktop-helper shortcut` invocation; `args[0]` is the program name.
+// Finder has aliases where other desktops have launcher files
+#[cfg(target_os = "macos")]
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    alias::run(args)
+}
@@ -0,0 +1,156 @@
+use altdesktop_core::path::{expand_env_vars, extended_length_path, PATH_BUFFER_LEN};
+use altdesktop_core::wide::{from_wide, to_wide};
+use windows::{
+    core::*,
+    Win32::Foundation::E_INVALIDARG,
+    Win32::System::Com::*,
+    Win32::UI::Shell::*,
+};
+
+pub fn has_env_vars(value: &str) -> bool {
+    value.matches('%').count() >= 2
+}
+
+pub fn new_shell_link() -> Result<IShellLinkW> {
+    unsafe { CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER) }
+}
+
+pub fn load_shell_link(shortcut_path: &str) -> Result<IShellLinkW> {
+    unsafe {
+        let shell = new_shell_link()?;
+        let persist_file: IPersistFile = shell.cast()?;
+        let shortcut_wide = to_wide(&extended_length_path(shortcut_path));
+        persist_file.Load(PCWSTR(shortcut_wide.as_ptr()), STGM_READ)?;
+        Ok(shell)
+    }
+}
+
+pub fn save_shell_link(shell: &IShellLinkW, shortcut_path: &str) -> Result<()> {
+    unsafe {
+        // Cast to IPersistFile interface
+        let persist_file: IPersistFile = shell.cast()?;
+        let shortcut_wide = to_wide(&extended_length_path(shortcut_path));
+        persist_file.Save(PCWSTR(shortcut_wide.as_ptr()), true)
+    }
+}
+
+/// Reads the SLDF_* flags stored in the shortcut's header.
+pub fn link_flags(shell: &IShellLinkW) -> Result<u32> {
+    unsafe {
+        let data_list: IShellLinkDataList = shell.cast()?;
+        data_list.GetFlags()
+    }
+}
+
+pub fn set_link_flag(shell: &IShellLinkW, flag: SHELL_LINK_DATA_FLAGS, enabled: bool) -> Result<()> {
+    unsafe {
+        let data_list: IShellLinkDataList = shell.cast()?;
+        let flags = data_list.GetFlags()?;
+        let flags = if enabled {
+            flags | flag.0 as u32
+        } else {
+            flags & !(flag.0 as u32)
+        };
+        data_list.SetFlags(flags)
+    }
+}
+
+/// Shell namespace targets such as `shell:AppsFolder\<PackageFamilyName>!<AppId>` or a
+/// CLSID path like `::{20D04FE0-3AEA-1069-A2D8-08002B30309D}` (This PC) don't exist on
+/// disk, so they have to be stored as an ID list rather than through `SetPath`.
+pub fn is_shell_target(target_path: &str) -> bool {
+    target_path.starts_with("::{")
+        || target_path
+            .get(..6)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("shell:"))
+}
+
+/// Parses a shell namespace name into an ID list. Free the result with `ILFree`.
+pub fn parse_shell_target(parsing_name: &str) -> Result<*mut Common::ITEMIDLIST> {
+    unsafe {
+        let mut pidl = std::ptr::null_mut();
+        SHParseDisplayName(&HSTRING::from(parsing_name), None::<&IBindCtx>, &mut pidl, 0, None)?;
+        Ok(pidl)
+    }
+}
+
+pub fn set_target_id_list(shell: &IShellLinkW, parsing_name: &str) -> Result<()> {
+    unsafe {
+        let pidl = parse_shell_target(parsing_name)?;
+        let result = shell.SetIDList(pidl);
+        ILFree(Some(pidl));
+        result
+    }
+}
+
+/// The shortcut's target path, falling back to the ID list's parsing name for
+/// namespace targets that have no file system path.
+pub fn target_path(shell: &IShellLinkW) -> Result<String> {
+    let mut buffer = vec![0u16; PATH_BUFFER_LEN];
+    unsafe {
+        shell.GetPath(&mut buffer, std::ptr::null_mut(), 0)?;
+    }
+    let path = from_wide(&buffer);
+    if path.is_empty() {
+        target_parsing_name(shell)
+    } else {
+        Ok(path)
+    }
+}
+
+fn target_parsing_name(shell: &IShellLinkW) -> Result<String> {
+    unsafe {
+        let pidl = shell.GetIDList()?;
+        if pidl.is_null() {
+            return Ok(String::new());
+        }
+        let name = SHGetNameFromIDList(pidl, SIGDN_DESKTOPABSOLUTEPARSING);
+        ILFree(Some(pidl));
+        let name = name?;
+        let value = name.to_string().unwrap_or_default();
+        CoTaskMemFree(Some(name.0 as *const _));
+        Ok(value)
+    }
+}
+
+/// Stores a target such as `%PROGRAMFILES%\App\app.exe` unexpanded in an EXP_SZ_LINK
+/// block, the same way Explorer does, so it is re-expanded every time the link is used.
+pub fn set_expandable_target(shell: &IShellLinkW, raw_target: &str) -> Result<()> {
+    unsafe {
+        shell.SetPath(&HSTRING::from(expand_env_vars(raw_target)))?;
+
+        let wide: Vec<u16> = raw_target.encode_utf16().collect();
+        let mut ansi_target = [0u8; 260];
+        let mut wide_target = [0u16; 260];
+        if wide.len() >= wide_target.len() {
+            return Err(Error::new(E_INVALIDARG, "Expandable target is too long".into()));
+        }
+        // The ANSI copy is only read by pre-Unicode shells; ASCII is enough there
+        for (i, &w) in wide.iter().enumerate() {
+            ansi_target[i] = if w < 0x80 { w as u8 } else { b'?' };
+            wide_target[i] = w;
+        }
+        let block = EXP_SZ_LINK {
+            cbSize: std::mem::size_of::<EXP_SZ_LINK>() as u32,
+            dwSignature: EXP_SZ_LINK_SIG,
+            szTarget: ansi_target,
+            swzTarget: wide_target,
+        };
+
+        let data_list: IShellLinkDataList = shell.cast()?;
+        let _ = data_list.RemoveDataBlock(EXP_SZ_LINK_SIG);
+        data_list.AddDataBlock(&block as *const _ as *const _)?;
+    }
+    set_link_flag(shell, SLDF_HAS_EXP_SZ, true)
+}
+
+pub fn clear_expandable_target(shell: &IShellLinkW) -> Result<()> {
+    if link_flags(shell)? & SLDF_HAS_EXP_SZ.0 as u32 == 0 {
+        return Ok(());
+    }
+    unsafe {
+        let data_list: IShellLinkDataList = shell.cast()?;
+        let _ = data_list.RemoveDataBlock(EXP_SZ_LINK_SIG);
+    }
+    set_link_flag(shell, SLDF_HAS_EXP_SZ, false)
+}
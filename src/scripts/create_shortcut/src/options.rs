@@ -0,0 +1,343 @@
+use windows::Win32::UI::WindowsAndMessaging::{
+    SHOW_WINDOW_CMD, SW_SHOWMAXIMIZED, SW_SHOWMINNOACTIVE, SW_SHOWNORMAL,
+};
+
+use crate::fslink::LinkType;
+use crate::hotkey::parse_hotkey;
+use crate::pin::{PinLocation, PinRequest};
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper shortcut create <targetPath> <shortcutPath> [fields]
+  altdesktop-helper shortcut validate <targetPath> <shortcutPath> [fields]
+  altdesktop-helper shortcut edit <shortcutPath> [--target <targetPath>] [fields]
+  altdesktop-helper shortcut clone <sourcePath> <destPath> [--target <targetPath>] [fields]
+  altdesktop-helper shortcut read <shortcutPath>
+  altdesktop-helper shortcut pin-state <shortcutPath>
+  altdesktop-helper shortcut resolve <shortcutPath> [--save]
+  altdesktop-helper shortcut verify <shortcutPath>
+  altdesktop-helper shortcut delete <shortcutPath>
+  altdesktop-helper shortcut url <url> <shortcutPath.url> [--icon <path>] [--icon-index <index>]
+  altdesktop-helper shortcut fs-link <targetPath> <linkPath> [--link-type auto|symlink|junction|hardlink]
+  altdesktop-helper shortcut jump-list <AppUserModelID> [--clear] < jumplist.json
+  altdesktop-helper shortcut batch [--progress] < specs.json
+  altdesktop-helper shortcut list-known-folders
+
+<shortcutPath> may start with a known folder token such as {Desktop}, {StartMenu} or {Startup}.
+Add --json to any mode to get {\"ok\":true,...} or {\"ok\":false,\"code\":...,\"hr\":...} on stdout
+instead of an error on stderr.
+
+Fields:
+  --args <arguments>  --working-dir <path>  --description <text>  --icon <path>  --icon-index <index>
+  --hotkey <Ctrl+Alt+Key>  --show normal|minimized|maximized
+  --run-as-admin  --no-run-as-admin  --app-id <AppUserModelID>
+  --base-dir <dir>  (resolves a relative <targetPath>; %VAR% targets are kept unexpanded)
+  --pin taskbar|start  --unpin taskbar|start  (create and edit only, applied after saving)
+
+read prints every field create can set, plus the shortcut's extra data blocks, each
+null when it has none: expandableTarget and expandableIcon, the %VAR% forms of the
+target and icon; specialFolder {\"csidl\",\"path\",\"offset\"} and knownFolder
+{\"id\",\"token\",\"path\",\"offset\"}, the folder the target's ID list starts in;
+console, the window a console program opens in (font, buffer and window sizes,
+colors, code page and edit modes); and advertised
+{\"descriptor\",\"productCode\",\"featureId\",\"componentCode\",\"state\",\"path\"}
+for a shortcut Windows Installer advertised, such as Office's. Its targetPath is the
+program the installer starts, once state is local or source, rather than the
+installer's stand-in.
+
+batch --progress prints {\"type\":\"progress\",\"id\",\"completed\",\"total\",\"item\"} as each
+shortcut is made, before the array of results, id being the spec's index and item its path.";
+
+pub struct Options {
+    pub command: Command,
+    pub json: bool,
+}
+
+pub enum Command {
+    Create { shortcut_path: String, fields: ShortcutFields, pins: Vec<PinRequest> },
+    Edit { shortcut_path: String, fields: ShortcutFields, pins: Vec<PinRequest> },
+    Clone { source_path: String, shortcut_path: String, fields: ShortcutFields },
+    Read { shortcut_path: String },
+    PinState { shortcut_path: String },
+    Resolve { shortcut_path: String, save: bool },
+    Verify { shortcut_path: String },
+    Validate { shortcut_path: String, fields: ShortcutFields },
+    Delete { shortcut_path: String },
+    Url { url: String, shortcut_path: String, fields: ShortcutFields },
+    FsLink { target_path: String, link_path: String, link_type: LinkType },
+    JumpList { app_id: String, clear: bool },
+    Batch { progress: bool },
+    ListKnownFolders,
+}
+
+impl Command {
+    /// The .lnk/.url paths the command operates on.
+    pub fn shortcut_paths_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Command::Clone { source_path, shortcut_path, .. } => vec![source_path, shortcut_path],
+            Command::Create { shortcut_path, .. }
+            | Command::Edit { shortcut_path, .. }
+            | Command::Read { shortcut_path }
+            | Command::PinState { shortcut_path }
+            | Command::Resolve { shortcut_path, .. }
+            | Command::Verify { shortcut_path }
+            | Command::Validate { shortcut_path, .. }
+            | Command::Delete { shortcut_path }
+            | Command::Url { shortcut_path, .. } => vec![shortcut_path],
+            Command::FsLink { link_path, .. } => vec![link_path],
+            Command::JumpList { .. } | Command::Batch { .. } | Command::ListKnownFolders => Vec::new(),
+        }
+    }
+
+    /// The paths the command writes or deletes, which `--allowed-output-root` confines.
+    pub fn output_paths(&self) -> Vec<&str> {
+        match self {
+            Command::Create { shortcut_path, .. }
+            | Command::Edit { shortcut_path, .. }
+            | Command::Clone { shortcut_path, .. }
+            | Command::Resolve { shortcut_path, save: true }
+            | Command::Delete { shortcut_path }
+            | Command::Url { shortcut_path, .. } => vec![shortcut_path],
+            Command::FsLink { link_path, .. } => vec![link_path],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Shortcut properties passed on the command line. `None` means "leave as is"
+/// when editing, or "use the default" when creating.
+#[derive(Default)]
+pub struct ShortcutFields {
+    pub target_path: Option<String>,
+    pub base_dir: Option<String>,
+    pub working_dir: Option<String>,
+    pub arguments: Option<String>,
+    pub description: Option<String>,
+    pub icon_path: Option<String>,
+    pub icon_index: Option<i32>,
+    pub hotkey: Option<u16>,
+    pub show_cmd: Option<SHOW_WINDOW_CMD>,
+    pub run_as_admin: Option<bool>,
+    pub app_user_model_id: Option<String>,
+}
+
+impl ShortcutFields {
+    // The first option given that only a .lnk has a place for
+    fn link_only_flag(&self) -> Option<&'static str> {
+        [
+            (self.target_path.is_some(), "--target"),
+            (self.base_dir.is_some(), "--base-dir"),
+            (self.working_dir.is_some(), "--working-dir"),
+            (self.arguments.is_some(), "--args"),
+            (self.description.is_some(), "--description"),
+            (self.hotkey.is_some(), "--hotkey"),
+            (self.show_cmd.is_some(), "--show"),
+            (self.run_as_admin == Some(true), "--run-as-admin"),
+            (self.run_as_admin == Some(false), "--no-run-as-admin"),
+            (self.app_user_model_id.is_some(), "--app-id"),
+        ]
+        .into_iter()
+        .find_map(|(given, flag)| given.then_some(flag))
+    }
+}
+
+// The flag that picked the mode, with its value
+enum Mode {
+    Create,
+    Read(String),
+    PinState(String),
+    Edit(String),
+    Clone(String),
+    Resolve(String),
+    Verify(String),
+    Delete(String),
+    Url(String),
+    FsLink,
+    JumpList(String),
+    Batch,
+    ListKnownFolders,
+}
+
+/// Checked before parsing so that usage errors can be reported as JSON too.
+pub fn wants_json(args: &[String]) -> bool {
+    args.iter().skip(1).any(|arg| arg == "--json")
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut mode = Mode::Create;
+    let mut json = false;
+    let mut mode_flag = None;
+    let mut save = false;
+    let mut validate = false;
+    let mut link_type = None;
+    let mut pins = Vec::new();
+    let mut clear = false;
+    let mut progress = false;
+    let mut positional = Vec::new();
+    let mut fields = ShortcutFields::default();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("{} requires a value", flag))
+        };
+        let new_mode = match flag {
+            "--read" => Some(Mode::Read(value()?)),
+            "--pin-state" => Some(Mode::PinState(value()?)),
+            "--edit" => Some(Mode::Edit(value()?)),
+            "--clone" => Some(Mode::Clone(value()?)),
+            "--resolve" => Some(Mode::Resolve(value()?)),
+            "--verify" => Some(Mode::Verify(value()?)),
+            "--delete" => Some(Mode::Delete(value()?)),
+            "--url" => Some(Mode::Url(value()?)),
+            "--fs-link" => Some(Mode::FsLink),
+            "--jump-list" => Some(Mode::JumpList(value()?)),
+            "--batch" => Some(Mode::Batch),
+            "--list-known-folders" => Some(Mode::ListKnownFolders),
+            _ => None,
+        };
+        if let Some(new_mode) = new_mode {
+            if let Some(previous) = mode_flag.replace(flag) {
+                return Err(format!("{} cannot be combined with {}", flag, previous));
+            }
+            mode = new_mode;
+            continue;
+        }
+
+        match flag {
+            "--json" => json = true,
+            "--save" => save = true,
+            "--validate" => validate = true,
+            "--pin" => pins.push(PinRequest { location: PinLocation::parse(&value()?)?, pin: true }),
+            "--unpin" => pins.push(PinRequest { location: PinLocation::parse(&value()?)?, pin: false }),
+            "--clear" => clear = true,
+            "--progress" => progress = true,
+            "--link-type" => link_type = Some(LinkType::parse(&value()?)?),
+            "--target" => fields.target_path = Some(value()?),
+            "--base-dir" => fields.base_dir = Some(value()?),
+            "--working-dir" => fields.working_dir = Some(value()?),
+            "--args" => fields.arguments = Some(value()?),
+            "--description" => fields.description = Some(value()?),
+            "--icon" => fields.icon_path = Some(value()?),
+            "--icon-index" => {
+                let value = value()?;
+                fields.icon_index = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid icon index: {}", value))?,
+                );
+            }
+            "--hotkey" => fields.hotkey = Some(parse_hotkey(&value()?)?),
+            "--show" => fields.show_cmd = Some(parse_show_cmd(&value()?)?),
+            "--run-as-admin" => fields.run_as_admin = Some(true),
+            "--no-run-as-admin" => fields.run_as_admin = Some(false),
+            "--app-id" => fields.app_user_model_id = Some(value()?),
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let expected_positional = match mode {
+        Mode::Create | Mode::FsLink => 2,
+        Mode::Url(_) | Mode::Clone(_) => 1,
+        _ => 0,
+    };
+    if positional.len() != expected_positional {
+        return Err(match mode {
+            Mode::Create => "Expected <targetPath> and <shortcutPath>".to_string(),
+            Mode::FsLink => "--fs-link expects <targetPath> and <linkPath>".to_string(),
+            Mode::Url(_) => "--url expects a single <shortcutPath>".to_string(),
+            Mode::Clone(_) => "--clone expects a single <destPath>".to_string(),
+            _ => format!("{} does not take positional arguments", mode_flag.unwrap_or_default()),
+        });
+    }
+
+    if clear && !matches!(mode, Mode::JumpList(_)) {
+        return Err("--clear only applies to --jump-list".to_string());
+    }
+
+    if progress && !matches!(mode, Mode::Batch) {
+        return Err("--progress only applies to --batch".to_string());
+    }
+
+    if link_type.is_some() && !matches!(mode, Mode::FsLink) {
+        return Err("--link-type only applies to --fs-link".to_string());
+    }
+
+    if let (Mode::Url(_), Some(flag)) = (&mode, fields.link_only_flag()) {
+        return Err(format!("{} cannot be combined with --url", flag));
+    }
+
+    if save && !matches!(mode, Mode::Resolve(_)) {
+        return Err("--save only applies to --resolve".to_string());
+    }
+
+    if !pins.is_empty() && (validate || !matches!(mode, Mode::Create | Mode::Edit(_))) {
+        return Err("--pin and --unpin only apply to creating or editing shortcuts".to_string());
+    }
+
+    if validate && !matches!(mode, Mode::Create | Mode::Url(_)) {
+        return Err("--validate only applies to creating shortcuts".to_string());
+    }
+
+    let command = match mode {
+        Mode::Create if validate => {
+            let shortcut_path = positional.pop().unwrap();
+            fields.target_path = positional.pop();
+            Command::Validate { shortcut_path, fields }
+        }
+        Mode::Url(_) if validate => Command::Validate {
+            shortcut_path: positional.pop().unwrap(),
+            fields,
+        },
+        Mode::Create => {
+            if fields.target_path.is_some() {
+                return Err("--target is only valid with --edit".to_string());
+            }
+            let shortcut_path = positional.pop().unwrap();
+            fields.target_path = positional.pop();
+            Command::Create { shortcut_path, fields, pins }
+        }
+        Mode::Read(shortcut_path) => Command::Read { shortcut_path },
+        Mode::PinState(shortcut_path) => Command::PinState { shortcut_path },
+        Mode::Edit(shortcut_path) => Command::Edit { shortcut_path, fields, pins },
+        Mode::Clone(source_path) => Command::Clone {
+            source_path,
+            shortcut_path: positional.pop().unwrap(),
+            fields,
+        },
+        Mode::Resolve(shortcut_path) => Command::Resolve { shortcut_path, save },
+        Mode::Verify(shortcut_path) => Command::Verify { shortcut_path },
+        Mode::Delete(shortcut_path) => Command::Delete { shortcut_path },
+        Mode::Url(url) => Command::Url {
+            url,
+            shortcut_path: positional.pop().unwrap(),
+            fields,
+        },
+        Mode::FsLink => {
+            let link_path = positional.pop().unwrap();
+            let target_path = positional.pop().unwrap();
+            Command::FsLink {
+                target_path,
+                link_path,
+                link_type: link_type.unwrap_or(LinkType::Auto),
+            }
+        }
+        Mode::JumpList(app_id) => Command::JumpList { app_id, clear },
+        Mode::Batch => Command::Batch { progress },
+        Mode::ListKnownFolders => Command::ListKnownFolders,
+    };
+    Ok(Options { command, json })
+}
+
+// Explorer's "Run" dropdown only offers these three states
+pub fn parse_show_cmd(value: &str) -> Result<SHOW_WINDOW_CMD, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "normal" => Ok(SW_SHOWNORMAL),
+        "minimized" => Ok(SW_SHOWMINNOACTIVE),
+        "maximized" => Ok(SW_SHOWMAXIMIZED),
+        _ => Err(format!("Invalid show command: {}", value)),
+    }
+}
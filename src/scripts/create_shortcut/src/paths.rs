@@ -0,0 +1,20 @@
+use altdesktop_core::path::{extended_length_path, strip_extended_prefix, wide_len, MAX_PATH, PATH_BUFFER_LEN};
+use altdesktop_core::wide::from_wide;
+use windows::{core::*, Win32::Storage::FileSystem::*};
+
+/// The shell link format keeps the target, working directory and icon in MAX_PATH
+/// sized fields. Paths that don't fit fall back to their 8.3 alias when the volume has one.
+pub fn link_field_path(path: &str) -> String {
+    let plain = strip_extended_prefix(path);
+    if wide_len(&plain) < MAX_PATH {
+        return plain;
+    }
+
+    let mut buffer = vec![0u16; PATH_BUFFER_LEN];
+    let long = HSTRING::from(extended_length_path(&plain));
+    let len = unsafe { GetShortPathNameW(&long, Some(&mut buffer)) };
+    if len == 0 || len as usize > buffer.len() {
+        return plain;
+    }
+    strip_extended_prefix(&from_wide(&buffer))
+}
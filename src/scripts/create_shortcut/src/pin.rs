@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Foundation::HWND,
+    Win32::System::Com::IBindCtx,
+    Win32::UI::Shell::*,
+    Win32::UI::WindowsAndMessaging::{CreatePopupMenu, DestroyMenu, SW_SHOWNORMAL},
+};
+
+use crate::known_folders::known_folder_path;
+use crate::link::{load_shell_link, target_path};
+
+const CMIC_MASK_FLAG_NO_UI: u32 = 0x0000_0400;
+
+#[derive(Clone, Copy)]
+pub enum PinLocation {
+    Taskbar,
+    Start,
+}
+
+impl PinLocation {
+    pub fn parse(value: &str) -> std::result::Result<PinLocation, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "taskbar" => Ok(PinLocation::Taskbar),
+            "start" => Ok(PinLocation::Start),
+            _ => Err(format!("Invalid pin location: {}", value)),
+        }
+    }
+
+    fn verb(self, pin: bool) -> &'static str {
+        match (self, pin) {
+            (PinLocation::Taskbar, true) => "taskbarpin\0",
+            (PinLocation::Taskbar, false) => "taskbarunpin\0",
+            (PinLocation::Start, true) => "startpin\0",
+            (PinLocation::Start, false) => "startunpin\0",
+        }
+    }
+}
+
+/// A pin or unpin to perform once the shortcut has been saved.
+#[derive(Clone, Copy)]
+pub struct PinRequest {
+    pub location: PinLocation,
+    pub pin: bool,
+}
+
+#[derive(Serialize)]
+pub struct PinState {
+    pub taskbar: bool,
+    /// The Start layout isn't readable through a public API, so this is always unknown.
+    pub start: Option<bool>,
+}
+
+/// Invokes the shell's pin verbs on a shortcut. Newer Windows builds may refuse these for
+/// third-party callers, in which case the verb lookup fails and the error is returned.
+pub fn apply_pin(shortcut_path: &str, request: PinRequest) -> Result<()> {
+    unsafe {
+        let item: IShellItem = SHCreateItemFromParsingName(&HSTRING::from(shortcut_path), None::<&IBindCtx>)?;
+        let menu: IContextMenu = item.BindToHandler(None::<&IBindCtx>, &BHID_SFUIObject)?;
+
+        // Some handlers only register their verbs once the menu has been built
+        let popup = CreatePopupMenu()?;
+        let built = menu.QueryContextMenu(popup, 0, 1, 0x7FFF, CMF_NORMAL);
+        let _ = DestroyMenu(popup);
+        built?;
+
+        let verb = request.location.verb(request.pin);
+        let info = CMINVOKECOMMANDINFO {
+            cbSize: std::mem::size_of::<CMINVOKECOMMANDINFO>() as u32,
+            fMask: CMIC_MASK_FLAG_NO_UI,
+            hwnd: HWND(0),
+            lpVerb: PCSTR(verb.as_ptr()),
+            nShow: SW_SHOWNORMAL.0,
+            ..Default::default()
+        };
+        menu.InvokeCommand(&info)
+    }
+}
+
+/// Checks the taskbar's "User Pinned" folder for a shortcut with the same target.
+pub fn pin_state(shortcut_path: &str) -> Result<PinState> {
+    let target = target_path(&load_shell_link(shortcut_path)?)?;
+    let pinned_dir = Path::new(&known_folder_path(&FOLDERID_UserPinned)?).join("TaskBar");
+
+    let taskbar = fs::read_dir(pinned_dir)
+        .map(|entries| {
+            entries.flatten().any(|entry| {
+                let path = entry.path();
+                path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("lnk"))
+                    && load_shell_link(&path.to_string_lossy())
+                        .and_then(|shell| target_path(&shell))
+                        .is_ok_and(|pinned| pinned.eq_ignore_ascii_case(&target))
+            })
+        })
+        .unwrap_or(false);
+
+    Ok(PinState { taskbar, start: None })
+}
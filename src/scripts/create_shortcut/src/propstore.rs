@@ -0,0 +1,42 @@
+use windows::{
+    core::*,
+    Win32::System::Com::CoTaskMemFree,
+    Win32::System::Com::StructuredStorage::*,
+    Win32::System::Variant::{VT_EMPTY, VT_LPWSTR},
+    Win32::UI::Shell::PropertiesSystem::*,
+    Win32::UI::Shell::*,
+};
+
+/// Writes a string property (e.g. PKEY_AppUserModel_ID) into the shortcut's property
+/// store. An empty value removes the property.
+pub fn set_string_property(shell: &IShellLinkW, key: &PROPERTYKEY, value: &str) -> Result<()> {
+    unsafe {
+        let store: IPropertyStore = shell.cast()?;
+        let mut variant = PROPVARIANT::default();
+        if !value.is_empty() {
+            let inner = &mut variant.Anonymous.Anonymous;
+            inner.vt = VT_LPWSTR;
+            inner.Anonymous.pwszVal = SHStrDupW(&HSTRING::from(value))?;
+        }
+        let result = store.SetValue(key, &variant).and_then(|_| store.Commit());
+        PropVariantClear(&mut variant)?;
+        result
+    }
+}
+
+pub fn get_string_property(shell: &IShellLinkW, key: &PROPERTYKEY) -> Result<Option<String>> {
+    unsafe {
+        let store: IPropertyStore = shell.cast()?;
+        let mut variant = store.GetValue(key)?;
+        let value = if variant.Anonymous.Anonymous.vt == VT_EMPTY {
+            None
+        } else {
+            let text = PropVariantToStringAlloc(&variant)?;
+            let value = text.to_string().ok();
+            CoTaskMemFree(Some(text.0 as *const _));
+            value
+        };
+        PropVariantClear(&mut variant)?;
+        Ok(value)
+    }
+}
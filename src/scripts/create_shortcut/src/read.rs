@@ -0,0 +1,90 @@
+use altdesktop_core::path::PATH_BUFFER_LEN;
+use altdesktop_core::wide::from_wide;
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Storage::EnhancedStorage::PKEY_AppUserModel_ID,
+    Win32::UI::Shell::SLDF_RUNAS_USER,
+    Win32::UI::WindowsAndMessaging::{SW_SHOWMAXIMIZED, SW_SHOWMINNOACTIVE},
+};
+
+use crate::data_blocks::{read_data_blocks, DataBlocks};
+use crate::hotkey::format_hotkey;
+use crate::link::{link_flags, load_shell_link, target_path};
+use crate::propstore::get_string_property;
+
+const INFOTIPSIZE: usize = 1024;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShortcutInfo {
+    pub target_path: String,
+    pub arguments: String,
+    pub working_directory: String,
+    pub description: String,
+    pub icon_location: String,
+    pub icon_index: i32,
+    pub hotkey: Option<String>,
+    pub show_cmd: &'static str,
+    pub run_as_admin: bool,
+    pub app_user_model_id: Option<String>,
+    #[serde(flatten)]
+    pub data_blocks: DataBlocks,
+}
+
+/// Reads back every field `create` can set on a shortcut, and the extra data
+/// blocks after them. An advertised shortcut's target is the installed program it
+/// starts rather than the installer's stand-in, when the installer knows it.
+pub fn read_shortcut(shortcut_path: &str) -> Result<ShortcutInfo> {
+    unsafe {
+        let shell = load_shell_link(shortcut_path)?;
+        let mut buffer = vec![0u16; PATH_BUFFER_LEN];
+
+        let data_blocks = read_data_blocks(&shell, shortcut_path)?;
+        let target_path = match data_blocks.advertised.as_ref().and_then(|advertised| advertised.path.clone()) {
+            Some(path) => path,
+            None => target_path(&shell)?,
+        };
+
+        shell.GetArguments(&mut buffer)?;
+        let arguments = from_wide(&buffer);
+
+        buffer.fill(0);
+        shell.GetWorkingDirectory(&mut buffer)?;
+        let working_directory = from_wide(&buffer);
+
+        buffer.fill(0);
+        shell.GetDescription(&mut buffer[..INFOTIPSIZE])?;
+        let description = from_wide(&buffer);
+
+        buffer.fill(0);
+        let mut icon_index = 0;
+        shell.GetIconLocation(&mut buffer, &mut icon_index)?;
+        let icon_location = from_wide(&buffer);
+
+        let hotkey = format_hotkey(shell.GetHotkey()?);
+
+        let show_cmd = match shell.GetShowCmd()? {
+            SW_SHOWMINNOACTIVE => "minimized",
+            SW_SHOWMAXIMIZED => "maximized",
+            _ => "normal",
+        };
+
+        let run_as_admin = link_flags(&shell)? & SLDF_RUNAS_USER.0 as u32 != 0;
+        let app_user_model_id = get_string_property(&shell, &PKEY_AppUserModel_ID)?;
+
+        Ok(ShortcutInfo {
+            target_path,
+            arguments,
+            working_directory,
+            description,
+            icon_location,
+            icon_index,
+            hotkey,
+            show_cmd,
+            run_as_admin,
+            app_user_model_id,
+            data_blocks,
+        })
+    }
+}
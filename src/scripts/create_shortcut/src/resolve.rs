@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use windows::{
+    core::*,
+    Win32::Foundation::HWND,
+    Win32::UI::Shell::*,
+};
+
+use crate::link::{load_shell_link, save_shell_link, target_path};
+
+// How long link tracking may search for a moved target before giving up
+const RESOLVE_TIMEOUT_MS: u32 = 3000;
+
+/// The `--resolve` arguments as a JSON object, for callers that send requests
+/// rather than command lines.
+#[derive(Deserialize)]
+pub struct ResolveRequest {
+    pub path: String,
+    #[serde(default)]
+    pub save: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolveResult {
+    pub resolved: bool,
+    pub original_target: String,
+    pub target_path: String,
+    pub changed: bool,
+    pub saved: bool,
+}
+
+/// Runs Windows' link tracking over a shortcut without showing any UI. When the
+/// target moved and `save` is set, the corrected shortcut is written back.
+pub fn resolve_shortcut(shortcut_path: &str, save: bool) -> Result<ResolveResult> {
+    let shell = load_shell_link(shortcut_path)?;
+    let original_target = target_path(&shell)?;
+
+    // With SLR_NO_UI the high word of the flags is the timeout in milliseconds
+    let timeout = altdesktop_core::config::get().timeouts.resolve.unwrap_or(RESOLVE_TIMEOUT_MS).min(u16::MAX as u32);
+    let mut flags = SLR_NO_UI.0 as u32 | (timeout << 16);
+    if !save {
+        flags |= SLR_NOUPDATE.0 as u32;
+    }
+
+    let resolved = unsafe { shell.Resolve(HWND(0), flags) }.is_ok();
+    let new_target = target_path(&shell)?;
+    let changed = resolved && !new_target.eq_ignore_ascii_case(&original_target);
+
+    let saved = save && changed;
+    if saved {
+        save_shell_link(&shell, shortcut_path)?;
+    }
+
+    Ok(ResolveResult {
+        resolved,
+        original_target,
+        target_path: new_target,
+        changed,
+        saved,
+    })
+}
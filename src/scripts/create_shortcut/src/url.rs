@@ -0,0 +1,47 @@
+use std::fs;
+use std::io;
+
+use windows::core::{Error, Result, HRESULT};
+use windows::Win32::Foundation::{E_FAIL, E_INVALIDARG};
+
+use crate::options::ShortcutFields;
+
+/// Writes a `.url` internet shortcut. These are plain INI files, so no COM is involved.
+pub fn create_url_shortcut(url: &str, shortcut_path: &str, fields: &ShortcutFields) -> Result<()> {
+    let icon = fields.icon_path.as_deref().map(|icon_path| (icon_path, fields.icon_index.unwrap_or(0)));
+    write_url_shortcut(url, shortcut_path, icon)
+}
+
+/// Writes a `.url` internet shortcut to `url`, with `icon` as a path and index.
+pub fn write_url_shortcut(url: &str, shortcut_path: &str, icon: Option<(&str, i32)>) -> Result<()> {
+    // A line break would start another key, such as a second URL=
+    for (name, value) in [("URL", Some(url)), ("icon path", icon.map(|(icon_path, _)| icon_path))] {
+        if value.is_some_and(|value| value.chars().any(char::is_control)) {
+            return Err(Error::new(E_INVALIDARG, format!("The {} contains a control character", name).into()));
+        }
+    }
+    let mut contents = format!("[InternetShortcut]\r\nURL={}\r\n", url);
+    if let Some((icon_path, icon_index)) = icon {
+        contents.push_str(&format!("IconFile={}\r\n", icon_path));
+        contents.push_str(&format!("IconIndex={}\r\n", icon_index));
+    }
+
+    // The profile APIs Explorer uses read ANSI unless the file starts with a UTF-16 BOM
+    let written = if contents.is_ascii() {
+        fs::write(shortcut_path, contents)
+    } else {
+        let bytes: Vec<u8> = std::iter::once(0xFEFF)
+            .chain(contents.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        fs::write(shortcut_path, bytes)
+    };
+    written.map_err(io_error)
+}
+
+fn io_error(error: io::Error) -> Error {
+    match error.raw_os_error() {
+        Some(code) => Error::from(HRESULT::from_win32(code as u32)),
+        None => Error::new(E_FAIL, error.to_string().into()),
+    }
+}
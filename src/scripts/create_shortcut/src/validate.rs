@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::path::Path;
+
+use altdesktop_core::exit;
+use altdesktop_core::path::{expand_env_vars, extended_length_path};
+use serde::Serialize;
+use windows::{
+    core::*,
+    Win32::Foundation::CloseHandle,
+    Win32::Storage::FileSystem::*,
+    Win32::UI::Shell::{ExtractIconExW, ILFree},
+};
+
+use crate::create::resolve_target;
+use crate::link::{has_env_vars, is_shell_target, parse_shell_target};
+use crate::options::ShortcutFields;
+
+#[derive(Clone, Copy)]
+pub enum ValidationIssue {
+    TargetNotFound,
+    DestinationNotWritable,
+    IconNotReadable,
+}
+
+impl ValidationIssue {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ValidationIssue::TargetNotFound => exit::NOT_FOUND,
+            ValidationIssue::DestinationNotWritable => exit::ACCESS_DENIED,
+            ValidationIssue::IconNotReadable => exit::FAILURE,
+        }
+    }
+
+    fn code(self) -> &'static str {
+        match self {
+            ValidationIssue::TargetNotFound => "TARGET_NOT_FOUND",
+            ValidationIssue::DestinationNotWritable => "DESTINATION_NOT_WRITABLE",
+            ValidationIssue::IconNotReadable => "ICON_NOT_READABLE",
+        }
+    }
+
+    fn field(self) -> &'static str {
+        match self {
+            ValidationIssue::TargetNotFound => "target",
+            ValidationIssue::DestinationNotWritable => "shortcutPath",
+            ValidationIssue::IconNotReadable => "icon",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ValidationReport {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl ValidationReport {
+    fn passed() -> Self {
+        ValidationReport { ok: true, code: None, field: None, message: None }
+    }
+
+    fn failed(issue: ValidationIssue, message: String) -> Self {
+        ValidationReport {
+            ok: false,
+            code: Some(issue.code()),
+            field: Some(issue.field()),
+            message: Some(message),
+        }
+    }
+}
+
+/// Pre-flights a create without writing anything. Returns the first problem found.
+pub fn validate_shortcut(shortcut_path: &str, fields: &ShortcutFields) -> (ValidationReport, Option<ValidationIssue>) {
+    match check(shortcut_path, fields) {
+        Ok(()) => (ValidationReport::passed(), None),
+        Err((issue, message)) => (ValidationReport::failed(issue, message), Some(issue)),
+    }
+}
+
+fn check(shortcut_path: &str, fields: &ShortcutFields) -> std::result::Result<(), (ValidationIssue, String)> {
+    if let Some(target_path) = &fields.target_path
+        && !target_exists(target_path, fields.base_dir.as_deref())
+    {
+        return Err((ValidationIssue::TargetNotFound, format!("Target does not exist: {}", target_path)));
+    }
+
+    let destination = Path::new(shortcut_path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    if !directory_writable(&destination.to_string_lossy()) {
+        return Err((
+            ValidationIssue::DestinationNotWritable,
+            format!("Cannot create files in: {}", destination.display()),
+        ));
+    }
+
+    if let Some(icon_path) = &fields.icon_path
+        && !icon_readable(icon_path, fields.icon_index.unwrap_or(0))
+    {
+        return Err((ValidationIssue::IconNotReadable, format!("Icon cannot be read: {}", icon_path)));
+    }
+
+    Ok(())
+}
+
+pub fn target_exists(target_path: &str, base_dir: Option<&str>) -> bool {
+    if is_shell_target(target_path) {
+        return match parse_shell_target(target_path) {
+            Ok(pidl) => {
+                unsafe { ILFree(Some(pidl)) };
+                true
+            }
+            Err(_) => false,
+        };
+    }
+    let target = resolve_target(target_path, base_dir);
+    let target = target.to_string_lossy();
+    let target = if has_env_vars(&target) { expand_env_vars(&target) } else { target.into_owned() };
+    Path::new(&extended_length_path(&target)).exists()
+}
+
+// Opening the directory for FILE_ADD_FILE runs the same ACL check a real create would,
+// without leaving anything behind
+fn directory_writable(directory: &str) -> bool {
+    let path = HSTRING::from(extended_length_path(directory));
+    unsafe {
+        match CreateFileW(
+            &path,
+            FILE_ADD_FILE.0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAG_BACKUP_SEMANTICS,
+            None,
+        ) {
+            Ok(handle) => {
+                let _ = CloseHandle(handle);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+pub fn icon_readable(icon_path: &str, icon_index: i32) -> bool {
+    let icon_path = if has_env_vars(icon_path) { expand_env_vars(icon_path) } else { icon_path.to_string() };
+    if File::open(extended_length_path(&icon_path)).is_err() {
+        return false;
+    }
+    // Resource IDs (negative indices) can't be checked against the icon count
+    if icon_index < 0 {
+        return true;
+    }
+    let count = unsafe { ExtractIconExW(&HSTRING::from(icon_path.as_str()), -1, None, None, 0) };
+    (icon_index as u32) < count
+}
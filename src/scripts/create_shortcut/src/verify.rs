@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use altdesktop_core::path::{expand_env_vars, extended_length_path};
+use serde::Serialize;
+use windows::core::*;
+
+use crate::link::has_env_vars;
+use crate::read::read_shortcut;
+use crate::validate::{icon_readable, target_exists};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FieldStatus {
+    Ok,
+    // The link leaves the field empty, so Windows falls back to a default
+    Unset,
+    Missing,
+    Unreadable,
+}
+
+#[derive(Serialize)]
+pub struct FieldReport {
+    pub status: FieldStatus,
+    pub path: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyReport {
+    pub ok: bool,
+    pub target: FieldReport,
+    pub working_directory: FieldReport,
+    pub icon: FieldReport,
+}
+
+/// Checks that an existing shortcut's target, working directory and icon still exist.
+/// Unlike --resolve this never searches for a moved target.
+pub fn verify_shortcut(shortcut_path: &str) -> Result<VerifyReport> {
+    let info = read_shortcut(shortcut_path)?;
+
+    let target = field(info.target_path, |path| {
+        if target_exists(path, None) { FieldStatus::Ok } else { FieldStatus::Missing }
+    });
+    let working_directory = field(info.working_directory, |path| {
+        let path = if has_env_vars(path) { expand_env_vars(path) } else { path.to_string() };
+        if Path::new(&extended_length_path(&path)).is_dir() { FieldStatus::Ok } else { FieldStatus::Missing }
+    });
+    let icon_index = info.icon_index;
+    let icon = field(info.icon_location, |path| {
+        let expanded = if has_env_vars(path) { expand_env_vars(path) } else { path.to_string() };
+        if !Path::new(&extended_length_path(&expanded)).exists() {
+            FieldStatus::Missing
+        } else if icon_readable(path, icon_index) {
+            FieldStatus::Ok
+        } else {
+            FieldStatus::Unreadable
+        }
+    });
+
+    let ok = [&target, &working_directory, &icon]
+        .iter()
+        .all(|report| matches!(report.status, FieldStatus::Ok | FieldStatus::Unset));
+    Ok(VerifyReport { ok, target, working_directory, icon })
+}
+
+fn field(path: String, check: impl FnOnce(&str) -> FieldStatus) -> FieldReport {
+    let status = if path.is_empty() { FieldStatus::Unset } else { check(&path) };
+    FieldReport { status, path }
+}
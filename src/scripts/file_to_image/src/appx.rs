@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use image::{imageops, Rgba, RgbaImage};
+use windows::{
+    core::{HSTRING, PWSTR},
+    Win32::Storage::Packaging::Appx::{GetPackagePathByFullName, GetPackagesByPackageFamily},
+};
+
+use crate::encode::parse_color;
+use crate::error::{failure, ErrorCode};
+
+// Logos up to this size come from Square44x44Logo, larger ones from Square150x150Logo
+const SMALL_LOGO_LIMIT: u32 = 64;
+
+/// Loads the logo of an installed Store app from its AppxManifest.xml, picking the
+/// asset variant closest to `size` and filling the manifest's background color
+/// behind it when the asset expects a plate.
+pub fn extract_package_logo(family_name: &str, size: u32) -> Result<RgbaImage> {
+    let install_dir = package_path(family_name)?;
+    let manifest = fs::read_to_string(install_dir.join("AppxManifest.xml")).context("Failed to read AppxManifest.xml")?;
+    let visuals = read_visual_elements(&manifest)?;
+
+    let logo = if size <= SMALL_LOGO_LIMIT {
+        visuals.small_logo.or(visuals.medium_logo)
+    } else {
+        visuals.medium_logo.or(visuals.small_logo)
+    }
+    .or(visuals.store_logo)
+    .ok_or_else(|| failure(ErrorCode::NotFound, format!("{} declares no logo", family_name)))?;
+
+    let asset = best_asset(&install_dir.join(logo.replace('\\', "/")), size)
+        .ok_or_else(|| failure(ErrorCode::NotFound, format!("No logo asset found for {}", logo)))?;
+    let img = image::open(&asset.path)?.to_rgba8();
+
+    // Unplated assets are drawn for the taskbar without a tile behind them
+    match visuals.background.filter(|_| !asset.unplated) {
+        Some(background) => Ok(composite_on(&img, background)),
+        None => Ok(img),
+    }
+}
+
+fn package_path(family_name: &str) -> Result<PathBuf> {
+    let family = HSTRING::from(family_name);
+    unsafe {
+        let (mut count, mut buffer_length) = (0u32, 0u32);
+        // The first call only reports the sizes, failing with ERROR_INSUFFICIENT_BUFFER
+        let _ = GetPackagesByPackageFamily(&family, &mut count, None, &mut buffer_length, PWSTR::null());
+        if count == 0 {
+            return Err(failure(ErrorCode::NotFound, format!("Package is not installed: {}", family_name)));
+        }
+
+        let mut names = vec![PWSTR::null(); count as usize];
+        let mut buffer = vec![0u16; buffer_length as usize];
+        GetPackagesByPackageFamily(
+            &family,
+            &mut count,
+            Some(names.as_mut_ptr()),
+            &mut buffer_length,
+            PWSTR(buffer.as_mut_ptr()),
+        )?;
+        // Several versions can be registered at once; the last is the newest
+        let full_name = names[count as usize - 1].to_string()?;
+
+        let full_name = HSTRING::from(full_name);
+        let mut path_length = 0u32;
+        let _ = GetPackagePathByFullName(&full_name, &mut path_length, PWSTR::null());
+        let mut path = vec![0u16; path_length as usize];
+        GetPackagePathByFullName(&full_name, &mut path_length, PWSTR(path.as_mut_ptr()))?;
+        let length = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+        Ok(PathBuf::from(String::from_utf16_lossy(&path[..length])))
+    }
+}
+
+struct VisualElements {
+    small_logo: Option<String>,
+    medium_logo: Option<String>,
+    store_logo: Option<String>,
+    background: Option<Rgba<u8>>,
+}
+
+fn read_visual_elements(manifest: &str) -> Result<VisualElements> {
+    let document = roxmltree::Document::parse(manifest).context("Invalid AppxManifest.xml")?;
+    let root = document.root_element();
+
+    // The uap:, uap3: etc. prefixes vary between manifest schema versions, so match on the local name only
+    let visuals = root.descendants().find(|node| node.tag_name().name() == "VisualElements");
+    let store_logo = root
+        .descendants()
+        .find(|node| {
+            node.tag_name().name() == "Logo" && node.parent().is_some_and(|parent| parent.tag_name().name() == "Properties")
+        })
+        .and_then(|node| node.text())
+        .map(str::to_string);
+    let attribute = |name: &str| visuals.and_then(|node| node.attribute(name)).map(str::to_string);
+
+    Ok(VisualElements {
+        small_logo: attribute("Square44x44Logo"),
+        medium_logo: attribute("Square150x150Logo"),
+        store_logo,
+        // "transparent" and named colors leave the logo as is
+        background: attribute("BackgroundColor")
+            .and_then(|color| parse_color(&color).ok())
+            .map(|rgb| Rgba([rgb[0], rgb[1], rgb[2], 255])),
+    })
+}
+
+struct Asset {
+    path: PathBuf,
+    unplated: bool,
+}
+
+struct Candidate {
+    path: PathBuf,
+    target_size: Option<u32>,
+    scale: u32,
+    unplated: bool,
+}
+
+// Manifests name the logo without its resource qualifiers; the files on disk are
+// e.g. Logo.scale-200.png or Logo.targetsize-48_altform-unplated.png
+fn best_asset(logo: &Path, size: u32) -> Option<Asset> {
+    let dir = logo.parent()?;
+    let stem = logo.file_stem()?.to_string_lossy().to_lowercase();
+    let extension = logo.extension()?.to_string_lossy().to_lowercase();
+
+    let mut candidates: Vec<Candidate> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_string_lossy().to_lowercase();
+            let qualifiers = name.strip_prefix(&stem)?.strip_suffix(&extension)?.strip_suffix('.')?;
+            let qualifiers = match qualifiers {
+                "" => "",
+                _ => qualifiers.strip_prefix('.')?,
+            };
+            parse_qualifiers(path, qualifiers)
+        })
+        .collect();
+
+    // Prefer explicit target sizes at or above the request, then the highest scale
+    candidates.sort_by_key(|candidate| match candidate.target_size {
+        Some(target) if target >= size => (0, target, !candidate.unplated, 0),
+        Some(target) => (1, u32::MAX - target, !candidate.unplated, 0),
+        None => (2, 0, false, u32::MAX - candidate.scale),
+    });
+    candidates.into_iter().next().map(|candidate| Asset { path: candidate.path, unplated: candidate.unplated })
+}
+
+fn parse_qualifiers(path: PathBuf, qualifiers: &str) -> Option<Candidate> {
+    let mut candidate = Candidate { path, target_size: None, scale: 100, unplated: false };
+    for qualifier in qualifiers.split(['_', '.']).filter(|q| !q.is_empty()) {
+        let (name, value) = qualifier.split_once('-')?;
+        match name {
+            "targetsize" => candidate.target_size = Some(value.parse().ok()?),
+            "scale" => candidate.scale = value.parse().ok()?,
+            "altform" if value == "unplated" => candidate.unplated = true,
+            // High contrast and light-theme variants only suit those themes
+            "contrast" | "altform" | "theme" => return None,
+            _ => {}
+        }
+    }
+    Some(candidate)
+}
+
+fn composite_on(img: &RgbaImage, background: Rgba<u8>) -> RgbaImage {
+    let mut plate = RgbaImage::from_pixel(img.width(), img.height(), background);
+    imageops::overlay(&mut plate, img, 0, 0);
+    plate
+}
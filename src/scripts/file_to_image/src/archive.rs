@@ -0,0 +1,324 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use altdesktop_core::sandbox;
+use anyhow::{Context, Result};
+use flate2::read::DeflateDecoder;
+use serde::Serialize;
+
+use crate::error::{failure, ErrorCode};
+use crate::extract::{extract, Output};
+use crate::image_file::is_image_file;
+use crate::options::Options;
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const CENTRAL_DIRECTORY_ENTRY: u32 = 0x0201_4b50;
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+const ZIP64_EXTRA_FIELD: u16 = 0x0001;
+// The end record is 22 bytes, followed by a comment of up to 64KB
+const END_SEARCH_LENGTH: u64 = 22 + 0xFFFF;
+const FLAG_ENCRYPTED: u16 = 1;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+// Anything bigger takes longer to unpack than a tile is worth waiting for
+const MAX_REPRESENTATIVE_SIZE: u64 = 256 * 1024 * 1024;
+
+const EXECUTABLES: &[&str] = &["exe", "msi"];
+const DOCUMENTS: &[&str] = &["pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "txt", "md", "rtf"];
+const AUDIO: &[&str] = &["mp3", "flac", "wav", "ogg", "m4a", "aac", "wma", "opus"];
+const VIDEO: &[&str] = &["mp4", "mkv", "avi", "mov", "webm", "wmv", "m4v"];
+const ARCHIVES: &[&str] = &["zip", "7z", "rar", "tar", "gz", "xz", "bz2", "cab", "iso"];
+// Names that say an image stands for the whole archive: album covers, app logos
+const COVER_NAMES: &[&str] = &["icon", "logo", "cover", "folder", "front", "albumart"];
+// Metadata other tools leave in archives, never what a user put there
+const JUNK_NAMES: &[&str] = &[".ds_store", "thumbs.db", "desktop.ini"];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveEntry {
+    /// The path inside the archive, with / separators; folders end with one.
+    pub name: String,
+    pub size: u64,
+    pub compressed_size: u64,
+    /// folder, image, icon, executable, document, audio, video, archive or file.
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    #[serde(skip)]
+    method: u16,
+    #[serde(skip)]
+    encrypted: bool,
+    #[serde(skip)]
+    header_offset: u64,
+}
+
+#[derive(Serialize)]
+pub struct ArchiveContents {
+    pub entries: Vec<ArchiveEntry>,
+    /// The entry the image was made from; None when it is the archive's own icon.
+    pub representative: Option<String>,
+    /// Whether that image is an icon or a thumbnail, when one was requested.
+    pub kind: Option<&'static str>,
+    pub outputs: Vec<Output>,
+}
+
+/// Lists an archive and, given extraction options for it, writes the image of its
+/// representative entry in place of the archive's own.
+pub fn describe_archive(file_path: &str, options: Option<Options>) -> Result<ArchiveContents> {
+    sandbox::check_input(file_path)?;
+    let entries = list_entries(file_path)?;
+    let Some(mut options) = options else {
+        return Ok(ArchiveContents { entries, representative: None, kind: None, outputs: Vec::new() });
+    };
+
+    let scratch = ScratchDir::new()?;
+    let unpacked = representative_entry(&entries).and_then(|entry| {
+        let path = extract_entry(file_path, entry, &scratch.0).ok()?;
+        options.file_path = path.to_string_lossy().into_owned();
+        let extraction = extract(&options).ok()?;
+        Some((entry.name.clone(), extraction))
+    });
+    // An archive with nothing inside worth showing, or whose entry won't unpack
+    // or render, keeps its generic type icon
+    let (representative, extraction) = match unpacked {
+        Some((name, extraction)) => (Some(name), extraction),
+        None => {
+            options.file_path = file_path.to_string();
+            (None, extract(&options)?)
+        }
+    };
+    Ok(ArchiveContents {
+        entries,
+        representative,
+        kind: Some(extraction.kind.name()),
+        outputs: extraction.outputs,
+    })
+}
+
+/// Reads the central directory of a .zip (or any file in the zip format, such as
+/// .jar or .epub) without unpacking any entry.
+pub fn list_entries(file_path: &str) -> Result<Vec<ArchiveEntry>> {
+    let mut file = BufReader::new(File::open(file_path)?);
+    let (count, offset) = central_directory(&mut file)?;
+    file.seek(SeekFrom::Start(offset))?;
+
+    // The count comes from the file, so it only hints at the capacity
+    let mut entries = Vec::with_capacity(count.min(4096) as usize);
+    for _ in 0..count {
+        let header = read_bytes(&mut file, 46)?;
+        if u32_at(&header, 0) != CENTRAL_DIRECTORY_ENTRY {
+            return Err(not_a_zip(file_path));
+        }
+        let flags = u16_at(&header, 8);
+        let name = read_bytes(&mut file, u16_at(&header, 28) as usize)?;
+        let extra = read_bytes(&mut file, u16_at(&header, 30) as usize)?;
+        file.seek_relative(u16_at(&header, 32) as i64)?;
+
+        // Names without the UTF-8 flag are in the DOS code page, which agrees with
+        // UTF-8 on the ASCII names almost every archive uses
+        let name = String::from_utf8_lossy(&name).replace('\\', "/");
+        let mut size = u32_at(&header, 24) as u64;
+        let mut compressed_size = u32_at(&header, 20) as u64;
+        let mut header_offset = u32_at(&header, 42) as u64;
+        if [size, compressed_size, header_offset].contains(&(u32::MAX as u64)) {
+            // ZIP64 moves the fields that overflowed into an extra field, in this order
+            if let Some(values) = extra_field(&extra, ZIP64_EXTRA_FIELD) {
+                let mut values = values.chunks_exact(8).map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()));
+                for field in [&mut size, &mut compressed_size, &mut header_offset] {
+                    if *field == u32::MAX as u64 {
+                        *field = values.next().unwrap_or(*field);
+                    }
+                }
+            }
+        }
+        entries.push(ArchiveEntry {
+            kind: entry_kind(&name),
+            name,
+            size,
+            compressed_size,
+            method: u16_at(&header, 10),
+            encrypted: flags & FLAG_ENCRYPTED != 0,
+            header_offset,
+        });
+    }
+    Ok(entries)
+}
+
+/// Picks the entry that best shows what the archive holds: an image named like a
+/// cover or logo, then a program, then any image, then the largest file, each
+/// preferring the shallowest. Returns None when no entry can be unpacked.
+pub fn representative_entry(entries: &[ArchiveEntry]) -> Option<&ArchiveEntry> {
+    entries
+        .iter()
+        .filter(|entry| entry.kind != "folder" && !entry.encrypted && entry.size <= MAX_REPRESENTATIVE_SIZE)
+        .filter(|entry| matches!(entry.method, METHOD_STORED | METHOD_DEFLATED))
+        .filter(|entry| !is_junk(&entry.name))
+        .min_by_key(|entry| {
+            let tier = match entry.kind {
+                "icon" => 0,
+                "image" if is_cover(&entry.name) => 0,
+                "executable" => 1,
+                "image" => 2,
+                _ => 3,
+            };
+            (tier, entry.name.matches('/').count(), std::cmp::Reverse(entry.size))
+        })
+}
+
+/// Unpacks one entry into `dir`, keeping its file name so the shell still knows
+/// its type, and returns the path written.
+pub fn extract_entry(file_path: &str, entry: &ArchiveEntry, dir: &Path) -> Result<PathBuf> {
+    let mut file = BufReader::new(File::open(file_path)?);
+    file.seek(SeekFrom::Start(entry.header_offset))?;
+    let header = read_bytes(&mut file, 30)?;
+    if u32_at(&header, 0) != LOCAL_FILE_HEADER {
+        return Err(not_a_zip(file_path));
+    }
+    // The local header repeats the name but may carry a different extra field
+    file.seek_relative(u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64)?;
+
+    // Written into `dir` only as a plain name: C:evil.exe would be joined onto C:'s
+    // current folder instead, and a:b would open a stream of a
+    let file_name = entry.name.rsplit('/').next().unwrap_or(&entry.name);
+    let mut components = Path::new(file_name).components();
+    let plain = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None));
+    if !plain || file_name.contains(':') {
+        return Err(failure(ErrorCode::InvalidArguments, format!("{} is not a file name to unpack", entry.name)));
+    }
+    let path = dir.join(file_name);
+    sandbox::check_output(&path)?;
+    let mut output = File::create(&path)?;
+    let mut data = file.take(entry.compressed_size);
+    let written = match entry.method {
+        METHOD_STORED => io::copy(&mut data, &mut output),
+        _ => io::copy(&mut DeflateDecoder::new(data), &mut output),
+    }
+    .with_context(|| format!("Failed to unpack {}", entry.name))?;
+    if written != entry.size {
+        return Err(failure(ErrorCode::Decode, format!("{} is truncated in the archive", entry.name)));
+    }
+    Ok(path)
+}
+
+/// A fresh folder to unpack into, removed again when dropped.
+pub struct ScratchDir(pub PathBuf);
+
+impl ScratchDir {
+    pub fn new() -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("altdesktop-archive-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path)?;
+        Ok(ScratchDir(path))
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// The entry count and offset of the central directory, from the end record and,
+// for archives with more than 65535 entries or past 4GB, its ZIP64 counterpart
+fn central_directory(file: &mut (impl Read + Seek)) -> Result<(u64, u64)> {
+    let length = file.seek(SeekFrom::End(0))?;
+    let start = length.saturating_sub(END_SEARCH_LENGTH);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&at| u32_at(&tail, at) == END_OF_CENTRAL_DIRECTORY)
+        .ok_or_else(|| failure(ErrorCode::Unsupported, "Not a zip archive"))?;
+    let count = u16_at(&tail, end + 10) as u64;
+    let offset = u32_at(&tail, end + 16) as u64;
+    if count != 0xFFFF && offset != u32::MAX as u64 {
+        return Ok((count, offset));
+    }
+
+    let locator = end
+        .checked_sub(20)
+        .filter(|&at| u32_at(&tail, at) == ZIP64_LOCATOR)
+        .ok_or_else(|| failure(ErrorCode::Decode, "The ZIP64 end of central directory is missing"))?;
+    file.seek(SeekFrom::Start(u64_at(&tail, locator + 8)))?;
+    let record = read_bytes(file, 56)?;
+    if u32_at(&record, 0) != ZIP64_END_OF_CENTRAL_DIRECTORY {
+        return Err(failure(ErrorCode::Decode, "The ZIP64 end of central directory is damaged"));
+    }
+    Ok((u64_at(&record, 32), u64_at(&record, 48)))
+}
+
+// Extra fields are a 2-byte id and a 2-byte length, then that many bytes
+fn extra_field(extra: &[u8], id: u16) -> Option<&[u8]> {
+    let mut at = 0;
+    while at + 4 <= extra.len() {
+        let length = u16_at(extra, at + 2) as usize;
+        let data = extra.get(at + 4..at + 4 + length)?;
+        if u16_at(extra, at) == id {
+            return Some(data);
+        }
+        at += 4 + length;
+    }
+    None
+}
+
+fn entry_kind(name: &str) -> &'static str {
+    if name.ends_with('/') {
+        return "folder";
+    }
+    if is_image_file(name) {
+        return "image";
+    }
+    let extension = Path::new(name).extension().and_then(|extension| extension.to_str()).unwrap_or("");
+    let is = |list: &[&str]| list.iter().any(|known| known.eq_ignore_ascii_case(extension));
+    if extension.eq_ignore_ascii_case("ico") {
+        "icon"
+    } else if is(EXECUTABLES) {
+        "executable"
+    } else if is(DOCUMENTS) {
+        "document"
+    } else if is(AUDIO) {
+        "audio"
+    } else if is(VIDEO) {
+        "video"
+    } else if is(ARCHIVES) {
+        "archive"
+    } else {
+        "file"
+    }
+}
+
+fn is_cover(name: &str) -> bool {
+    let stem = Path::new(name).file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+    COVER_NAMES.iter().any(|cover| cover.eq_ignore_ascii_case(stem))
+}
+
+// macOS's Finder adds a __MACOSX folder of resource forks to every archive it makes
+fn is_junk(name: &str) -> bool {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    name.starts_with("__MACOSX/") || JUNK_NAMES.iter().any(|junk| junk.eq_ignore_ascii_case(file_name))
+}
+
+fn not_a_zip(file_path: &str) -> anyhow::Error {
+    failure(ErrorCode::Decode, format!("{} is not a valid zip archive", file_path))
+}
+
+fn read_bytes(reader: &mut impl Read, length: usize) -> Result<Vec<u8>> {
+    let mut buffer = vec![0; length];
+    reader.read_exact(&mut buffer).map_err(|_| failure(ErrorCode::Decode, "The archive is truncated"))?;
+    Ok(buffer)
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
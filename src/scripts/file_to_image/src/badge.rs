@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use image::{imageops, DynamicImage, RgbaImage};
+
+use crate::ico;
+use crate::resample::Resample;
+
+#[derive(Clone, Copy)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "top-left" => Ok(Corner::TopLeft),
+            "top-right" => Ok(Corner::TopRight),
+            "bottom-left" => Ok(Corner::BottomLeft),
+            "bottom-right" => Ok(Corner::BottomRight),
+            _ => Err(format!("Invalid corner: {} (expected top-left, top-right, bottom-left or bottom-right)", value)),
+        }
+    }
+}
+
+/// An emblem drawn over a corner of every output, sized as a percentage of it.
+#[derive(Clone)]
+pub struct Badge {
+    pub path: String,
+    pub corner: Corner,
+    pub size: u32,
+}
+
+pub const DEFAULT_BADGE_SIZE: u32 = 40;
+
+impl Badge {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=100).contains(&self.size) {
+            return Err(format!("Invalid badge size: {}% (expected 1 to 100)", self.size));
+        }
+        Ok(())
+    }
+
+    /// Decodes the badge once, at the largest size it will be drawn at.
+    pub fn load(&self, largest: u32) -> Result<RgbaImage> {
+        let size = (largest * self.size / 100).max(1);
+        if ico::is_ico(&self.path) {
+            if let Ok(Some(img)) = ico::extract_ico_frame(&self.path, size) {
+                return Ok(img);
+            }
+        }
+        Ok(image::open(&self.path)
+            .with_context(|| format!("Failed to read badge {}", self.path))?
+            .to_rgba8())
+    }
+
+    pub fn apply(&self, img: DynamicImage, badge: &RgbaImage, resample: &Resample) -> DynamicImage {
+        let mut img = img.to_rgba8();
+        let (x, y, size) = self.placement(img.width(), img.height());
+        let badge = resample.resize(&DynamicImage::ImageRgba8(badge.clone()), size, size).to_rgba8();
+        // Badges that aren't square hug the corner rather than the middle of their box
+        let x = match self.corner {
+            Corner::TopRight | Corner::BottomRight => x + size - badge.width(),
+            _ => x,
+        };
+        let y = match self.corner {
+            Corner::BottomLeft | Corner::BottomRight => y + size - badge.height(),
+            _ => y,
+        };
+        imageops::overlay(&mut img, &badge, x as i64, y as i64);
+        DynamicImage::ImageRgba8(img)
+    }
+
+    /// The square the badge occupies on a `width`x`height` image, as x, y and size.
+    pub fn placement(&self, width: u32, height: u32) -> (u32, u32, u32) {
+        let size = (width.min(height) * self.size / 100).max(1);
+        let x = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => 0,
+            Corner::TopRight | Corner::BottomRight => width.saturating_sub(size),
+        };
+        let y = match self.corner {
+            Corner::TopLeft | Corner::TopRight => 0,
+            Corner::BottomLeft | Corner::BottomRight => height.saturating_sub(size),
+        };
+        (x, y, size)
+    }
+}
@@ -0,0 +1,364 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use altdesktop_core::cancel::{self, CancelToken};
+#[cfg(windows)]
+use altdesktop_core::com::ComGuard;
+use altdesktop_core::progress::Progress;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::badge::{Badge, Corner, DEFAULT_BADGE_SIZE};
+use crate::colors::Colors;
+use crate::compose::{Composite, Fill};
+use crate::encode::{parse_color, OutputFormat, DEFAULT_BACKGROUND};
+use crate::error::{classify, failure, log_failure, ErrorCode, ErrorReport};
+use crate::extract::{extract, Extraction, Output, Section};
+use crate::hash::Hashes;
+use crate::ico::PACK_SIZES;
+use crate::montage::{Montage, MontageLayout, DEFAULT_MONTAGE_ITEMS};
+use crate::options::{
+    default_cache_dir, default_format, default_sizes, default_timeout, normalize_sizes, timeout_from_millis, Options,
+};
+use crate::resample::Resample;
+use crate::trim::DEFAULT_TRIM_PADDING;
+use crate::variants::VariantStyle;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    input: String,
+    output: String,
+    /// `input` is a stock icon's name or number, as for --stock.
+    #[serde(default)]
+    stock: bool,
+    size: Option<u32>,
+    sizes: Option<Vec<u32>>,
+    format: Option<String>,
+    background: Option<String>,
+    resource_index: Option<i32>,
+    #[serde(default)]
+    thumbnail: bool,
+    #[serde(default)]
+    thumbnail_cache: bool,
+    video_frame: Option<f64>,
+    fill: Option<String>,
+    #[serde(default)]
+    padding: u32,
+    #[serde(default)]
+    corner_radius: u32,
+    badge: Option<String>,
+    badge_corner: Option<String>,
+    badge_size: Option<u32>,
+    #[serde(default)]
+    no_follow_lnk: bool,
+    #[serde(default)]
+    no_fallback: bool,
+    #[serde(default)]
+    colors: bool,
+    #[serde(default)]
+    hash: bool,
+    #[serde(default)]
+    trim: bool,
+    trim_padding: Option<u32>,
+    filter: Option<String>,
+    sharpen: Option<f32>,
+    variants: Option<String>,
+    #[serde(default)]
+    blur_backdrop: bool,
+    montage: Option<String>,
+    montage_items: Option<u32>,
+    /// `output` is a single .ico holding every size.
+    #[serde(default)]
+    ico_out: bool,
+    scale: Option<f32>,
+    /// Milliseconds, 0 for no limit.
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobOutcome {
+    kind: &'static str,
+    source: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    outputs: Vec<Output>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sections: Vec<Section>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    colors: Option<Colors>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hashes: Option<Hashes>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    animated: bool,
+}
+
+impl From<Extraction> for JobOutcome {
+    fn from(extraction: Extraction) -> Self {
+        JobOutcome {
+            kind: extraction.kind.name(),
+            source: extraction.source.name(),
+            outputs: extraction.outputs,
+            sections: extraction.sections,
+            colors: extraction.colors,
+            hashes: extraction.hashes,
+            animated: extraction.animated,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobResult {
+    index: usize,
+    input: String,
+    ok: bool,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    outcome: Option<JobOutcome>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<ErrorCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_message: Option<&'static str>,
+}
+
+/// A lone job, as sent to `--serve`, which has no batch-wide cache directory.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SingleJob {
+    #[serde(flatten)]
+    job: Job,
+    cache_dir: Option<String>,
+    #[serde(default)]
+    shared_memory: bool,
+}
+
+/// Reads a JSON array of jobs from stdin and runs them on up to `jobs` worker
+/// threads. Prints one JSON line per job as soon as it finishes, in completion order
+/// with its `index` in the input, and returns whether every job succeeded. With
+/// `progress`, each result is followed by a progress line counting it off.
+///
+/// Stdin may stay open after the array for cancel lines, {"cancel": <index>} for one
+/// job or {"cancel": "all"}: a job that hasn't started fails with `cancelled` at once,
+/// and a running one at its next safe point, removing what it had written.
+pub fn run_batch(cache_dir: Option<&str>, jobs: usize, progress: bool) -> Result<bool> {
+    let queue = read_jobs()?;
+    let queue: Vec<Mutex<Option<Job>>> = queue.into_iter().map(|job| Mutex::new(Some(job))).collect();
+    let tokens: Arc<Vec<CancelToken>> = Arc::new(queue.iter().map(|_| CancelToken::new()).collect());
+    // Not scoped: it blocks on stdin, which the caller needn't ever close
+    let cancels = tokens.clone();
+    thread::spawn(move || read_cancels(&cancels));
+
+    let next = AtomicUsize::new(0);
+    let (results, finished) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, queue.len().max(1)) {
+            let results = results.clone();
+            let (queue, next, tokens) = (&queue, &next, &tokens);
+            scope.spawn(move || {
+                // Shell icon and thumbnail lookups need COM on every thread that makes them
+                #[cfg(windows)]
+                let _com = ComGuard::apartment();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(job) = queue.get(index).and_then(|job| job.lock().unwrap().take()) else {
+                        break;
+                    };
+                    let input = job.input.clone();
+                    let result = cancel::scope(&tokens[index], || run_job(job, cache_dir, false));
+                    if results.send(JobResult::new(index, input, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(results);
+
+        let mut all_ok = true;
+        let mut progress = Progress::new(progress, queue.len());
+        let mut stdout = io::stdout().lock();
+        for result in finished {
+            all_ok &= result.ok;
+            writeln!(stdout, "{}", serde_json::to_string(&result).unwrap())?;
+            // Flush per job so the caller can update progress while the rest run
+            stdout.flush()?;
+            progress.finished(result.index, &result.input);
+        }
+        Ok(all_ok)
+    })
+}
+
+impl JobResult {
+    pub fn new(index: usize, input: String, result: Result<Extraction>) -> Self {
+        match result {
+            Ok(extraction) => JobResult {
+                index,
+                input,
+                ok: true,
+                outcome: Some(extraction.into()),
+                error: None,
+                error_code: None,
+                user_message: None,
+            },
+            Err(error) => {
+                let code = classify(&error, Some(&input));
+                JobResult {
+                    index,
+                    ok: false,
+                    outcome: None,
+                    error: Some(format!("{:#}", error)),
+                    error_code: Some(code),
+                    user_message: Some(code.user_message()),
+                    input,
+                }
+            }
+        }
+    }
+}
+
+/// The JSON array of jobs on stdin, read only as far as its end, so whatever the
+/// caller sends after it is left for the tool to read.
+pub fn read_jobs() -> Result<Vec<Job>> {
+    Vec::<Job>::deserialize(&mut serde_json::Deserializer::from_reader(io::stdin().lock()))
+        .context("Invalid batch JSON")
+}
+
+impl Job {
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+}
+
+fn read_cancels(tokens: &[CancelToken]) {
+    for line in io::stdin().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        match &message["cancel"] {
+            serde_json::Value::String(all) if all == "all" => tokens.iter().for_each(CancelToken::cancel),
+            index => {
+                if let Some(token) = index.as_u64().and_then(|index| tokens.get(index as usize)) {
+                    token.cancel();
+                }
+            }
+        }
+    }
+}
+
+/// Runs one job object, shaped like a batch entry plus an optional `cacheDir`,
+/// and returns its result rather than printing it.
+pub fn run_single(job: serde_json::Value) -> Result<JobOutcome, ErrorReport> {
+    let SingleJob { job, cache_dir, shared_memory } = serde_json::from_value(job)
+        .map_err(|error| ErrorReport::with_code(ErrorCode::InvalidArguments, format!("Invalid job: {}", error)))?;
+    let input = job.input.clone();
+    run_job(job, cache_dir.or_else(default_cache_dir).as_deref(), shared_memory).map(JobOutcome::from).map_err(|error| ErrorReport::new(&error, Some(&input)))
+}
+
+// The job's fields go with everything logged while it runs
+fn run_job(job: Job, cache_dir: Option<&str>, shared_memory: bool) -> Result<Extraction> {
+    let _span = tracing::info_span!("job", ?job).entered();
+    let input = job.input.clone();
+    let result = extract_job(job, cache_dir, shared_memory);
+    if let Err(error) = &result {
+        log_failure(error, Some(&input));
+    }
+    result
+}
+
+fn extract_job(job: Job, cache_dir: Option<&str>, shared_memory: bool) -> Result<Extraction> {
+    extract(&job_options(job, cache_dir, shared_memory)?)
+}
+
+/// The options a job stands for, validated as an `extract` command line is.
+pub fn job_options(job: Job, cache_dir: Option<&str>, shared_memory: bool) -> Result<Options> {
+    let sizes = match (job.size, job.sizes) {
+        (Some(size), None) => vec![size],
+        (None, Some(sizes)) => normalize_sizes(sizes),
+        (None, None) if job.ico_out => PACK_SIZES.to_vec(),
+        (None, None) if default_sizes().is_some() => default_sizes().unwrap(),
+        _ => return Err(invalid("Each job needs exactly one of size or sizes")),
+    };
+    let options = Options {
+        file_path: job.input,
+        output_path: job.output,
+        sizes,
+        format: match job.format.as_deref() {
+            _ if job.ico_out => OutputFormat::Ico,
+            Some(format) => OutputFormat::parse(format).map_err(invalid)?,
+            None => default_format().map_err(invalid)?,
+        },
+        background: job
+            .background
+            .as_deref()
+            .map(parse_color)
+            .transpose()
+            .map_err(invalid)?
+            .unwrap_or(DEFAULT_BACKGROUND),
+        resource_index: job.resource_index,
+        thumbnail: job.thumbnail,
+        thumbnail_cache: job.thumbnail_cache,
+        video_frame: job.video_frame,
+        // stdout carries the JSON results
+        stdout: false,
+        data_uri: false,
+        cache_dir: cache_dir.map(str::to_string),
+        package: false,
+        stock: job.stock,
+        composite: Composite {
+            fill: job.fill.as_deref().map(Fill::parse).transpose().map_err(invalid)?,
+            padding: job.padding,
+            corner_radius: job.corner_radius,
+        },
+        montage: match job.montage.as_deref() {
+            Some(layout) => Some(Montage {
+                layout: MontageLayout::parse(layout).map_err(invalid)?,
+                items: job.montage_items.unwrap_or(DEFAULT_MONTAGE_ITEMS),
+            }),
+            None if job.montage_items.is_some() => return Err(invalid("montageItems requires montage")),
+            None => None,
+        },
+        badge: match job.badge {
+            Some(path) => Some(Badge {
+                path,
+                corner: job
+                    .badge_corner
+                    .as_deref()
+                    .map(Corner::parse)
+                    .transpose()
+                    .map_err(invalid)?
+                    .unwrap_or(Corner::BottomRight),
+                size: job.badge_size.unwrap_or(DEFAULT_BADGE_SIZE),
+            }),
+            None => None,
+        },
+        follow_lnk: !job.no_follow_lnk,
+        fallback: !job.no_fallback,
+        colors: job.colors,
+        hash: job.hash,
+        trim: job.trim.then(|| job.trim_padding.unwrap_or(DEFAULT_TRIM_PADDING)),
+        resample: Resample {
+            filter: match job.filter.as_deref() {
+                Some(filter) => Resample::parse_filter(filter).map_err(invalid)?,
+                None => Resample::default().filter,
+            },
+            sharpen: job.sharpen,
+        },
+        ico_pack: job.ico_out,
+        scale: job.scale.unwrap_or(1.0),
+        timeout: job.timeout.map_or_else(default_timeout, timeout_from_millis),
+        shared_memory,
+        variants: job.variants.as_deref().map(VariantStyle::parse).transpose().map_err(invalid)?,
+        blur_backdrop: job.blur_backdrop,
+    };
+    options.validate().map_err(invalid)?;
+    Ok(options)
+}
+
+fn invalid(message: impl Into<String>) -> anyhow::Error {
+    failure(ErrorCode::InvalidArguments, message)
+}
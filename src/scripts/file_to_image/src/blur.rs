@@ -0,0 +1,57 @@
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+use crate::resample::Resample;
+
+// The middle of the icon, scaled up past the tile, so its colors fill the frame
+// instead of its transparent margin
+const CROP: f32 = 0.6;
+// As a fraction of the tile size; enough that no shapes survive
+const SIGMA: f32 = 0.08;
+// Keeps the icon and any label drawn over the texture readable
+const BRIGHTNESS: f32 = 0.55;
+
+/// A blurred, darkened texture of the icon at `size`x`size`, for tile hover states
+/// and backdrops. Always opaque.
+pub fn blurred_backdrop(img: &DynamicImage, size: u32, resample: &Resample) -> DynamicImage {
+    let source = img.to_rgba8();
+    let (width, height) = source.dimensions();
+    let crop_width = ((width as f32 * CROP).round() as u32).max(1);
+    let crop_height = ((height as f32 * CROP).round() as u32).max(1);
+    let cropped = imageops::crop_imm(&source, (width - crop_width) / 2, (height - crop_height) / 2, crop_width, crop_height).to_image();
+    let zoomed = resample.resize_exact(&DynamicImage::ImageRgba8(cropped), size, size).to_rgba8();
+
+    // Transparent areas take the icon's mean color, so blurring doesn't pull
+    // everything towards black
+    let fill = mean_color(&zoomed);
+    let flattened = RgbaImage::from_fn(size, size, |x, y| {
+        let Rgba([r, g, b, a]) = *zoomed.get_pixel(x, y);
+        let alpha = a as f32 / 255.0;
+        let blend = |channel: u8, fill: f32| channel as f32 * alpha + fill * (1.0 - alpha);
+        Rgba([blend(r, fill[0]) as u8, blend(g, fill[1]) as u8, blend(b, fill[2]) as u8, 255])
+    });
+
+    let mut blurred = DynamicImage::ImageRgba8(flattened).blur((size as f32 * SIGMA).max(1.0)).to_rgba8();
+    for pixel in blurred.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = (*channel as f32 * BRIGHTNESS).round() as u8;
+        }
+        pixel[3] = 255;
+    }
+    DynamicImage::ImageRgba8(blurred)
+}
+
+// Alpha-weighted, so faint shadows don't count; mid-grey for a blank image
+fn mean_color(img: &RgbaImage) -> [f32; 3] {
+    let (mut sum, mut weight) = ([0.0f32; 3], 0.0);
+    for pixel in img.pixels() {
+        let alpha = pixel[3] as f32;
+        for (total, &channel) in sum.iter_mut().zip(&pixel.0[..3]) {
+            *total += channel as f32 * alpha;
+        }
+        weight += alpha;
+    }
+    if weight == 0.0 {
+        return [128.0; 3];
+    }
+    sum.map(|total| total / weight)
+}
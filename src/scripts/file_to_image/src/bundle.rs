@@ -0,0 +1,106 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The .icns Finder shows for an app bundle, which to the file system is just a
+/// folder: CFBundleIconFile from its Info.plist, often written without the
+/// extension, or else the file named after CFBundleIconName, which apps whose icon
+/// lives in an asset catalog still ship for older systems.
+pub fn bundle_icon(folder: &Path) -> Option<PathBuf> {
+    if !folder.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("app")) {
+        return None;
+    }
+    let contents = folder.join("Contents");
+    let plist = fs::read(contents.join("Info.plist")).ok()?;
+    ["CFBundleIconFile", "CFBundleIconName"].iter().filter_map(|key| plist_string(&plist, key)).find_map(|name| {
+        let name = if name.to_ascii_lowercase().ends_with(".icns") { name } else { format!("{}.icns", name) };
+        let path = contents.join("Resources").join(name);
+        path.is_file().then_some(path)
+    })
+}
+
+// A string from the top-level dictionary of a property list, in either of the
+// formats Xcode writes Info.plist in
+fn plist_string(bytes: &[u8], key: &str) -> Option<String> {
+    if bytes.starts_with(b"bplist00") {
+        return binary_plist_string(bytes, key);
+    }
+    let text = std::str::from_utf8(bytes).ok()?;
+    let document = roxmltree::Document::parse(text).ok()?;
+    let dict = document.root_element().children().find(|node| node.has_tag_name("dict"))?;
+    let mut elements = dict.children().filter(|node| node.is_element());
+    while let Some(element) = elements.next() {
+        if element.has_tag_name("key") && element.text() == Some(key) {
+            let value = elements.next().filter(|value| value.has_tag_name("string"))?;
+            return Some(value.text().unwrap_or_default().to_string());
+        }
+    }
+    None
+}
+
+// A binary plist ends in a 32-byte trailer giving the width of object references and
+// of the offset table's entries, the object count, the top object and where the
+// offset table is. Each object starts with a marker byte: its type in the high
+// nibble and its length in the low one, or 0xF and the length as an integer object.
+fn binary_plist_string(bytes: &[u8], key: &str) -> Option<String> {
+    let trailer = bytes.get(bytes.len().checked_sub(32)?..)?;
+    let offset_size = trailer[6] as usize;
+    let ref_size = trailer[7] as usize;
+    let count = big_endian(&trailer[8..16]);
+    let top = big_endian(&trailer[16..24]);
+    let table = big_endian(&trailer[24..32]) as usize;
+    let object = |index: u64| -> Option<usize> {
+        if index >= count {
+            return None;
+        }
+        let at = table + index as usize * offset_size;
+        Some(big_endian(bytes.get(at..at + offset_size)?) as usize)
+    };
+    let reference = |at: usize| bytes.get(at..at + ref_size).map(big_endian);
+
+    let (kind, entries, body) = object_header(bytes, object(top)?)?;
+    // 0xD is a dictionary: its key references, then its value references
+    if kind != 0xD {
+        return None;
+    }
+    (0..entries).find_map(|i| {
+        let name = binary_string(bytes, object(reference(body + i * ref_size)?)?)?;
+        if name != key {
+            return None;
+        }
+        binary_string(bytes, object(reference(body + (entries + i) * ref_size)?)?)
+    })
+}
+
+fn object_header(bytes: &[u8], at: usize) -> Option<(u8, usize, usize)> {
+    let marker = *bytes.get(at)?;
+    let (kind, length) = (marker >> 4, marker & 0xF);
+    if length != 0xF {
+        return Some((kind, length as usize, at + 1));
+    }
+    // An integer object, 0x1 with the log2 of its width
+    let integer = *bytes.get(at + 1)?;
+    if integer >> 4 != 0x1 {
+        return None;
+    }
+    let width = 1usize << (integer & 0xF);
+    let length = big_endian(bytes.get(at + 2..at + 2 + width)?) as usize;
+    Some((kind, length, at + 2 + width))
+}
+
+// 0x5 is an ASCII string, 0x6 a UTF-16 one with its length in code units
+fn binary_string(bytes: &[u8], at: usize) -> Option<String> {
+    let (kind, length, body) = object_header(bytes, at)?;
+    match kind {
+        0x5 => Some(bytes.get(body..body + length)?.iter().map(|&byte| byte as char).collect()),
+        0x6 => {
+            let units: Vec<u16> =
+                bytes.get(body..body + length * 2)?.chunks_exact(2).map(|unit| u16::from_be_bytes([unit[0], unit[1]])).collect();
+            String::from_utf16(&units).ok()
+        }
+        _ => None,
+    }
+}
+
+fn big_endian(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |value, &byte| value << 8 | byte as u64)
+}
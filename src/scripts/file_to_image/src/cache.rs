@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use altdesktop_core::sandbox;
+use anyhow::Result;
+
+use crate::colors::Colors;
+use crate::desktop_ini;
+use crate::encode::OutputFormat;
+use crate::extract::{Frame, ImageKind, ImageSource};
+use crate::hash::Hashes;
+use crate::options::Options;
+
+// Written last, so an entry without it is incomplete and treated as a miss
+const KIND_FILE: &str = "kind";
+const COLORS_FILE: &str = "colors.json";
+const HASHES_FILE: &str = "hashes.json";
+// Present when the source was animated
+const ANIMATED_FILE: &str = "animated";
+// Missing from entries written before it was recorded, which were all the file's own
+const SOURCE_FILE: &str = "source";
+
+/// One cached extraction: a directory named after a hash of the source file's
+/// path, modification time and length plus every option that changes the pixels,
+/// holding one encoded image per size.
+pub struct CacheEntry {
+    dir: PathBuf,
+}
+
+impl CacheEntry {
+    pub fn for_options(cache_dir: &str, options: &Options) -> Result<Self> {
+        let path = fs::canonicalize(&options.file_path).unwrap_or_else(|_| PathBuf::from(&options.file_path));
+        let metadata = fs::metadata(&path)?;
+        let modified = modified_nanos(&metadata);
+        // Editing a folder's desktop.ini doesn't touch the folder's own mtime
+        let ini_modified = fs::metadata(desktop_ini::ini_path(&path)).map_or(0, |ini| modified_nanos(&ini));
+
+        let mut key = Fnv1a::new();
+        key.write(env!("CARGO_PKG_VERSION").as_bytes());
+        key.write(path.to_string_lossy().as_bytes());
+        key.write(&modified.to_le_bytes());
+        key.write(&ini_modified.to_le_bytes());
+        key.write(&metadata.len().to_le_bytes());
+        key.write(options.format.extension().as_bytes());
+        key.write(&options.background.0);
+        key.write(&options.resource_index.unwrap_or(i32::MIN).to_le_bytes());
+        key.write(&[options.thumbnail as u8, options.thumbnail_cache as u8]);
+        key.write(&options.video_frame.unwrap_or(-1.0).to_le_bytes());
+        let composite = &options.composite;
+        key.write(&composite.fill.map_or([0; 7], |fill| fill.key()));
+        key.write(&composite.padding.to_le_bytes());
+        key.write(&composite.corner_radius.to_le_bytes());
+        key.write(&[options.follow_lnk as u8]);
+        key.write(&options.trim.unwrap_or(u32::MAX).to_le_bytes());
+        key.write(&options.resample.key());
+        key.write(&options.scale.to_le_bytes());
+        // Adding, removing or renaming an item touches the folder's mtime, though
+        // editing one doesn't
+        key.write(&options.montage.map_or([0; 2], |montage| montage.key()));
+        if options.ico_pack {
+            // Every size ends up in the one file
+            let sizes: Vec<u8> = options.sizes.iter().flat_map(|size| size.to_le_bytes()).collect();
+            key.write(&sizes);
+        }
+        if let Some(badge) = &options.badge {
+            // The badge is an input too, so replacing its file has to miss the cache
+            key.write(badge.path.as_bytes());
+            key.write(&fs::metadata(&badge.path).map_or(0, |badge| modified_nanos(&badge)).to_le_bytes());
+            key.write(&[badge.corner as u8]);
+            key.write(&badge.size.to_le_bytes());
+        }
+
+        Ok(CacheEntry { dir: Path::new(cache_dir).join(format!("{:016x}", key.finish())) })
+    }
+
+    /// Returns the cached images for `sizes`, or `None` if any of them is missing.
+    pub fn load(&self, sizes: &[u32], format: OutputFormat) -> Option<(ImageKind, ImageSource, Vec<Frame>)> {
+        let kind = ImageKind::parse(fs::read_to_string(self.dir.join(KIND_FILE)).ok()?.trim())?;
+        let source = fs::read_to_string(self.dir.join(SOURCE_FILE))
+            .ok()
+            .and_then(|source| ImageSource::parse(source.trim()))
+            .unwrap_or(ImageSource::File);
+        let frames = sizes
+            .iter()
+            .map(|&size| Some((size, fs::read(self.frame_path(size, format)).ok()?)))
+            .collect::<Option<Vec<_>>>()?;
+        Some((kind, source, frames))
+    }
+
+    pub fn store(&self, kind: ImageKind, source: ImageSource, frames: &[Frame], format: OutputFormat) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        for (size, encoded) in frames {
+            fs::write(self.frame_path(*size, format), encoded)?;
+        }
+        fs::write(self.dir.join(SOURCE_FILE), source.name())?;
+        fs::write(self.dir.join(KIND_FILE), kind.name())?;
+        Ok(())
+    }
+
+    /// `Some(None)` means the colors were computed and the image had no visible pixels.
+    pub fn load_colors(&self) -> Option<Option<Colors>> {
+        serde_json::from_str(&fs::read_to_string(self.dir.join(COLORS_FILE)).ok()?).ok()
+    }
+
+    pub fn store_colors(&self, colors: Option<&Colors>) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(COLORS_FILE), serde_json::to_string(&colors)?)?;
+        Ok(())
+    }
+
+    pub fn load_hashes(&self) -> Option<Hashes> {
+        serde_json::from_str(&fs::read_to_string(self.dir.join(HASHES_FILE)).ok()?).ok()
+    }
+
+    pub fn store_hashes(&self, hashes: &Hashes) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(HASHES_FILE), serde_json::to_string(hashes)?)?;
+        Ok(())
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.dir.join(ANIMATED_FILE).is_file()
+    }
+
+    pub fn mark_animated(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(ANIMATED_FILE), "")?;
+        Ok(())
+    }
+
+    /// Deletes the entry, for a source that changed in a way its key can't tell, such
+    /// as a shortcut's target being updated while the shortcut stays the same.
+    pub fn remove(&self) -> Result<()> {
+        sandbox::check_output(&self.dir)?;
+        match fs::remove_dir_all(&self.dir) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn frame_path(&self, size: u32, format: OutputFormat) -> PathBuf {
+        self.dir.join(format!("{}.{}", size, format.extension()))
+    }
+}
+
+// std's DefaultHasher isn't guaranteed stable between Rust releases, and the
+// cache outlives the binary that wrote it
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        // Separates fields so ("ab", "c") and ("a", "bc") hash differently
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn modified_nanos(metadata: &fs::Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_nanos())
+}
@@ -0,0 +1,57 @@
+use image::{imageops::FilterType, DynamicImage, Rgb};
+use serde::{Deserialize, Serialize};
+
+// Pixels more transparent than this are edges and shadows, not the icon's color
+const MIN_ALPHA: u8 = 128;
+// The histogram keeps 4 bits per channel, which groups shades of the same color
+const BUCKET_BITS: u32 = 4;
+// Colors don't change with resolution, and a 256px tile is 16x the work of 64px
+const SAMPLE_SIZE: u32 = 64;
+
+/// Colors for tinting a tile to match its icon, as "#RRGGBB".
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Colors {
+    /// The most common color, averaged over the pixels that share its histogram bucket.
+    pub dominant: String,
+    /// The alpha-weighted mean of every visible pixel.
+    pub average: String,
+}
+
+/// Returns `None` for images with no visible pixels.
+pub fn icon_colors(img: &DynamicImage) -> Option<Colors> {
+    let sample = if img.width() > SAMPLE_SIZE || img.height() > SAMPLE_SIZE {
+        img.resize(SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle).to_rgba8()
+    } else {
+        img.to_rgba8()
+    };
+
+    const BUCKETS: usize = 1 << (3 * BUCKET_BITS);
+    // Per bucket: alpha-weighted sums of r, g, b, and the total weight
+    let mut histogram = vec![[0u64; 4]; BUCKETS];
+    let mut total = [0u64; 4];
+    for pixel in sample.pixels().filter(|pixel| pixel[3] >= MIN_ALPHA) {
+        let [r, g, b, a] = pixel.0.map(u64::from);
+        let shift = 8 - BUCKET_BITS;
+        let bucket = ((r >> shift) << (2 * BUCKET_BITS)) | ((g >> shift) << BUCKET_BITS) | (b >> shift);
+        for sums in [&mut histogram[bucket as usize], &mut total] {
+            sums[0] += r * a;
+            sums[1] += g * a;
+            sums[2] += b * a;
+            sums[3] += a;
+        }
+    }
+    if total[3] == 0 {
+        return None;
+    }
+
+    let dominant = histogram.iter().max_by_key(|sums| sums[3]).unwrap();
+    Some(Colors { dominant: hex(mean(dominant)), average: hex(mean(&total)) })
+}
+
+fn mean(sums: &[u64; 4]) -> Rgb<u8> {
+    Rgb([0, 1, 2].map(|i| ((sums[i] + sums[3] / 2) / sums[3]) as u8))
+}
+
+fn hex(Rgb([r, g, b]): Rgb<u8>) -> String {
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
@@ -0,0 +1,110 @@
+use image::{imageops, DynamicImage, Rgb, Rgba, RgbaImage};
+
+use crate::encode::parse_color;
+use crate::resample::Resample;
+
+#[derive(Clone, Copy)]
+pub enum Fill {
+    Solid(Rgb<u8>),
+    // Top to bottom
+    Gradient(Rgb<u8>, Rgb<u8>),
+}
+
+impl Fill {
+    /// Parses "#RRGGBB", or "#RRGGBB,#RRGGBB" for a vertical gradient.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.split_once(',') {
+            Some((top, bottom)) => Ok(Fill::Gradient(parse_color(top.trim())?, parse_color(bottom.trim())?)),
+            None => Ok(Fill::Solid(parse_color(value)?)),
+        }
+    }
+
+    fn color_at(self, y: u32, height: u32) -> Rgba<u8> {
+        let Rgb(color) = match self {
+            Fill::Solid(color) => color,
+            Fill::Gradient(Rgb(top), Rgb(bottom)) => {
+                let t = y as f32 / (height.max(2) - 1) as f32;
+                Rgb(std::array::from_fn(|i| (top[i] as f32 + (bottom[i] as f32 - top[i] as f32) * t).round() as u8))
+            }
+        };
+        Rgba([color[0], color[1], color[2], 255])
+    }
+
+    /// Bytes that identify the fill in cache keys.
+    pub fn key(self) -> [u8; 7] {
+        match self {
+            Fill::Solid(Rgb([r, g, b])) => [1, r, g, b, 0, 0, 0],
+            Fill::Gradient(Rgb([r1, g1, b1]), Rgb([r2, g2, b2])) => [2, r1, g1, b1, r2, g2, b2],
+        }
+    }
+}
+
+/// Turns the extracted image into a finished tile: the image centered on `fill`,
+/// inset by `padding` and clipped to rounded corners. Padding and radius are
+/// percentages of the tile size, so every requested size looks the same.
+#[derive(Clone, Copy, Default)]
+pub struct Composite {
+    pub fill: Option<Fill>,
+    pub padding: u32,
+    pub corner_radius: u32,
+}
+
+impl Composite {
+    pub fn is_active(&self) -> bool {
+        self.fill.is_some() || self.padding > 0 || self.corner_radius > 0
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.padding >= 50 {
+            return Err(format!("Invalid padding: {}% (must be below 50)", self.padding));
+        }
+        if self.corner_radius > 50 {
+            return Err(format!("Invalid corner radius: {}% (at most 50, which gives a circle)", self.corner_radius));
+        }
+        Ok(())
+    }
+
+    /// The image scaled to fit inside the padding of a `size`x`size` tile.
+    pub fn content(&self, img: &DynamicImage, size: u32, resample: &Resample) -> RgbaImage {
+        let inner = (size - 2 * (size * self.padding / 100)).max(1);
+        resample.resize(img, inner, inner).to_rgba8()
+    }
+
+    pub fn corner_radius_px(&self, size: u32) -> f32 {
+        size as f32 * self.corner_radius as f32 / 100.0
+    }
+
+    /// Renders a `size`x`size` tile. The image keeps its aspect ratio inside the padding.
+    pub fn apply(&self, img: &DynamicImage, size: u32, resample: &Resample) -> DynamicImage {
+        let content = self.content(img, size, resample);
+
+        let mut tile = match self.fill {
+            Some(fill) => RgbaImage::from_fn(size, size, |_, y| fill.color_at(y, size)),
+            None => RgbaImage::new(size, size),
+        };
+        let x = (size - content.width()) / 2;
+        let y = (size - content.height()) / 2;
+        imageops::overlay(&mut tile, &content, x as i64, y as i64);
+
+        if self.corner_radius > 0 {
+            round_corners(&mut tile, self.corner_radius_px(size));
+        }
+        DynamicImage::ImageRgba8(tile)
+    }
+}
+
+// Scales alpha by how much of each pixel lies inside the rounded rectangle, which
+// anti-aliases the edge
+fn round_corners(tile: &mut RgbaImage, radius: f32) {
+    let (width, height) = (tile.width() as f32, tile.height() as f32);
+    for (x, y, pixel) in tile.enumerate_pixels_mut() {
+        let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+        let dx = (radius - px).max(px - (width - radius)).max(0.0);
+        let dy = (radius - py).max(py - (height - radius)).max(0.0);
+        if dx == 0.0 || dy == 0.0 {
+            continue;
+        }
+        let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+}
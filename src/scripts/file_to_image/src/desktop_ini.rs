@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::RgbaImage;
+
+use crate::ico;
+#[cfg(windows)]
+use crate::resource;
+
+/// The icon a folder's desktop.ini points Explorer at, as a path and icon index.
+pub struct FolderIcon {
+    pub path: PathBuf,
+    pub index: i32,
+}
+
+pub fn ini_path(folder: &Path) -> PathBuf {
+    folder.join("desktop.ini")
+}
+
+/// Reads `IconResource=<path>,<index>` from the `[.ShellClassInfo]` section, or the
+/// older `IconFile`/`IconIndex` pair. Relative paths are resolved against the folder.
+pub fn folder_icon(folder: &Path) -> Option<FolderIcon> {
+    let text = read_ini(&ini_path(folder))?;
+
+    let mut in_section = false;
+    let mut icon_resource = None;
+    let mut icon_file = None;
+    let mut icon_index = None;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_section = line.eq_ignore_ascii_case("[.ShellClassInfo]");
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=').filter(|_| in_section) else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_string();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "iconresource" => icon_resource = Some(value),
+            "iconfile" => icon_file = Some(value),
+            "iconindex" => icon_index = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    let (path, index) = match icon_resource {
+        // The index follows the last comma, since paths may contain commas too
+        Some(resource) => match resource.rsplit_once(',') {
+            Some((path, index)) if index.trim().parse::<i32>().is_ok() => {
+                (path.trim().to_string(), index.trim().parse().unwrap())
+            }
+            _ => (resource, 0),
+        },
+        None => (icon_file?, icon_index.unwrap_or(0)),
+    };
+    if path.is_empty() {
+        return None;
+    }
+    Some(FolderIcon { path: folder.join(expand_env_vars(&path)), index })
+}
+
+/// Extracts the folder's custom icon at `size`, or `None` to use the shell's folder icon.
+pub fn extract_folder_icon(folder: &Path, size: u32) -> Option<RgbaImage> {
+    let icon = folder_icon(folder)?;
+    let path = icon.path.to_str()?;
+    if ico::is_ico(path) {
+        if let Ok(Some(img)) = ico::extract_ico_frame(path, size) {
+            return Some(img);
+        }
+    }
+    #[cfg(windows)]
+    if let Ok(img) = resource::extract_resource_icon(path, icon.index, size) {
+        return Some(img);
+    }
+    None
+}
+
+// Explorer writes desktop.ini as UTF-16 once a folder has a localized name, ANSI otherwise
+fn read_ini(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    Some(match bytes.strip_prefix(&[0xFF, 0xFE]) {
+        Some(utf16) => String::from_utf16_lossy(
+            &utf16.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect::<Vec<_>>(),
+        ),
+        None => String::from_utf8_lossy(&bytes).into_owned(),
+    })
+}
+
+// %SystemRoot%\system32\shell32.dll and the like; unknown variables are left as written
+fn expand_env_vars(value: &str) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('%') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('%') {
+            Some(end) => {
+                let name = &after[..end];
+                match std::env::var(name) {
+                    Ok(var) if !name.is_empty() => expanded.push_str(&var),
+                    _ => {
+                        expanded.push('%');
+                        expanded.push_str(name);
+                        expanded.push('%');
+                    }
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                expanded.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
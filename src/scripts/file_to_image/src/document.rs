@@ -0,0 +1,53 @@
+use std::path::Path;
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use windows::{
+    core::HSTRING,
+    Data::Pdf::{PdfDocument, PdfPageRenderOptions},
+    Storage::StorageFile,
+    Storage::Streams::{DataReader, InMemoryRandomAccessStream},
+};
+
+use crate::error::{failure, ErrorCode};
+
+pub fn is_pdf(file_path: &str) -> bool {
+    Path::new(file_path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"))
+}
+
+/// Renders the first page of a PDF to fit in `size`, using the PDF engine built
+/// into Windows rather than whichever reader registered its thumbnail handler.
+pub fn render_pdf_page(file_path: &str, size: u32) -> Result<RgbaImage> {
+    render(file_path, size).with_context(|| format!("Failed to render the first page of {}", file_path))
+}
+
+fn render(file_path: &str, size: u32) -> Result<RgbaImage> {
+    // StorageFile wants an absolute path with backslashes
+    let path = std::fs::canonicalize(file_path)?;
+    let path = path.to_string_lossy();
+    let file = StorageFile::GetFileFromPathAsync(&HSTRING::from(path.trim_start_matches(r"\\?\")))?.get()?;
+    let document = PdfDocument::LoadFromFileAsync(&file)?.get()?;
+    if document.PageCount()? == 0 {
+        return Err(failure(ErrorCode::Decode, "The document has no pages"));
+    }
+    let page = document.GetPage(0)?;
+
+    // Fit the page's longer side to the requested size
+    let page_size = page.Size()?;
+    let scale = size as f32 / page_size.Width.max(page_size.Height).max(1.0);
+    let options = PdfPageRenderOptions::new()?;
+    options.SetDestinationWidth(((page_size.Width * scale).round() as u32).max(1))?;
+    options.SetDestinationHeight(((page_size.Height * scale).round() as u32).max(1))?;
+
+    let stream = InMemoryRandomAccessStream::new()?;
+    page.RenderWithOptionsToStreamAsync(&stream, &options)?.get()?;
+    let length = stream.Size()? as u32;
+    let reader = DataReader::CreateDataReader(&stream.GetInputStreamAt(0)?)?;
+    reader.LoadAsync(length)?.get()?;
+    let mut encoded = vec![0; length as usize];
+    reader.ReadBytes(&mut encoded)?;
+
+    // The page comes back encoded as a PNG
+    Ok(image::load_from_memory(&encoded)?.to_rgba8())
+}
@@ -0,0 +1,40 @@
+use windows::{
+    Win32::Foundation::POINT,
+    Win32::Graphics::Gdi::{MonitorFromPoint, HMONITOR, MONITOR_DEFAULTTONEAREST, MONITOR_DEFAULTTOPRIMARY},
+    Win32::UI::HiDpi::{
+        GetDpiForMonitor, SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+        MDT_EFFECTIVE_DPI,
+    },
+    Win32::UI::WindowsAndMessaging::GetCursorPos,
+};
+
+const BASE_DPI: u32 = 96;
+
+/// The primary monitor's display scale, e.g. 1.5 at 150%. Falls back to 1.0.
+pub fn primary_monitor_scale() -> f32 {
+    monitor_scale(|| unsafe { MonitorFromPoint(POINT { x: 0, y: 0 }, MONITOR_DEFAULTTOPRIMARY) })
+}
+
+/// The display scale of the monitor under the mouse cursor, which on a mixed-DPI
+/// setup is usually the one the icons are about to be shown on. Falls back to 1.0.
+pub fn cursor_monitor_scale() -> f32 {
+    monitor_scale(|| unsafe {
+        let mut point = POINT::default();
+        let _ = GetCursorPos(&mut point);
+        MonitorFromPoint(point, MONITOR_DEFAULTTONEAREST)
+    })
+}
+
+// The monitor is looked up once the process is DPI aware, so its coordinates are
+// physical pixels too
+fn monitor_scale(monitor: impl FnOnce() -> HMONITOR) -> f32 {
+    unsafe {
+        // DPI-unaware processes are always told 96
+        let _ = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+        let (mut dpi_x, mut dpi_y) = (0, 0);
+        match GetDpiForMonitor(monitor(), MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) {
+            Ok(()) if dpi_x > 0 => dpi_x as f32 / BASE_DPI as f32,
+            _ => 1.0,
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, Write},
+};
+use anyhow::Result;
+use crate::svg;
+use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageFormat, Rgb, RgbImage};
+
+pub const DEFAULT_BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+
+#[derive(Clone, Copy)]
+pub enum OutputFormat {
+    Png,
+    WebP,
+    Ico,
+    Bmp,
+    Jpeg,
+    // A PNG embedded in an SVG, so tiles scale in the frontend
+    Svg,
+    // Undecoded pixels for a host that draws them straight away: the width and height
+    // as little-endian u32s, then the RGBA rows top to bottom
+    Rgba,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "png" => Ok(OutputFormat::Png),
+            "webp" => Ok(OutputFormat::WebP),
+            "ico" => Ok(OutputFormat::Ico),
+            "bmp" => Ok(OutputFormat::Bmp),
+            "jpeg" | "jpg" => Ok(OutputFormat::Jpeg),
+            "svg" => Ok(OutputFormat::Svg),
+            "rgba" => Ok(OutputFormat::Rgba),
+            _ => Err(format!("Invalid format: {} (expected png, webp, ico, bmp, jpeg, svg or rgba)", value)),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Ico => "ico",
+            OutputFormat::Bmp => "bmp",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Svg => "svg",
+            OutputFormat::Rgba => "rgba",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::WebP => "image/webp",
+            OutputFormat::Ico => "image/x-icon",
+            OutputFormat::Bmp => "image/bmp",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Svg => "image/svg+xml",
+            OutputFormat::Rgba => "application/octet-stream",
+        }
+    }
+}
+
+/// Parses "#RRGGBB" (the # is optional).
+pub fn parse_color(value: &str) -> Result<Rgb<u8>, String> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok(Rgb([r, g, b])),
+        _ => Err(format!("Invalid color: {} (expected #RRGGBB)", value)),
+    }
+}
+
+pub fn write_image(img: &DynamicImage, output_path: &str, format: OutputFormat, background: Rgb<u8>) -> Result<()> {
+    let mut out = BufWriter::new(File::create(output_path)?);
+    encode_image(img, &mut out, format, background)?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn encode_image<W: Write + Seek>(img: &DynamicImage, out: &mut W, format: OutputFormat, background: Rgb<u8>) -> Result<()> {
+    match format {
+        OutputFormat::Png => img.write_to(out, ImageFormat::Png)?,
+        // The WebP encoder is lossless, so this only trades encode time for a smaller file
+        OutputFormat::WebP => img.write_to(out, ImageFormat::WebP)?,
+        OutputFormat::Ico => img.write_to(out, ImageFormat::Ico)?,
+        OutputFormat::Bmp => img.write_to(out, ImageFormat::Bmp)?,
+        // JPEG has no alpha channel, so transparent pixels are blended onto the background
+        OutputFormat::Jpeg => {
+            JpegEncoder::new_with_quality(out, 90).encode_image(&flatten(img, background))?
+        }
+        OutputFormat::Svg => out.write_all(&svg::wrap_image(img)?)?,
+        OutputFormat::Rgba => {
+            let rgba = img.to_rgba8();
+            out.write_all(&rgba.width().to_le_bytes())?;
+            out.write_all(&rgba.height().to_le_bytes())?;
+            out.write_all(rgba.as_raw())?;
+        }
+    }
+    Ok(())
+}
+
+fn flatten(img: &DynamicImage, background: Rgb<u8>) -> RgbImage {
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let pixel = rgba.get_pixel(x, y);
+        let alpha = pixel[3] as u32;
+        Rgb(std::array::from_fn(|i| {
+            ((pixel[i] as u32 * alpha + background[i] as u32 * (255 - alpha) + 127) / 255) as u8
+        }))
+    })
+}
@@ -0,0 +1,163 @@
+use std::path::Path;
+use altdesktop_core::sandbox;
+use anyhow::{bail, Result};
+use image::{imageops::FilterType, DynamicImage, Rgb};
+use serde::Serialize;
+use windows::{
+    core::{HSTRING, PCWSTR},
+    Win32::Foundation::{FreeLibrary, BOOL, HMODULE},
+    Win32::System::LibraryLoader::*,
+};
+
+use crate::encode::{write_image, OutputFormat};
+use crate::error::{failure, ErrorCode};
+use crate::resource::extract_resource_icon;
+
+// MAKEINTRESOURCE(RT_ICON + 11); the windows crate only defines RT_ICON
+const RT_GROUP_ICON: PCWSTR = PCWSTR(14 as _);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IconGroup {
+    /// Position as used by --resource-index and the "Change Icon" dialog.
+    pub index: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_id: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub images: Vec<IconImage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_path: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IconImage {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u16,
+}
+
+enum ResourceName {
+    Id(u16),
+    Name(String),
+}
+
+/// Lists the icon groups in an .exe or .dll. With `output_dir`, also writes the
+/// largest image of every group there as `icon_<index>`.
+pub fn enumerate_icons(
+    file_path: &str,
+    output_dir: Option<&str>,
+    format: OutputFormat,
+    background: Rgb<u8>,
+) -> Result<Vec<IconGroup>> {
+    sandbox::check_input(file_path)?;
+    if let Some(output_dir) = output_dir {
+        sandbox::check_output(Path::new(output_dir))?;
+    }
+    let module = unsafe {
+        LoadLibraryExW(
+            &HSTRING::from(file_path),
+            None,
+            LOAD_LIBRARY_AS_DATAFILE | LOAD_LIBRARY_AS_IMAGE_RESOURCE,
+        )
+    }
+    .map_err(|e| failure(ErrorCode::Unsupported, format!("Not a PE binary: {} ({})", file_path, e.message())))?;
+
+    let groups = read_groups(module);
+    unsafe {
+        let _ = FreeLibrary(module);
+    }
+    let mut groups = groups?;
+
+    if let Some(output_dir) = output_dir {
+        std::fs::create_dir_all(output_dir)?;
+        for group in &mut groups {
+            let size = group.images.iter().map(|image| image.width).max().unwrap_or(32);
+            let img = extract_resource_icon(file_path, group.index, size)?;
+            // Icons can come back at a neighbouring size, so normalize to the frame's size
+            let img = DynamicImage::ImageRgba8(img).resize_exact(size, size, FilterType::Lanczos3);
+            let output_path = Path::new(output_dir)
+                .join(format!("icon_{}.{}", group.index, format.extension()))
+                .to_string_lossy()
+                .into_owned();
+            sandbox::check_output(Path::new(&output_path))?;
+            write_image(&img, &output_path, format, background)?;
+            group.output_path = Some(output_path);
+        }
+    }
+
+    Ok(groups)
+}
+
+fn read_groups(module: HMODULE) -> Result<Vec<IconGroup>> {
+    let mut names: Vec<ResourceName> = Vec::new();
+    unsafe {
+        // Fails with ERROR_RESOURCE_TYPE_NOT_FOUND when there are no icons, which is just an empty list
+        let _ = EnumResourceNamesW(module, RT_GROUP_ICON, Some(collect_name), &mut names as *mut _ as isize);
+    }
+
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let images = read_group_directory(module, &name)?;
+            let (resource_id, name) = match name {
+                ResourceName::Id(id) => (Some(id), None),
+                ResourceName::Name(name) => (None, Some(name)),
+            };
+            Ok(IconGroup { index: index as i32, resource_id, name, images, output_path: None })
+        })
+        .collect()
+}
+
+unsafe extern "system" fn collect_name(_module: HMODULE, _kind: PCWSTR, name: PCWSTR, names: isize) -> BOOL {
+    let names = &mut *(names as *mut Vec<ResourceName>);
+    // IS_INTRESOURCE: IDs are passed as pointers below 0x10000
+    if (name.0 as usize) >> 16 == 0 {
+        names.push(ResourceName::Id(name.0 as usize as u16));
+    } else {
+        names.push(ResourceName::Name(name.to_string().unwrap_or_default()));
+    }
+    BOOL(1)
+}
+
+// A GRPICONDIR: a 6-byte header followed by 14-byte entries describing each RT_ICON image
+fn read_group_directory(module: HMODULE, name: &ResourceName) -> Result<Vec<IconImage>> {
+    let wide_name;
+    let name = match name {
+        ResourceName::Id(id) => PCWSTR(*id as usize as *const u16),
+        ResourceName::Name(name) => {
+            wide_name = HSTRING::from(name.as_str());
+            PCWSTR(wide_name.as_ptr())
+        }
+    };
+
+    let data = unsafe {
+        let info = FindResourceW(module, name, RT_GROUP_ICON);
+        if info.is_invalid() {
+            bail!("Icon group resource is missing");
+        }
+        let size = SizeofResource(module, info) as usize;
+        let pointer = LockResource(LoadResource(module, info)?) as *const u8;
+        if pointer.is_null() {
+            bail!("Icon group resource could not be loaded");
+        }
+        std::slice::from_raw_parts(pointer, size)
+    };
+
+    let u16_at = |offset: usize| data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let count = u16_at(4).unwrap_or(0) as usize;
+    Ok((0..count)
+        .map_while(|i| {
+            let entry = data.get(6 + i * 14..6 + (i + 1) * 14)?;
+            // Sizes are stored in a byte, where 0 means 256
+            let dimension = |byte: u8| if byte == 0 { 256 } else { byte as u32 };
+            Some(IconImage {
+                width: dimension(entry[0]),
+                height: dimension(entry[1]),
+                bit_depth: u16::from_le_bytes([entry[6], entry[7]]),
+            })
+        })
+        .collect())
+}
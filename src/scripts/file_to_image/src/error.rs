@@ -0,0 +1,204 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use altdesktop_core::exit;
+use altdesktop_core::sandbox::Rejected;
+use serde::Serialize;
+
+use crate::favicon;
+
+/// Why a run failed, as stable names callers can branch on: `timeout` is worth a
+/// retry, `notFound` and `unsupported` call for a custom icon, `cancelled` for
+/// nothing, and the rest for the generic one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    InvalidArguments,
+    NotFound,
+    AccessDenied,
+    /// The file type, or the requested mode, has nothing to extract from.
+    Unsupported,
+    Timeout,
+    /// The source was found but its contents couldn't be decoded.
+    Decode,
+    /// A batch or served job was cancelled before it finished.
+    Cancelled,
+    Unknown,
+}
+
+impl ErrorCode {
+    /// What to show the user, from the catalog the helper's own tools share, in the
+    /// language `--lang` picked.
+    pub fn user_message(self) -> &'static str {
+        altdesktop_core::messages::user_message(match self {
+            ErrorCode::InvalidArguments => "E_INVALID_ARG",
+            ErrorCode::NotFound => "E_NOT_FOUND",
+            ErrorCode::AccessDenied => "E_ACCESS",
+            ErrorCode::Unsupported => "E_UNSUPPORTED",
+            ErrorCode::Timeout => "E_TIMEOUT",
+            ErrorCode::Decode => "E_DECODE",
+            ErrorCode::Cancelled => "E_CANCELLED",
+            ErrorCode::Unknown => "E_FAIL",
+        })
+    }
+
+    /// What a run that failed with this code exits with, from the scheme the helper's
+    /// own tools share.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorCode::InvalidArguments => exit::USAGE,
+            ErrorCode::NotFound => exit::NOT_FOUND,
+            ErrorCode::AccessDenied => exit::ACCESS_DENIED,
+            ErrorCode::Unsupported => exit::UNSUPPORTED,
+            ErrorCode::Timeout => exit::TIMEOUT,
+            ErrorCode::Decode | ErrorCode::Cancelled | ErrorCode::Unknown => exit::FAILURE,
+        }
+    }
+}
+
+/// An error whose cause is known where it's raised, rather than inferred from
+/// the underlying error.
+#[derive(Debug)]
+pub struct Failure {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+pub fn failure(code: ErrorCode, message: impl Into<String>) -> anyhow::Error {
+    Failure { code, message: message.into() }.into()
+}
+
+/// A safe point: fails with `cancelled` once the job running on this thread has been
+/// cancelled.
+pub fn check_cancelled() -> anyhow::Result<()> {
+    if altdesktop_core::cancel::cancelled() {
+        return Err(failure(ErrorCode::Cancelled, "The job was cancelled"));
+    }
+    Ok(())
+}
+
+/// The JSON written to stderr when a run fails.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorReport {
+    pub code: ErrorCode,
+    pub message: String,
+    pub user_message: &'static str,
+}
+
+impl ErrorReport {
+    pub fn new(error: &anyhow::Error, source: Option<&str>) -> Self {
+        Self::with_code(classify(error, source), format!("{:#}", error))
+    }
+
+    pub fn with_code(code: ErrorCode, message: String) -> Self {
+        ErrorReport { code, message, user_message: code.user_message() }
+    }
+}
+
+/// Records a failure in the log, when it's on, with the HRESULT behind it if the
+/// shell, WIC or Media Foundation raised it. The caller's span carries the request.
+pub fn log_failure(error: &anyhow::Error, source: Option<&str>) {
+    tracing::error!(code = ?classify(error, source), hr = hresult(error).as_deref(), "{:#}", error);
+}
+
+#[cfg(windows)]
+fn hresult(error: &anyhow::Error) -> Option<String> {
+    let error = error.chain().find_map(|cause| cause.downcast_ref::<windows::core::Error>())?;
+    Some(format!("0x{:08X}", error.code().0 as u32))
+}
+
+#[cfg(not(windows))]
+fn hresult(_error: &anyhow::Error) -> Option<String> {
+    None
+}
+
+/// Finds the first cause in the chain with a recognizable kind. `source` is the
+/// input path, which settles otherwise unexplained failures when it doesn't exist.
+pub fn classify(error: &anyhow::Error, source: Option<&str>) -> ErrorCode {
+    let code = error.chain().find_map(code_of).unwrap_or(ErrorCode::Unknown);
+    match source {
+        // Shell namespaces and URLs aren't paths, so their absence proves nothing
+        Some(source)
+            if code == ErrorCode::Unknown
+                && !favicon::is_web_source(source)
+                && !source.starts_with("::")
+                && !source.to_ascii_lowercase().starts_with("shell:")
+                && !Path::new(source).exists() =>
+        {
+            ErrorCode::NotFound
+        }
+        _ => code,
+    }
+}
+
+fn code_of(cause: &(dyn std::error::Error + 'static)) -> Option<ErrorCode> {
+    if let Some(failure) = cause.downcast_ref::<Failure>() {
+        return Some(failure.code);
+    }
+    if let Some(rejected) = cause.downcast_ref::<Rejected>() {
+        return Some(match rejected {
+            Rejected::Invalid(_) => ErrorCode::InvalidArguments,
+            Rejected::Outside(_) => ErrorCode::AccessDenied,
+        });
+    }
+    if let Some(error) = cause.downcast_ref::<io::Error>() {
+        return io_code(error);
+    }
+    if let Some(error) = cause.downcast_ref::<image::ImageError>() {
+        return match error {
+            image::ImageError::IoError(error) => io_code(error),
+            image::ImageError::Unsupported(_) => Some(ErrorCode::Unsupported),
+            _ => Some(ErrorCode::Decode),
+        };
+    }
+    if let Some(ureq::Error::Status(status, _)) = cause.downcast_ref::<ureq::Error>() {
+        return match status {
+            401 | 403 => Some(ErrorCode::AccessDenied),
+            404 | 410 => Some(ErrorCode::NotFound),
+            408 | 504 => Some(ErrorCode::Timeout),
+            _ => None,
+        };
+    }
+    #[cfg(windows)]
+    if let Some(error) = cause.downcast_ref::<windows::core::Error>() {
+        return hresult_code(error.code().0 as u32);
+    }
+    None
+}
+
+fn io_code(error: &io::Error) -> Option<ErrorCode> {
+    match error.kind() {
+        io::ErrorKind::NotFound => Some(ErrorCode::NotFound),
+        io::ErrorKind::PermissionDenied => Some(ErrorCode::AccessDenied),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Some(ErrorCode::Timeout),
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => Some(ErrorCode::Decode),
+        io::ErrorKind::Unsupported => Some(ErrorCode::Unsupported),
+        _ => None,
+    }
+}
+
+#[cfg(windows)]
+fn hresult_code(hresult: u32) -> Option<ErrorCode> {
+    match hresult {
+        // Win32 file, path, name, resource type and resource name not found
+        0x8007_0002 | 0x8007_0003 | 0x8007_007B | 0x8007_0715 | 0x8007_0716 => Some(ErrorCode::NotFound),
+        // Access denied, sharing violation
+        0x8007_0005 | 0x8007_0020 => Some(ErrorCode::AccessDenied),
+        0x8007_05B4 => Some(ErrorCode::Timeout),
+        // No handler: E_NOINTERFACE, REGDB_E_CLASSNOTREG, WINCODEC_ERR_COMPONENTNOTFOUND,
+        // MF_E_UNSUPPORTED_BYTESTREAM_TYPE, ERROR_BAD_EXE_FORMAT
+        0x8000_4002 | 0x8004_0154 | 0x8898_2F50 | 0xC00D_36C4 | 0x8007_00C1 => Some(ErrorCode::Unsupported),
+        // ERROR_INVALID_DATA, WINCODEC_ERR_BADHEADER, WINCODEC_ERR_BADIMAGE
+        0x8007_000D | 0x8898_2F61 | 0x8898_2F60 => Some(ErrorCode::Decode),
+        _ => None,
+    }
+}
@@ -0,0 +1,642 @@
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::path::Path;
+use altdesktop_core::sandbox;
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::DynamicImage;
+use file_icon_provider::get_file_icon;
+use serde::Serialize;
+
+use crate::blur::blurred_backdrop;
+use crate::cache::CacheEntry;
+use crate::colors::{icon_colors, Colors};
+use crate::encode::{encode_image, OutputFormat};
+use crate::error::check_cancelled;
+use crate::fallback::fallback_icon;
+use crate::hash::{icon_hashes, Hashes};
+use crate::{bundle, desktop_ini, favicon, icns, ico, svg};
+#[cfg(windows)]
+use crate::{appx, document, jumbo, lnk, resource, shared_memory, stock, thumbnail, video};
+#[cfg(not(windows))]
+use crate::error::{failure, ErrorCode};
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::{xdg, xdg_thumbnail};
+use crate::image_file::{decode_image_file, is_image_file};
+use crate::montage::folder_montage;
+use crate::options::Options;
+use crate::resample::Resample;
+use crate::trim::trim;
+use crate::variants::{variant_for, variant_path, Theme};
+use crate::watchdog;
+
+// The 100% to 200% renditions of the standard 16, 32 and 48px icons, plus 256
+const NATIVE_SIZES: &[u32] = &[16, 20, 24, 32, 40, 48, 64, 96, 128, 256];
+
+/// Whether an image shows the file's contents or just its type.
+#[derive(Clone, Copy)]
+pub enum ImageKind {
+    Thumbnail,
+    Icon,
+}
+
+impl ImageKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageKind::Thumbnail => "thumbnail",
+            ImageKind::Icon => "icon",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "thumbnail" => Some(ImageKind::Thumbnail),
+            "icon" => Some(ImageKind::Icon),
+            _ => None,
+        }
+    }
+}
+
+/// Which step of the chain the image came from. The last three are fallbacks, for a
+/// source whose own icon couldn't be extracted, and are never cached.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ImageSource {
+    /// The file itself: its icon, thumbnail or image.
+    File,
+    /// A shortcut's icon location.
+    LinkIcon,
+    /// A shortcut's target.
+    LinkTarget,
+    /// The icon registered for the file's type.
+    Association,
+    /// The shell's stock folder, program or document icon.
+    Stock,
+    /// A plain page, when nothing else gave an icon.
+    Generic,
+}
+
+impl ImageSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageSource::File => "file",
+            ImageSource::LinkIcon => "linkIcon",
+            ImageSource::LinkTarget => "linkTarget",
+            ImageSource::Association => "association",
+            ImageSource::Stock => "stock",
+            ImageSource::Generic => "generic",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "file" => Some(ImageSource::File),
+            "linkIcon" => Some(ImageSource::LinkIcon),
+            "linkTarget" => Some(ImageSource::LinkTarget),
+            "association" => Some(ImageSource::Association),
+            "stock" => Some(ImageSource::Stock),
+            "generic" => Some(ImageSource::Generic),
+            _ => None,
+        }
+    }
+
+    pub fn is_fallback(self) -> bool {
+        matches!(self, ImageSource::Association | ImageSource::Stock | ImageSource::Generic)
+    }
+}
+
+/// An encoded image and the size it was requested at.
+pub type Frame = (u32, Vec<u8>);
+
+// A --variants or --blur-backdrop image: its path, its variant name and the encoded image
+type Companion = (String, &'static str, Vec<u8>);
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputStatus {
+    Extracted,
+    // Served from --cache-dir without touching the source file
+    Cached,
+    // The output file already held exactly these bytes, so it wasn't rewritten
+    Unchanged,
+}
+
+#[derive(Serialize)]
+pub struct Output {
+    pub path: String,
+    pub status: OutputStatus,
+    /// Which companion image this is: the theme a --variants output is meant for,
+    /// or "blur" for --blur-backdrop.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<&'static str>,
+}
+
+/// A frame handed over in a named section instead of a file, for a host that would
+/// otherwise read hundreds of icons back from disk or as base64.
+#[derive(Serialize)]
+pub struct Section {
+    pub name: String,
+    /// The requested size the frame is for.
+    pub size: u32,
+    /// Bytes of image data at the start of the section, which the system may round up.
+    pub length: usize,
+}
+
+pub struct Extraction {
+    pub kind: ImageKind,
+    pub source: ImageSource,
+    pub outputs: Vec<Output>,
+    /// Only for `shared_memory`, in place of the outputs.
+    pub sections: Vec<Section>,
+    /// Only computed for --colors.
+    pub colors: Option<Colors>,
+    /// Only computed for --hash.
+    pub hashes: Option<Hashes>,
+    /// The source was an animated image, and only its first frame was used.
+    pub animated: bool,
+}
+
+/// Extracts and writes every requested size, returning the files written.
+/// No outputs are returned for --stdout and --data-uri, which print the frames instead.
+/// Runs under a watchdog unless `options.timeout` is None.
+pub fn extract(options: &Options) -> Result<Extraction> {
+    let Some(timeout) = options.timeout else {
+        return extract_now(options);
+    };
+    let options = options.clone();
+    watchdog::run(timeout, move || extract_now(&options))
+}
+
+fn extract_now(options: &Options) -> Result<Extraction> {
+    // A job cancelled while it waited for a worker doesn't start
+    check_cancelled()?;
+    check_paths(options)?;
+    // Sources without file metadata (shell namespaces, missing files) just skip the cache.
+    // So do variants, which only exist for some icons and aren't worth tracking there,
+    // and blurred backdrops along with them.
+    let cache = options
+        .cache_dir
+        .as_deref()
+        .filter(|_| options.variants.is_none() && !options.blur_backdrop)
+        .and_then(|dir| CacheEntry::for_options(dir, options).ok());
+    // A packed .ico is a single file, stored under its largest size
+    let largest = *options.sizes.last().unwrap();
+    let frame_sizes = if options.ico_pack { vec![largest] } else { options.sizes.clone() };
+    if let Some(cache) = &cache {
+        if let Some((kind, source, frames)) = cache.load(&frame_sizes, options.format) {
+            // Entries written without --colors or --hash don't have them yet
+            let colors = if options.colors { cache.load_colors() } else { Some(None) };
+            let hashes = if options.hash { cache.load_hashes().map(Some) } else { Some(None) };
+            if let (Some(colors), Some(hashes)) = (colors, hashes) {
+                let sections = share(options, &frames)?;
+                let outputs = deliver(options, &frames, &[], OutputStatus::Cached)?;
+                let animated = cache.is_animated();
+                return Ok(Extraction { kind, source, outputs, sections, colors, hashes, animated });
+            }
+        }
+    }
+
+    let (img, kind, source, animated) = match load_source(options) {
+        Ok(loaded) => loaded,
+        // A video frame, package logo, stock icon or resource index was asked for by
+        // name, and anything else would only hide that it isn't there
+        Err(e)
+            if !options.fallback
+                || options.video_frame.is_some()
+                || options.package
+                || options.stock
+                || options.resource_index.is_some() =>
+        {
+            return Err(e)
+        }
+        Err(e) => {
+            check_cancelled()?;
+            tracing::debug!(source = %options.file_path, "Falling back from the file's own icon: {:#}", e);
+            let type_path = lnk_target(options).unwrap_or_else(|| options.file_path.clone());
+            let (img, source) = fallback_icon(&type_path, native_size(options.pixels(largest)));
+            (img, ImageKind::Icon, source, false)
+        }
+    };
+    // Loading is the slow part; the rest is resizing and writing
+    check_cancelled()?;
+    let img = match options.trim {
+        Some(padding) => trim(&img, kind, padding),
+        None => img,
+    };
+    // Measured before compositing, so a --fill doesn't drown out the icon itself
+    let colors = if options.colors { icon_colors(&img) } else { None };
+    let hashes = options.hash.then(|| icon_hashes(&img));
+    let badge = match &options.badge {
+        Some(badge) => Some((badge, badge.load(options.pixels(largest))?)),
+        None => None,
+    };
+    let mut frames = Vec::with_capacity(options.sizes.len());
+    let mut variants = Vec::new();
+    let mut renditions = Vec::new();
+    for &size in &options.sizes {
+        check_cancelled()?;
+        // Outputs are named after the requested size but rendered at --scale
+        let pixels = options.pixels(size);
+        if matches!(options.format, OutputFormat::Svg) {
+            let badge = badge.as_ref().map(|(badge, badge_img)| (*badge, badge_img));
+            frames.push((size, svg::render_tile(&img, kind, pixels, &options.composite, badge, &options.resample)?));
+            continue;
+        }
+        let resized = if options.composite.is_active() {
+            options.composite.apply(&img, pixels, &options.resample)
+        } else {
+            fit(&img, kind, pixels, &options.resample)
+        };
+        if let Some(style) = options.variants {
+            for theme in Theme::ALL {
+                if let Some(variant) = variant_for(&resized, theme, style, &options.resample) {
+                    let variant = match &badge {
+                        Some((badge, badge_img)) => badge.apply(variant, badge_img, &options.resample),
+                        None => variant,
+                    };
+                    variants.push((variant_path(&options.output_for(size), theme.name()), theme.name(), encode(&variant, options)?));
+                }
+            }
+        }
+        if options.blur_backdrop {
+            // From the untouched icon: a fill, plate or badge would only blur into mush
+            let backdrop = blurred_backdrop(&img, pixels, &options.resample);
+            variants.push((variant_path(&options.output_for(size), "blur"), "blur", encode(&backdrop, options)?));
+        }
+        let resized = match &badge {
+            Some((badge, badge_img)) => badge.apply(resized, badge_img, &options.resample),
+            None => resized,
+        };
+        if options.ico_pack {
+            renditions.push(resized.to_rgba8());
+        } else {
+            frames.push((size, encode(&resized, options)?));
+        }
+    }
+    if options.ico_pack {
+        frames.push((largest, ico::encode_icon(&renditions)?));
+    }
+
+    let sections = share(options, &frames)?;
+    let outputs = deliver(options, &frames, &variants, OutputStatus::Extracted)?;
+    if let Some(cache) = &cache {
+        // A cache that can't be written only costs the next run some time
+        if options.colors {
+            let _ = cache.store_colors(colors.as_ref());
+        }
+        if let Some(hashes) = &hashes {
+            let _ = cache.store_hashes(hashes);
+        }
+        if animated {
+            let _ = cache.mark_animated();
+        }
+        // A fallback may only stand in for a failure that goes away
+        if !source.is_fallback() {
+            let _ = cache.store(kind, source, &frames, options.format);
+        }
+    }
+    Ok(Extraction { kind, source, outputs, sections, colors, hashes, animated })
+}
+
+/// Decodes the source at the largest size asked for, before any resizing or
+/// compositing, with its kind and where it came from, and whether it was animated.
+pub fn load_source(options: &Options) -> Result<(DynamicImage, ImageKind, ImageSource, bool)> {
+    // An empty folder has only its own icon to show
+    if let Some(montage) = options.montage.filter(|_| Path::new(&options.file_path).is_dir()) {
+        if let Some(img) = folder_montage(options, montage)? {
+            return Ok((img, ImageKind::Icon, ImageSource::File, false));
+        }
+    }
+    // Image files are their own icon, rather than the shell's icon for their type
+    Ok(if let Some(seconds) = options.video_frame {
+        (load_video_frame(&options.file_path, seconds)?, ImageKind::Thumbnail, ImageSource::File, false)
+    } else if is_image_file(&options.file_path) && !options.package && !options.stock && options.resource_index.is_none() {
+        let decoded = decode_image_file(&options.file_path)?;
+        // Saves the file manager decoding it again
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if options.thumbnail {
+            xdg_thumbnail::store_image_thumbnail(&options.file_path, &decoded.image, options.pixels(*options.sizes.last().unwrap()));
+        }
+        (decoded.image, ImageKind::Thumbnail, ImageSource::File, decoded.animated)
+    } else {
+        let (img, kind, source) = load_image(options)?;
+        (img, kind, source, false)
+    })
+}
+
+/// Scales the image to `size`. Thumbnails keep the file's aspect ratio instead of
+/// being squashed into a square.
+pub fn fit(img: &DynamicImage, kind: ImageKind, size: u32, resample: &Resample) -> DynamicImage {
+    match kind {
+        ImageKind::Thumbnail => resample.resize(img, size, size),
+        ImageKind::Icon => resample.resize_exact(img, size, size),
+    }
+}
+
+/// Rounds up to a size icons are commonly drawn at, so a 72px request at 150% is
+/// downscaled from real 96px art instead of the shell stretching the 48px frame.
+pub fn native_size(pixels: u32) -> u32 {
+    NATIVE_SIZES.iter().copied().find(|&native| native >= pixels).unwrap_or(pixels)
+}
+
+#[cfg(windows)]
+fn load_video_frame(file_path: &str, seconds: f64) -> Result<DynamicImage> {
+    Ok(DynamicImage::ImageRgba8(video::extract_video_frame(file_path, seconds)?))
+}
+
+#[cfg(not(windows))]
+fn load_video_frame(_file_path: &str, _seconds: f64) -> Result<DynamicImage> {
+    Err(failure(ErrorCode::Unsupported, "--video-frame is only supported on Windows"))
+}
+
+// Extract once at the largest size and scale down for the rest, rather than
+// asking the shell for every size
+fn load_image(options: &Options) -> Result<(DynamicImage, ImageKind, ImageSource)> {
+    let largest = native_size(options.pixels(*options.sizes.last().unwrap()));
+    if options.package {
+        #[cfg(windows)]
+        return Ok((
+            DynamicImage::ImageRgba8(appx::extract_package_logo(&options.file_path, largest)?),
+            ImageKind::Icon,
+            ImageSource::File,
+        ));
+        #[cfg(not(windows))]
+        return Err(failure(ErrorCode::Unsupported, "--package is only supported on Windows"));
+    }
+    if options.stock {
+        #[cfg(windows)]
+        return Ok((
+            DynamicImage::ImageRgba8(stock::extract_stock_icon(stock::parse_stock_id(&options.file_path)?, largest)?),
+            ImageKind::Icon,
+            ImageSource::Stock,
+        ));
+        #[cfg(not(windows))]
+        return Err(failure(ErrorCode::Unsupported, "--stock is only supported on Windows"));
+    }
+    // A shortcut's art lives in its icon location or target; the .lnk itself is the
+    // last resort, since the shell may only have a generic glyph with an arrow for it
+    for (file_path, resource_index) in lnk_sources(options) {
+        let source = if resource_index.is_some() { ImageSource::LinkIcon } else { ImageSource::LinkTarget };
+        match load_from(options, &file_path, resource_index, largest) {
+            Ok((img, kind)) => return Ok((img, kind, source)),
+            Err(e) => tracing::debug!(source = %file_path, resource_index, "Shortcut icon source failed: {:#}", e),
+        }
+    }
+    let (img, kind) = load_from(options, &options.file_path, options.resource_index, largest)?;
+    Ok((img, kind, ImageSource::File))
+}
+
+fn load_from(options: &Options, file_path: &str, resource_index: Option<i32>, largest: u32) -> Result<(DynamicImage, ImageKind)> {
+    Ok(match resource_index {
+        #[cfg(windows)]
+        Some(index) => (
+            DynamicImage::ImageRgba8(resource::extract_resource_icon(file_path, index, largest)?),
+            ImageKind::Icon,
+        ),
+        #[cfg(not(windows))]
+        Some(_) => return Err(failure(ErrorCode::Unsupported, "--resource-index is only supported on Windows")),
+        #[cfg(windows)]
+        None if options.thumbnail => {
+            if options.thumbnail_cache {
+                if let Some(img) = thumbnail::cached_thumbnail(file_path, largest) {
+                    return Ok((DynamicImage::ImageRgba8(img), ImageKind::Thumbnail));
+                }
+            }
+            // Without Acrobat or a similar reader installed, the shell has no PDF
+            // thumbnailer and every PDF gets the same type icon
+            if document::is_pdf(file_path) {
+                match document::render_pdf_page(file_path, largest) {
+                    Ok(img) => return Ok((DynamicImage::ImageRgba8(img), ImageKind::Thumbnail)),
+                    Err(e) => tracing::debug!("PDF render failed, trying the shell: {:#}", e),
+                }
+            }
+            // Office documents and everything else go through the shell's thumbnail
+            // handlers, which fall back to the type icon themselves
+            let (img, kind) = thumbnail::extract_thumbnail(file_path, largest)?;
+            (DynamicImage::ImageRgba8(img), kind)
+        }
+        // Like the shell, files nothing can preview get their type icon
+        #[cfg(all(unix, not(target_os = "macos")))]
+        None if options.thumbnail => match xdg_thumbnail::shared_thumbnail(file_path, largest) {
+            Some(img) => (DynamicImage::ImageRgba8(img), ImageKind::Thumbnail),
+            None => (extract_icon(file_path, largest)?, ImageKind::Icon),
+        },
+        #[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+        None if options.thumbnail => {
+            return Err(failure(ErrorCode::Unsupported, "--thumbnail is only supported on Windows and Linux"))
+        }
+        None => (extract_icon(file_path, largest)?, ImageKind::Icon),
+    })
+}
+
+// The places to try before the .lnk itself, unless --no-follow-lnk asked for the
+// shortcut's own shell icon
+#[cfg(windows)]
+fn lnk_sources(options: &Options) -> Vec<(String, Option<i32>)> {
+    if !options.follow_lnk || options.resource_index.is_some() || !lnk::is_lnk(&options.file_path) {
+        return Vec::new();
+    }
+    lnk::icon_sources(&options.file_path)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|source| match source {
+            lnk::IconSource::Resource(path, index) => (path, Some(index)),
+            lnk::IconSource::Target(target) => (target, None),
+        })
+        .collect()
+}
+
+// .lnk files are a Windows format
+#[cfg(not(windows))]
+fn lnk_sources(_options: &Options) -> Vec<(String, Option<i32>)> {
+    Vec::new()
+}
+
+// What a shortcut points at, whose type says more than the .lnk's own
+fn lnk_target(options: &Options) -> Option<String> {
+    lnk_sources(options).into_iter().find_map(|(path, resource_index)| resource_index.is_none().then_some(path))
+}
+
+#[cfg(windows)]
+fn share(options: &Options, frames: &[Frame]) -> Result<Vec<Section>> {
+    if !options.shared_memory {
+        return Ok(Vec::new());
+    }
+    let mut sections = Vec::with_capacity(frames.len());
+    for (size, encoded) in frames {
+        match check_cancelled().and_then(|_| shared_memory::share(encoded)) {
+            Ok(name) => sections.push(Section { name, size: *size, length: encoded.len() }),
+            Err(error) => {
+                // Nobody would ever release the sections of a job that failed
+                for section in &sections {
+                    shared_memory::release(&section.name);
+                }
+                return Err(error);
+            }
+        }
+    }
+    Ok(sections)
+}
+
+#[cfg(not(windows))]
+fn share(options: &Options, _frames: &[Frame]) -> Result<Vec<Section>> {
+    if options.shared_memory {
+        return Err(failure(ErrorCode::Unsupported, "sharedMemory is only supported on Windows"));
+    }
+    Ok(Vec::new())
+}
+
+// The outputs are checked once they're known, when they're delivered; a web page or
+// Store app isn't a path
+fn check_paths(options: &Options) -> Result<()> {
+    if !options.package && !options.stock && !favicon::is_web_source(&options.file_path) {
+        sandbox::check_input(&options.file_path)?;
+    }
+    if let Some(badge) = &options.badge {
+        sandbox::check_input(&badge.path)?;
+    }
+    if let Some(cache_dir) = &options.cache_dir {
+        sandbox::check_output(Path::new(cache_dir))?;
+    }
+    Ok(())
+}
+
+fn deliver(options: &Options, frames: &[Frame], companions: &[Companion], status: OutputStatus) -> Result<Vec<Output>> {
+    // An abandoned extraction mustn't print into whatever the tool writes after it
+    check_cancelled()?;
+    if options.shared_memory {
+        return Ok(Vec::new());
+    }
+    if options.stdout {
+        let mut stdout = io::stdout().lock();
+        for (size, encoded) in frames {
+            write_frame(&mut stdout, *size, encoded)?;
+        }
+        stdout.flush()?;
+        return Ok(Vec::new());
+    }
+    if options.data_uri {
+        let mut stdout = io::stdout().lock();
+        for (_, encoded) in frames {
+            writeln!(stdout, "data:{};base64,{}", options.format.mime_type(), STANDARD.encode(encoded))?;
+        }
+        stdout.flush()?;
+        return Ok(Vec::new());
+    }
+
+    let files = frames
+        .iter()
+        .map(|(size, encoded)| (options.output_for(*size), None, encoded.as_slice()))
+        .chain(companions.iter().map(|(path, variant, encoded)| (path.clone(), Some(*variant), encoded.as_slice())))
+        .collect::<Vec<_>>();
+    // All of them before any is written, so a rejected one leaves nothing behind
+    for (path, _, _) in &files {
+        sandbox::check_output(Path::new(path))?;
+    }
+    let mut outputs = Vec::new();
+    let written = files.into_iter().try_for_each(|(path, variant, encoded)| {
+        check_cancelled()?;
+        outputs.push(Output { variant, ..write_output(path, encoded, status)? });
+        Ok(())
+    });
+    // Checked once more after the last write, since a watchdog that gave up on the
+    // job meanwhile has already reported it as failed
+    if let Err(error) = written.and_then(|_| check_cancelled()) {
+        // A cancelled job takes back the sizes it had written, rather than leave the
+        // caller some of them
+        if altdesktop_core::cancel::cancelled() {
+            for output in outputs.iter().filter(|output| !matches!(output.status, OutputStatus::Unchanged)) {
+                let _ = fs::remove_file(&output.path);
+            }
+        }
+        return Err(error);
+    }
+    Ok(outputs)
+}
+
+fn write_output(path: String, encoded: &[u8], status: OutputStatus) -> Result<Output> {
+    // Skipping identical writes keeps file watchers and mtimes quiet on refresh
+    if fs::read(&path).is_ok_and(|existing| existing == encoded) {
+        return Ok(Output { path, status: OutputStatus::Unchanged, variant: None });
+    }
+    fs::write(&path, encoded)?;
+    Ok(Output { path, status, variant: None })
+}
+
+fn encode(img: &DynamicImage, options: &Options) -> Result<Vec<u8>> {
+    let mut encoded = Cursor::new(Vec::new());
+    encode_image(img, &mut encoded, options.format, options.background)?;
+    Ok(encoded.into_inner())
+}
+
+// The requested size and the byte length as little-endian u32s, then the encoded image
+fn write_frame(out: &mut impl Write, size: u32, encoded: &[u8]) -> Result<()> {
+    out.write_all(&size.to_le_bytes())?;
+    out.write_all(&(encoded.len() as u32).to_le_bytes())?;
+    out.write_all(encoded)?;
+    Ok(())
+}
+
+fn extract_icon(file_path: &str, size: u32) -> Result<DynamicImage> {
+    // A .url file's own icon is the generic browser one; the site's favicon is what
+    // users expect. Offline, a .url still falls back to that generic icon below.
+    if favicon::is_web_source(file_path) {
+        match favicon::fetch_favicon(file_path, size) {
+            Ok(img) => return Ok(DynamicImage::ImageRgba8(img)),
+            Err(e) if !Path::new(file_path).is_file() => return Err(e),
+            Err(e) => tracing::debug!("Favicon fetch failed, using the file's icon: {:#}", e),
+        }
+    }
+
+    // Explorer shows a folder's desktop.ini icon, but the generic icon lookup doesn't.
+    // A macOS app bundle is a folder too, one whose Info.plist names its icon.
+    if Path::new(file_path).is_dir() {
+        if let Some(img) = desktop_ini::extract_folder_icon(Path::new(file_path), size) {
+            return Ok(DynamicImage::ImageRgba8(img));
+        }
+        if let Some(icns_path) = bundle::bundle_icon(Path::new(file_path)) {
+            if let Ok(Some(img)) = icns::extract_icns_image(&icns_path.to_string_lossy(), size) {
+                return Ok(DynamicImage::ImageRgba8(img));
+            }
+        }
+    }
+
+    // The shell picks an .ico frame by its own rules and rescales it; reading the
+    // file directly keeps a crisp frame when one matches
+    if ico::is_ico(file_path) {
+        if let Ok(Some(img)) = ico::extract_ico_frame(file_path, size) {
+            return Ok(DynamicImage::ImageRgba8(img));
+        }
+    }
+    if icns::is_icns(file_path) {
+        if let Ok(Some(img)) = icns::extract_icns_image(file_path, size) {
+            return Ok(DynamicImage::ImageRgba8(img));
+        }
+    }
+
+    // file_icon_provider lets the shell scale small art up, which blurs large tiles
+    #[cfg(windows)]
+    if size > 48 {
+        match jumbo::extract_jumbo(file_path) {
+            Ok(Some(img)) => return Ok(DynamicImage::ImageRgba8(img)),
+            Ok(None) => {}
+            Err(e) => tracing::debug!("Jumbo icon lookup failed: {:#}", e),
+        }
+    }
+
+    // The provider asks GTK, which needs a display and only knows its own theme
+    // lookups; reading the theme directly also renders its SVGs at the tile's size
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if let Some(img) = xdg::extract_xdg_icon(file_path, size) {
+        return Ok(DynamicImage::ImageRgba8(img));
+    }
+
+    // Retrieve icon
+    let icon = get_file_icon(Path::new(file_path), size as u16)
+        .map_err(|e| anyhow::anyhow!("Failed to get icon: {:?}", e))?;
+
+    // Convert raw RGBA bytes into an image
+    Ok(DynamicImage::ImageRgba8(
+        image::RgbaImage::from_raw(icon.width, icon.height, icon.pixels)
+            .ok_or_else(|| anyhow::anyhow!("Invalid icon buffer size"))?
+    ))
+}
@@ -0,0 +1,91 @@
+#[cfg(windows)]
+use std::path::Path;
+use image::{DynamicImage, Rgba, RgbaImage};
+#[cfg(windows)]
+use windows::Win32::UI::Shell::{SHSTOCKICONID, SIID_APPLICATION, SIID_DOCNOASSOC, SIID_FOLDER};
+
+use crate::extract::ImageSource;
+#[cfg(windows)]
+use crate::stock;
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::xdg;
+
+#[cfg(windows)]
+const EXECUTABLE_EXTENSIONS: &[&str] = &["exe", "com", "bat", "cmd", "msi", "scr"];
+
+/// The end of the chain, for a source whose own icon couldn't be had: the icon
+/// registered for its type, then the shell's stock icon for a folder, program or
+/// document, then a plain page drawn here, which can't fail. `type_path` is what the
+/// type is read from: the source itself, or a shortcut's target.
+pub fn fallback_icon(type_path: &str, size: u32) -> (DynamicImage, ImageSource) {
+    #[cfg(windows)]
+    {
+        let directory = type_path.ends_with(['\\', '/']) || Path::new(type_path).is_dir();
+        match stock::extract_type_icon(type_path, directory, size) {
+            Ok(img) => return (DynamicImage::ImageRgba8(img), ImageSource::Association),
+            Err(e) => tracing::debug!(source = %type_path, "Type icon lookup failed: {:#}", e),
+        }
+        match stock::extract_stock_icon(stock_id(type_path, directory), size) {
+            Ok(img) => return (DynamicImage::ImageRgba8(img), ImageSource::Stock),
+            Err(e) => tracing::debug!(source = %type_path, "Stock icon lookup failed: {:#}", e),
+        }
+    }
+    // By the name's MIME type, which holds for a file that isn't there
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if let Some(img) = xdg::extract_xdg_icon(type_path, size) {
+        return (DynamicImage::ImageRgba8(img), ImageSource::Association);
+    }
+    #[cfg(target_os = "macos")]
+    let _ = type_path;
+    (DynamicImage::ImageRgba8(generic_icon(size)), ImageSource::Generic)
+}
+
+#[cfg(windows)]
+fn stock_id(type_path: &str, directory: bool) -> SHSTOCKICONID {
+    let extension = Path::new(type_path).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    if directory {
+        SIID_FOLDER
+    } else if EXECUTABLE_EXTENSIONS.iter().any(|executable| extension.eq_ignore_ascii_case(executable)) {
+        SIID_APPLICATION
+    } else {
+        SIID_DOCNOASSOC
+    }
+}
+
+// A white page with a folded top-right corner and a grey outline, in the proportions
+// of the shell's own document icon
+fn generic_icon(size: u32) -> RgbaImage {
+    let size = size.max(16);
+    let (left, right) = (size * 3 / 16, size * 13 / 16);
+    let (top, bottom) = (size / 16, size * 15 / 16);
+    let fold = size / 4;
+    let border = (size / 32).max(1);
+    let outline = Rgba([112, 112, 112, 255]);
+    let page = Rgba([250, 250, 250, 255]);
+    let flap = Rgba([214, 214, 214, 255]);
+    RgbaImage::from_fn(size, size, |x, y| {
+        if x < left || x >= right || y < top || y >= bottom {
+            return Rgba([0, 0, 0, 0]);
+        }
+        // How far into the corner square the fold cuts off, which is then the flap
+        let (from_right, from_top) = (right - 1 - x, y - top);
+        let cut = from_right + from_top;
+        if cut < fold {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let in_flap = from_right < fold && from_top < fold;
+        let on_edge = x < left + border
+            || x + border >= right
+            || y < top + border
+            || y + border >= bottom
+            || cut < fold + border
+            || (in_flap && (from_right + border >= fold || from_top + border >= fold));
+        if on_edge {
+            outline
+        } else if in_flap {
+            flap
+        } else {
+            page
+        }
+    })
+}
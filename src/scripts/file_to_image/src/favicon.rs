@@ -0,0 +1,179 @@
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Context, Result};
+use image::{imageops::FilterType, RgbaImage};
+use url::Url;
+
+use crate::error::{failure, ErrorCode};
+use crate::ico;
+
+// Pages are only scanned for <link> tags, which live in <head>
+const MAX_PAGE_BYTES: u64 = 1024 * 1024;
+const MAX_ICON_BYTES: u64 = 4 * 1024 * 1024;
+const DOWNLOAD_TIMEOUT_MS: u64 = 10_000;
+
+pub fn is_web_source(input: &str) -> bool {
+    let lower = input.to_ascii_lowercase();
+    lower.starts_with("http://")
+        || lower.starts_with("https://")
+        || Path::new(input).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("url"))
+}
+
+struct IconLink {
+    url: Url,
+    // The largest size listed in sizes="", 0 if unknown
+    size: u32,
+}
+
+/// Downloads the favicon of a web page, or of the page a .url shortcut points to.
+/// Uses the best-sized icon from the page's <link> tags, falling back to /favicon.ico.
+pub fn fetch_favicon(input: &str, size: u32) -> Result<RgbaImage> {
+    let page = if input.to_ascii_lowercase().starts_with("http") {
+        input.to_string()
+    } else {
+        read_internet_shortcut(input)?
+    };
+    let page = Url::parse(&page).with_context(|| format!("Invalid URL: {}", page))?;
+    if !matches!(page.scheme(), "http" | "https") {
+        return Err(failure(ErrorCode::Unsupported, format!("Favicons can only be fetched for http(s) URLs: {}", page)));
+    }
+
+    let agent = web_agent()?;
+
+    // A page that fails to load can still have a /favicon.ico
+    let mut links = download(&agent, page.as_str(), MAX_PAGE_BYTES)
+        .map(|html| icon_links(&String::from_utf8_lossy(&html), &page))
+        .unwrap_or_default();
+    links.sort_by_key(|link| match link.size {
+        0 => (1, 0),
+        n if n >= size => (0, n),
+        n => (2, u32::MAX - n),
+    });
+    links.extend(page.join("/favicon.ico").ok().map(|url| IconLink { url, size: 0 }));
+
+    for link in links {
+        if let Ok(bytes) = download(&agent, link.url.as_str(), MAX_ICON_BYTES) {
+            if let Some(img) = decode_icon(&bytes, size) {
+                return Ok(img);
+            }
+        }
+    }
+    Err(failure(ErrorCode::NotFound, format!("No usable favicon found for {}", page)))
+}
+
+/// The HTTP client downloads share, with native TLS and a 10 second timeout unless
+/// the config sets another.
+pub fn web_agent() -> Result<ureq::Agent> {
+    let timeout = altdesktop_core::config::get().timeouts.download.unwrap_or(DOWNLOAD_TIMEOUT_MS);
+    Ok(ureq::AgentBuilder::new()
+        .tls_connector(Arc::new(native_tls::TlsConnector::new()?))
+        .timeout(Duration::from_millis(timeout))
+        .user_agent(concat!("Alt-Desktop/", env!("CARGO_PKG_VERSION")))
+        .build())
+}
+
+/// Reads at most `limit` bytes of the response to `url`.
+pub fn download(agent: &ureq::Agent, url: &str, limit: u64) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    agent.get(url).call()?.into_reader().take(limit).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn decode_icon(bytes: &[u8], size: u32) -> Option<RgbaImage> {
+    // .ico files usually carry several sizes; take the one that fits instead of the first
+    if let Some(img) = ico::best_frame(bytes, size) {
+        return Some(img);
+    }
+    let img = image::load_from_memory(bytes).ok()?;
+    Some(img.resize(size, size, FilterType::Lanczos3).to_rgba8())
+}
+
+// .url files are INI files; Alt-Desktop writes them as UTF-16 when the URL isn't ASCII
+fn read_internet_shortcut(path: &str) -> Result<String> {
+    let bytes = std::fs::read(path)?;
+    let text = match bytes.strip_prefix(&[0xFF, 0xFE]) {
+        Some(utf16) => String::from_utf16_lossy(
+            &utf16.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect::<Vec<_>>(),
+        ),
+        None => String::from_utf8_lossy(&bytes).into_owned(),
+    };
+    text.lines()
+        .find_map(|line| line.trim().strip_prefix("URL="))
+        .map(str::to_string)
+        .ok_or_else(|| failure(ErrorCode::Decode, format!("No URL in {}", path)))
+}
+
+// A tolerant scan over <link ...> tags, since real-world HTML is rarely well-formed
+fn icon_links(html: &str, page: &Url) -> Vec<IconLink> {
+    let lower = html.to_ascii_lowercase();
+    let mut links = Vec::new();
+    let mut rest = 0;
+    while let Some(start) = lower[rest..].find("<link") {
+        let start = rest + start;
+        let end = lower[start..].find('>').map_or(lower.len(), |end| start + end);
+        let tag = &html[start..end];
+        rest = end;
+
+        let rel = attribute(tag, "rel").unwrap_or_default().to_ascii_lowercase();
+        if !rel.split_whitespace().any(|rel| matches!(rel, "icon" | "apple-touch-icon" | "apple-touch-icon-precomposed")) {
+            continue;
+        }
+        // SVG icons need a rasterizer
+        if attribute(tag, "type").is_some_and(|kind| kind.contains("svg")) {
+            continue;
+        }
+        let Some(url) = attribute(tag, "href").and_then(|href| page.join(&href).ok()) else {
+            continue;
+        };
+        if url.path().to_ascii_lowercase().ends_with(".svg") {
+            continue;
+        }
+        // sizes="16x16 32x32" or "any"
+        let size = attribute(tag, "sizes")
+            .unwrap_or_default()
+            .split_whitespace()
+            .filter_map(|pair| pair.to_ascii_lowercase().split_once('x').and_then(|(w, _)| w.parse().ok()))
+            .max()
+            .unwrap_or(0);
+        links.push(IconLink { url, size });
+    }
+    links
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search = 0;
+    while let Some(found) = lower[search..].find(name) {
+        let start = search + found;
+        search = start + name.len();
+        // Must be a whole attribute name, e.g. not the "rel" in "preload"
+        let preceded = lower[..start].chars().last().is_some_and(|c| c.is_whitespace());
+        let after = lower[search..].trim_start();
+        if !preceded || !after.starts_with('=') {
+            continue;
+        }
+        let offset = tag.len() - after.len() + 1;
+        let value = tag[offset..].trim_start();
+        return Some(match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default().to_string(),
+            // Unquoted values run to the next space, slashes included, as in href=/favicon.png
+            _ => value.split(|c: char| c.is_whitespace() || c == '>').next().unwrap_or_default().to_string(),
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unquoted_root_relative_href() {
+        let page = Url::parse("https://example.com/app/").unwrap();
+        let links = icon_links("<head><link rel=icon href=/favicon.png></head>", &page);
+        let urls: Vec<&str> = links.iter().map(|link| link.url.as_str()).collect();
+        assert_eq!(urls, ["https://example.com/favicon.png"]);
+    }
+}
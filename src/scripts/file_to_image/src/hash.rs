@@ -0,0 +1,82 @@
+use std::f32::consts::PI;
+use image::{imageops::FilterType, DynamicImage, GrayImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+// pHash keeps the lowest 8x8 frequencies of a 32x32 DCT
+const PHASH_SAMPLE: u32 = 32;
+const HASH_SIDE: usize = 8;
+
+/// 64-bit perceptual hashes of an icon, as 16 hex digits. Visually identical icons
+/// hash the same at any resolution; compare by Hamming distance for near matches.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hashes {
+    /// Gradient hash: whether each pixel of a 9x8 sample is brighter than its neighbour.
+    pub dhash: String,
+    /// DCT hash: whether each low frequency is above the median. Tolerates small
+    /// shifts and recompression better than `dhash`.
+    pub phash: String,
+}
+
+pub fn icon_hashes(img: &DynamicImage) -> Hashes {
+    Hashes {
+        dhash: format!("{:016x}", dhash(img)),
+        phash: format!("{:016x}", phash(img)),
+    }
+}
+
+fn dhash(img: &DynamicImage) -> u64 {
+    let sample = grayscale(img, HASH_SIDE as u32 + 1, HASH_SIDE as u32);
+    let mut hash = 0;
+    for y in 0..HASH_SIDE as u32 {
+        for x in 0..HASH_SIDE as u32 {
+            let brighter = sample.get_pixel(x, y)[0] > sample.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    hash
+}
+
+fn phash(img: &DynamicImage) -> u64 {
+    let sample = grayscale(img, PHASH_SAMPLE, PHASH_SAMPLE);
+    let n = PHASH_SAMPLE as usize;
+    let pixels: Vec<f32> = sample.pixels().map(|pixel| pixel[0] as f32).collect();
+    let cosines: Vec<f32> = (0..HASH_SIDE * n)
+        .map(|i| {
+            let (frequency, position) = (i / n, i % n);
+            ((2 * position + 1) as f32 * frequency as f32 * PI / (2 * n) as f32).cos()
+        })
+        .collect();
+
+    // Only the low frequencies are kept, so there's no need for the full transform
+    let mut coefficients = Vec::with_capacity(HASH_SIDE * HASH_SIDE);
+    for v in 0..HASH_SIDE {
+        for u in 0..HASH_SIDE {
+            let mut sum = 0.0;
+            for y in 0..n {
+                for x in 0..n {
+                    sum += pixels[y * n + x] * cosines[u * n + x] * cosines[v * n + y];
+                }
+            }
+            coefficients.push(sum);
+        }
+    }
+
+    // The DC term is the overall brightness, which would swamp the median
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+    coefficients.iter().fold(0, |hash, &coefficient| (hash << 1) | (coefficient > median) as u64)
+}
+
+// Transparent areas are flattened onto mid-grey, so the same glyph on a
+// transparent or a grey background hashes alike
+fn grayscale(img: &DynamicImage, width: u32, height: u32) -> GrayImage {
+    let sample = img.resize_exact(width, height, FilterType::Triangle).to_rgba8();
+    let flattened = RgbaImage::from_fn(width, height, |x, y| {
+        let Rgba([r, g, b, a]) = *sample.get_pixel(x, y);
+        let blend = |channel: u8| ((channel as u32 * a as u32 + 128 * (255 - a as u32)) / 255) as u8;
+        Rgba([blend(r), blend(g), blend(b), 255])
+    });
+    DynamicImage::ImageRgba8(flattened).to_luma8()
+}
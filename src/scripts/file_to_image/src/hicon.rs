@@ -0,0 +1,82 @@
+use std::mem;
+use anyhow::{bail, Result};
+use image::RgbaImage;
+use windows::Win32::{
+    Graphics::Gdi::*,
+    UI::WindowsAndMessaging::{GetIconInfo, HICON, ICONINFO},
+};
+
+use crate::error::{failure, ErrorCode};
+
+/// Copies an HICON's pixels into an RGBA image. The icon itself is not destroyed.
+pub fn icon_to_image(icon: HICON) -> Result<RgbaImage> {
+    let mut info = ICONINFO::default();
+    unsafe { GetIconInfo(icon, &mut info)? };
+
+    let result = read_icon_bitmaps(&info);
+    unsafe {
+        DeleteObject(info.hbmColor);
+        DeleteObject(info.hbmMask);
+    }
+    result
+}
+
+fn read_icon_bitmaps(info: &ICONINFO) -> Result<RgbaImage> {
+    if info.hbmColor.is_invalid() {
+        return Err(failure(ErrorCode::Unsupported, "Monochrome icons are not supported"));
+    }
+    let (width, height, mut pixels) = bitmap_bgra(info.hbmColor)?;
+
+    // Icons without an alpha channel rely on the AND mask for transparency
+    if pixels.chunks_exact(4).all(|pixel| pixel[3] == 0) {
+        let (_, _, mask) = bitmap_bgra(info.hbmMask)?;
+        for (pixel, mask) in pixels.chunks_exact_mut(4).zip(mask.chunks_exact(4)) {
+            pixel[3] = if mask[0] == 0 { 255 } else { 0 };
+        }
+    }
+
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+    RgbaImage::from_raw(width, height, pixels).ok_or_else(|| anyhow::anyhow!("Invalid icon buffer size"))
+}
+
+/// Reads a bitmap as top-down 32-bit BGRA, returning its width, height and pixels.
+pub fn bitmap_bgra(bitmap: HBITMAP) -> Result<(u32, u32, Vec<u8>)> {
+    unsafe {
+        let mut header = BITMAP::default();
+        if GetObjectW(bitmap, mem::size_of::<BITMAP>() as i32, Some(&mut header as *mut _ as *mut _)) == 0 {
+            bail!("Failed to read icon bitmap");
+        }
+        let (width, height) = (header.bmWidth, header.bmHeight);
+
+        let mut bitmap_info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width,
+                biHeight: -height,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        let dc = GetDC(None);
+        let lines = GetDIBits(
+            dc,
+            bitmap,
+            0,
+            height as u32,
+            Some(pixels.as_mut_ptr() as *mut _),
+            &mut bitmap_info,
+            DIB_RGB_COLORS,
+        );
+        ReleaseDC(None, dc);
+        if lines == 0 {
+            bail!("Failed to read icon bitmap");
+        }
+        Ok((width as u32, height as u32, pixels))
+    }
+}
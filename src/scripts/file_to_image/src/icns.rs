@@ -0,0 +1,147 @@
+use std::io::Cursor;
+use std::path::Path;
+use anyhow::Result;
+use image::{ImageFormat, RgbaImage};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// How an element of an .icns stores its image.
+enum Encoding {
+    /// PNG (JPEG 2000 in files from before 10.7, which nothing here decodes).
+    Png,
+    /// 'ARGB' followed by the four channels, each run-length encoded.
+    Argb,
+    /// The three color channels, run-length encoded, with the alpha in a separate
+    /// 8-bit mask element of the given type.
+    Rgb(&'static [u8; 4]),
+}
+
+// The element types holding whole images, with their width in pixels. The @2x
+// types are listed at their pixel size, which is all that matters for picking one.
+const ELEMENTS: &[(&[u8; 4], u32, Encoding)] = &[
+    (b"icp4", 16, Encoding::Png),
+    (b"icp5", 32, Encoding::Png),
+    (b"icp6", 64, Encoding::Png),
+    (b"ic07", 128, Encoding::Png),
+    (b"ic08", 256, Encoding::Png),
+    (b"ic09", 512, Encoding::Png),
+    (b"ic10", 1024, Encoding::Png),
+    (b"ic11", 32, Encoding::Png),
+    (b"ic12", 64, Encoding::Png),
+    (b"ic13", 256, Encoding::Png),
+    (b"ic14", 512, Encoding::Png),
+    (b"ic04", 16, Encoding::Argb),
+    (b"ic05", 32, Encoding::Argb),
+    (b"is32", 16, Encoding::Rgb(b"s8mk")),
+    (b"il32", 32, Encoding::Rgb(b"l8mk")),
+    (b"ih32", 48, Encoding::Rgb(b"h8mk")),
+    (b"it32", 128, Encoding::Rgb(b"t8mk")),
+];
+
+pub fn is_icns(file_path: &str) -> bool {
+    Path::new(file_path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("icns"))
+}
+
+/// Decodes the image of a macOS .icns that best matches `size`, picked the way
+/// `ico::extract_ico_frame` picks a frame. Returns `None` if the file isn't a
+/// readable icon family.
+pub fn extract_icns_image(file_path: &str, size: u32) -> Result<Option<RgbaImage>> {
+    Ok(best_image(&std::fs::read(file_path)?, size))
+}
+
+fn best_image(bytes: &[u8], size: u32) -> Option<RgbaImage> {
+    let elements = read_elements(bytes);
+    let mut candidates: Vec<(u32, &Encoding, &[u8])> = elements
+        .iter()
+        .filter_map(|(kind, data)| {
+            let (_, width, encoding) = ELEMENTS.iter().find(|(known, _, _)| *known == kind)?;
+            Some((*width, encoding, *data))
+        })
+        .collect();
+    // Smallest at least as big as asked, then the rest largest first; PNG before
+    // the older formats at the same width
+    candidates.sort_by_key(|(width, encoding, _)| {
+        let too_small = *width < size;
+        let order = if too_small { u32::MAX - width } else { *width };
+        (too_small, order, !matches!(encoding, Encoding::Png))
+    });
+    candidates.into_iter().find_map(|(width, encoding, data)| match encoding {
+        Encoding::Png if data.starts_with(PNG_SIGNATURE) => {
+            image::load(Cursor::new(data), ImageFormat::Png).ok().map(|img| img.to_rgba8())
+        }
+        Encoding::Png => None,
+        Encoding::Argb => {
+            let channels = unpack(data.strip_prefix(b"ARGB")?, width * width, 4)?;
+            let (alpha, rest) = channels.split_at((width * width) as usize);
+            Some(interleave(width, rest, Some(alpha)))
+        }
+        Encoding::Rgb(mask_kind) => {
+            // it32 has four zero bytes before its channels
+            let data = if width == 128 { data.get(4..)? } else { data };
+            let channels = unpack(data, width * width, 3)?;
+            let mask = elements
+                .iter()
+                .find(|(kind, _)| kind == *mask_kind)
+                .map(|(_, mask)| *mask)
+                .filter(|mask| mask.len() == (width * width) as usize);
+            Some(interleave(width, &channels, mask))
+        }
+    })
+}
+
+// The header is 'icns' and the file's length, then elements of a 4-byte type, their
+// length including that 8-byte header, and their data
+fn read_elements(bytes: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut elements = Vec::new();
+    if !bytes.starts_with(b"icns") {
+        return elements;
+    }
+    let mut offset = 8;
+    while offset + 8 <= bytes.len() {
+        let kind: [u8; 4] = bytes[offset..offset + 4].try_into().unwrap();
+        let length = u32::from_be_bytes(bytes[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        if length < 8 {
+            break;
+        }
+        let Some(data) = bytes.get(offset + 8..offset + length) else {
+            break;
+        };
+        elements.push((kind, data));
+        offset += length;
+    }
+    elements
+}
+
+// Channels are stored one after the other, each in runs: a byte below 0x80 is
+// followed by that many plus one literal bytes, a byte from 0x80 by one byte
+// repeated that many minus 125 times
+fn unpack(data: &[u8], pixels: u32, channels: u32) -> Option<Vec<u8>> {
+    let expected = (pixels * channels) as usize;
+    let mut output = Vec::with_capacity(expected);
+    let mut bytes = data.iter();
+    while output.len() < expected {
+        let header = *bytes.next()?;
+        if header < 0x80 {
+            for _ in 0..=header {
+                output.push(*bytes.next()?);
+            }
+        } else {
+            let value = *bytes.next()?;
+            output.extend(std::iter::repeat_n(value, header as usize - 125));
+        }
+    }
+    output.truncate(expected);
+    Some(output)
+}
+
+// Planar red, green and blue into RGBA; opaque without a mask
+fn interleave(width: u32, planes: &[u8], alpha: Option<&[u8]>) -> RgbaImage {
+    let pixels = (width * width) as usize;
+    let mut output = Vec::with_capacity(pixels * 4);
+    for i in 0..pixels {
+        output.extend([planes[i], planes[pixels + i], planes[2 * pixels + i], alpha.map_or(255, |alpha| alpha[i])]);
+    }
+    RgbaImage::from_raw(width, width, output).unwrap()
+}
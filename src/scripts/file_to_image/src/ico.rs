@@ -0,0 +1,142 @@
+use std::io::Cursor;
+use std::path::Path;
+use anyhow::Result;
+use image::{ImageFormat, RgbaImage};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+struct IconEntry<'a> {
+    width: u32,
+    bit_depth: u16,
+    // Width, height, color count, reserved, planes and bit count, as stored in the ICONDIRENTRY
+    header: &'a [u8],
+    data: &'a [u8],
+}
+
+pub fn is_ico(file_path: &str) -> bool {
+    Path::new(file_path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ico"))
+}
+
+/// Decodes the frame of a .ico file that best matches `size`: the smallest one at
+/// least that big, or the largest one if they are all smaller. 32-bit frames win
+/// over lower bit depths of the same size. Returns `None` if the file isn't a
+/// readable icon, so the caller can fall back to the shell.
+pub fn extract_ico_frame(file_path: &str, size: u32) -> Result<Option<RgbaImage>> {
+    Ok(best_frame(&std::fs::read(file_path)?, size))
+}
+
+/// Same as `extract_ico_frame`, for an icon that is already in memory.
+pub fn best_frame(bytes: &[u8], size: u32) -> Option<RgbaImage> {
+    let entries = read_entries(bytes);
+
+    let best = entries
+        .iter()
+        .filter(|entry| entry.width >= size)
+        .min_by_key(|entry| (entry.width, entry.bit_depth != 32))
+        .or_else(|| entries.iter().max_by_key(|entry| (entry.width, entry.bit_depth == 32)));
+    let best = best?;
+
+    // Re-wrap the frame as a single-image icon so the decoder handles both PNG and
+    // BMP frames without picking a different one itself
+    let mut single = Vec::with_capacity(22 + best.data.len());
+    single.extend_from_slice(&[0, 0, 1, 0, 1, 0]);
+    single.extend_from_slice(best.header);
+    single.extend_from_slice(&(best.data.len() as u32).to_le_bytes());
+    single.extend_from_slice(&22u32.to_le_bytes());
+    single.extend_from_slice(best.data);
+
+    image::load_from_memory_with_format(&single, ImageFormat::Ico)
+        .ok()
+        .map(|img| img.to_rgba8())
+}
+
+// An ICONDIR: a 6-byte header followed by 16-byte ICONDIRENTRYs pointing at each frame
+fn read_entries(bytes: &[u8]) -> Vec<IconEntry<'_>> {
+    let u16_at = |offset: usize| bytes.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let u32_at = |offset: usize| bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    if u16_at(0) != Some(0) || u16_at(2) != Some(1) {
+        return Vec::new();
+    }
+    let count = u16_at(4).unwrap_or(0) as usize;
+
+    (0..count)
+        .filter_map(|i| {
+            let entry = 6 + i * 16;
+            let header = bytes.get(entry..entry + 8)?;
+            let length = u32_at(entry + 8)? as usize;
+            let offset = u32_at(entry + 12)? as usize;
+            let data = bytes.get(offset..offset.checked_add(length)?)?;
+            // Sizes are stored in a byte, where 0 means 256. PNG frames often leave the
+            // bit count at 0, but they are always RGBA in practice.
+            let width = if header[0] == 0 { 256 } else { header[0] as u32 };
+            let bit_depth = match u16::from_le_bytes([header[6], header[7]]) {
+                0 if data.starts_with(PNG_SIGNATURE) => 32,
+                bit_depth => bit_depth,
+            };
+            Some(IconEntry { width, bit_depth, header, data })
+        })
+        .collect()
+}
+
+/// The renditions Explorer asks for at the common DPI scales.
+pub const PACK_SIZES: &[u32] = &[16, 24, 32, 48, 256];
+
+/// Packs several renditions into one .ico. Frames of 256px and up are stored as
+/// PNG like Windows' own icons; smaller ones as 32-bit BMPs, which every icon
+/// reader understands.
+pub fn encode_icon(frames: &[RgbaImage]) -> Result<Vec<u8>> {
+    let data = frames
+        .iter()
+        .map(|frame| {
+            if frame.width() >= 256 || frame.height() >= 256 {
+                let mut png = Cursor::new(Vec::new());
+                frame.write_to(&mut png, ImageFormat::Png)?;
+                Ok(png.into_inner())
+            } else {
+                Ok(bmp_frame(frame))
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut icon = Vec::new();
+    icon.extend_from_slice(&[0, 0, 1, 0]);
+    icon.extend_from_slice(&(frames.len() as u16).to_le_bytes());
+    let mut offset = 6 + 16 * frames.len() as u32;
+    for (frame, data) in frames.iter().zip(&data) {
+        // 0 means 256 in the one-byte size fields
+        icon.push(frame.width().min(256) as u8);
+        icon.push(frame.height().min(256) as u8);
+        icon.extend_from_slice(&[0, 0, 1, 0, 32, 0]);
+        icon.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        icon.extend_from_slice(&offset.to_le_bytes());
+        offset += data.len() as u32;
+    }
+    for data in data {
+        icon.extend_from_slice(&data);
+    }
+    Ok(icon)
+}
+
+// A BITMAPINFOHEADER with the height doubled for the AND mask, bottom-up BGRA
+// rows, then the mask itself. Alpha makes the mask redundant, so it stays empty.
+fn bmp_frame(frame: &RgbaImage) -> Vec<u8> {
+    let (width, height) = frame.dimensions();
+    let mask_row = width.div_ceil(32) * 4;
+    let mut data = Vec::with_capacity(40 + (width * height * 4 + mask_row * height) as usize);
+    data.extend_from_slice(&40u32.to_le_bytes());
+    data.extend_from_slice(&(width as i32).to_le_bytes());
+    data.extend_from_slice(&(height as i32 * 2).to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&32u16.to_le_bytes());
+    data.extend_from_slice(&[0; 24]);
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let [r, g, b, a] = frame.get_pixel(x, y).0;
+            data.extend_from_slice(&[b, g, r, a]);
+        }
+    }
+    data.resize(data.len() + (mask_row * height) as usize, 0);
+    data
+}
@@ -0,0 +1,58 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use anyhow::{Context, Result};
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    metadata::Orientation,
+    AnimationDecoder, DynamicImage, ImageDecoder, ImageFormat, ImageReader,
+};
+
+// .ico files go through the frame picker in ico.rs instead
+const EXTENSIONS: &[&str] = &["png", "apng", "gif", "webp", "jpg", "jpeg", "bmp"];
+
+pub fn is_image_file(file_path: &str) -> bool {
+    Path::new(file_path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| EXTENSIONS.iter().any(|known| known.eq_ignore_ascii_case(extension)))
+}
+
+pub struct Decoded {
+    pub image: DynamicImage,
+    /// The file holds more than one frame; `image` is the first.
+    pub animated: bool,
+}
+
+/// Decodes an image file that is used as its own icon, upright according to its
+/// EXIF orientation. Animated GIF, APNG and WebP files decode to their first frame.
+pub fn decode_image_file(file_path: &str) -> Result<Decoded> {
+    let reader = ImageReader::open(file_path)?.with_guessed_format()?;
+    let animated = match reader.format() {
+        Some(format) => is_animated(file_path, format).unwrap_or(false),
+        None => false,
+    };
+    let decode = || -> image::ImageResult<DynamicImage> {
+        let mut decoder = reader.into_decoder()?;
+        // Phones store photos as shot and record the rotation in EXIF instead. A
+        // missing or unreadable tag leaves the pixels as stored.
+        let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+        // Every decoder here returns the first frame when asked for a single image
+        let mut image = DynamicImage::from_decoder(decoder)?;
+        image.apply_orientation(orientation);
+        Ok(image)
+    };
+    let image = decode().with_context(|| format!("Failed to decode {}", file_path))?;
+    Ok(Decoded { image, animated })
+}
+
+fn is_animated(file_path: &str, format: ImageFormat) -> Result<bool> {
+    let file = BufReader::new(File::open(file_path)?);
+    Ok(match format {
+        // GIFs don't declare a frame count, so look for a second frame
+        ImageFormat::Gif => GifDecoder::new(file)?.into_frames().nth(1).is_some(),
+        ImageFormat::Png => PngDecoder::new(file)?.is_apng()?,
+        ImageFormat::WebP => WebPDecoder::new(file)?.has_animation(),
+        _ => false,
+    })
+}
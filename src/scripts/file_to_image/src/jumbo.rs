@@ -0,0 +1,48 @@
+use std::mem;
+use anyhow::Result;
+use image::RgbaImage;
+use windows::{
+    core::HSTRING,
+    Win32::Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES,
+    Win32::UI::Controls::{IImageList, ILD_TRANSPARENT},
+    Win32::UI::Shell::{SHGetFileInfoW, SHGetImageList, SHFILEINFOW, SHGFI_SYSICONINDEX, SHIL_JUMBO},
+    Win32::UI::WindowsAndMessaging::DestroyIcon,
+};
+
+use crate::hicon::icon_to_image;
+
+// Files that only ship 32/48px art still get a 256px jumbo entry, with the small
+// image drawn in the top-left corner
+const SMALL_ART_LIMIT: u32 = 48;
+
+/// Extracts the 256px icon from the system's jumbo image list. Returns `None`
+/// when the file has no real art at that size, so the caller can fall back.
+pub fn extract_jumbo(file_path: &str) -> Result<Option<RgbaImage>> {
+    unsafe {
+        let mut info = SHFILEINFOW::default();
+        let found = SHGetFileInfoW(
+            &HSTRING::from(file_path),
+            FILE_FLAGS_AND_ATTRIBUTES(0),
+            Some(&mut info),
+            mem::size_of::<SHFILEINFOW>() as u32,
+            SHGFI_SYSICONINDEX,
+        );
+        if found == 0 {
+            return Ok(None);
+        }
+
+        let image_list: IImageList = SHGetImageList(SHIL_JUMBO as i32)?;
+        let icon = image_list.GetIcon(info.iIcon, ILD_TRANSPARENT.0)?;
+        let image = icon_to_image(icon);
+        let _ = DestroyIcon(icon);
+        let image = image?;
+
+        Ok((!is_small_art(&image)).then_some(image))
+    }
+}
+
+fn is_small_art(image: &RgbaImage) -> bool {
+    !image
+        .enumerate_pixels()
+        .any(|(x, y, pixel)| pixel[3] != 0 && (x >= SMALL_ART_LIMIT || y >= SMALL_ART_LIMIT))
+}
@@ -0,0 +1,263 @@
+#[cfg(windows)]
+mod appx;
+mod archive;
+mod badge;
+mod batch;
+mod blur;
+mod bundle;
+mod cache;
+mod colors;
+mod compose;
+mod desktop_ini;
+#[cfg(windows)]
+mod document;
+#[cfg(windows)]
+mod dpi;
+mod encode;
+mod error;
+#[cfg(windows)]
+mod enumerate;
+mod extract;
+mod fallback;
+mod favicon;
+mod hash;
+#[cfg(windows)]
+mod hicon;
+mod icns;
+mod ico;
+mod image_file;
+#[cfg(windows)]
+mod jumbo;
+#[cfg(windows)]
+mod lnk;
+mod montage;
+mod options;
+#[cfg(windows)]
+mod overlay;
+mod resample;
+mod steam_art;
+#[cfg(windows)]
+mod resource;
+#[cfg(windows)]
+mod shared_memory;
+#[cfg(windows)]
+mod stock;
+mod svg;
+#[cfg(windows)]
+mod thumbnail;
+mod trim;
+mod variants;
+#[cfg(windows)]
+mod video;
+mod watch;
+mod watchdog;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod xdg;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod xdg_thumbnail;
+
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use anyhow::Result;
+
+#[cfg(not(windows))]
+use error::failure;
+use error::{ErrorCode, ErrorReport};
+use extract::{extract, Extraction, OutputStatus};
+use options::{parse_args, Command, Options, USAGE};
+
+/// Runs one `altdesktop-helper icon` invocation; `args[0]` is the program name.
+/// Expects COM to be initialized, apartment-threaded, on the calling thread,
+/// which the system image list needs.
+///
+/// Failures are reported on stderr as one JSON line,
+/// {"code":"notFound","message":"...","userMessage":"..."}, so callers can tell a
+/// missing file from an unsupported one without parsing prose, and have something
+/// to show the user in the language `--lang` picked.
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    let args: Vec<String> = match args.into_iter().map(|arg| arg.into_string()).collect() {
+        Ok(args) => args,
+        Err(arg) => return usage_error(format!("Argument is not valid Unicode: {}", arg.to_string_lossy())),
+    };
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(message) => return usage_error(message),
+    };
+
+    let source = match &command {
+        Command::Extract(options) => Some(options.file_path.clone()),
+        Command::Enumerate { file_path, .. } | Command::Overlay { file_path } | Command::Archive { file_path, .. } => {
+            Some(file_path.clone())
+        }
+        Command::SteamArt { app_id, .. } => Some(app_id.clone()),
+        Command::Batch { .. } | Command::Watch { .. } => None,
+    };
+    match execute(command) {
+        Ok(true) => ExitCode::SUCCESS,
+        // A batch with failed jobs; each already reported its own error
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            error::log_failure(&error, source.as_deref());
+            let report = ErrorReport::new(&error, source.as_deref());
+            eprintln!("{}", serde_json::to_string(&report).unwrap());
+            ExitCode::from(report.code.exit_code())
+        }
+    }
+}
+
+/// Extracts one icon described by a batch job object, plus an optional `cacheDir`,
+/// and returns the job's result instead of printing it. Errors are the same
+/// {"code","message"} object `run` writes to stderr. Used by `altdesktop-helper --serve`.
+pub fn extract_json(job: serde_json::Value) -> Result<serde_json::Value, serde_json::Value> {
+    match batch::run_single(job) {
+        Ok(outcome) => Ok(serde_json::to_value(outcome).unwrap()),
+        Err(report) => Err(serde_json::to_value(report).unwrap()),
+    }
+}
+
+/// Frees a section an `extract_json` job with `sharedMemory` left for the host, given
+/// {"name"}, and returns {"released"}: false for a name that isn't held.
+pub fn release_json(params: serde_json::Value) -> Result<serde_json::Value, serde_json::Value> {
+    let Some(name) = params.get("name").and_then(serde_json::Value::as_str) else {
+        let report = ErrorReport::with_code(ErrorCode::InvalidArguments, "Expected a name".to_string());
+        return Err(serde_json::to_value(report).unwrap());
+    };
+    #[cfg(windows)]
+    let released = shared_memory::release(name);
+    #[cfg(not(windows))]
+    let released = {
+        let _ = name;
+        false
+    };
+    Ok(serde_json::json!({ "released": released }))
+}
+
+fn usage_error(message: String) -> ExitCode {
+    let report = ErrorReport::with_code(ErrorCode::InvalidArguments, message);
+    eprintln!("{}", serde_json::to_string(&report).unwrap());
+    eprintln!("{}", USAGE);
+    ExitCode::from(ErrorCode::InvalidArguments.exit_code())
+}
+
+// What an extract prints after it's written the image. `notes` are for a person,
+// and take stderr once --stdout or --data-uri has put the image itself on stdout
+fn print_extraction(
+    options: &Options,
+    extraction: &Extraction,
+    out: &mut impl Write,
+    notes: &mut impl Write,
+) -> io::Result<()> {
+    let kind = extraction.kind.name();
+    for output in &extraction.outputs {
+        let kind = match output.variant {
+            Some(theme) => format!("{} {} variant", theme, kind),
+            None => kind.to_string(),
+        };
+        match output.status {
+            OutputStatus::Unchanged => writeln!(out, "Unchanged {} at {}", kind, output.path)?,
+            _ => writeln!(out, "Saved {} to {}", kind, output.path)?,
+        }
+    }
+    let notes: &mut dyn Write = if options.stdout || options.data_uri { notes } else { &mut *out };
+    if extraction.source.is_fallback() && !options.stock {
+        writeln!(notes, "Used the {} icon; the file's own couldn't be extracted", extraction.source.name())?;
+    }
+    if extraction.animated {
+        writeln!(notes, "Source is animated; used its first frame")?;
+    }
+    if options.colors {
+        writeln!(out, "{}", serde_json::to_string(&extraction.colors).unwrap())?;
+    }
+    if let Some(hashes) = &extraction.hashes {
+        writeln!(out, "{}", serde_json::to_string(hashes).unwrap())?;
+    }
+    Ok(())
+}
+
+fn execute(command: Command) -> Result<bool> {
+    match command {
+        Command::Extract(options) => {
+            let extraction = extract(&options)?;
+            print_extraction(&options, &extraction, &mut io::stdout().lock(), &mut io::stderr().lock())?;
+            Ok(true)
+        }
+        Command::Batch { cache_dir, jobs, progress } => batch::run_batch(cache_dir.as_deref(), jobs, progress),
+        Command::Watch { cache_dir, interval, reextract } => watch::run_watch(cache_dir.as_deref(), interval, reextract),
+        Command::Archive { file_path, extract } => {
+            println!("{}", serde_json::to_string(&archive::describe_archive(&file_path, extract)?).unwrap());
+            Ok(true)
+        }
+        Command::SteamArt { app_id, art, download, extract } => {
+            println!("{}", serde_json::to_string(&steam_art::steam_art(&app_id, art, download, extract)?).unwrap());
+            Ok(true)
+        }
+        #[cfg(windows)]
+        Command::Enumerate { file_path, output_dir, format, background } => {
+            let groups = enumerate::enumerate_icons(&file_path, output_dir.as_deref(), format, background)?;
+            println!("{}", serde_json::to_string(&groups).unwrap());
+            Ok(true)
+        }
+        #[cfg(not(windows))]
+        Command::Enumerate { .. } => Err(failure(ErrorCode::Unsupported, "--enumerate is only supported on Windows")),
+        #[cfg(windows)]
+        Command::Overlay { file_path } => {
+            println!("{}", serde_json::to_string(&overlay::overlay_state(&file_path)?).unwrap());
+            Ok(true)
+        }
+        #[cfg(not(windows))]
+        Command::Overlay { .. } => Err(failure(ErrorCode::Unsupported, "--overlay is only supported on Windows")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::{ImageKind, ImageSource};
+
+    fn extract_options(args: &[&str]) -> Options {
+        let args: Vec<String> = ["icon"].iter().chain(args).map(|arg| arg.to_string()).collect();
+        match parse_args(&args) {
+            Ok(Command::Extract(options)) => options,
+            _ => panic!("expected an extract command"),
+        }
+    }
+
+    fn animated_extraction() -> Extraction {
+        Extraction {
+            kind: ImageKind::Icon,
+            source: ImageSource::File,
+            outputs: Vec::new(),
+            sections: Vec::new(),
+            colors: None,
+            hashes: None,
+            animated: true,
+        }
+    }
+
+    #[test]
+    fn animated_note_stays_out_of_the_stdout_frames() {
+        let options = extract_options(&["spinner.gif", "--stdout", "32"]);
+        let mut frames = Vec::new();
+        frames.extend_from_slice(&32u32.to_le_bytes());
+        frames.extend_from_slice(&4u32.to_le_bytes());
+        frames.extend_from_slice(b"\x89PNG");
+        let mut out = frames.clone();
+        let mut notes = Vec::new();
+        print_extraction(&options, &animated_extraction(), &mut out, &mut notes).unwrap();
+        assert_eq!(out, frames);
+        assert_eq!(String::from_utf8(notes).unwrap(), "Source is animated; used its first frame\n");
+    }
+
+    #[test]
+    fn fallback_note_stays_out_of_the_data_uri() {
+        let options = extract_options(&["setup.exe", "--data-uri", "32"]);
+        let extraction = Extraction { source: ImageSource::Association, animated: false, ..animated_extraction() };
+        let uri = b"data:image/png;base64,iVBORw==\n".to_vec();
+        let mut out = uri.clone();
+        let mut notes = Vec::new();
+        print_extraction(&options, &extraction, &mut out, &mut notes).unwrap();
+        assert_eq!(out, uri);
+        assert_eq!(String::from_utf8(notes).unwrap(), "Used the association icon; the file's own couldn't be extracted\n");
+    }
+}
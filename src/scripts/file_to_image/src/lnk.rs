@@ -0,0 +1,53 @@
+use std::path::Path;
+use altdesktop_core::path::{expand_env_vars, PATH_BUFFER_LEN};
+use altdesktop_core::wide::from_wide;
+use anyhow::Result;
+use windows::{
+    core::{ComInterface, HSTRING, PCWSTR},
+    Win32::System::Com::{CoCreateInstance, IPersistFile, CLSCTX_INPROC_SERVER, STGM_READ},
+    Win32::UI::Shell::{IShellLinkW, ShellLink},
+};
+
+/// Where a shortcut's icon comes from, read straight from the .lnk so the shell
+/// never composites its arrow overlay onto it or falls back to a generic glyph.
+pub enum IconSource {
+    // An explicit icon location: a file and an icon index inside it
+    Resource(String, i32),
+    // What Explorer shows when no icon was set
+    Target(String),
+}
+
+pub fn is_lnk(file_path: &str) -> bool {
+    Path::new(file_path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("lnk"))
+}
+
+/// The shortcut's explicit icon location followed by its target, skipping whichever
+/// isn't set. Shortcuts to shell namespaces have neither.
+pub fn icon_sources(file_path: &str) -> Result<Vec<IconSource>> {
+    unsafe {
+        let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
+        let persist_file: IPersistFile = link.cast()?;
+        persist_file.Load(PCWSTR(HSTRING::from(file_path).as_ptr()), STGM_READ)?;
+
+        let mut buffer = vec![0u16; PATH_BUFFER_LEN];
+        let mut index = 0;
+        link.GetIconLocation(&mut buffer, &mut index)?;
+        let icon_path = expand_env_vars(&from_wide(&buffer));
+
+        buffer.fill(0);
+        // Shell namespace targets have no path, which isn't an error here
+        let _ = link.GetPath(&mut buffer, std::ptr::null_mut(), 0);
+        let target = from_wide(&buffer);
+
+        let mut sources = Vec::new();
+        if !icon_path.is_empty() {
+            sources.push(IconSource::Resource(icon_path, index));
+        }
+        if !target.is_empty() {
+            sources.push(IconSource::Target(target));
+        }
+        Ok(sources)
+    }
+}
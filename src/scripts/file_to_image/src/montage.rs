@@ -0,0 +1,250 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::SystemTime;
+use altdesktop_core::cancel;
+#[cfg(windows)]
+use altdesktop_core::com::ComGuard;
+use anyhow::Result;
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+use crate::compose::Composite;
+use crate::error::check_cancelled;
+use crate::extract::{load_source, native_size};
+use crate::fallback::fallback_icon;
+use crate::image_file::is_image_file;
+use crate::options::Options;
+
+pub const DEFAULT_MONTAGE_ITEMS: u32 = 4;
+// A 3x3 grid is as small as a cell can get and still be told apart at 48px
+const MAX_MONTAGE_ITEMS: u32 = 9;
+// Bookkeeping files Explorer and Finder leave behind, which no preview should show
+const SKIPPED_NAMES: &[&str] = &["desktop.ini", "thumbs.db", ".ds_store"];
+#[cfg(windows)]
+const HIDDEN_ATTRIBUTES: u32 = 0x2 | 0x4;
+
+/// How a folder's items are laid out in its preview.
+#[derive(Clone, Copy)]
+pub enum MontageLayout {
+    /// Side by side in rows, as Explorer previews a folder of pictures.
+    Grid,
+    /// Overlapping cards, the first item on top.
+    Stack,
+}
+
+impl MontageLayout {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "grid" => Ok(MontageLayout::Grid),
+            "stack" => Ok(MontageLayout::Stack),
+            _ => Err(format!("Invalid montage layout: {} (expected grid or stack)", value)),
+        }
+    }
+}
+
+/// A preview of up to `items` of a folder's contents in place of its icon.
+#[derive(Clone, Copy)]
+pub struct Montage {
+    pub layout: MontageLayout,
+    pub items: u32,
+}
+
+impl Montage {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_MONTAGE_ITEMS).contains(&self.items) {
+            return Err(format!("Invalid montage item count: {} (expected 1 to {})", self.items, MAX_MONTAGE_ITEMS));
+        }
+        Ok(())
+    }
+
+    /// Bytes that identify the montage in cache keys.
+    pub fn key(&self) -> [u8; 2] {
+        [1 + self.layout as u8, self.items as u8]
+    }
+}
+
+/// Draws the preview of `options.file_path`, a folder, or None when it has nothing
+/// to show, which leaves the folder to its own icon. Each item is loaded as an
+/// extraction of its own would load it, thumbnails first and falling back to its
+/// type's icon, on a thread of its own as batch jobs are.
+pub fn folder_montage(options: &Options, montage: Montage) -> Result<Option<DynamicImage>> {
+    let items = sample(Path::new(&options.file_path), montage.items as usize);
+    if items.is_empty() {
+        return Ok(None);
+    }
+    let size = native_size(options.pixels(*options.sizes.last().unwrap()));
+    let cell = match montage.layout {
+        MontageLayout::Grid => size / columns(items.len()),
+        MontageLayout::Stack => card_size(size, items.len()),
+    }
+    .max(1);
+
+    let token = cancel::current().unwrap_or_default();
+    let images: Vec<RgbaImage> = thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .iter()
+            .map(|item| {
+                let token = &token;
+                scope.spawn(move || {
+                    #[cfg(windows)]
+                    let _com = ComGuard::apartment();
+                    cancel::scope(token, || item_image(options, item, cell))
+                })
+            })
+            .collect();
+        workers.into_iter().filter_map(|worker| worker.join().ok()).collect()
+    });
+    check_cancelled()?;
+    if images.is_empty() {
+        return Ok(None);
+    }
+
+    let canvas = match montage.layout {
+        MontageLayout::Grid => grid(&images, size, options),
+        MontageLayout::Stack => stack(&images, size, options),
+    };
+    Ok(Some(DynamicImage::ImageRgba8(canvas)))
+}
+
+// Pictures first, as they say the most about a folder, then other files, then
+// subfolders, each newest first
+fn sample(folder: &Path, count: usize) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    let mut items: Vec<(u8, SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            let metadata = entry.metadata().ok()?;
+            if name.starts_with('.') || SKIPPED_NAMES.contains(&name.as_str()) || is_hidden(&metadata) {
+                return None;
+            }
+            let path = entry.path();
+            let rank = if metadata.is_dir() {
+                2
+            } else if is_image_file(&path.to_string_lossy()) {
+                0
+            } else {
+                1
+            };
+            Some((rank, metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH), path))
+        })
+        .collect();
+    items.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.cmp(&a.1)));
+    items.into_iter().take(count).map(|(_, _, path)| path).collect()
+}
+
+#[cfg(windows)]
+fn is_hidden(metadata: &fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    metadata.file_attributes() & HIDDEN_ATTRIBUTES != 0
+}
+
+#[cfg(not(windows))]
+fn is_hidden(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+// The item's thumbnail with only the options that shape its pixels carried over;
+// its tile is the montage's, so compositing, badges and caching are left to that
+fn item_image(options: &Options, item: &Path, cell: u32) -> RgbaImage {
+    let file_path = item.to_string_lossy().into_owned();
+    let item_options = Options {
+        file_path: file_path.clone(),
+        output_path: String::new(),
+        sizes: vec![cell],
+        thumbnail: true,
+        thumbnail_cache: options.thumbnail_cache,
+        resource_index: None,
+        video_frame: None,
+        cache_dir: None,
+        package: false,
+        stock: false,
+        composite: Composite::default(),
+        badge: None,
+        montage: None,
+        trim: None,
+        variants: None,
+        blur_backdrop: false,
+        colors: false,
+        hash: false,
+        ico_pack: false,
+        scale: 1.0,
+        // The montage's own extraction is what runs under the watchdog
+        timeout: None,
+        ..options.clone()
+    };
+    match load_source(&item_options) {
+        Ok((img, ..)) => img.to_rgba8(),
+        Err(e) => {
+            tracing::debug!(source = %file_path, "Montage item fell back to its type's icon: {:#}", e);
+            fallback_icon(&file_path, native_size(cell)).0.to_rgba8()
+        }
+    }
+}
+
+fn columns(count: usize) -> u32 {
+    (count as f32).sqrt().ceil() as u32
+}
+
+// Square cells, with a gap of 1/32 of the tile between them, the grid centered
+fn grid(images: &[RgbaImage], size: u32, options: &Options) -> RgbaImage {
+    let columns = columns(images.len());
+    let rows = (images.len() as u32).div_ceil(columns);
+    let gap = if columns > 1 { size / 32 } else { 0 };
+    let cell = ((size - gap * (columns - 1)) / columns).max(1);
+    let top = (size - (cell * rows + gap * (rows - 1))) / 2;
+    let mut canvas = RgbaImage::new(size, size);
+    for (index, img) in images.iter().enumerate() {
+        let (column, row) = (index as u32 % columns, index as u32 / columns);
+        let fitted = options.resample.resize(&DynamicImage::ImageRgba8(img.clone()), cell, cell).to_rgba8();
+        let x = column * (cell + gap) + (cell - fitted.width()) / 2;
+        let y = top + row * (cell + gap) + (cell - fitted.height()) / 2;
+        imageops::overlay(&mut canvas, &fitted, x as i64, y as i64);
+    }
+    canvas
+}
+
+// A lone item fills the tile; otherwise the cards leave a quarter of it for the
+// ones behind to show past
+fn card_size(size: u32, count: usize) -> u32 {
+    if count == 1 {
+        size
+    } else {
+        size * 3 / 4
+    }
+}
+
+// Each further card a step up and to the right of the one in front, on a white
+// mount with a grey edge, which hides the cards behind a transparent icon
+fn stack(images: &[RgbaImage], size: u32, options: &Options) -> RgbaImage {
+    let mut canvas = RgbaImage::new(size, size);
+    if let [img] = images {
+        let fitted = options.resample.resize(&DynamicImage::ImageRgba8(img.clone()), size, size).to_rgba8();
+        let (x, y) = ((size - fitted.width()) / 2, (size - fitted.height()) / 2);
+        imageops::overlay(&mut canvas, &fitted, x as i64, y as i64);
+        return canvas;
+    }
+    let card = card_size(size, images.len());
+    let step = (size - card) / (images.len() as u32 - 1);
+    let border = (card / 32).max(1);
+    let mount = RgbaImage::from_fn(card, card, |x, y| {
+        if x < border || y < border || x + border >= card || y + border >= card {
+            Rgba([160, 160, 160, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+    let inner = card - 4 * border;
+    // Back to front, so the first item ends up on top
+    for (depth, img) in images.iter().enumerate().rev() {
+        let depth = depth as u32;
+        let (x, y) = (depth * step, size - card - depth * step);
+        imageops::overlay(&mut canvas, &mount, x as i64, y as i64);
+        let fitted = options.resample.resize(&DynamicImage::ImageRgba8(img.clone()), inner, inner).to_rgba8();
+        let (inset_x, inset_y) = ((card - fitted.width()) / 2, (card - fitted.height()) / 2);
+        imageops::overlay(&mut canvas, &fitted, (x + inset_x) as i64, (y + inset_y) as i64);
+    }
+    canvas
+}
@@ -0,0 +1,744 @@
+use std::thread;
+use std::time::Duration;
+use altdesktop_core::config;
+use image::Rgb;
+
+use crate::badge::{Badge, Corner, DEFAULT_BADGE_SIZE};
+use crate::compose::{Composite, Fill};
+use crate::encode::{parse_color, OutputFormat, DEFAULT_BACKGROUND};
+use crate::ico::PACK_SIZES;
+use crate::montage::{Montage, MontageLayout, DEFAULT_MONTAGE_ITEMS};
+use crate::resample::Resample;
+use crate::steam_art::Art;
+use crate::trim::DEFAULT_TRIM_PADDING;
+use crate::variants::VariantStyle;
+use crate::watch::DEFAULT_INTERVAL_MS;
+
+// Far longer than any healthy handler takes, even for a thumbnail off a slow drive
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+
+pub const USAGE: &str = "Usage:
+  altdesktop-helper icon extract <filePath> <outputPath> <imageSize> [options]
+  altdesktop-helper icon extract <filePath> <outputTemplate> --sizes <size,size,...> [options]
+  altdesktop-helper icon extract <filePath> --stdout <imageSize> [options]
+  altdesktop-helper icon extract <filePath> --data-uri <imageSize> [options]
+  altdesktop-helper icon extract <filePath> --ico-out <output.ico> [--sizes <size,size,...>] [options]
+  altdesktop-helper icon extract --package <PackageFamilyName> <outputPath> <imageSize> [options]
+  altdesktop-helper icon extract --stock <name|number> <outputPath> <imageSize> [options]
+  altdesktop-helper icon batch [--cache-dir <dir>] [--jobs <count>] [--progress] < jobs.json
+  altdesktop-helper icon watch [--cache-dir <dir>] [--interval <ms>] [--reextract] < jobs.json
+  altdesktop-helper icon enumerate <file.exe|file.dll> [<outputDir>] [--format <format>]
+  altdesktop-helper icon overlay <filePath>
+  altdesktop-helper icon archive <file.zip> [<outputPath> <imageSize>] [options]
+  altdesktop-helper icon steam-art <appid> <outputPath> <imageSize> [--art <art>] [--download] [options]
+
+<outputTemplate> must contain {size} when more than one size is requested,
+e.g. icons\\app_{size}.png
+
+--stock draws one of the shell's stock icons, as Windows draws it for its own folders,
+drives, warnings and prompts, through the same resizing, compositing and encoding as a
+file's icon, to head a category or, once written, to serve as a --badge. It's named
+as its SHSTOCKICONID less SIID_, e.g. folder, drivefixed, drivenet, mynetwork,
+warning, info, error, shield, lock, users, recycler or settings, or by number; the
+source printed is stock. Batch jobs take \"stock\":true with the name as input.
+
+--ico-out packs every size into one .ico, 16, 24, 32, 48 and 256px unless --sizes is given.
+
+--batch prints a JSON result line per job as each finishes, with its kind and its source:
+file, linkIcon or linkTarget for the file's own icon, a shortcut's icon location or its
+target, and association, stock or generic when it fell back; --progress follows each with
+{\"type\":\"progress\",\"id\",\"completed\",\"total\",\"item\"}, id being the job's index.
+Stdin may be kept open after the jobs to send {\"cancel\":<index>} or {\"cancel\":\"all\"}
+lines: cancelled jobs fail with errorCode cancelled, and any files they had written
+are removed.
+
+--watch takes the same jobs and watches the files each icon comes from: the file, a
+folder's desktop.ini and the icon it names, a shortcut's icon location and target,
+and the badge, checking every --interval ms (2000). When one has changed and then
+held still for a check, as after an app updates itself, it removes the job's
+--cache-dir entry and prints {\"type\":\"invalidated\",\"index\",\"input\",
+\"changed\":[{\"path\",\"change\"}]}, change being modified, created or deleted.
+--reextract also runs the job again in the background and prints its result line,
+as --batch would, with \"type\":\"extracted\". Jobs that aren't valid are reported
+at once with \"type\":\"rejected\", then {\"type\":\"ready\",\"jobs\",\"sources\"}
+once watching starts. It runs until stdin is closed.
+
+--overlay prints the icon overlay Explorer draws on the path (shortcut arrow, share,
+OneDrive sync state...) and the handlers and file attributes behind it, as JSON.
+
+--archive prints {\"entries\":[{\"name\",\"size\",\"compressedSize\",\"type\"}],\"representative\",\"kind\",\"outputs\"}
+for a .zip (or .jar, .epub and other zip-based files) without unpacking it. Given
+an <outputPath> and size, it also writes the icon or thumbnail of the entry that best
+shows what's inside: an image named like icon, logo or cover, then a program, then
+any image, then the largest file. type is folder, image, icon, executable, document,
+audio, video, archive or file; representative is null when the archive's own icon
+was used instead.
+
+--steam-art writes the art Steam's library shows for a game, from the cache of the
+Steam client installed, as a tile: --art capsule (the default) is the portrait cover
+of the grid view, hero the wide banner of the game's page, header the store's
+banner and logo the game's transparent logo. --download fetches it from Steam's CDN
+when it isn't cached, keeping it in --cache-dir when one is given. Prints
+{\"appId\",\"art\",\"source\",\"downloaded\",\"outputs\"}, where source is the file or
+URL the art came from, plus colors and hashes when asked for.
+
+--stdout writes each size as a frame instead of a file: the requested size and the
+byte length of the encoded image as little-endian u32s, followed by the image bytes.
+
+--data-uri prints each size as a data: URI line instead of writing a file, e.g.
+data:image/png;base64,iVBORw0..., smallest size first.
+
+With either, stdout holds only the image: the notes an extract otherwise prints there,
+on a fallback source or an animated one, go to stderr instead. A batch job reports both
+in its result, as source and animated.
+
+Options:
+  --format png|webp|ico|bmp|jpeg|svg|rgba  (default png; rgba is the raw pixels after the width
+                                          and height as little-endian u32s)
+  --filter nearest|triangle|catmull-rom|gaussian|lanczos3  (default catmull-rom; nearest for pixel art)
+  --sharpen <sigma>  (unsharp mask after scaling, e.g. 0.8)
+  --scale <factor>|<percent>%|auto|cursor  (render sizes for a high-DPI display; auto
+                                            reads the primary monitor's scale, cursor
+                                            that of the monitor under the mouse cursor)
+  --svg  (same as --format svg: the image embedded in an SVG, with --fill and
+          --corner-radius drawn as vector shapes)
+  --background <#RRGGBB>  (fills transparency for jpeg, default #FFFFFF)
+  --resource-index <index>  (icon number inside an .exe/.dll/.ico; negative for a resource ID)
+  --thumbnail  (preview of the file's contents, falling back to its icon; keeps the aspect ratio.
+               PDFs render their first page even without a PDF reader installed. On Linux,
+               previews come from and are saved to the thumbnail cache file managers share,
+               made by the thumbnailers installed for videos and other types)
+  --thumbnail-cache  (with --thumbnail on Windows, first take the preview Explorer already has
+                      in its thumbnail cache, without running a thumbnail handler; extracts as
+                      usual when the cache has none at the largest size)
+  --video-frame <seconds>  (decode the frame of a video file at that time instead of its icon)
+  --cache-dir <dir>  (reuse earlier results while the file's path, size and mtime are unchanged)
+  --trim [--trim-padding <percent>]  (crop transparent margins before resizing, default 4% padding)
+  --variants outline|backdrop  (when the icon has too little contrast against light or dark
+                                wallpapers, also write <output>.light.<ext> / <output>.dark.<ext>)
+  --blur-backdrop  (also write <output>.blur.<ext>, a blurred and darkened texture of the icon
+                    for hover states and tile backdrops)
+  --colors  (also print {\"dominant\":\"#RRGGBB\",\"average\":\"#RRGGBB\"} for tinting the tile)
+  --timeout <ms>  (give up with a timeout error when the shell handler or codec for the file
+                  hasn't answered by then; default 30000, 0 waits forever)
+  --hash  (also print {\"dhash\":\"<16 hex>\",\"phash\":\"<16 hex>\"} for spotting duplicate icons;
+           compare by Hamming distance, a few bits apart is the same icon)
+
+Tile compositing (percentages are of the image size):
+  --fill <#RRGGBB>|<#RRGGBB,#RRGGBB>  (solid or top-to-bottom gradient background)
+  --padding <percent>  --corner-radius <percent>  (50 gives a circle)
+  --badge <image>  (emblem drawn over a corner of the output)
+  --badge-corner top-left|top-right|bottom-left|bottom-right  (default bottom-right)
+  --badge-size <percent>  (default 40)
+
+Folder previews:
+  --montage grid|stack  (for a folder, draw the thumbnails of some of its items instead of
+                        its icon, as Explorer previews folders: side by side in a grid, or
+                        as overlapping cards with the first on top; pictures come first,
+                        then other files, then subfolders, each newest first, and hidden
+                        items are left out. An empty folder keeps its own icon. Batch jobs
+                        take \"montage\" and \"montageItems\")
+  --montage-items <count>  (how many items to show, 1 to 9, default 4)
+  --no-follow-lnk  (use a .lnk's own shell icon, arrow overlay included, instead of the icon
+                   location or target it points at)
+  --no-fallback  (fail when the file's own icon can't be extracted, instead of falling back to
+                 the icon registered for its type, then the shell's stock folder, program or
+                 document icon, then a plain page; the source used is printed either way)
+
+<filePath> may also be an http(s) URL or a .url file, which extracts the site's favicon.
+A macOS .app bundle gives the .icns its Info.plist names, and an .icns its image closest
+to the size.
+Image files (png, gif, webp, jpeg, bmp) are used as their own icon; animated ones use
+their first frame.
+
+Outputs that already hold identical bytes are left untouched and reported as unchanged.
+
+Failures print {\"code\":\"...\",\"message\":\"...\"} on stderr, where code is one of
+invalidArguments, notFound, accessDenied, unsupported, timeout, decode or unknown, and
+exit with the helper's code for it: 2, 3, 4, 6 and 5 for the first five, 1 for the rest.";
+
+pub enum Command {
+    Extract(Options),
+    Batch { cache_dir: Option<String>, jobs: usize, progress: bool },
+    Watch { cache_dir: Option<String>, interval: Duration, reextract: bool },
+    Enumerate { file_path: String, output_dir: Option<String>, format: OutputFormat, background: Rgb<u8> },
+    Overlay { file_path: String },
+    /// `extract` holds the options for the representative entry's image, if one was asked for.
+    Archive { file_path: String, extract: Option<Options> },
+    /// `extract` has the app id in place of the file until the art is found.
+    SteamArt { app_id: String, art: Art, download: bool, extract: Options },
+}
+
+#[derive(Clone)]
+pub struct Options {
+    pub file_path: String,
+    pub output_path: String,
+    pub sizes: Vec<u32>,
+    pub format: OutputFormat,
+    pub background: Rgb<u8>,
+    pub resource_index: Option<i32>,
+    pub thumbnail: bool,
+    /// Try the shell's thumbnail cache before extracting a thumbnail.
+    pub thumbnail_cache: bool,
+    /// Seconds into a video file to grab the frame from.
+    pub video_frame: Option<f64>,
+    pub stdout: bool,
+    /// Print each size as a base64 data: URI line instead of writing files.
+    pub data_uri: bool,
+    pub cache_dir: Option<String>,
+    /// `file_path` is the family name of an installed Store app rather than a path.
+    pub package: bool,
+    /// `file_path` is a stock icon's name or number rather than a path.
+    pub stock: bool,
+    pub composite: Composite,
+    pub badge: Option<Badge>,
+    /// Preview a folder's contents rather than draw its icon.
+    pub montage: Option<Montage>,
+    /// Extract a .lnk's icon location or target rather than the shortcut file.
+    pub follow_lnk: bool,
+    /// Fall back to a type, stock or generic icon rather than fail.
+    pub fallback: bool,
+    pub colors: bool,
+    pub hash: bool,
+    /// Padding percentage to leave around the art after cropping, if --trim was given.
+    pub trim: Option<u32>,
+    pub resample: Resample,
+    pub variants: Option<VariantStyle>,
+    pub blur_backdrop: bool,
+    /// Write all sizes into a single multi-resolution .ico at `output_path`.
+    pub ico_pack: bool,
+    /// Display scale the sizes are in; 1.5 renders a 64px request at 96px.
+    pub scale: f32,
+    /// How long the extraction may take before it's abandoned; None waits for it.
+    pub timeout: Option<Duration>,
+    /// Hand each frame over in a named section rather than a file, for `--serve`,
+    /// whose sections outlive the request.
+    pub shared_memory: bool,
+}
+
+impl Options {
+    /// Checks the combinations that can't be expressed by the parser alone.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sizes.is_empty() {
+            return Err("No image size requested".to_string());
+        }
+        if let Some(&size) = self.sizes.iter().find(|&&size| !(1..=65535).contains(&size)) {
+            return Err(format!("Invalid image size: {}", size));
+        }
+
+        if self.sizes.len() > 1 && !self.stdout && !self.data_uri && !self.ico_pack && !self.output_path.contains("{size}") {
+            return Err("<outputTemplate> must contain {size} when several sizes are requested".to_string());
+        }
+
+        if self.stdout && self.data_uri {
+            return Err("--stdout cannot be combined with --data-uri".to_string());
+        }
+
+        // Their JSON would land in the same stdout as the image
+        if (self.colors || self.hash) && (self.stdout || self.data_uri) {
+            return Err("--colors and --hash cannot be combined with --stdout or --data-uri".to_string());
+        }
+
+        if self.variants.is_some() {
+            if self.stdout || self.data_uri || self.ico_pack || matches!(self.format, OutputFormat::Svg) {
+                return Err("--variants cannot be combined with --stdout, --data-uri, --ico-out or SVG output".to_string());
+            }
+            // A filled tile is its own backdrop
+            if self.composite.fill.is_some() {
+                return Err("--variants cannot be combined with --fill".to_string());
+            }
+        }
+
+        if self.shared_memory && (self.variants.is_some() || self.blur_backdrop) {
+            return Err("sharedMemory cannot be combined with variants or blurBackdrop".to_string());
+        }
+
+        if self.blur_backdrop && (self.stdout || self.data_uri || self.ico_pack || matches!(self.format, OutputFormat::Svg)) {
+            return Err("--blur-backdrop cannot be combined with --stdout, --data-uri, --ico-out or SVG output".to_string());
+        }
+
+        if self.stock && (self.package || self.resource_index.is_some() || self.thumbnail || self.video_frame.is_some()) {
+            return Err("--stock cannot be combined with --package, --resource-index, --thumbnail or --video-frame".to_string());
+        }
+
+        if self.thumbnail && self.resource_index.is_some() {
+            return Err("--thumbnail cannot be combined with --resource-index".to_string());
+        }
+        if self.thumbnail_cache && !self.thumbnail {
+            return Err("--thumbnail-cache requires --thumbnail".to_string());
+        }
+
+        if let Some(seconds) = self.video_frame {
+            if !(seconds >= 0.0 && seconds.is_finite()) {
+                return Err(format!("Invalid video frame time: {} (expected seconds, e.g. 12.5)", seconds));
+            }
+            if self.thumbnail || self.resource_index.is_some() || self.package {
+                return Err("--video-frame cannot be combined with --thumbnail, --resource-index or --package".to_string());
+            }
+        }
+
+        if !(self.scale > 0.0 && self.scale <= 8.0) {
+            return Err(format!("Invalid scale: {} (expected a factor such as 1.5)", self.scale));
+        }
+
+        // ICO frames store their size in a byte, where 0 means 256
+        if matches!(self.format, OutputFormat::Ico) && self.sizes.iter().any(|&size| self.pixels(size) > 256) {
+            return Err("ICO output is limited to 256px".to_string());
+        }
+        if let Some(badge) = &self.badge {
+            badge.validate()?;
+        }
+        if let Some(montage) = &self.montage {
+            if self.package || self.stock || self.resource_index.is_some() || self.video_frame.is_some() {
+                return Err("--montage cannot be combined with --package, --stock, --resource-index or --video-frame".to_string());
+            }
+            montage.validate()?;
+        }
+        self.resample.validate()?;
+        self.composite.validate()
+    }
+
+    /// The size in physical pixels that `size` is rendered at.
+    pub fn pixels(&self, size: u32) -> u32 {
+        ((size as f32 * self.scale).round() as u32).max(1)
+    }
+
+    /// The file written for one of the requested sizes.
+    pub fn output_for(&self, size: u32) -> String {
+        self.output_path.replace("{size}", &size.to_string())
+    }
+}
+
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut enumerate = None;
+    let mut overlay = None;
+    let mut archive = None;
+    let mut steam_art = None;
+    let mut art = None;
+    let mut download = false;
+    let mut batch = false;
+    let mut watch = false;
+    let mut interval = None;
+    let mut reextract = false;
+    let mut jobs = None;
+    let mut progress = false;
+    let mut sizes = None;
+    let mut format = default_format()?;
+    let mut format_flag = false;
+    let mut background = DEFAULT_BACKGROUND;
+    let mut resource_index = None;
+    let mut thumbnail = false;
+    let mut thumbnail_cache = false;
+    let mut video_frame = None;
+    let mut stdout = false;
+    let mut data_uri = false;
+    let mut cache_dir = None;
+    let mut package = None;
+    let mut stock = None;
+    let mut composite = Composite::default();
+    let mut badge = None;
+    let mut badge_corner = None;
+    let mut badge_size = None;
+    let mut montage = None;
+    let mut montage_items = None;
+    let mut follow_lnk = true;
+    let mut no_shortcut_arrow = false;
+    let mut fallback = true;
+    let mut colors = false;
+    let mut hash = false;
+    let mut trim = false;
+    let mut resample = Resample::default();
+    let mut variants = None;
+    let mut blur_backdrop = false;
+    let mut ico_out = None;
+    let mut scale = 1.0;
+    let mut timeout = default_timeout();
+    let mut trim_padding = None;
+    let mut positional = Vec::new();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("{} requires a value", flag))
+        };
+        match flag {
+            "--enumerate" => enumerate = Some(value()?),
+            "--overlay" => overlay = Some(value()?),
+            "--archive" => archive = Some(value()?),
+            "--steam-art" => steam_art = Some(value()?),
+            "--art" => art = Some(Art::parse(&value()?)?),
+            "--download" => download = true,
+            "--progress" => progress = true,
+            "--batch" => batch = true,
+            "--watch" => watch = true,
+            "--interval" => {
+                let value = value()?;
+                interval = Some(match value.parse() {
+                    Ok(ms @ 1..) => Duration::from_millis(ms),
+                    _ => return Err(format!("Invalid interval: {} (expected milliseconds)", value)),
+                });
+            }
+            "--reextract" => reextract = true,
+            "--jobs" => {
+                let value = value()?;
+                jobs = Some(match value.parse() {
+                    Ok(count @ 1..) => count,
+                    _ => return Err(format!("Invalid job count: {}", value)),
+                });
+            }
+            "--sizes" => sizes = Some(parse_sizes(&value()?)?),
+            "--format" => {
+                format = OutputFormat::parse(&value()?)?;
+                format_flag = true;
+            }
+            "--svg" => {
+                format = OutputFormat::Svg;
+                format_flag = true;
+            }
+            "--filter" => resample.filter = Resample::parse_filter(&value()?)?,
+            "--sharpen" => {
+                let value = value()?;
+                resample.sharpen = Some(value.parse().map_err(|_| format!("Invalid sharpen amount: {}", value))?);
+            }
+            "--background" => background = parse_color(&value()?)?,
+            "--thumbnail" => thumbnail = true,
+            "--thumbnail-cache" => thumbnail_cache = true,
+            "--video-frame" => {
+                let value = value()?;
+                video_frame = Some(value.parse().map_err(|_| format!("Invalid video frame time: {}", value))?);
+            }
+            "--stdout" => stdout = true,
+            "--data-uri" => data_uri = true,
+            "--cache-dir" => cache_dir = Some(value()?),
+            "--package" => package = Some(value()?),
+            "--stock" => stock = Some(value()?),
+            "--fill" => composite.fill = Some(Fill::parse(&value()?)?),
+            "--padding" => composite.padding = parse_percent(flag, &value()?)?,
+            "--corner-radius" => composite.corner_radius = parse_percent(flag, &value()?)?,
+            "--badge" => badge = Some(value()?),
+            "--badge-corner" => badge_corner = Some(Corner::parse(&value()?)?),
+            "--badge-size" => badge_size = Some(parse_percent(flag, &value()?)?),
+            "--montage" => montage = Some(MontageLayout::parse(&value()?)?),
+            "--montage-items" => {
+                let value = value()?;
+                montage_items = Some(value.parse().map_err(|_| format!("Invalid montage item count: {}", value))?);
+            }
+            // Following shortcuts is the default now, which never draws the arrow
+            "--no-shortcut-arrow" => no_shortcut_arrow = true,
+            "--no-follow-lnk" => follow_lnk = false,
+            "--no-fallback" => fallback = false,
+            "--colors" => colors = true,
+            "--hash" => hash = true,
+            "--variants" => variants = Some(VariantStyle::parse(&value()?)?),
+            "--blur-backdrop" => blur_backdrop = true,
+            "--ico-out" => ico_out = Some(value()?),
+            "--scale" => scale = parse_scale(&value()?)?,
+            "--timeout" => timeout = parse_timeout(&value()?)?,
+            "--trim" => trim = true,
+            "--trim-padding" => trim_padding = Some(parse_percent(flag, &value()?)?),
+            "--resource-index" => {
+                let value = value()?;
+                resource_index = Some(value.parse().map_err(|_| format!("Invalid resource index: {}", value))?);
+            }
+            _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    // The .lnk's own shell icon is the one with the arrow drawn on
+    if no_shortcut_arrow && !follow_lnk {
+        return Err("--no-shortcut-arrow cannot be combined with --no-follow-lnk".to_string());
+    }
+
+    if watch {
+        if batch
+            || enumerate.is_some()
+            || overlay.is_some()
+            || archive.is_some()
+            || steam_art.is_some()
+            || stdout
+            || data_uri
+            || jobs.is_some()
+            || progress
+            || !positional.is_empty()
+        {
+            return Err("--watch reads its jobs from stdin and takes only --cache-dir, --interval and --reextract".to_string());
+        }
+        let interval = interval.unwrap_or(Duration::from_millis(DEFAULT_INTERVAL_MS));
+        return Ok(Command::Watch { cache_dir: cache_dir.or_else(default_cache_dir), interval, reextract });
+    }
+    if interval.is_some() || reextract {
+        return Err("--interval and --reextract only apply to --watch".to_string());
+    }
+
+    if batch {
+        if enumerate.is_some()
+            || overlay.is_some()
+            || archive.is_some()
+            || steam_art.is_some()
+            || stdout
+            || data_uri
+            || !positional.is_empty()
+        {
+            return Err("--batch reads its jobs from stdin and takes no other arguments".to_string());
+        }
+        // Extraction is mostly CPU-bound decoding and resizing
+        let jobs = jobs.unwrap_or_else(|| thread::available_parallelism().map_or(1, |count| count.get()));
+        return Ok(Command::Batch { cache_dir: cache_dir.or_else(default_cache_dir), jobs, progress });
+    }
+
+    if let Some(file_path) = overlay {
+        if args.len() != 3 {
+            return Err("--overlay takes only a <filePath>".to_string());
+        }
+        return Ok(Command::Overlay { file_path });
+    }
+
+    if jobs.is_some() || progress {
+        return Err("--jobs and --progress only apply to --batch".to_string());
+    }
+
+    if package.is_some() && (enumerate.is_some() || resource_index.is_some() || thumbnail) {
+        return Err("--package cannot be combined with --enumerate, --resource-index or --thumbnail".to_string());
+    }
+    if stock.is_some() && (enumerate.is_some() || archive.is_some() || steam_art.is_some()) {
+        return Err("--stock cannot be combined with --enumerate, --archive or --steam-art".to_string());
+    }
+    // --package and --stock name the source in place of the <filePath>
+    let named = package.is_some() || stock.is_some();
+
+    if let Some(file_path) = &archive {
+        // The image comes from a file unpacked for this run only, so it isn't worth caching
+        if enumerate.is_some() || package.is_some() || video_frame.is_some() || stdout || data_uri || cache_dir.is_some() {
+            return Err(
+                "--archive cannot be combined with --enumerate, --package, --video-frame, --stdout, --data-uri or --cache-dir"
+                    .to_string(),
+            );
+        }
+        if positional.is_empty() && sizes.is_none() && ico_out.is_none() {
+            return Ok(Command::Archive { file_path: file_path.clone(), extract: None });
+        }
+        positional.insert(0, file_path.clone());
+    } else {
+        cache_dir = cache_dir.or_else(default_cache_dir);
+    }
+
+    if let Some(app_id) = &steam_art {
+        if enumerate.is_some()
+            || package.is_some()
+            || archive.is_some()
+            || resource_index.is_some()
+            || thumbnail
+            || video_frame.is_some()
+            || stdout
+            || data_uri
+        {
+            return Err("--steam-art cannot be combined with --enumerate, --package, --archive, --resource-index, \
+                        --thumbnail, --video-frame, --stdout or --data-uri"
+                .to_string());
+        }
+        positional.insert(0, app_id.clone());
+    } else if art.is_some() || download {
+        return Err("--art and --download require --steam-art".to_string());
+    }
+
+    if let Some(file_path) = enumerate {
+        if sizes.is_some() || resource_index.is_some() || thumbnail || stdout {
+            return Err("--enumerate cannot be combined with --sizes, --resource-index, --thumbnail or --stdout".to_string());
+        }
+        if positional.len() > 1 {
+            return Err("--enumerate expects at most an <outputDir>".to_string());
+        }
+        return Ok(Command::Enumerate { file_path, output_dir: positional.pop(), format, background });
+    }
+
+    if badge.is_none() && (badge_corner.is_some() || badge_size.is_some()) {
+        return Err("--badge-corner and --badge-size require --badge".to_string());
+    }
+    let badge = badge.map(|path| Badge {
+        path,
+        corner: badge_corner.unwrap_or(Corner::BottomRight),
+        size: badge_size.unwrap_or(DEFAULT_BADGE_SIZE),
+    });
+
+    if montage.is_none() && montage_items.is_some() {
+        return Err("--montage-items requires --montage".to_string());
+    }
+    let montage = montage.map(|layout| Montage { layout, items: montage_items.unwrap_or(DEFAULT_MONTAGE_ITEMS) });
+
+    if trim_padding.is_some() && !trim {
+        return Err("--trim-padding requires --trim".to_string());
+    }
+    let trim = trim.then(|| trim_padding.unwrap_or(DEFAULT_TRIM_PADDING));
+
+    let ico_pack = ico_out.is_some();
+    let (sizes, output_path) = if let Some(ico_out) = ico_out {
+        if stdout || data_uri || format_flag {
+            return Err("--ico-out cannot be combined with --stdout, --data-uri or --format".to_string());
+        }
+        if positional.len() != 1 - named as usize {
+            return Err("--ico-out expects only <filePath>".to_string());
+        }
+        format = OutputFormat::Ico;
+        (sizes.unwrap_or_else(|| PACK_SIZES.to_vec()), ico_out)
+    } else {
+        // The original positional form passes a single size as the third argument;
+        // --stdout and --data-uri drop the <outputPath>, --package and --stock the <filePath>
+        let printed = match (stdout, data_uri) {
+            (true, _) => Some("--stdout"),
+            (_, true) => Some("--data-uri"),
+            _ => None,
+        };
+        let expected = (if printed.is_some() { 1 } else { 2 }) - named as usize;
+        // The config's sizes stand in for a missing <imageSize>
+        let sizes = sizes.or_else(|| (positional.len() == expected).then(default_sizes).flatten());
+        let sizes = match (sizes, positional.len(), printed) {
+            (None, n, _) if n == expected + 1 => vec![parse_size(&positional.pop().unwrap())?],
+            (Some(sizes), n, _) if n == expected => sizes,
+            (None, _, Some(flag)) => return Err(format!("{} expects <filePath> <imageSize>", flag)),
+            (None, _, None) => return Err("Expected <filePath> <outputPath> <imageSize>".to_string()),
+            (Some(_), _, Some(flag)) => return Err(format!("{} with --sizes expects only <filePath>", flag)),
+            (Some(_), _, None) => return Err("--sizes expects <filePath> and <outputTemplate>".to_string()),
+        };
+        (sizes, if printed.is_some() { String::new() } else { positional.pop().unwrap() })
+    };
+    let file_path = match package.clone().or_else(|| stock.clone()) {
+        Some(name) => name,
+        None => positional.pop().unwrap(),
+    };
+
+    let options = Options {
+        file_path,
+        output_path,
+        sizes,
+        format,
+        background,
+        resource_index,
+        thumbnail,
+        thumbnail_cache,
+        video_frame,
+        stdout,
+        data_uri,
+        cache_dir,
+        package: package.is_some(),
+        stock: stock.is_some(),
+        composite,
+        badge,
+        montage,
+        follow_lnk,
+        fallback,
+        colors,
+        hash,
+        trim,
+        resample,
+        variants,
+        blur_backdrop,
+        ico_pack,
+        scale,
+        timeout,
+        shared_memory: false,
+    };
+    options.validate()?;
+    Ok(match (archive, steam_art) {
+        (Some(file_path), _) => Command::Archive { file_path, extract: Some(options) },
+        (None, Some(app_id)) => Command::SteamArt { app_id, art: art.unwrap_or(Art::Capsule), download, extract: options },
+        (None, None) => Command::Extract(options),
+    })
+}
+
+fn parse_sizes(value: &str) -> Result<Vec<u32>, String> {
+    let sizes: Vec<u32> = value.split(',').map(|size| parse_size(size.trim())).collect::<Result<_, _>>()?;
+    Ok(normalize_sizes(sizes))
+}
+
+/// The format an extract or job that names none is written in: the config's, else PNG.
+pub fn default_format() -> Result<OutputFormat, String> {
+    match &config::get().icon.format {
+        Some(format) => OutputFormat::parse(format).map_err(|error| format!("{} (icon.format in the config)", error)),
+        None => Ok(OutputFormat::Png),
+    }
+}
+
+/// The config's sizes, for an extract or job that gives no size.
+pub fn default_sizes() -> Option<Vec<u32>> {
+    config::get().icon.sizes.clone().map(normalize_sizes)
+}
+
+pub fn default_cache_dir() -> Option<String> {
+    config::get().icon.cache_dir.clone()
+}
+
+/// 30 seconds unless the config sets another, which may be 0 for no limit.
+pub fn default_timeout() -> Option<Duration> {
+    timeout_from_millis(config::get().timeouts.extract.unwrap_or(DEFAULT_TIMEOUT_MS))
+}
+
+pub fn timeout_from_millis(milliseconds: u64) -> Option<Duration> {
+    (milliseconds > 0).then(|| Duration::from_millis(milliseconds))
+}
+
+fn parse_timeout(value: &str) -> Result<Option<Duration>, String> {
+    value.parse().map(timeout_from_millis).map_err(|_| format!("Invalid timeout: {} (expected milliseconds)", value))
+}
+
+/// Sorts smallest first, so the last size is the one to extract at.
+pub fn normalize_sizes(mut sizes: Vec<u32>) -> Vec<u32> {
+    sizes.sort_unstable();
+    sizes.dedup();
+    sizes
+}
+
+fn parse_size(value: &str) -> Result<u32, String> {
+    match value.parse() {
+        // file_icon_provider takes the size as a u16
+        Ok(size @ 1..=65535) => Ok(size),
+        _ => Err(format!("Invalid image size: {}", value)),
+    }
+}
+
+// A factor such as 1.5, a percentage such as 150%, "auto" for the primary monitor
+// or "cursor" for the one under the mouse cursor
+fn parse_scale(value: &str) -> Result<f32, String> {
+    if value.eq_ignore_ascii_case("auto") || value.eq_ignore_ascii_case("cursor") {
+        #[cfg(windows)]
+        return Ok(if value.eq_ignore_ascii_case("auto") {
+            crate::dpi::primary_monitor_scale()
+        } else {
+            crate::dpi::cursor_monitor_scale()
+        });
+        #[cfg(not(windows))]
+        return Err(format!("--scale {} is only supported on Windows", value));
+    }
+    let parsed = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().map(|percent| percent / 100.0),
+        None => value.parse::<f32>(),
+    };
+    parsed.map_err(|_| format!("Invalid scale: {}", value))
+}
+
+fn parse_percent(flag: &str, value: &str) -> Result<u32, String> {
+    value
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("Invalid {} value: {} (expected a percentage)", flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        let args: Vec<String> = ["icon"].iter().chain(args).map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn no_shortcut_arrow_rejects_no_follow_lnk() {
+        let error = parse(&["app.lnk", "app.png", "64", "--no-shortcut-arrow", "--no-follow-lnk"]).err();
+        assert_eq!(error.as_deref(), Some("--no-shortcut-arrow cannot be combined with --no-follow-lnk"));
+        assert!(parse(&["app.lnk", "app.png", "64", "--no-shortcut-arrow"]).is_ok());
+    }
+}
@@ -0,0 +1,221 @@
+use std::mem::size_of;
+use std::os::windows::fs::MetadataExt;
+use altdesktop_core::sandbox;
+use anyhow::{bail, Result};
+use serde::Serialize;
+use windows::{
+    core::{Interface, HSTRING, PCWSTR, PWSTR},
+    Win32::Foundation::S_OK,
+    Win32::Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES,
+    Win32::System::Com::{CLSIDFromString, CoCreateInstance, CLSCTX_INPROC_SERVER},
+    Win32::System::Registry::*,
+    Win32::UI::Shell::*,
+    Win32::UI::WindowsAndMessaging::DestroyIcon,
+};
+
+const IDENTIFIERS_KEY: &str = r"SOFTWARE\Microsoft\Windows\CurrentVersion\Explorer\ShellIconOverlayIdentifiers";
+
+// GetOverlayInfo flags saying which out-parameters were filled in
+const ISIOI_ICONFILE: u32 = 1;
+const ISIOI_ICONINDEX: u32 = 2;
+
+// Attribute bits std doesn't name. Cloud providers such as OneDrive use them for
+// "always keep on this device", "free up space" and online-only files.
+const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+const FILE_ATTRIBUTE_PINNED: u32 = 0x8_0000;
+const FILE_ATTRIBUTE_UNPINNED: u32 = 0x10_0000;
+const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x40_0000;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlayState {
+    /// Position of the overlay Explorer draws in the system image list's overlays,
+    /// if it draws one.
+    pub overlay_index: Option<i32>,
+    /// What that overlay is: "link", "share", "slowFile", or the name of the
+    /// handler key under ShellIconOverlayIdentifiers (e.g. " OneDrive1").
+    pub overlay: Option<String>,
+    /// Every registered overlay handler that claims the path, in the order Explorer
+    /// consults them. Explorer only draws one, and only loads the first 15 handlers.
+    pub handlers: Vec<OverlayHandler>,
+    pub attributes: Attributes,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlayHandler {
+    pub name: String,
+    pub clsid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_index: Option<i32>,
+    /// 0 is the highest; breaks ties when several handlers claim a path.
+    pub priority: i32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attributes {
+    pub read_only: bool,
+    pub hidden: bool,
+    pub system: bool,
+    pub offline: bool,
+    /// Cloud file kept on this device.
+    pub pinned: bool,
+    /// Cloud file whose local copy may be freed.
+    pub unpinned: bool,
+    /// Cloud file that is only online and downloads when opened.
+    pub online_only: bool,
+}
+
+/// Reports which overlay Explorer would draw on the path's icon, and why.
+/// Needs COM initialized on the calling thread.
+pub fn overlay_state(file_path: &str) -> Result<OverlayState> {
+    sandbox::check_input(file_path)?;
+    let metadata = std::fs::metadata(file_path)?;
+    let path = HSTRING::from(file_path);
+
+    let mut info = SHFILEINFOW::default();
+    let found = unsafe {
+        SHGetFileInfoW(
+            &path,
+            FILE_FLAGS_AND_ATTRIBUTES(0),
+            Some(&mut info),
+            size_of::<SHFILEINFOW>() as u32,
+            SHGFI_ICON | SHGFI_OVERLAYINDEX | SHGFI_ATTRIBUTES,
+        )
+    };
+    if found == 0 {
+        bail!("The shell has no icon for {}", file_path);
+    }
+    if !info.hIcon.is_invalid() {
+        unsafe {
+            let _ = DestroyIcon(info.hIcon);
+        }
+    }
+    // The overlay is packed into the top byte of the icon index, 0 meaning none
+    let overlay_index = Some((info.iIcon >> 24) & 0xff).filter(|&index| index != 0);
+
+    let handlers = overlay_handlers(&path, info.dwAttributes);
+    let overlay = overlay_index.and_then(|overlay_index| {
+        let standard = [("link", IDO_SHGIOI_LINK as i32), ("share", IDO_SHGIOI_SHARE as i32), ("slowFile", IDO_SHGIOI_SLOWFILE as i32)];
+        standard
+            .iter()
+            .find(|(_, id)| unsafe { SHGetIconOverlayIndexW(PCWSTR::null(), *id) } == overlay_index)
+            .map(|(name, _)| name.to_string())
+            .or_else(|| {
+                handlers.iter().find_map(|(handler, index)| (*index == Some(overlay_index)).then(|| handler.name.clone()))
+            })
+    });
+
+    let attributes = metadata.file_attributes();
+    let has = |flag: u32| attributes & flag != 0;
+    Ok(OverlayState {
+        overlay_index,
+        overlay,
+        handlers: handlers.into_iter().map(|(handler, _)| handler).collect(),
+        attributes: Attributes {
+            read_only: has(FILE_ATTRIBUTE_READONLY),
+            hidden: has(FILE_ATTRIBUTE_HIDDEN),
+            system: has(FILE_ATTRIBUTE_SYSTEM),
+            offline: has(FILE_ATTRIBUTE_OFFLINE),
+            pinned: has(FILE_ATTRIBUTE_PINNED),
+            unpinned: has(FILE_ATTRIBUTE_UNPINNED),
+            online_only: has(FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS),
+        },
+    })
+}
+
+// The handlers that claim the path, each with the overlay index its icon was given
+fn overlay_handlers(path: &HSTRING, attributes: u32) -> Vec<(OverlayHandler, Option<i32>)> {
+    let mut handlers = Vec::new();
+    for (name, clsid) in registered_identifiers() {
+        let Ok(guid) = (unsafe { CLSIDFromString(&HSTRING::from(clsid.as_str())) }) else {
+            continue;
+        };
+        let Ok(identifier) = (unsafe { CoCreateInstance::<_, IShellIconOverlayIdentifier>(&guid, None, CLSCTX_INPROC_SERVER) }) else {
+            continue;
+        };
+        // S_FALSE means "not mine", which the generated wrapper would report as success
+        let claimed = unsafe {
+            (Interface::vtable(&identifier).IsMemberOf)(Interface::as_raw(&identifier), PCWSTR(path.as_ptr()), attributes)
+        };
+        if claimed != S_OK {
+            continue;
+        }
+
+        let mut icon_file = [0u16; 260];
+        let (mut icon_index, mut flags) = (0, 0);
+        let has_info = unsafe {
+            (Interface::vtable(&identifier).GetOverlayInfo)(
+                Interface::as_raw(&identifier),
+                PWSTR(icon_file.as_mut_ptr()),
+                icon_file.len() as i32,
+                &mut icon_index,
+                &mut flags,
+            )
+        }
+        .is_ok();
+        let icon_file = (has_info && flags & ISIOI_ICONFILE != 0).then(|| {
+            let length = icon_file.iter().position(|&c| c == 0).unwrap_or(icon_file.len());
+            String::from_utf16_lossy(&icon_file[..length])
+        });
+        let icon_index = (has_info && flags & ISIOI_ICONINDEX != 0).then_some(icon_index);
+        let overlay_index = icon_file.as_ref().and_then(|file| {
+            let index = unsafe { SHGetIconOverlayIndexW(&HSTRING::from(file.as_str()), icon_index.unwrap_or(0)) };
+            (index >= 0).then_some(index)
+        });
+        let priority = unsafe { identifier.GetPriority() }.unwrap_or(100);
+
+        handlers.push((OverlayHandler { name, clsid, icon_file, icon_index, priority }, overlay_index));
+    }
+    handlers
+}
+
+// Key names and CLSIDs, in the alphabetical order Explorer loads them in. Vendors
+// pad names with leading spaces to sort first.
+fn registered_identifiers() -> Vec<(String, String)> {
+    let mut identifiers = Vec::new();
+    let mut key = HKEY::default();
+    if unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, &HSTRING::from(IDENTIFIERS_KEY), 0, KEY_READ, &mut key) }.is_err() {
+        return identifiers;
+    }
+    for index in 0.. {
+        let mut name = [0u16; 256];
+        let mut length = name.len() as u32;
+        let enumerated =
+            unsafe { RegEnumKeyExW(key, index, PWSTR(name.as_mut_ptr()), &mut length, None, PWSTR::null(), None, None) };
+        if enumerated.is_err() {
+            break;
+        }
+        let mut clsid = [0u16; 64];
+        let mut size = size_of::<[u16; 64]>() as u32;
+        let read = unsafe {
+            RegGetValueW(
+                key,
+                PCWSTR(name.as_ptr()),
+                PCWSTR::null(),
+                RRF_RT_REG_SZ,
+                None,
+                Some(clsid.as_mut_ptr().cast()),
+                Some(&mut size),
+            )
+        };
+        if read.is_ok() {
+            let clsid_length = clsid.iter().position(|&c| c == 0).unwrap_or(clsid.len());
+            identifiers.push((
+                String::from_utf16_lossy(&name[..length as usize]),
+                String::from_utf16_lossy(&clsid[..clsid_length]),
+            ));
+        }
+    }
+    unsafe {
+        let _ = RegCloseKey(key);
+    }
+    identifiers.sort();
+    identifiers
+}
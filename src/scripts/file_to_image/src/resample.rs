@@ -0,0 +1,72 @@
+use image::{imageops::FilterType, DynamicImage};
+
+// Differences below this stay unsharpened, so flat areas don't pick up noise
+const SHARPEN_THRESHOLD: i32 = 2;
+
+/// How images are scaled to each output size.
+#[derive(Clone, Copy)]
+pub struct Resample {
+    pub filter: FilterType,
+    /// Sigma of an unsharp mask applied after scaling.
+    pub sharpen: Option<f32>,
+}
+
+impl Default for Resample {
+    fn default() -> Self {
+        Resample { filter: FilterType::CatmullRom, sharpen: None }
+    }
+}
+
+impl Resample {
+    pub fn parse_filter(value: &str) -> Result<FilterType, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "nearest" => Ok(FilterType::Nearest),
+            "triangle" | "bilinear" => Ok(FilterType::Triangle),
+            "catmull-rom" | "bicubic" => Ok(FilterType::CatmullRom),
+            "gaussian" => Ok(FilterType::Gaussian),
+            "lanczos3" => Ok(FilterType::Lanczos3),
+            _ => Err(format!(
+                "Invalid filter: {} (expected nearest, triangle, catmull-rom, gaussian or lanczos3)",
+                value
+            )),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self.sharpen {
+            Some(sigma) if !(sigma > 0.0 && sigma.is_finite()) => {
+                Err(format!("Invalid sharpen amount: {} (expected a positive sigma, e.g. 0.8)", sigma))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Scales to fit within `width`x`height`, keeping the aspect ratio.
+    pub fn resize(&self, img: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+        self.sharpen(img.resize(width, height, self.filter))
+    }
+
+    pub fn resize_exact(&self, img: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+        self.sharpen(img.resize_exact(width, height, self.filter))
+    }
+
+    /// Bytes that identify these settings in cache keys.
+    pub fn key(&self) -> [u8; 5] {
+        let filter = match self.filter {
+            FilterType::Nearest => 0,
+            FilterType::Triangle => 1,
+            FilterType::CatmullRom => 2,
+            FilterType::Gaussian => 3,
+            FilterType::Lanczos3 => 4,
+        };
+        let [a, b, c, d] = self.sharpen.unwrap_or(0.0).to_le_bytes();
+        [filter, a, b, c, d]
+    }
+
+    fn sharpen(&self, img: DynamicImage) -> DynamicImage {
+        match self.sharpen {
+            Some(sigma) => img.unsharpen(sigma, SHARPEN_THRESHOLD),
+            None => img,
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use image::RgbaImage;
+use windows::{
+    core::HSTRING,
+    Win32::Foundation::MAX_PATH,
+    Win32::UI::Shell::ExtractIconExW,
+    Win32::UI::WindowsAndMessaging::{DestroyIcon, PrivateExtractIconsW, HICON, LR_DEFAULTCOLOR},
+};
+
+use crate::error::{failure, ErrorCode};
+use crate::hicon::icon_to_image;
+
+/// Extracts one icon from an .exe, .dll or .ico, numbered like the "Change Icon"
+/// dialog. Negative indices select the icon group whose resource ID is `-index`.
+pub fn extract_resource_icon(file_path: &str, index: i32, size: u32) -> Result<RgbaImage> {
+    let icon = private_extract_icon(file_path, index, size).or_else(|| extract_icon_ex(file_path, index));
+    let Some(icon) = icon else {
+        return Err(failure(ErrorCode::NotFound, format!("No icon at index {} in {}", index, file_path)));
+    };
+    let image = icon_to_image(icon);
+    unsafe {
+        let _ = DestroyIcon(icon);
+    }
+    image
+}
+
+// PrivateExtractIcons picks the frame closest to the requested size instead of
+// always returning 32px
+fn private_extract_icon(file_path: &str, index: i32, size: u32) -> Option<HICON> {
+    let wide: Vec<u16> = file_path.encode_utf16().collect();
+    if wide.len() >= MAX_PATH as usize {
+        return None;
+    }
+    let mut file_name = [0u16; MAX_PATH as usize];
+    file_name[..wide.len()].copy_from_slice(&wide);
+
+    let mut icons = [HICON::default()];
+    let extracted = unsafe {
+        PrivateExtractIconsW(&file_name, index, size as i32, size as i32, Some(&mut icons), None, LR_DEFAULTCOLOR.0)
+    };
+    // u32::MAX means the file couldn't be opened
+    (extracted != 0 && extracted != u32::MAX && !icons[0].is_invalid()).then_some(icons[0])
+}
+
+fn extract_icon_ex(file_path: &str, index: i32) -> Option<HICON> {
+    let mut icon = HICON::default();
+    let extracted = unsafe { ExtractIconExW(&HSTRING::from(file_path), index, Some(&mut icon), None, 1) };
+    (extracted != 0 && extracted != u32::MAX && !icon.is_invalid()).then_some(icon)
+}
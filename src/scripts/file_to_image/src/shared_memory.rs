@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use anyhow::Result;
+use windows::{
+    core::HSTRING,
+    Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE},
+    Win32::System::Memory::{CreateFileMappingW, MapViewOfFile, UnmapViewOfFile, FILE_MAP_WRITE, PAGE_READWRITE},
+};
+
+// A section lasts as long as a handle to it is open, so the helper holds one for
+// every section until the host releases it or the helper exits
+static SECTIONS: Mutex<Option<HashMap<String, isize>>> = Mutex::new(None);
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// Copies the bytes into a new section named Local\altdesktop-helper-<pid>-<n>, and
+/// returns its name.
+pub fn share(bytes: &[u8]) -> Result<String> {
+    let name = format!(r"Local\altdesktop-helper-{}-{}", process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+    // A section can't be empty
+    let length = bytes.len().max(1);
+    unsafe {
+        let section = CreateFileMappingW(
+            INVALID_HANDLE_VALUE,
+            None,
+            PAGE_READWRITE,
+            (length as u64 >> 32) as u32,
+            length as u32,
+            &HSTRING::from(name.as_str()),
+        )?;
+        let view = MapViewOfFile(section, FILE_MAP_WRITE, 0, 0, length);
+        if view.Value.is_null() {
+            let error = windows::core::Error::from_win32();
+            let _ = CloseHandle(section);
+            return Err(error.into());
+        }
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), view.Value as *mut u8, bytes.len());
+        let _ = UnmapViewOfFile(view);
+        SECTIONS.lock().unwrap().get_or_insert_with(HashMap::new).insert(name.clone(), section.0);
+    }
+    Ok(name)
+}
+
+/// Closes the helper's handle to a section, which frees it once the host has closed
+/// its own. False for a name the helper doesn't hold.
+pub fn release(name: &str) -> bool {
+    let section = SECTIONS.lock().unwrap().as_mut().and_then(|sections| sections.remove(name));
+    if let Some(section) = section {
+        unsafe {
+            let _ = CloseHandle(HANDLE(section));
+        }
+    }
+    section.is_some()
+}
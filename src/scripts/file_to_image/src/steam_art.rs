@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use altdesktop_core::sandbox;
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::archive::ScratchDir;
+use crate::colors::Colors;
+use crate::error::{failure, ErrorCode};
+use crate::extract::{extract, Output};
+use crate::favicon::{download, web_agent};
+use crate::hash::Hashes;
+use crate::options::Options;
+
+// Where Steam's store and library get their art from
+const CDN: &str = "https://cdn.cloudflare.steamstatic.com/steam/apps";
+const MAX_ART_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(Clone, Copy)]
+pub enum Art {
+    /// The 600x900 portrait cover of the library's grid view.
+    Capsule,
+    /// The wide banner behind a game's library page.
+    Hero,
+    /// The 460x215 store header.
+    Header,
+    /// The game's logo, transparent, drawn over the hero.
+    Logo,
+}
+
+impl Art {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "capsule" => Ok(Art::Capsule),
+            "hero" => Ok(Art::Hero),
+            "header" => Ok(Art::Header),
+            "logo" => Ok(Art::Logo),
+            _ => Err(format!("Invalid art: {} (expected capsule, hero, header or logo)", value)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Art::Capsule => "capsule",
+            Art::Hero => "hero",
+            Art::Header => "header",
+            Art::Logo => "logo",
+        }
+    }
+
+    // Largest first; the library and the CDN use the same names
+    fn file_names(self) -> &'static [&'static str] {
+        match self {
+            Art::Capsule => &["library_600x900_2x.jpg", "library_600x900.jpg"],
+            Art::Hero => &["library_hero_2x.jpg", "library_hero.jpg"],
+            Art::Header => &["header.jpg"],
+            Art::Logo => &["logo_2x.png", "logo.png"],
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SteamArt {
+    pub app_id: String,
+    pub art: &'static str,
+    /// The cached file or the URL the art came from.
+    pub source: String,
+    pub downloaded: bool,
+    pub outputs: Vec<Output>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colors: Option<Colors>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<Hashes>,
+}
+
+/// Writes the art Steam's library shows for `app_id` through the usual resize and
+/// compose steps, from Steam's own cache or, with `download`, from its CDN.
+pub fn steam_art(app_id: &str, art: Art, download_missing: bool, mut options: Options) -> Result<SteamArt> {
+    if app_id.is_empty() || !app_id.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(failure(ErrorCode::InvalidArguments, format!("Invalid Steam appid: {}", app_id)));
+    }
+    let _scratch;
+    let (source, file_path, downloaded) = match cached_art(app_id, art) {
+        Some(path) => (path.to_string_lossy().into_owned(), path, false),
+        None if download_missing => {
+            // Kept in --cache-dir for next time, or only for this run
+            let folder = match &options.cache_dir {
+                Some(cache_dir) => {
+                    sandbox::check_output(Path::new(cache_dir))?;
+                    PathBuf::from(cache_dir)
+                }
+                None => {
+                    _scratch = ScratchDir::new()?;
+                    _scratch.0.clone()
+                }
+            };
+            let (url, path) = download_art(app_id, art, &folder)?;
+            (url, path, true)
+        }
+        None => {
+            return Err(failure(
+                ErrorCode::NotFound,
+                format!("Steam has no {} cached for app {}; --download fetches it", art.name(), app_id),
+            ))
+        }
+    };
+
+    options.file_path = file_path.to_string_lossy().into_owned();
+    let extraction = extract(&options)?;
+    Ok(SteamArt {
+        app_id: app_id.to_string(),
+        art: art.name(),
+        source,
+        downloaded,
+        outputs: extraction.outputs,
+        colors: extraction.colors,
+        hashes: extraction.hashes,
+    })
+}
+
+// Clients before 2024 kept <appid>_<name> in librarycache; newer ones a folder per
+// app, with some art a folder further down
+fn cached_art(app_id: &str, art: Art) -> Option<PathBuf> {
+    let cache = steam_path()?.join("appcache").join("librarycache");
+    let folder = cache.join(app_id);
+    let subfolders: Vec<PathBuf> = fs::read_dir(&folder)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).filter(|path| path.is_dir()).collect())
+        .unwrap_or_default();
+    art.file_names().iter().find_map(|name| {
+        std::iter::once(cache.join(format!("{}_{}", app_id, name)))
+            .chain(std::iter::once(folder.join(name)))
+            .chain(subfolders.iter().map(|subfolder| subfolder.join(name)))
+            .find(|path| path.is_file())
+    })
+}
+
+fn download_art(app_id: &str, art: Art, folder: &Path) -> Result<(String, PathBuf)> {
+    let url = |name: &str| format!("{}/{}/{}", CDN, app_id, name);
+    let downloaded = |name: &str| folder.join(format!("steam-{}-{}", app_id, name));
+    // Fetched on an earlier run with the same --cache-dir
+    if let Some(name) = art.file_names().iter().find(|name| downloaded(name).is_file()) {
+        return Ok((url(name), downloaded(name)));
+    }
+    let agent = web_agent()?;
+    for name in art.file_names() {
+        // Older games have no 2x art, and some no library art at all
+        let Ok(bytes) = download(&agent, &url(name), MAX_ART_BYTES) else {
+            continue;
+        };
+        fs::create_dir_all(folder)?;
+        fs::write(downloaded(name), bytes)?;
+        return Ok((url(name), downloaded(name)));
+    }
+    Err(failure(ErrorCode::NotFound, format!("Steam has no {} for app {}", art.name(), app_id)))
+}
+
+/// Where Steam is installed, as its client last recorded it.
+#[cfg(windows)]
+fn steam_path() -> Option<PathBuf> {
+    use windows::{core::*, Win32::System::Registry::*};
+
+    let mut buffer = [0u16; 1024];
+    let mut size = std::mem::size_of_val(&buffer) as u32;
+    unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            w!(r"Software\Valve\Steam"),
+            w!("SteamPath"),
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr().cast()),
+            Some(&mut size),
+        )
+        .ok()?;
+    }
+    let length = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    // SteamPath is written with forward slashes
+    let path = PathBuf::from(String::from_utf16_lossy(&buffer[..length]).replace('/', "\\"));
+    path.is_dir().then_some(path)
+}
+
+#[cfg(not(windows))]
+fn steam_path() -> Option<PathBuf> {
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    [".local/share/Steam", ".steam/steam", "Library/Application Support/Steam"]
+        .iter()
+        .map(|folder| home.join(folder))
+        .find(|path| path.is_dir())
+}
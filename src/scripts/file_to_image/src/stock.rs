@@ -0,0 +1,109 @@
+use std::mem;
+use anyhow::Result;
+use image::RgbaImage;
+use windows::{
+    core::HSTRING,
+    Win32::Storage::FileSystem::{FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_NORMAL},
+    Win32::UI::Controls::{IImageList, ILD_TRANSPARENT},
+    // For the stock icon IDs, all of which are named below
+    Win32::UI::Shell::*,
+    Win32::UI::WindowsAndMessaging::DestroyIcon,
+};
+
+use crate::error::{failure, ErrorCode};
+use crate::hicon::icon_to_image;
+
+/// Every stock icon by its SHSTOCKICONID name less SIID_, in lowercase, in SIID order.
+const STOCK_ICONS: &[(&str, SHSTOCKICONID)] = &[
+    ("docnoassoc", SIID_DOCNOASSOC), ("docassoc", SIID_DOCASSOC), ("application", SIID_APPLICATION),
+    ("folder", SIID_FOLDER), ("folderopen", SIID_FOLDEROPEN), ("drive525", SIID_DRIVE525),
+    ("drive35", SIID_DRIVE35), ("driveremove", SIID_DRIVEREMOVE), ("drivefixed", SIID_DRIVEFIXED),
+    ("drivenet", SIID_DRIVENET), ("drivenetdisabled", SIID_DRIVENETDISABLED), ("drivecd", SIID_DRIVECD),
+    ("driveram", SIID_DRIVERAM), ("world", SIID_WORLD), ("server", SIID_SERVER), ("printer", SIID_PRINTER),
+    ("mynetwork", SIID_MYNETWORK), ("find", SIID_FIND), ("help", SIID_HELP), ("share", SIID_SHARE),
+    ("link", SIID_LINK), ("slowfile", SIID_SLOWFILE), ("recycler", SIID_RECYCLER),
+    ("recyclerfull", SIID_RECYCLERFULL), ("mediacdaudio", SIID_MEDIACDAUDIO), ("lock", SIID_LOCK),
+    ("autolist", SIID_AUTOLIST), ("printernet", SIID_PRINTERNET), ("servershare", SIID_SERVERSHARE),
+    ("printerfax", SIID_PRINTERFAX), ("printerfaxnet", SIID_PRINTERFAXNET), ("printerfile", SIID_PRINTERFILE),
+    ("stack", SIID_STACK), ("mediasvcd", SIID_MEDIASVCD), ("stuffedfolder", SIID_STUFFEDFOLDER),
+    ("driveunknown", SIID_DRIVEUNKNOWN), ("drivedvd", SIID_DRIVEDVD), ("mediadvd", SIID_MEDIADVD),
+    ("mediadvdram", SIID_MEDIADVDRAM), ("mediadvdrw", SIID_MEDIADVDRW), ("mediadvdr", SIID_MEDIADVDR),
+    ("mediadvdrom", SIID_MEDIADVDROM), ("mediacdaudioplus", SIID_MEDIACDAUDIOPLUS), ("mediacdrw", SIID_MEDIACDRW),
+    ("mediacdr", SIID_MEDIACDR), ("mediacdburn", SIID_MEDIACDBURN), ("mediablankcd", SIID_MEDIABLANKCD),
+    ("mediacdrom", SIID_MEDIACDROM), ("audiofiles", SIID_AUDIOFILES), ("imagefiles", SIID_IMAGEFILES),
+    ("videofiles", SIID_VIDEOFILES), ("mixedfiles", SIID_MIXEDFILES), ("folderback", SIID_FOLDERBACK),
+    ("folderfront", SIID_FOLDERFRONT), ("shield", SIID_SHIELD), ("warning", SIID_WARNING), ("info", SIID_INFO),
+    ("error", SIID_ERROR), ("key", SIID_KEY), ("software", SIID_SOFTWARE), ("rename", SIID_RENAME),
+    ("delete", SIID_DELETE), ("mediaaudiodvd", SIID_MEDIAAUDIODVD), ("mediamoviedvd", SIID_MEDIAMOVIEDVD),
+    ("mediaenhancedcd", SIID_MEDIAENHANCEDCD), ("mediaenhanceddvd", SIID_MEDIAENHANCEDDVD),
+    ("mediahddvd", SIID_MEDIAHDDVD), ("mediabluray", SIID_MEDIABLURAY), ("mediavcd", SIID_MEDIAVCD),
+    ("mediadvdplusr", SIID_MEDIADVDPLUSR), ("mediadvdplusrw", SIID_MEDIADVDPLUSRW), ("desktoppc", SIID_DESKTOPPC),
+    ("mobilepc", SIID_MOBILEPC), ("users", SIID_USERS), ("mediasmartmedia", SIID_MEDIASMARTMEDIA),
+    ("mediacompactflash", SIID_MEDIACOMPACTFLASH), ("devicecellphone", SIID_DEVICECELLPHONE),
+    ("devicecamera", SIID_DEVICECAMERA), ("devicevideocamera", SIID_DEVICEVIDEOCAMERA),
+    ("deviceaudioplayer", SIID_DEVICEAUDIOPLAYER), ("networkconnect", SIID_NETWORKCONNECT),
+    ("internet", SIID_INTERNET), ("zipfile", SIID_ZIPFILE), ("settings", SIID_SETTINGS),
+    ("drivehddvd", SIID_DRIVEHDDVD), ("drivebd", SIID_DRIVEBD), ("mediahddvdrom", SIID_MEDIAHDDVDROM),
+    ("mediahddvdr", SIID_MEDIAHDDVDR), ("mediahddvdram", SIID_MEDIAHDDVDRAM), ("mediabdrom", SIID_MEDIABDROM),
+    ("mediabdr", SIID_MEDIABDR), ("mediabdre", SIID_MEDIABDRE), ("clustereddrive", SIID_CLUSTEREDDRIVE),
+];
+
+/// Draws one of the shell's stock icons, such as SIID_FOLDER or SIID_SHIELD, from
+/// the smallest system image list that holds `size`, so it matches what Explorer
+/// shows for the same thing.
+pub fn extract_stock_icon(id: SHSTOCKICONID, size: u32) -> Result<RgbaImage> {
+    let mut info = SHSTOCKICONINFO { cbSize: mem::size_of::<SHSTOCKICONINFO>() as u32, ..Default::default() };
+    unsafe { SHGetStockIconInfo(id, SHGSI_SYSICONINDEX, &mut info)? };
+    system_image(info.iSysImageIndex, size)
+}
+
+/// Reads a stock icon's name, such as `shield` or `SIID_DRIVEFIXED`, or its number.
+pub fn parse_stock_id(name: &str) -> Result<SHSTOCKICONID> {
+    let bare = name.trim();
+    let bare = bare.get(..5).filter(|prefix| prefix.eq_ignore_ascii_case("SIID_")).map_or(bare, |_| &bare[5..]);
+    if let Ok(number) = bare.parse::<i32>() {
+        if (0..SIID_MAX_ICONS.0).contains(&number) {
+            return Ok(SHSTOCKICONID(number));
+        }
+    }
+    match STOCK_ICONS.iter().find(|(known, _)| known.eq_ignore_ascii_case(bare)) {
+        Some((_, id)) => Ok(*id),
+        None => Err(failure(ErrorCode::InvalidArguments, format!("Unknown stock icon: {}", name))),
+    }
+}
+
+/// The icon registered for a file's type, found by its name alone, so it works for
+/// a file that's missing or can't be read.
+pub fn extract_type_icon(file_path: &str, directory: bool, size: u32) -> Result<RgbaImage> {
+    let attributes = if directory { FILE_ATTRIBUTE_DIRECTORY } else { FILE_ATTRIBUTE_NORMAL };
+    let mut info = SHFILEINFOW::default();
+    let found = unsafe {
+        SHGetFileInfoW(
+            &HSTRING::from(file_path),
+            attributes,
+            Some(&mut info),
+            mem::size_of::<SHFILEINFOW>() as u32,
+            SHGFI_SYSICONINDEX | SHGFI_USEFILEATTRIBUTES,
+        )
+    };
+    if found == 0 {
+        return Err(failure(ErrorCode::NotFound, format!("No icon is registered for {}", file_path)));
+    }
+    system_image(info.iIcon, size)
+}
+
+fn system_image(index: i32, size: u32) -> Result<RgbaImage> {
+    let list = match size {
+        0..=16 => SHIL_SMALL,
+        17..=32 => SHIL_LARGE,
+        33..=48 => SHIL_EXTRALARGE,
+        _ => SHIL_JUMBO,
+    };
+    unsafe {
+        let image_list: IImageList = SHGetImageList(list as i32)?;
+        let icon = image_list.GetIcon(index, ILD_TRANSPARENT.0)?;
+        let image = icon_to_image(icon);
+        let _ = DestroyIcon(icon);
+        image
+    }
+}
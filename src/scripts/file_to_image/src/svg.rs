@@ -0,0 +1,118 @@
+use std::fmt::Write as _;
+use std::io::Cursor;
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+
+use crate::badge::Badge;
+use crate::compose::{Composite, Fill};
+use crate::extract::{fit, ImageKind};
+use crate::resample::Resample;
+
+/// Wraps the image as an embedded PNG in an SVG of the same size.
+pub fn wrap_image(img: &DynamicImage) -> Result<Vec<u8>> {
+    let (width, height) = (img.width(), img.height());
+    let mut svg = open_svg(width, height);
+    push_image(&mut svg, &img.to_rgba8(), 0, 0)?;
+    svg.push_str("</svg>\n");
+    Ok(svg.into_bytes())
+}
+
+/// Renders a tile as SVG. The fill and rounded corners are vector elements, so the
+/// frontend can scale the tile without requesting a new size; only the icon and
+/// badge are rasters.
+pub fn render_tile(
+    img: &DynamicImage,
+    kind: ImageKind,
+    size: u32,
+    composite: &Composite,
+    badge: Option<(&Badge, &RgbaImage)>,
+    resample: &Resample,
+) -> Result<Vec<u8>> {
+    let (content, width, height) = if composite.is_active() {
+        (composite.content(img, size, resample), size, size)
+    } else {
+        let content = fit(img, kind, size, resample).to_rgba8();
+        let (width, height) = content.dimensions();
+        (content, width, height)
+    };
+
+    let mut svg = open_svg(width, height);
+    let radius = composite.corner_radius_px(size);
+    if let Some(Fill::Gradient(top, bottom)) = composite.fill {
+        let _ = write!(
+            svg,
+            "<defs><linearGradient id=\"fill\" x1=\"0\" y1=\"0\" x2=\"0\" y2=\"1\">\
+             <stop offset=\"0\" stop-color=\"{}\"/><stop offset=\"1\" stop-color=\"{}\"/></linearGradient></defs>",
+            hex(top.0),
+            hex(bottom.0)
+        );
+    }
+    if radius > 0.0 {
+        let _ = write!(
+            svg,
+            "<clipPath id=\"tile\"><rect width=\"{}\" height=\"{}\" rx=\"{}\"/></clipPath><g clip-path=\"url(#tile)\">",
+            width, height, radius
+        );
+    }
+    match composite.fill {
+        Some(Fill::Solid(color)) => {
+            let _ = write!(svg, "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>", width, height, hex(color.0));
+        }
+        Some(Fill::Gradient(..)) => {
+            let _ = write!(svg, "<rect width=\"{}\" height=\"{}\" fill=\"url(#fill)\"/>", width, height);
+        }
+        None => {}
+    }
+    push_image(&mut svg, &content, (width - content.width()) / 2, (height - content.height()) / 2)?;
+    if radius > 0.0 {
+        svg.push_str("</g>");
+    }
+
+    // The badge sits on top of the clip, like it does in raster output
+    if let Some((badge, badge_img)) = badge {
+        let (x, y, badge_size) = badge.placement(width, height);
+        let scaled = resample.resize(&DynamicImage::ImageRgba8(badge_img.clone()), badge_size, badge_size).to_rgba8();
+        let _ = write!(
+            svg,
+            "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" href=\"data:image/png;base64,{}\"/>",
+            x,
+            y,
+            badge_size,
+            badge_size,
+            png_base64(&scaled)?
+        );
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg.into_bytes())
+}
+
+fn open_svg(width: u32, height: u32) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">",
+        width, height
+    )
+}
+
+fn push_image(svg: &mut String, img: &RgbaImage, x: u32, y: u32) -> Result<()> {
+    let _ = write!(
+        svg,
+        "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" href=\"data:image/png;base64,{}\"/>",
+        x,
+        y,
+        img.width(),
+        img.height(),
+        png_base64(img)?
+    );
+    Ok(())
+}
+
+fn png_base64(img: &RgbaImage) -> Result<String> {
+    let mut png = Cursor::new(Vec::new());
+    img.write_to(&mut png, ImageFormat::Png)?;
+    Ok(STANDARD.encode(png.into_inner()))
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
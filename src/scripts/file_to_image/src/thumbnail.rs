@@ -0,0 +1,62 @@
+use anyhow::Result;
+use image::RgbaImage;
+use windows::{
+    core::HSTRING,
+    Win32::Foundation::SIZE,
+    Win32::Graphics::Gdi::{DeleteObject, HBITMAP},
+    Win32::UI::Shell::*,
+};
+
+use crate::hicon::bitmap_bgra;
+use crate::extract::ImageKind;
+
+/// Asks the shell for a preview of the file's contents (pictures, videos, PDFs,
+/// Office documents...) that fits in `size`. Files without a thumbnail handler get
+/// their type icon instead, which the returned kind reports.
+pub fn extract_thumbnail(file_path: &str, size: u32) -> Result<(RgbaImage, ImageKind)> {
+    let factory: IShellItemImageFactory = unsafe { SHCreateItemFromParsingName(&HSTRING::from(file_path), None)? };
+    let size = SIZE { cx: size as i32, cy: size as i32 };
+
+    // THUMBNAILONLY fails instead of silently handing back the icon
+    let (bitmap, kind) = match unsafe { factory.GetImage(size, SIIGBF_THUMBNAILONLY) } {
+        Ok(bitmap) => (bitmap, ImageKind::Thumbnail),
+        Err(_) => (unsafe { factory.GetImage(size, SIIGBF_ICONONLY)? }, ImageKind::Icon),
+    };
+    Ok((bitmap_image(bitmap)?, kind))
+}
+
+/// The preview Explorer already keeps for the file in its thumbcache_*.db, if it
+/// has one at least `size` big. Never runs a thumbnail handler, so a miss costs
+/// only the lookup.
+pub fn cached_thumbnail(file_path: &str, size: u32) -> Option<RgbaImage> {
+    let factory: IShellItemImageFactory = unsafe { SHCreateItemFromParsingName(&HSTRING::from(file_path), None) }.ok()?;
+    let size = SIZE { cx: size as i32, cy: size as i32 };
+    let bitmap = unsafe { factory.GetImage(size, SIIGBF_THUMBNAILONLY | SIIGBF_INCACHEONLY) }.ok()?;
+    bitmap_image(bitmap).ok()
+}
+
+// Takes ownership of the bitmap
+fn bitmap_image(bitmap: HBITMAP) -> Result<RgbaImage> {
+    let pixels = bitmap_bgra(bitmap);
+    unsafe {
+        DeleteObject(bitmap);
+    }
+    let (width, height, mut pixels) = pixels?;
+
+    // Photo and video thumbnails come back without alpha, icons with premultiplied alpha
+    let opaque = pixels.chunks_exact(4).all(|pixel| pixel[3] == 0);
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+        match pixel[3] {
+            _ if opaque => pixel[3] = 255,
+            0 | 255 => {}
+            alpha => {
+                for channel in &mut pixel[..3] {
+                    *channel = ((*channel as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8;
+                }
+            }
+        }
+    }
+
+    RgbaImage::from_raw(width, height, pixels).ok_or_else(|| anyhow::anyhow!("Invalid thumbnail buffer size"))
+}
@@ -0,0 +1,42 @@
+use image::{imageops, DynamicImage, RgbaImage};
+
+use crate::extract::ImageKind;
+
+// Faint shadows and anti-aliasing fringes shouldn't count as part of the icon
+const ALPHA_THRESHOLD: u8 = 16;
+
+pub const DEFAULT_TRIM_PADDING: u32 = 4;
+
+/// Crops away transparent margins, then adds back `padding` percent of the
+/// remaining art on each side. Icons are re-centered on a square canvas so they
+/// aren't stretched by the final resize. Fully transparent images are returned as is.
+pub fn trim(img: &DynamicImage, kind: ImageKind, padding: u32) -> DynamicImage {
+    let Some((x, y, width, height)) = opaque_bounds(&img.to_rgba8()) else {
+        return img.clone();
+    };
+    let cropped = img.crop_imm(x, y, width, height).to_rgba8();
+
+    let (canvas_width, canvas_height) = match kind {
+        ImageKind::Icon => (width.max(height), width.max(height)),
+        ImageKind::Thumbnail => (width, height),
+    };
+    let margin = canvas_width.max(canvas_height) * padding / 100;
+    let mut canvas = RgbaImage::new(canvas_width + 2 * margin, canvas_height + 2 * margin);
+    let left = (canvas.width() - width) / 2;
+    let top = (canvas.height() - height) / 2;
+    imageops::overlay(&mut canvas, &cropped, left as i64, top as i64);
+    DynamicImage::ImageRgba8(canvas)
+}
+
+fn opaque_bounds(img: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in img.enumerate_pixels() {
+        if pixel[3] > ALPHA_THRESHOLD {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    (min_x != u32::MAX).then(|| (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
+}
@@ -0,0 +1,131 @@
+use std::path::Path;
+use image::{imageops, DynamicImage, Rgb, Rgba, RgbaImage};
+
+use crate::compose::{Composite, Fill};
+use crate::resample::Resample;
+
+// Below this alpha-weighted mean contrast ratio an icon is hard to make out.
+// Pure white on a light wallpaper scores about 1.1, typical colorful icons 3 or more.
+const CONTRAST_THRESHOLD: f32 = 1.5;
+
+/// How a low-contrast icon is made visible again.
+#[derive(Clone, Copy)]
+pub enum VariantStyle {
+    Outline,
+    Backdrop,
+}
+
+impl VariantStyle {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "outline" => Ok(VariantStyle::Outline),
+            "backdrop" => Ok(VariantStyle::Backdrop),
+            _ => Err(format!("Invalid variant style: {} (expected outline or backdrop)", value)),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Light, Theme::Dark];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+
+    // Roughly the Windows 11 light and dark surfaces
+    fn background(self) -> Rgb<u8> {
+        match self {
+            Theme::Light => Rgb([0xF3, 0xF3, 0xF3]),
+            Theme::Dark => Rgb([0x20, 0x20, 0x20]),
+        }
+    }
+
+    fn contrasting(self) -> Rgb<u8> {
+        match self {
+            Theme::Light => Theme::Dark.background(),
+            Theme::Dark => Theme::Light.background(),
+        }
+    }
+}
+
+/// Returns a variant of `img` for wallpapers of `theme`, or `None` if the icon
+/// already stands out against them.
+pub fn variant_for(img: &DynamicImage, theme: Theme, style: VariantStyle, resample: &Resample) -> Option<DynamicImage> {
+    let rgba = img.to_rgba8();
+    if contrast(&rgba, theme.background())? >= CONTRAST_THRESHOLD {
+        return None;
+    }
+    let size = rgba.width().max(rgba.height());
+    Some(match style {
+        VariantStyle::Outline => DynamicImage::ImageRgba8(outline(&rgba, theme.contrasting(), (size / 32).max(1))),
+        VariantStyle::Backdrop => {
+            let plate = Composite { fill: Some(Fill::Solid(theme.contrasting())), padding: 8, corner_radius: 22 };
+            plate.apply(img, size, resample)
+        }
+    })
+}
+
+/// `icons\app.png` becomes `icons\app.light.png` for the "light" variant.
+pub fn variant_path(path: &str, variant: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, variant, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, variant),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+// The alpha-weighted mean WCAG contrast ratio of the visible pixels; `None` if there are none
+fn contrast(img: &RgbaImage, background: Rgb<u8>) -> Option<f32> {
+    let background = luminance(background.0);
+    let (mut sum, mut weight) = (0.0, 0.0);
+    for pixel in img.pixels().filter(|pixel| pixel[3] > 0) {
+        let alpha = pixel[3] as f32 / 255.0;
+        let foreground = luminance([pixel[0], pixel[1], pixel[2]]);
+        let (lighter, darker) = if foreground > background { (foreground, background) } else { (background, foreground) };
+        sum += (lighter + 0.05) / (darker + 0.05) * alpha;
+        weight += alpha;
+    }
+    (weight > 0.0).then(|| sum / weight)
+}
+
+fn luminance(rgb: [u8; 3]) -> f32 {
+    let linear = rgb.map(|channel| {
+        let c = channel as f32 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    });
+    0.2126 * linear[0] + 0.7152 * linear[1] + 0.0722 * linear[2]
+}
+
+// Grows the icon's alpha by `radius` pixels in `color`, then draws the icon on top
+fn outline(img: &RgbaImage, color: Rgb<u8>, radius: u32) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    let r = radius as i64;
+    let mut outlined = RgbaImage::from_fn(width, height, |x, y| {
+        let mut alpha = 0;
+        for dy in -r..=r {
+            for dx in -r..=r {
+                if dx * dx + dy * dy > r * r {
+                    continue;
+                }
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                if nx >= 0 && ny >= 0 && nx < width as i64 && ny < height as i64 {
+                    alpha = alpha.max(img.get_pixel(nx as u32, ny as u32)[3]);
+                }
+            }
+        }
+        Rgba([color[0], color[1], color[2], alpha])
+    });
+    imageops::overlay(&mut outlined, img, 0, 0);
+    outlined
+}
@@ -0,0 +1,133 @@
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use windows::{
+    core::{GUID, HSTRING},
+    Win32::Media::MediaFoundation::*,
+    Win32::System::Com::StructuredStorage::PROPVARIANT,
+    Win32::System::Variant::VT_I8,
+};
+
+use crate::error::{failure, ErrorCode};
+
+// Media Foundation positions are in 100ns units
+const TICKS_PER_SECOND: f64 = 10_000_000.0;
+// Seeking lands on the keyframe before the target; a few seconds of frames at
+// 60fps is plenty to decode forward from it
+const MAX_SAMPLES: usize = 600;
+
+/// Decodes the frame of a video file shown at `seconds` into it.
+pub fn extract_video_frame(file_path: &str, seconds: f64) -> Result<RgbaImage> {
+    unsafe {
+        MFStartup(MF_VERSION, MFSTARTUP_LITE)?;
+    }
+    let frame = read_frame(file_path, seconds);
+    unsafe {
+        let _ = MFShutdown();
+    }
+    frame.with_context(|| format!("Failed to read a frame from {}", file_path))
+}
+
+fn read_frame(file_path: &str, seconds: f64) -> Result<RgbaImage> {
+    let stream = MF_SOURCE_READER_FIRST_VIDEO_STREAM.0 as u32;
+    let reader = unsafe {
+        let mut attributes = None;
+        MFCreateAttributes(&mut attributes, 1)?;
+        let attributes = attributes.ok_or_else(|| anyhow::anyhow!("MFCreateAttributes returned no attributes"))?;
+        // Lets the reader convert whatever the decoder outputs into RGB32
+        attributes.SetUINT32(&MF_SOURCE_READER_ENABLE_VIDEO_PROCESSING, 1)?;
+        MFCreateSourceReaderFromURL(&HSTRING::from(file_path), &attributes)?
+    };
+
+    unsafe {
+        let media_type = MFCreateMediaType()?;
+        media_type.SetGUID(&MF_MT_MAJOR_TYPE, &MFMediaType_Video)?;
+        media_type.SetGUID(&MF_MT_SUBTYPE, &MFVideoFormat_RGB32)?;
+        reader.SetCurrentMediaType(stream, None, &media_type)?;
+    }
+
+    let target = (seconds * TICKS_PER_SECOND) as i64;
+    let duration = unsafe {
+        reader
+            .GetPresentationAttribute(MF_SOURCE_READER_MEDIASOURCE.0 as u32, &MF_PD_DURATION)
+            .map(|duration| duration.Anonymous.Anonymous.Anonymous.uhVal as i64)
+    };
+    if let Ok(duration) = duration {
+        if target >= duration {
+            return Err(failure(
+                ErrorCode::InvalidArguments,
+                format!("--video-frame {}s is past the end of the video ({:.1}s)", seconds, duration as f64 / TICKS_PER_SECOND),
+            ));
+        }
+    }
+    if target > 0 {
+        let mut position = PROPVARIANT::default();
+        unsafe {
+            (*position.Anonymous.Anonymous).vt = VT_I8;
+            (*position.Anonymous.Anonymous).Anonymous.hVal = target;
+            reader.SetCurrentPosition(&GUID::zeroed(), &position)?;
+        }
+    }
+
+    // Decode forward from the keyframe until reaching the requested time
+    let mut sample = None;
+    for _ in 0..MAX_SAMPLES {
+        let mut flags = 0;
+        let mut timestamp = 0;
+        let mut next = None;
+        unsafe {
+            reader.ReadSample(stream, 0, None, Some(&mut flags), Some(&mut timestamp), Some(&mut next))?;
+        }
+        if flags & MF_SOURCE_READERF_ENDOFSTREAM.0 as u32 != 0 {
+            break;
+        }
+        // Gaps in the stream come back as empty reads
+        if next.is_some() {
+            sample = next;
+            if timestamp >= target {
+                break;
+            }
+        }
+    }
+    let sample = sample.ok_or_else(|| failure(ErrorCode::Decode, "The video has no frames to decode"))?;
+
+    let (width, height, stride) = unsafe {
+        let media_type = reader.GetCurrentMediaType(stream)?;
+        let frame_size = media_type.GetUINT64(&MF_MT_FRAME_SIZE)?;
+        let (width, height) = ((frame_size >> 32) as u32, frame_size as u32);
+        // Stored as a signed value; negative means bottom-up rows
+        let stride = media_type.GetUINT32(&MF_MT_DEFAULT_STRIDE).map_or(width as i32 * 4, |stride| stride as i32);
+        (width, height, stride)
+    };
+
+    let buffer = unsafe { sample.ConvertToContiguousBuffer()? };
+    let mut data = std::ptr::null_mut();
+    let mut length = 0;
+    unsafe {
+        buffer.Lock(&mut data, None, Some(&mut length))?;
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(data, length as usize) };
+    let row_bytes = width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+    let complete = (0..height as usize).all(|y| {
+        let row = if stride < 0 { height as usize - 1 - y } else { y };
+        let start = row * stride.unsigned_abs() as usize;
+        match bytes.get(start..start + row_bytes) {
+            Some(row) => {
+                // RGB32 is BGRX, with the padding byte undefined
+                for pixel in row.chunks_exact(4) {
+                    pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+                }
+                true
+            }
+            None => false,
+        }
+    });
+    unsafe {
+        let _ = buffer.Unlock();
+    }
+    if !complete {
+        anyhow::bail!("Video frame buffer is smaller than {}x{}", width, height);
+    }
+
+    RgbaImage::from_raw(width, height, pixels).ok_or_else(|| anyhow::anyhow!("Invalid video frame buffer size"))
+}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, SystemTime};
+#[cfg(windows)]
+use altdesktop_core::com::ComGuard;
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::batch::{job_options, read_jobs, JobResult};
+use crate::cache::CacheEntry;
+use crate::desktop_ini;
+use crate::extract::extract;
+#[cfg(windows)]
+use crate::lnk::{self, IconSource};
+use crate::options::Options;
+
+pub const DEFAULT_INTERVAL_MS: u64 = 2_000;
+
+// A source's modification time and length, or None while it doesn't exist
+type Stamp = Option<(SystemTime, u64)>;
+
+struct Watched {
+    index: usize,
+    options: Options,
+    /// Each file the icon is drawn from, as it was when last reported.
+    sources: Vec<(PathBuf, Stamp)>,
+    /// What the last poll saw when it differed, which is reported once a poll sees
+    /// it again, so an installer still writing the file isn't reported every poll.
+    changing: Option<Vec<Stamp>>,
+    cache: Option<CacheEntry>,
+}
+
+/// Reads a JSON array of batch jobs from stdin and watches the files each job's icon
+/// comes from: its input, a folder's desktop.ini and the icon it names, a shortcut's
+/// icon location and target, and the badge. Once a change has settled it removes
+/// the job's cache entry, prints {"type":"invalidated","index","input","changed"}
+/// and, with `reextract`, runs the job again in the background and prints its result
+/// line with "type":"extracted". Runs until stdin is closed.
+pub fn run_watch(cache_dir: Option<&str>, interval: Duration, reextract: bool) -> Result<bool> {
+    let mut watched = Vec::new();
+    for (index, job) in read_jobs()?.into_iter().enumerate() {
+        let input = job.input().to_string();
+        match job_options(job, cache_dir, false) {
+            Ok(options) => watched.push(Watched::new(index, options)),
+            Err(error) => emit(result_event("rejected", JobResult::new(index, input, Err(error)))),
+        }
+    }
+    let sources: usize = watched.iter().map(|watched| watched.sources.len()).sum();
+    emit(json!({ "type": "ready", "jobs": watched.len(), "sources": sources }));
+
+    // Nothing more is read from stdin; closing it is the signal to stop
+    let (stop, stopped) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        let _ = io::stdin().read_to_end(&mut Vec::new());
+        drop(stop);
+    });
+    let (queue, queued) = mpsc::channel::<(usize, Options)>();
+    let worker = thread::spawn(move || {
+        // Shell icon and thumbnail lookups need COM on every thread that makes them
+        #[cfg(windows)]
+        let _com = ComGuard::apartment();
+        for (index, options) in queued {
+            let _span = tracing::info_span!("reextract", index, input = %options.file_path).entered();
+            let input = options.file_path.clone();
+            emit(result_event("extracted", JobResult::new(index, input, extract(&options))));
+        }
+    });
+
+    while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+        for watched in &mut watched {
+            let Some(changed) = watched.poll() else {
+                continue;
+            };
+            let input = &watched.options.file_path;
+            emit(json!({ "type": "invalidated", "index": watched.index, "input": input, "changed": changed }));
+            if reextract {
+                let _ = queue.send((watched.index, watched.options.clone()));
+            }
+        }
+    }
+    // Re-extractions already queued still finish and print their results
+    drop(queue);
+    let _ = worker.join();
+    Ok(true)
+}
+
+impl Watched {
+    fn new(index: usize, options: Options) -> Self {
+        let mut watched = Watched { index, options, sources: Vec::new(), changing: None, cache: None };
+        watched.resolve();
+        watched
+    }
+
+    // The sources are found again after every change, since a retargeted shortcut
+    // or an edited desktop.ini points somewhere else. The cache entry is that of the
+    // source as it is now, which is the one the next change makes stale
+    fn resolve(&mut self) {
+        self.sources = sources(&self.options).into_iter().map(|path| (path.clone(), stamp(&path))).collect();
+        self.cache = self.options.cache_dir.as_deref().and_then(|dir| CacheEntry::for_options(dir, &self.options).ok());
+    }
+
+    // Each changed source as {"path","change"}, change being modified, created or
+    // deleted, once the change has held for a poll
+    fn poll(&mut self) -> Option<Vec<Value>> {
+        let now: Vec<Stamp> = self.sources.iter().map(|(path, _)| stamp(path)).collect();
+        if self.sources.iter().zip(&now).all(|((_, before), now)| before == now) {
+            self.changing = None;
+            return None;
+        }
+        if self.changing.as_ref() != Some(&now) {
+            self.changing = Some(now);
+            return None;
+        }
+        self.changing = None;
+        let changed = self
+            .sources
+            .iter()
+            .zip(&now)
+            .filter(|((_, before), now)| before != *now)
+            .map(|((path, before), now)| {
+                let change = match (before, now) {
+                    (None, _) => "created",
+                    (_, None) => "deleted",
+                    _ => "modified",
+                };
+                json!({ "path": path.to_string_lossy(), "change": change })
+            })
+            .collect();
+        if let Some(cache) = &self.cache {
+            if let Err(error) = cache.remove() {
+                tracing::warn!(source = %self.options.file_path, "Could not remove a stale cache entry: {:#}", error);
+            }
+        }
+        self.resolve();
+        Some(changed)
+    }
+}
+
+fn sources(options: &Options) -> Vec<PathBuf> {
+    let mut sources = Vec::new();
+    // A package's logo is found through the package manager and a stock icon is the
+    // shell's, not files of their own
+    if !options.package && !options.stock {
+        let input = PathBuf::from(&options.file_path);
+        if input.is_dir() {
+            sources.push(desktop_ini::ini_path(&input));
+            if let Some(icon) = desktop_ini::folder_icon(&input) {
+                sources.push(icon.path);
+            }
+        }
+        sources.insert(0, input);
+    }
+    #[cfg(windows)]
+    if options.follow_lnk && !options.package && !options.stock && lnk::is_lnk(&options.file_path) {
+        for source in lnk::icon_sources(&options.file_path).unwrap_or_default() {
+            let (IconSource::Resource(path, _) | IconSource::Target(path)) = source;
+            sources.push(PathBuf::from(path));
+        }
+    }
+    if let Some(badge) = &options.badge {
+        sources.push(PathBuf::from(&badge.path));
+    }
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for source in sources {
+        if !unique.contains(&source) {
+            unique.push(source);
+        }
+    }
+    unique
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn result_event(kind: &str, result: JobResult) -> Value {
+    let mut event = serde_json::to_value(result).unwrap();
+    event["type"] = Value::from(kind);
+    event
+}
+
+// Locked per line, as the re-extraction worker prints too
+fn emit(event: Value) {
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", event);
+    let _ = stdout.flush();
+}
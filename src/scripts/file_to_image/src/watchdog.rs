@@ -0,0 +1,39 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use altdesktop_core::cancel::{self, CancelToken};
+#[cfg(windows)]
+use altdesktop_core::com::ComGuard;
+use anyhow::Result;
+
+use crate::error::{failure, ErrorCode};
+
+/// Runs `work` on a thread of its own and gives up on it after `timeout`, so a shell
+/// extension or codec that never returns fails one job with `timeout` instead of
+/// stalling the run. A thread can't be killed, so the abandoned one is cancelled: it
+/// stops at its next safe point, removing what it had written, or if it's stuck for
+/// good, stays blocked until the process exits.
+pub fn run<T: Send + 'static>(timeout: Duration, work: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    // Cancelling the job, as a batch or served request can, still reaches the worker
+    let token = cancel::current().map_or_else(CancelToken::new, |parent| parent.child());
+    let worker_token = token.clone();
+    let span = tracing::Span::current();
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new().name("extract".to_string()).spawn(move || {
+        let _span = span.entered();
+        // The shell's icon and thumbnail handlers need COM on every thread that calls them
+        #[cfg(windows)]
+        let _com = ComGuard::apartment();
+        let _ = sender.send(cancel::scope(&worker_token, work));
+    })?;
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => {
+            token.cancel();
+            tracing::warn!(timeout_ms = timeout.as_millis() as u64, "Abandoned an extraction that didn't finish");
+            Err(failure(ErrorCode::Timeout, format!("Extraction didn't finish within {} ms", timeout.as_millis())))
+        }
+        // Only a panic drops the sender without sending
+        Err(RecvTimeoutError::Disconnected) => Err(failure(ErrorCode::Unknown, "Extraction failed unexpectedly")),
+    }
+}
@@ -0,0 +1,414 @@
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use image::RgbaImage;
+use resvg::{tiny_skia, usvg};
+
+use crate::image_file::decode_image_file;
+
+// The formats a theme may hold. XPM is allowed too, but nothing here decodes it,
+// and themes that ship XPM ship PNG alongside.
+const EXTENSIONS: &[&str] = &["png", "svg", "svgz"];
+// Every theme falls back to hicolor, where applications install their own icons
+const FALLBACK_THEME: &str = "hicolor";
+
+enum Icon {
+    File(PathBuf),
+    /// Theme icon names, most specific first.
+    Names(Vec<String>),
+}
+
+struct Theme {
+    name: String,
+    inherits: Vec<String>,
+    // Only the directories that exist in some base directory, each with where it is
+    dirs: Vec<(ThemeDir, PathBuf)>,
+}
+
+struct ThemeDir {
+    kind: DirKind,
+    // In pixels, with the directory's scale already applied
+    size: u32,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+enum DirKind {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+impl ThemeDir {
+    // DirectoryMatchesSize from the icon theme spec
+    fn matches(&self, size: u32) -> bool {
+        match self.kind {
+            DirKind::Fixed => self.size == size,
+            DirKind::Scalable => (self.min_size..=self.max_size).contains(&size),
+            DirKind::Threshold => self.size.saturating_sub(self.threshold) <= size && size <= self.size + self.threshold,
+        }
+    }
+
+    // DirectorySizeDistance from the spec
+    fn distance(&self, size: u32) -> u32 {
+        match self.kind {
+            DirKind::Fixed => self.size.abs_diff(size),
+            DirKind::Scalable | DirKind::Threshold => {
+                let (low, high) = match self.kind {
+                    DirKind::Threshold => (self.size.saturating_sub(self.threshold), self.size + self.threshold),
+                    _ => (self.min_size, self.max_size),
+                };
+                if size < low {
+                    self.min_size.abs_diff(size)
+                } else if size > high {
+                    size.abs_diff(self.max_size)
+                } else {
+                    0
+                }
+            }
+        }
+    }
+}
+
+/// The icon a Linux desktop shows for a file: a .desktop entry's `Icon=`, or the
+/// one for the file's MIME type, looked up in the active icon theme and the themes
+/// it inherits, then hicolor. None when no theme has one, or it can't be decoded.
+pub fn extract_xdg_icon(file_path: &str, size: u32) -> Option<RgbaImage> {
+    let path = match icon_for(Path::new(file_path))? {
+        Icon::File(path) => path,
+        Icon::Names(names) => {
+            let themes = theme_chain();
+            names.iter().find_map(|name| {
+                themes.iter().find_map(|theme| lookup(theme, name, size)).or_else(|| pixmap(name))
+            })?
+        }
+    };
+    load_icon(&path, size)
+}
+
+fn icon_for(path: &Path) -> Option<Icon> {
+    let is_entry = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("desktop"));
+    if is_entry && path.is_file() {
+        let icon = desktop_entry_icon(path)?;
+        if Path::new(&icon).is_absolute() {
+            return Some(Icon::File(PathBuf::from(icon)));
+        }
+        // Names are meant to be written bare, but plenty of entries add an extension
+        let name = match icon.rsplit_once('.') {
+            Some((stem, extension)) if EXTENSIONS.contains(&extension) || extension == "xpm" => stem,
+            _ => &icon,
+        };
+        return Some(Icon::Names(fallback_names(name)));
+    }
+    if path.is_dir() {
+        return Some(Icon::Names(vec!["folder".into(), "inode-directory".into()]));
+    }
+    let metadata = fs::metadata(path).ok()?;
+    let mut names = Vec::new();
+    match mime_type(path) {
+        Some(mime) => names.extend(mime_icon_names(&mime)),
+        None if metadata.permissions().mode() & 0o111 != 0 => names.push("application-x-executable".into()),
+        None => {}
+    }
+    names.push("text-x-generic".into());
+    Some(Icon::Names(names))
+}
+
+// Icon= from the [Desktop Entry] group; the localized Icon[xx]= keys are skipped,
+// as they are almost always the same icon
+fn desktop_entry_icon(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    ini_value(&text, "Desktop Entry", "Icon").filter(|icon| !icon.is_empty())
+}
+
+// The spec's fallback for names that aren't found: drop one dash-separated part at a
+// time, so "org.example.App-beta" still finds "org.example.App"
+fn fallback_names(name: &str) -> Vec<String> {
+    let mut names = vec![name.to_string()];
+    let mut rest = name;
+    while let Some((stem, _)) = rest.rsplit_once('-') {
+        names.push(stem.to_string());
+        rest = stem;
+    }
+    names
+}
+
+/// The type shared-mime-info's globs give the file name: the heaviest match, then
+/// the longest pattern. Only plain names and *.suffix patterns are tried, which
+/// covers nearly every glob the database has.
+pub fn mime_type(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let lower = name.to_lowercase();
+    let mut best: Option<(u32, usize, String)> = None;
+    for dir in data_dirs() {
+        let Ok(text) = fs::read_to_string(dir.join("mime/globs2")) else {
+            continue;
+        };
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            let mut fields = line.split(':');
+            let (Some(weight), Some(mime), Some(glob)) = (fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            let case_sensitive = fields.next().is_some_and(|flags| flags.split(',').any(|flag| flag == "cs"));
+            let candidate = if case_sensitive { name } else { lower.as_str() };
+            let glob = if case_sensitive { glob.to_string() } else { glob.to_lowercase() };
+            let matched = match glob.strip_prefix('*') {
+                Some(suffix) if !suffix.contains(['*', '?', '[']) => candidate.ends_with(suffix),
+                Some(_) => false,
+                None => !glob.contains(['*', '?', '[']) && candidate == glob,
+            };
+            let Ok(weight) = weight.parse::<u32>() else {
+                continue;
+            };
+            let better = best.as_ref().is_none_or(|(best_weight, best_length, _)| {
+                (weight, glob.len()) > (*best_weight, *best_length)
+            });
+            if matched && better {
+                best = Some((weight, glob.len(), mime.to_string()));
+            }
+        }
+    }
+    best.map(|(_, _, mime)| mime)
+}
+
+// The names the shared-mime-info spec gives a type's icon, in the order to try
+fn mime_icon_names(mime: &str) -> Vec<String> {
+    let mut names: Vec<String> = mime_table("icons", mime).into_iter().collect();
+    names.push(mime.replace('/', "-"));
+    names.extend(mime_table("generic-icons", mime));
+    if let Some((media, _)) = mime.split_once('/') {
+        names.push(format!("{}-x-generic", media));
+    }
+    names
+}
+
+fn mime_table(file: &str, mime: &str) -> Option<String> {
+    data_dirs().iter().find_map(|dir| {
+        let text = fs::read_to_string(dir.join("mime").join(file)).ok()?;
+        text.lines().find_map(|line| line.strip_prefix(mime)?.strip_prefix(':').map(str::to_string))
+    })
+}
+
+// The theme the desktop has set: KDE keeps it in kdeglobals, GTK desktops in
+// settings.ini when it was set there, or else in GSettings
+fn current_theme() -> Option<String> {
+    let config = config_home();
+    let kdeglobals = || ini_file_value(&config.join("kdeglobals"), "Icons", "Theme");
+    let is_kde = env::var("XDG_CURRENT_DESKTOP").is_ok_and(|desktop| desktop.to_uppercase().contains("KDE"));
+    is_kde
+        .then(kdeglobals)
+        .flatten()
+        .or_else(|| ini_file_value(&config.join("gtk-3.0/settings.ini"), "Settings", "gtk-icon-theme-name"))
+        .or_else(gsettings_theme)
+        .or_else(kdeglobals)
+}
+
+fn gsettings_theme() -> Option<String> {
+    let output = Command::new("gsettings").args(["get", "org.gnome.desktop.interface", "icon-theme"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let theme = String::from_utf8(output.stdout).ok()?.trim().trim_matches('\'').to_string();
+    (!theme.is_empty()).then_some(theme)
+}
+
+// The active theme, then what it inherits, depth first, then hicolor
+fn theme_chain() -> Vec<Theme> {
+    let bases = base_dirs();
+    let mut themes = Vec::new();
+    if let Some(name) = current_theme() {
+        push_theme(&name, &bases, &mut themes);
+    }
+    push_theme(FALLBACK_THEME, &bases, &mut themes);
+    themes
+}
+
+fn push_theme(name: &str, bases: &[PathBuf], themes: &mut Vec<Theme>) {
+    if themes.iter().any(|theme| theme.name == name) {
+        return;
+    }
+    let Some(theme) = load_theme(name, bases) else {
+        return;
+    };
+    let inherits = theme.inherits.clone();
+    themes.push(theme);
+    for parent in inherits {
+        push_theme(&parent, bases, themes);
+    }
+}
+
+// A theme's index.theme is the first one found; its directories may be spread over
+// every base directory, such as an app's icons in ~/.local/share/icons/hicolor
+fn load_theme(name: &str, bases: &[PathBuf]) -> Option<Theme> {
+    let text = bases.iter().find_map(|base| fs::read_to_string(base.join(name).join("index.theme")).ok())?;
+    let sections = ini_sections(&text);
+    let list = |key: &str| -> Vec<String> {
+        section_value(&sections, "Icon Theme", key)
+            .map(|value| value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default()
+    };
+    let mut dirs = Vec::new();
+    for subdir in list("Directories").into_iter().chain(list("ScaledDirectories")) {
+        for base in bases {
+            let path = base.join(name).join(&subdir);
+            if !path.is_dir() {
+                continue;
+            }
+            if let Some(dir) = theme_dir(&sections, &subdir) {
+                dirs.push((dir, path));
+            }
+        }
+    }
+    Some(Theme { name: name.to_string(), inherits: list("Inherits"), dirs })
+}
+
+fn theme_dir(sections: &[(String, Vec<(String, String)>)], subdir: &str) -> Option<ThemeDir> {
+    let number = |key: &str| section_value(sections, subdir, key).and_then(|value| value.parse::<u32>().ok());
+    let size = number("Size")?;
+    let scale = number("Scale").unwrap_or(1).max(1);
+    let kind = match section_value(sections, subdir, "Type") {
+        Some("Fixed") => DirKind::Fixed,
+        Some("Scalable") => DirKind::Scalable,
+        _ => DirKind::Threshold,
+    };
+    Some(ThemeDir {
+        kind,
+        size: size * scale,
+        min_size: number("MinSize").unwrap_or(size) * scale,
+        max_size: number("MaxSize").unwrap_or(size) * scale,
+        threshold: number("Threshold").unwrap_or(2) * scale,
+    })
+}
+
+// The first icon in a directory that matches the size; failing that, a vector icon,
+// which renders crisply at any size, then the nearest raster larger than the size,
+// which scales down cleanly, then the nearest smaller one
+fn lookup(theme: &Theme, name: &str, size: u32) -> Option<PathBuf> {
+    let mut best: Option<((u8, u32), PathBuf)> = None;
+    for (dir, folder) in &theme.dirs {
+        for extension in EXTENSIONS {
+            let path = folder.join(format!("{}.{}", name, extension));
+            if !path.is_file() {
+                continue;
+            }
+            if dir.matches(size) {
+                return Some(path);
+            }
+            let class = if *extension != "png" {
+                0
+            } else if dir.size >= size {
+                1
+            } else {
+                2
+            };
+            let rank = (class, dir.distance(size));
+            if best.as_ref().is_none_or(|(best_rank, _)| rank < *best_rank) {
+                best = Some((rank, path));
+            }
+        }
+    }
+    best.map(|(_, path)| path)
+}
+
+// Icons outside any theme, where older applications install them
+fn pixmap(name: &str) -> Option<PathBuf> {
+    data_dirs().iter().find_map(|dir| {
+        EXTENSIONS.iter().map(|extension| dir.join("pixmaps").join(format!("{}.{}", name, extension))).find(|path| path.is_file())
+    })
+}
+
+fn load_icon(path: &Path, size: u32) -> Option<RgbaImage> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "svg" | "svgz" => render_svg(path, size),
+        _ => decode_image_file(path.to_str()?).ok().map(|decoded| decoded.image.to_rgba8()),
+    }
+}
+
+// Renders with the longer side at the requested size, as a raster icon is fitted
+fn render_svg(path: &Path, size: u32) -> Option<RgbaImage> {
+    let data = fs::read(path).ok()?;
+    let tree = usvg::Tree::from_data(&data, &usvg::Options::default()).ok()?;
+    let view = tree.size();
+    let scale = size as f32 / view.width().max(view.height());
+    let width = ((view.width() * scale).round() as u32).max(1);
+    let height = ((view.height() * scale).round() as u32).max(1);
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    // tiny-skia keeps premultiplied alpha
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    RgbaImage::from_raw(width, height, pixels)
+}
+
+// Where themes live, in the spec's order of precedence
+fn base_dirs() -> Vec<PathBuf> {
+    let mut bases = vec![home_dir().join(".icons")];
+    bases.extend(data_dirs().iter().map(|dir| dir.join("icons")));
+    bases
+}
+
+/// XDG_DATA_HOME, then XDG_DATA_DIRS.
+pub fn data_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![env_path("XDG_DATA_HOME").unwrap_or_else(|| home_dir().join(".local/share"))];
+    let system = env::var("XDG_DATA_DIRS").ok().filter(|value| !value.is_empty());
+    let system = system.as_deref().unwrap_or("/usr/local/share:/usr/share");
+    dirs.extend(system.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from));
+    dirs
+}
+
+fn config_home() -> PathBuf {
+    env_path("XDG_CONFIG_HOME").unwrap_or_else(|| home_dir().join(".config"))
+}
+
+pub fn home_dir() -> PathBuf {
+    env::var_os("HOME").map(PathBuf::from).unwrap_or_default()
+}
+
+/// The base directory spec treats an empty variable as unset.
+pub fn env_path(name: &str) -> Option<PathBuf> {
+    env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from)
+}
+
+fn ini_file_value(path: &Path, section: &str, key: &str) -> Option<String> {
+    ini_value(&fs::read_to_string(path).ok()?, section, key)
+}
+
+/// A key from a group of a key file, as .desktop entries and .thumbnailer files are.
+pub fn ini_value(text: &str, section: &str, key: &str) -> Option<String> {
+    section_value(&ini_sections(text), section, key).map(str::to_string)
+}
+
+fn section_value<'a>(sections: &'a [(String, Vec<(String, String)>)], section: &str, key: &str) -> Option<&'a str> {
+    let (_, entries) = sections.iter().find(|(name, _)| name == section)?;
+    entries.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str())
+}
+
+// The key file format shared by index.theme, .desktop entries and the settings
+// files: [groups] of key=value lines, with # comments
+fn ini_sections(text: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut sections: Vec<(String, Vec<(String, String)>)> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            sections.push((name.to_string(), Vec::new()));
+            continue;
+        }
+        let (Some((key, value)), Some((_, entries))) = (line.split_once('='), sections.last_mut()) else {
+            continue;
+        };
+        entries.push((key.trim().to_string(), value.trim().to_string()));
+    }
+    sections
+}
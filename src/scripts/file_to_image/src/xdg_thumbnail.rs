@@ -0,0 +1,268 @@
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{BufWriter, Cursor};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use anyhow::Result;
+use image::{DynamicImage, RgbaImage};
+
+use crate::image_file::{decode_image_file, is_image_file};
+use crate::xdg;
+
+// The cache's size buckets, from the freedesktop thumbnail spec
+const BUCKETS: &[(&str, u32)] = &[("normal", 128), ("large", 256), ("x-large", 512), ("xx-large", 1024)];
+// Failed attempts are recorded under fail/<application>, so nothing retries them
+// until the file changes
+const FAIL_DIR: &str = "fail/altdesktop";
+const THUMBNAILER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The file as the cache identifies it.
+struct Source {
+    path: PathBuf,
+    uri: String,
+    // Seconds since the epoch, as Thumb::MTime records it
+    mtime: u64,
+    size: u64,
+    // The entry's file name: the MD5 of the URI, in hex, with .png
+    name: String,
+}
+
+enum Generated {
+    Image(RgbaImage),
+    /// A thumbnailer ran and produced nothing.
+    Failed,
+    /// Nothing here can preview the file.
+    Unsupported,
+}
+
+/// A preview of the file from the freedesktop thumbnail cache, the one file managers
+/// share, at least `size` big when the cache has one. Otherwise one is made, by
+/// scaling an image down or running the thumbnailer installed for the file's type,
+/// and stored back for the file manager to find. None when nothing can preview it.
+pub fn shared_thumbnail(file_path: &str, size: u32) -> Option<RgbaImage> {
+    let source = source(file_path)?;
+    let root = cache_root();
+    // The spec rules out thumbnails of thumbnails
+    if source.path.starts_with(&root) {
+        return None;
+    }
+    let (bucket, bucket_size) = bucket_for(size);
+    let cached = BUCKETS
+        .iter()
+        .filter(|(_, candidate)| *candidate >= bucket_size)
+        .find_map(|(candidate, _)| read_entry(&root.join(candidate).join(&source.name), &source));
+    if let Some(img) = cached {
+        return Some(img);
+    }
+    if read_entry(&root.join(FAIL_DIR).join(&source.name), &source).is_some() {
+        return None;
+    }
+    match generate(&source, bucket_size) {
+        Generated::Image(img) => {
+            let _ = write_entry(&root.join(bucket), &source, &img);
+            Some(img)
+        }
+        Generated::Failed => {
+            let _ = write_entry(&root.join(FAIL_DIR), &source, &RgbaImage::new(1, 1));
+            None
+        }
+        Generated::Unsupported => None,
+    }
+}
+
+/// Stores a thumbnail of an image that was decoded anyway, unless the cache already
+/// has a current one for its size.
+pub fn store_image_thumbnail(file_path: &str, img: &DynamicImage, size: u32) {
+    let Some(source) = source(file_path) else {
+        return;
+    };
+    let root = cache_root();
+    let (bucket, bucket_size) = bucket_for(size);
+    let folder = root.join(bucket);
+    if source.path.starts_with(&root) || read_entry(&folder.join(&source.name), &source).is_some() {
+        return;
+    }
+    let _ = write_entry(&folder, &source, &scale_down(img, bucket_size));
+}
+
+fn source(file_path: &str) -> Option<Source> {
+    let path = fs::canonicalize(file_path).ok()?;
+    let metadata = fs::metadata(&path).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let uri = file_uri(&path)?;
+    let name = format!("{:x}.png", md5::compute(&uri));
+    Some(Source { path, uri, mtime, size: metadata.len(), name })
+}
+
+// The smallest bucket the size fits in; past the largest, the largest
+fn bucket_for(size: u32) -> (&'static str, u32) {
+    *BUCKETS.iter().find(|(_, bucket_size)| *bucket_size >= size).unwrap_or(&BUCKETS[BUCKETS.len() - 1])
+}
+
+fn cache_root() -> PathBuf {
+    xdg::env_path("XDG_CACHE_HOME").unwrap_or_else(|| xdg::home_dir().join(".cache")).join("thumbnails")
+}
+
+// The URI GLib builds for the path, since entries are found by its hash: everything
+// but unreserved characters and those allowed in a path is percent-encoded
+fn file_uri(path: &Path) -> Option<String> {
+    let mut uri = String::from("file://");
+    for byte in path.to_str()?.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    Some(uri)
+}
+
+// An entry is current when it was made from this file as it is now
+fn read_entry(path: &Path, source: &Source) -> Option<RgbaImage> {
+    let bytes = fs::read(path).ok()?;
+    let reader = png::Decoder::new(Cursor::new(&bytes)).read_info().ok()?;
+    let text = |keyword: &str| {
+        reader.info().uncompressed_latin1_text.iter().find(|chunk| chunk.keyword == keyword).map(|chunk| chunk.text.clone())
+    };
+    if text("Thumb::URI")? != source.uri || text("Thumb::MTime")?.parse::<u64>().ok()? != source.mtime {
+        return None;
+    }
+    image::load_from_memory(&bytes).ok().map(|img| img.to_rgba8())
+}
+
+// Written to a temporary file and renamed, so a reader never sees half an entry;
+// entries are private to the user, as the spec asks
+fn write_entry(folder: &Path, source: &Source, img: &RgbaImage) -> Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(folder)?;
+    let temporary = folder.join(format!("{}.{}.tmp", source.name, std::process::id()));
+    let write = || -> Result<()> {
+        let file = OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(&temporary)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), img.width(), img.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.add_text_chunk("Thumb::URI".into(), source.uri.clone())?;
+        encoder.add_text_chunk("Thumb::MTime".into(), source.mtime.to_string())?;
+        encoder.add_text_chunk("Thumb::Size".into(), source.size.to_string())?;
+        encoder.add_text_chunk("Software".into(), "Alt-Desktop".into())?;
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(img.as_raw())?;
+        writer.finish()?;
+        Ok(())
+    };
+    let written = write().and_then(|()| Ok(fs::rename(&temporary, folder.join(&source.name))?));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written
+}
+
+fn generate(source: &Source, bucket_size: u32) -> Generated {
+    let file_path = source.path.to_string_lossy();
+    if is_image_file(&file_path) {
+        return match decode_image_file(&file_path) {
+            Ok(decoded) => Generated::Image(scale_down(&decoded.image, bucket_size)),
+            Err(_) => Generated::Failed,
+        };
+    }
+    let Some(exec) = xdg::mime_type(&source.path).and_then(|mime| thumbnailer(&mime)) else {
+        return Generated::Unsupported;
+    };
+    match run_thumbnailer(&exec, source, bucket_size) {
+        Some(img) => Generated::Image(scale_down(&DynamicImage::ImageRgba8(img), bucket_size)),
+        None => Generated::Failed,
+    }
+}
+
+// Thumbnails fit the bucket but are never blown up past the original
+fn scale_down(img: &DynamicImage, bucket_size: u32) -> RgbaImage {
+    if img.width() <= bucket_size && img.height() <= bucket_size {
+        img.to_rgba8()
+    } else {
+        img.thumbnail(bucket_size, bucket_size).to_rgba8()
+    }
+}
+
+// The Exec line of the first .thumbnailer that lists the type and whose program is
+// installed, which is how GNOME finds the ones for videos, fonts, documents...
+fn thumbnailer(mime: &str) -> Option<String> {
+    xdg::data_dirs().iter().find_map(|dir| {
+        let mut entries: Vec<PathBuf> =
+            fs::read_dir(dir.join("thumbnailers")).ok()?.flatten().map(|entry| entry.path()).collect();
+        entries.sort();
+        entries.iter().find_map(|path| {
+            if path.extension().is_none_or(|extension| extension != "thumbnailer") {
+                return None;
+            }
+            let text = fs::read_to_string(path).ok()?;
+            let field = |key: &str| xdg::ini_value(&text, "Thumbnailer Entry", key);
+            if !field("MimeType")?.split(';').any(|listed| listed == mime) {
+                return None;
+            }
+            if field("TryExec").is_some_and(|program| !is_installed(&program)) {
+                return None;
+            }
+            field("Exec")
+        })
+    })
+}
+
+fn is_installed(program: &str) -> bool {
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+    std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+// Runs Exec with its %i (input path), %u (URI), %o (output path) and %s (size)
+// codes filled in, giving up on one that hangs
+fn run_thumbnailer(exec: &str, source: &Source, bucket_size: u32) -> Option<RgbaImage> {
+    let output = std::env::temp_dir().join(format!("altdesktop-thumbnail-{}.png", std::process::id()));
+    let input = source.path.to_string_lossy();
+    let mut args = exec.split_whitespace().map(|arg| {
+        let mut expanded = String::new();
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('i') => expanded.push_str(&input),
+                Some('u') => expanded.push_str(&source.uri),
+                Some('o') => expanded.push_str(&output.to_string_lossy()),
+                Some('s') => expanded.push_str(&bucket_size.to_string()),
+                Some('%') => expanded.push('%'),
+                _ => {}
+            }
+        }
+        expanded
+    });
+    let program = args.next()?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if started.elapsed() < THUMBNAILER_TIMEOUT => thread::sleep(Duration::from_millis(50)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+        }
+    };
+    let img = status.filter(|status| status.success()).and_then(|_| image::open(&output).ok()).map(|img| img.to_rgba8());
+    let _ = fs::remove_file(&output);
+    img
+}